@@ -0,0 +1,82 @@
+#[cfg(test)]
+mod tests {
+    use crate::common::{
+        clear, connect_with_tls, insert_with_client, random_id, simple_query_with_client, trace,
+        PROXY,
+    };
+
+    /// 1/3 with 15 significant digits, as Postgres formats it with `extra_float_digits = 0`
+    const ROUNDED: &str = "0.333333333333333";
+
+    /// 1/3 in the shortest form that reads back as the same value, as with a positive `extra_float_digits`
+    const SHORTEST: &str = "0.3333333333333333";
+
+    async fn select_float(client: &tokio_postgres::Client, id: i64) -> String {
+        let sql = format!("SELECT encrypted_float8 FROM encrypted WHERE id = {id}");
+        let result = simple_query_with_client::<String>(&sql, client).await;
+        result.into_iter().next().unwrap()
+    }
+
+    #[tokio::test]
+    async fn decrypted_floats_follow_set_config() {
+        trace();
+
+        clear().await;
+
+        let client = connect_with_tls(*PROXY).await;
+
+        let id = random_id();
+        let sql = "INSERT INTO encrypted (id, encrypted_float8) VALUES ($1, $2)";
+        insert_with_client(sql, &[&id, &(1.0_f64 / 3.0)], &client).await;
+
+        assert_eq!(select_float(&client, id).await, SHORTEST);
+
+        client
+            .simple_query("SELECT set_config('extra_float_digits', '0', false)")
+            .await
+            .unwrap();
+        assert_eq!(select_float(&client, id).await, ROUNDED);
+
+        client
+            .simple_query("SET extra_float_digits = 1")
+            .await
+            .unwrap();
+        assert_eq!(select_float(&client, id).await, SHORTEST);
+    }
+
+    #[tokio::test]
+    async fn decrypted_floats_follow_rollback_to_savepoint() {
+        trace();
+
+        clear().await;
+
+        let client = connect_with_tls(*PROXY).await;
+
+        let id = random_id();
+        let sql = "INSERT INTO encrypted (id, encrypted_float8) VALUES ($1, $2)";
+        insert_with_client(sql, &[&id, &(1.0_f64 / 3.0)], &client).await;
+
+        client.simple_query("BEGIN").await.unwrap();
+        client.simple_query("SAVEPOINT floats").await.unwrap();
+        client
+            .simple_query("SET extra_float_digits = 0")
+            .await
+            .unwrap();
+        assert_eq!(select_float(&client, id).await, ROUNDED);
+
+        client
+            .simple_query("ROLLBACK TO SAVEPOINT floats")
+            .await
+            .unwrap();
+        assert_eq!(select_float(&client, id).await, SHORTEST);
+
+        client
+            .simple_query("SET LOCAL extra_float_digits = 0")
+            .await
+            .unwrap();
+        assert_eq!(select_float(&client, id).await, ROUNDED);
+
+        client.simple_query("COMMIT").await.unwrap();
+        assert_eq!(select_float(&client, id).await, SHORTEST);
+    }
+}
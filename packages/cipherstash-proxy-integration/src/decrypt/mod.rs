@@ -1 +1,2 @@
+mod extra_float_digits;
 mod insert_returning;
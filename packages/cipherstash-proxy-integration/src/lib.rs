@@ -28,6 +28,7 @@ mod schema_change;
 mod select;
 mod set_keyset_error;
 mod simple_protocol;
+mod strict_mapping;
 mod support;
 mod update;
 
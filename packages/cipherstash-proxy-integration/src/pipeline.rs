@@ -1,6 +1,10 @@
 #[cfg(test)]
 mod tests {
     use crate::common::{clear, connect_with_tls, random_id, trace, PROXY};
+    use crate::support::protocol::{
+        codes, connect_raw, BIND_COMPLETE, COMMAND_COMPLETE, DATA_ROW, ERROR_RESPONSE,
+        PARSE_COMPLETE, READY_FOR_QUERY,
+    };
     use std::sync::atomic::{AtomicUsize, Ordering};
 
     ///
@@ -140,4 +144,131 @@ mod tests {
         let count = counter.load(Ordering::SeqCst);
         assert_eq!(count, 4);
     }
+
+    ///
+    /// Test a run of pipelined Binds and Executes ended by a single Sync
+    ///
+    /// The params of every Bind in the run are encrypted together, and each
+    /// result must still come back in order, mapped to its own Bind.
+    ///
+    #[tokio::test]
+    async fn pipelined_binds_before_a_single_sync() {
+        trace();
+
+        clear().await;
+
+        let mut client = connect_raw(*PROXY).await;
+
+        let ids: Vec<String> = (0..5).map(|_| random_id().to_string()).collect();
+        let values: Vec<String> = (0..5).map(|i| format!("pipelined-{i}")).collect();
+
+        let sql = "INSERT INTO encrypted (id, encrypted_text) VALUES ($1, $2)";
+        client.parse("insert", sql);
+        for (id, value) in ids.iter().zip(&values) {
+            client.bind("insert", &[Some(id), Some(value)]);
+            client.execute();
+        }
+        client.sync();
+
+        let messages = client.send_and_read_until_ready().await;
+
+        let mut expected = vec![PARSE_COMPLETE];
+        expected.extend([BIND_COMPLETE, COMMAND_COMPLETE].repeat(5));
+        expected.push(READY_FOR_QUERY);
+        assert_eq!(codes(&messages), expected);
+
+        let sql = "SELECT id, encrypted_text FROM encrypted WHERE id = $1";
+        client.parse("select", sql);
+        for id in &ids {
+            client.bind("select", &[Some(id)]);
+            client.execute();
+        }
+        client.sync();
+
+        let messages = client.send_and_read_until_ready().await;
+
+        let mut expected = vec![PARSE_COMPLETE];
+        expected.extend([BIND_COMPLETE, DATA_ROW, COMMAND_COMPLETE].repeat(5));
+        expected.push(READY_FOR_QUERY);
+        assert_eq!(codes(&messages), expected);
+
+        let rows: Vec<Vec<Option<String>>> = messages
+            .iter()
+            .filter(|message| message.code == DATA_ROW)
+            .map(|message| message.data_row())
+            .collect();
+
+        let expected: Vec<Vec<Option<String>>> = ids
+            .iter()
+            .zip(&values)
+            .map(|(id, value)| vec![Some(id.to_owned()), Some(value.to_owned())])
+            .collect();
+
+        assert_eq!(rows, expected);
+    }
+
+    ///
+    /// Test a run of pipelined Binds where one Bind fails
+    ///
+    /// The failing Bind returns an error, every message after it is skipped up
+    /// to the Sync, and the run is rolled back as a single implicit transaction.
+    /// The connection is then ready for the next run.
+    ///
+    #[tokio::test]
+    async fn pipelined_bind_failure_skips_to_sync() {
+        trace();
+
+        clear().await;
+
+        let mut client = connect_raw(*PROXY).await;
+
+        let ids: Vec<i64> = (0..5).map(|_| random_id()).collect();
+        let values = ["1", "2", "not-a-number", "4", "5"];
+
+        let sql = "INSERT INTO encrypted (id, encrypted_int4) VALUES ($1, $2)";
+        client.parse("insert", sql);
+        for (id, value) in ids.iter().zip(values) {
+            client.bind("insert", &[Some(&id.to_string()), Some(value)]);
+            client.execute();
+        }
+        client.sync();
+
+        let messages = client.send_and_read_until_ready().await;
+
+        assert_eq!(
+            codes(&messages),
+            vec![
+                PARSE_COMPLETE,
+                BIND_COMPLETE,
+                COMMAND_COMPLETE,
+                BIND_COMPLETE,
+                COMMAND_COMPLETE,
+                ERROR_RESPONSE,
+                READY_FOR_QUERY,
+            ]
+        );
+
+        let error = messages
+            .iter()
+            .find(|message| message.code == ERROR_RESPONSE)
+            .unwrap();
+        assert!(!error.error_message().is_empty());
+
+        // The connection is still in step after the failed run
+        let id = random_id().to_string();
+        client.bind("insert", &[Some(&id), Some("6")]);
+        client.execute();
+        client.sync();
+
+        let messages = client.send_and_read_until_ready().await;
+        assert_eq!(
+            codes(&messages),
+            vec![BIND_COMPLETE, COMMAND_COMPLETE, READY_FOR_QUERY]
+        );
+
+        let checker = connect_with_tls(*PROXY).await;
+        let sql = "SELECT id FROM encrypted WHERE id = ANY($1)";
+        let rows = checker.query(sql, &[&ids]).await.unwrap();
+        assert!(rows.is_empty(), "No Bind of the failed run is committed");
+    }
 }
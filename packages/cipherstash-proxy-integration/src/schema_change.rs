@@ -1,6 +1,6 @@
 #[cfg(test)]
 mod tests {
-    use crate::common::{connect_with_tls, random_id, PROXY};
+    use crate::common::{clear, connect_with_tls, random_id, trace, PROXY};
 
     #[tokio::test]
     async fn schema_change_reloads_schema() {
@@ -22,4 +22,42 @@ mod tests {
 
         assert!(rows.is_empty());
     }
+
+    /// A table created from an encrypted column keeps it encrypted: the copy is
+    /// decrypted on read and searchable like the original.
+    #[tokio::test]
+    async fn create_table_as_keeps_encrypted_columns() {
+        trace();
+        clear().await;
+
+        let client = connect_with_tls(*PROXY).await;
+
+        let id = random_id();
+        let encrypted_text = "snapshot";
+
+        let sql = "INSERT INTO encrypted (id, encrypted_text) VALUES ($1, $2)";
+        client.execute(sql, &[&id, &encrypted_text]).await.unwrap();
+
+        for (snapshot, sql) in [
+            (
+                format!("ctas_{id}"),
+                format!("CREATE TABLE ctas_{id} AS SELECT id, encrypted_text FROM encrypted WHERE encrypted_text = '{encrypted_text}'"),
+            ),
+            (
+                format!("select_into_{id}"),
+                format!("SELECT id, encrypted_text INTO select_into_{id} FROM encrypted WHERE encrypted_text = '{encrypted_text}'"),
+            ),
+        ] {
+            client.execute(&sql, &[]).await.unwrap();
+
+            let sql = format!("SELECT encrypted_text FROM {snapshot} WHERE encrypted_text = $1");
+            let rows = client.query(&sql, &[&encrypted_text]).await.unwrap();
+
+            let actual = rows.iter().map(|row| row.get(0)).collect::<Vec<String>>();
+            assert_eq!(actual, vec![encrypted_text.to_string()], "{snapshot}");
+
+            let sql = format!("DROP TABLE {snapshot}");
+            client.execute(&sql, &[]).await.unwrap();
+        }
+    }
 }
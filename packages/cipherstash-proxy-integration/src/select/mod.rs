@@ -19,6 +19,7 @@ mod operator_class_shapes;
 mod order_by;
 mod order_by_with_null;
 mod pg_catalog;
+mod proxy_aggregates;
 mod regression;
 mod select_domain_type;
 mod select_where_in;
@@ -0,0 +1,90 @@
+#[cfg(test)]
+mod tests {
+    use crate::common::{clear, execute_query, query, random_id, simple_query, trace};
+
+    async fn insert_int4(values: &[Option<i32>]) {
+        for value in values {
+            let id = random_id();
+            let sql = "INSERT INTO encrypted (id, encrypted_int4) VALUES ($1, $2)";
+            execute_query(sql, &[&id, value]).await;
+        }
+    }
+
+    /// Requires `CS_MAPPING__PROXY_AGGREGATES=true`, which only the
+    /// `test:integration:proxy_aggregates` run sets.
+    #[tokio::test]
+    pub async fn sum_over_encrypted_int4() {
+        trace();
+
+        clear().await;
+
+        insert_int4(&[Some(1), Some(2), None, Some(3), Some(4)]).await;
+
+        let sql = "SELECT sum(encrypted_int4) FROM encrypted";
+
+        let rows = query::<i64>(sql).await;
+        assert_eq!(rows, vec![10]);
+
+        let rows = simple_query::<i64>(sql).await;
+        assert_eq!(rows, vec![10]);
+    }
+
+    #[tokio::test]
+    pub async fn avg_over_encrypted_int4() {
+        trace();
+
+        clear().await;
+
+        insert_int4(&[Some(1), Some(2), Some(3), Some(4)]).await;
+
+        let sql = "SELECT avg(encrypted_int4) FROM encrypted";
+
+        let rows = simple_query::<f64>(sql).await;
+        assert_eq!(rows, vec![2.5]);
+    }
+
+    #[tokio::test]
+    pub async fn percentile_disc_over_encrypted_int4() {
+        trace();
+
+        clear().await;
+
+        insert_int4(&[Some(5), Some(1), Some(3), Some(2)]).await;
+
+        let sql =
+            "SELECT percentile_disc(0.5) WITHIN GROUP (ORDER BY encrypted_int4) FROM encrypted";
+
+        let rows = query::<i32>(sql).await;
+        assert_eq!(rows, vec![2]);
+    }
+
+    #[tokio::test]
+    pub async fn sum_over_encrypted_int4_by_group() {
+        trace();
+
+        clear().await;
+
+        for (group, value) in [(1, 10), (1, 20), (2, 5)] {
+            let id = random_id();
+            let sql = "INSERT INTO encrypted (id, plaintext, encrypted_int4) VALUES ($1, $2, $3)";
+            execute_query(sql, &[&id, &group.to_string(), &value]).await;
+        }
+
+        let sql = "SELECT sum(encrypted_int4) FROM encrypted GROUP BY plaintext ORDER BY plaintext";
+
+        let rows = query::<i64>(sql).await;
+        assert_eq!(rows, vec![30, 5]);
+    }
+
+    #[tokio::test]
+    pub async fn sum_over_an_empty_group_is_null() {
+        trace();
+
+        clear().await;
+
+        let sql = "SELECT sum(encrypted_int4) FROM encrypted";
+
+        let rows = query::<Option<i64>>(sql).await;
+        assert_eq!(rows, vec![None]);
+    }
+}
@@ -0,0 +1,74 @@
+#[cfg(test)]
+mod tests {
+    use crate::common::{connect_with_tls, random_id, trace, PROXY};
+    use tokio_postgres::SimpleQueryMessage::Row;
+
+    fn rows(messages: &[tokio_postgres::SimpleQueryMessage]) -> Vec<(String, String, String)> {
+        messages
+            .iter()
+            .filter_map(|message| match message {
+                Row(r) => Some((
+                    r.get(0).unwrap_or_default().to_string(),
+                    r.get(1).unwrap_or_default().to_string(),
+                    r.get(2).unwrap_or_default().to_string(),
+                )),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn explain_shows_mapping_without_executing() {
+        trace();
+
+        let client = connect_with_tls(*PROXY).await;
+        let id = random_id();
+
+        let sql = format!(
+            "CIPHERSTASH EXPLAIN INSERT INTO encrypted (id, encrypted_text) VALUES ({id}, 'hello')"
+        );
+        let messages = client.simple_query(&sql).await.unwrap();
+        let rows = rows(&messages);
+
+        let (kind, _, statement) = &rows[0];
+        assert_eq!(kind, "statement");
+        assert!(statement.contains("'<encrypted>'"), "{statement}");
+        assert!(!statement.contains("hello"), "{statement}");
+
+        // The plaintext of an encrypted literal is redacted, because the
+        // explanation is returned by a query sent to the database
+        assert!(rows.iter().any(|(kind, name, detail)| kind == "literal"
+            && name == "'<encrypted>'"
+            && detail.contains("encrypted.encrypted_text")));
+        assert!(!rows
+            .iter()
+            .any(|(_, name, detail)| name.contains("hello") || detail.contains("hello")));
+
+        // The explained statement is never executed
+        let sql = format!("SELECT id FROM encrypted WHERE id = {id}");
+        let messages = client.simple_query(&sql).await.unwrap();
+        assert!(!messages.iter().any(|message| matches!(message, Row(_))));
+    }
+
+    #[tokio::test]
+    async fn explain_shows_decrypted_projection() {
+        trace();
+
+        let client = connect_with_tls(*PROXY).await;
+
+        let sql = "CIPHERSTASH EXPLAIN SELECT id, encrypted_text FROM encrypted WHERE encrypted_text = $1";
+        let messages = client.simple_query(sql).await.unwrap();
+        let rows = rows(&messages);
+
+        assert!(rows.iter().any(|(kind, name, detail)| kind == "param"
+            && name == "$1"
+            && detail.contains("encrypted.encrypted_text")));
+        assert!(rows.iter().any(|(kind, name, detail)| kind == "projection"
+            && name == "id"
+            && detail == "native"));
+        assert!(rows.iter().any(|(kind, name, detail)| kind == "projection"
+            && name == "encrypted_text"
+            && detail.starts_with("decrypt encrypted.encrypted_text")));
+        assert!(rows.iter().any(|(kind, _, _)| kind == "rule"));
+    }
+}
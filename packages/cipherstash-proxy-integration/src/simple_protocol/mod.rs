@@ -1,4 +1,5 @@
 mod error_handling;
+mod explain;
 mod map_literals;
 mod map_nulls;
 mod multiple_statements;
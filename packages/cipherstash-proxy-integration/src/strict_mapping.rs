@@ -0,0 +1,120 @@
+///
+/// Requires `CS_MAPPING__STRICT=true`, which only the `test:integration:strict_mapping` run sets.
+///
+#[cfg(test)]
+mod tests {
+    use crate::common::{clear, connect_with_tls, get_database_port, random_id, trace, PROXY};
+    use tokio_postgres::SimpleQueryMessage;
+
+    fn assert_refused<T>(result: Result<T, tokio_postgres::Error>) {
+        let Err(err) = result else {
+            panic!("expected the statement to be refused");
+        };
+        let db_err = err
+            .as_db_error()
+            .unwrap_or_else(|| panic!("expected a db error refusing the statement, got: {err:?}"));
+        assert!(
+            db_err.message().contains("refused in strict mode"),
+            "expected a strict mode refusal, got: {db_err:?}"
+        );
+    }
+
+    async fn count_rows(table: &str) -> usize {
+        let client = connect_with_tls(get_database_port()).await;
+        let sql = format!("SELECT id FROM {table}");
+        client.query(&sql, &[]).await.unwrap().len()
+    }
+
+    #[tokio::test]
+    async fn copy_from_stdin_into_encrypted_table_is_refused() {
+        trace();
+
+        clear().await;
+
+        let client = connect_with_tls(*PROXY).await;
+
+        let result = client
+            .copy_in::<_, bytes::Bytes>("COPY encrypted (id, encrypted_text) FROM STDIN")
+            .await;
+        assert_refused(result);
+
+        let result = client
+            .simple_query("COPY encrypted (id, encrypted_text) FROM STDIN")
+            .await;
+        assert_refused(result);
+
+        assert_eq!(count_rows("encrypted").await, 0);
+
+        // The connection remains usable
+        let rows = client.query("SELECT 1::int4", &[]).await.unwrap();
+        let one: i32 = rows[0].get(0);
+        assert_eq!(one, 1);
+    }
+
+    #[tokio::test]
+    async fn copy_to_stdout_from_encrypted_table_is_refused() {
+        trace();
+
+        let client = connect_with_tls(*PROXY).await;
+
+        let result = client.copy_out("COPY encrypted TO STDOUT").await;
+        assert_refused(result);
+
+        let result = client
+            .copy_out("COPY (SELECT encrypted_text FROM encrypted) TO STDOUT")
+            .await;
+        assert_refused(result);
+    }
+
+    #[tokio::test]
+    async fn prepare_and_execute_with_encrypted_column_is_refused() {
+        trace();
+
+        clear().await;
+
+        let client = connect_with_tls(*PROXY).await;
+
+        let sql = "PREPARE strict_insert (bigint, text) AS INSERT INTO encrypted (id, encrypted_text) VALUES ($1, $2)";
+        assert_refused(client.simple_query(sql).await);
+
+        // The statement was not prepared, so it cannot be executed with a plaintext value
+        let sql = format!("EXECUTE strict_insert({}, 'plaintext')", random_id());
+        let result = client.simple_query(&sql).await;
+        assert!(result.is_err());
+
+        let sql = "PREPARE strict_select AS SELECT encrypted_text FROM encrypted";
+        assert_refused(client.simple_query(sql).await);
+
+        assert_eq!(count_rows("encrypted").await, 0);
+    }
+
+    #[tokio::test]
+    async fn prepare_and_execute_without_encrypted_columns_is_passed_through() {
+        trace();
+
+        let client = connect_with_tls(*PROXY).await;
+
+        let id = random_id();
+
+        client
+            .simple_query(
+                "PREPARE strict_plaintext (bigint, text) AS INSERT INTO plaintext (id, plaintext) VALUES ($1, $2)",
+            )
+            .await
+            .unwrap();
+
+        let sql = format!("EXECUTE strict_plaintext({id}, 'plaintext')");
+        client.simple_query(&sql).await.unwrap();
+
+        let sql = format!("SELECT plaintext FROM plaintext WHERE id = {id}");
+        let messages = client.simple_query(&sql).await.unwrap();
+        let values = messages
+            .iter()
+            .filter_map(|message| match message {
+                SimpleQueryMessage::Row(row) => row.get(0).map(str::to_owned),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(values, vec!["plaintext".to_string()]);
+    }
+}
@@ -2,3 +2,6 @@
 pub mod assert;
 
 pub mod json_path;
+
+#[cfg(test)]
+pub mod protocol;
@@ -0,0 +1,250 @@
+//! A client that writes PostgreSQL protocol messages directly.
+//!
+//! `tokio_postgres` sends a Sync after every Bind and Execute, so it never sends
+//! a run of several Binds before a single Sync. This client writes each message
+//! as it is asked for, so a test can send exactly the sequence it needs and
+//! assert on every message that comes back.
+
+use bytes::{Buf, BufMut, BytesMut};
+use postgres_protocol::authentication::md5_hash;
+use tokio::{
+    io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt},
+    net::TcpStream,
+};
+use tokio_postgres::tls::{MakeTlsConnect, TlsConnect};
+use tokio_postgres_rustls::MakeRustlsConnect;
+
+use crate::common::configure_test_client;
+
+const USERNAME: &str = "cipherstash";
+const PASSWORD: &str = "p@ssword";
+const DATABASE: &str = "cipherstash";
+
+const PROTOCOL_VERSION: i32 = 196608;
+const SSL_REQUEST_CODE: i32 = 80877103;
+const AUTHENTICATION_OK: i32 = 0;
+const AUTHENTICATION_MD5_PASSWORD: i32 = 5;
+
+pub const PARSE_COMPLETE: u8 = b'1';
+pub const BIND_COMPLETE: u8 = b'2';
+pub const COMMAND_COMPLETE: u8 = b'C';
+pub const DATA_ROW: u8 = b'D';
+pub const ERROR_RESPONSE: u8 = b'E';
+pub const READY_FOR_QUERY: u8 = b'Z';
+
+/// A message received from the server, with its code and body.
+#[derive(Debug)]
+pub struct BackendMessage {
+    pub code: u8,
+    pub body: BytesMut,
+}
+
+impl BackendMessage {
+    /// The values of a DataRow, in the text format
+    pub fn data_row(&self) -> Vec<Option<String>> {
+        assert_eq!(self.code, DATA_ROW, "Expected a DataRow, got {self:?}");
+
+        let mut body = &self.body[..];
+        let count = body.get_i16();
+
+        (0..count)
+            .map(|_| {
+                let len = body.get_i32();
+                if len < 0 {
+                    return None;
+                }
+                let (value, rest) = body.split_at(len as usize);
+                body = rest;
+                Some(String::from_utf8(value.to_vec()).unwrap())
+            })
+            .collect()
+    }
+
+    /// The message field of an ErrorResponse
+    pub fn error_message(&self) -> String {
+        assert_eq!(
+            self.code, ERROR_RESPONSE,
+            "Expected an ErrorResponse, got {self:?}"
+        );
+
+        self.body
+            .split(|b| *b == 0)
+            .find_map(|field| field.strip_prefix(b"M"))
+            .map(|message| String::from_utf8_lossy(message).to_string())
+            .unwrap_or_default()
+    }
+}
+
+/// A client connected to Proxy over TLS, that buffers messages until `send`.
+pub struct RawClient<S> {
+    stream: S,
+    write_buffer: BytesMut,
+}
+
+/// Connects to Proxy over TLS and authenticates with the test credentials.
+///
+/// Returns once the server has sent its first ReadyForQuery.
+pub async fn connect_raw(port: u16) -> RawClient<impl AsyncRead + AsyncWrite + Unpin> {
+    let mut tcp = TcpStream::connect(("localhost", port)).await.unwrap();
+
+    let mut ssl_request = BytesMut::new();
+    ssl_request.put_i32(8);
+    ssl_request.put_i32(SSL_REQUEST_CODE);
+    tcp.write_all(&ssl_request).await.unwrap();
+
+    let response = tcp.read_u8().await.unwrap();
+    assert_eq!(response, b'S', "Expected the server to accept TLS");
+
+    let mut tls = MakeRustlsConnect::new(configure_test_client());
+    let stream =
+        <MakeRustlsConnect as MakeTlsConnect<TcpStream>>::make_tls_connect(&mut tls, "localhost")
+            .unwrap()
+            .connect(tcp)
+            .await
+            .unwrap();
+
+    let mut client = RawClient {
+        stream,
+        write_buffer: BytesMut::new(),
+    };
+
+    client.startup().await;
+    client
+}
+
+impl<S> RawClient<S>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    async fn startup(&mut self) {
+        let mut body = BytesMut::new();
+        body.put_i32(PROTOCOL_VERSION);
+        for (name, value) in [("user", USERNAME), ("database", DATABASE)] {
+            put_cstr(&mut body, name);
+            put_cstr(&mut body, value);
+        }
+        body.put_u8(0);
+
+        self.write_buffer.put_i32(body.len() as i32 + 4);
+        self.write_buffer.put(body);
+        self.send().await;
+
+        loop {
+            let mut message = self.read().await;
+            match message.code {
+                b'R' => match message.body.get_i32() {
+                    AUTHENTICATION_OK => {}
+                    AUTHENTICATION_MD5_PASSWORD => {
+                        let salt = [
+                            message.body[0],
+                            message.body[1],
+                            message.body[2],
+                            message.body[3],
+                        ];
+                        let password = md5_hash(USERNAME.as_bytes(), PASSWORD.as_bytes(), salt);
+
+                        let mut body = BytesMut::new();
+                        put_cstr(&mut body, &password);
+                        self.message(b'p', body);
+                        self.send().await;
+                    }
+                    other => panic!("Unexpected authentication request {other}"),
+                },
+                ERROR_RESPONSE => panic!("Connection failed: {}", message.error_message()),
+                READY_FOR_QUERY => return,
+                _ => {}
+            }
+        }
+    }
+
+    /// Buffers a Parse of `sql` into the named statement, leaving the server to infer param types
+    pub fn parse(&mut self, statement: &str, sql: &str) {
+        let mut body = BytesMut::new();
+        put_cstr(&mut body, statement);
+        put_cstr(&mut body, sql);
+        body.put_i16(0);
+        self.message(b'P', body);
+    }
+
+    /// Buffers a Bind of the named statement to the unnamed portal, with params and results in the text format
+    pub fn bind(&mut self, statement: &str, params: &[Option<&str>]) {
+        let mut body = BytesMut::new();
+        put_cstr(&mut body, "");
+        put_cstr(&mut body, statement);
+        body.put_i16(1);
+        body.put_i16(0);
+        body.put_i16(params.len() as i16);
+        for param in params {
+            match param {
+                Some(value) => {
+                    body.put_i32(value.len() as i32);
+                    body.put_slice(value.as_bytes());
+                }
+                None => body.put_i32(-1),
+            }
+        }
+        body.put_i16(0);
+        self.message(b'B', body);
+    }
+
+    /// Buffers an Execute of the unnamed portal, returning every row
+    pub fn execute(&mut self) {
+        let mut body = BytesMut::new();
+        put_cstr(&mut body, "");
+        body.put_i32(0);
+        self.message(b'E', body);
+    }
+
+    /// Buffers a Sync
+    pub fn sync(&mut self) {
+        self.message(b'S', BytesMut::new());
+    }
+
+    /// Writes every buffered message in a single write
+    pub async fn send(&mut self) {
+        let messages = self.write_buffer.split();
+        self.stream.write_all(&messages).await.unwrap();
+        self.stream.flush().await.unwrap();
+    }
+
+    /// Sends every buffered message and reads the responses up to and including the next ReadyForQuery
+    pub async fn send_and_read_until_ready(&mut self) -> Vec<BackendMessage> {
+        self.send().await;
+
+        let mut messages = vec![];
+        loop {
+            let message = self.read().await;
+            let code = message.code;
+            messages.push(message);
+            if code == READY_FOR_QUERY {
+                return messages;
+            }
+        }
+    }
+
+    async fn read(&mut self) -> BackendMessage {
+        let code = self.stream.read_u8().await.unwrap();
+        let len = self.stream.read_i32().await.unwrap();
+
+        let mut body = BytesMut::zeroed(len as usize - 4);
+        self.stream.read_exact(&mut body).await.unwrap();
+
+        BackendMessage { code, body }
+    }
+
+    fn message(&mut self, code: u8, body: BytesMut) {
+        self.write_buffer.put_u8(code);
+        self.write_buffer.put_i32(body.len() as i32 + 4);
+        self.write_buffer.put(body);
+    }
+}
+
+fn put_cstr(buf: &mut BytesMut, s: &str) {
+    buf.put_slice(s.as_bytes());
+    buf.put_u8(0);
+}
+
+/// The codes of `messages`, in order
+pub fn codes(messages: &[BackendMessage]) -> Vec<u8> {
+    messages.iter().map(|message| message.code).collect()
+}
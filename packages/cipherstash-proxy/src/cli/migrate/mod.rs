@@ -69,27 +69,7 @@ impl Migrate {
 
         // Important!!
         // Migrator connects to the the Proxy, not the database directly
-        // Build the Proxy connection config from the TandemConfig
-        let mut connection_config = tokio_postgres::Config::new();
-        connection_config
-            .host(&config.server.host) // Proxy/Server host
-            .port(config.server.port) // Proxy/Server port
-            .user(&config.database.username)
-            .password(config.database.password())
-            .dbname(&config.database.name);
-
-        let client =
-            connect_with_tls(connection_config, config.database.with_tls_verification).await?;
-
-        info!(
-            target: MIGRATE,
-            msg = "Connected to Database",
-            database = config.database.name,
-            host = config.server.host,
-            port = config.server.port,
-            username = config.database.username,
-            with_tls_verification = config.database.with_tls_verification,
-        );
+        let client = connect_to_proxy(&config).await?;
 
         info!(target: MIGRATE, msg = "Encrypting table", table = self.table, columns = ?self.columns);
 
@@ -252,6 +232,34 @@ where
     Ok((s[..pos].parse()?, s[pos + 1..].parse()?))
 }
 
+///
+/// Connects to the Proxy, with the database credentials of the TandemConfig
+///
+pub async fn connect_to_proxy(config: &TandemConfig) -> Result<Client, Error> {
+    // Build the Proxy connection config from the TandemConfig
+    let mut connection_config = tokio_postgres::Config::new();
+    connection_config
+        .host(&config.server.host) // Proxy/Server host
+        .port(config.server.port) // Proxy/Server port
+        .user(&config.database.username)
+        .password(config.database.password())
+        .dbname(&config.database.name);
+
+    let client = connect_with_tls(connection_config, config.database.with_tls_verification).await?;
+
+    info!(
+        target: MIGRATE,
+        msg = "Connected to Database",
+        database = config.database.name,
+        host = config.server.host,
+        port = config.server.port,
+        username = config.database.username,
+        with_tls_verification = config.database.with_tls_verification,
+    );
+
+    Ok(client)
+}
+
 pub async fn connect_with_tls(
     connection_config: tokio_postgres::Config,
     with_tls_verification: bool,
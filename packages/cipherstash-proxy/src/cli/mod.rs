@@ -1,4 +1,5 @@
 mod migrate;
+mod reencrypt;
 
 use crate::{
     config::{LogConfig, LogFormat, LogLevel},
@@ -10,6 +11,7 @@ use clap::{Parser, Subcommand};
 use tracing::debug;
 
 pub use migrate::Migrate;
+pub use reencrypt::Reencrypt;
 
 const DEFAULT_CONFIG_FILE: &str = "cipherstash-proxy.toml";
 
@@ -64,6 +66,7 @@ pub struct Args {
 
 pub enum Commands {
     Encrypt(Migrate),
+    Reencrypt(Reencrypt),
 }
 
 ///
@@ -77,6 +80,11 @@ pub async fn run(args: Args, config: TandemConfig) -> Result<bool, Error> {
             migrate.run(config).await?;
             Ok(true)
         }
+        Some(Commands::Reencrypt(reencrypt)) => {
+            debug!(target: MIGRATE, ?reencrypt);
+            reencrypt.run(config).await?;
+            Ok(true)
+        }
         None => Ok(false),
     }
 }
@@ -0,0 +1,648 @@
+use super::migrate::connect_to_proxy;
+use crate::error::{Error, ReencryptError};
+use crate::log::MIGRATE;
+use crate::TandemConfig;
+use postgres_protocol::escape::{escape_identifier, escape_literal};
+use serde_json::Value;
+use std::collections::HashMap;
+use tokio_postgres::{Client, SimpleQueryMessage};
+use tracing::{debug, info, warn};
+use uuid::Uuid;
+
+const ID: &str = "id";
+
+/// Pins the settings the values are read with, so that a value that cannot be decrypted fails the run
+/// instead of being read as NULL or the sentinel and written back with the target keyset.
+/// Values are read as text and written back as literals, so dates, times and floats are read in the
+/// ISO format, in UTC and with full precision, whatever the role or database defaults are.
+const SESSION_SQL: [&str; 5] = [
+    "SET CIPHERSTASH.DECRYPT_FAILURE = 'fail';",
+    "SET CIPHERSTASH.DECRYPT = on;",
+    "SET DateStyle = 'ISO';",
+    "SET TimeZone = 'UTC';",
+    "SET extra_float_digits = 3;",
+];
+
+/// The keys of the search terms of an EQL payload
+/// `hm` equality, `ob`/`op` order, `bf` match, and `sv` the entries of an encrypted jsonb document
+const SEARCH_TERMS: [&str; 5] = ["hm", "ob", "op", "bf", "sv"];
+
+#[derive(clap::Args, Clone, Debug)]
+#[command(version, about, long_about)]
+///
+/// Re-encrypt one or more encrypted columns in table from one keyset to another
+/// Requires a running and configured CipherStash Proxy instance without a default keyset.
+///
+pub struct Reencrypt {
+    ///
+    /// Name of database table
+    ///
+    #[arg(short, long)]
+    pub table: String,
+
+    ///
+    /// Encrypted columns as a space-delimited list `--columns email name`
+    ///
+    #[arg(short, required = true, long, num_args(1..))]
+    pub columns: Vec<String>,
+
+    ///
+    /// Primary key column/s
+    /// Compound primary keys can be provided as a space delimted list: `--primary-key id user_id`
+    ///
+    #[arg(short = 'k', long, num_args(1..), value_delimiter = ' ', default_values_t = vec![ID.to_string()])]
+    pub primary_key: Vec<String>,
+
+    /// Keyset the data is encrypted with, as a keyset id or name
+    #[arg(long)]
+    pub source_keyset: String,
+
+    /// Keyset to re-encrypt the data with, as a keyset id or name
+    #[arg(long)]
+    pub target_keyset: String,
+
+    // Updates `batch_size` records at a time
+    #[arg(short, long, default_value_t = 100)]
+    pub batch_size: usize,
+
+    /// Resume after the record with this primary key, as logged in the checkpoint of each committed batch
+    #[arg(short, long, num_args(1..), value_delimiter = ' ')]
+    pub resume_after: Vec<String>,
+
+    /// Run without update. Data is fetched and decrypted with the source keyset, but updates are not performed.
+    #[arg(short, long, default_value_t = false)]
+    pub dry_run: bool,
+
+    /// Turn on additional logging output
+    #[arg(short, long, default_value_t = false)]
+    pub verbose: bool,
+}
+
+///
+/// A record read with the source keyset
+///
+#[derive(Debug)]
+struct Record {
+    primary_key: Vec<String>,
+    /// Decrypted value of each column
+    values: Vec<Option<String>>,
+    /// Stored EQL payload of each column
+    payloads: Vec<Option<String>>,
+}
+
+impl Reencrypt {
+    ///
+    /// Returns true if this is not a `dry_run`
+    ///
+    fn commit(&self) -> bool {
+        !self.dry_run
+    }
+
+    ///
+    /// Run the re-encryption process
+    ///
+    /// Each batch is read with the source keyset, and written with the target keyset in its own transaction.
+    /// Before the transaction is committed, the stored payloads are read again to check every search term was regenerated.
+    /// Batches are read in primary key order, so a failed run can be resumed from the last checkpoint.
+    ///
+    pub async fn run(&self, config: TandemConfig) -> Result<(), Error> {
+        debug!(target: MIGRATE, ?config);
+
+        if self.source_keyset == self.target_keyset {
+            return Err(ReencryptError::SameKeyset {
+                keyset: self.source_keyset.to_owned(),
+            }
+            .into());
+        }
+
+        if !self.resume_after.is_empty() && self.resume_after.len() != self.primary_key.len() {
+            return Err(ReencryptError::InvalidCheckpoint {
+                primary_key: self.primary_key.join(", "),
+            }
+            .into());
+        }
+
+        // Important!!
+        // Re-encryption connects to the Proxy, not the database directly
+        let client = connect_to_proxy(&config).await?;
+
+        for sql in SESSION_SQL {
+            client.simple_query(sql).await?;
+        }
+
+        info!(target: MIGRATE, msg = "Re-encrypting table", table = self.table, columns = ?self.columns, source_keyset = self.source_keyset, target_keyset = self.target_keyset);
+
+        if !self.commit() {
+            warn!(msg = "Dry run is enabled");
+        }
+
+        let mut batch_count = 0;
+        let mut updated_count = 0;
+
+        let mut after = if self.resume_after.is_empty() {
+            None
+        } else {
+            Some(self.resume_after.to_owned())
+        };
+
+        loop {
+            client
+                .simple_query(&set_keyset_sql(&self.source_keyset))
+                .await?;
+
+            if self.commit() {
+                client.simple_query("BEGIN;").await?;
+            }
+
+            let sql = self.select_sql(after.as_deref());
+            debug!(target: MIGRATE, msg = "Select", sql);
+
+            let records = self.read_records(&client, &sql).await?;
+
+            let last = match records.last() {
+                Some(record) => record.primary_key.to_owned(),
+                None => {
+                    if self.commit() {
+                        client.simple_query("COMMIT;").await?;
+                    }
+                    break;
+                }
+            };
+
+            if self.verbose {
+                let primary_keys = records
+                    .iter()
+                    .map(|record| &record.primary_key)
+                    .collect::<Vec<_>>();
+                info!(target: MIGRATE, msg = "Re-encrypting", records = ?primary_keys);
+            }
+
+            if self.commit() {
+                client
+                    .simple_query(&set_keyset_sql(&self.target_keyset))
+                    .await?;
+
+                let update_sql = self.update_sql(&records);
+                debug!(target: MIGRATE, msg = "Update", update_sql);
+                client.simple_query(&update_sql).await?;
+
+                if let Err(err) = self
+                    .verify(&client, &records, after.as_deref(), &last)
+                    .await
+                {
+                    client.simple_query("ROLLBACK;").await?;
+                    return Err(err);
+                }
+
+                client.simple_query("COMMIT;").await?;
+            }
+
+            updated_count += records.len();
+            batch_count += 1;
+
+            // The primary key of the last record is the checkpoint to resume from
+            info!(target: MIGRATE, msg = "Batch re-encrypted", batch = batch_count, records = records.len(), checkpoint = last.join(" "));
+
+            if records.len() < self.batch_size {
+                break;
+            }
+
+            after = Some(last);
+        }
+
+        info!(
+            target: MIGRATE,
+            msg = "Re-encryption complete",
+            updated = updated_count,
+            batches = batch_count,
+        );
+
+        Ok(())
+    }
+
+    ///
+    /// Reads the primary key, decrypted value and stored payload of each column
+    ///
+    async fn read_records(&self, client: &Client, sql: &str) -> Result<Vec<Record>, Error> {
+        let key_count = self.primary_key.len();
+        let column_count = self.columns.len();
+
+        let records = client
+            .simple_query(sql)
+            .await?
+            .into_iter()
+            .filter_map(|message| match message {
+                SimpleQueryMessage::Row(row) => Some(Record {
+                    primary_key: (0..key_count)
+                        .map(|idx| row.get(idx).unwrap_or("").to_owned())
+                        .collect(),
+                    values: (key_count..key_count + column_count)
+                        .map(|idx| row.get(idx).map(str::to_owned))
+                        .collect(),
+                    payloads: (key_count + column_count..key_count + column_count * 2)
+                        .map(|idx| row.get(idx).map(str::to_owned))
+                        .collect(),
+                }),
+                _ => None,
+            })
+            .collect();
+
+        Ok(records)
+    }
+
+    ///
+    /// Reads the payloads written with the target keyset, and checks that each search term of the source payload was regenerated
+    ///
+    async fn verify(
+        &self,
+        client: &Client,
+        records: &[Record],
+        after: Option<&[String]>,
+        last: &[String],
+    ) -> Result<(), Error> {
+        let sql = self.verify_sql(after, last);
+        debug!(target: MIGRATE, msg = "Verify", sql);
+
+        let key_count = self.primary_key.len();
+
+        let payloads = client
+            .simple_query(&sql)
+            .await?
+            .into_iter()
+            .filter_map(|message| match message {
+                SimpleQueryMessage::Row(row) => {
+                    let primary_key = (0..key_count)
+                        .map(|idx| row.get(idx).unwrap_or("").to_owned())
+                        .collect::<Vec<_>>();
+                    let payloads = (key_count..key_count + self.columns.len())
+                        .map(|idx| row.get(idx).map(str::to_owned))
+                        .collect::<Vec<_>>();
+                    Some((primary_key, payloads))
+                }
+                _ => None,
+            })
+            .collect::<HashMap<_, _>>();
+
+        self.check_payloads(records, &payloads)
+    }
+
+    ///
+    /// Checks the payload written for each value of the source, and that its search terms were regenerated
+    ///
+    fn check_payloads(
+        &self,
+        records: &[Record],
+        payloads: &HashMap<Vec<String>, Vec<Option<String>>>,
+    ) -> Result<(), Error> {
+        for record in records {
+            let target = payloads.get(&record.primary_key);
+
+            for (idx, column) in self.columns.iter().enumerate() {
+                let Some(source) = &record.payloads[idx] else {
+                    continue;
+                };
+
+                let Some(target) = target.and_then(|payloads| payloads[idx].as_deref()) else {
+                    return Err(ReencryptError::ValueNotWritten {
+                        table: self.table.to_owned(),
+                        column: column.to_owned(),
+                        primary_key: record.primary_key.join(", "),
+                    }
+                    .into());
+                };
+
+                let terms = stale_search_terms(source, Some(target));
+                if !terms.is_empty() {
+                    return Err(ReencryptError::SearchTermsNotRegenerated {
+                        table: self.table.to_owned(),
+                        column: column.to_owned(),
+                        primary_key: record.primary_key.join(", "),
+                        terms: terms.join(", "),
+                    }
+                    .into());
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    ///
+    /// Selects the next batch of records after the `after` primary key
+    ///
+    /// `cipherstash.raw` returns the stored payload, which is compared with the payload written with the target keyset.
+    ///
+    fn select_sql(&self, after: Option<&[String]>) -> String {
+        let table = escape_identifier(&self.table);
+        let primary_key = self.primary_key_idents();
+
+        let columns = self
+            .columns
+            .iter()
+            .map(|column| escape_identifier(column))
+            .chain(
+                self.columns
+                    .iter()
+                    .map(|column| format!("cipherstash.raw({})", escape_identifier(column))),
+            )
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        let where_clause = match after {
+            Some(after) => format!(" WHERE {}", self.after_condition(after)),
+            None => String::new(),
+        };
+
+        let lock = if self.commit() { " FOR UPDATE" } else { "" };
+
+        format!(
+            "SELECT {primary_key}, {columns} FROM {table}{where_clause} ORDER BY {primary_key} LIMIT {}{lock}",
+            self.batch_size
+        )
+    }
+
+    ///
+    /// Selects the stored payloads of the records of the batch, from after the `after` primary key up to and including `last`
+    ///
+    fn verify_sql(&self, after: Option<&[String]>, last: &[String]) -> String {
+        let table = escape_identifier(&self.table);
+        let primary_key = self.primary_key_idents();
+
+        let columns = self
+            .columns
+            .iter()
+            .map(|column| format!("cipherstash.raw({})", escape_identifier(column)))
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        let mut conditions = vec![];
+        if let Some(after) = after {
+            conditions.push(self.after_condition(after));
+        }
+        conditions.push(format!("NOT ({})", self.after_condition(last)));
+
+        format!(
+            "SELECT {primary_key}, {columns} FROM {table} WHERE {}",
+            conditions.join(" AND ")
+        )
+    }
+
+    ///
+    /// Updates each record with its decrypted values, which are encrypted by Proxy with the target keyset
+    ///
+    fn update_sql(&self, records: &[Record]) -> String {
+        let table = escape_identifier(&self.table);
+
+        records
+            .iter()
+            .map(|record| {
+                let set = self
+                    .columns
+                    .iter()
+                    .zip(record.values.iter())
+                    .map(|(column, value)| {
+                        let column = escape_identifier(column);
+                        match value {
+                            Some(value) => format!("{column} = {}", escape_literal(value)),
+                            None => format!("{column} = NULL"),
+                        }
+                    })
+                    .collect::<Vec<_>>()
+                    .join(", ");
+
+                let where_clause = self
+                    .primary_key
+                    .iter()
+                    .zip(record.primary_key.iter())
+                    .map(|(column, value)| {
+                        format!("{} = {}", escape_identifier(column), escape_literal(value))
+                    })
+                    .collect::<Vec<_>>()
+                    .join(" AND ");
+
+                format!("UPDATE {table} SET {set} WHERE {where_clause};\n")
+            })
+            .collect()
+    }
+
+    fn primary_key_idents(&self) -> String {
+        self.primary_key
+            .iter()
+            .map(|pk| escape_identifier(pk))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    ///
+    /// Matches the records ordered after the primary key `values`
+    /// A compound key `(a, b) > (1, 2)` is expanded to `a > 1 OR (a = 1 AND b > 2)`
+    ///
+    fn after_condition(&self, values: &[String]) -> String {
+        let keys = self
+            .primary_key
+            .iter()
+            .map(|pk| escape_identifier(pk))
+            .zip(values.iter().map(|value| escape_literal(value)))
+            .collect::<Vec<_>>();
+
+        (0..keys.len())
+            .map(|idx| {
+                let (column, value) = &keys[idx];
+                let condition = keys[..idx]
+                    .iter()
+                    .map(|(column, value)| format!("{column} = {value}"))
+                    .chain(std::iter::once(format!("{column} > {value}")))
+                    .collect::<Vec<_>>()
+                    .join(" AND ");
+                format!("({condition})")
+            })
+            .collect::<Vec<_>>()
+            .join(" OR ")
+    }
+}
+
+///
+/// Sets the active keyset of the Proxy connection, by id if the keyset is a UUID or otherwise by name
+///
+fn set_keyset_sql(keyset: &str) -> String {
+    match Uuid::parse_str(keyset) {
+        Ok(id) => format!("SET CIPHERSTASH.KEYSET_ID = '{id}';"),
+        Err(_) => format!("SET CIPHERSTASH.KEYSET_NAME = {};", escape_literal(keyset)),
+    }
+}
+
+///
+/// Returns the search terms of the source payload that are missing from the target payload, or have not changed
+///
+/// A term with the same value was not regenerated with the target keyset, and queries using the term would not match.
+/// The entries of an encrypted jsonb document (`sv`) are regenerated together, and the target has the same number of entries.
+///
+fn stale_search_terms(source: &str, target: Option<&str>) -> Vec<&'static str> {
+    let Ok(source) = serde_json::from_str::<Value>(source) else {
+        return vec![];
+    };
+
+    let target = target
+        .and_then(|target| serde_json::from_str::<Value>(target).ok())
+        .unwrap_or(Value::Null);
+
+    SEARCH_TERMS
+        .into_iter()
+        .filter(|term| match (source.get(term), target.get(term)) {
+            (None, _) => false,
+            (Some(Value::Array(source)), Some(Value::Array(target))) if *term == "sv" => {
+                source.len() != target.len() || source == target
+            }
+            (Some(source), Some(target)) => source == target,
+            (Some(_), None) => true,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{set_keyset_sql, stale_search_terms, Record, Reencrypt};
+    use crate::error::{Error, ReencryptError};
+    use std::collections::HashMap;
+
+    fn reencrypt(primary_key: &[&str]) -> Reencrypt {
+        Reencrypt {
+            table: "users".to_string(),
+            columns: vec!["email".to_string(), "name".to_string()],
+            primary_key: primary_key.iter().map(|pk| pk.to_string()).collect(),
+            source_keyset: "tenant-a".to_string(),
+            target_keyset: "tenant-b".to_string(),
+            batch_size: 100,
+            resume_after: vec![],
+            dry_run: false,
+            verbose: false,
+        }
+    }
+
+    #[test]
+    fn select_sql() {
+        let reencrypt = reencrypt(&["id"]);
+
+        assert_eq!(
+            reencrypt.select_sql(None),
+            r#"SELECT "id", "email", "name", cipherstash.raw("email"), cipherstash.raw("name") FROM "users" ORDER BY "id" LIMIT 100 FOR UPDATE"#
+        );
+
+        assert_eq!(
+            reencrypt.select_sql(Some(&["42".to_string()])),
+            r#"SELECT "id", "email", "name", cipherstash.raw("email"), cipherstash.raw("name") FROM "users" WHERE ("id" > '42') ORDER BY "id" LIMIT 100 FOR UPDATE"#
+        );
+    }
+
+    #[test]
+    fn select_sql_dry_run_does_not_lock() {
+        let reencrypt = Reencrypt {
+            dry_run: true,
+            ..reencrypt(&["id"])
+        };
+
+        assert!(!reencrypt.select_sql(None).contains("FOR UPDATE"));
+    }
+
+    #[test]
+    fn after_condition_with_compound_primary_key() {
+        let reencrypt = reencrypt(&["tenant_id", "id"]);
+
+        assert_eq!(
+            reencrypt.after_condition(&["1".to_string(), "2".to_string()]),
+            r#"("tenant_id" > '1') OR ("tenant_id" = '1' AND "id" > '2')"#
+        );
+    }
+
+    #[test]
+    fn verify_sql() {
+        let reencrypt = reencrypt(&["id"]);
+
+        assert_eq!(
+            reencrypt.verify_sql(Some(&["1".to_string()]), &["100".to_string()]),
+            r#"SELECT "id", cipherstash.raw("email"), cipherstash.raw("name") FROM "users" WHERE ("id" > '1') AND NOT (("id" > '100'))"#
+        );
+    }
+
+    #[test]
+    fn update_sql() {
+        let reencrypt = reencrypt(&["id"]);
+
+        let records = vec![Record {
+            primary_key: vec!["1".to_string()],
+            values: vec![Some("o'brien@example.com".to_string()), None],
+            payloads: vec![None, None],
+        }];
+
+        assert_eq!(
+            reencrypt.update_sql(&records),
+            "UPDATE \"users\" SET \"email\" = 'o''brien@example.com', \"name\" = NULL WHERE \"id\" = '1';\n"
+        );
+    }
+
+    #[test]
+    fn set_keyset_by_id_or_name() {
+        assert_eq!(
+            set_keyset_sql("2cace9db-3a2a-4b46-a184-ba412b3e0730"),
+            "SET CIPHERSTASH.KEYSET_ID = '2cace9db-3a2a-4b46-a184-ba412b3e0730';"
+        );
+        assert_eq!(
+            set_keyset_sql("tenant-a"),
+            "SET CIPHERSTASH.KEYSET_NAME = 'tenant-a';"
+        );
+    }
+
+    #[test]
+    fn regenerated_search_terms() {
+        let source = r#"{"v": 3, "c": "a", "hm": "aa", "ob": ["aa"], "bf": [1, 2]}"#;
+        let target = r#"{"v": 3, "c": "b", "hm": "bb", "ob": ["bb"], "bf": [3, 4]}"#;
+
+        assert!(stale_search_terms(source, Some(target)).is_empty());
+    }
+
+    #[test]
+    fn stale_and_missing_search_terms() {
+        let source = r#"{"v": 3, "c": "a", "hm": "aa", "op": "aa", "bf": [1, 2]}"#;
+        let target = r#"{"v": 3, "c": "b", "hm": "aa", "bf": [3, 4]}"#;
+
+        assert_eq!(stale_search_terms(source, Some(target)), vec!["hm", "op"]);
+        assert_eq!(stale_search_terms(source, None), vec!["hm", "op", "bf"]);
+    }
+
+    #[test]
+    fn ste_vec_entries_are_regenerated() {
+        let source = r#"{"v": 3, "k": "sv", "sv": [{"s": "a", "c": "a"}, {"s": "b", "c": "b"}]}"#;
+        let regenerated =
+            r#"{"v": 3, "k": "sv", "sv": [{"s": "c", "c": "c"}, {"s": "d", "c": "d"}]}"#;
+        let truncated = r#"{"v": 3, "k": "sv", "sv": [{"s": "c", "c": "c"}]}"#;
+
+        assert!(stale_search_terms(source, Some(regenerated)).is_empty());
+        assert_eq!(stale_search_terms(source, Some(truncated)), vec!["sv"]);
+    }
+
+    #[test]
+    fn value_written_as_null_fails_the_batch() {
+        let reencrypt = reencrypt(&["id"]);
+
+        // A payload without search terms, read as NULL when it could not be decrypted
+        let source = r#"{"v": 3, "c": "a"}"#;
+        let records = vec![Record {
+            primary_key: vec!["1".to_string()],
+            values: vec![None, None],
+            payloads: vec![Some(source.to_string()), None],
+        }];
+
+        let written = HashMap::from([(
+            vec!["1".to_string()],
+            vec![Some(r#"{"v": 3, "c": "b"}"#.to_string()), None],
+        )]);
+        assert!(reencrypt.check_payloads(&records, &written).is_ok());
+
+        let nulled = HashMap::from([(vec!["1".to_string()], vec![None, None])]);
+        let err = reencrypt.check_payloads(&records, &nulled).unwrap_err();
+        assert!(matches!(
+            err,
+            Error::Reencrypt(ReencryptError::ValueNotWritten { ref column, .. }) if column == "email"
+        ));
+    }
+}
@@ -1,8 +1,12 @@
-use super::protected_string_deserializer;
+use super::{protected_string_deserializer, server::unix_socket_path, string_list_deserializer};
 use crate::error::{ConfigError, Error};
 use rustls_pki_types::ServerName;
 use serde::Deserialize;
-use std::{fmt::Display, time::Duration};
+use std::{
+    fmt::Display,
+    path::{Path, PathBuf},
+    time::Duration,
+};
 use vitaminc_protected::{Controlled, Protected};
 
 #[derive(Clone, Debug, Deserialize)]
@@ -13,6 +17,18 @@ pub struct DatabaseConfig {
     #[serde(default = "DatabaseConfig::default_port")]
     pub port: u16,
 
+    /// Database hosts as `host` or `host:port`, tried in order in place of `host` and `port`
+    #[serde(default, deserialize_with = "string_list_deserializer")]
+    pub hosts: Vec<String>,
+
+    /// The session a host must accept to be connected, following libpq `target_session_attrs`
+    #[serde(default)]
+    pub target_session_attrs: TargetSessionAttrs,
+
+    /// How often the selected host is checked, in seconds
+    #[serde(default = "DatabaseConfig::default_host_check_interval")]
+    pub host_check_interval: u64,
+
     pub name: String,
 
     #[serde(default = "DatabaseConfig::default_username")]
@@ -26,9 +42,42 @@ pub struct DatabaseConfig {
     #[serde(default)]
     pub with_tls_verification: bool,
 
+    /// How TLS is negotiated with the database, following libpq `sslmode`.
+    /// Defaults to `verify-full` with `with_tls_verification`, and `prefer` without.
+    pub ssl_mode: Option<SslMode>,
+
+    /// CA certificates used to verify the database server, in place of the system roots
+    pub ca_certificate_path: Option<String>,
+    pub ca_certificate_pem: Option<String>,
+
+    /// Client certificate and private key presented to the database
+    pub client_certificate_path: Option<String>,
+    pub client_certificate_pem: Option<String>,
+    pub client_private_key_path: Option<String>,
+    pub client_private_key_pem: Option<String>,
+
     #[serde(default = "DatabaseConfig::default_config_reload_interval")]
     pub config_reload_interval: u64,
 
+    /// Read replicas as `host` or `host:port`, using the database `port` if none is given
+    #[serde(default, deserialize_with = "string_list_deserializer")]
+    pub replicas: Vec<String>,
+
+    #[serde(default)]
+    pub replica_balancing: ReplicaBalancing,
+
+    /// How often replica health is checked, in seconds
+    #[serde(default = "DatabaseConfig::default_replica_health_check_interval")]
+    pub replica_health_check_interval: u64,
+
+    /// How long a connection keeps reading from the primary after a write, in milliseconds
+    #[serde(default = "DatabaseConfig::default_replica_sticky_primary_ms")]
+    pub replica_sticky_primary_ms: u64,
+
+    /// How far a replica can fall behind the primary before reads stop using it, in milliseconds
+    #[serde(default = "DatabaseConfig::default_replica_max_lag_ms")]
+    pub replica_max_lag_ms: u64,
+
     #[serde(default = "DatabaseConfig::default_schema_reload_interval")]
     pub schema_reload_interval: u64,
 }
@@ -54,10 +103,128 @@ impl DatabaseConfig {
         60
     }
 
+    pub const fn default_host_check_interval() -> u64 {
+        10
+    }
+
+    pub const fn default_replica_health_check_interval() -> u64 {
+        10
+    }
+
+    pub const fn default_replica_sticky_primary_ms() -> u64 {
+        1000
+    }
+
+    pub const fn default_replica_max_lag_ms() -> u64 {
+        5000
+    }
+
+    pub fn host_check_interval(&self) -> Duration {
+        Duration::from_secs(self.host_check_interval)
+    }
+
+    pub fn replica_health_check_interval(&self) -> Duration {
+        Duration::from_secs(self.replica_health_check_interval)
+    }
+
+    pub fn replica_sticky_primary(&self) -> Duration {
+        Duration::from_millis(self.replica_sticky_primary_ms)
+    }
+
+    pub fn replica_max_lag(&self) -> Duration {
+        Duration::from_millis(self.replica_max_lag_ms)
+    }
+
+    ///
+    /// The connection configuration of each database host, in the order they are tried.
+    ///
+    /// Without `hosts`, this is the configured `host` and `port`.
+    /// Hosts share the name, credentials and TLS settings of the database.
+    ///
+    pub fn host_configs(&self) -> Result<Vec<DatabaseConfig>, ConfigError> {
+        if self.hosts.is_empty() {
+            return Ok(vec![self.clone()]);
+        }
+        self.with_hosts(&self.hosts, "database.hosts")
+    }
+
+    ///
+    /// Returns true if a host is selected from `hosts` or checked against `target_session_attrs` on connect
+    ///
+    pub fn selects_host(&self) -> bool {
+        self.hosts.len() > 1 || self.target_session_attrs != TargetSessionAttrs::Any
+    }
+
+    ///
+    /// The connection configuration of each replica.
+    ///
+    /// Replicas share the name, credentials and TLS settings of the primary.
+    /// A replica starting with `/` is a Unix domain socket directory, and an IPv6 address with a port is written as `[::1]:5432`.
+    ///
+    pub fn replica_configs(&self) -> Result<Vec<DatabaseConfig>, ConfigError> {
+        self.with_hosts(&self.replicas, "database.replicas")
+    }
+
+    fn with_hosts(&self, hosts: &[String], name: &str) -> Result<Vec<DatabaseConfig>, ConfigError> {
+        hosts
+            .iter()
+            .map(|value| {
+                let (host, port) = parse_host_port(value, self.port).ok_or_else(|| {
+                    ConfigError::InvalidParameter {
+                        name: name.to_string(),
+                        value: value.to_owned(),
+                    }
+                })?;
+
+                Ok(DatabaseConfig {
+                    host,
+                    port,
+                    hosts: vec![],
+                    target_session_attrs: TargetSessionAttrs::Any,
+                    replicas: vec![],
+                    ..self.clone()
+                })
+            })
+            .collect()
+    }
+
+    ///
+    /// The effective `sslmode`.
+    ///
+    /// Without an explicit `ssl_mode` the legacy `with_tls_verification` flag decides:
+    /// verification implies `verify-full`, and no verification implies `prefer`.
+    ///
+    pub fn ssl_mode(&self) -> SslMode {
+        match self.ssl_mode {
+            Some(ssl_mode) => ssl_mode,
+            None if self.with_tls_verification => SslMode::VerifyFull,
+            None => SslMode::Prefer,
+        }
+    }
+
+    ///
+    /// The Unix domain socket path, if the host is a socket directory.
+    ///
+    /// As with libpq, a host starting with `/` is the directory containing the `.s.PGSQL.<port>` socket.
+    ///
+    pub fn unix_socket_path(&self) -> Option<PathBuf> {
+        self.host
+            .starts_with('/')
+            .then(|| unix_socket_path(Path::new(&self.host), self.port))
+    }
+
     pub fn to_socket_address(&self) -> String {
-        format!("{}:{}", self.host, self.port)
+        match self.unix_socket_path() {
+            Some(path) => path.to_string_lossy().to_string(),
+            None => format!("{}:{}", self.host, self.port),
+        }
     }
 
+    ///
+    /// The connection configuration used by the Proxy's own database connections
+    ///
+    /// A host that does not answer within the connect timeout is treated as down, so the next host can be tried.
+    ///
     pub fn to_connection_config(&self) -> tokio_postgres::Config {
         let mut db_config = tokio_postgres::Config::new();
         let password = self.password();
@@ -66,7 +233,14 @@ impl DatabaseConfig {
             .port(self.port)
             .user(&self.username)
             .password(password)
-            .dbname(&self.name);
+            .dbname(&self.name)
+            .connect_timeout(Self::CONNECT_TIMEOUT);
+
+        // libpq does not negotiate TLS over a Unix domain socket
+        match self.unix_socket_path() {
+            Some(_) => db_config.ssl_mode(tokio_postgres::config::SslMode::Disable),
+            None => db_config.ssl_mode(self.ssl_mode().into()),
+        };
 
         db_config
     }
@@ -77,6 +251,8 @@ impl DatabaseConfig {
 
     const DEFAULT_CONNECTION_TIMEOUT_MS: u64 = 120_000;
 
+    const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
     pub fn connection_timeout(&self) -> Option<Duration> {
         match self.connection_timeout {
             Some(0) => None,
@@ -99,17 +275,175 @@ impl DatabaseConfig {
         Self {
             host: Self::default_host(),
             port: Self::default_port(),
+            hosts: vec![],
+            target_session_attrs: TargetSessionAttrs::default(),
+            host_check_interval: Self::default_host_check_interval(),
             name: "test".to_string(),
             username: "test".to_string(),
             password: Protected::new("test".to_string()),
             connection_timeout: None,
             with_tls_verification: false,
+            ssl_mode: None,
+            ca_certificate_path: None,
+            ca_certificate_pem: None,
+            client_certificate_path: None,
+            client_certificate_pem: None,
+            client_private_key_path: None,
+            client_private_key_pem: None,
             config_reload_interval: Self::default_config_reload_interval(),
             schema_reload_interval: Self::default_schema_reload_interval(),
+            replicas: vec![],
+            replica_balancing: ReplicaBalancing::default(),
+            replica_health_check_interval: Self::default_replica_health_check_interval(),
+            replica_sticky_primary_ms: Self::default_replica_sticky_primary_ms(),
+            replica_max_lag_ms: Self::default_replica_max_lag_ms(),
+        }
+    }
+}
+
+///
+/// TLS negotiation with the database, with the semantics of libpq `sslmode`
+///
+///   - `disable` never uses TLS
+///   - `prefer` uses TLS if the database supports it, without verifying the certificate
+///   - `require` always uses TLS, without verifying the certificate
+///   - `verify-ca` always uses TLS, and verifies the certificate is signed by a trusted CA
+///   - `verify-full` as `verify-ca`, and also verifies the certificate matches the host name
+///
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum SslMode {
+    // Serde does not seem to have a case insensitive option. alias is clunky, but better than custom de/serialisers
+    #[serde(alias = "Disable", alias = "DISABLE")]
+    Disable,
+    #[serde(alias = "Prefer", alias = "PREFER")]
+    Prefer,
+    #[serde(alias = "Require", alias = "REQUIRE")]
+    Require,
+    #[serde(alias = "VerifyCa", alias = "VERIFY-CA", alias = "verify_ca")]
+    VerifyCa,
+    #[serde(alias = "VerifyFull", alias = "VERIFY-FULL", alias = "verify_full")]
+    VerifyFull,
+}
+
+impl SslMode {
+    /// Returns true if the connection must not fall back to plaintext
+    pub fn requires_tls(&self) -> bool {
+        !matches!(self, SslMode::Disable | SslMode::Prefer)
+    }
+
+    /// Returns true if the database certificate chain is verified
+    pub fn verifies_certificate(&self) -> bool {
+        matches!(self, SslMode::VerifyCa | SslMode::VerifyFull)
+    }
+}
+
+impl Display for SslMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            SslMode::Disable => "disable",
+            SslMode::Prefer => "prefer",
+            SslMode::Require => "require",
+            SslMode::VerifyCa => "verify-ca",
+            SslMode::VerifyFull => "verify-full",
+        };
+        write!(f, "{s}")
+    }
+}
+
+///
+/// tokio_postgres only negotiates TLS, verification is configured on the connector
+///
+impl From<SslMode> for tokio_postgres::config::SslMode {
+    fn from(ssl_mode: SslMode) -> Self {
+        match ssl_mode {
+            SslMode::Disable => tokio_postgres::config::SslMode::Disable,
+            SslMode::Prefer => tokio_postgres::config::SslMode::Prefer,
+            SslMode::Require | SslMode::VerifyCa | SslMode::VerifyFull => {
+                tokio_postgres::config::SslMode::Require
+            }
         }
     }
 }
 
+///
+/// Splits `host[:port]`, where an IPv6 host with a port is bracketed
+///
+fn parse_host_port(value: &str, default_port: u16) -> Option<(String, u16)> {
+    if value.starts_with('/') {
+        return Some((value.to_string(), default_port));
+    }
+
+    if let Some(bracketed) = value.strip_prefix('[') {
+        let (host, rest) = bracketed.split_once(']')?;
+        let port = match rest.strip_prefix(':') {
+            Some(port) => port.parse().ok()?,
+            None if rest.is_empty() => default_port,
+            None => return None,
+        };
+        return Some((host.to_string(), port));
+    }
+
+    match value.split_once(':') {
+        Some((host, port)) if !host.is_empty() => Some((host.to_string(), port.parse().ok()?)),
+        Some(_) => None,
+        None => Some((value.to_string(), default_port)),
+    }
+}
+
+///
+/// The session a database host must accept, with the semantics of libpq `target_session_attrs`
+///
+///   - `any` accepts any host that can be connected
+///   - `read-write` accepts a host that is not in recovery, skipping standbys
+///
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum TargetSessionAttrs {
+    #[default]
+    #[serde(alias = "Any", alias = "ANY")]
+    Any,
+    #[serde(alias = "ReadWrite", alias = "READ-WRITE", alias = "read_write")]
+    ReadWrite,
+}
+
+impl TargetSessionAttrs {
+    /// Returns true if a host in the given recovery state accepts the session
+    pub fn accepts(&self, in_recovery: bool) -> bool {
+        match self {
+            TargetSessionAttrs::Any => true,
+            TargetSessionAttrs::ReadWrite => !in_recovery,
+        }
+    }
+}
+
+impl Display for TargetSessionAttrs {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            TargetSessionAttrs::Any => "any",
+            TargetSessionAttrs::ReadWrite => "read-write",
+        };
+        write!(f, "{s}")
+    }
+}
+
+///
+/// How read-only statements are spread across healthy replicas
+///
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum ReplicaBalancing {
+    #[default]
+    #[serde(alias = "RoundRobin", alias = "ROUND-ROBIN", alias = "round_robin")]
+    RoundRobin,
+    #[serde(
+        alias = "LeastConnections",
+        alias = "LEAST-CONNECTIONS",
+        alias = "least_connections"
+    )]
+    LeastConnections,
+}
+
 ///
 /// Password is NEVER EVER displayed
 ///
@@ -127,6 +461,114 @@ impl Display for DatabaseConfig {
 mod tests {
     use super::*;
 
+    #[test]
+    fn ssl_mode_defaults_from_tls_verification() {
+        let mut config = DatabaseConfig::for_testing();
+        assert_eq!(config.ssl_mode(), SslMode::Prefer);
+
+        config.with_tls_verification = true;
+        assert_eq!(config.ssl_mode(), SslMode::VerifyFull);
+
+        config.ssl_mode = Some(SslMode::Require);
+        assert_eq!(config.ssl_mode(), SslMode::Require);
+    }
+
+    #[test]
+    fn replica_configs() {
+        let mut config = DatabaseConfig::for_testing();
+        config.replicas = vec![
+            "replica-1".to_string(),
+            "replica-2:5433".to_string(),
+            "[::1]:5434".to_string(),
+            "/var/run/postgresql".to_string(),
+        ];
+
+        let replicas = config.replica_configs().unwrap();
+        let hosts = replicas
+            .iter()
+            .map(|replica| (replica.host.as_str(), replica.port))
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            hosts,
+            vec![
+                ("replica-1", 5432),
+                ("replica-2", 5433),
+                ("::1", 5434),
+                ("/var/run/postgresql", 5432)
+            ]
+        );
+        assert_eq!(replicas[0].name, config.name);
+
+        config.replicas = vec!["replica-1:port".to_string()];
+        assert!(config.replica_configs().is_err());
+    }
+
+    #[test]
+    fn host_configs() {
+        let mut config = DatabaseConfig::for_testing();
+        config.port = 5433;
+
+        let hosts = config.host_configs().unwrap();
+        assert_eq!(hosts.len(), 1);
+        assert_eq!((hosts[0].host.as_str(), hosts[0].port), ("127.0.0.1", 5433));
+        assert!(!config.selects_host());
+
+        config.hosts = vec!["primary".to_string(), "standby:5434".to_string()];
+        let hosts = config
+            .host_configs()
+            .unwrap()
+            .iter()
+            .map(|host| (host.host.clone(), host.port, host.hosts.len()))
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            hosts,
+            vec![
+                ("primary".to_string(), 5433, 0),
+                ("standby".to_string(), 5434, 0)
+            ]
+        );
+        assert!(config.selects_host());
+
+        config.hosts = vec!["primary:".to_string()];
+        assert!(config.host_configs().is_err());
+    }
+
+    #[test]
+    fn target_session_attrs_accepts() {
+        assert!(TargetSessionAttrs::Any.accepts(false));
+        assert!(TargetSessionAttrs::Any.accepts(true));
+        assert!(TargetSessionAttrs::ReadWrite.accepts(false));
+        assert!(!TargetSessionAttrs::ReadWrite.accepts(true));
+    }
+
+    #[test]
+    fn connection_config_has_connect_timeout() {
+        let config = DatabaseConfig::for_testing();
+        assert_eq!(
+            config.to_connection_config().get_connect_timeout(),
+            Some(&Duration::from_secs(10))
+        );
+    }
+
+    #[test]
+    fn host_directory_is_unix_socket() {
+        let mut config = DatabaseConfig::for_testing();
+        assert_eq!(config.unix_socket_path(), None);
+        assert_eq!(config.to_socket_address(), "127.0.0.1:5432");
+
+        config.host = "/var/run/postgresql".to_string();
+        assert_eq!(
+            config.unix_socket_path(),
+            Some(PathBuf::from("/var/run/postgresql/.s.PGSQL.5432"))
+        );
+        assert_eq!(
+            config.to_socket_address(),
+            "/var/run/postgresql/.s.PGSQL.5432"
+        );
+    }
+
     #[test]
     fn connection_timeout_defaults_to_120_seconds() {
         let config = DatabaseConfig::for_testing();
@@ -0,0 +1,26 @@
+use serde::Deserialize;
+
+///
+/// Connection and rate limits enforced by the proxy.
+///
+/// A limit of `0` is unlimited.
+///
+#[derive(Clone, Debug, Default, Deserialize, PartialEq)]
+pub struct LimitsConfig {
+    /// Maximum number of concurrent client connections.
+    #[serde(default)]
+    pub max_connections: usize,
+
+    /// Maximum number of concurrent client connections for each database role.
+    #[serde(default)]
+    pub max_connections_per_role: usize,
+
+    /// Maximum number of statements per second for each keyset.
+    /// Each statement in a simple query, and each `Bind`, counts as one statement.
+    #[serde(default)]
+    pub statements_per_second_per_keyset: u32,
+
+    /// Maximum number of values encrypted and decrypted per second for each keyset.
+    #[serde(default)]
+    pub values_per_second_per_keyset: u32,
+}
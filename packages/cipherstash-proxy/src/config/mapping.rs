@@ -0,0 +1,87 @@
+use super::{
+    string_list_deserializer, DEFAULT_PLAINTEXT_SOURCE_MAX_ROWS, DEFAULT_PROXY_AGGREGATE_MAX_BYTES,
+    DEFAULT_PROXY_AGGREGATE_MAX_VALUES,
+};
+use serde::Deserialize;
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct MappingConfig {
+    /// Compute `sum`, `avg`, the variance family and `percentile_disc` over
+    /// encrypted numerics in the proxy.
+    ///
+    /// The database ships each group's encrypted values and the proxy decrypts
+    /// them to compute the result, so this trades memory and decryption volume
+    /// for the aggregate.
+    #[serde(default)]
+    pub proxy_aggregates: bool,
+
+    /// Maximum number of values the proxy-side aggregates of a single row may
+    /// decrypt, across all of the row's aggregate columns.
+    /// A group larger than this fails the statement rather than buffering it.
+    #[serde(default = "MappingConfig::default_proxy_aggregate_max_values")]
+    pub proxy_aggregate_max_values: usize,
+
+    /// Maximum size in bytes of the encrypted values returned for the
+    /// proxy-side aggregates of a single row, across all of the row's
+    /// aggregate columns. Checked before the values are parsed.
+    #[serde(default = "MappingConfig::default_proxy_aggregate_max_bytes")]
+    pub proxy_aggregate_max_bytes: usize,
+
+    /// Refuse statements that reference a table with encrypted columns but
+    /// cannot be type checked, instead of passing them through unchanged, and
+    /// refuse `SET CIPHERSTASH.UNSAFE_DISABLE_MAPPING`.
+    #[serde(default)]
+    pub strict: bool,
+
+    /// Fingerprints of statements that pass through unchanged in strict mode,
+    /// even though they cannot be type checked.
+    /// The fingerprint of a refused statement is included in the error.
+    #[serde(default, deserialize_with = "string_list_deserializer")]
+    pub strict_allowlist: Vec<String>,
+
+    /// Report encrypted columns as their plaintext types to queries over
+    /// `information_schema.columns`, `pg_attribute` and `pg_type`, so schema
+    /// introspection sees `text` or `integer` rather than an EQL domain.
+    #[serde(default)]
+    pub catalog_masquerade: bool,
+
+    /// Maximum number of rows Proxy reads and encrypts for an
+    /// `INSERT ... SELECT` or `UPDATE` that copies plaintext columns into
+    /// encrypted columns. `0` disables encrypting these statements.
+    #[serde(default = "MappingConfig::default_plaintext_source_max_rows")]
+    pub plaintext_source_max_rows: usize,
+}
+
+impl Default for MappingConfig {
+    fn default() -> Self {
+        MappingConfig {
+            proxy_aggregates: false,
+            proxy_aggregate_max_values: MappingConfig::default_proxy_aggregate_max_values(),
+            proxy_aggregate_max_bytes: MappingConfig::default_proxy_aggregate_max_bytes(),
+            strict: false,
+            strict_allowlist: vec![],
+            catalog_masquerade: false,
+            plaintext_source_max_rows: MappingConfig::default_plaintext_source_max_rows(),
+        }
+    }
+}
+
+impl MappingConfig {
+    pub const fn default_proxy_aggregate_max_values() -> usize {
+        DEFAULT_PROXY_AGGREGATE_MAX_VALUES
+    }
+
+    pub const fn default_proxy_aggregate_max_bytes() -> usize {
+        DEFAULT_PROXY_AGGREGATE_MAX_BYTES
+    }
+
+    pub const fn default_plaintext_source_max_rows() -> usize {
+        DEFAULT_PLAINTEXT_SOURCE_MAX_ROWS
+    }
+
+    pub fn strict_allowlist_contains(&self, fingerprint: &str) -> bool {
+        self.strict_allowlist
+            .iter()
+            .any(|allowed| allowed.trim().eq_ignore_ascii_case(fingerprint))
+    }
+}
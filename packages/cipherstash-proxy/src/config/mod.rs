@@ -1,14 +1,18 @@
 mod database;
+mod limits;
 mod log;
+mod mapping;
 mod server;
 mod tandem;
 mod tls;
 
-pub use database::DatabaseConfig;
+pub use database::{DatabaseConfig, ReplicaBalancing, SslMode, TargetSessionAttrs};
+pub use limits::LimitsConfig;
 pub use log::{LogConfig, LogFormat, LogLevel, LogOutput};
+pub use mapping::MappingConfig;
 use serde::Deserialize;
-pub use server::ServerConfig;
-pub use tandem::TandemConfig;
+pub use server::{DecryptFailurePolicy, ServerConfig};
+pub use tandem::{TandemConfig, TelemetryConfig};
 pub use tls::TlsConfig;
 use vitaminc_protected::Protected;
 
@@ -29,6 +33,28 @@ pub const DEFAULT_WORKER_THREADS: usize = 4;
 pub const DEFAULT_CIPHER_CACHE_SIZE: usize = 64;
 pub const DEFAULT_CIPHER_CACHE_TTL_SECONDS: u64 = 3600; // 1 hour
 
+pub const DEFAULT_ZEROKMS_MAX_RETRIES: u32 = 3;
+pub const DEFAULT_ZEROKMS_RETRY_BASE_DELAY_MS: u64 = 100;
+pub const DEFAULT_ZEROKMS_RETRY_MAX_DELAY_MS: u64 = 2000;
+pub const DEFAULT_ZEROKMS_CIRCUIT_BREAKER_THRESHOLD: u32 = 5;
+pub const DEFAULT_ZEROKMS_CIRCUIT_BREAKER_RESET_SECONDS: u64 = 30;
+pub const DEFAULT_ZEROKMS_COALESCE_MAX_VALUES: usize = 1000;
+
+pub const DEFAULT_PROXY_AGGREGATE_MAX_VALUES: usize = 100_000;
+// 64 MiB
+pub const DEFAULT_PROXY_AGGREGATE_MAX_BYTES: usize = 64 * 1024 * 1024;
+
+pub const DEFAULT_PLAINTEXT_SOURCE_MAX_ROWS: usize = 10_000;
+
+pub const DEFAULT_DECRYPT_BATCH_SIZE: usize = 4096;
+pub const DEFAULT_BIND_BATCH_SIZE: usize = 256;
+pub const DEFAULT_DECRYPT_CONCURRENCY: usize = 4;
+pub const DEFAULT_DECRYPT_FAILURE_SENTINEL: &str = "<undecryptable>";
+// 64 MiB
+pub const DEFAULT_DECRYPT_BUFFER_BYTES: usize = 64 * 1024 * 1024;
+// 8 MiB
+pub const DEFAULT_CLIENT_WRITE_BUFFER_BYTES: usize = 8 * 1024 * 1024;
+
 fn protected_string_deserializer<'de, D>(deserializer: D) -> Result<Protected<String>, D::Error>
 where
     D: serde::Deserializer<'de>,
@@ -36,3 +62,29 @@ where
     let s = String::deserialize(deserializer)?;
     Ok(Protected::new(s))
 }
+
+///
+/// A list from either a TOML array or a comma-separated string, as env vars cannot hold arrays
+///
+fn string_list_deserializer<'de, D>(deserializer: D) -> Result<Vec<String>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum StringList {
+        List(Vec<String>),
+        String(String),
+    }
+
+    let list = match StringList::deserialize(deserializer)? {
+        StringList::List(list) => list,
+        StringList::String(s) => s.split(',').map(str::to_string).collect(),
+    };
+
+    Ok(list
+        .into_iter()
+        .map(|s| s.trim().to_string())
+        .filter(|s| !s.is_empty())
+        .collect())
+}
@@ -1,10 +1,18 @@
 use super::{
-    DEFAULT_CIPHER_CACHE_SIZE, DEFAULT_CIPHER_CACHE_TTL_SECONDS, DEFAULT_PORT,
+    DEFAULT_BIND_BATCH_SIZE, DEFAULT_CIPHER_CACHE_SIZE, DEFAULT_CIPHER_CACHE_TTL_SECONDS,
+    DEFAULT_CLIENT_WRITE_BUFFER_BYTES, DEFAULT_DECRYPT_BATCH_SIZE, DEFAULT_DECRYPT_BUFFER_BYTES,
+    DEFAULT_DECRYPT_CONCURRENCY, DEFAULT_DECRYPT_FAILURE_SENTINEL, DEFAULT_PORT,
     DEFAULT_SHUTDOWN_TIMEOUT, DEFAULT_WORKER_THREADS,
+    DEFAULT_ZEROKMS_CIRCUIT_BREAKER_RESET_SECONDS, DEFAULT_ZEROKMS_CIRCUIT_BREAKER_THRESHOLD,
+    DEFAULT_ZEROKMS_COALESCE_MAX_VALUES, DEFAULT_ZEROKMS_MAX_RETRIES,
+    DEFAULT_ZEROKMS_RETRY_BASE_DELAY_MS, DEFAULT_ZEROKMS_RETRY_MAX_DELAY_MS,
 };
 use crate::error::{ConfigError, Error};
 use rustls_pki_types::ServerName;
 use serde::Deserialize;
+use std::fmt::Display;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
 use std::thread;
 use std::time::Duration;
 
@@ -19,6 +27,24 @@ pub struct ServerConfig {
     #[serde(default)]
     pub require_tls: bool,
 
+    /// Also listen on a Unix domain socket named `.s.PGSQL.<port>` in this directory
+    pub unix_socket_directory: Option<String>,
+
+    /// Octal access permissions of the Unix domain socket, for example `0770`
+    pub unix_socket_permissions: Option<String>,
+
+    /// Reject clients that do not present a certificate signed by the configured client CA
+    #[serde(default)]
+    pub require_client_certificate: bool,
+
+    /// Use the identity of the client certificate as the keyset name
+    #[serde(default)]
+    pub keyset_from_client_certificate: bool,
+
+    /// How often path-based TLS certificate files are checked for changes, in seconds
+    #[serde(default = "ServerConfig::default_tls_reload_interval")]
+    pub tls_reload_interval: u64,
+
     #[serde(default = "ServerConfig::default_shutdown_timeout")]
     pub shutdown_timeout: u64,
 
@@ -32,6 +58,113 @@ pub struct ServerConfig {
 
     #[serde(default = "ServerConfig::default_cipher_cache_ttl_seconds")]
     pub cipher_cache_ttl_seconds: u64,
+
+    /// How long an expired cipher is still used while it is refreshed in the background, in seconds
+    #[serde(default)]
+    pub cipher_cache_stale_seconds: u64,
+
+    /// Retries of a ZeroKMS request that failed with a transient error
+    #[serde(default = "ServerConfig::default_zerokms_max_retries")]
+    pub zerokms_max_retries: u32,
+
+    #[serde(default = "ServerConfig::default_zerokms_retry_base_delay_ms")]
+    pub zerokms_retry_base_delay_ms: u64,
+
+    #[serde(default = "ServerConfig::default_zerokms_retry_max_delay_ms")]
+    pub zerokms_retry_max_delay_ms: u64,
+
+    /// Consecutive ZeroKMS failures that open the circuit breaker, `0` disables the circuit breaker
+    #[serde(default = "ServerConfig::default_zerokms_circuit_breaker_threshold")]
+    pub zerokms_circuit_breaker_threshold: u32,
+
+    /// How long the circuit breaker fails fast before ZeroKMS is tried again, in seconds
+    #[serde(default = "ServerConfig::default_zerokms_circuit_breaker_reset_seconds")]
+    pub zerokms_circuit_breaker_reset_seconds: u64,
+
+    /// How long encrypt and decrypt requests for the same keyset are gathered into one ZeroKMS request,
+    /// in milliseconds. `0` disables coalescing
+    #[serde(default)]
+    pub zerokms_coalesce_window_ms: u64,
+
+    /// Maximum number of values in a coalesced ZeroKMS request.
+    /// Larger requests are sent on their own
+    #[serde(default = "ServerConfig::default_zerokms_coalesce_max_values")]
+    pub zerokms_coalesce_max_values: usize,
+
+    /// Maximum number of pipelined Bind messages whose params are encrypted in a single request.
+    /// `1` encrypts the params of each Bind on its own
+    #[serde(default = "ServerConfig::default_bind_batch_size")]
+    pub bind_batch_size: usize,
+
+    /// Maximum number of result rows decrypted in a single ZeroKMS request
+    #[serde(default = "ServerConfig::default_decrypt_batch_size")]
+    pub decrypt_batch_size: usize,
+
+    /// Maximum number of decrypt requests in flight for each connection
+    #[serde(default = "ServerConfig::default_decrypt_concurrency")]
+    pub decrypt_concurrency: usize,
+
+    /// Maximum bytes of encrypted result rows buffered and in flight for each connection.
+    /// Rows are not read from the database while the budget is used.
+    #[serde(default = "ServerConfig::default_decrypt_buffer_bytes")]
+    pub decrypt_buffer_bytes: usize,
+
+    /// Maximum bytes of result messages sent and not yet written to the client for each connection.
+    /// Rows are not read from the database while the budget is used.
+    #[serde(default = "ServerConfig::default_client_write_buffer_bytes")]
+    pub client_write_buffer_bytes: usize,
+
+    /// How a value that cannot be decrypted is returned.
+    /// Can be changed for a session with `SET CIPHERSTASH.DECRYPT_FAILURE`
+    #[serde(default)]
+    pub decrypt_failure_policy: DecryptFailurePolicy,
+
+    /// The value returned in place of a text value that cannot be decrypted,
+    /// with the `sentinel` policy
+    #[serde(default = "ServerConfig::default_decrypt_failure_sentinel")]
+    pub decrypt_failure_sentinel: String,
+}
+
+///
+/// How a value that cannot be decrypted is returned, such as a value encrypted with another keyset
+///
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum DecryptFailurePolicy {
+    /// The statement fails
+    #[default]
+    #[serde(alias = "Fail", alias = "FAIL")]
+    Fail,
+    /// The value is returned as NULL, with a warning
+    #[serde(alias = "Null", alias = "NULL")]
+    Null,
+    /// Text values are returned as `decrypt_failure_sentinel`, and other values as NULL, with a warning
+    #[serde(alias = "Sentinel", alias = "SENTINEL")]
+    Sentinel,
+}
+
+impl FromStr for DecryptFailurePolicy {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "fail" => Ok(DecryptFailurePolicy::Fail),
+            "null" => Ok(DecryptFailurePolicy::Null),
+            "sentinel" => Ok(DecryptFailurePolicy::Sentinel),
+            _ => Err(()),
+        }
+    }
+}
+
+impl Display for DecryptFailurePolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let policy = match self {
+            DecryptFailurePolicy::Fail => "fail",
+            DecryptFailurePolicy::Null => "null",
+            DecryptFailurePolicy::Sentinel => "sentinel",
+        };
+        write!(f, "{policy}")
+    }
 }
 
 impl Default for ServerConfig {
@@ -40,11 +173,33 @@ impl Default for ServerConfig {
             host: ServerConfig::default_host(),
             port: ServerConfig::default_port(),
             require_tls: false,
+            unix_socket_directory: None,
+            unix_socket_permissions: None,
+            require_client_certificate: false,
+            keyset_from_client_certificate: false,
+            tls_reload_interval: ServerConfig::default_tls_reload_interval(),
             shutdown_timeout: ServerConfig::default_shutdown_timeout(),
             worker_threads: ServerConfig::default_worker_threads(),
             thread_stack_size: None,
             cipher_cache_size: ServerConfig::default_cipher_cache_size(),
             cipher_cache_ttl_seconds: ServerConfig::default_cipher_cache_ttl_seconds(),
+            cipher_cache_stale_seconds: 0,
+            zerokms_max_retries: ServerConfig::default_zerokms_max_retries(),
+            zerokms_retry_base_delay_ms: ServerConfig::default_zerokms_retry_base_delay_ms(),
+            zerokms_retry_max_delay_ms: ServerConfig::default_zerokms_retry_max_delay_ms(),
+            zerokms_circuit_breaker_threshold:
+                ServerConfig::default_zerokms_circuit_breaker_threshold(),
+            zerokms_circuit_breaker_reset_seconds:
+                ServerConfig::default_zerokms_circuit_breaker_reset_seconds(),
+            zerokms_coalesce_window_ms: 0,
+            zerokms_coalesce_max_values: ServerConfig::default_zerokms_coalesce_max_values(),
+            bind_batch_size: ServerConfig::default_bind_batch_size(),
+            decrypt_batch_size: ServerConfig::default_decrypt_batch_size(),
+            decrypt_concurrency: ServerConfig::default_decrypt_concurrency(),
+            decrypt_buffer_bytes: ServerConfig::default_decrypt_buffer_bytes(),
+            client_write_buffer_bytes: ServerConfig::default_client_write_buffer_bytes(),
+            decrypt_failure_policy: DecryptFailurePolicy::default(),
+            decrypt_failure_sentinel: ServerConfig::default_decrypt_failure_sentinel(),
         }
     }
 }
@@ -76,6 +231,10 @@ impl ServerConfig {
         }
     }
 
+    pub const fn default_tls_reload_interval() -> u64 {
+        60
+    }
+
     pub const fn default_cipher_cache_size() -> usize {
         DEFAULT_CIPHER_CACHE_SIZE
     }
@@ -84,6 +243,54 @@ impl ServerConfig {
         DEFAULT_CIPHER_CACHE_TTL_SECONDS
     }
 
+    pub const fn default_zerokms_max_retries() -> u32 {
+        DEFAULT_ZEROKMS_MAX_RETRIES
+    }
+
+    pub const fn default_zerokms_retry_base_delay_ms() -> u64 {
+        DEFAULT_ZEROKMS_RETRY_BASE_DELAY_MS
+    }
+
+    pub const fn default_zerokms_retry_max_delay_ms() -> u64 {
+        DEFAULT_ZEROKMS_RETRY_MAX_DELAY_MS
+    }
+
+    pub const fn default_zerokms_circuit_breaker_threshold() -> u32 {
+        DEFAULT_ZEROKMS_CIRCUIT_BREAKER_THRESHOLD
+    }
+
+    pub const fn default_zerokms_circuit_breaker_reset_seconds() -> u64 {
+        DEFAULT_ZEROKMS_CIRCUIT_BREAKER_RESET_SECONDS
+    }
+
+    pub const fn default_zerokms_coalesce_max_values() -> usize {
+        DEFAULT_ZEROKMS_COALESCE_MAX_VALUES
+    }
+
+    pub const fn default_bind_batch_size() -> usize {
+        DEFAULT_BIND_BATCH_SIZE
+    }
+
+    pub const fn default_decrypt_batch_size() -> usize {
+        DEFAULT_DECRYPT_BATCH_SIZE
+    }
+
+    pub const fn default_decrypt_concurrency() -> usize {
+        DEFAULT_DECRYPT_CONCURRENCY
+    }
+
+    pub const fn default_decrypt_buffer_bytes() -> usize {
+        DEFAULT_DECRYPT_BUFFER_BYTES
+    }
+
+    pub const fn default_client_write_buffer_bytes() -> usize {
+        DEFAULT_CLIENT_WRITE_BUFFER_BYTES
+    }
+
+    pub fn default_decrypt_failure_sentinel() -> String {
+        DEFAULT_DECRYPT_FAILURE_SENTINEL.to_string()
+    }
+
     pub fn server_name(&self) -> Result<ServerName<'_>, Error> {
         let name = ServerName::try_from(self.host.as_str()).map_err(|_| {
             ConfigError::InvalidServerName {
@@ -97,7 +304,70 @@ impl ServerConfig {
         format!("{}:{}", self.host, self.port)
     }
 
+    ///
+    /// The path of the Unix domain socket, using the `.s.PGSQL.<port>` naming expected by libpq clients
+    ///
+    pub fn unix_socket_path(&self) -> Option<PathBuf> {
+        self.unix_socket_directory
+            .as_ref()
+            .map(|dir| unix_socket_path(Path::new(dir), self.port))
+    }
+
+    pub fn unix_socket_permissions(&self) -> Result<Option<u32>, ConfigError> {
+        self.unix_socket_permissions
+            .as_ref()
+            .map(|value| {
+                let digits = value.trim_start_matches("0o");
+                u32::from_str_radix(digits, 8)
+                    .ok()
+                    .filter(|mode| *mode <= 0o777)
+                    .ok_or_else(|| ConfigError::InvalidParameter {
+                        name: "server.unix_socket_permissions".to_string(),
+                        value: value.to_owned(),
+                    })
+            })
+            .transpose()
+    }
+
     pub fn shutdown_timeout(&self) -> Duration {
         Duration::from_millis(self.shutdown_timeout)
     }
+
+    pub fn tls_reload_interval(&self) -> Duration {
+        Duration::from_secs(self.tls_reload_interval)
+    }
+}
+
+///
+/// The socket path for a directory and port, as used by Postgres and libpq
+///
+pub fn unix_socket_path(directory: &Path, port: u16) -> PathBuf {
+    directory.join(format!(".s.PGSQL.{port}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unix_socket_config() {
+        let mut config = ServerConfig::default();
+        assert_eq!(config.unix_socket_path(), None);
+        assert_eq!(config.unix_socket_permissions().unwrap(), None);
+
+        config.unix_socket_directory = Some("/tmp".to_string());
+        assert_eq!(
+            config.unix_socket_path(),
+            Some(PathBuf::from(format!("/tmp/.s.PGSQL.{DEFAULT_PORT}")))
+        );
+
+        config.unix_socket_permissions = Some("0770".to_string());
+        assert_eq!(config.unix_socket_permissions().unwrap(), Some(0o770));
+
+        config.unix_socket_permissions = Some("0o660".to_string());
+        assert_eq!(config.unix_socket_permissions().unwrap(), Some(0o660));
+
+        config.unix_socket_permissions = Some("0999".to_string());
+        assert!(config.unix_socket_permissions().is_err());
+    }
 }
@@ -1,7 +1,7 @@
 use super::tls::TlsConfig;
 use super::{
-    DatabaseConfig, LogConfig, LogLevel, ServerConfig, CS_PREFIX, DEBUG_THREAD_STACK_SIZE,
-    DEFAULT_CONFIG_FILE_PATH, DEFAULT_THREAD_STACK_SIZE,
+    DatabaseConfig, LimitsConfig, LogConfig, LogLevel, MappingConfig, ServerConfig, CS_PREFIX,
+    DEBUG_THREAD_STACK_SIZE, DEFAULT_CONFIG_FILE_PATH, DEFAULT_THREAD_STACK_SIZE,
 };
 use crate::config::LogFormat;
 use crate::error::{ConfigError, Error};
@@ -32,6 +32,12 @@ pub struct TandemConfig {
     pub log: LogConfig,
     #[serde(default)]
     pub prometheus: PrometheusConfig,
+    #[serde(default)]
+    pub telemetry: TelemetryConfig,
+    #[serde(default)]
+    pub mapping: MappingConfig,
+    #[serde(default)]
+    pub limits: LimitsConfig,
     pub development: Option<DevelopmentConfig>,
 }
 
@@ -57,6 +63,19 @@ pub struct PrometheusConfig {
     pub port: u16,
 }
 
+/// OpenTelemetry trace export over OTLP/HTTP
+#[derive(Clone, Debug, Deserialize)]
+pub struct TelemetryConfig {
+    #[serde(default)]
+    pub enabled: bool,
+
+    #[serde(default = "TelemetryConfig::default_endpoint")]
+    pub endpoint: String,
+
+    #[serde(default = "TelemetryConfig::default_service_name")]
+    pub service_name: String,
+}
+
 #[derive(Clone, Debug, Deserialize)]
 pub struct DevelopmentConfig {
     #[serde(default)]
@@ -228,6 +247,10 @@ impl TandemConfig {
 
         config.encrypt.build_client_key()?;
 
+        if config.mapping.strict && config.mapping_disabled() {
+            return Err(ConfigError::StrictMappingDisabled.into());
+        }
+
         Ok(config)
     }
 
@@ -252,6 +275,11 @@ impl TandemConfig {
         }
     }
 
+    /// Returns true if aggregates over encrypted numerics are computed in the proxy
+    pub fn proxy_aggregates_enabled(&self) -> bool {
+        self.mapping.proxy_aggregates
+    }
+
     pub fn use_structured_logging(&self) -> bool {
         matches!(self.log.format, LogFormat::Structured)
     }
@@ -263,6 +291,10 @@ impl TandemConfig {
         self.prometheus.enabled
     }
 
+    pub fn telemetry_enabled(&self) -> bool {
+        self.telemetry.enabled
+    }
+
     ///
     /// Thread stack size
     /// Not defined using a default, as we depend on the log level to increase the size for debugging
@@ -329,6 +361,9 @@ impl TandemConfig {
             tls: None,
             log: LogConfig::default(),
             prometheus: PrometheusConfig::default(),
+            telemetry: TelemetryConfig::default(),
+            mapping: MappingConfig::default(),
+            limits: LimitsConfig::default(),
             development: None,
         }
     }
@@ -347,6 +382,26 @@ impl PrometheusConfig {
     }
 }
 
+impl TelemetryConfig {
+    pub fn default_endpoint() -> String {
+        "http://localhost:4318/v1/traces".to_string()
+    }
+
+    pub fn default_service_name() -> String {
+        "cipherstash-proxy".to_string()
+    }
+}
+
+impl Default for TelemetryConfig {
+    fn default() -> Self {
+        TelemetryConfig {
+            enabled: false,
+            endpoint: TelemetryConfig::default_endpoint(),
+            service_name: TelemetryConfig::default_service_name(),
+        }
+    }
+}
+
 impl Default for PrometheusConfig {
     fn default() -> Self {
         PrometheusConfig {
@@ -408,8 +463,8 @@ fn extract_invalid_field(input: &str) -> (String, String) {
 mod tests {
     use crate::test_helpers::with_no_cs_vars;
     use crate::{
-        config::{tandem::extract_missing_field_and_key, TandemConfig},
-        error::Error,
+        config::{tandem::extract_missing_field_and_key, TandemConfig, TargetSessionAttrs},
+        error::{ConfigError, Error},
     };
     use cipherstash_client::config::vars::{
         CS_CLIENT_ACCESS_KEY, CS_CLIENT_ID, CS_CLIENT_KEY, CS_DEFAULT_KEYSET_ID,
@@ -670,6 +725,104 @@ mod tests {
         })
     }
 
+    #[test]
+    fn database_hosts_from_env() {
+        let env = merge_env_vars(vec![
+            (
+                "CS_WORKSPACE_CRN",
+                Some("crn:us-west-1.aws:E4UMRN47WJNSMAKR"),
+            ),
+            ("CS_DATABASE__HOSTS", Some("primary, standby:5433")),
+            ("CS_DATABASE__TARGET_SESSION_ATTRS", Some("read-write")),
+        ]);
+
+        with_no_cs_vars(|| {
+            temp_env::with_vars(env, || {
+                let config = TandemConfig::build_path("tests/config/unknown.toml").unwrap();
+
+                assert_eq!(config.database.hosts, vec!["primary", "standby:5433"]);
+                assert_eq!(
+                    config.database.target_session_attrs,
+                    TargetSessionAttrs::ReadWrite
+                );
+            })
+        });
+    }
+
+    #[test]
+    fn strict_mapping_from_env() {
+        let env = merge_env_vars(vec![
+            (
+                "CS_WORKSPACE_CRN",
+                Some("crn:us-west-1.aws:E4UMRN47WJNSMAKR"),
+            ),
+            ("CS_MAPPING__STRICT", Some("true")),
+            (
+                "CS_MAPPING__STRICT_ALLOWLIST",
+                Some("0123456789abcdef, FEDCBA9876543210"),
+            ),
+        ]);
+
+        with_no_cs_vars(|| {
+            temp_env::with_vars(env, || {
+                let config = TandemConfig::build_path("tests/config/unknown.toml").unwrap();
+
+                assert!(config.mapping.strict);
+                assert!(config.mapping.strict_allowlist_contains("0123456789abcdef"));
+                assert!(config.mapping.strict_allowlist_contains("fedcba9876543210"));
+                assert!(!config.mapping.strict_allowlist_contains("0000000000000000"));
+            })
+        });
+    }
+
+    #[test]
+    fn strict_mapping_cannot_be_disabled() {
+        let env = merge_env_vars(vec![
+            (
+                "CS_WORKSPACE_CRN",
+                Some("crn:us-west-1.aws:E4UMRN47WJNSMAKR"),
+            ),
+            ("CS_MAPPING__STRICT", Some("true")),
+            ("CS_DEVELOPMENT__DISABLE_MAPPING", Some("true")),
+        ]);
+
+        with_no_cs_vars(|| {
+            temp_env::with_vars(env, || {
+                let config = TandemConfig::build_path("tests/config/unknown.toml");
+
+                assert!(matches!(
+                    config,
+                    Err(Error::Config(ConfigError::StrictMappingDisabled))
+                ));
+            })
+        });
+    }
+
+    #[test]
+    fn limits_from_env() {
+        let env = merge_env_vars(vec![
+            (
+                "CS_WORKSPACE_CRN",
+                Some("crn:us-west-1.aws:E4UMRN47WJNSMAKR"),
+            ),
+            ("CS_LIMITS__MAX_CONNECTIONS", Some("100")),
+            ("CS_LIMITS__MAX_CONNECTIONS_PER_ROLE", Some("20")),
+            ("CS_LIMITS__STATEMENTS_PER_SECOND_PER_KEYSET", Some("500")),
+        ]);
+
+        with_no_cs_vars(|| {
+            temp_env::with_vars(env, || {
+                let config = TandemConfig::build_path("tests/config/unknown.toml").unwrap();
+
+                assert_eq!(config.limits.max_connections, 100);
+                assert_eq!(config.limits.max_connections_per_role, 20);
+                assert_eq!(config.limits.statements_per_second_per_keyset, 500);
+                // Unlimited
+                assert_eq!(config.limits.values_per_second_per_keyset, 0);
+            })
+        });
+    }
+
     #[test]
     fn no_crn_provided() {
         let env = merge_env_vars(vec![("CS_WORKSPACE_CRN", None)]);
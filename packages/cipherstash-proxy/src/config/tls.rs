@@ -10,20 +10,70 @@ use crate::{error::TlsConfigError, log::CONFIG};
 /// Server TLS Configuration
 /// This is listener/inbound connection config
 ///
+/// The optional client CA certificate enables client certificate verification.
+///
 #[derive(Clone, Debug, Deserialize, PartialEq)]
 #[serde(untagged)]
 pub enum TlsConfig {
     Pem {
         certificate_pem: String,
         private_key_pem: String,
+        #[serde(default)]
+        client_ca_certificate_pem: Option<String>,
     },
     Path {
         certificate_path: String,
         private_key_path: String,
+        #[serde(default)]
+        client_ca_certificate_path: Option<String>,
     },
 }
 
 impl TlsConfig {
+    /// Returns true if client certificates are verified
+    pub fn verifies_client_certificates(&self) -> bool {
+        match self {
+            TlsConfig::Pem {
+                client_ca_certificate_pem,
+                ..
+            } => client_ca_certificate_pem.is_some(),
+            TlsConfig::Path {
+                client_ca_certificate_path,
+                ..
+            } => client_ca_certificate_path.is_some(),
+        }
+    }
+
+    pub fn check_client_ca_cert(&self) -> Result<(), TlsConfigError> {
+        match self {
+            TlsConfig::Pem {
+                client_ca_certificate_pem: Some(certificate),
+                ..
+            } => {
+                debug!(target: CONFIG, msg = "TLS client CA certificate from PEM string");
+                let certs = CertificateDer::pem_slice_iter(certificate.as_bytes())
+                    .collect::<Result<Vec<_>, _>>()
+                    .unwrap_or(Vec::new());
+                if certs.is_empty() {
+                    return Err(TlsConfigError::InvalidClientCaCertificate);
+                }
+            }
+            TlsConfig::Path {
+                client_ca_certificate_path: Some(client_ca_certificate_path),
+                ..
+            } => {
+                debug!(target: CONFIG, msg = "TLS client CA certificate from path", client_ca_certificate_path);
+                if !PathBuf::from(client_ca_certificate_path).exists() {
+                    return Err(TlsConfigError::MissingClientCaCertificate {
+                        path: client_ca_certificate_path.to_owned(),
+                    });
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
     pub fn check_cert(&self) -> Result<(), TlsConfigError> {
         match self {
             TlsConfig::Pem {
@@ -86,6 +136,7 @@ mod tests {
         TlsConfig::Path {
             certificate_path: "../../tests/tls/server.cert".to_string(),
             private_key_path: "../../tests/tls/server.key".to_string(),
+            client_ca_certificate_path: None,
         }
     }
 
@@ -93,6 +144,7 @@ mod tests {
         TlsConfig::Path {
             certificate_path: "/path/to/non-existent/file".to_string(),
             private_key_path: "/path/to/non-existent/file".to_string(),
+            client_ca_certificate_path: Some("/path/to/non-existent/file".to_string()),
         }
     }
 
@@ -151,6 +203,7 @@ B+qwsnNEiDoJhgYj+cQ=
 -----END PRIVATE KEY-----
 "
             .to_string(),
+            client_ca_certificate_pem: None,
         }
     }
 
@@ -158,6 +211,7 @@ B+qwsnNEiDoJhgYj+cQ=
         TlsConfig::Pem {
             certificate_pem: "-----INVALID PEM-----".to_string(),
             private_key_pem: "-----INVALID PEM-----".to_string(),
+            client_ca_certificate_pem: Some("-----INVALID PEM-----".to_string()),
         }
     }
 
@@ -179,6 +233,20 @@ B+qwsnNEiDoJhgYj+cQ=
         assert!(test_config_with_invalid_path().check_private_key().is_err());
     }
 
+    #[test]
+    fn test_tls_client_ca_cert_exists() {
+        assert!(test_config_with_path().check_client_ca_cert().is_ok());
+        assert!(test_config_with_invalid_path()
+            .check_client_ca_cert()
+            .is_err());
+        assert!(test_config_with_invalid_pem()
+            .check_client_ca_cert()
+            .is_err());
+
+        assert!(!test_config_with_path().verifies_client_certificates());
+        assert!(test_config_with_invalid_path().verifies_client_certificates());
+    }
+
     #[test]
     fn test_tls_private_key_exists_with_pem() {
         assert!(test_config_with_pem().check_private_key().is_ok());
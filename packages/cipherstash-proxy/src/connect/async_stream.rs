@@ -1,5 +1,5 @@
-use super::{configure, connect_with_retry};
-use crate::{error::Error, log::AUTHENTICATION};
+use super::{configure, connect_unix_with_retry, connect_with_retry};
+use crate::{error::Error, log::AUTHENTICATION, tls::ClientIdentity};
 use aws_lc_rs::digest;
 use core::str;
 use oid_registry::{
@@ -10,12 +10,13 @@ use oid_registry::{
 use postgres_protocol::authentication::sasl::ChannelBinding;
 
 use std::{
+    path::Path,
     pin::Pin,
     task::{Context, Poll},
 };
 use tokio::{
     io::{split, AsyncRead, AsyncWrite, ReadBuf},
-    net::{TcpListener, TcpStream},
+    net::{TcpListener, TcpStream, UnixListener, UnixStream},
 };
 use tokio_rustls::TlsStream;
 use tracing::debug;
@@ -25,6 +26,7 @@ use x509_parser::prelude::{FromDer, X509Certificate};
 pub enum AsyncStream {
     Tcp(TcpStream),
     Tls(Box<TlsStream<TcpStream>>),
+    Unix(UnixStream),
 }
 
 impl AsyncStream {
@@ -34,12 +36,22 @@ impl AsyncStream {
         Ok(AsyncStream::Tcp(stream))
     }
 
+    pub async fn accept_unix(listener: &UnixListener) -> Result<AsyncStream, Error> {
+        let (stream, _) = listener.accept().await?;
+        Ok(AsyncStream::Unix(stream))
+    }
+
     pub async fn connect(addr: &str) -> Result<AsyncStream, Error> {
         let stream = connect_with_retry(addr).await?;
         configure(&stream);
         Ok(AsyncStream::Tcp(stream))
     }
 
+    pub async fn connect_unix(path: &Path) -> Result<AsyncStream, Error> {
+        let stream = connect_unix_with_retry(path).await?;
+        Ok(AsyncStream::Unix(stream))
+    }
+
     pub fn split(
         self,
     ) -> (
@@ -54,12 +66,34 @@ impl AsyncStream {
     }
 
     pub fn is_tcp(&self) -> bool {
-        !self.is_tls()
+        matches!(self, AsyncStream::Tcp(_))
+    }
+
+    pub fn is_unix(&self) -> bool {
+        matches!(self, AsyncStream::Unix(_))
+    }
+
+    ///
+    /// The identity of the peer certificate, if the peer presented one
+    ///
+    /// The certificate has been verified by the TLS handshake before it is available here.
+    ///
+    pub fn client_identity(&self) -> Option<ClientIdentity> {
+        match self {
+            AsyncStream::Tcp(_) | AsyncStream::Unix(_) => None,
+            AsyncStream::Tls(stream) => {
+                let (_, session) = stream.get_ref();
+                session
+                    .peer_certificates()
+                    .and_then(|certs| certs.first())
+                    .and_then(ClientIdentity::from_certificate)
+            }
+        }
     }
 
     pub fn channel_binding(&self) -> ChannelBinding {
         match self {
-            AsyncStream::Tcp(_) => ChannelBinding::unsupported(),
+            AsyncStream::Tcp(_) | AsyncStream::Unix(_) => ChannelBinding::unsupported(),
             AsyncStream::Tls(stream) => {
                 let (_, session) = stream.get_ref();
                 let certs = session.peer_certificates();
@@ -116,6 +150,7 @@ impl AsyncRead for AsyncStream {
         match *self {
             AsyncStream::Tcp(ref mut stream) => Pin::new(stream).poll_read(cx, buf),
             AsyncStream::Tls(ref mut stream) => Pin::new(stream).poll_read(cx, buf),
+            AsyncStream::Unix(ref mut stream) => Pin::new(stream).poll_read(cx, buf),
         }
     }
 }
@@ -129,6 +164,7 @@ impl AsyncWrite for AsyncStream {
         match *self {
             AsyncStream::Tcp(ref mut stream) => Pin::new(stream).poll_write(cx, buf),
             AsyncStream::Tls(ref mut stream) => Pin::new(stream).poll_write(cx, buf),
+            AsyncStream::Unix(ref mut stream) => Pin::new(stream).poll_write(cx, buf),
         }
     }
 
@@ -136,6 +172,7 @@ impl AsyncWrite for AsyncStream {
         match *self {
             AsyncStream::Tcp(ref mut stream) => Pin::new(stream).poll_flush(cx),
             AsyncStream::Tls(ref mut stream) => Pin::new(stream).poll_flush(cx),
+            AsyncStream::Unix(ref mut stream) => Pin::new(stream).poll_flush(cx),
         }
     }
 
@@ -143,6 +180,7 @@ impl AsyncWrite for AsyncStream {
         match *self {
             AsyncStream::Tcp(ref mut stream) => Pin::new(stream).poll_shutdown(cx),
             AsyncStream::Tls(ref mut stream) => Pin::new(stream).poll_shutdown(cx),
+            AsyncStream::Unix(ref mut stream) => Pin::new(stream).poll_shutdown(cx),
         }
     }
 }
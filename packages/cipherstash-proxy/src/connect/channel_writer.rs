@@ -1,14 +1,105 @@
 use bytes::BytesMut;
+use std::sync::Arc;
 use tokio::{
     io::{AsyncWrite, AsyncWriteExt},
-    sync::mpsc::{self, UnboundedReceiver, UnboundedSender},
+    sync::{
+        mpsc::{self, error::SendError, UnboundedReceiver, UnboundedSender},
+        OwnedSemaphorePermit, Semaphore,
+    },
 };
 use tracing::{debug, error};
 
 use crate::log::PROTOCOL;
 
-pub type Receiver = UnboundedReceiver<BytesMut>;
-pub type Sender = UnboundedSender<BytesMut>;
+pub type Receiver = UnboundedReceiver<QueuedMessage>;
+
+///
+/// A message waiting to be written to the client
+///
+/// A message sent with [`Sender::send_bytes`] holds its share of the byte budget until it has been written.
+///
+#[derive(Debug)]
+pub struct QueuedMessage {
+    bytes: BytesMut,
+    _permit: Option<OwnedSemaphorePermit>,
+}
+
+impl QueuedMessage {
+    pub fn bytes(&self) -> &BytesMut {
+        &self.bytes
+    }
+}
+
+///
+/// Creates the channel used by a [`ChannelWriter`]
+///
+/// `budget_bytes` is the most bytes sent with [`Sender::send_bytes`] that are not yet written to the client.
+///
+pub fn channel(budget_bytes: usize) -> (Sender, Receiver) {
+    let (sender, receiver) = mpsc::unbounded_channel();
+
+    // Semaphore permits are counted in u32
+    let budget_bytes = budget_bytes.clamp(1, Semaphore::MAX_PERMITS.min(u32::MAX as usize));
+
+    let sender = Sender {
+        sender,
+        budget: Arc::new(Semaphore::new(budget_bytes)),
+        budget_bytes,
+    };
+
+    (sender, receiver)
+}
+
+///
+/// Sends messages to the [`ChannelWriter`] of a client connection
+///
+/// The Frontend and Backend send concurrently, and messages are written in the order they are sent.
+/// Messages sent with [`Sender::send_bytes`] share a budget of bytes that have been sent and not yet written,
+/// so a client that stops reading stops the Backend reading from the database, instead of queueing its results
+/// in memory. Small protocol messages, such as an `ErrorResponse`, are sent with [`Sender::send`] and do not wait.
+///
+#[derive(Clone, Debug)]
+pub struct Sender {
+    sender: UnboundedSender<QueuedMessage>,
+    budget: Arc<Semaphore>,
+    budget_bytes: usize,
+}
+
+impl Sender {
+    ///
+    /// Sends a message without waiting for the byte budget
+    ///
+    pub fn send(&self, bytes: BytesMut) -> Result<(), SendError<BytesMut>> {
+        self.sender
+            .send(QueuedMessage {
+                bytes,
+                _permit: None,
+            })
+            .map_err(|SendError(message)| SendError(message.bytes))
+    }
+
+    ///
+    /// Sends a message once it fits in the budget of bytes sent and not yet written to the client
+    ///
+    /// A message larger than the budget waits for the whole budget.
+    ///
+    pub async fn send_bytes(&self, bytes: BytesMut) -> Result<(), SendError<BytesMut>> {
+        let len = bytes.len().clamp(1, self.budget_bytes) as u32;
+
+        let permit = match self.budget.clone().acquire_many_owned(len).await {
+            Ok(permit) => permit,
+            // The budget is never closed
+            Err(_) => return Err(SendError(bytes)),
+        };
+
+        self.sender
+            .send(QueuedMessage {
+                bytes,
+                _permit: Some(permit),
+            })
+            .map_err(|SendError(message)| SendError(message.bytes))
+    }
+}
 
 #[derive(Debug)]
 pub struct ChannelWriter<W>
@@ -25,9 +116,11 @@ impl<W> ChannelWriter<W>
 where
     W: AsyncWrite + Unpin,
 {
-    pub fn new(writer: W, client_id: i32) -> Self {
-        let (sender, receiver): (UnboundedSender<BytesMut>, UnboundedReceiver<BytesMut>) =
-            mpsc::unbounded_channel();
+    ///
+    /// `budget_bytes` is the most bytes sent with [`Sender::send_bytes`] that are not yet written to the client
+    ///
+    pub fn new(writer: W, client_id: i32, budget_bytes: usize) -> Self {
+        let (sender, receiver) = channel(budget_bytes);
 
         ChannelWriter {
             writer,
@@ -48,14 +141,16 @@ where
         // but we're holding one of them ourselves!
         drop(self.sender);
 
-        while let Some(bytes) = self.receiver.recv().await {
+        // The budget held by a message is released when it is dropped, once it has been written
+        while let Some(message) = self.receiver.recv().await {
+            let bytes = &message.bytes;
             debug!(target: PROTOCOL,
                 client_id = self.client_id,
                 msg = "Writing",
                 ?bytes
             );
 
-            match self.writer.write_all(&bytes).await {
+            match self.writer.write_all(bytes).await {
                 Ok(_) => {
                     debug!(target: PROTOCOL,
                         client_id = self.client_id,
@@ -107,3 +202,75 @@ where
         self.sender.clone()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::ChannelWriter;
+    use bytes::BytesMut;
+    use std::time::Duration;
+    use tokio::io::{duplex, AsyncReadExt};
+
+    fn message(len: usize) -> BytesMut {
+        BytesMut::from(vec![b'D'; len].as_slice())
+    }
+
+    #[tokio::test]
+    async fn sending_waits_while_the_client_is_not_reading() {
+        // The client side of the connection buffers 64 bytes, and is not read until later
+        let (mut client, proxy) = duplex(64);
+
+        let writer = ChannelWriter::new(proxy, 1, 256);
+        let sender = writer.sender();
+        let writer = tokio::spawn(writer.receive());
+
+        // 64 bytes are written to the client, and 256 wait to be written
+        for _ in 0..5 {
+            sender.send_bytes(message(64)).await.unwrap();
+        }
+
+        let blocked =
+            tokio::time::timeout(Duration::from_millis(100), sender.send_bytes(message(64)));
+        assert!(
+            blocked.await.is_err(),
+            "sent more than the budget to a stalled client"
+        );
+
+        // Protocol messages are not held back
+        sender.send(message(8)).unwrap();
+
+        // Once the client reads, the budget is released as the bytes are written
+        let reader = tokio::spawn(async move {
+            let mut received = vec![];
+            client.read_to_end(&mut received).await.unwrap();
+            received.len()
+        });
+
+        tokio::time::timeout(Duration::from_secs(5), sender.send_bytes(message(64)))
+            .await
+            .expect("budget was not released as the client read")
+            .unwrap();
+
+        drop(sender);
+        writer.await.unwrap();
+        assert_eq!(reader.await.unwrap(), 5 * 64 + 8 + 64);
+    }
+
+    #[tokio::test]
+    async fn a_message_larger_than_the_budget_waits_for_all_of_it() {
+        let (mut client, proxy) = duplex(1024);
+
+        let writer = ChannelWriter::new(proxy, 1, 16);
+        let sender = writer.sender();
+        let writer = tokio::spawn(writer.receive());
+
+        sender.send_bytes(message(100)).await.unwrap();
+        sender.send_bytes(message(100)).await.unwrap();
+
+        drop(sender);
+        writer.await.unwrap();
+
+        let mut received = vec![];
+        client.read_to_end(&mut received).await.unwrap();
+        assert_eq!(received.len(), 200);
+    }
+}
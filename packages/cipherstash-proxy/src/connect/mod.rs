@@ -2,13 +2,25 @@ mod async_stream;
 mod channel_writer;
 
 pub use async_stream::AsyncStream;
-pub use channel_writer::{ChannelWriter, Sender};
+pub use channel_writer::{channel, ChannelWriter, Sender};
 
-use crate::{config::ServerConfig, error::Error, log::DEVELOPMENT, tls, DatabaseConfig};
+use crate::{
+    config::{ServerConfig, TargetSessionAttrs},
+    error::Error,
+    log::DEVELOPMENT,
+    tls, DatabaseConfig,
+};
 use socket2::TcpKeepalive;
-use std::time::Duration;
+use std::{
+    fs,
+    future::Future,
+    io,
+    os::unix::fs::{DirBuilderExt, FileTypeExt, PermissionsExt},
+    path::{Path, PathBuf},
+    time::Duration,
+};
 use tokio::{
-    net::{TcpListener, TcpStream},
+    net::{TcpListener, TcpStream, UnixListener, UnixStream},
     time::{self},
 };
 use tokio_postgres::Client;
@@ -22,15 +34,14 @@ const TCP_KEEPALIVE_RETRIES: u32 = 5;
 const MAX_RETRY_DELAY: Duration = Duration::from_secs(2);
 const MAX_RETRY_COUNT: u32 = 3;
 
+///
+/// Connects to the database
+///
+/// With `hosts` or `target_session_attrs`, connects to the first host that accepts the session.
+///
 pub async fn database(config: &DatabaseConfig) -> Result<Client, Error> {
-    let connection_config = config.to_connection_config();
-
-    let tls_config = tls::configure_client(config);
-    let tls = tokio_postgres_rustls::MakeRustlsConnect::new(tls_config);
-
-    let (client, connection) = match connection_config.connect(tls).await {
-        Ok((client, connection)) => (client, connection),
-        Err(e) => {
+    if !config.selects_host() {
+        return connect_client(config).await.inspect_err(|_| {
             error!(
                 msg = "Could not connect to database",
                 database = config.name,
@@ -39,9 +50,114 @@ pub async fn database(config: &DatabaseConfig) -> Result<Client, Error> {
                 username = config.username,
             );
             error!(msg = "Confirm that the database configuration is correct");
-            return Err(Error::Config(e.into()));
-        }
+        });
+    }
+
+    let (client, _host) = database_host(config).await?;
+    Ok(client)
+}
+
+///
+/// Selects the database host for a client connection
+///
+/// Hosts are only checked if `hosts` or `target_session_attrs` are configured, otherwise this is the configured `host`.
+///
+pub async fn select_database_host(config: &DatabaseConfig) -> Result<DatabaseConfig, Error> {
+    if !config.selects_host() {
+        return Ok(config.clone());
+    }
+
+    let (_client, host) = database_host(config).await?;
+    Ok(host)
+}
+
+///
+/// Connects to the first host, in order, that accepts the `target_session_attrs`
+///
+/// A `read-write` session is not accepted by a host in recovery, as with libpq.
+///
+async fn database_host(config: &DatabaseConfig) -> Result<(Client, DatabaseConfig), Error> {
+    let target_session_attrs = config.target_session_attrs;
+    let hosts = config.host_configs()?;
+
+    let selected = first_accepting_host(&hosts, target_session_attrs, |host| async move {
+        let client = connect_client(&host).await?;
+        let in_recovery = match target_session_attrs {
+            TargetSessionAttrs::Any => false,
+            TargetSessionAttrs::ReadWrite => in_recovery(&client).await?,
+        };
+        Ok((client, in_recovery))
+    })
+    .await;
+
+    if let Some((client, host)) = selected {
+        return Ok((client, host.clone()));
+    }
+
+    let err = Error::NoDatabaseHost {
+        target_session_attrs,
     };
+    error!(
+        msg = "Could not connect to database",
+        database = config.name,
+        hosts = ?config.hosts,
+        username = config.username,
+        error = err.to_string(),
+    );
+    error!(msg = "Confirm that the database configuration is correct");
+    Err(err)
+}
+
+///
+/// Probes each host in order, and returns the first that accepts the `target_session_attrs`
+///
+/// The probe connects to a host and reports whether it is in recovery.
+/// A host that cannot be connected or checked is skipped.
+///
+async fn first_accepting_host<'h, C, F, Fut>(
+    hosts: &'h [DatabaseConfig],
+    target_session_attrs: TargetSessionAttrs,
+    probe: F,
+) -> Option<(C, &'h DatabaseConfig)>
+where
+    F: Fn(DatabaseConfig) -> Fut,
+    Fut: Future<Output = Result<(C, bool), Error>>,
+{
+    for host in hosts {
+        match probe(host.clone()).await {
+            Ok((client, in_recovery)) if target_session_attrs.accepts(in_recovery) => {
+                debug!(target: DEVELOPMENT, msg = "Database host selected", host = host.to_socket_address());
+                return Some((client, host));
+            }
+            Ok(_) => {
+                info!(
+                    msg = "Database host is in recovery",
+                    host = host.to_socket_address(),
+                    target_session_attrs = %target_session_attrs,
+                );
+            }
+            Err(err) => {
+                warn!(
+                    msg = "Could not connect to database host",
+                    host = host.to_socket_address(),
+                    error = err.to_string()
+                );
+            }
+        }
+    }
+    None
+}
+
+async fn connect_client(config: &DatabaseConfig) -> Result<Client, Error> {
+    let connection_config = config.to_connection_config();
+
+    let tls_config = tls::configure_client(config)?;
+    let tls = tokio_postgres_rustls::MakeRustlsConnect::new(tls_config);
+
+    let (client, connection) = connection_config
+        .connect(tls)
+        .await
+        .map_err(|err| Error::Config(err.into()))?;
 
     tokio::spawn(async move {
         if let Err(err) = connection.await {
@@ -51,14 +167,173 @@ pub async fn database(config: &DatabaseConfig) -> Result<Client, Error> {
     Ok(client)
 }
 
-pub async fn bind_with_retry(server: &ServerConfig) -> TcpListener {
-    let address = &server.to_socket_address();
+async fn in_recovery(client: &Client) -> Result<bool, Error> {
+    let row = client.query_one("SELECT pg_is_in_recovery()", &[]).await?;
+    Ok(row.get(0))
+}
+
+///
+/// The listener for client connections
+///
+/// Accepts connections on TCP and, if `server.unix_socket_directory` is configured, on a Unix domain socket.
+///
+#[derive(Debug)]
+pub struct Listener {
+    address: String,
+    tcp: TcpListener,
+    unix: Option<UnixSocketListener>,
+}
+
+impl Listener {
+    pub async fn accept(&self) -> Result<AsyncStream, Error> {
+        match &self.unix {
+            Some(unix) => {
+                tokio::select! {
+                    stream = AsyncStream::accept(&self.tcp) => stream,
+                    stream = AsyncStream::accept_unix(&unix.listener) => stream,
+                }
+            }
+            None => AsyncStream::accept(&self.tcp).await,
+        }
+    }
+
+    ///
+    /// Move the listener to the address and socket of a reloaded configuration
+    ///
+    /// Addresses are bound before the current ones are released, and on error the current listener is unchanged.
+    /// An unchanged socket path keeps the existing Unix domain socket.
+    ///
+    pub async fn rebind(&mut self, server: &ServerConfig) -> Result<(), Error> {
+        let address = server.to_socket_address();
+        let tcp = match address != self.address {
+            true => Some(TcpListener::bind(&address).await?),
+            false => None,
+        };
+
+        let unix = match server.unix_socket_path() {
+            Some(path) if self.unix.as_ref().is_some_and(|unix| unix.path == path) => {
+                set_unix_socket_permissions(&path, server)?;
+                self.unix.take()
+            }
+            Some(path) => Some(UnixSocketListener::bind(path, server)?),
+            None => None,
+        };
+
+        if let Some(tcp) = tcp {
+            self.tcp = tcp;
+            self.address = address;
+        }
+        self.unix = unix;
+
+        Ok(())
+    }
+}
+
+///
+/// Removes the socket file when the listener is closed
+///
+#[derive(Debug)]
+struct UnixSocketListener {
+    listener: UnixListener,
+    path: PathBuf,
+}
+
+impl UnixSocketListener {
+    ///
+    /// A socket file left behind by a process that did not shut down cleanly is replaced.
+    /// A socket that still accepts connections belongs to a running process, and is an error.
+    ///
+    /// The socket is created with the permissions of the umask, so it is bound in a directory only this process can
+    /// enter, and moved into place once its permissions are set. Other users can never connect to it with the
+    /// permissions of the umask.
+    ///
+    fn bind(path: PathBuf, server: &ServerConfig) -> Result<Self, Error> {
+        let stale =
+            fs::symlink_metadata(&path).is_ok_and(|metadata| metadata.file_type().is_socket());
+        if stale {
+            if std::os::unix::net::UnixStream::connect(&path).is_ok() {
+                return Err(io::Error::new(
+                    io::ErrorKind::AddrInUse,
+                    format!("{} is in use", path.display()),
+                )
+                .into());
+            }
+            fs::remove_file(&path)?;
+        }
+
+        // Left behind if a process with the same id did not shut down cleanly
+        let private_dir = private_bind_dir(&path);
+        let _ = fs::remove_dir_all(&private_dir);
+        fs::DirBuilder::new().mode(0o700).create(&private_dir)?;
+
+        let bound = bind_in(&private_dir, &path, server);
+        let _ = fs::remove_dir_all(&private_dir);
+        let listener = bound?;
+
+        let unix = UnixSocketListener { listener, path };
+
+        info!(msg = "Server waiting for connections", socket = %unix.path.display());
+        Ok(unix)
+    }
+}
+
+impl Drop for UnixSocketListener {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+///
+/// The directory the socket is bound in before it is moved to `path`, next to it so that the move is a rename
+///
+fn private_bind_dir(path: &Path) -> PathBuf {
+    let name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+    path.with_file_name(format!(".{name}.{}", std::process::id()))
+}
+
+fn bind_in(private_dir: &Path, path: &Path, server: &ServerConfig) -> Result<UnixListener, Error> {
+    let private_path = private_dir.join("socket");
+
+    let listener = UnixListener::bind(&private_path)?;
+    set_unix_socket_permissions(&private_path, server)?;
+    fs::rename(&private_path, path)?;
+
+    Ok(listener)
+}
+
+fn set_unix_socket_permissions(path: &Path, server: &ServerConfig) -> Result<(), Error> {
+    if let Some(mode) = server.unix_socket_permissions()? {
+        fs::set_permissions(path, fs::Permissions::from_mode(mode))?;
+    }
+    Ok(())
+}
+
+///
+/// Bind a listener without retrying
+/// Used when the listener is moved on a configuration reload, where an error keeps the current listener
+///
+pub async fn bind(server: &ServerConfig) -> Result<Listener, Error> {
+    let address = server.to_socket_address();
+    let tcp = TcpListener::bind(&address).await?;
+    info!(msg = "Server waiting for connections", address);
+
+    let unix = server
+        .unix_socket_path()
+        .map(|path| UnixSocketListener::bind(path, server))
+        .transpose()?;
+
+    Ok(Listener { address, tcp, unix })
+}
+
+pub async fn bind_with_retry(server: &ServerConfig) -> Listener {
     let mut retry_count = 0;
 
     loop {
-        match TcpListener::bind(address).await {
+        match bind(server).await {
             Ok(listener) => {
-                info!(msg = "Server waiting for connections", address);
                 return listener;
             }
             Err(err) => {
@@ -104,6 +379,30 @@ pub async fn connect_with_retry(addr: &str) -> Result<TcpStream, Error> {
     }
 }
 
+pub async fn connect_unix_with_retry(path: &Path) -> Result<UnixStream, Error> {
+    let mut retry_count = 0;
+
+    loop {
+        debug!(target: DEVELOPMENT, msg = "Connecting to database", socket = %path.display());
+        match UnixStream::connect(path).await {
+            Ok(stream) => {
+                return Ok(stream);
+            }
+            Err(err) => {
+                if retry_count > MAX_RETRY_COUNT {
+                    error!(msg = "Could not connect to database", retries = ?retry_count, error = err.to_string());
+                    return Err(Error::DatabaseConnection);
+                }
+            }
+        };
+        let sleep_duration_ms =
+            (100 * 2_u64.pow(retry_count)).min(MAX_RETRY_DELAY.as_millis() as _);
+        time::sleep(Duration::from_millis(sleep_duration_ms)).await;
+
+        retry_count += 1;
+    }
+}
+
 ///
 /// Configure the tcp socket
 ///     set_nodelay
@@ -157,3 +456,111 @@ pub fn configure(stream: &TcpStream) {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    fn hosts(names: &[&str]) -> Vec<DatabaseConfig> {
+        let mut config = DatabaseConfig::for_testing();
+        config.hosts = names.iter().map(|name| name.to_string()).collect();
+        config.host_configs().unwrap()
+    }
+
+    ///
+    /// Selects a host from hosts that are "down", "standby" or up, recording the hosts probed
+    ///
+    async fn select(
+        hosts: &[DatabaseConfig],
+        target_session_attrs: TargetSessionAttrs,
+    ) -> (Option<String>, Vec<String>) {
+        let probed = Mutex::new(vec![]);
+        let selected = first_accepting_host(hosts, target_session_attrs, |host| {
+            probed.lock().unwrap().push(host.host.clone());
+            async move {
+                match host.host.as_str() {
+                    name if name.starts_with("down") => Err(Error::NoDatabaseHost {
+                        target_session_attrs,
+                    }),
+                    name => Ok(((), name.starts_with("standby"))),
+                }
+            }
+        })
+        .await
+        .map(|(_, host)| host.host.clone());
+
+        (selected, probed.into_inner().unwrap())
+    }
+
+    #[tokio::test]
+    async fn first_host_that_connects_is_selected() {
+        let hosts = hosts(&["down-1", "standby-1", "primary-1", "primary-2"]);
+
+        let (selected, probed) = select(&hosts, TargetSessionAttrs::Any).await;
+        assert_eq!(selected.as_deref(), Some("standby-1"));
+        assert_eq!(probed, vec!["down-1", "standby-1"]);
+    }
+
+    #[tokio::test]
+    async fn read_write_skips_hosts_in_recovery() {
+        let hosts = hosts(&["down-1", "standby-1", "primary-1", "primary-2"]);
+
+        let (selected, probed) = select(&hosts, TargetSessionAttrs::ReadWrite).await;
+        assert_eq!(selected.as_deref(), Some("primary-1"));
+        assert_eq!(probed, vec!["down-1", "standby-1", "primary-1"]);
+    }
+
+    #[tokio::test]
+    async fn no_host_is_selected_if_none_accept() {
+        let hosts = hosts(&["down-1", "standby-1"]);
+
+        let (selected, probed) = select(&hosts, TargetSessionAttrs::ReadWrite).await;
+        assert_eq!(selected, None);
+        assert_eq!(probed, vec!["down-1", "standby-1"]);
+    }
+
+    fn server_config(dir: &Path) -> ServerConfig {
+        ServerConfig {
+            host: "127.0.0.1".to_string(),
+            port: 0,
+            unix_socket_directory: Some(dir.to_string_lossy().to_string()),
+            unix_socket_permissions: Some("0770".to_string()),
+            ..ServerConfig::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn accepts_unix_socket_connections() {
+        let dir = std::env::temp_dir().join(format!("proxy-unix-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+
+        let server = server_config(&dir);
+        let path = server.unix_socket_path().unwrap();
+
+        // A socket file left behind by a previous process is replaced
+        drop(std::os::unix::net::UnixListener::bind(&path).unwrap());
+        assert!(path.exists());
+
+        let listener = bind(&server).await.unwrap();
+
+        let mode = fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o770);
+
+        let _client = UnixStream::connect(&path).await.unwrap();
+        let stream = listener.accept().await.unwrap();
+        assert!(stream.is_unix());
+
+        // A socket in use is not replaced
+        assert!(bind(&server).await.is_err());
+
+        // The directory the socket was bound in is removed
+        let entries = fs::read_dir(&dir).unwrap().count();
+        assert_eq!(entries, 1);
+
+        drop(listener);
+        assert!(!path.exists());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,9 +1,13 @@
-use crate::{postgresql::Column, Identifier};
+use crate::{
+    config::{SslMode, TargetSessionAttrs},
+    postgresql::Column,
+    Identifier,
+};
 use bytes::BytesMut;
 use cipherstash_client::{encryption, schema::ColumnType};
 use eql_mapper::{EqlMapperError, EqlTermVariant};
 use metrics_exporter_prometheus::BuildError;
-use std::{io, time::Duration};
+use std::{io, sync::Arc, time::Duration};
 use thiserror::Error;
 
 const ERROR_DOC_BASE_URL: &str = "https://github.com/cipherstash/proxy/blob/main/docs/errors.md";
@@ -36,15 +40,29 @@ pub enum Error {
     #[error(transparent)]
     Io(io::Error),
 
+    #[error(transparent)]
+    Limit(#[from] LimitError),
+
     #[error(transparent)]
     Mapping(#[from] MappingError),
 
+    #[error("No database host accepts a {target_session_attrs} session. For help visit {}#database-no-host-available", ERROR_DOC_BASE_URL)]
+    NoDatabaseHost {
+        target_session_attrs: TargetSessionAttrs,
+    },
+
     #[error(transparent)]
     Prometheus(#[from] BuildError),
 
     #[error(transparent)]
     Protocol(#[from] ProtocolError),
 
+    #[error(transparent)]
+    Reencrypt(#[from] ReencryptError),
+
+    #[error(transparent)]
+    Telemetry(#[from] opentelemetry_otlp::ExporterBuildError),
+
     #[error(transparent)]
     Tls(#[from] rustls::Error),
 
@@ -56,9 +74,23 @@ pub enum Error {
 
     #[error(transparent)]
     SendError(#[from] tokio::sync::mpsc::error::SendError<BytesMut>),
+
+    /// An error returned to every request of a coalesced ZeroKMS call
+    #[error(transparent)]
+    Shared(Arc<Error>),
 }
 
 impl Error {
+    /// The error itself, or the shared error if this is an `Error::Shared`.
+    ///
+    /// Match on the root when the variant matters, so the requests of a coalesced call handle an error the same way.
+    pub fn root(&self) -> &Error {
+        match self {
+            Error::Shared(err) => err.root(),
+            err => err,
+        }
+    }
+
     /// Whether this error must be returned to the client even when the proxy is
     /// configured to fall back to forwarding statements it could not map.
     ///
@@ -75,6 +107,10 @@ impl Error {
             // stores plaintext in a column its operator believes is encrypted
             // (CIP-3688). No configuration may turn that back on.
             Error::Mapping(MappingError::UnmappableEncryptedColumn { .. })
+                // Strict mode exists to stop the passthrough of anything touching
+                // an encrypted table that the mapper could not prove safe.
+                | Error::Mapping(MappingError::StrictModeRefused { .. })
+                | Error::Mapping(MappingError::StrictModeDisableMapping)
         )
     }
 }
@@ -95,6 +131,51 @@ pub enum ZeroKMSError {
 
     #[error(transparent)]
     System(#[from] cipherstash_client::zerokms::Error),
+
+    #[error("ZeroKMS is unavailable, retrying in {retry_after_seconds} seconds. For help visit {}#zerokms-unavailable", ERROR_DOC_BASE_URL)]
+    Unavailable { retry_after_seconds: u64 },
+}
+
+#[derive(Error, Debug)]
+pub enum LimitError {
+    #[error("Too many connections, the limit of {limit} client connections has been reached. For help visit {}#limits-too-many-connections", ERROR_DOC_BASE_URL)]
+    TooManyConnections { limit: usize },
+
+    #[error("Too many connections for role '{role}', the limit of {limit} client connections per role has been reached. For help visit {}#limits-too-many-connections", ERROR_DOC_BASE_URL)]
+    TooManyRoleConnections { role: String, limit: usize },
+
+    #[error("Statement rate limit of {limit} per second exceeded for keyset '{keyset}'. For help visit {}#limits-rate-limit-exceeded", ERROR_DOC_BASE_URL)]
+    StatementRateLimitExceeded { keyset: String, limit: u32 },
+
+    #[error("Encrypted value rate limit of {limit} per second exceeded for keyset '{keyset}'. For help visit {}#limits-rate-limit-exceeded", ERROR_DOC_BASE_URL)]
+    ValueRateLimitExceeded { keyset: String, limit: u32 },
+}
+
+#[derive(Error, Debug)]
+pub enum ReencryptError {
+    #[error(
+        "Source and target keyset are both '{keyset}'. For help visit {}#reencrypt-same-keyset",
+        ERROR_DOC_BASE_URL
+    )]
+    SameKeyset { keyset: String },
+
+    #[error("Checkpoint does not match the primary key ({primary_key}). For help visit {}#reencrypt-invalid-checkpoint", ERROR_DOC_BASE_URL)]
+    InvalidCheckpoint { primary_key: String },
+
+    #[error("Search terms ({terms}) of column '{table}.{column}' were not regenerated for the record with primary key ({primary_key}). The batch was rolled back. For help visit {}#reencrypt-search-terms-not-regenerated", ERROR_DOC_BASE_URL)]
+    SearchTermsNotRegenerated {
+        table: String,
+        column: String,
+        primary_key: String,
+        terms: String,
+    },
+
+    #[error("Column '{table}.{column}' is NULL after it was re-encrypted for the record with primary key ({primary_key}). The batch was rolled back. For help visit {}#reencrypt-value-not-written", ERROR_DOC_BASE_URL)]
+    ValueNotWritten {
+        table: String,
+        column: String,
+        primary_key: String,
+    },
 }
 
 #[derive(Error, Debug)]
@@ -129,6 +210,31 @@ pub enum MappingError {
     #[error("Statement could not be transformed: {0}")]
     StatementCouldNotBeTransformed(String),
 
+    #[error("CIPHERSTASH EXPLAIN is only supported in the simple query protocol. For help visit {}#mapping-explain-requires-simple-query", ERROR_DOC_BASE_URL)]
+    ExplainRequiresSimpleQuery,
+
+    #[error("Statement references a table with encrypted columns but could not be type checked, and is refused in strict mode. Statement fingerprint: {fingerprint}. For help visit {}#mapping-strict-mode-refused", ERROR_DOC_BASE_URL)]
+    StrictModeRefused { fingerprint: String },
+
+    #[error("SET CIPHERSTASH.UNSAFE_DISABLE_MAPPING is refused in strict mode. For help visit {}#mapping-strict-mode-refused", ERROR_DOC_BASE_URL)]
+    StrictModeDisableMapping,
+
+    /// Proxy reads every source row of an `INSERT ... SELECT` or `UPDATE` from
+    /// plaintext columns before encrypting them, so the statement is bounded by
+    /// `mapping.plaintext_source_max_rows`.
+    #[error("Statement copies more than {max} rows into encrypted columns. For help visit {}#mapping-plaintext-source-too-large", ERROR_DOC_BASE_URL)]
+    PlaintextSourceTooLarge { max: usize },
+
+    /// Proxy runs the source query of a plaintext source on the client's connection before the statement
+    /// that writes the encrypted values, so the statement cannot share a simple query with other statements,
+    /// or follow an extended protocol batch that has not been closed with a Sync.
+    #[error("Statement copies plaintext into encrypted columns and must be sent in a query on its own. For help visit {}#mapping-plaintext-source-not-alone", ERROR_DOC_BASE_URL)]
+    PlaintextSourceNotAlone,
+
+    /// Every source row is written, or the statement fails instead of dropping a row.
+    #[error("Statement read {read} rows to copy into encrypted columns, but wrote {written}. For help visit {}#mapping-plaintext-source-rows-changed", ERROR_DOC_BASE_URL)]
+    PlaintextSourceRowsChanged { read: usize, written: usize },
+
     #[error("Could not parse parameter")]
     CouldNotParseParameter,
 
@@ -207,6 +313,9 @@ pub enum ConfigError {
     #[error("Expected an Encrypt configuration table")]
     MissingEncryptConfigTable,
 
+    #[error("Encrypted statement mapping cannot be disabled when mapping.strict is enabled. For help visit {}#mapping-strict-mode-refused", ERROR_DOC_BASE_URL)]
+    StrictMappingDisabled,
+
     #[error("Network configuration change requires restart For help visit {}#config-network-change-requires-restart", ERROR_DOC_BASE_URL)]
     NetworkConfigurationChangeRequiresRestart,
 
@@ -222,6 +331,24 @@ pub enum ConfigError {
     #[error("Client must connect with Transport Layer Security (TLS)")]
     TlsRequired,
 
+    #[error(
+        "Client must connect with a certificate signed by the client CA. For help visit {}#config-client-certificate-required",
+        ERROR_DOC_BASE_URL
+    )]
+    ClientCertificateRequired,
+
+    #[error(
+        "Client certificates cannot be required or used for keysets without a client CA certificate. For help visit {}#config-missing-or-invalid-tls",
+        ERROR_DOC_BASE_URL
+    )]
+    MissingClientCaCertificate,
+
+    #[error(
+        "Database does not support Transport Layer Security (TLS), which is required by sslmode {ssl_mode}. For help visit {}#config-database-tls-required",
+        ERROR_DOC_BASE_URL
+    )]
+    DatabaseTlsRequired { ssl_mode: SslMode },
+
     #[error(transparent)]
     TlsConfigError(#[from] TlsConfigError),
 }
@@ -251,6 +378,36 @@ pub enum TlsConfigError {
         ERROR_DOC_BASE_URL
     )]
     MissingPrivateKey { path: String },
+
+    #[error(
+        "Invalid Transport Layer Security (TLS) client CA certificate. For help visit {}#config-missing-or-invalid-tls",
+        ERROR_DOC_BASE_URL
+    )]
+    InvalidClientCaCertificate,
+
+    #[error(
+        "Missing Transport Layer Security (TLS) client CA certificate at path: {path}. For help visit {}#config-missing-or-invalid-tls",
+        ERROR_DOC_BASE_URL
+    )]
+    MissingClientCaCertificate { path: String },
+
+    #[error(
+        "Invalid database Transport Layer Security (TLS) {name}. For help visit {}#config-invalid-database-tls",
+        ERROR_DOC_BASE_URL
+    )]
+    InvalidDatabaseTls { name: &'static str },
+
+    #[error(
+        "Database Transport Layer Security (TLS) {name} is configured with both a path and a PEM. For help visit {}#config-invalid-database-tls",
+        ERROR_DOC_BASE_URL
+    )]
+    AmbiguousDatabaseTls { name: &'static str },
+
+    #[error(
+        "Database client certificate and client private key must be configured together. For help visit {}#config-invalid-database-tls",
+        ERROR_DOC_BASE_URL
+    )]
+    IncompleteDatabaseClientCertificate,
 }
 
 #[derive(Error, Debug)]
@@ -336,6 +493,28 @@ pub enum EncryptError {
     #[error("Decrypted column could not be encoded as the expected type. For help visit {}#encrypt-plaintext-could-not-be-encoded", ERROR_DOC_BASE_URL)]
     PlaintextCouldNotBeEncoded,
 
+    /// A proxy-side aggregate holds every value of its group in memory until it
+    /// is decrypted, so the values of a row's aggregates are bounded by
+    /// `mapping.proxy_aggregate_max_values`.
+    #[error("Aggregate over column '{column}' in table '{table}' exceeds the limit of {max} values. For help visit {}#encrypt-proxy-aggregate-too-large", ERROR_DOC_BASE_URL)]
+    ProxyAggregateTooLarge {
+        table: String,
+        column: String,
+        max: usize,
+    },
+
+    /// The encrypted values of a row's aggregates are bounded by
+    /// `mapping.proxy_aggregate_max_bytes` before they are parsed.
+    #[error("Aggregate over column '{column}' in table '{table}' exceeds the limit of {max_bytes} bytes. For help visit {}#encrypt-proxy-aggregate-too-many-bytes", ERROR_DOC_BASE_URL)]
+    ProxyAggregateTooManyBytes {
+        table: String,
+        column: String,
+        max_bytes: usize,
+    },
+
+    #[error("Aggregate over column '{column}' in table '{table}' could not be computed. For help visit {}#encrypt-proxy-aggregate-could-not-be-computed", ERROR_DOC_BASE_URL)]
+    ProxyAggregateCouldNotBeComputed { table: String, column: String },
+
     #[error(transparent)]
     Pipeline(#[from] encryption::EncryptionError),
 
@@ -351,6 +530,12 @@ pub enum EncryptError {
     )]
     UnexpectedSetKeyset,
 
+    #[error(
+        "Cannot SET CIPHERSTASH.KEYSET if the keyset is set by the client certificate. For help visit {}#encrypt-unexpected-set-keyset",
+        ERROR_DOC_BASE_URL
+    )]
+    UnexpectedSetKeysetWithClientCertificate,
+
     #[error(
         "Column '{column}' in table '{table}' has no Encrypt configuration. For help visit {}#encrypt-unknown-column",
         ERROR_DOC_BASE_URL
@@ -8,6 +8,7 @@ pub mod log;
 pub mod postgresql;
 pub mod prometheus;
 pub mod proxy;
+pub mod telemetry;
 pub mod tls;
 
 pub use crate::cli::Args;
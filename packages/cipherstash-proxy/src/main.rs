@@ -1,9 +1,9 @@
 use cipherstash_proxy::config::TandemConfig;
-use cipherstash_proxy::connect::{self, AsyncStream};
+use cipherstash_proxy::connect;
 use cipherstash_proxy::error::{ConfigError, Error};
 use cipherstash_proxy::prometheus::CLIENTS_ACTIVE_CONNECTIONS;
-use cipherstash_proxy::proxy::Proxy;
-use cipherstash_proxy::{cli, log, postgresql as pg, prometheus, tls, Args};
+use cipherstash_proxy::proxy::{Limits, Proxy};
+use cipherstash_proxy::{cli, log, postgresql as pg, prometheus, telemetry, tls, Args};
 use clap::Parser;
 use metrics::gauge;
 use tokio::signal::unix::{signal, SignalKind};
@@ -25,6 +25,16 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     log::init(config.log.clone());
 
+    if config.telemetry_enabled() {
+        if let Err(err) = telemetry::init(&config.telemetry) {
+            error!(
+                msg = "Could not start CipherStash proxy",
+                error = err.to_string()
+            );
+            std::process::exit(exitcode::CONFIG);
+        }
+    }
+
     let runtime = tokio::runtime::Builder::new_multi_thread()
         .worker_threads(config.server.worker_threads)
         .thread_stack_size(config.thread_stack_size())
@@ -54,7 +64,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
 
         let mut proxy = init(config).await;
 
-        let listener = connect::bind_with_retry(&proxy.config.server).await;
+        let mut listener = connect::bind_with_retry(&proxy.config.server).await;
         let tracker = TaskTracker::new();
 
         let mut client_id = 0;
@@ -73,6 +83,9 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
             }
         }
 
+        // Limits outlive a reload, so connections are counted across configurations
+        let limits = Limits::new(&proxy.config.limits);
+
     loop {
         tokio::select! {
             _ = sigint() => {
@@ -81,23 +94,54 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
             },
             _ = sighup() => {
                 info!(msg = "Received SIGHUP. Reloading application configuration");
-                proxy = reload_application_config(&proxy.config, &args).await.unwrap_or(proxy);
+                if let Ok(reloaded) = reload_application_config(&proxy.config, &args).await {
+                    if has_listener_config_changed(&proxy.config, &reloaded.config) {
+                        // Existing connections are not bound to the listener, and drain on the previous configuration
+                        match listener.rebind(&reloaded.config.server).await {
+                            Ok(()) => {
+                                info!(
+                                    msg = "Listener moved, existing connections continue until they close",
+                                    previous_address = proxy.config.server.to_socket_address(),
+                                    address = reloaded.config.server.to_socket_address(),
+                                    socket = ?reloaded.config.server.unix_socket_path(),
+                                );
+                                proxy = reloaded;
+                                limits.update(&proxy.config.limits);
+                            }
+                            Err(err) => {
+                                warn!(
+                                    msg = "Configuration could not be reloaded, listener could not be moved",
+                                    address = reloaded.config.server.to_socket_address(),
+                                    socket = ?reloaded.config.server.unix_socket_path(),
+                                    error = err.to_string()
+                                );
+                            }
+                        }
+                    } else {
+                        proxy = reloaded;
+                        limits.update(&proxy.config.limits);
+                    }
+                }
             },
             _ = sigterm() => {
                 info!(msg = "Received SIGTERM");
                 break;
             },
-            Ok(client_stream) = AsyncStream::accept(&listener) => {
+            Ok(client_stream) = listener.accept() => {
 
                     client_id += 1;
 
-                    let context = proxy.context(client_id);
+                    let mut context = proxy.context(client_id);
+                    context.set_limits(limits.clone());
+                    let server_tls = proxy.server_tls.clone();
+                    let hosts = proxy.hosts.clone();
+                    let replicas = proxy.replicas.clone();
 
                     tracker.spawn(async move {
 
                         gauge!(CLIENTS_ACTIVE_CONNECTIONS).increment(1);
 
-                        match pg::handler(client_stream,context).await {
+                        match pg::handler(client_stream, context, server_tls, hosts, replicas).await {
                             Ok(_) => (),
                             Err(err) => {
 
@@ -113,6 +157,9 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                                     Error::ConnectionTimeout{..} => {
                                         warn!(msg = "Database connection timeout", error = err.to_string());
                                     }
+                                    Error::Limit(_) => {
+                                        warn!(msg = "Database connection rejected by connection limit", error = err.to_string());
+                                    }
                                     _ => {
                                         error!(msg = "Database connection error", error = err.to_string());
                                     }
@@ -137,6 +184,9 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
             warn!(msg = "Terminated client connections", count = tracker.len());
         }
     });
+
+    telemetry::shutdown();
+
     Ok(())
 }
 
@@ -144,29 +194,12 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
 /// Validate various configuration options and
 /// Init the Proxy service
 ///
-async fn init(mut config: TandemConfig) -> Proxy {
+async fn init(config: TandemConfig) -> Proxy {
     if config.encrypt.default_keyset_id.is_none() {
         warn!(msg = "Default Keyset Id has not been configured");
         warn!(msg = "A Keyset Identifier must be set using the `SET CIPHERSTASH.KEYSET_ID` or `SET CIPHERSTASH.KEYSET_NAME` commands");
     }
 
-    match config.server.server_name() {
-        Ok(_) => {}
-        Err(err) => {
-            error!(
-                msg = "Could not start CipherStash proxy",
-                error = err.to_string()
-            );
-            std::process::exit(exitcode::CONFIG);
-        }
-    }
-
-    if !config.database.with_tls_verification {
-        warn!(
-            msg = "Bypassing Transport Layer Security (TLS) verification for database connections"
-        );
-    }
-
     if config.mapping_disabled() {
         warn!(msg = "Encrypted statement mapping is not enabled");
     }
@@ -182,35 +215,12 @@ async fn init(mut config: TandemConfig) -> Proxy {
             std::process::exit(exitcode::CONFIG);
         });
 
-    match config.tls {
-        Some(ref mut tls) => {
-            _ = tls.check_cert().inspect_err(|err| {
-                error!(msg = err.to_string());
-                std::process::exit(exitcode::CONFIG);
-            });
-
-            _ = tls.check_private_key().inspect_err(|err| {
-                error!(msg = err.to_string());
-                std::process::exit(exitcode::CONFIG);
-            });
-
-            match tls::configure_server(tls) {
-                Ok(_) => {
-                    info!(msg = "Server Transport Layer Security (TLS) configuration validated");
-                }
-                Err(err) => {
-                    error!(
-                        msg = "Server Transport Layer Security (TLS) configuration error",
-                        error = err.to_string()
-                    );
-                    std::process::exit(exitcode::CONFIG);
-                }
-            }
-        }
-        None => {
-            warn!(msg = "Transport Layer Security (TLS) is not configured");
-            warn!(msg = "Listening on an unsafe connection");
-        }
+    if let Err(err) = validate(&config) {
+        error!(
+            msg = "Could not start CipherStash proxy",
+            error = err.to_string()
+        );
+        std::process::exit(exitcode::CONFIG);
     }
 
     match Proxy::init(config).await {
@@ -243,6 +253,63 @@ async fn init(mut config: TandemConfig) -> Proxy {
     }
 }
 
+///
+/// Validate the configuration options that are not checked when the configuration is loaded
+///
+/// Used both at startup and before a reloaded configuration is applied, so it must not exit the process.
+///
+fn validate(config: &TandemConfig) -> Result<(), Error> {
+    config.server.server_name()?;
+    config.server.unix_socket_permissions()?;
+
+    tls::configure_client(&config.database)?;
+    info!(
+        msg = "Database Transport Layer Security (TLS) configuration validated",
+        ssl_mode = %config.database.ssl_mode()
+    );
+
+    if !config.database.ssl_mode().verifies_certificate() {
+        warn!(
+            msg = "Bypassing Transport Layer Security (TLS) verification for database connections"
+        );
+    }
+
+    match &config.tls {
+        Some(tls) => {
+            tls.check_cert().map_err(ConfigError::from)?;
+            tls.check_private_key().map_err(ConfigError::from)?;
+            tls.check_client_ca_cert().map_err(ConfigError::from)?;
+            tls::configure_server(tls)?;
+            info!(msg = "Server Transport Layer Security (TLS) configuration validated");
+        }
+        None => {
+            warn!(msg = "Transport Layer Security (TLS) is not configured");
+            warn!(msg = "Listening on an unsafe connection");
+        }
+    }
+
+    let verifies_client_certificates = config
+        .tls
+        .as_ref()
+        .is_some_and(|tls| tls.verifies_client_certificates());
+
+    if (config.server.require_client_certificate || config.server.keyset_from_client_certificate)
+        && !verifies_client_certificates
+    {
+        return Err(ConfigError::MissingClientCaCertificate.into());
+    }
+
+    if verifies_client_certificates {
+        info!(
+            msg = "Client certificates are verified",
+            require_client_certificate = config.server.require_client_certificate,
+            keyset_from_client_certificate = config.server.keyset_from_client_certificate,
+        );
+    }
+
+    Ok(())
+}
+
 async fn sigint() -> std::io::Result<()> {
     signal(SignalKind::interrupt())?.recv().await;
     Ok(())
@@ -258,20 +325,31 @@ async fn sighup() -> std::io::Result<()> {
     Ok(())
 }
 
+///
+/// The worker threads are fixed when the runtime is built
+///
 fn has_network_config_changed(current: &TandemConfig, new: &TandemConfig) -> bool {
+    current.server.worker_threads != new.server.worker_threads
+}
+
+fn has_listener_config_changed(current: &TandemConfig, new: &TandemConfig) -> bool {
     current.server.host != new.server.host
         || current.server.port != new.server.port
-        || current.server.require_tls != new.server.require_tls
-        || current.server.worker_threads != new.server.worker_threads
-        || current.tls != new.tls
+        || current.server.unix_socket_directory != new.server.unix_socket_directory
+        || current.server.unix_socket_permissions != new.server.unix_socket_permissions
 }
 
+///
+/// Loads, validates and connects a new configuration, without changing the running one
+///
+/// Any error is logged and returned, and the proxy continues with its current configuration.
+///
 async fn reload_application_config(config: &TandemConfig, args: &Args) -> Result<Proxy, Error> {
     let new_config = match TandemConfig::load(args) {
         Ok(config) => config,
         Err(err) => {
             warn!(
-                msg = "Configuration could not be reloaded: {}",
+                msg = "Configuration could not be reloaded",
                 error = err.to_string()
             );
             return Err(err);
@@ -286,7 +364,23 @@ async fn reload_application_config(config: &TandemConfig, args: &Args) -> Result
         return Err(err.into());
     }
 
+    // The listener is moved by the caller, and the previous configuration is kept if it cannot be
+    if let Err(err) = validate(&new_config) {
+        warn!(
+            msg = "Configuration could not be reloaded",
+            error = err.to_string()
+        );
+        return Err(err);
+    }
+
+    // Connects to the database and ZeroKMS with the new configuration
+    let proxy = Proxy::init(new_config).await.inspect_err(|err| {
+        warn!(
+            msg = "Configuration could not be reloaded",
+            error = err.to_string()
+        );
+    })?;
+
     info!(msg = "Configuration reloaded");
-    let proxy = init(new_config).await;
     Ok(proxy)
 }
@@ -0,0 +1,335 @@
+//! Computes proxy-side aggregates over decrypted values.
+//!
+//! With `mapping.proxy_aggregates` enabled, eql-mapper rewrites an aggregate
+//! PostgreSQL cannot compute over an encrypted numeric — `sum`, `avg`, the
+//! variance family, `percentile_disc` — to return the group's encrypted values
+//! instead (see [`eql_mapper::ProxyAggregate`]). The backend decrypts them and
+//! computes the result here, with PostgreSQL's semantics:
+//!
+//! - `NULL` inputs are ignored, and an aggregate of no values is `NULL`
+//! - `var_samp`/`stddev_samp` of a single value is `NULL`
+//! - `sum` of a 16 or 32-bit integer is a `bigint`; any other aggregate of an
+//!   integer or decimal is a `numeric`, and of a float a `double precision`
+//! - `percentile_disc` returns the first value whose position in the ordered
+//!   group equals or exceeds the fraction
+//!
+//! The result types match those [`Column::with_aggregate`] describes to the
+//! client.
+
+use crate::error::{EncryptError, Error};
+use crate::postgresql::Column;
+use cipherstash_client::encryption::Plaintext;
+use eql_mapper::ProxyAggregate;
+use rust_decimal::prelude::{FromPrimitive, ToPrimitive};
+use rust_decimal::Decimal;
+use std::cmp::Ordering;
+
+/// A decrypted input value.
+#[derive(Debug, Clone, Copy)]
+enum Number {
+    Exact(Decimal),
+    Float(f64),
+}
+
+impl Number {
+    fn total_cmp(&self, other: &Number) -> Ordering {
+        match (self, other) {
+            (Number::Exact(a), Number::Exact(b)) => a.cmp(b),
+            (Number::Float(a), Number::Float(b)) => a.total_cmp(b),
+            // A column decrypts to a single type
+            _ => Ordering::Equal,
+        }
+    }
+}
+
+/// The values could not be aggregated: they are not numbers, or the result
+/// does not fit the result type.
+#[derive(Debug)]
+struct NotComputable;
+
+///
+/// Computes the proxy-side aggregate of `column` over its decrypted `values`.
+///
+/// Returns `None` where PostgreSQL would return `NULL`.
+///
+pub fn compute(column: &Column, values: &[Plaintext]) -> Result<Option<Plaintext>, Error> {
+    let not_computable = || {
+        Error::from(EncryptError::ProxyAggregateCouldNotBeComputed {
+            table: column.table_name(),
+            column: column.column_name(),
+        })
+    };
+
+    let aggregate = column.aggregate.ok_or_else(not_computable)?;
+
+    compute_aggregate(aggregate, values).map_err(|NotComputable| not_computable())
+}
+
+fn compute_aggregate(
+    aggregate: ProxyAggregate,
+    values: &[Plaintext],
+) -> Result<Option<Plaintext>, NotComputable> {
+    let mut inputs = Vec::with_capacity(values.len());
+    for plaintext in values {
+        if let Some(number) = to_number(plaintext)? {
+            inputs.push((number, plaintext));
+        }
+    }
+
+    let Some((_, first)) = inputs.first() else {
+        return Ok(None);
+    };
+
+    if let ProxyAggregate::PercentileDisc(fraction) = aggregate {
+        inputs.sort_by(|(a, _), (b, _)| a.total_cmp(b));
+
+        let position = (fraction * inputs.len() as f64).ceil() as usize;
+        let (_, plaintext) = inputs[position.clamp(1, inputs.len()) - 1];
+
+        return Ok(Some(plaintext.clone()));
+    }
+
+    let narrow = matches!(first, Plaintext::SmallInt(_) | Plaintext::Int(_));
+
+    let mut exact = Vec::with_capacity(inputs.len());
+    let mut floats = Vec::new();
+    for (number, _) in inputs {
+        match number {
+            Number::Exact(value) => exact.push(value),
+            Number::Float(value) => floats.push(value),
+        }
+    }
+
+    match (exact.is_empty(), floats.is_empty()) {
+        (true, false) => Ok(float_aggregate(aggregate, &floats).map(|x| Plaintext::Float(Some(x)))),
+        (false, true) => match exact_aggregate(aggregate, &exact)? {
+            Some(result) if narrow && aggregate == ProxyAggregate::Sum => {
+                let result = result.to_i64().ok_or(NotComputable)?;
+                Ok(Some(Plaintext::BigInt(Some(result))))
+            }
+            Some(result) => Ok(Some(Plaintext::Decimal(Some(result)))),
+            None => Ok(None),
+        },
+        _ => Err(NotComputable),
+    }
+}
+
+///
+/// A decrypted value as a number, or `None` if it is `NULL`.
+///
+fn to_number(plaintext: &Plaintext) -> Result<Option<Number>, NotComputable> {
+    let exact = match plaintext {
+        Plaintext::SmallInt(x) => x.map(Decimal::from),
+        Plaintext::Int(x) => x.map(Decimal::from),
+        Plaintext::BigInt(x) => x.map(Decimal::from),
+        Plaintext::BigUInt(x) => x.map(Decimal::from),
+        Plaintext::Decimal(x) => *x,
+        Plaintext::Float(x) => return Ok(x.map(Number::Float)),
+        _ => return Err(NotComputable),
+    };
+
+    Ok(exact.map(Number::Exact))
+}
+
+fn float_aggregate(aggregate: ProxyAggregate, values: &[f64]) -> Option<f64> {
+    let n = values.len() as f64;
+    let sum: f64 = values.iter().sum();
+
+    let sum_of_squares = || {
+        let mean = sum / n;
+        values.iter().map(|x| (x - mean) * (x - mean)).sum::<f64>()
+    };
+
+    match aggregate {
+        ProxyAggregate::Sum => Some(sum),
+        ProxyAggregate::Avg => Some(sum / n),
+        ProxyAggregate::VarPop => Some(sum_of_squares() / n),
+        ProxyAggregate::StddevPop => Some((sum_of_squares() / n).sqrt()),
+        ProxyAggregate::VarSamp if values.len() > 1 => Some(sum_of_squares() / (n - 1.0)),
+        ProxyAggregate::StddevSamp if values.len() > 1 => {
+            Some((sum_of_squares() / (n - 1.0)).sqrt())
+        }
+        _ => None,
+    }
+}
+
+fn exact_aggregate(
+    aggregate: ProxyAggregate,
+    values: &[Decimal],
+) -> Result<Option<Decimal>, NotComputable> {
+    let n = Decimal::from(values.len());
+    let sum = values
+        .iter()
+        .try_fold(Decimal::ZERO, |acc, x| acc.checked_add(*x))
+        .ok_or(NotComputable)?;
+
+    let variance = |divisor: Decimal| -> Result<Decimal, NotComputable> {
+        let mean = sum.checked_div(n).ok_or(NotComputable)?;
+        values
+            .iter()
+            .try_fold(Decimal::ZERO, |acc, x| {
+                let deviation = x.checked_sub(mean)?;
+                acc.checked_add(deviation.checked_mul(deviation)?)
+            })
+            .and_then(|sum_of_squares| sum_of_squares.checked_div(divisor))
+            .ok_or(NotComputable)
+    };
+
+    let result = match aggregate {
+        ProxyAggregate::Sum => sum,
+        ProxyAggregate::Avg => sum.checked_div(n).ok_or(NotComputable)?,
+        ProxyAggregate::VarPop => variance(n)?,
+        ProxyAggregate::StddevPop => sqrt(variance(n)?)?,
+        ProxyAggregate::VarSamp if values.len() > 1 => variance(n - Decimal::ONE)?,
+        ProxyAggregate::StddevSamp if values.len() > 1 => sqrt(variance(n - Decimal::ONE)?)?,
+        _ => return Ok(None),
+    };
+
+    Ok(Some(result.normalize()))
+}
+
+///
+/// Square root of a non-negative decimal
+///
+/// `rust_decimal` only has `sqrt` with its `maths` feature, so this refines an
+/// `f64` estimate with Newton's method until it stops changing.
+///
+fn sqrt(value: Decimal) -> Result<Decimal, NotComputable> {
+    if value.is_zero() {
+        return Ok(Decimal::ZERO);
+    }
+
+    let estimate = value.to_f64().ok_or(NotComputable)?.sqrt();
+    let mut x = Decimal::from_f64(estimate).ok_or(NotComputable)?;
+
+    for _ in 0..16 {
+        let next = value
+            .checked_div(x)
+            .and_then(|quotient| x.checked_add(quotient))
+            .and_then(|sum| sum.checked_div(Decimal::TWO))
+            .ok_or(NotComputable)?;
+
+        if next == x {
+            break;
+        }
+        x = next;
+    }
+
+    Ok(x)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn ints(values: &[i32]) -> Vec<Plaintext> {
+        values.iter().map(|x| Plaintext::Int(Some(*x))).collect()
+    }
+
+    fn decimal(s: &str) -> Option<Plaintext> {
+        Some(Plaintext::Decimal(Some(Decimal::from_str(s).unwrap())))
+    }
+
+    fn compute(aggregate: ProxyAggregate, values: &[Plaintext]) -> Option<Plaintext> {
+        compute_aggregate(aggregate, values).unwrap()
+    }
+
+    #[test]
+    fn sum_of_int_is_bigint() {
+        assert_eq!(
+            compute(ProxyAggregate::Sum, &ints(&[i32::MAX, i32::MAX])),
+            Some(Plaintext::BigInt(Some(2 * i32::MAX as i64)))
+        );
+    }
+
+    #[test]
+    fn sum_of_bigint_is_numeric() {
+        let values = [
+            Plaintext::BigInt(Some(i64::MAX)),
+            Plaintext::BigInt(Some(i64::MAX)),
+        ];
+
+        assert_eq!(
+            compute(ProxyAggregate::Sum, &values),
+            decimal("18446744073709551614")
+        );
+    }
+
+    #[test]
+    fn avg_of_int_is_numeric() {
+        assert_eq!(
+            compute(ProxyAggregate::Avg, &ints(&[1, 2, 3, 4])),
+            decimal("2.5")
+        );
+    }
+
+    #[test]
+    fn nulls_are_ignored() {
+        let mut values = ints(&[1, 3]);
+        values.push(Plaintext::Int(None));
+
+        assert_eq!(compute(ProxyAggregate::Avg, &values), decimal("2"));
+        assert_eq!(compute(ProxyAggregate::Sum, &[Plaintext::Int(None)]), None);
+        assert_eq!(compute(ProxyAggregate::Sum, &[]), None);
+    }
+
+    #[test]
+    fn variance_and_stddev() {
+        let values = ints(&[2, 4, 4, 4, 5, 5, 7, 9]);
+
+        assert_eq!(compute(ProxyAggregate::VarPop, &values), decimal("4"));
+        assert_eq!(compute(ProxyAggregate::StddevPop, &values), decimal("2"));
+        assert_eq!(
+            compute(ProxyAggregate::VarSamp, &values),
+            decimal("4.5714285714285714285714285714")
+        );
+
+        // A sample of one has no variance
+        assert_eq!(compute(ProxyAggregate::VarSamp, &ints(&[1])), None);
+        assert_eq!(compute(ProxyAggregate::StddevSamp, &ints(&[1])), None);
+    }
+
+    #[test]
+    fn float_aggregates_are_float() {
+        let values = [Plaintext::Float(Some(1.0)), Plaintext::Float(Some(3.0))];
+
+        assert_eq!(
+            compute(ProxyAggregate::Avg, &values),
+            Some(Plaintext::Float(Some(2.0)))
+        );
+        assert_eq!(
+            compute(ProxyAggregate::StddevPop, &values),
+            Some(Plaintext::Float(Some(1.0)))
+        );
+    }
+
+    #[test]
+    fn percentile_disc_returns_an_input() {
+        let values = ints(&[5, 1, 3, 2]);
+
+        assert_eq!(
+            compute(ProxyAggregate::PercentileDisc(0.0), &values),
+            Some(Plaintext::Int(Some(1)))
+        );
+        assert_eq!(
+            compute(ProxyAggregate::PercentileDisc(0.5), &values),
+            Some(Plaintext::Int(Some(2)))
+        );
+        assert_eq!(
+            compute(ProxyAggregate::PercentileDisc(0.51), &values),
+            Some(Plaintext::Int(Some(3)))
+        );
+        assert_eq!(
+            compute(ProxyAggregate::PercentileDisc(1.0), &values),
+            Some(Plaintext::Int(Some(5)))
+        );
+    }
+
+    #[test]
+    fn non_numeric_values_are_not_computable() {
+        let values = [Plaintext::Text(Some("1".to_string()))];
+
+        assert!(compute_aggregate(ProxyAggregate::Sum, &values).is_err());
+    }
+}
@@ -1,27 +1,25 @@
+use super::catalog::{self, CatalogColumns};
 use super::context::Context;
-use super::data::to_sql;
+use super::decrypt_pipeline::{DecryptPipeline, DecryptedBatch};
 use super::error_handler::PostgreSqlErrorHandler;
-use super::message_buffer::MessageBuffer;
 use super::messages::error_response::ErrorResponse;
+use super::messages::parameter_status::ParameterStatus;
 use super::messages::row_description::RowDescription;
 use super::messages::{BackendCode, UNSPECIFIED_TYPE_OID};
-use super::Column;
 use crate::connect::Sender;
-use crate::error::{EncryptError, Error};
+use crate::error::Error;
 use crate::log::{CONTEXT, DEVELOPMENT, MAPPER, PROTOCOL};
 use crate::postgresql::context::Portal;
 use crate::postgresql::messages::data_row::DataRow;
 use crate::postgresql::messages::param_description::ParamDescription;
 use crate::postgresql::protocol::{self};
 use crate::prometheus::{
-    CLIENTS_BYTES_SENT_TOTAL, DECRYPTED_VALUES_TOTAL, DECRYPTION_DURATION_SECONDS,
-    DECRYPTION_ERROR_TOTAL, DECRYPTION_REQUESTS_TOTAL, ROWS_ENCRYPTED_TOTAL,
-    ROWS_PASSTHROUGH_TOTAL, ROWS_TOTAL, SERVER_BYTES_RECEIVED_TOTAL,
+    CLIENTS_BYTES_SENT_TOTAL, ROWS_ENCRYPTED_TOTAL, ROWS_PASSTHROUGH_TOTAL, ROWS_TOTAL,
+    SERVER_BYTES_RECEIVED_TOTAL,
 };
 use crate::proxy::EncryptionService;
-use crate::EqlCiphertext;
 use bytes::BytesMut;
-use metrics::{counter, histogram};
+use metrics::counter;
 use std::time::Instant;
 use tokio::io::AsyncRead;
 use tracing::{debug, error, info, warn};
@@ -58,8 +56,10 @@ use tracing::{debug, error, info, warn};
 /// # Buffering Strategy
 ///
 /// DataRow messages containing encrypted data are buffered to enable batch decryption:
-/// - Buffer fills up to a configurable capacity
-/// - Flush occurs on buffer full, session end, or non-DataRow message
+/// - A batch fills up to `server.decrypt_batch_size` rows, or its share of `server.decrypt_buffer_bytes`
+/// - Up to `server.decrypt_concurrency` batches are decrypted concurrently, and written in order
+/// - Reading from the server waits while the batches in flight use the byte budget
+/// - Flush occurs on session end, or non-DataRow message
 /// - Batching reduces encryption API round-trips and improves performance
 ///
 /// # Message Types Handled
@@ -81,14 +81,16 @@ where
     server_reader: R,
     /// Session context with portal and statement metadata
     context: Context<S>,
-    /// Buffer for batching DataRow messages before decryption
-    buffer: MessageBuffer,
+    /// Batches DataRow messages and decrypts them concurrently
+    pipeline: DecryptPipeline,
+    /// The result columns of the catalog query being executed, if it was described
+    catalog_columns: Option<CatalogColumns>,
 }
 
 impl<R, S> Backend<R, S>
 where
     R: AsyncRead + Unpin,
-    S: EncryptionService,
+    S: EncryptionService + Clone + 'static,
 {
     /// Creates a new Backend instance.
     ///
@@ -99,12 +101,13 @@ where
     /// * `encrypt` - Encryption service for handling column decryption
     /// * `context` - Session context shared with the frontend
     pub fn new(client_sender: Sender, server_reader: R, context: Context<S>) -> Self {
-        let buffer = MessageBuffer::new();
+        let pipeline = DecryptPipeline::new(&context.config().server);
         Backend {
             client_sender,
             server_reader,
             context,
-            buffer,
+            pipeline,
+            catalog_columns: None,
         }
     }
 
@@ -172,6 +175,16 @@ where
             );
         }
 
+        // Session parameters are tracked in passthrough as well, as mapping may be enabled on reload
+        if matches!(code.into(), BackendCode::ParameterStatus) {
+            self.parameter_status_handler(&bytes);
+        }
+
+        // Responses to statements the frontend runs itself are not sent to the client
+        if self.context.divert(code.into(), &bytes)? {
+            return Ok(());
+        }
+
         if self.context.is_passthrough() {
             debug!(target: DEVELOPMENT,
                 client_id = self.context.client_id,
@@ -186,6 +199,7 @@ where
             // the newly loaded schema and encrypt configuration.
             if matches!(code.into(), BackendCode::ReadyForQuery) {
                 self.context.reload_schema_if_changed().await;
+                self.ready_for_query(&bytes);
             }
 
             self.write_with_flush(bytes).await?;
@@ -202,8 +216,12 @@ where
             match code.into() {
                 BackendCode::CommandComplete
                 | BackendCode::EmptyQueryResponse
-                | BackendCode::PortalSuspended
-                | BackendCode::ErrorResponse => {
+                | BackendCode::PortalSuspended => {
+                    self.context.complete_execution();
+                    self.context.finish_session();
+                }
+                BackendCode::ErrorResponse => {
+                    self.context.statement_failed();
                     self.context.complete_execution();
                     self.context.finish_session();
                 }
@@ -240,10 +258,13 @@ where
                     }
                 }
 
+                self.catalog_columns = None;
                 self.context.complete_execution();
                 self.context.finish_session();
             }
             BackendCode::ErrorResponse => {
+                self.context.statement_failed();
+
                 if let Some(b) = self.error_response_handler(&bytes)? {
                     bytes = b
                 }
@@ -256,6 +277,7 @@ where
                     }
                 }
 
+                self.catalog_columns = None;
                 self.context.complete_execution();
                 self.context.finish_session();
             }
@@ -292,6 +314,7 @@ where
                     msg = "ReadyForQuery"
                 );
                 self.context.reload_schema_if_changed().await;
+                self.ready_for_query(&bytes);
             }
 
             code => {
@@ -353,16 +376,21 @@ where
 
     ///
     /// DataRows are buffered so that Decryption can be batched
-    /// Decryption will occur
+    /// A batch is decrypted
+    ///  - when the batch is full
     ///  - on direct call to flush()
-    ///  - when the buffer is full
     ///  - when any other message type is written
     ///
-    async fn buffer(&mut self, data_row: DataRow) -> Result<(), Error> {
-        self.buffer.push(data_row);
-        if self.buffer.at_capacity() {
-            debug!(target: DEVELOPMENT, client_id = self.context.client_id, msg = "Flush message buffer");
-            self.flush().await?;
+    /// Batches that have already been decrypted are written without waiting for the others.
+    ///
+    async fn buffer(&mut self, data_row: DataRow, len: usize) -> Result<(), Error> {
+        if self.pipeline.push(data_row, len) {
+            debug!(target: DEVELOPMENT, client_id = self.context.client_id, msg = "Decrypt batch");
+            self.dispatch().await?;
+        }
+
+        while let Some(batch) = self.pipeline.next_if_ready().await {
+            self.write_batch(batch).await?;
         }
         Ok(())
     }
@@ -394,39 +422,63 @@ where
         counter!(CLIENTS_BYTES_SENT_TOTAL).increment(sent);
 
         let start = Instant::now();
-        self.client_sender.send(bytes)?;
+        // Waits while the client has not read what was already sent
+        self.client_sender.send_bytes(bytes).await?;
         let duration = start.elapsed();
         self.context.add_client_write_duration_for_execute(duration);
 
         Ok(())
     }
 
-    /// Flushes all buffered DataRow messages by performing batch decryption.
-    ///
-    /// This is the core decryption logic that processes buffered DataRow messages,
-    /// extracts encrypted column values, performs batch decryption, and sends the
-    /// decrypted results back to the client in the proper PostgreSQL wire format.
-    ///
-    /// # Process Overview
-    ///
-    /// 1. **Portal Validation**: Check if current portal requires decryption
-    /// 2. **Data Extraction**: Extract encrypted values from buffered DataRows
-    /// 3. **Batch Decryption**: Send all encrypted values to decryption service
-    /// 4. **Format Conversion**: Convert decrypted plaintext to PostgreSQL wire format
-    /// 5. **Result Assembly**: Reconstruct DataRows with decrypted values
-    /// 6. **Client Delivery**: Send decrypted DataRows to client
-    ///
-    /// # Portal-Based Processing
+    /// Starts decrypting the buffered DataRow messages as a batch.
     ///
     /// Decryption behavior is determined by the portal associated with the current execution:
     /// - **Encrypted Portal**: Contains column metadata for decryption
     /// - **Passthrough Portal**: No decryption needed, should not have buffered data
     ///
-    /// # Batch Decryption Benefits
+    /// # Backpressure
+    ///
+    /// While the pipeline is saturated, the oldest batch is awaited and written to the client
+    /// before the batch is started. Rows are not read from the server in the meantime, so the
+    /// rows buffered and in flight stay within `server.decrypt_buffer_bytes`.
+    async fn dispatch(&mut self) -> Result<(), Error> {
+        if !self.pipeline.has_buffered_rows() {
+            debug!(target: MAPPER, client_id = self.context.client_id, msg = "Empty buffer");
+            return Ok(());
+        }
+
+        while self.pipeline.is_saturated() {
+            if let Some(batch) = self.pipeline.next().await {
+                self.write_batch(batch).await?;
+            }
+        }
+
+        let portal = self.context.get_portal_from_execute();
+        match portal {
+            Some(portal) if matches!(*portal, Portal::Encrypted { .. }) => {
+                self.pipeline.dispatch(&self.context, portal);
+            }
+            _ => {
+                debug!(target: MAPPER, client_id = self.context.client_id, msg = "Passthrough portal");
+                error!(
+                    client_id = self.context.client_id,
+                    msg = "Buffer is not empty"
+                );
+                self.pipeline.clear();
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Decrypts all buffered DataRow messages, and writes every batch to the client in order.
+    ///
+    /// # Process Overview
     ///
-    /// - **Performance**: Single API call for multiple encrypted values
-    /// - **Efficiency**: Reduces network round-trips to encryption service
-    /// - **Consistency**: All values decrypted with same keyset ID
+    /// 1. **Dispatch**: Start decrypting any rows still in the buffer
+    /// 2. **Batch Decryption**: Each batch extracts its encrypted values and decrypts them in one request
+    /// 3. **Format Conversion**: Decrypted plaintext is converted to PostgreSQL wire format
+    /// 4. **Client Delivery**: Batches are written to the client in the order the rows were read
     ///
     /// # Format Code Handling
     ///
@@ -440,138 +492,39 @@ where
     /// appropriate error responses and recorded in metrics. The error mapping
     /// implemented in the encryption service ensures proper keyset ID context
     /// is preserved in error messages.
+    /// After an error, the remaining batches are cancelled and their rows are not written.
     async fn flush(&mut self) -> Result<(), Error> {
-        if self.buffer.is_empty() {
-            debug!(target: MAPPER, client_id = self.context.client_id, msg = "Empty buffer");
-        }
+        self.dispatch().await?;
 
-        let portal = self.context.get_portal_from_execute();
-        let portal = match portal.as_deref() {
-            Some(Portal::Encrypted { .. }) => portal.unwrap(),
-            _ => {
-                debug!(target: MAPPER, client_id = self.context.client_id, msg = "Passthrough portal");
-                if !self.buffer.is_empty() {
-                    error!(
-                        client_id = self.context.client_id,
-                        msg = "Buffer is not empty"
-                    );
-                }
-                return Ok(());
-            }
-        };
+        while let Some(batch) = self.pipeline.next().await {
+            self.write_batch(batch).await?;
+        }
 
-        let mut rows: Vec<DataRow> = self.buffer.drain().into_iter().collect();
-        debug!(target: DEVELOPMENT, client_id = self.context.client_id, rows = rows.len());
+        Ok(())
+    }
 
-        let result_column_count = match rows.first() {
-            Some(row) => row.column_count(),
-            None => return Ok(()),
+    ///
+    /// Writes the notices and rows of a decrypted batch to the client
+    ///
+    async fn write_batch(&mut self, batch: Result<DecryptedBatch, Error>) -> Result<(), Error> {
+        let batch = match batch {
+            Ok(batch) => batch,
+            Err(err) => {
+                self.pipeline.clear();
+                return Err(err);
+            }
         };
 
-        // Result Column Format Codes are passed with the Bind message
-        // Bind is turned into a Portal
-        // We pull the format codes from the portal
-        // If no portal, assume Text for all columns
-        let result_column_format_codes = portal.format_codes(result_column_count);
-
-        let projection_columns = portal.projection_columns();
-
-        // Each row is converted into Vec<Option<CipherText>>
-        let ciphertexts: Vec<Option<EqlCiphertext>> = rows
-            .iter_mut()
-            .flat_map(|row| row.as_ciphertext(projection_columns))
-            .collect::<Vec<_>>();
-
-        let start = Instant::now();
-
-        self.check_column_config(projection_columns, &ciphertexts)?;
-
-        let keyset_id = self.context.keyset_identifier();
-
-        debug!(target: CONTEXT,
-            client_id = self.context.client_id,
-            ?keyset_id,
-        );
-
-        // Decrypt CipherText -> Plaintext
-        let plaintexts = self.context.decrypt(ciphertexts).await.inspect_err(|_| {
-            counter!(DECRYPTION_ERROR_TOTAL).increment(1);
-        })?;
-
-        let duration = Instant::now().duration_since(start);
-
         // Always record for slow-statement diagnostics
-        self.context.add_decrypt_duration_for_execute(duration);
-
-        // Prometheus metrics remain gated
-        if self.context.prometheus_enabled() {
-            let decrypted_count =
-                plaintexts
-                    .iter()
-                    .fold(0, |acc, o| if o.is_some() { acc + 1 } else { acc });
-
-            counter!(DECRYPTION_REQUESTS_TOTAL).increment(1);
-            counter!(DECRYPTED_VALUES_TOTAL).increment(decrypted_count);
-            histogram!(DECRYPTION_DURATION_SECONDS).record(duration);
-        }
+        self.context.add_decrypt_for_execute(batch.decrypt);
 
-        // Chunk rows into sets of columns
-        let rows = plaintexts.chunks(result_column_count).zip(rows);
-
-        // Stitch Plaintext back into Rows encoded with the appropriate Format Code
-        // Each chunk is written to the client
-        for (chunk, mut row) in rows {
-            let data = chunk
-                .iter()
-                .zip(result_column_format_codes.iter())
-                .map(|(plaintext, format_code)| match plaintext {
-                    Some(plaintext) => to_sql(plaintext, format_code),
-                    None => Ok(None),
-                })
-                .collect::<Result<Vec<_>, _>>()?;
-
-            row.rewrite(&data)?;
-
-            let bytes = BytesMut::try_from(row)?;
+        for bytes in batch.notices.into_iter().chain(batch.rows) {
             self.write(bytes).await?;
         }
 
         Ok(())
     }
 
-    fn check_column_config(
-        &mut self,
-        projection_columns: &[Option<Column>],
-        ciphertexts: &[Option<EqlCiphertext>],
-    ) -> Result<(), Error> {
-        for (col, ct) in projection_columns.iter().zip(ciphertexts) {
-            match (col, ct) {
-                (Some(col), Some(ct)) => {
-                    if &col.identifier != ct.identifier() {
-                        return Err(EncryptError::ColumnConfigurationMismatch {
-                            table: col.identifier.table.to_owned(),
-                            column: col.identifier.column.to_owned(),
-                        }
-                        .into());
-                    }
-                }
-                // configured column with NULL ciphertext
-                (Some(_), None) => {}
-                // unconfigured column *should* have no ciphertext,
-                (None, None) => {}
-                // ciphertext with no column configuration is bad
-                (None, Some(ct)) => {
-                    return Err(EncryptError::ColumnConfigurationMismatch {
-                        table: ct.identifier().table.to_owned(),
-                        column: ct.identifier().column.to_owned(),
-                    }
-                    .into());
-                }
-            }
-        }
-        Ok(())
-    }
-
     async fn parameter_description_handler(
         &self,
         bytes: &BytesMut,
@@ -647,6 +600,10 @@ where
             description.map_types(&projection_types);
         }
 
+        if let Some(Portal::Catalog { .. }) = self.context.get_portal_from_execute().as_deref() {
+            self.catalog_columns = Some(CatalogColumns::from(&description));
+        }
+
         if description.requires_rewrite() {
             let bytes = BytesMut::try_from(description)?;
             debug!(target: MAPPER, client_id = self.context.client_id, msg = "Rewrite RowDescription", bytes = ?bytes);
@@ -668,6 +625,7 @@ where
     /// The handler examines the portal associated with the current execution:
     /// - **Encrypted Portal**: Rows may contain encrypted data, buffer for decryption
     /// - **Passthrough Portal**: Rows contain no encrypted data, forward immediately
+    /// - **Catalog Portal**: Rows describing encrypted columns are rewritten and written, see [`catalog`]
     /// - **No Portal**: No execution context, forward immediately
     ///
     /// # Buffering Strategy
@@ -683,7 +641,7 @@ where
     ///
     /// # Return Value
     ///
-    /// Returns `Ok(true)` if the row was buffered (caller should not forward),
+    /// Returns `Ok(true)` if the row was buffered or written (caller should not forward),
     /// or `Ok(false)` if the row should be forwarded unchanged by the caller.
     ///
     /// # Metrics
@@ -697,11 +655,26 @@ where
                 debug!(target: MAPPER, client_id = self.context.client_id, msg = "Encrypted");
 
                 let data_row = DataRow::try_from(bytes)?;
-                self.buffer(data_row).await?;
+                self.buffer(data_row, bytes.len()).await?;
 
                 counter!(ROWS_ENCRYPTED_TOTAL).increment(1);
                 Ok(true)
             }
+            Some(Portal::Catalog { .. }) => {
+                debug!(target: MAPPER, client_id = self.context.client_id, msg = "Catalog");
+                counter!(ROWS_PASSTHROUGH_TOTAL).increment(1);
+
+                let mut data_row = DataRow::try_from(bytes)?;
+                let resolver = self.context.get_table_resolver();
+
+                if catalog::rewrite_row(&mut data_row, self.catalog_columns.as_ref(), &resolver) {
+                    let bytes = BytesMut::try_from(data_row)?;
+                    self.write_with_flush(bytes).await?;
+                    return Ok(true);
+                }
+
+                Ok(false)
+            }
             _ => {
                 debug!(target: MAPPER, client_id = self.context.client_id, msg = "Passthrough");
                 counter!(ROWS_PASSTHROUGH_TOTAL).increment(1);
@@ -709,6 +682,30 @@ where
             }
         }
     }
+
+    ///
+    /// Passes the transaction status to the router, which may change the active database connection once the
+    /// message has been read
+    ///
+    fn ready_for_query(&self, bytes: &BytesMut) {
+        let transaction_status = bytes.get(5).copied().unwrap_or_default();
+        self.context.ready_for_query(transaction_status);
+    }
+
+    ///
+    /// Records the session parameters used to encode decrypted values, such as `DateStyle` and `TimeZone`
+    /// The message is passed through to the client unchanged
+    ///
+    fn parameter_status_handler(&self, bytes: &BytesMut) {
+        match ParameterStatus::try_from(bytes) {
+            Ok(status) => self
+                .context
+                .set_session_parameter(&status.name, &status.value),
+            Err(err) => {
+                warn!(target: PROTOCOL, client_id = self.context.client_id, msg = "Invalid ParameterStatus", error = err.to_string());
+            }
+        }
+    }
 }
 
 /// Implementation of PostgreSQL error handling for the Backend component.
@@ -732,8 +729,8 @@ where
     /// the current query execution.
     fn send_error_response(&mut self, err: Error) -> Result<(), Error> {
         let error_response = self.error_to_response(err);
-        // Ensure any buffered data is cleared before sending error
-        self.buffer.clear();
+        // Ensure any buffered and in flight data is cleared before sending error
+        self.pipeline.clear();
 
         let message = BytesMut::try_from(error_response)?;
 
@@ -753,16 +750,19 @@ where
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::config::{LogConfig, TandemConfig};
+    use crate::config::{LogConfig, TandemConfig, DEFAULT_CLIENT_WRITE_BUFFER_BYTES};
+    use crate::connect;
     use crate::log;
     use crate::postgresql::context::KeysetIdentifier;
     use crate::postgresql::messages::Name;
+    use crate::postgresql::Column;
     use crate::proxy::{EncryptConfig, EncryptionService};
     use eql_mapper::Schema;
     use std::io::Cursor;
     use std::sync::Arc;
     use tokio::sync::mpsc;
 
+    #[derive(Clone)]
     struct TestService {}
 
     #[async_trait::async_trait]
@@ -871,15 +871,16 @@ mod tests {
             responder.send(true).expect("reload receiver must be open");
         });
 
-        let (client_sender, mut client_receiver) = mpsc::unbounded_channel();
+        let (client_sender, mut client_receiver) =
+            connect::channel(DEFAULT_CLIENT_WRITE_BUFFER_BYTES);
         let reader = Cursor::new(ready_for_query_bytes().to_vec());
         let mut backend = Backend::new(client_sender, reader, context);
 
         backend.rewrite().await.unwrap();
         reload_task.await.unwrap();
         assert_eq!(
-            client_receiver.recv().await.unwrap(),
-            ready_for_query_bytes()
+            client_receiver.recv().await.unwrap().bytes(),
+            &ready_for_query_bytes()
         );
         assert!(!backend.context.take_schema_changed());
     }
@@ -927,7 +928,8 @@ mod tests {
             }
 
             // Keep the client receiver alive so write_with_flush succeeds.
-            let (client_sender, _client_receiver) = mpsc::unbounded_channel();
+            let (client_sender, _client_receiver) =
+                connect::channel(DEFAULT_CLIENT_WRITE_BUFFER_BYTES);
             let reader = Cursor::new(server_bytes.to_vec());
             let mut backend = Backend::new(client_sender, reader, context);
 
@@ -0,0 +1,550 @@
+//! Catalog masquerade — reports encrypted columns as their plaintext types to
+//! schema introspection queries.
+//!
+//! ORMs, `psql \d` and schema dumpers read column types from the catalog. An
+//! encrypted column is an `eql_v3_*` domain over `jsonb`, so without this they
+//! see `jsonb` or the domain name rather than `text` or `integer`.
+//!
+//! When `mapping.catalog_masquerade` is enabled, a query that references
+//! `information_schema.columns`, `pg_attribute`, `pg_type` or `format_type`,
+//! and no table with encrypted columns, is passed through unchanged, and the
+//! rows it returns are rewritten:
+//!
+//! - a value that names an EQL domain in a column of type names is replaced
+//!   with the plaintext type. The type name columns are `format_type`,
+//!   `typname`, `data_type`, `udt_name` and any `regtype` column, such as
+//!   `atttypid::regtype`. If the result columns are not known, a value in any
+//!   column that names an EQL domain is replaced
+//! - an `information_schema.columns` row for an encrypted column reports the
+//!   plaintext type in `data_type`, `udt_schema` and `udt_name`, and the
+//!   `domain_*` columns are NULL
+//!
+//! The encrypted column of an `information_schema.columns` row is identified by
+//! its `domain_name`, or by its `table_name` and `column_name` in the loaded
+//! schema, so at least one of those must be selected. Without `domain_name`,
+//! select `table_schema` too, or a table in a schema that is not on the
+//! `search_path` is matched by name alone. Type OIDs, such as
+//! `pg_attribute.atttypid`, are not rewritten.
+
+use super::messages::{
+    data_row::DataRow,
+    row_description::{RowDescription, RowDescriptionField},
+};
+use eql_mapper::{ColumnKind, DomainIdentity, TableResolver, TokenType};
+use postgres_types::Type;
+use sqltk::parser::ast::{self, Ident, ObjectName, ObjectNamePart, Statement};
+use sqltk::{Break, Visitable, Visitor};
+use std::{convert::Infallible, ops::ControlFlow};
+
+/// Catalog relations and functions that describe column types, with the schema they live in
+const CATALOG_OBJECTS: &[(&str, &str)] = &[
+    ("information_schema", "columns"),
+    ("pg_catalog", "pg_attribute"),
+    ("pg_catalog", "pg_type"),
+    ("pg_catalog", "format_type"),
+];
+
+/// The schema Postgres searches implicitly, so its objects may be unqualified
+const PG_CATALOG: &str = "pg_catalog";
+
+///
+/// Checks if a query references a catalog relation or function that describes column types, and no table with
+/// encrypted columns
+///
+/// A query that also reads an encrypted table is type checked as normal, so its literals and params are encrypted.
+///
+pub fn is_catalog_query(resolver: &TableResolver, statement: &Statement) -> bool {
+    struct CatalogObjectFinder;
+
+    impl<'ast> Visitor<'ast> for CatalogObjectFinder {
+        type Error = Infallible;
+
+        fn enter<N: Visitable>(&mut self, node: &'ast N) -> ControlFlow<Break<Self::Error>> {
+            if let Some(name) = node.downcast_ref::<ast::ObjectName>() {
+                if is_catalog_object(name) {
+                    return ControlFlow::Break(Break::Finished);
+                }
+            }
+
+            ControlFlow::Continue(())
+        }
+    }
+
+    matches!(statement, Statement::Query(_))
+        && statement.accept(&mut CatalogObjectFinder).is_break()
+        && !eql_mapper::references_encrypted_table(resolver, statement)
+}
+
+fn is_catalog_object(name: &ObjectName) -> bool {
+    let parts = name
+        .0
+        .iter()
+        .map(|ObjectNamePart::Identifier(ident)| ident.value.to_lowercase())
+        .collect::<Vec<_>>();
+
+    CATALOG_OBJECTS
+        .iter()
+        .any(|(schema, object)| match &parts[..] {
+            [name] => schema == &PG_CATALOG && name == object,
+            [qualifier, name] => qualifier == schema && name == object,
+            _ => false,
+        })
+}
+
+///
+/// The plaintext type reported for an encrypted column, as
+/// (`format_type` and `information_schema` name, `pg_type.typname`)
+///
+/// Matches the type of the decrypted values Proxy returns.
+///
+fn plaintext_type_names(token: &TokenType) -> (&'static str, &'static str) {
+    match token {
+        TokenType::SmallInt => ("smallint", "int2"),
+        TokenType::Integer => ("integer", "int4"),
+        TokenType::BigInt => ("bigint", "int8"),
+        TokenType::Real => ("real", "float4"),
+        TokenType::Double => ("double precision", "float8"),
+        TokenType::Numeric => ("numeric", "numeric"),
+        TokenType::Text => ("text", "text"),
+        TokenType::Boolean => ("boolean", "bool"),
+        TokenType::Date => ("date", "date"),
+        TokenType::Timestamp => ("timestamp with time zone", "timestamptz"),
+        TokenType::Json => ("jsonb", "jsonb"),
+    }
+}
+
+///
+/// Parses an EQL domain name, as returned by `format_type` or `pg_type.typname`
+/// The name may be schema qualified and quoted, eg `public.eql_v3_text_eq`
+///
+fn domain_identity(value: &[u8]) -> Option<DomainIdentity> {
+    let value = std::str::from_utf8(value).ok()?;
+    let name = value.rsplit('.').next()?.trim_matches('"');
+    DomainIdentity::from_domain_name(name)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum CatalogColumn {
+    DataType,
+    UdtSchema,
+    UdtName,
+    DomainCatalog,
+    DomainSchema,
+    DomainName,
+    TableSchema,
+    TableName,
+    ColumnName,
+    TypeName,
+    FormatType,
+    Other,
+}
+
+impl CatalogColumn {
+    /// True if the column holds type names, which are rewritten if they name an EQL domain
+    fn is_type_name(&self) -> bool {
+        matches!(
+            self,
+            CatalogColumn::DataType
+                | CatalogColumn::UdtName
+                | CatalogColumn::TypeName
+                | CatalogColumn::FormatType
+        )
+    }
+}
+
+impl From<&str> for CatalogColumn {
+    fn from(name: &str) -> Self {
+        match name {
+            "data_type" => CatalogColumn::DataType,
+            "udt_schema" => CatalogColumn::UdtSchema,
+            "udt_name" => CatalogColumn::UdtName,
+            "domain_catalog" => CatalogColumn::DomainCatalog,
+            "domain_schema" => CatalogColumn::DomainSchema,
+            "domain_name" => CatalogColumn::DomainName,
+            "table_schema" => CatalogColumn::TableSchema,
+            "table_name" => CatalogColumn::TableName,
+            "column_name" => CatalogColumn::ColumnName,
+            "typname" => CatalogColumn::TypeName,
+            "format_type" => CatalogColumn::FormatType,
+            _ => CatalogColumn::Other,
+        }
+    }
+}
+
+impl From<&RowDescriptionField> for CatalogColumn {
+    fn from(field: &RowDescriptionField) -> Self {
+        // A type OID cast to `regtype` is output as its type name, whatever the column is called
+        if field.type_oid as u32 == Type::REGTYPE.oid() {
+            return CatalogColumn::FormatType;
+        }
+        CatalogColumn::from(field.name.as_str())
+    }
+}
+
+///
+/// The result columns of a catalog query, read from its RowDescription
+///
+#[derive(Clone, Debug, Default)]
+pub struct CatalogColumns {
+    columns: Vec<CatalogColumn>,
+}
+
+impl From<&RowDescription> for CatalogColumns {
+    fn from(description: &RowDescription) -> Self {
+        CatalogColumns {
+            columns: description.fields.iter().map(CatalogColumn::from).collect(),
+        }
+    }
+}
+
+impl CatalogColumns {
+    fn position(&self, column: CatalogColumn) -> Option<usize> {
+        self.columns.iter().position(|c| *c == column)
+    }
+
+    fn get(&self, idx: usize) -> CatalogColumn {
+        self.columns
+            .get(idx)
+            .copied()
+            .unwrap_or(CatalogColumn::Other)
+    }
+}
+
+///
+/// Rewrites a row returned by a catalog query, so encrypted columns report their plaintext type
+///
+/// `columns` is `None` if the result columns are not known, for example when a prepared statement is executed without
+/// a Describe. Values that name an EQL domain are then rewritten in any column, as the type name columns cannot be
+/// told apart from the others. Otherwise only the type name columns are rewritten.
+///
+/// Returns true if the row was changed.
+///
+pub fn rewrite_row(
+    data_row: &mut DataRow,
+    columns: Option<&CatalogColumns>,
+    resolver: &TableResolver,
+) -> bool {
+    let unknown = CatalogColumns::default();
+    let is_known = columns.is_some();
+    let columns = columns.unwrap_or(&unknown);
+    let mut rewritten = false;
+
+    if let Some(identity) = information_schema_identity(data_row, columns, resolver) {
+        let (type_name, udt_name) = plaintext_type_names(&identity.token);
+
+        for (idx, data_column) in data_row.columns.iter_mut().enumerate() {
+            match columns.get(idx) {
+                CatalogColumn::DataType => data_column.rewrite(type_name.as_bytes()),
+                CatalogColumn::UdtSchema => data_column.rewrite(PG_CATALOG.as_bytes()),
+                CatalogColumn::UdtName => data_column.rewrite(udt_name.as_bytes()),
+                CatalogColumn::DomainCatalog
+                | CatalogColumn::DomainSchema
+                | CatalogColumn::DomainName => data_column.set_null(),
+                _ => continue,
+            }
+            rewritten = true;
+        }
+    }
+
+    for (idx, data_column) in data_row.columns.iter_mut().enumerate() {
+        if is_known && !columns.get(idx).is_type_name() {
+            continue;
+        }
+
+        let Some(identity) = data_column.as_bytes().and_then(domain_identity) else {
+            continue;
+        };
+
+        let (type_name, udt_name) = plaintext_type_names(&identity.token);
+        match columns.get(idx) {
+            CatalogColumn::TypeName | CatalogColumn::UdtName => {
+                data_column.rewrite(udt_name.as_bytes())
+            }
+            _ => data_column.rewrite(type_name.as_bytes()),
+        }
+        rewritten = true;
+    }
+
+    rewritten
+}
+
+///
+/// The domain of the encrypted column an `information_schema.columns` row describes
+///
+/// The `domain_name` is used if selected, otherwise the column is looked up in the schema by `table_name` and
+/// `column_name`. A row with a `table_schema` other than the schema the table was loaded from is not encrypted.
+///
+fn information_schema_identity(
+    data_row: &DataRow,
+    columns: &CatalogColumns,
+    resolver: &TableResolver,
+) -> Option<DomainIdentity> {
+    let value = |column: CatalogColumn| {
+        columns
+            .position(column)
+            .and_then(|idx| data_row.columns.get(idx))
+            .and_then(|data_column| data_column.as_bytes())
+            .and_then(|bytes| std::str::from_utf8(bytes).ok())
+    };
+
+    if columns.position(CatalogColumn::DomainName).is_some() {
+        return value(CatalogColumn::DomainName).and_then(DomainIdentity::from_domain_name);
+    }
+
+    let table = value(CatalogColumn::TableName)?;
+    let column = value(CatalogColumn::ColumnName)?;
+
+    let table = ObjectName(vec![ObjectNamePart::Identifier(Ident::with_quote(
+        '"', table,
+    ))]);
+    let column = Ident::with_quote('"', column);
+
+    let table = resolver.resolve_table(&table).ok()?;
+    if let (Some(schema), Some(loaded)) = (value(CatalogColumn::TableSchema), &table.schema) {
+        if schema != loaded.value {
+            return None;
+        }
+    }
+
+    match &table.get_column(&column).ok()?.kind {
+        ColumnKind::Eql(_, identity) => Some(identity.clone()),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::postgresql::{
+        format_code::FormatCode, messages::row_description::RowDescriptionField, parser::SqlParser,
+    };
+    use bytes::{BufMut, BytesMut};
+    use eql_mapper::{Column, EqlTraits, Schema, Table};
+    use std::sync::Arc;
+
+    fn is_catalog(sql: &str) -> bool {
+        is_catalog_query(&resolver(), &SqlParser::parse_statement(sql).unwrap())
+    }
+
+    fn resolver() -> TableResolver {
+        let mut table = Table::new(Ident::new("users"));
+        table.schema = Some(Ident::new("public"));
+        table.add_column(Arc::new(Column::eql(
+            Ident::new("email"),
+            EqlTraits::none(),
+            DomainIdentity::from_domain_name("eql_v3_text_eq").unwrap(),
+        )));
+        table.add_column(Arc::new(Column::native(Ident::new("id"))));
+
+        let mut schema = Schema::new("public");
+        schema.add_table(table);
+        TableResolver::new_fixed(Arc::new(schema))
+    }
+
+    fn columns(names: &[&str]) -> CatalogColumns {
+        columns_of_type(names, Type::TEXT)
+    }
+
+    fn columns_of_type(names: &[&str], ty: Type) -> CatalogColumns {
+        let description = RowDescription {
+            fields: names
+                .iter()
+                .map(|name| RowDescriptionField {
+                    name: name.to_string(),
+                    table_oid: 0,
+                    table_column: 0,
+                    type_oid: ty.oid() as i32,
+                    type_size: -1,
+                    type_modifier: -1,
+                    format_code: FormatCode::Text,
+                })
+                .collect(),
+        };
+        CatalogColumns::from(&description)
+    }
+
+    fn data_row(values: &[Option<&str>]) -> DataRow {
+        let mut body = BytesMut::new();
+        body.put_i16(values.len() as i16);
+        for value in values {
+            match value {
+                Some(value) => {
+                    body.put_i32(value.len() as i32);
+                    body.put_slice(value.as_bytes());
+                }
+                None => body.put_i32(-1),
+            }
+        }
+
+        let mut bytes = BytesMut::new();
+        bytes.put_u8(b'D');
+        bytes.put_i32(4 + body.len() as i32);
+        bytes.put_slice(&body);
+
+        DataRow::try_from(&bytes).unwrap()
+    }
+
+    fn values(data_row: &DataRow) -> Vec<Option<String>> {
+        data_row
+            .columns
+            .iter()
+            .map(|c| c.as_bytes().map(|b| String::from_utf8_lossy(b).to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn detects_catalog_queries() {
+        assert!(is_catalog(
+            "SELECT column_name, data_type FROM information_schema.columns WHERE table_name = 'users'"
+        ));
+        assert!(is_catalog(
+            "SELECT a.attname, pg_catalog.format_type(a.atttypid, a.atttypmod) FROM pg_catalog.pg_attribute a WHERE a.attnum > 0"
+        ));
+        assert!(is_catalog("SELECT typname FROM pg_type WHERE oid = 25"));
+        assert!(is_catalog("SELECT format_type(25, NULL)"));
+
+        assert!(!is_catalog("SELECT * FROM users"));
+        assert!(!is_catalog("SELECT * FROM columns"));
+        assert!(!is_catalog("SELECT * FROM app.pg_type"));
+        assert!(!is_catalog("DELETE FROM users"));
+    }
+
+    #[test]
+    fn queries_of_encrypted_tables_are_not_catalog_queries() {
+        assert!(!is_catalog(
+            "SELECT email FROM users WHERE email = 'x' AND format_type(25, NULL) IS NOT NULL"
+        ));
+        assert!(!is_catalog(
+            "SELECT u.email, t.typname FROM users u JOIN pg_type t ON t.oid = 25"
+        ));
+        assert!(!is_catalog(
+            "SELECT typname FROM pg_type WHERE typname IN (SELECT email FROM users)"
+        ));
+    }
+
+    #[test]
+    fn rewrites_information_schema_rows_by_domain_name() {
+        let resolver = resolver();
+        let columns = columns(&["column_name", "data_type", "udt_name", "domain_name"]);
+
+        let mut row = data_row(&[
+            Some("email"),
+            Some("jsonb"),
+            Some("jsonb"),
+            Some("eql_v3_integer_ord"),
+        ]);
+        assert!(rewrite_row(&mut row, Some(&columns), &resolver));
+        assert_eq!(
+            values(&row),
+            vec![
+                Some("email".to_string()),
+                Some("integer".to_string()),
+                Some("int4".to_string()),
+                None
+            ]
+        );
+
+        let mut row = data_row(&[Some("notes"), Some("jsonb"), Some("jsonb"), None]);
+        assert!(!rewrite_row(&mut row, Some(&columns), &resolver));
+    }
+
+    #[test]
+    fn rewrites_information_schema_rows_from_schema() {
+        let resolver = resolver();
+        let columns = columns(&["table_name", "column_name", "data_type", "udt_schema"]);
+
+        let mut row = data_row(&[
+            Some("users"),
+            Some("email"),
+            Some("jsonb"),
+            Some("pg_catalog"),
+        ]);
+        assert!(rewrite_row(&mut row, Some(&columns), &resolver));
+        assert_eq!(values(&row)[2], Some("text".to_string()));
+
+        let mut row = data_row(&[
+            Some("users"),
+            Some("id"),
+            Some("integer"),
+            Some("pg_catalog"),
+        ]);
+        assert!(!rewrite_row(&mut row, Some(&columns), &resolver));
+    }
+
+    #[test]
+    fn rewrites_information_schema_rows_in_the_loaded_schema() {
+        let resolver = resolver();
+        let columns = columns(&["table_schema", "table_name", "column_name", "data_type"]);
+
+        let mut row = data_row(&[Some("public"), Some("users"), Some("email"), Some("jsonb")]);
+        assert!(rewrite_row(&mut row, Some(&columns), &resolver));
+        assert_eq!(values(&row)[3], Some("text".to_string()));
+
+        // A table of the same name in another schema is not encrypted
+        let mut row = data_row(&[Some("archive"), Some("users"), Some("email"), Some("text")]);
+        assert!(!rewrite_row(&mut row, Some(&columns), &resolver));
+        assert_eq!(values(&row)[3], Some("text".to_string()));
+    }
+
+    #[test]
+    fn rewrites_domain_names() {
+        let resolver = resolver();
+
+        let mut row = data_row(&[
+            Some("email"),
+            Some("public.eql_v3_timestamp_ord"),
+            Some("integer"),
+        ]);
+        assert!(rewrite_row(&mut row, None, &resolver));
+        assert_eq!(
+            values(&row),
+            vec![
+                Some("email".to_string()),
+                Some("timestamp with time zone".to_string()),
+                Some("integer".to_string())
+            ]
+        );
+
+        let columns = columns(&["oid", "typname"]);
+        let mut row = data_row(&[Some("16385"), Some("eql_v3_double_ord")]);
+        assert!(rewrite_row(&mut row, Some(&columns), &resolver));
+        assert_eq!(values(&row)[1], Some("float8".to_string()));
+    }
+
+    #[test]
+    fn rewrites_domain_names_only_in_type_name_columns() {
+        let resolver = resolver();
+
+        let columns = columns(&["attname", "format_type", "description"]);
+        let mut row = data_row(&[
+            Some("email"),
+            Some("eql_v3_text_eq"),
+            Some("eql_v3_text_eq"),
+        ]);
+        assert!(rewrite_row(&mut row, Some(&columns), &resolver));
+        assert_eq!(
+            values(&row),
+            vec![
+                Some("email".to_string()),
+                Some("text".to_string()),
+                Some("eql_v3_text_eq".to_string())
+            ]
+        );
+
+        // A column of user text is not rewritten
+        let columns = columns(&["note"]);
+        let mut row = data_row(&[Some("eql_v3_integer_ord")]);
+        assert!(!rewrite_row(&mut row, Some(&columns), &resolver));
+        assert_eq!(values(&row), vec![Some("eql_v3_integer_ord".to_string())]);
+    }
+
+    #[test]
+    fn rewrites_domain_names_in_regtype_columns() {
+        let resolver = resolver();
+
+        let columns = columns_of_type(&["type"], Type::REGTYPE);
+        let mut row = data_row(&[Some("public.eql_v3_integer_ord")]);
+        assert!(rewrite_row(&mut row, Some(&columns), &resolver));
+        assert_eq!(values(&row), vec![Some("integer".to_string())]);
+    }
+}
@@ -35,7 +35,7 @@ impl ColumnMapper {
     ) -> Result<Vec<Option<Column>>, Error> {
         let mut projection_columns = vec![];
 
-        for col in typed_statement.projection.columns() {
+        for (idx, col) in typed_statement.projection.columns().iter().enumerate() {
             let eql_mapper::ProjectionColumn { ty, .. } = col;
             let configured_column = match &**ty {
                 eql_mapper::Type::Value(eql_mapper::Value::Eql(eql_term)) => {
@@ -53,6 +53,14 @@ impl ColumnMapper {
                 }
                 _ => None,
             };
+
+            // A proxy-side aggregate arrives as the group's encrypted values and
+            // leaves as the aggregate's result type.
+            let configured_column = match typed_statement.proxy_aggregates.for_column(idx) {
+                Some(aggregate) => configured_column.map(|column| column.with_aggregate(aggregate)),
+                None => configured_column,
+            };
+
             projection_columns.push(configured_column)
         }
 
@@ -178,10 +186,12 @@ impl ColumnMapper {
                     None
                 };
 
+                let token_type = eql_term.eql_value().domain_identity().token;
                 let eql_term = eql_term.variant();
                 Ok(Some(Column::new(
                     identifier,
                     config,
+                    Some(token_type),
                     postgres_type,
                     eql_term,
                 )))
@@ -1,5 +1,5 @@
 use cipherstash_client::schema::{ColumnConfig, ColumnType};
-use eql_mapper::EqlTermVariant;
+use eql_mapper::{EqlTermVariant, ProxyAggregate, TokenType};
 use postgres_types::Type;
 
 use crate::Identifier;
@@ -9,24 +9,50 @@ pub struct Column {
     pub identifier: Identifier,
     pub config: ColumnConfig,
     pub postgres_type: Type,
+    /// The plaintext token type of the column's EQL domain, when known.
+    /// `real` and `double precision` columns share `ColumnType::Float`, and only
+    /// the token type tells them apart.
+    pub token_type: Option<TokenType>,
     pub eql_term: EqlTermVariant,
+    /// Set when the database returns the column's encrypted values for the
+    /// proxy to aggregate, in which case `postgres_type` is the aggregate's
+    /// result type rather than the column's.
+    pub aggregate: Option<ProxyAggregate>,
 }
 
 impl Column {
     pub fn new(
         identifier: Identifier,
         config: ColumnConfig,
+        token_type: Option<TokenType>,
         postgres_type: Option<Type>,
         eql_term: EqlTermVariant,
     ) -> Column {
-        let postgres_type =
-            postgres_type.unwrap_or(column_type_to_postgres_type(&config.cast_type, eql_term));
+        let postgres_type = postgres_type.unwrap_or(column_type_to_postgres_type(
+            &config.cast_type,
+            token_type,
+            eql_term,
+        ));
 
         Column {
             identifier,
             config,
             postgres_type,
+            token_type,
             eql_term,
+            aggregate: None,
+        }
+    }
+
+    /// This column as the input of a proxy-side `aggregate`.
+    pub fn with_aggregate(self, aggregate: ProxyAggregate) -> Column {
+        let postgres_type =
+            aggregate_result_type(aggregate, &self.config.cast_type, &self.postgres_type);
+
+        Column {
+            postgres_type,
+            aggregate: Some(aggregate),
+            ..self
         }
     }
 
@@ -46,6 +72,10 @@ impl Column {
         self.config.cast_type
     }
 
+    pub fn token_type(&self) -> Option<TokenType> {
+        self.token_type
+    }
+
     pub fn eql_term(&self) -> EqlTermVariant {
         self.eql_term
     }
@@ -63,8 +93,11 @@ impl Column {
 ///
 /// JSONAccessors are mapped to a string for the client, but are JSONB for the server
 ///
+/// A `real` column is a `Float` with the `Real` token type, and is a `float4`
+///
 fn column_type_to_postgres_type(
     col_type: &ColumnType,
+    token_type: Option<TokenType>,
     eql_term: EqlTermVariant,
 ) -> postgres_types::Type {
     match (col_type, eql_term) {
@@ -73,6 +106,9 @@ fn column_type_to_postgres_type(
         (ColumnType::BigUInt, _) => postgres_types::Type::INT8,
         (ColumnType::Date, _) => postgres_types::Type::DATE,
         (ColumnType::Decimal, _) => postgres_types::Type::NUMERIC,
+        (ColumnType::Float, _) if token_type == Some(TokenType::Real) => {
+            postgres_types::Type::FLOAT4
+        }
         (ColumnType::Float, _) => postgres_types::Type::FLOAT8,
         (ColumnType::Int, _) => postgres_types::Type::INT4,
         (ColumnType::SmallInt, _) => postgres_types::Type::INT2,
@@ -83,6 +119,29 @@ fn column_type_to_postgres_type(
     }
 }
 
+///
+/// Maps a proxy-side aggregate over a column to the Postgres Type PostgreSQL
+/// itself would return for it
+///
+/// `sum` of a 16 or 32-bit integer is a `bigint`, of a `bigint` a `numeric`,
+/// and of a float the float's own type. Every other aggregate of an integer or
+/// decimal is a `numeric`, and of a float a `double precision`.
+/// `percentile_disc` returns one of its inputs.
+///
+fn aggregate_result_type(
+    aggregate: ProxyAggregate,
+    col_type: &ColumnType,
+    input: &postgres_types::Type,
+) -> postgres_types::Type {
+    match (aggregate, col_type) {
+        (ProxyAggregate::PercentileDisc(_), _) => input.clone(),
+        (ProxyAggregate::Sum, ColumnType::Float) => input.clone(),
+        (_, ColumnType::Float) => postgres_types::Type::FLOAT8,
+        (ProxyAggregate::Sum, ColumnType::SmallInt | ColumnType::Int) => postgres_types::Type::INT8,
+        _ => postgres_types::Type::NUMERIC,
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -91,7 +150,7 @@ mod tests {
     #[test]
     fn text_column_maps_to_postgres_text() {
         assert_eq!(
-            column_type_to_postgres_type(&ColumnType::Text, EqlTermVariant::Full),
+            column_type_to_postgres_type(&ColumnType::Text, None, EqlTermVariant::Full),
             postgres_types::Type::TEXT
         );
     }
@@ -99,7 +158,7 @@ mod tests {
     #[test]
     fn json_column_maps_to_postgres_jsonb() {
         assert_eq!(
-            column_type_to_postgres_type(&ColumnType::Json, EqlTermVariant::Full),
+            column_type_to_postgres_type(&ColumnType::Json, None, EqlTermVariant::Full),
             postgres_types::Type::JSONB
         );
     }
@@ -107,11 +166,131 @@ mod tests {
     #[test]
     fn json_accessor_maps_to_postgres_text() {
         assert_eq!(
-            column_type_to_postgres_type(&ColumnType::Json, EqlTermVariant::JsonAccessor),
+            column_type_to_postgres_type(&ColumnType::Json, None, EqlTermVariant::JsonAccessor),
             postgres_types::Type::TEXT
         );
     }
 
+    /// Every v3 token type is described to the client as its plaintext type.
+    #[test]
+    fn token_types_map_to_plaintext_postgres_types() {
+        let cases = [
+            (
+                ColumnType::SmallInt,
+                TokenType::SmallInt,
+                postgres_types::Type::INT2,
+            ),
+            (
+                ColumnType::Int,
+                TokenType::Integer,
+                postgres_types::Type::INT4,
+            ),
+            (
+                ColumnType::BigInt,
+                TokenType::BigInt,
+                postgres_types::Type::INT8,
+            ),
+            (
+                ColumnType::Float,
+                TokenType::Real,
+                postgres_types::Type::FLOAT4,
+            ),
+            (
+                ColumnType::Float,
+                TokenType::Double,
+                postgres_types::Type::FLOAT8,
+            ),
+            (
+                ColumnType::Decimal,
+                TokenType::Numeric,
+                postgres_types::Type::NUMERIC,
+            ),
+            (
+                ColumnType::Text,
+                TokenType::Text,
+                postgres_types::Type::TEXT,
+            ),
+            (
+                ColumnType::Boolean,
+                TokenType::Boolean,
+                postgres_types::Type::BOOL,
+            ),
+            (
+                ColumnType::Date,
+                TokenType::Date,
+                postgres_types::Type::DATE,
+            ),
+            (
+                ColumnType::Timestamp,
+                TokenType::Timestamp,
+                postgres_types::Type::TIMESTAMPTZ,
+            ),
+            (
+                ColumnType::Json,
+                TokenType::Json,
+                postgres_types::Type::JSONB,
+            ),
+        ];
+
+        for (col_type, token_type, expected) in cases {
+            assert_eq!(
+                column_type_to_postgres_type(&col_type, Some(token_type), EqlTermVariant::Full),
+                expected,
+                "{token_type}"
+            );
+        }
+    }
+
+    #[test]
+    fn float_without_token_type_maps_to_float8() {
+        assert_eq!(
+            column_type_to_postgres_type(&ColumnType::Float, None, EqlTermVariant::Full),
+            postgres_types::Type::FLOAT8
+        );
+    }
+
+    #[test]
+    fn proxy_aggregates_map_to_postgres_result_types() {
+        let int4 = postgres_types::Type::INT4;
+
+        assert_eq!(
+            aggregate_result_type(ProxyAggregate::Sum, &ColumnType::Int, &int4),
+            postgres_types::Type::INT8
+        );
+        assert_eq!(
+            aggregate_result_type(ProxyAggregate::Sum, &ColumnType::BigInt, &int4),
+            postgres_types::Type::NUMERIC
+        );
+        assert_eq!(
+            aggregate_result_type(ProxyAggregate::Avg, &ColumnType::Int, &int4),
+            postgres_types::Type::NUMERIC
+        );
+        assert_eq!(
+            aggregate_result_type(ProxyAggregate::StddevSamp, &ColumnType::Float, &int4),
+            postgres_types::Type::FLOAT8
+        );
+        assert_eq!(
+            aggregate_result_type(
+                ProxyAggregate::Sum,
+                &ColumnType::Float,
+                &postgres_types::Type::FLOAT4
+            ),
+            postgres_types::Type::FLOAT4
+        );
+        assert_eq!(
+            aggregate_result_type(
+                ProxyAggregate::Avg,
+                &ColumnType::Float,
+                &postgres_types::Type::FLOAT4
+            ),
+            postgres_types::Type::FLOAT8
+        );
+        assert_eq!(
+            aggregate_result_type(ProxyAggregate::PercentileDisc(0.5), &ColumnType::Int, &int4),
+            postgres_types::Type::INT4
+        );
+    }
+
     #[test]
     fn all_column_types_have_postgres_mapping() {
         let types = vec![
@@ -130,7 +309,7 @@ mod tests {
 
         for ct in types {
             // Should not panic
-            let _ = column_type_to_postgres_type(&ct, EqlTermVariant::Full);
+            let _ = column_type_to_postgres_type(&ct, None, EqlTermVariant::Full);
         }
     }
 }
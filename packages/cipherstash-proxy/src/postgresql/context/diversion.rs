@@ -0,0 +1,268 @@
+use crate::{
+    error::Error,
+    postgresql::messages::{data_row::DataRow, BackendCode},
+};
+use bytes::BytesMut;
+use tokio::sync::oneshot;
+
+///
+/// The response to a statement Proxy runs on the client's server connection
+///
+#[derive(Debug, Default)]
+pub struct DivertedResponse {
+    /// The rows, with each value in the text format
+    pub rows: Vec<Vec<Option<String>>>,
+    /// The CommandComplete that ended the statement
+    pub command_complete: BytesMut,
+}
+
+impl DivertedResponse {
+    ///
+    /// The number of rows in the command tag, such as `3` for `UPDATE 3` or `INSERT 0 3`
+    ///
+    pub fn row_count(&self) -> Option<usize> {
+        // Code, length, then the tag as a C string
+        let tag = self.command_complete.get(5..)?;
+        let tag = String::from_utf8_lossy(tag);
+        tag.trim_end_matches('\0')
+            .rsplit(' ')
+            .next()
+            .and_then(|count| count.parse().ok())
+    }
+}
+
+///
+/// The responses to the statements, or the ErrorResponse of the statement that failed
+///
+pub type DivertedResponses = Result<Vec<DivertedResponse>, BytesMut>;
+
+///
+/// Collects the responses to statements Proxy runs itself, instead of sending them to the client
+///
+/// The statements are sent with the extended protocol and a Flush, without a Sync, so they run in the
+/// client's transaction, or in the implicit transaction that the client's next statement joins.
+/// The responses end with the CommandComplete of the last statement, or with the first ErrorResponse,
+/// after which the server discards messages until a Sync.
+///
+/// Statements sent with their own Sync run in their own transaction when the client is not in one,
+/// and their responses end with the ReadyForQuery that answers the Sync.
+///
+#[derive(Debug)]
+pub struct Diversion {
+    statements: usize,
+    synced: bool,
+    responses: Vec<DivertedResponse>,
+    current: DivertedResponse,
+    error: Option<BytesMut>,
+    sender: oneshot::Sender<DivertedResponses>,
+}
+
+impl Diversion {
+    pub fn new(statements: usize) -> (Diversion, oneshot::Receiver<DivertedResponses>) {
+        Self::with_sync(statements, false)
+    }
+
+    /// Collects the responses to statements followed by a Sync, up to and including its ReadyForQuery
+    pub fn synced(statements: usize) -> (Diversion, oneshot::Receiver<DivertedResponses>) {
+        Self::with_sync(statements, true)
+    }
+
+    fn with_sync(
+        statements: usize,
+        synced: bool,
+    ) -> (Diversion, oneshot::Receiver<DivertedResponses>) {
+        let (sender, receiver) = oneshot::channel();
+        let diversion = Diversion {
+            statements,
+            synced,
+            responses: vec![],
+            current: DivertedResponse::default(),
+            error: None,
+            sender,
+        };
+        (diversion, receiver)
+    }
+
+    ///
+    /// Adds a message from the server
+    ///
+    /// Returns the responses once the last statement has completed, or a statement has failed.
+    ///
+    pub fn receive(
+        &mut self,
+        code: BackendCode,
+        bytes: &BytesMut,
+    ) -> Result<Option<DivertedResponses>, Error> {
+        match code {
+            BackendCode::DataRow => {
+                let row = DataRow::try_from(bytes)?;
+                let values = row
+                    .columns
+                    .iter()
+                    .map(|column| {
+                        column
+                            .as_bytes()
+                            .map(|b| String::from_utf8_lossy(b).to_string())
+                    })
+                    .collect();
+                self.current.rows.push(values);
+            }
+            BackendCode::CommandComplete => {
+                self.current.command_complete = bytes.clone();
+                self.responses.push(std::mem::take(&mut self.current));
+
+                if self.responses.len() == self.statements && !self.synced {
+                    return Ok(Some(Ok(std::mem::take(&mut self.responses))));
+                }
+            }
+            BackendCode::ErrorResponse if self.synced => self.error = Some(bytes.clone()),
+            BackendCode::ErrorResponse => return Ok(Some(Err(bytes.clone()))),
+            BackendCode::ReadyForQuery if self.synced => {
+                return match self.error.take() {
+                    Some(error) => Ok(Some(Err(error))),
+                    None => Ok(Some(Ok(std::mem::take(&mut self.responses)))),
+                };
+            }
+            // ParseComplete, BindComplete, and any notice or parameter status raised by the statements
+            _ => {}
+        }
+
+        Ok(None)
+    }
+
+    /// Sends the responses to the frontend waiting for them
+    pub fn complete(self, responses: DivertedResponses) {
+        let _ = self.sender.send(responses);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Diversion, DivertedResponse};
+    use crate::postgresql::messages::BackendCode;
+    use bytes::{BufMut, BytesMut};
+
+    fn message(code: u8, body: &[u8]) -> BytesMut {
+        let mut bytes = BytesMut::new();
+        bytes.put_u8(code);
+        bytes.put_i32(body.len() as i32 + 4);
+        bytes.put_slice(body);
+        bytes
+    }
+
+    fn data_row(values: &[Option<&str>]) -> BytesMut {
+        let mut body = BytesMut::new();
+        body.put_i16(values.len() as i16);
+        for value in values {
+            match value {
+                Some(value) => {
+                    body.put_i32(value.len() as i32);
+                    body.put_slice(value.as_bytes());
+                }
+                None => body.put_i32(-1),
+            }
+        }
+        message(b'D', &body)
+    }
+
+    #[test]
+    fn collects_the_rows_of_each_statement() {
+        let (mut diversion, _receiver) = Diversion::new(2);
+
+        let messages = [
+            (BackendCode::ParseComplete, message(b'1', b"")),
+            (BackendCode::BindComplete, message(b'2', b"")),
+            (BackendCode::DataRow, data_row(&[Some("1"), None])),
+            (BackendCode::DataRow, data_row(&[Some("2"), Some("b")])),
+            (BackendCode::CommandComplete, message(b'C', b"SELECT 2\0")),
+            (BackendCode::ParseComplete, message(b'1', b"")),
+            (BackendCode::BindComplete, message(b'2', b"")),
+        ];
+        for (code, bytes) in messages {
+            assert!(diversion.receive(code, &bytes).unwrap().is_none());
+        }
+
+        let responses = diversion
+            .receive(BackendCode::CommandComplete, &message(b'C', b"UPDATE 0\0"))
+            .unwrap()
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(responses.len(), 2);
+        assert_eq!(
+            responses[0].rows,
+            vec![
+                vec![Some("1".to_string()), None],
+                vec![Some("2".to_string()), Some("b".to_string())]
+            ]
+        );
+        assert_eq!(responses[0].row_count(), Some(2));
+        assert!(responses[1].rows.is_empty());
+        assert_eq!(responses[1].row_count(), Some(0));
+    }
+
+    #[test]
+    fn ends_at_the_first_error() {
+        let (mut diversion, _receiver) = Diversion::new(2);
+
+        let error = message(b'E', b"SERROR\0C42501\0Mpermission denied\0\0");
+        let responses = diversion
+            .receive(BackendCode::ErrorResponse, &error)
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(responses.unwrap_err(), error);
+    }
+
+    #[test]
+    fn synced_responses_end_with_ready_for_query() {
+        let (mut diversion, _receiver) = Diversion::synced(1);
+
+        let messages = [
+            (BackendCode::ParseComplete, message(b'1', b"")),
+            (BackendCode::BindComplete, message(b'2', b"")),
+            (BackendCode::DataRow, data_row(&[Some("3")])),
+            (BackendCode::CommandComplete, message(b'C', b"SELECT 1\0")),
+            (BackendCode::CloseComplete, message(b'3', b"")),
+        ];
+        for (code, bytes) in messages {
+            assert!(diversion.receive(code, &bytes).unwrap().is_none());
+        }
+
+        let responses = diversion
+            .receive(BackendCode::ReadyForQuery, &message(b'Z', b"I"))
+            .unwrap()
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(responses.len(), 1);
+        assert_eq!(responses[0].rows, vec![vec![Some("3".to_string())]]);
+    }
+
+    #[test]
+    fn synced_error_ends_with_ready_for_query() {
+        let (mut diversion, _receiver) = Diversion::synced(1);
+
+        let error = message(b'E', b"SERROR\025P02\0Mcurrent transaction is aborted\0\0");
+        assert!(diversion
+            .receive(BackendCode::ErrorResponse, &error)
+            .unwrap()
+            .is_none());
+
+        let responses = diversion
+            .receive(BackendCode::ReadyForQuery, &message(b'Z', b"E"))
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(responses.unwrap_err(), error);
+    }
+
+    #[test]
+    fn row_count_of_insert() {
+        let response = DivertedResponse {
+            rows: vec![],
+            command_complete: message(b'C', b"INSERT 0 3\0"),
+        };
+        assert_eq!(response.row_count(), Some(3));
+    }
+}
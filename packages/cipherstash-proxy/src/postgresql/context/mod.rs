@@ -1,37 +1,51 @@
 pub mod column;
+pub mod diversion;
 pub mod phase_timing;
 pub mod portal;
+pub mod session_parameters;
 pub mod statement;
 pub mod statement_metadata;
-pub use self::{phase_timing::PhaseTiming, portal::Portal, statement::Statement};
+use self::diversion::{Diversion, DivertedResponses};
+pub use self::{
+    phase_timing::{PhaseSpan, PhaseTiming},
+    portal::Portal,
+    session_parameters::SessionParameters,
+    statement::Statement,
+};
 use super::{
     column_mapper::ColumnMapper,
-    messages::{describe::Describe, Name, Target},
+    messages::{describe::Describe, BackendCode, Name, Target},
+    router::{self, Route, Router},
     Column,
 };
 use crate::{
-    config::TandemConfig,
-    error::{EncryptError, Error},
+    config::{DecryptFailurePolicy, TandemConfig},
+    error::{ConfigError, EncryptError, Error, MappingError},
     log::{CONTEXT, SLOW_STATEMENTS},
     prometheus::{
         SLOW_STATEMENTS_TOTAL, STATEMENTS_EXECUTION_DURATION_SECONDS,
-        STATEMENTS_SESSION_DURATION_SECONDS,
+        STATEMENTS_SESSION_DURATION_SECONDS, STATEMENTS_STRICT_REFUSED_TOTAL,
+    },
+    proxy::{
+        ConnectionPermit, EncryptConfig, EncryptionService, Limits, ReloadCommand, ReloadSender,
     },
-    proxy::{EncryptConfig, EncryptionService, ReloadCommand, ReloadSender},
+    telemetry::{self, StatementTrace, TraceParent},
+    tls::ClientIdentity,
 };
+use bytes::BytesMut;
 use cipherstash_client::IdentifiedBy;
 use eql_mapper::{Schema, TableResolver};
 use metrics::{counter, histogram};
 use serde_json::json;
 use sqltk::parser::ast::{Expr, Ident, ObjectName, ObjectNamePart, Set, Value, ValueWithSpan};
-pub use statement_metadata::StatementMetadata;
+pub use statement_metadata::{statement_fingerprint, StatementMetadata};
 use std::{
-    collections::{HashMap, VecDeque},
+    collections::{HashMap, HashSet, VecDeque},
     sync::{
         atomic::{AtomicBool, AtomicU64, Ordering},
         Arc, LazyLock, RwLock,
     },
-    time::{Duration, Instant},
+    time::{Duration, Instant, SystemTime},
 };
 use tokio::sync::oneshot;
 use tracing::{debug, error, warn};
@@ -66,6 +80,8 @@ where
     reload_sender: ReloadSender,
     column_mapper: ColumnMapper,
     statements: Arc<RwLock<HashMap<Name, Arc<Statement>>>>,
+    catalog_statements: Arc<RwLock<HashSet<Name>>>,
+    float_digits_statements: Arc<RwLock<HashSet<Name>>>,
     statement_sessions: Arc<RwLock<HashMap<Name, SessionId>>>,
     portals: Arc<RwLock<HashMap<Name, PortalQueue>>>,
     describe: Arc<RwLock<DescribeQueue>>,
@@ -74,8 +90,16 @@ where
     session_metrics: Arc<RwLock<SessionMetricsQueue>>,
     table_resolver: Arc<TableResolver>,
     unsafe_disable_mapping: bool,
+    decrypt: Arc<AtomicBool>,
+    decrypt_failure_policy: Arc<RwLock<Option<DecryptFailurePolicy>>>,
     keyset_id: Arc<RwLock<Option<KeysetIdentifier>>>,
+    traceparent: Arc<RwLock<Option<TraceParent>>>,
     session_id_counter: Arc<AtomicU64>,
+    client_identity: Option<ClientIdentity>,
+    router: Option<Arc<Router>>,
+    limits: Limits,
+    session_parameters: Arc<RwLock<SessionParameters>>,
+    diversion: Arc<RwLock<Option<Diversion>>>,
 }
 
 /// Context for tracking an in-flight Execute operation.
@@ -89,9 +113,9 @@ pub struct ExecuteContext {
     name: Name,
     start: Instant,
     session_id: Option<SessionId>,
-    /// Server wait duration (time to first response byte).
+    /// Server wait (time to first response byte).
     /// Accumulated here during execution, transferred to SessionMetricsContext on completion.
-    server_wait_duration: Option<Duration>,
+    server_wait: Option<PhaseSpan>,
     /// Server response duration (time spent receiving response data after first byte).
     /// Accumulated here during execution, transferred to SessionMetricsContext on completion.
     server_response_duration: Duration,
@@ -103,7 +127,7 @@ impl ExecuteContext {
             name,
             start: Instant::now(),
             session_id,
-            server_wait_duration: None,
+            server_wait: None,
             server_response_duration: Duration::from_secs(0),
         }
     }
@@ -113,15 +137,15 @@ impl ExecuteContext {
     }
 
     fn record_server_wait_or_add_response(&mut self, duration: Duration) {
-        if self.server_wait_duration.is_none() {
-            self.server_wait_duration = Some(duration);
+        if self.server_wait.is_none() {
+            self.server_wait = Some(PhaseSpan::ended_now(duration));
         } else {
             self.server_response_duration += duration;
         }
     }
 
-    fn server_wait_duration(&self) -> Option<Duration> {
-        self.server_wait_duration
+    fn server_wait(&self) -> Option<PhaseSpan> {
+        self.server_wait
     }
 
     fn server_response_duration(&self) -> Duration {
@@ -137,17 +161,21 @@ impl ExecuteContext {
 pub struct SessionMetricsContext {
     id: SessionId,
     start: Instant,
+    started_at: SystemTime,
     pub phase_timing: PhaseTiming,
     pub metadata: StatementMetadata,
+    pub traceparent: Option<TraceParent>,
 }
 
 impl SessionMetricsContext {
-    fn new(id: SessionId) -> SessionMetricsContext {
+    fn new(id: SessionId, traceparent: Option<TraceParent>) -> SessionMetricsContext {
         SessionMetricsContext {
             id,
             start: Instant::now(),
+            started_at: SystemTime::now(),
             phase_timing: PhaseTiming::new(),
             metadata: StatementMetadata::new(),
+            traceparent,
         }
     }
 
@@ -181,6 +209,8 @@ where
 
         Context {
             statements: Arc::new(RwLock::new(HashMap::new())),
+            catalog_statements: Arc::new(RwLock::new(HashSet::new())),
+            float_digits_statements: Arc::new(RwLock::new(HashSet::new())),
             statement_sessions: Arc::new(RwLock::new(HashMap::new())),
             portals: Arc::new(RwLock::new(HashMap::new())),
             describe: Arc::new(RwLock::from(Queue::new())),
@@ -195,8 +225,16 @@ where
             encryption,
             reload_sender,
             unsafe_disable_mapping: false,
+            decrypt: Arc::new(AtomicBool::new(true)),
+            decrypt_failure_policy: Arc::new(RwLock::new(None)),
             keyset_id: Arc::new(RwLock::new(None)),
+            traceparent: Arc::new(RwLock::new(None)),
             session_id_counter: Arc::new(AtomicU64::new(1)),
+            client_identity: None,
+            router: None,
+            limits: Limits::default(),
+            session_parameters: Arc::new(RwLock::new(SessionParameters::default())),
+            diversion: Arc::new(RwLock::new(None)),
         }
     }
 
@@ -215,7 +253,8 @@ where
 
     pub fn start_session(&mut self) -> SessionId {
         let id = SessionId(self.session_id_counter.fetch_add(1, Ordering::Relaxed));
-        let ctx = SessionMetricsContext::new(id);
+        let traceparent = self.traceparent.read().ok().and_then(|guard| *guard);
+        let ctx = SessionMetricsContext::new(id, traceparent);
         let _ = self.session_metrics.write().map(|mut queue| queue.add(ctx));
         id
     }
@@ -281,11 +320,33 @@ where
                     param_bytes = metadata.param_bytes,
                     query_fingerprint = ?metadata.query_fingerprint,
                     keyset_id = ?self.keyset_identifier(),
+                    client_identity = self.client_identity.as_ref().map(|identity| identity.name()),
                     mapping_disabled = self.mapping_disabled(),
                     breakdown = %breakdown,
                     msg = "Slow statement detected"
                 );
             }
+
+            if self.config.telemetry_enabled() {
+                let timing = &session.phase_timing;
+
+                telemetry::record_statement(&StatementTrace {
+                    client_id: self.client_id,
+                    started_at: session.started_at,
+                    duration,
+                    statement_type,
+                    protocol,
+                    encrypted: metadata.encrypted,
+                    encrypted_values_count: metadata.encrypted_values_count,
+                    parse: timing.parse_span,
+                    encrypt: timing.encrypt_span,
+                    encrypt_requests: timing.encrypt_requests,
+                    server_wait: timing.server_wait_span,
+                    decrypt: timing.decrypt_span,
+                    decrypt_requests: timing.decrypt_requests,
+                    traceparent: session.traceparent,
+                });
+            }
         }
 
         let _ = self
@@ -329,7 +390,7 @@ where
     /// Marks the current Execution as Complete.
     ///
     /// Transfers accumulated timing data from ExecuteContext to SessionMetricsContext.phase_timing:
-    /// - `server_wait_duration` (time to first response byte) is recorded to the session
+    /// - `server_wait` (time to first response byte) is recorded to the session
     /// - `server_response_duration` (time receiving response data) is added to the session
     ///
     /// This two-phase timing pattern exists because the backend operates on the execute queue
@@ -348,8 +409,8 @@ where
 
         if let Some(execute) = self.get_execute() {
             if let Some(session_id) = execute.session_id() {
-                if let Some(wait) = execute.server_wait_duration() {
-                    self.record_server_wait_duration(session_id, wait);
+                if let Some(wait) = execute.server_wait() {
+                    self.record_server_wait(session_id, wait);
                 }
                 let response = execute.server_response_duration();
                 if !response.is_zero() {
@@ -415,6 +476,35 @@ where
             .statement_sessions
             .write()
             .map(|mut guarded| guarded.remove(name));
+
+        let _ = self
+            .catalog_statements
+            .write()
+            .map(|mut guarded| guarded.remove(name));
+
+        let _ = self
+            .float_digits_statements
+            .write()
+            .map(|mut guarded| guarded.remove(name));
+    }
+
+    ///
+    /// Records a prepared statement as a catalog introspection query
+    /// Portals bound to the statement have their rows rewritten, see [`crate::postgresql::catalog`]
+    ///
+    pub fn add_catalog_statement(&mut self, name: Name) {
+        debug!(target: CONTEXT, client_id = self.client_id, catalog_statement = ?name);
+        let _ = self
+            .catalog_statements
+            .write()
+            .map(|mut guarded| guarded.insert(name));
+    }
+
+    pub fn is_catalog_statement(&self, name: &Name) -> bool {
+        self.catalog_statements
+            .read()
+            .map(|guarded| guarded.contains(name))
+            .unwrap_or(false)
     }
 
     /// Close both statement and its associated portal.
@@ -499,7 +589,7 @@ where
 
         match portal.as_ref() {
             Portal::Encrypted { statement, .. } => Some(statement.clone()),
-            Portal::Passthrough { .. } => None,
+            Portal::Passthrough { .. } | Portal::Catalog { .. } => None,
         }
     }
 
@@ -511,6 +601,13 @@ where
     }
 
     pub fn get_statement_for_row_decription(&self) -> Option<Arc<Statement>> {
+        // With CIPHERSTASH.DECRYPT = off a described statement returns the stored
+        // payload, so the RowDescription is not rewritten.
+        // Portals are only Encrypted when decryption was enabled at Bind.
+        if !self.decrypt_enabled() && self.describe_targets_statement() {
+            return None;
+        }
+
         if let Some(statement) = self.get_statement_from_describe() {
             return Some(statement.clone());
         }
@@ -523,6 +620,18 @@ where
         None
     }
 
+    fn describe_targets_statement(&self) -> bool {
+        self.describe
+            .read()
+            .ok()
+            .and_then(|queue| {
+                queue
+                    .next()
+                    .map(|describe| matches!(describe.target, Target::Statement))
+            })
+            .unwrap_or(false)
+    }
+
     pub fn get_statement_from_describe(&self) -> Option<Arc<Statement>> {
         let queue = self.describe.read().ok()?;
         let describe = queue.next()?;
@@ -585,8 +694,8 @@ where
     pub fn maybe_set_unsafe_disable_mapping(
         &mut self,
         statement: &sqltk::parser::ast::Statement,
-    ) -> Option<bool> {
-        // The CIPHERSTASH. namespace prevents errors UNSAFE_DISABLE_MAPPING
+    ) -> Result<Option<bool>, Error> {
+        // Postgres accepts any namespaced setting, so SET CIPHERSTASH.UNSAFE_DISABLE_MAPPING does not fail on the server
         // The constants avoid the need to allocate Vecs every time we examine the statement.
         static SQL_SETTING_NAME_UNSAFE_DISABLE_MAPPING: LazyLock<ObjectName> =
             LazyLock::new(|| {
@@ -601,17 +710,22 @@ where
         }) = statement
         {
             if variable == &*SQL_SETTING_NAME_UNSAFE_DISABLE_MAPPING {
+                if self.strict_mapping_enabled() {
+                    counter!(STATEMENTS_STRICT_REFUSED_TOTAL).increment(1);
+                    return Err(MappingError::StrictModeDisableMapping.into());
+                }
+
                 if let Some(Expr::Value(ValueWithSpan {
                     value: Value::Boolean(value),
                     ..
                 })) = values.first()
                 {
                     self.unsafe_disable_mapping = *value;
-                    return Some(*value);
+                    return Ok(Some(*value));
                 }
             }
         }
-        None
+        Ok(None)
     }
 
     pub fn unsafe_disable_mapping(&mut self) -> bool {
@@ -626,7 +740,19 @@ where
         &mut self,
         statement: &sqltk::parser::ast::Statement,
     ) -> Result<Option<KeysetIdentifier>, Error> {
-        // The CIPHERSTASH. namespace prevents errors KEYSET_ID
+        let identifier = self.keyset_id_in(statement)?;
+        if let Some(identifier) = &identifier {
+            self.set_keyset_identifier(identifier.clone());
+        }
+        Ok(identifier)
+    }
+
+    /// The keyset of a `SET CIPHERSTASH.KEYSET_ID = {keyset_id};` statement, without setting it.
+    fn keyset_id_in(
+        &self,
+        statement: &sqltk::parser::ast::Statement,
+    ) -> Result<Option<KeysetIdentifier>, Error> {
+        // Postgres accepts any namespaced setting, so SET CIPHERSTASH.KEYSET_ID does not fail on the server
         // The constants avoid the need to allocate Vecs every time we examine the statement.
         static SQL_SETTING_NAME_KEYSET_ID: LazyLock<ObjectName> = LazyLock::new(|| {
             ObjectName(vec![
@@ -656,15 +782,7 @@ where
                         }
                     })?;
 
-                    debug!(target: CONTEXT, client_id = self.client_id, msg = "Set KeysetId", ?keyset_id);
-
-                    let identifier = KeysetIdentifier(IdentifiedBy::Uuid(keyset_id));
-                    let _ = self
-                        .keyset_id
-                        .write()
-                        .map(|mut guard| *guard = Some(identifier.clone()));
-
-                    return Ok(Some(identifier));
+                    return Ok(Some(KeysetIdentifier(IdentifiedBy::Uuid(keyset_id))));
                 } else {
                     let err = EncryptError::KeysetIdCouldNotBeSet;
                     warn!(target: CONTEXT, client_id = self.client_id, msg = err.to_string());
@@ -683,7 +801,19 @@ where
         &mut self,
         statement: &sqltk::parser::ast::Statement,
     ) -> Result<Option<KeysetIdentifier>, Error> {
-        // The CIPHERSTASH. namespace prevents errors KEYSET_NAME
+        let identifier = self.keyset_name_in(statement)?;
+        if let Some(identifier) = &identifier {
+            self.set_keyset_identifier(identifier.clone());
+        }
+        Ok(identifier)
+    }
+
+    /// The keyset of a `SET CIPHERSTASH.KEYSET_NAME = {keyset_name};` statement, without setting it.
+    fn keyset_name_in(
+        &self,
+        statement: &sqltk::parser::ast::Statement,
+    ) -> Result<Option<KeysetIdentifier>, Error> {
+        // Postgres accepts any namespaced setting, so SET CIPHERSTASH.KEYSET_NAME does not fail on the server
         // The constants avoid the need to allocate Vecs every time we examine the statement.
         static SQL_SETTING_NAME_KEYSET_NAME: LazyLock<ObjectName> = LazyLock::new(|| {
             ObjectName(vec![
@@ -711,15 +841,9 @@ where
                 };
 
                 if let Some(keyset_name) = keyset_name {
-                    debug!(target: CONTEXT, client_id = self.client_id, msg = "Set KeysetName", ?keyset_name);
-
-                    let identifier = KeysetIdentifier(IdentifiedBy::Name(keyset_name.into()));
-                    let _ = self
-                        .keyset_id
-                        .write()
-                        .map(|mut guard| *guard = Some(identifier.clone()));
-
-                    return Ok(Some(identifier));
+                    return Ok(Some(KeysetIdentifier(IdentifiedBy::Name(
+                        keyset_name.into(),
+                    ))));
                 } else {
                     let err = EncryptError::KeysetNameCouldNotBeSet;
                     warn!(target: CONTEXT, client_id = self.client_id, msg = err.to_string());
@@ -730,29 +854,497 @@ where
         Ok(None)
     }
 
+    fn set_keyset_identifier(&mut self, identifier: KeysetIdentifier) {
+        debug!(target: CONTEXT, client_id = self.client_id, msg = "Set Keyset", ?identifier);
+
+        let _ = self
+            .keyset_id
+            .write()
+            .map(|mut guard| *guard = Some(identifier));
+    }
+
+    /// Examines a [`sqltk::parser::ast::Statement`] and if it is precisely equal to `SET CIPHERSTASH.TRACEPARENT = {traceparent};`
+    /// then statements on this connection are traced as children of `{traceparent}`.
+    ///
+    /// An invalid traceparent clears the parent, and statements are traced as new traces.
+    ///
+    pub fn maybe_set_traceparent(
+        &mut self,
+        statement: &sqltk::parser::ast::Statement,
+    ) -> Option<TraceParent> {
+        // Postgres accepts any namespaced setting, so SET CIPHERSTASH.TRACEPARENT does not fail on the server
+        // The constants avoid the need to allocate Vecs every time we examine the statement.
+        static SQL_SETTING_NAME_TRACEPARENT: LazyLock<ObjectName> = LazyLock::new(|| {
+            ObjectName(vec![
+                ObjectNamePart::Identifier(Ident::new("CIPHERSTASH")),
+                ObjectNamePart::Identifier(Ident::new("TRACEPARENT")),
+            ])
+        });
+
+        if let sqltk::parser::ast::Statement::Set(Set::SingleAssignment {
+            variable, values, ..
+        }) = statement
+        {
+            if variable == &*SQL_SETTING_NAME_TRACEPARENT {
+                let traceparent = match values.first() {
+                    Some(Expr::Value(ValueWithSpan {
+                        value: Value::SingleQuotedString(s),
+                        ..
+                    })) => TraceParent::parse(s),
+                    _ => None,
+                };
+
+                if traceparent.is_none() {
+                    warn!(target: CONTEXT, client_id = self.client_id, msg = "Invalid CIPHERSTASH.TRACEPARENT, statements will not be traced with a parent");
+                }
+
+                debug!(target: CONTEXT, client_id = self.client_id, msg = "Set TraceParent", ?traceparent);
+                let _ = self
+                    .traceparent
+                    .write()
+                    .map(|mut guard| *guard = traceparent);
+
+                return traceparent;
+            }
+        }
+        None
+    }
+
+    /// Examines a [`sqltk::parser::ast::Statement`] and if it is `SET CIPHERSTASH.DECRYPT = {on|off};`
+    /// then it sets the [`Context::decrypt`] flag.
+    ///
+    /// With decryption off, encrypted columns are returned as the stored EQL payload.
+    /// Parameters and literals are still encrypted.
+    ///
+    pub fn maybe_set_decrypt(&self, statement: &sqltk::parser::ast::Statement) -> Option<bool> {
+        // The constants avoid the need to allocate Vecs every time we examine the statement.
+        static SQL_SETTING_NAME_DECRYPT: LazyLock<ObjectName> = LazyLock::new(|| {
+            ObjectName(vec![
+                ObjectNamePart::Identifier(Ident::new("CIPHERSTASH")),
+                ObjectNamePart::Identifier(Ident::new("DECRYPT")),
+            ])
+        });
+
+        if let sqltk::parser::ast::Statement::Set(Set::SingleAssignment {
+            variable, values, ..
+        }) = statement
+        {
+            if variable == &*SQL_SETTING_NAME_DECRYPT {
+                let decrypt = match values.first() {
+                    Some(Expr::Value(ValueWithSpan {
+                        value: Value::Boolean(value),
+                        ..
+                    })) => Some(*value),
+                    Some(Expr::Identifier(ident)) => parse_on_off(&ident.value),
+                    Some(Expr::Value(ValueWithSpan {
+                        value: Value::SingleQuotedString(s),
+                        ..
+                    })) => parse_on_off(s),
+                    _ => None,
+                };
+
+                match decrypt {
+                    Some(decrypt) => {
+                        debug!(target: CONTEXT, client_id = self.client_id, msg = "Set CIPHERSTASH.DECRYPT", decrypt);
+                        self.decrypt.store(decrypt, Ordering::Release);
+                    }
+                    None => {
+                        warn!(target: CONTEXT, client_id = self.client_id, msg = "Invalid CIPHERSTASH.DECRYPT, expected on or off");
+                    }
+                }
+
+                return decrypt;
+            }
+        }
+        None
+    }
+
+    /// Returns false when the session has disabled decryption with `SET CIPHERSTASH.DECRYPT = off`.
+    pub fn decrypt_enabled(&self) -> bool {
+        self.decrypt.load(Ordering::Acquire)
+    }
+
+    /// Examines a [`sqltk::parser::ast::Statement`] and if it is precisely equivalent
+    /// to `SET CIPHERSTASH.DECRYPT_FAILURE = {fail|null|sentinel};`,
+    /// then it sets the decryption failure policy of the session.
+    ///
+    pub fn maybe_set_decrypt_failure_policy(
+        &self,
+        statement: &sqltk::parser::ast::Statement,
+    ) -> Option<DecryptFailurePolicy> {
+        static SQL_SETTING_NAME_DECRYPT_FAILURE: LazyLock<ObjectName> = LazyLock::new(|| {
+            ObjectName(vec![
+                ObjectNamePart::Identifier(Ident::new("CIPHERSTASH")),
+                ObjectNamePart::Identifier(Ident::new("DECRYPT_FAILURE")),
+            ])
+        });
+
+        if let sqltk::parser::ast::Statement::Set(Set::SingleAssignment {
+            variable, values, ..
+        }) = statement
+        {
+            if variable == &*SQL_SETTING_NAME_DECRYPT_FAILURE {
+                let policy = match values.first() {
+                    Some(Expr::Value(ValueWithSpan {
+                        value: Value::Null, ..
+                    })) => Some(DecryptFailurePolicy::Null),
+                    Some(Expr::Identifier(ident)) => ident.value.parse().ok(),
+                    Some(Expr::Value(ValueWithSpan {
+                        value: Value::SingleQuotedString(s),
+                        ..
+                    })) => s.parse().ok(),
+                    _ => None,
+                };
+
+                match policy {
+                    Some(policy) => {
+                        debug!(target: CONTEXT, client_id = self.client_id, msg = "Set CIPHERSTASH.DECRYPT_FAILURE", %policy);
+                        let _ = self
+                            .decrypt_failure_policy
+                            .write()
+                            .map(|mut current| *current = Some(policy));
+                    }
+                    None => {
+                        warn!(target: CONTEXT, client_id = self.client_id, msg = "Invalid CIPHERSTASH.DECRYPT_FAILURE, expected fail, null or sentinel");
+                    }
+                }
+
+                return policy;
+            }
+        }
+        None
+    }
+
+    ///
+    /// The decryption failure policy of the session, or `server.decrypt_failure_policy`
+    ///
+    pub fn decrypt_failure_policy(&self) -> DecryptFailurePolicy {
+        self.decrypt_failure_policy
+            .read()
+            .ok()
+            .and_then(|policy| *policy)
+            .unwrap_or(self.config.server.decrypt_failure_policy)
+    }
+
+    pub fn decrypt_failure_sentinel(&self) -> &str {
+        &self.config.server.decrypt_failure_sentinel
+    }
+
+    ///
+    /// Records a statement that may change `extra_float_digits`, see [`may_change_extra_float_digits`]
+    ///
+    /// Postgres does not report `extra_float_digits` with a `ParameterStatus` message,
+    /// so it is read from the database again before decrypted floats are next formatted.
+    ///
+    pub fn extra_float_digits_may_change(&self) {
+        let _ = self.session_parameters.write().map(|mut parameters| {
+            if !parameters.extra_float_digits_stale {
+                debug!(target: CONTEXT, client_id = self.client_id, msg = "extra_float_digits may change");
+            }
+            parameters.extra_float_digits_may_change();
+        });
+    }
+
+    ///
+    /// Records `extra_float_digits` read from the database
+    ///
+    pub fn read_extra_float_digits(&self, value: &str) {
+        let _ = self.session_parameters.write().map(|mut parameters| {
+            if parameters.read_extra_float_digits(value) {
+                debug!(target: CONTEXT, client_id = self.client_id, msg = "Read extra_float_digits", value);
+            }
+        });
+    }
+
+    ///
+    /// Records a prepared statement that may change `extra_float_digits` each time it is executed
+    ///
+    pub fn add_float_digits_statement(&mut self, name: Name) {
+        let _ = self
+            .float_digits_statements
+            .write()
+            .map(|mut guarded| guarded.insert(name));
+    }
+
+    pub fn is_float_digits_statement(&self, name: &Name) -> bool {
+        self.float_digits_statements
+            .read()
+            .map(|guarded| guarded.contains(name))
+            .unwrap_or(false)
+    }
+
+    ///
+    /// Traces the statement as a child of the `traceparent` in a sqlcommenter style comment, if there is one.
+    /// Takes precedence over `SET CIPHERSTASH.TRACEPARENT` for this statement only.
+    ///
+    pub fn set_statement_traceparent(&mut self, session_id: SessionId, sql: &str) {
+        if let Some(traceparent) = TraceParent::from_sql_comment(sql) {
+            self.with_session_metrics_mut(session_id, |m| m.traceparent = Some(traceparent));
+        }
+    }
+
     /// Single entry point for setting keyset identifiers by either ID or name.
     /// Tries to set keyset_id first, then keyset_name if that doesn't match.
     ///
+    /// If the keyset is set by the client certificate it cannot be changed, and the statement is an error.
+    /// The statement is validated before the keyset is written, so on error the keyset is unchanged.
+    ///
     pub fn maybe_set_keyset(
         &mut self,
         statement: &sqltk::parser::ast::Statement,
     ) -> Result<Option<KeysetIdentifier>, Error> {
-        match self.maybe_set_keyset_id(statement)? {
-            Some(identifier) => Ok(Some(identifier)),
-            None => self.maybe_set_keyset_name(statement),
+        let identifier = match self.keyset_id_in(statement)? {
+            Some(identifier) => identifier,
+            None => match self.keyset_name_in(statement)? {
+                Some(identifier) => identifier,
+                None => return Ok(None),
+            },
+        };
+
+        // Validated before the keyset is written, so a refused statement never changes it
+        if self.keyset_from_client_certificate() {
+            return Err(EncryptError::UnexpectedSetKeysetWithClientCertificate.into());
+        }
+
+        self.set_keyset_identifier(identifier.clone());
+
+        Ok(Some(identifier))
+    }
+
+    ///
+    /// Records the identity of the client certificate for the session
+    ///
+    /// If `server.keyset_from_client_certificate` is enabled, the identity is also the session keyset name.
+    ///
+    pub fn set_client_identity(&mut self, identity: ClientIdentity) {
+        debug!(target: CONTEXT, client_id = self.client_id, client_identity = %identity);
+
+        if self.keyset_from_client_certificate() {
+            let identifier = KeysetIdentifier(IdentifiedBy::Name(identity.name().into()));
+            let _ = self
+                .keyset_id
+                .write()
+                .map(|mut guard| *guard = Some(identifier));
+        }
+
+        self.client_identity = Some(identity);
+    }
+
+    ///
+    /// Records the identity of the client certificate the client connected with, if any
+    ///
+    /// A client without a certificate, or with a certificate whose identity cannot be read, is an error if a
+    /// certificate is required. If the keyset is set by the certificate the client would otherwise use the default keyset, so a
+    /// certificate is required.
+    ///
+    pub fn check_client_identity(&mut self, identity: Option<ClientIdentity>) -> Result<(), Error> {
+        match identity {
+            Some(identity) => {
+                self.set_client_identity(identity);
+                Ok(())
+            }
+            None if self.require_client_certificate() || self.keyset_from_client_certificate() => {
+                Err(ConfigError::ClientCertificateRequired.into())
+            }
+            None => Ok(()),
         }
     }
 
+    pub fn client_identity(&self) -> Option<&ClientIdentity> {
+        self.client_identity.as_ref()
+    }
+
+    pub fn set_router(&mut self, router: Arc<Router>) {
+        self.router = Some(router);
+    }
+
+    ///
+    /// The connection for a simple query, which is the primary unless the statements can be read from a replica
+    ///
+    pub fn route_statements(&self, statements: &[sqltk::parser::ast::Statement]) -> Route {
+        self.router
+            .as_ref()
+            .map_or(Route::Primary, |router| router.route(statements))
+    }
+
+    ///
+    /// Records writes and session changes in statements sent to the primary by the extended protocol
+    ///
+    pub fn observe_statements(&self, statements: &[sqltk::parser::ast::Statement]) {
+        if let Some(router) = &self.router {
+            router.observe(statements);
+        }
+    }
+
+    ///
+    /// Makes the route the active database connection before the messages in `bytes` are written
+    /// Returns the route that was used
+    ///
+    pub async fn route_message(&self, route: Route, bytes: &[u8]) -> Route {
+        match &self.router {
+            Some(router) => {
+                let route = router.switch(route).await;
+                router.sent(bytes);
+                route
+            }
+            None => Route::Primary,
+        }
+    }
+
+    ///
+    /// Waits until the server has answered every request sent to it
+    ///
+    /// Returns false without waiting when the client has an extended protocol batch open without a Sync.
+    ///
+    pub async fn wait_until_server_idle(&self) -> bool {
+        match &self.router {
+            Some(router) => router.wait_until_idle().await,
+            None => true,
+        }
+    }
+
+    ///
+    /// Collects the responses to the next `statements` the frontend sends, instead of sending them to the client
+    ///
+    pub fn divert_responses(&self, statements: usize) -> oneshot::Receiver<DivertedResponses> {
+        self.start_diversion(Diversion::new(statements))
+    }
+
+    ///
+    /// Collects the responses to the next `statements` the frontend sends with a Sync, and the ReadyForQuery
+    /// that answers the Sync, instead of sending them to the client
+    ///
+    pub fn divert_synced_responses(
+        &self,
+        statements: usize,
+    ) -> oneshot::Receiver<DivertedResponses> {
+        self.start_diversion(Diversion::synced(statements))
+    }
+
+    fn start_diversion(
+        &self,
+        (diversion, receiver): (Diversion, oneshot::Receiver<DivertedResponses>),
+    ) -> oneshot::Receiver<DivertedResponses> {
+        let _ = self
+            .diversion
+            .write()
+            .map(|mut current| *current = Some(diversion));
+        receiver
+    }
+
+    ///
+    /// Adds a message from the server to the responses being collected
+    ///
+    /// Returns true if the message was collected and must not be sent to the client.
+    ///
+    pub fn divert(&self, code: BackendCode, bytes: &BytesMut) -> Result<bool, Error> {
+        let Ok(mut current) = self.diversion.write() else {
+            return Ok(false);
+        };
+        let Some(diversion) = current.as_mut() else {
+            return Ok(false);
+        };
+
+        // The ReadyForQuery answers a Sync the frontend sent, and is counted as if the client had sent it
+        if code == BackendCode::ReadyForQuery {
+            self.ready_for_query(bytes.get(5).copied().unwrap_or_default());
+        }
+
+        if let Some(responses) = diversion.receive(code, bytes)? {
+            if let Some(diversion) = current.take() {
+                diversion.complete(responses);
+            }
+        }
+
+        Ok(true)
+    }
+
+    pub fn ready_for_query(&self, transaction_status: u8) {
+        if let Some(router) = &self.router {
+            router.ready_for_query(transaction_status);
+        }
+
+        let _ = self
+            .session_parameters
+            .write()
+            .map(|mut parameters| parameters.ready_for_query(transaction_status));
+    }
+
+    ///
+    /// Records an `ErrorResponse`, so session parameter changes made in the failed transaction are undone
+    ///
+    pub fn statement_failed(&self) {
+        let _ = self
+            .session_parameters
+            .write()
+            .map(|mut parameters| parameters.statement_failed());
+    }
+
+    fn keyset_from_client_certificate(&self) -> bool {
+        self.config.server.keyset_from_client_certificate
+    }
+
     pub fn keyset_identifier(&self) -> Option<KeysetIdentifier> {
         self.keyset_id.read().ok().and_then(|k| k.clone())
     }
 
+    ///
+    /// Records a session parameter reported by the database, or sent by the client
+    ///
+    pub fn set_session_parameter(&self, name: &str, value: &str) {
+        let _ = self.session_parameters.write().map(|mut parameters| {
+            if parameters.set(name, value) {
+                debug!(target: CONTEXT, client_id = self.client_id, msg = "Set session parameter", name, value);
+            }
+        });
+    }
+
+    ///
+    /// The session parameters used to encode decrypted values for the client
+    ///
+    pub fn session_parameters(&self) -> SessionParameters {
+        self.session_parameters
+            .read()
+            .map(|parameters| parameters.clone())
+            .unwrap_or_default()
+    }
+
+    pub fn set_limits(&mut self, limits: Limits) {
+        self.limits = limits;
+    }
+
+    ///
+    /// Counts the connection against the total connection limit
+    /// The connection is counted until the permit is dropped
+    ///
+    pub fn acquire_connection(&self) -> Result<ConnectionPermit, Error> {
+        self.limits.acquire_connection()
+    }
+
+    ///
+    /// Counts statements against the statement rate limit of the current keyset
+    ///
+    pub fn check_statement_rate(&self, count: usize) -> Result<(), Error> {
+        self.limits.check_statements(&self.keyset_label(), count)
+    }
+
+    ///
+    /// The keyset rate limits are tracked by, using `default` for the default keyset
+    ///
+    fn keyset_label(&self) -> String {
+        self.keyset_identifier()
+            .map_or_else(|| "default".to_string(), |keyset_id| keyset_id.to_string())
+    }
+
     // Service delegation methods
     pub async fn encrypt(
         &self,
         plaintexts: Vec<Option<cipherstash_client::encryption::Plaintext>>,
         columns: &[Option<Column>],
     ) -> Result<Vec<Option<crate::EqlOutput>>, Error> {
+        let count = plaintexts.iter().flatten().count();
+        self.limits.check_values(&self.keyset_label(), count)?;
+
         let keyset_id = self.keyset_identifier();
 
         self.encryption
@@ -764,6 +1356,9 @@ where
         &self,
         ciphertexts: Vec<Option<crate::EqlCiphertext>>,
     ) -> Result<Vec<Option<cipherstash_client::encryption::Plaintext>>, Error> {
+        let count = ciphertexts.iter().flatten().count();
+        self.limits.check_values(&self.keyset_label(), count)?;
+
         let keyset_id = self.keyset_identifier();
         self.encryption.decrypt(keyset_id, ciphertexts).await
     }
@@ -845,10 +1440,42 @@ where
         self.config.mapping_errors_enabled()
     }
 
+    pub fn proxy_aggregates_enabled(&self) -> bool {
+        self.config.proxy_aggregates_enabled()
+    }
+
+    pub fn strict_mapping_enabled(&self) -> bool {
+        self.config.mapping.strict
+    }
+
+    pub fn strict_allowlist_contains(&self, fingerprint: &str) -> bool {
+        self.config.mapping.strict_allowlist_contains(fingerprint)
+    }
+
+    pub fn catalog_masquerade_enabled(&self) -> bool {
+        self.config.mapping.catalog_masquerade
+    }
+
+    pub fn proxy_aggregate_max_values(&self) -> usize {
+        self.config.mapping.proxy_aggregate_max_values
+    }
+
+    pub fn proxy_aggregate_max_bytes(&self) -> usize {
+        self.config.mapping.proxy_aggregate_max_bytes
+    }
+
+    pub fn plaintext_source_max_rows(&self) -> usize {
+        self.config.mapping.plaintext_source_max_rows
+    }
+
     pub fn slow_db_response_min_duration(&self) -> std::time::Duration {
         self.config.slow_db_response_min_duration()
     }
 
+    pub fn bind_batch_size(&self) -> usize {
+        self.config.server.bind_batch_size
+    }
+
     pub fn prometheus_enabled(&self) -> bool {
         self.config.prometheus_enabled()
     }
@@ -873,18 +1500,14 @@ where
         self.config.database.password()
     }
 
-    pub fn tls_config(&self) -> &Option<crate::config::TlsConfig> {
-        &self.config.tls
-    }
-
-    pub fn use_tls(&self) -> bool {
-        self.config.tls.is_some()
-    }
-
     pub fn require_tls(&self) -> bool {
         self.config.server.require_tls
     }
 
+    pub fn require_client_certificate(&self) -> bool {
+        self.config.server.require_client_certificate
+    }
+
     pub fn use_structured_logging(&self) -> bool {
         self.config.use_structured_logging()
     }
@@ -945,10 +1568,10 @@ where
         });
     }
 
-    /// Record server wait phase duration (time to first response byte)
-    pub fn record_server_wait_duration(&mut self, session_id: SessionId, duration: Duration) {
+    /// Record server wait phase (time to first response byte)
+    pub fn record_server_wait(&mut self, session_id: SessionId, span: PhaseSpan) {
         self.with_session_metrics_mut(session_id, |session| {
-            session.phase_timing.record_server_wait(duration);
+            session.phase_timing.record_server_wait(span);
         });
     }
 
@@ -980,10 +1603,10 @@ where
         });
     }
 
-    /// Add decrypt phase duration (accumulate)
-    pub fn add_decrypt_duration(&mut self, session_id: SessionId, duration: Duration) {
+    /// Add decrypt phase (accumulate)
+    pub fn add_decrypt(&mut self, session_id: SessionId, span: PhaseSpan) {
         self.with_session_metrics_mut(session_id, |session| {
-            session.phase_timing.add_decrypt(duration);
+            session.phase_timing.add_decrypt(span);
         });
     }
 
@@ -1016,11 +1639,11 @@ where
         }
     }
 
-    /// Add decrypt phase duration for the current execute session (if any)
-    pub fn add_decrypt_duration_for_execute(&mut self, duration: Duration) {
+    /// Add decrypt phase for the current execute session (if any)
+    pub fn add_decrypt_for_execute(&mut self, span: PhaseSpan) {
         let session_id = self.get_execute().and_then(|execute| execute.session_id());
         if let Some(session_id) = session_id {
-            self.add_decrypt_duration(session_id, duration);
+            self.add_decrypt(session_id, span);
         }
     }
 
@@ -1033,6 +1656,45 @@ where
     }
 }
 
+/// Parses a Postgres boolean setting value such as `on`, `off`, `true` or `false`.
+fn parse_on_off(value: &str) -> Option<bool> {
+    match value.to_ascii_lowercase().as_str() {
+        "on" | "true" => Some(true),
+        "off" | "false" => Some(false),
+        _ => None,
+    }
+}
+
+///
+/// Returns true if the statement may change `extra_float_digits`
+///
+/// Besides `SET` and `RESET`, the value is changed by `set_config`, which may be called from any function,
+/// and by `ROLLBACK`, `ROLLBACK TO SAVEPOINT` and `DISCARD ALL`. Statements are allowed by kind,
+/// so anything unrecognised may change it.
+///
+pub fn may_change_extra_float_digits(statement: &sqltk::parser::ast::Statement) -> bool {
+    use sqltk::parser::ast::Statement;
+
+    match statement {
+        Statement::Set(Set::SingleAssignment { variable, .. }) => matches!(
+            variable.0.as_slice(),
+            [ObjectNamePart::Identifier(ident)] if ident.value.eq_ignore_ascii_case("extra_float_digits")
+        ),
+        Statement::Query(_)
+        | Statement::Insert(_)
+        | Statement::Update { .. }
+        | Statement::Delete(_)
+        | Statement::Merge { .. } => !router::calls_read_only_functions(statement),
+        Statement::StartTransaction { .. }
+        | Statement::Prepare { .. }
+        | Statement::CreateTable(_)
+        | Statement::CreateView { .. }
+        | Statement::AlterTable { .. }
+        | Statement::Drop { .. } => false,
+        _ => true,
+    }
+}
+
 impl<T> Queue<T> {
     pub fn new() -> Self {
         Queue {
@@ -1060,18 +1722,23 @@ impl<T> Queue<T> {
 
 #[cfg(test)]
 mod tests {
-    use super::{Context, Describe, KeysetIdentifier, Portal, Statement};
+    use super::{
+        may_change_extra_float_digits, session_parameters::DateOutput, Context, Describe,
+        KeysetIdentifier, Portal, Statement, TraceParent,
+    };
     use crate::{
-        config::LogConfig,
-        error::Error,
+        config::{DecryptFailurePolicy, LogConfig},
+        error::{ConfigError, EncryptError, Error, MappingError},
         log,
         postgresql::{
             messages::{Name, Target},
             Column,
         },
         proxy::{EncryptConfig, EncryptionService, ReloadCommand},
+        tls::ClientIdentity,
         TandemConfig,
     };
+    use bytes::BytesMut;
     use cipherstash_client::IdentifiedBy;
     use eql_mapper::Schema;
     use sqltk::parser::{dialect::PostgreSqlDialect, parser::Parser};
@@ -1429,34 +2096,141 @@ mod tests {
         let sql = "SET CIPHERSTASH.UNSAFE_DISABLE_MAPPING = true";
         let statement = parse_statement(sql);
 
-        context.maybe_set_unsafe_disable_mapping(&statement);
+        context
+            .maybe_set_unsafe_disable_mapping(&statement)
+            .unwrap();
         assert!(context.unsafe_disable_mapping());
 
         let sql = "SET CIPHERSTASH.UNSAFE_DISABLE_MAPPING = false";
         let statement = parse_statement(sql);
 
-        context.maybe_set_unsafe_disable_mapping(&statement);
+        context
+            .maybe_set_unsafe_disable_mapping(&statement)
+            .unwrap();
         assert!(!context.unsafe_disable_mapping());
 
         let sql = "SET CIPHERSTASH.UNSAFE_DISABLE_MAPPING = 1";
         let statement = parse_statement(sql);
 
-        context.maybe_set_unsafe_disable_mapping(&statement);
+        context
+            .maybe_set_unsafe_disable_mapping(&statement)
+            .unwrap();
         assert!(!context.unsafe_disable_mapping());
 
         let sql = "SET CIPHERSTASH.UNSAFE_DISABLE_MAPPING = '1'";
         let statement = parse_statement(sql);
 
-        context.maybe_set_unsafe_disable_mapping(&statement);
+        context
+            .maybe_set_unsafe_disable_mapping(&statement)
+            .unwrap();
         assert!(!context.unsafe_disable_mapping());
 
         let sql = "SET CIPHERSTASH.UNSAFE_DISABLE_MAPPING = t";
         let statement = parse_statement(sql);
 
-        context.maybe_set_unsafe_disable_mapping(&statement);
+        context
+            .maybe_set_unsafe_disable_mapping(&statement)
+            .unwrap();
         assert!(!context.unsafe_disable_mapping());
     }
 
+    #[test]
+    pub fn set_decrypt() {
+        log::init(LogConfig::default());
+
+        let context = create_context();
+        assert!(context.decrypt_enabled());
+
+        for (sql, expected) in [
+            ("SET CIPHERSTASH.DECRYPT = off", Some(false)),
+            ("SET CIPHERSTASH.DECRYPT = on", Some(true)),
+            ("SET CIPHERSTASH.DECRYPT = false", Some(false)),
+            ("SET CIPHERSTASH.DECRYPT = 'TRUE'", Some(true)),
+            ("SET CIPHERSTASH.DECRYPT = 'off'", Some(false)),
+        ] {
+            let statement = parse_statement(sql);
+            assert_eq!(context.maybe_set_decrypt(&statement), expected, "{sql}");
+            assert_eq!(context.decrypt_enabled(), expected.unwrap(), "{sql}");
+        }
+
+        // Invalid values leave the setting unchanged
+        let statement = parse_statement("SET CIPHERSTASH.DECRYPT = 1");
+        assert_eq!(context.maybe_set_decrypt(&statement), None);
+        assert!(!context.decrypt_enabled());
+    }
+
+    #[test]
+    pub fn set_decrypt_failure_policy() {
+        log::init(LogConfig::default());
+
+        let context = create_context();
+        assert_eq!(context.decrypt_failure_policy(), DecryptFailurePolicy::Fail);
+
+        for (sql, expected) in [
+            (
+                "SET CIPHERSTASH.DECRYPT_FAILURE = null",
+                DecryptFailurePolicy::Null,
+            ),
+            (
+                "SET CIPHERSTASH.DECRYPT_FAILURE = sentinel",
+                DecryptFailurePolicy::Sentinel,
+            ),
+            (
+                "SET CIPHERSTASH.DECRYPT_FAILURE = 'FAIL'",
+                DecryptFailurePolicy::Fail,
+            ),
+            (
+                "SET CIPHERSTASH.DECRYPT_FAILURE = 'null'",
+                DecryptFailurePolicy::Null,
+            ),
+        ] {
+            let statement = parse_statement(sql);
+            assert_eq!(
+                context.maybe_set_decrypt_failure_policy(&statement),
+                Some(expected),
+                "{sql}"
+            );
+            assert_eq!(context.decrypt_failure_policy(), expected, "{sql}");
+        }
+
+        // Invalid values leave the policy unchanged
+        let statement = parse_statement("SET CIPHERSTASH.DECRYPT_FAILURE = skip");
+        assert_eq!(context.maybe_set_decrypt_failure_policy(&statement), None);
+        assert_eq!(context.decrypt_failure_policy(), DecryptFailurePolicy::Null);
+    }
+
+    #[test]
+    pub fn disable_mapping_is_refused_in_strict_mode() {
+        log::init(LogConfig::default());
+
+        let mut config = TandemConfig::for_testing();
+        config.mapping.strict = true;
+
+        let (reload_sender, _reload_receiver) = mpsc::unbounded_channel();
+        let mut context = Context::new(
+            1,
+            Arc::new(config),
+            Arc::new(EncryptConfig::default()),
+            Arc::new(Schema::new("public")),
+            TestService {},
+            reload_sender,
+        );
+
+        for sql in [
+            "SET CIPHERSTASH.UNSAFE_DISABLE_MAPPING = true",
+            "SET CIPHERSTASH.UNSAFE_DISABLE_MAPPING = false",
+        ] {
+            let statement = parse_statement(sql);
+            let result = context.maybe_set_unsafe_disable_mapping(&statement);
+
+            assert!(matches!(
+                result,
+                Err(Error::Mapping(MappingError::StrictModeDisableMapping))
+            ));
+            assert!(!context.unsafe_disable_mapping());
+        }
+    }
+
     #[test]
     pub fn set_keyset_id() {
         log::init(LogConfig::default());
@@ -1487,6 +2261,100 @@ mod tests {
         }
     }
 
+    #[test]
+    pub fn set_traceparent() {
+        log::init(LogConfig::default());
+
+        let traceparent = "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01";
+        let expected = TraceParent::parse(traceparent);
+
+        let mut context = create_context();
+
+        let statement = parse_statement(&format!("SET CIPHERSTASH.TRACEPARENT = '{traceparent}'"));
+        assert_eq!(context.maybe_set_traceparent(&statement), expected);
+
+        // Statements inherit the connection traceparent
+        context.start_session();
+        assert_eq!(context.get_session_metrics().unwrap().traceparent, expected);
+        context.finish_session();
+
+        // A comment overrides it for one statement
+        let comment = "00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01";
+        let session_id = context.start_session();
+        context.set_statement_traceparent(
+            session_id,
+            &format!("SELECT 1 /*traceparent='{comment}'*/"),
+        );
+        assert_eq!(
+            context.get_session_metrics().unwrap().traceparent,
+            TraceParent::parse(comment)
+        );
+        context.finish_session();
+
+        // An invalid value clears it
+        let statement = parse_statement("SET CIPHERSTASH.TRACEPARENT = 'invalid'");
+        assert!(context.maybe_set_traceparent(&statement).is_none());
+        context.start_session();
+        assert!(context.get_session_metrics().unwrap().traceparent.is_none());
+    }
+
+    #[test]
+    pub fn set_session_parameters() {
+        log::init(LogConfig::default());
+
+        let context = create_context();
+        assert_eq!(context.session_parameters().extra_float_digits, 1);
+
+        context.set_session_parameter("DateStyle", "SQL, DMY");
+        assert_eq!(
+            context.session_parameters().date_style.output,
+            DateOutput::Sql
+        );
+
+        // extra_float_digits is read from the database before it is used
+        assert!(context.session_parameters().extra_float_digits_stale);
+        context.read_extra_float_digits("3");
+        assert_eq!(context.session_parameters().extra_float_digits, 3);
+        assert!(!context.session_parameters().extra_float_digits_stale);
+
+        context.extra_float_digits_may_change();
+        assert!(context.session_parameters().extra_float_digits_stale);
+    }
+
+    #[test]
+    pub fn statements_that_may_change_extra_float_digits() {
+        for sql in [
+            "SET extra_float_digits = 3",
+            "SET LOCAL extra_float_digits TO 0",
+            "SELECT set_config('extra_float_digits', '0', false)",
+            "SELECT pg_catalog.set_config('extra_float_digits', '0', true)",
+            "SELECT my_function()",
+            "ROLLBACK",
+            "ROLLBACK TO SAVEPOINT sp1",
+            "DISCARD ALL",
+            "EXECUTE reset_floats",
+        ] {
+            assert!(
+                may_change_extra_float_digits(&parse_statement(sql)),
+                "{sql}"
+            );
+        }
+
+        for sql in [
+            "SELECT id, price FROM products WHERE price > 1.5",
+            "INSERT INTO products (id, price) VALUES (1, 2.5)",
+            "SET search_path = public",
+            "SET CIPHERSTASH.KEYSET_NAME = 'test'",
+            "BEGIN",
+            "PREPARE reset_floats AS SELECT set_config('extra_float_digits', '0', false)",
+        ] {
+            assert!(
+                !may_change_extra_float_digits(&parse_statement(sql)),
+                "{sql}"
+            );
+        }
+    }
+
     #[test]
     pub fn set_keyset_id_error_handling() {
         log::init(LogConfig::default());
@@ -1611,6 +2479,80 @@ mod tests {
         assert!(result.is_err());
     }
 
+    #[test]
+    pub fn client_certificate_sets_keyset_name() {
+        log::init(LogConfig::default());
+
+        let mut config = TandemConfig::for_testing();
+        config.server.keyset_from_client_certificate = true;
+
+        let (reload_sender, _reload_receiver) = mpsc::unbounded_channel();
+        let mut context = Context::new(
+            1,
+            Arc::new(config),
+            Arc::new(EncryptConfig::default()),
+            Arc::new(Schema::new("public")),
+            TestService {},
+            reload_sender,
+        );
+
+        context.set_client_identity(ClientIdentity {
+            subject: "CN=api".to_string(),
+            common_name: Some("api".to_string()),
+            subject_alt_names: vec![],
+        });
+
+        let identifier = KeysetIdentifier(IdentifiedBy::Name("api".to_string().into()));
+        assert_eq!(Some(identifier.clone()), context.keyset_identifier());
+
+        // The keyset cannot be changed by the client
+        let statement = parse_statement("SET CIPHERSTASH.KEYSET_NAME = 'other'");
+        let result = context.maybe_set_keyset(&statement);
+        assert!(matches!(
+            result,
+            Err(Error::Encrypt(
+                EncryptError::UnexpectedSetKeysetWithClientCertificate
+            ))
+        ));
+        assert_eq!(Some(identifier), context.keyset_identifier());
+    }
+
+    #[test]
+    pub fn client_without_certificate_is_refused_if_the_keyset_is_set_by_certificate() {
+        log::init(LogConfig::default());
+
+        let mut config = TandemConfig::for_testing();
+        config.server.keyset_from_client_certificate = true;
+        config.server.require_client_certificate = false;
+
+        let (reload_sender, _reload_receiver) = mpsc::unbounded_channel();
+        let mut context = Context::new(
+            1,
+            Arc::new(config),
+            Arc::new(EncryptConfig::default()),
+            Arc::new(Schema::new("public")),
+            TestService {},
+            reload_sender,
+        );
+
+        let result = context.check_client_identity(None);
+        assert!(matches!(
+            result,
+            Err(Error::Config(ConfigError::ClientCertificateRequired))
+        ));
+        assert_eq!(None, context.keyset_identifier());
+    }
+
+    #[test]
+    pub fn client_without_certificate_is_accepted_if_none_is_required() {
+        log::init(LogConfig::default());
+
+        let mut context = create_context();
+
+        assert!(context.check_client_identity(None).is_ok());
+        assert!(context.client_identity().is_none());
+    }
+
     #[test]
     pub fn maybe_set_keyset_unified_function() {
         log::init(LogConfig::default());
@@ -1653,4 +2595,27 @@ mod tests {
         let identifier = result.unwrap();
         assert!(identifier.is_none());
     }
+
+    #[test]
+    pub fn maybe_set_keyset_leaves_keyset_unchanged_on_error() {
+        log::init(LogConfig::default());
+
+        let mut context = create_context();
+
+        let statement = parse_statement("SET CIPHERSTASH.KEYSET_NAME = 'test-keyset'");
+        context.maybe_set_keyset(&statement).unwrap();
+
+        let identifier = KeysetIdentifier(IdentifiedBy::Name("test-keyset".to_string().into()));
+
+        let statement = parse_statement("SET CIPHERSTASH.KEYSET_ID = 'not-a-uuid'");
+        let result = context.maybe_set_keyset(&statement);
+
+        assert!(matches!(
+            result,
+            Err(Error::Encrypt(
+                EncryptError::KeysetIdCouldNotBeParsed { .. }
+            ))
+        ));
+        assert_eq!(Some(identifier), context.keyset_identifier());
+    }
 }
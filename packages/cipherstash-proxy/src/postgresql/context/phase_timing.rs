@@ -1,4 +1,4 @@
-use std::time::{Duration, Instant};
+use std::time::{Duration, Instant, SystemTime};
 
 /// Tracks timing for individual phases of statement processing
 #[derive(Clone, Debug, Default)]
@@ -17,6 +17,50 @@ pub struct PhaseTiming {
     pub client_write_duration: Option<Duration>,
     /// Decryption operation time
     pub decrypt_duration: Option<Duration>,
+    /// Number of encrypt operations, each a single batched ZeroKMS request
+    pub encrypt_requests: u32,
+    /// Number of decrypt operations, each a single batched ZeroKMS request
+    pub decrypt_requests: u32,
+    /// When each traced phase ran
+    pub parse_span: Option<PhaseSpan>,
+    pub encrypt_span: Option<PhaseSpan>,
+    pub server_wait_span: Option<PhaseSpan>,
+    pub decrypt_span: Option<PhaseSpan>,
+}
+
+///
+/// When a phase ran, from the start of its first operation to the end of its last
+///
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PhaseSpan {
+    pub start: SystemTime,
+    pub end: SystemTime,
+}
+
+impl PhaseSpan {
+    /// A phase that ran for `duration` and has just ended
+    pub fn ended_now(duration: Duration) -> Self {
+        let end = SystemTime::now();
+        PhaseSpan {
+            start: end.checked_sub(duration).unwrap_or(end),
+            end,
+        }
+    }
+
+    pub fn duration(&self) -> Duration {
+        self.end.duration_since(self.start).unwrap_or_default()
+    }
+
+    /// Extends the span of a phase to include another operation
+    fn extend(span: &mut Option<PhaseSpan>, other: PhaseSpan) {
+        *span = Some(match *span {
+            Some(span) => PhaseSpan {
+                start: span.start.min(other.start),
+                end: span.end.max(other.end),
+            },
+            None => other,
+        });
+    }
 }
 
 impl PhaseTiming {
@@ -26,22 +70,31 @@ impl PhaseTiming {
 
     /// Record parse phase duration (first write wins)
     pub fn record_parse(&mut self, duration: Duration) {
-        self.parse_duration.get_or_insert(duration);
+        if self.parse_duration.is_none() {
+            self.parse_duration = Some(duration);
+            self.parse_span = Some(PhaseSpan::ended_now(duration));
+        }
     }
 
     /// Add parse duration (accumulate)
     pub fn add_parse(&mut self, duration: Duration) {
         self.parse_duration = Some(self.parse_duration.unwrap_or_default() + duration);
+        PhaseSpan::extend(&mut self.parse_span, PhaseSpan::ended_now(duration));
     }
 
     /// Record encrypt phase duration (first write wins)
     pub fn record_encrypt(&mut self, duration: Duration) {
-        self.encrypt_duration.get_or_insert(duration);
+        if self.encrypt_duration.is_none() {
+            self.encrypt_duration = Some(duration);
+            self.encrypt_span = Some(PhaseSpan::ended_now(duration));
+        }
     }
 
-    /// Add encrypt duration (accumulate)
+    /// Add encrypt duration (accumulate), counting one ZeroKMS request
     pub fn add_encrypt(&mut self, duration: Duration) {
         self.encrypt_duration = Some(self.encrypt_duration.unwrap_or_default() + duration);
+        self.encrypt_requests += 1;
+        PhaseSpan::extend(&mut self.encrypt_span, PhaseSpan::ended_now(duration));
     }
 
     /// Record server write phase duration (first write wins)
@@ -55,9 +108,14 @@ impl PhaseTiming {
             Some(self.server_write_duration.unwrap_or_default() + duration);
     }
 
-    /// Record server wait phase duration (first byte latency, first write wins)
-    pub fn record_server_wait(&mut self, duration: Duration) {
-        self.server_wait_duration.get_or_insert(duration);
+    /// Record server wait phase (first byte latency, first write wins)
+    ///
+    /// Server wait is transferred from the execute after it completes, so it is recorded with the time it ran.
+    pub fn record_server_wait(&mut self, span: PhaseSpan) {
+        if self.server_wait_duration.is_none() {
+            self.server_wait_duration = Some(span.duration());
+            self.server_wait_span = Some(span);
+        }
     }
 
     /// Record server response phase duration (first write wins)
@@ -82,14 +140,21 @@ impl PhaseTiming {
             Some(self.client_write_duration.unwrap_or_default() + duration);
     }
 
-    /// Record decrypt phase duration (first write wins)
-    pub fn record_decrypt(&mut self, duration: Duration) {
-        self.decrypt_duration.get_or_insert(duration);
+    /// Record decrypt phase (first write wins)
+    pub fn record_decrypt(&mut self, span: PhaseSpan) {
+        if self.decrypt_duration.is_none() {
+            self.decrypt_duration = Some(span.duration());
+            self.decrypt_span = Some(span);
+        }
     }
 
-    /// Add decrypt duration (accumulate)
-    pub fn add_decrypt(&mut self, duration: Duration) {
-        self.decrypt_duration = Some(self.decrypt_duration.unwrap_or_default() + duration);
+    /// Add decrypt phase (accumulate), counting one ZeroKMS request
+    ///
+    /// Decrypted rows may be written after later rows are decrypted, so decrypt is recorded with the time it ran.
+    pub fn add_decrypt(&mut self, span: PhaseSpan) {
+        self.decrypt_duration = Some(self.decrypt_duration.unwrap_or_default() + span.duration());
+        self.decrypt_requests += 1;
+        PhaseSpan::extend(&mut self.decrypt_span, span);
     }
 
     /// Calculate total tracked duration
@@ -136,7 +201,7 @@ mod tests {
 
         timing.record_parse(Duration::from_millis(5));
         timing.record_encrypt(Duration::from_millis(100));
-        timing.record_server_wait(Duration::from_millis(50));
+        timing.record_server_wait(PhaseSpan::ended_now(Duration::from_millis(50)));
 
         assert_eq!(timing.parse_duration, Some(Duration::from_millis(5)));
         assert_eq!(timing.encrypt_duration, Some(Duration::from_millis(100)));
@@ -149,7 +214,7 @@ mod tests {
 
         timing.record_parse(Duration::from_millis(5));
         timing.record_encrypt(Duration::from_millis(100));
-        timing.record_server_wait(Duration::from_millis(50));
+        timing.record_server_wait(PhaseSpan::ended_now(Duration::from_millis(50)));
 
         assert_eq!(timing.total_tracked(), Duration::from_millis(155));
     }
@@ -162,6 +227,46 @@ mod tests {
         timing.add_encrypt(Duration::from_millis(15));
 
         assert_eq!(timing.encrypt_duration, Some(Duration::from_millis(25)));
+        assert_eq!(timing.encrypt_requests, 2);
+    }
+
+    #[test]
+    fn phase_span_covers_every_operation() {
+        let mut timing = PhaseTiming::new();
+        let start = SystemTime::now();
+
+        let first = PhaseSpan {
+            start,
+            end: start + Duration::from_millis(10),
+        };
+        let second = PhaseSpan {
+            start: start + Duration::from_millis(30),
+            end: start + Duration::from_millis(35),
+        };
+        timing.add_decrypt(first);
+        timing.add_decrypt(second);
+
+        assert_eq!(timing.decrypt_duration, Some(Duration::from_millis(15)));
+        assert_eq!(
+            timing.decrypt_span,
+            Some(PhaseSpan {
+                start,
+                end: start + Duration::from_millis(35),
+            })
+        );
+    }
+
+    #[test]
+    fn phase_span_ends_when_recorded() {
+        let mut timing = PhaseTiming::new();
+
+        let before = SystemTime::now();
+        timing.add_encrypt(Duration::from_millis(20));
+        let after = SystemTime::now();
+
+        let span = timing.encrypt_span.unwrap();
+        assert!(span.end >= before && span.end <= after);
+        assert_eq!(span.duration(), Duration::from_millis(20));
     }
 
     #[test]
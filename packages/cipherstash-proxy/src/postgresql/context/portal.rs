@@ -12,6 +12,10 @@ pub enum Portal {
     Passthrough {
         session_id: Option<SessionId>,
     },
+    /// A catalog introspection query, whose rows are rewritten to report encrypted columns as their plaintext types
+    Catalog {
+        session_id: Option<SessionId>,
+    },
 }
 
 impl Portal {
@@ -40,11 +44,15 @@ impl Portal {
         Portal::Passthrough { session_id }
     }
 
+    pub fn catalog(session_id: Option<SessionId>) -> Portal {
+        Portal::Catalog { session_id }
+    }
+
     pub fn projection_columns(&self) -> &Vec<Option<Column>> {
         static EMPTY: Vec<Option<Column>> = vec![];
         match self {
             Portal::Encrypted { statement, .. } => &statement.projection_columns,
-            Portal::Passthrough { .. } => &EMPTY,
+            Portal::Passthrough { .. } | Portal::Catalog { .. } => &EMPTY,
         }
     }
 
@@ -66,7 +74,7 @@ impl Portal {
                 }
                 _ => format_codes.clone(),
             },
-            Portal::Passthrough { .. } => {
+            Portal::Passthrough { .. } | Portal::Catalog { .. } => {
                 unreachable!()
             }
         }
@@ -75,7 +83,7 @@ impl Portal {
     pub fn session_id(&self) -> Option<SessionId> {
         match self {
             Portal::Encrypted { session_id, .. } => *session_id,
-            Portal::Passthrough { session_id } => *session_id,
+            Portal::Passthrough { session_id } | Portal::Catalog { session_id } => *session_id,
         }
     }
 }
@@ -0,0 +1,370 @@
+use chrono::{DateTime, FixedOffset, NaiveDateTime, Offset, Utc};
+use chrono_tz::{OffsetName, Tz};
+
+/// Postgres clamps `extra_float_digits` to this range
+const EXTRA_FLOAT_DIGITS_MIN: i32 = -15;
+const EXTRA_FLOAT_DIGITS_MAX: i32 = 3;
+
+/// ReadyForQuery transaction status outside of a transaction block
+const TRANSACTION_IDLE: u8 = b'I';
+
+///
+/// The session settings that change how Postgres formats values for the client
+///
+/// `DateStyle` and `TimeZone` are reported by the database with `ParameterStatus` messages.
+/// `extra_float_digits` is not reported, and is read from the database when it may have changed,
+/// see [`SessionParameters::extra_float_digits_stale`].
+///
+#[derive(Clone, Debug, PartialEq)]
+pub struct SessionParameters {
+    pub date_style: DateStyle,
+    pub time_zone: SessionTimeZone,
+    pub extra_float_digits: i32,
+    /// `extra_float_digits` may have changed since it was last read from the database
+    pub extra_float_digits_stale: bool,
+    in_transaction: bool,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct DateStyle {
+    pub output: DateOutput,
+    pub order: DateOrder,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum DateOutput {
+    #[default]
+    Iso,
+    Postgres,
+    Sql,
+    German,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum DateOrder {
+    #[default]
+    Mdy,
+    Dmy,
+    Ymd,
+}
+
+///
+/// A time zone from the tz database, or a fixed offset in the POSIX form Postgres reports, eg `<+05:30>-05:30`
+///
+#[derive(Clone, Debug, PartialEq)]
+pub enum SessionTimeZone {
+    Named(Tz),
+    Fixed {
+        abbreviation: String,
+        offset: FixedOffset,
+    },
+}
+
+///
+/// A timestamp in the session time zone
+///
+#[derive(Clone, Debug, PartialEq)]
+pub struct LocalTimestamp {
+    pub timestamp: NaiveDateTime,
+    /// Seconds east of UTC
+    pub offset: i32,
+    /// Empty if the time zone has no abbreviation, and the offset is used instead
+    pub abbreviation: String,
+}
+
+impl Default for SessionParameters {
+    fn default() -> Self {
+        SessionParameters {
+            date_style: DateStyle::default(),
+            time_zone: SessionTimeZone::Named(Tz::UTC),
+            extra_float_digits: 1,
+            // Role and database defaults apply to the session, so the value is read before it is first used
+            extra_float_digits_stale: true,
+            in_transaction: false,
+        }
+    }
+}
+
+impl SessionParameters {
+    ///
+    /// Updates the parameter from a `ParameterStatus` message, or a startup parameter
+    /// Returns false if the parameter is not used, or the value could not be parsed
+    ///
+    pub fn set(&mut self, name: &str, value: &str) -> bool {
+        match name.to_lowercase().as_str() {
+            "datestyle" => match DateStyle::parse(value) {
+                Some(date_style) => {
+                    self.date_style = date_style;
+                    true
+                }
+                None => false,
+            },
+            "timezone" => match SessionTimeZone::parse(value) {
+                Some(time_zone) => {
+                    self.time_zone = time_zone;
+                    true
+                }
+                None => false,
+            },
+            "extra_float_digits" => match value.trim().parse::<i32>() {
+                Ok(digits) => {
+                    self.extra_float_digits =
+                        digits.clamp(EXTRA_FLOAT_DIGITS_MIN, EXTRA_FLOAT_DIGITS_MAX);
+                    true
+                }
+                Err(_) => false,
+            },
+            _ => false,
+        }
+    }
+
+    ///
+    /// Records `extra_float_digits` read from the database with `current_setting`
+    ///
+    pub fn read_extra_float_digits(&mut self, value: &str) -> bool {
+        let read = self.set("extra_float_digits", value);
+        if read {
+            self.extra_float_digits_stale = false;
+        }
+        read
+    }
+
+    ///
+    /// Records a statement that may change `extra_float_digits`, so it is read again before it is used
+    ///
+    pub fn extra_float_digits_may_change(&mut self) {
+        self.extra_float_digits_stale = true;
+    }
+
+    ///
+    /// Records a statement that failed, as the changes made in its transaction may be undone
+    ///
+    pub fn statement_failed(&mut self) {
+        self.extra_float_digits_stale = true;
+    }
+
+    ///
+    /// Records the transaction status of a `ReadyForQuery`
+    ///
+    /// The end of a transaction block drops `SET LOCAL` values, and undoes every change if it is rolled back.
+    ///
+    pub fn ready_for_query(&mut self, transaction_status: u8) {
+        let in_transaction = transaction_status != TRANSACTION_IDLE;
+        if self.in_transaction && !in_transaction {
+            self.extra_float_digits_stale = true;
+        }
+        self.in_transaction = in_transaction;
+    }
+}
+
+impl DateStyle {
+    ///
+    /// Parses a `DateStyle` value such as `ISO, MDY`
+    ///
+    /// The output style and the field order can be in either order, and either may be missing.
+    /// As in Postgres, `German` without a field order is `DMY`.
+    ///
+    pub fn parse(value: &str) -> Option<DateStyle> {
+        let mut output = None;
+        let mut order = None;
+
+        for token in value
+            .split(|c: char| c == ',' || c.is_whitespace())
+            .filter(|token| !token.is_empty())
+        {
+            match token.to_uppercase().as_str() {
+                "ISO" => output = Some(DateOutput::Iso),
+                "POSTGRES" => output = Some(DateOutput::Postgres),
+                "SQL" => output = Some(DateOutput::Sql),
+                "GERMAN" => output = Some(DateOutput::German),
+                "MDY" | "US" | "NONEURO" | "NONEUROPEAN" => order = Some(DateOrder::Mdy),
+                "DMY" | "EURO" | "EUROPEAN" => order = Some(DateOrder::Dmy),
+                "YMD" => order = Some(DateOrder::Ymd),
+                "DEFAULT" => {}
+                _ => return None,
+            }
+        }
+
+        let output = output.unwrap_or_default();
+        let order = order.unwrap_or(match output {
+            DateOutput::German => DateOrder::Dmy,
+            _ => DateOrder::Mdy,
+        });
+
+        Some(DateStyle { output, order })
+    }
+}
+
+impl SessionTimeZone {
+    pub fn parse(value: &str) -> Option<SessionTimeZone> {
+        if let Ok(tz) = value.parse::<Tz>() {
+            return Some(SessionTimeZone::Named(tz));
+        }
+
+        Self::parse_posix(value)
+    }
+
+    ///
+    /// Parses the `<abbreviation>offset` form Postgres reports for a fixed offset
+    ///
+    /// POSIX offsets are west of UTC, so `<+05:30>-05:30` is 5 hours 30 minutes east.
+    ///
+    fn parse_posix(value: &str) -> Option<SessionTimeZone> {
+        let value = value.strip_prefix('<')?;
+        let (abbreviation, offset) = value.split_once('>')?;
+
+        let (sign, offset) = match offset.as_bytes().first()? {
+            b'-' => (1, &offset[1..]),
+            b'+' => (-1, &offset[1..]),
+            _ => (-1, offset),
+        };
+
+        let mut parts = offset.split(':').map(|part| part.parse::<i32>().ok());
+        let hours = parts.next()??;
+        let minutes = parts.next().unwrap_or(Some(0))?;
+        let seconds = parts.next().unwrap_or(Some(0))?;
+
+        let offset = FixedOffset::east_opt(sign * (hours * 3600 + minutes * 60 + seconds))?;
+
+        Some(SessionTimeZone::Fixed {
+            abbreviation: abbreviation.to_string(),
+            offset,
+        })
+    }
+
+    pub fn localize(&self, timestamp: &DateTime<Utc>) -> LocalTimestamp {
+        match self {
+            SessionTimeZone::Named(tz) => {
+                let local = timestamp.with_timezone(tz);
+                LocalTimestamp {
+                    timestamp: local.naive_local(),
+                    offset: local.offset().fix().local_minus_utc(),
+                    abbreviation: local
+                        .offset()
+                        .abbreviation()
+                        .unwrap_or_default()
+                        .to_string(),
+                }
+            }
+            SessionTimeZone::Fixed {
+                abbreviation,
+                offset,
+            } => LocalTimestamp {
+                timestamp: timestamp.with_timezone(offset).naive_local(),
+                offset: offset.local_minus_utc(),
+                abbreviation: abbreviation.to_owned(),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DateOrder, DateOutput, DateStyle, SessionParameters, SessionTimeZone};
+    use chrono::{TimeZone, Utc};
+
+    #[test]
+    fn parse_date_style() {
+        assert_eq!(
+            DateStyle::parse("ISO, MDY"),
+            Some(DateStyle {
+                output: DateOutput::Iso,
+                order: DateOrder::Mdy
+            })
+        );
+        assert_eq!(
+            DateStyle::parse("SQL, DMY"),
+            Some(DateStyle {
+                output: DateOutput::Sql,
+                order: DateOrder::Dmy
+            })
+        );
+        assert_eq!(
+            DateStyle::parse("German"),
+            Some(DateStyle {
+                output: DateOutput::German,
+                order: DateOrder::Dmy
+            })
+        );
+        assert_eq!(DateStyle::parse("Klingon"), None);
+    }
+
+    #[test]
+    fn set_parameters() {
+        let mut parameters = SessionParameters::default();
+
+        assert!(parameters.set("DateStyle", "Postgres, DMY"));
+        assert!(parameters.set("TimeZone", "Australia/Melbourne"));
+        assert!(parameters.set("extra_float_digits", "5"));
+        assert!(!parameters.set("TimeZone", "Mars/Olympus_Mons"));
+        assert!(!parameters.set("application_name", "psql"));
+
+        assert_eq!(parameters.date_style.output, DateOutput::Postgres);
+        assert_eq!(
+            parameters.time_zone,
+            SessionTimeZone::Named(chrono_tz::Australia::Melbourne)
+        );
+        assert_eq!(parameters.extra_float_digits, 3);
+    }
+
+    #[test]
+    fn extra_float_digits_are_stale_until_read() {
+        let mut parameters = SessionParameters::default();
+        assert!(parameters.extra_float_digits_stale);
+
+        // The startup value is used until the value is read
+        assert!(parameters.set("extra_float_digits", "2"));
+        assert!(parameters.extra_float_digits_stale);
+
+        assert!(parameters.read_extra_float_digits("0"));
+        assert_eq!(parameters.extra_float_digits, 0);
+        assert!(!parameters.extra_float_digits_stale);
+
+        assert!(!parameters.read_extra_float_digits("none"));
+        assert_eq!(parameters.extra_float_digits, 0);
+
+        parameters.extra_float_digits_may_change();
+        assert!(parameters.extra_float_digits_stale);
+    }
+
+    #[test]
+    fn extra_float_digits_are_stale_when_a_transaction_ends() {
+        let mut parameters = SessionParameters::default();
+        parameters.read_extra_float_digits("1");
+
+        parameters.ready_for_query(b'I');
+        assert!(!parameters.extra_float_digits_stale);
+
+        // SET LOCAL lasts until the end of the transaction block
+        parameters.ready_for_query(b'T');
+        assert!(!parameters.extra_float_digits_stale);
+        parameters.ready_for_query(b'I');
+        assert!(parameters.extra_float_digits_stale);
+
+        parameters.read_extra_float_digits("1");
+        parameters.ready_for_query(b'T');
+        parameters.ready_for_query(b'E');
+        assert!(!parameters.extra_float_digits_stale);
+
+        // A failed statement may undo a change, such as with ROLLBACK TO SAVEPOINT
+        parameters.statement_failed();
+        assert!(parameters.extra_float_digits_stale);
+    }
+
+    #[test]
+    fn localize_in_named_and_fixed_time_zones() {
+        let timestamp = Utc.with_ymd_and_hms(2024, 1, 15, 10, 30, 0).unwrap();
+
+        let melbourne = SessionTimeZone::parse("Australia/Melbourne").unwrap();
+        let local = melbourne.localize(&timestamp);
+        assert_eq!(local.timestamp.to_string(), "2024-01-15 21:30:00");
+        assert_eq!(local.offset, 11 * 3600);
+        assert_eq!(local.abbreviation, "AEDT");
+
+        let fixed = SessionTimeZone::parse("<+05:30>-05:30").unwrap();
+        let local = fixed.localize(&timestamp);
+        assert_eq!(local.timestamp.to_string(), "2024-01-15 16:00:00");
+        assert_eq!(local.offset, 5 * 3600 + 30 * 60);
+        assert_eq!(local.abbreviation, "+05:30");
+    }
+}
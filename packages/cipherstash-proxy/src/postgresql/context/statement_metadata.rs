@@ -124,6 +124,16 @@ impl StatementMetadata {
     }
 }
 
+/// Stable fingerprint of a parsed statement, used by the strict mode allowlist.
+///
+/// Unlike the query fingerprint, it is not keyed, so it is the same across restarts and proxy instances and can be
+/// configured ahead of time. The statement is hashed in its parsed form, so differences in whitespace and keyword
+/// case do not change the fingerprint, but different literal values do.
+pub fn statement_fingerprint(statement: &Statement) -> String {
+    let hash = blake3::hash(statement.to_string().as_bytes());
+    hex::encode(&hash.as_bytes()[..8])
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -194,6 +204,21 @@ mod tests {
         assert_eq!(m1.query_fingerprint, m2.query_fingerprint);
     }
 
+    #[test]
+    fn statement_fingerprint_ignores_formatting() {
+        let fingerprint = statement_fingerprint(&parse("SELECT * FROM users WHERE id = $1"));
+
+        assert_eq!(fingerprint.len(), 16);
+        assert_eq!(
+            fingerprint,
+            statement_fingerprint(&parse("select *\n  from users where id = $1"))
+        );
+        assert_ne!(
+            fingerprint,
+            statement_fingerprint(&parse("SELECT * FROM users WHERE id = 1"))
+        );
+    }
+
     #[test]
     fn multi_statement_flag_defaults_false() {
         let metadata = StatementMetadata::new();
@@ -5,7 +5,7 @@ use crate::{
 };
 use bigdecimal::BigDecimal;
 use bytes::BytesMut;
-use chrono::NaiveDate;
+use chrono::{DateTime, NaiveDate, NaiveDateTime, Utc};
 use cipherstash_client::{encryption::Plaintext, schema::ColumnType};
 use eql_mapper::EqlTermVariant;
 use postgres_types::FromSql;
@@ -379,7 +379,7 @@ fn text_from_sql(
                 .map(Plaintext::new)
         }
         (EqlTermVariant::Full | EqlTermVariant::Partial, ColumnType::Timestamp) => {
-            unimplemented!("Timestamp")
+            timestamp_from_str(val).map(|ts| Plaintext::Timestamp(Some(ts)))
         }
 
         // If JSONB, JSONPATH values are treated as strings
@@ -436,6 +436,11 @@ fn binary_from_sql(
         (EqlTermVariant::Full | EqlTermVariant::Partial, ColumnType::Date, _) => {
             parse_bytes_from_sql::<NaiveDate>(bytes, pg_type).map(Plaintext::new)
         }
+        // A `real` is widened through its shortest decimal form, so a binary
+        // float4 `0.1` is the same plaintext as the text literal `0.1`
+        (EqlTermVariant::Full | EqlTermVariant::Partial, ColumnType::Float, &Type::FLOAT4) => {
+            parse_bytes_from_sql::<f32>(bytes, pg_type).map(|f| Plaintext::new(widen_float4(f)))
+        }
         (EqlTermVariant::Full | EqlTermVariant::Partial, ColumnType::Float, _) => {
             parse_bytes_from_sql::<f64>(bytes, pg_type).map(Plaintext::new)
         }
@@ -520,6 +525,24 @@ fn binary_from_sql(
     }
 }
 
+/// Parses an ISO 8601 timestamp, as output by PostgreSQL with `DateStyle = ISO`.
+/// A timestamp without an offset is taken to be UTC.
+fn timestamp_from_str(val: &str) -> Result<DateTime<Utc>, MappingError> {
+    DateTime::parse_from_rfc3339(val)
+        .or_else(|_| DateTime::parse_from_str(val, "%Y-%m-%d %H:%M:%S%.f%#z"))
+        .map(|ts| ts.with_timezone(&Utc))
+        .or_else(|_| {
+            NaiveDateTime::parse_from_str(val, "%Y-%m-%d %H:%M:%S%.f")
+                .or_else(|_| NaiveDateTime::parse_from_str(val, "%Y-%m-%dT%H:%M:%S%.f"))
+                .map(|ts| ts.and_utc())
+        })
+        .map_err(|_| MappingError::CouldNotParseParameter)
+}
+
+fn widen_float4(value: f32) -> f64 {
+    value.to_string().parse().unwrap_or(value as f64)
+}
+
 fn parse_bytes_from_sql<T>(bytes: &BytesMut, pg_type: &Type) -> Result<T, MappingError>
 where
     T: for<'a> FromSql<'a>,
@@ -733,18 +756,22 @@ mod tests {
         config::LogConfig,
         log,
         postgresql::{
-            data::bind_param_from_sql, format_code::FormatCode, messages::bind::BindParam, Column,
+            data::{bind_param_from_sql, literal_from_sql},
+            format_code::FormatCode,
+            messages::bind::BindParam,
+            Column,
         },
         Identifier,
     };
     use bytes::{BufMut, BytesMut};
-    use chrono::NaiveDate;
+    use chrono::{NaiveDate, TimeZone, Utc};
     use cipherstash_client::{
         encryption::Plaintext,
         schema::{ColumnConfig, ColumnMode, ColumnType},
     };
     use eql_mapper::EqlTermVariant;
     use postgres_types::{ToSql, Type};
+    use sqltk::parser::ast::Value;
 
     fn to_message(s: &[u8]) -> BytesMut {
         BytesMut::from(s)
@@ -761,7 +788,9 @@ mod tests {
                 mode: ColumnMode::PlaintextDuplicate,
             },
             postgres_type: ty,
+            token_type: None,
             eql_term: EqlTermVariant::Full,
+            aggregate: None,
         }
     }
 
@@ -805,6 +834,38 @@ mod tests {
         assert_eq!(pt, Plaintext::BigInt(Some(val)));
     }
 
+    #[test]
+    pub fn bind_param_to_plaintext_float4() {
+        log::init(LogConfig::default());
+
+        // Binary float4 decodes to the same plaintext as its text form
+        let mut bytes = BytesMut::with_capacity(4);
+        bytes.put_f32(0.1);
+        let param = BindParam::new(FormatCode::Binary, bytes);
+
+        let pt = bind_param_from_sql(
+            &param,
+            &Type::FLOAT4,
+            EqlTermVariant::Full,
+            ColumnType::Float,
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(pt, Plaintext::Float(Some(0.1)));
+
+        let param = BindParam::new(FormatCode::Text, BytesMut::from("0.1"));
+
+        let pt = bind_param_from_sql(
+            &param,
+            &Type::FLOAT4,
+            EqlTermVariant::Full,
+            ColumnType::Float,
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(pt, Plaintext::Float(Some(0.1)));
+    }
+
     #[test]
     pub fn bind_param_to_plaintext_boolean() {
         log::init(LogConfig::default());
@@ -873,4 +934,30 @@ mod tests {
             .unwrap();
         assert_eq!(pt, Plaintext::NaiveDate(Some(val)));
     }
+
+    #[test]
+    pub fn literal_to_plaintext_timestamp() {
+        log::init(LogConfig::default());
+
+        let expected =
+            Plaintext::Timestamp(Some(Utc.with_ymd_and_hms(2025, 1, 15, 10, 30, 0).unwrap()));
+
+        for val in [
+            "2025-01-15 10:30:00+00",
+            "2025-01-15 21:30:00+11",
+            "2025-01-15 16:00:00+05:30",
+            "2025-01-15T10:30:00Z",
+            "2025-01-15 10:30:00",
+            "2025-01-15 10:30:00.000",
+        ] {
+            let literal = Value::SingleQuotedString(val.to_owned());
+            let pt = literal_from_sql(&literal, EqlTermVariant::Full, ColumnType::Timestamp)
+                .unwrap()
+                .unwrap();
+            assert_eq!(pt, expected, "{val}");
+        }
+
+        let literal = Value::SingleQuotedString("15/01/2025".to_owned());
+        assert!(literal_from_sql(&literal, EqlTermVariant::Full, ColumnType::Timestamp).is_err());
+    }
 }
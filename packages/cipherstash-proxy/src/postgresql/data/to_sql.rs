@@ -1,54 +1,106 @@
 use crate::error::EncryptError;
+use crate::postgresql::context::session_parameters::{DateOrder, DateOutput, SessionParameters};
 use crate::{error::Error, postgresql::format_code::FormatCode};
-use bytes::BytesMut;
+use bytes::{BufMut, BytesMut};
+use chrono::{DateTime, Datelike, NaiveDate, NaiveDateTime, Timelike, Utc};
 use cipherstash_client::encryption::Plaintext;
 use postgres_types::ToSql;
 use postgres_types::Type;
+use rust_decimal::Decimal;
+use serde_json::Value;
+use std::fmt::Write;
 
-pub fn to_sql(plaintext: &Plaintext, format_code: &FormatCode) -> Result<Option<BytesMut>, Error> {
+/// Significant digits of `float8` and `float4` output when `extra_float_digits` is 0
+const DBL_DIG: i32 = 15;
+const FLT_DIG: i32 = 6;
+
+/// JSONB binary format version
+const JSONB_VERSION: u8 = 1;
+
+const DAYS: [&str; 7] = ["Sun", "Mon", "Tue", "Wed", "Thu", "Fri", "Sat"];
+const MONTHS: [&str; 12] = [
+    "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+];
+
+///
+/// Encodes a decrypted value as `postgres_type`, the type of the column described to the client
+///
+/// The encoding is the same as Postgres would return for a column of the type,
+/// using the `DateStyle`, `TimeZone` and `extra_float_digits` of the session.
+///
+pub fn to_sql(
+    plaintext: &Plaintext,
+    postgres_type: &Type,
+    format_code: &FormatCode,
+    session: &SessionParameters,
+) -> Result<Option<BytesMut>, Error> {
     let bytes = match format_code {
-        FormatCode::Text => text_to_sql(plaintext)?,
-        FormatCode::Binary => binary_to_sql(plaintext)?,
+        FormatCode::Text => text_to_sql(plaintext, postgres_type, session)?,
+        FormatCode::Binary => binary_to_sql(plaintext, postgres_type)?,
     };
 
     Ok(Some(bytes))
 }
 
-fn text_to_sql(plaintext: &Plaintext) -> Result<BytesMut, Error> {
+fn text_to_sql(
+    plaintext: &Plaintext,
+    postgres_type: &Type,
+    session: &SessionParameters,
+) -> Result<BytesMut, Error> {
     let s = match &plaintext {
         Plaintext::Text(Some(x)) => x.to_string(),
         Plaintext::Int(Some(x)) => x.to_string(),
         Plaintext::BigInt(Some(x)) => x.to_string(),
         Plaintext::BigUInt(Some(x)) => x.to_string(),
-        Plaintext::Boolean(Some(x)) => x.to_string(),
+        Plaintext::Boolean(Some(x)) => if *x { "t" } else { "f" }.to_string(),
         Plaintext::Decimal(Some(x)) => x.to_string(),
-        Plaintext::Float(Some(x)) => x.to_string(),
-        Plaintext::NaiveDate(Some(x)) => x.to_string(),
+        Plaintext::Float(Some(x)) if *postgres_type == Type::FLOAT4 => {
+            float4_out(*x as f32, session.extra_float_digits)
+        }
+        Plaintext::Float(Some(x)) => float8_out(*x, session.extra_float_digits),
+        Plaintext::NaiveDate(Some(x)) => date_out(x, session),
         Plaintext::SmallInt(Some(x)) => x.to_string(),
-        Plaintext::Timestamp(Some(x)) => x.to_string(),
-        Plaintext::Json(Some(x)) => x.to_string(),
+        Plaintext::Timestamp(Some(x)) => timestamptz_out(x, session),
+        Plaintext::Json(Some(x)) => jsonb_out(x),
         _ => "".to_string(),
     };
 
     Ok(BytesMut::from(s.as_bytes()))
 }
 
-fn binary_to_sql(plaintext: &Plaintext) -> Result<BytesMut, Error> {
+fn binary_to_sql(plaintext: &Plaintext, postgres_type: &Type) -> Result<BytesMut, Error> {
     let mut bytes = BytesMut::new();
 
     let result = match &plaintext {
         Plaintext::BigInt(x) => x.to_sql_checked(&Type::INT8, &mut bytes),
         Plaintext::Boolean(x) => x.to_sql_checked(&Type::BOOL, &mut bytes),
+        Plaintext::Float(x) if *postgres_type == Type::FLOAT4 => x
+            .map(|x| x as f32)
+            .to_sql_checked(&Type::FLOAT4, &mut bytes),
         Plaintext::Float(x) => x.to_sql_checked(&Type::FLOAT8, &mut bytes),
         Plaintext::Int(x) => x.to_sql_checked(&Type::INT4, &mut bytes),
         Plaintext::NaiveDate(x) => x.to_sql_checked(&Type::DATE, &mut bytes),
         Plaintext::SmallInt(x) => x.to_sql_checked(&Type::INT2, &mut bytes),
         Plaintext::Timestamp(x) => x.to_sql_checked(&Type::TIMESTAMPTZ, &mut bytes),
         Plaintext::Text(x) => x.to_sql_checked(&Type::TEXT, &mut bytes),
-        Plaintext::Json(x) => x.to_sql_checked(&Type::JSONB, &mut bytes),
+        Plaintext::Json(Some(x)) => {
+            // The binary format of jsonb is a version number followed by the text format
+            // A JSON accessor is described to the client as text, which has no version number
+            if *postgres_type == Type::JSONB {
+                bytes.put_u8(JSONB_VERSION);
+            }
+            bytes.put_slice(jsonb_out(x).as_bytes());
+            Ok(postgres_types::IsNull::No)
+        }
+        Plaintext::Json(None) => Ok(postgres_types::IsNull::Yes),
         Plaintext::Decimal(x) => x.to_sql_checked(&Type::NUMERIC, &mut bytes),
-        // TODO: Implement these
-        Plaintext::BigUInt(_x) => unimplemented!(),
+        Plaintext::BigUInt(x) if *postgres_type == Type::NUMERIC => x
+            .map(Decimal::from)
+            .to_sql_checked(&Type::NUMERIC, &mut bytes),
+        Plaintext::BigUInt(x) => match x.map(i64::try_from).transpose() {
+            Ok(x) => x.to_sql_checked(&Type::INT8, &mut bytes),
+            Err(_) => return Err(EncryptError::PlaintextCouldNotBeEncoded.into()),
+        },
     };
 
     match result {
@@ -56,3 +108,496 @@ fn binary_to_sql(plaintext: &Plaintext) -> Result<BytesMut, Error> {
         Err(_e) => Err(EncryptError::PlaintextCouldNotBeEncoded.into()),
     }
 }
+
+///
+/// Formats a `float8` as Postgres `float8out`
+///
+/// With a positive `extra_float_digits` (the default) this is the shortest text that reads back as the same value.
+/// Otherwise the value is rounded to `15 + extra_float_digits` significant digits.
+///
+fn float8_out(value: f64, extra_float_digits: i32) -> String {
+    if let Some(special) = float_special(value.is_nan(), value.is_infinite(), value < 0.0) {
+        return special.to_string();
+    }
+
+    let scientific = if extra_float_digits > 0 {
+        format!("{value:e}")
+    } else {
+        let precision = (DBL_DIG + extra_float_digits).max(1) as usize;
+        format!("{:.*e}", precision - 1, value)
+    };
+
+    float_layout(&scientific, DBL_DIG, extra_float_digits)
+}
+
+///
+/// Formats a `float4` as Postgres `float4out`
+///
+fn float4_out(value: f32, extra_float_digits: i32) -> String {
+    if let Some(special) = float_special(value.is_nan(), value.is_infinite(), value < 0.0) {
+        return special.to_string();
+    }
+
+    let scientific = if extra_float_digits > 0 {
+        format!("{value:e}")
+    } else {
+        let precision = (FLT_DIG + extra_float_digits).max(1) as usize;
+        format!("{:.*e}", precision - 1, value)
+    };
+
+    float_layout(&scientific, FLT_DIG, extra_float_digits)
+}
+
+fn float_special(is_nan: bool, is_infinite: bool, is_negative: bool) -> Option<&'static str> {
+    match (is_nan, is_infinite, is_negative) {
+        (true, _, _) => Some("NaN"),
+        (_, true, true) => Some("-Infinity"),
+        (_, true, false) => Some("Infinity"),
+        _ => None,
+    }
+}
+
+///
+/// Lays out a float formatted by Rust in scientific notation (eg `-1.25e-5`) as C `%g` would
+///
+/// Fixed notation is used for decimal exponents from -4 to less than the precision.
+/// The shortest format uses the precision of the type, as Postgres does.
+///
+fn float_layout(scientific: &str, digits_of_type: i32, extra_float_digits: i32) -> String {
+    let (mantissa, exponent) = scientific.split_once('e').unwrap_or((scientific, "0"));
+    let exponent = exponent.parse::<i32>().unwrap_or_default();
+
+    let (sign, mantissa) = match mantissa.strip_prefix('-') {
+        Some(mantissa) => ("-", mantissa),
+        None => ("", mantissa),
+    };
+
+    // Significant digits, without trailing zeros
+    let digits = mantissa.replace('.', "");
+    let digits = match digits.trim_end_matches('0') {
+        "" => "0",
+        digits => digits,
+    };
+
+    let precision = if extra_float_digits > 0 {
+        digits_of_type
+    } else {
+        (digits_of_type + extra_float_digits).max(1)
+    };
+
+    let mut out = String::from(sign);
+
+    if exponent < -4 || exponent >= precision {
+        out.push_str(&digits[..1]);
+        if digits.len() > 1 {
+            out.push('.');
+            out.push_str(&digits[1..]);
+        }
+        let exponent_sign = if exponent < 0 { '-' } else { '+' };
+        let _ = write!(out, "e{exponent_sign}{:02}", exponent.abs());
+    } else if exponent < 0 {
+        out.push_str("0.");
+        out.push_str(&"0".repeat((-exponent - 1) as usize));
+        out.push_str(digits);
+    } else {
+        let integer_len = exponent as usize + 1;
+        if digits.len() > integer_len {
+            out.push_str(&digits[..integer_len]);
+            out.push('.');
+            out.push_str(&digits[integer_len..]);
+        } else {
+            out.push_str(digits);
+            out.push_str(&"0".repeat(integer_len - digits.len()));
+        }
+    }
+
+    out
+}
+
+///
+/// Formats a `date` in the `DateStyle` of the session
+///
+fn date_out(date: &NaiveDate, session: &SessionParameters) -> String {
+    let mut out = String::new();
+    write_date(&mut out, date, session);
+    write_era(&mut out, date.year());
+    out
+}
+
+///
+/// Formats a `timestamptz` in the `DateStyle` and `TimeZone` of the session
+///
+/// ISO style uses the numeric offset, and the other styles the time zone abbreviation if there is one.
+///
+fn timestamptz_out(timestamp: &DateTime<Utc>, session: &SessionParameters) -> String {
+    let local = session.time_zone.localize(timestamp);
+
+    let mut out = String::new();
+    write_date_time(&mut out, &local.timestamp, session);
+
+    if session.date_style.output == DateOutput::Iso || local.abbreviation.is_empty() {
+        if session.date_style.output != DateOutput::Iso {
+            out.push(' ');
+        }
+        write_offset(&mut out, local.offset);
+    } else {
+        out.push(' ');
+        out.push_str(&local.abbreviation);
+    }
+
+    write_era(&mut out, local.timestamp.year());
+    out
+}
+
+fn write_date(out: &mut String, date: &NaiveDate, session: &SessionParameters) {
+    let year = display_year(date.year());
+    let (month, day) = (date.month(), date.day());
+    let dmy = session.date_style.order == DateOrder::Dmy;
+
+    let _ = match (session.date_style.output, dmy) {
+        (DateOutput::Iso, _) => write!(out, "{year:04}-{month:02}-{day:02}"),
+        (DateOutput::Sql, true) => write!(out, "{day:02}/{month:02}/{year:04}"),
+        (DateOutput::Sql, false) => write!(out, "{month:02}/{day:02}/{year:04}"),
+        (DateOutput::German, _) => write!(out, "{day:02}.{month:02}.{year:04}"),
+        (DateOutput::Postgres, true) => write!(out, "{day:02}-{month:02}-{year:04}"),
+        (DateOutput::Postgres, false) => write!(out, "{month:02}-{day:02}-{year:04}"),
+    };
+}
+
+fn write_date_time(out: &mut String, timestamp: &NaiveDateTime, session: &SessionParameters) {
+    if session.date_style.output != DateOutput::Postgres {
+        write_date(out, &timestamp.date(), session);
+        out.push(' ');
+        write_time(out, timestamp);
+        return;
+    }
+
+    // Postgres style, eg `Wed Dec 17 07:37:16 1997`
+    let day_name = DAYS[timestamp.weekday().num_days_from_sunday() as usize];
+    let month_name = MONTHS[timestamp.month0() as usize];
+    let day = timestamp.day();
+
+    let _ = match session.date_style.order {
+        DateOrder::Dmy => write!(out, "{day_name} {day:02} {month_name} "),
+        _ => write!(out, "{day_name} {month_name} {day:02} "),
+    };
+    write_time(out, timestamp);
+    let _ = write!(out, " {:04}", display_year(timestamp.year()));
+}
+
+///
+/// Writes the time with microseconds, without trailing zeros
+///
+fn write_time(out: &mut String, timestamp: &NaiveDateTime) {
+    let _ = write!(
+        out,
+        "{:02}:{:02}:{:02}",
+        timestamp.hour(),
+        timestamp.minute(),
+        timestamp.second()
+    );
+
+    // Leap seconds are represented as more than a second of nanoseconds
+    let micros = (timestamp.nanosecond() % 1_000_000_000) / 1_000;
+    if micros > 0 {
+        let fraction = format!("{micros:06}");
+        out.push('.');
+        out.push_str(fraction.trim_end_matches('0'));
+    }
+}
+
+///
+/// Writes a UTC offset in seconds east, as `+HH`, `+HH:MM` or `+HH:MM:SS`
+///
+fn write_offset(out: &mut String, offset: i32) {
+    let sign = if offset >= 0 { '+' } else { '-' };
+    let offset = offset.unsigned_abs();
+    let (hours, minutes, seconds) = (offset / 3600, (offset / 60) % 60, offset % 60);
+
+    let _ = if seconds != 0 {
+        write!(out, "{sign}{hours:02}:{minutes:02}:{seconds:02}")
+    } else if minutes != 0 {
+        write!(out, "{sign}{hours:02}:{minutes:02}")
+    } else {
+        write!(out, "{sign}{hours:02}")
+    };
+}
+
+/// Years before 1 AD are displayed as BC, and there is no year 0
+fn display_year(year: i32) -> i32 {
+    if year > 0 {
+        year
+    } else {
+        1 - year
+    }
+}
+
+fn write_era(out: &mut String, year: i32) {
+    if year <= 0 {
+        out.push_str(" BC");
+    }
+}
+
+///
+/// Formats JSON as Postgres `jsonb_out`
+///
+/// Object keys are ordered by length and then bytes, and separated with `", "` and `": "`.
+///
+fn jsonb_out(value: &Value) -> String {
+    let mut out = String::new();
+    write_jsonb(&mut out, value);
+    out
+}
+
+fn write_jsonb(out: &mut String, value: &Value) {
+    match value {
+        Value::Null => out.push_str("null"),
+        Value::Bool(b) => out.push_str(if *b { "true" } else { "false" }),
+        Value::Number(n) => out.push_str(&n.to_string()),
+        Value::String(s) => write_json_string(out, s),
+        Value::Array(values) => {
+            out.push('[');
+            for (idx, value) in values.iter().enumerate() {
+                if idx > 0 {
+                    out.push_str(", ");
+                }
+                write_jsonb(out, value);
+            }
+            out.push(']');
+        }
+        Value::Object(map) => {
+            let mut entries = map.iter().collect::<Vec<_>>();
+            entries.sort_by(|(a, _), (b, _)| a.len().cmp(&b.len()).then_with(|| a.cmp(b)));
+
+            out.push('{');
+            for (idx, (key, value)) in entries.into_iter().enumerate() {
+                if idx > 0 {
+                    out.push_str(", ");
+                }
+                write_json_string(out, key);
+                out.push_str(": ");
+                write_jsonb(out, value);
+            }
+            out.push('}');
+        }
+    }
+}
+
+fn write_json_string(out: &mut String, s: &str) {
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\u{08}' => out.push_str("\\b"),
+            '\u{0C}' => out.push_str("\\f"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(out, "\\u{:04x}", c as u32);
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use serde_json::json;
+
+    fn session(date_style: &str, time_zone: &str) -> SessionParameters {
+        let mut session = SessionParameters::default();
+        session.set("DateStyle", date_style);
+        session.set("TimeZone", time_zone);
+        session
+    }
+
+    fn text(plaintext: Plaintext, postgres_type: &Type, session: &SessionParameters) -> String {
+        let bytes = to_sql(&plaintext, postgres_type, &FormatCode::Text, session)
+            .unwrap()
+            .unwrap();
+        String::from_utf8(bytes.to_vec()).unwrap()
+    }
+
+    #[test]
+    fn float8_shortest() {
+        assert_eq!(float8_out(0.1, 1), "0.1");
+        assert_eq!(float8_out(1.0, 1), "1");
+        assert_eq!(float8_out(-0.0, 1), "-0");
+        assert_eq!(float8_out(100.0, 1), "100");
+        assert_eq!(float8_out(123456789012345.0, 1), "123456789012345");
+        assert_eq!(float8_out(1e15, 1), "1e+15");
+        assert_eq!(float8_out(1.5e-5, 1), "1.5e-05");
+        assert_eq!(float8_out(0.0001, 1), "0.0001");
+        assert_eq!(float8_out(1e100, 1), "1e+100");
+        assert_eq!(float8_out(f64::NAN, 1), "NaN");
+        assert_eq!(float8_out(f64::NEG_INFINITY, 1), "-Infinity");
+    }
+
+    #[test]
+    fn float8_with_extra_float_digits() {
+        assert_eq!(float8_out(0.1, 0), "0.1");
+        assert_eq!(float8_out(1.0 / 3.0, 0), "0.333333333333333");
+        assert_eq!(float8_out(1.0 / 3.0, -5), "0.3333333333");
+        assert_eq!(float8_out(1.0 / 3.0, 3), "0.3333333333333333");
+        assert_eq!(float8_out(123456.0, -12), "1.23e+05");
+    }
+
+    #[test]
+    fn float4_shortest() {
+        assert_eq!(float4_out(0.1, 1), "0.1");
+        assert_eq!(float4_out(1234567.0, 1), "1.234567e+06");
+        assert_eq!(float4_out(123456.0, 1), "123456");
+    }
+
+    #[test]
+    fn float4_binary_and_text() {
+        let plaintext = Plaintext::Float(Some(0.1));
+        let session = SessionParameters::default();
+
+        let bytes = to_sql(&plaintext, &Type::FLOAT4, &FormatCode::Binary, &session)
+            .unwrap()
+            .unwrap();
+        assert_eq!(&bytes[..], &0.1f32.to_be_bytes());
+
+        assert_eq!(text(plaintext, &Type::FLOAT4, &session), "0.1");
+    }
+
+    #[test]
+    fn boolean_text() {
+        let session = SessionParameters::default();
+        assert_eq!(
+            text(Plaintext::Boolean(Some(true)), &Type::BOOL, &session),
+            "t"
+        );
+        assert_eq!(
+            text(Plaintext::Boolean(Some(false)), &Type::BOOL, &session),
+            "f"
+        );
+    }
+
+    #[test]
+    fn date_styles() {
+        let date = Plaintext::NaiveDate(NaiveDate::from_ymd_opt(2024, 1, 5));
+
+        let expected = [
+            ("ISO, MDY", "2024-01-05"),
+            ("SQL, MDY", "01/05/2024"),
+            ("SQL, DMY", "05/01/2024"),
+            ("Postgres, MDY", "01-05-2024"),
+            ("German, DMY", "05.01.2024"),
+        ];
+
+        for (date_style, expected) in expected {
+            let session = session(date_style, "UTC");
+            assert_eq!(text(date.clone(), &Type::DATE, &session), expected);
+        }
+
+        let bc = Plaintext::NaiveDate(NaiveDate::from_ymd_opt(-43, 3, 15));
+        let session = SessionParameters::default();
+        assert_eq!(text(bc, &Type::DATE, &session), "0044-03-15 BC");
+    }
+
+    #[test]
+    fn timestamptz_in_session_time_zone() {
+        let timestamp = Utc.with_ymd_and_hms(2024, 1, 15, 10, 30, 0).unwrap();
+        let plaintext = Plaintext::Timestamp(Some(timestamp));
+
+        let expected = [
+            ("ISO, MDY", "UTC", "2024-01-15 10:30:00+00"),
+            ("ISO, MDY", "Australia/Melbourne", "2024-01-15 21:30:00+11"),
+            ("ISO, MDY", "Asia/Kolkata", "2024-01-15 16:00:00+05:30"),
+            ("ISO, MDY", "<+05:30>-05:30", "2024-01-15 16:00:00+05:30"),
+            ("SQL, MDY", "America/New_York", "01/15/2024 05:30:00 EST"),
+            ("German, DMY", "UTC", "15.01.2024 10:30:00 UTC"),
+            (
+                "Postgres, MDY",
+                "America/Los_Angeles",
+                "Mon Jan 15 02:30:00 2024 PST",
+            ),
+            (
+                "Postgres, DMY",
+                "America/Los_Angeles",
+                "Mon 15 Jan 02:30:00 2024 PST",
+            ),
+        ];
+
+        for (date_style, time_zone, expected) in expected {
+            let session = session(date_style, time_zone);
+            assert_eq!(
+                text(plaintext.clone(), &Type::TIMESTAMPTZ, &session),
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn timestamp_binary_is_timestamptz() {
+        let timestamp = Utc.with_ymd_and_hms(2024, 1, 15, 10, 30, 0).unwrap();
+        let plaintext = Plaintext::Timestamp(Some(timestamp));
+        let session = SessionParameters::default();
+
+        let mut expected = BytesMut::new();
+        timestamp.to_sql(&Type::TIMESTAMPTZ, &mut expected).unwrap();
+
+        let bytes = to_sql(
+            &plaintext,
+            &Type::TIMESTAMPTZ,
+            &FormatCode::Binary,
+            &session,
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(bytes, expected);
+    }
+
+    #[test]
+    fn big_uint_binary() {
+        let session = SessionParameters::default();
+
+        let bytes = to_sql(
+            &Plaintext::BigUInt(Some(42)),
+            &Type::INT8,
+            &FormatCode::Binary,
+            &session,
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(bytes.as_ref(), 42i64.to_be_bytes());
+
+        assert!(to_sql(
+            &Plaintext::BigUInt(Some(u64::MAX)),
+            &Type::INT8,
+            &FormatCode::Binary,
+            &session,
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn jsonb_text_and_binary() {
+        let value = json!({"name": "a\nb", "id": 1, "tags": [1, 2]});
+        let session = SessionParameters::default();
+
+        let expected = r#"{"id": 1, "name": "a\nb", "tags": [1, 2]}"#;
+        assert_eq!(
+            text(Plaintext::Json(Some(value.clone())), &Type::JSONB, &session),
+            expected
+        );
+
+        let bytes = to_sql(
+            &Plaintext::Json(Some(value)),
+            &Type::JSONB,
+            &FormatCode::Binary,
+            &session,
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(bytes[0], JSONB_VERSION);
+        assert_eq!(&bytes[1..], expected.as_bytes());
+    }
+}
@@ -0,0 +1,635 @@
+use super::aggregate;
+use super::context::{phase_timing::PhaseSpan, Context, Portal};
+use super::data::to_sql;
+use super::message_buffer::MessageBuffer;
+use super::messages::data_row::DataRow;
+use super::messages::notice_response::NoticeResponse;
+use super::Column;
+use crate::config::{DecryptFailurePolicy, ServerConfig};
+use crate::error::{EncryptError, Error, ZeroKMSError};
+use crate::log::{CONTEXT, DECRYPT, DEVELOPMENT};
+use crate::prometheus::{
+    DECRYPTED_VALUES_TOTAL, DECRYPTION_DURATION_SECONDS, DECRYPTION_ERROR_TOTAL,
+    DECRYPTION_FAILED_VALUES_TOTAL, DECRYPTION_REQUESTS_TOTAL, PROXY_AGGREGATE_VALUES_TOTAL,
+};
+use crate::proxy::EncryptionService;
+use crate::EqlCiphertext;
+use bytes::BytesMut;
+use cipherstash_client::encryption::Plaintext;
+use cipherstash_client::schema::ColumnType;
+use metrics::{counter, histogram};
+use std::collections::VecDeque;
+use std::ops::Range;
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime};
+use tokio::task::JoinHandle;
+use tracing::{debug, warn};
+
+///
+/// Decrypts batches of encrypted DataRows concurrently, and returns them in the order they were read
+///
+/// Rows are buffered until the batch reaches `decrypt_batch_size` rows, or its share of
+/// `decrypt_buffer_bytes`. Each batch is decrypted in its own task, so that reading from the
+/// database, ZeroKMS requests and writing to the client overlap.
+///
+/// The pipeline is saturated when `decrypt_concurrency` batches are in flight, or the batches in flight
+/// use the byte budget. The Backend waits for the oldest batch before reading more rows from the database.
+///
+pub struct DecryptPipeline {
+    buffer: MessageBuffer,
+    in_flight: VecDeque<InFlightBatch>,
+    in_flight_bytes: usize,
+    concurrency: usize,
+    budget_bytes: usize,
+}
+
+struct InFlightBatch {
+    bytes: usize,
+    handle: JoinHandle<Result<DecryptedBatch, Error>>,
+}
+
+///
+/// The decrypted rows of a batch, encoded for the client
+///
+/// `notices` warn of values that could not be decrypted, and are written before the rows.
+///
+pub struct DecryptedBatch {
+    pub notices: Vec<BytesMut>,
+    pub rows: Vec<BytesMut>,
+    /// When the batch was decrypted
+    pub decrypt: PhaseSpan,
+}
+
+impl DecryptPipeline {
+    pub fn new(config: &ServerConfig) -> Self {
+        let concurrency = config.decrypt_concurrency.max(1);
+        let budget_bytes = config.decrypt_buffer_bytes.max(1);
+
+        DecryptPipeline {
+            buffer: MessageBuffer::new(config.decrypt_batch_size, budget_bytes / concurrency),
+            in_flight: VecDeque::with_capacity(concurrency),
+            in_flight_bytes: 0,
+            concurrency,
+            budget_bytes,
+        }
+    }
+
+    ///
+    /// Buffers the row, returning true if the batch is ready to be decrypted
+    ///
+    pub fn push(&mut self, row: DataRow, len: usize) -> bool {
+        self.buffer.push(row, len);
+        self.buffer.at_capacity()
+    }
+
+    pub fn has_buffered_rows(&self) -> bool {
+        !self.buffer.is_empty()
+    }
+
+    ///
+    /// No more batches can be started until the oldest batch in flight completes
+    ///
+    pub fn is_saturated(&self) -> bool {
+        !self.in_flight.is_empty()
+            && (self.in_flight.len() >= self.concurrency
+                || self.in_flight_bytes + self.buffer.bytes() > self.budget_bytes)
+    }
+
+    ///
+    /// Starts decrypting the buffered rows of the portal
+    ///
+    pub fn dispatch<S>(&mut self, context: &Context<S>, portal: Arc<Portal>)
+    where
+        S: EncryptionService + Clone + 'static,
+    {
+        if self.buffer.is_empty() {
+            return;
+        }
+
+        let bytes = self.buffer.bytes();
+        debug!(target: DEVELOPMENT, client_id = context.client_id, rows = self.buffer.len(), bytes, in_flight = self.in_flight.len());
+
+        let rows = self.buffer.drain();
+        let handle = tokio::spawn(decrypt_batch(context.clone(), portal, rows));
+
+        self.in_flight_bytes += bytes;
+        self.in_flight.push_back(InFlightBatch { bytes, handle });
+    }
+
+    ///
+    /// Waits for the oldest batch in flight
+    ///
+    pub async fn next(&mut self) -> Option<Result<DecryptedBatch, Error>> {
+        let batch = self.in_flight.pop_front()?;
+        self.in_flight_bytes -= batch.bytes;
+
+        // Tasks are only cancelled by `clear`, which also removes them from the queue
+        let result = batch
+            .handle
+            .await
+            .unwrap_or_else(|err| std::panic::resume_unwind(err.into_panic()));
+
+        Some(result)
+    }
+
+    ///
+    /// Returns the oldest batch in flight if it has already completed
+    ///
+    pub async fn next_if_ready(&mut self) -> Option<Result<DecryptedBatch, Error>> {
+        match self.in_flight.front() {
+            Some(batch) if batch.handle.is_finished() => self.next().await,
+            _ => None,
+        }
+    }
+
+    ///
+    /// Drops the buffered rows and cancels the batches in flight
+    ///
+    pub fn clear(&mut self) {
+        self.buffer.clear();
+        for batch in self.in_flight.drain(..) {
+            batch.handle.abort();
+        }
+        self.in_flight_bytes = 0;
+    }
+}
+
+impl Drop for DecryptPipeline {
+    fn drop(&mut self) {
+        self.clear();
+    }
+}
+
+///
+/// Decrypts a batch of rows, and encodes the plaintexts with the format codes of the portal
+///
+async fn decrypt_batch<S>(
+    context: Context<S>,
+    portal: Arc<Portal>,
+    mut rows: Vec<DataRow>,
+) -> Result<DecryptedBatch, Error>
+where
+    S: EncryptionService,
+{
+    let result_column_count = match rows.first() {
+        Some(row) => row.column_count(),
+        None => {
+            return Ok(DecryptedBatch {
+                notices: vec![],
+                rows: vec![],
+                decrypt: PhaseSpan::ended_now(Duration::ZERO),
+            })
+        }
+    };
+
+    // Result Column Format Codes are passed with the Bind message
+    // Bind is turned into a Portal
+    // We pull the format codes from the portal
+    // If no portal, assume Text for all columns
+    let result_column_format_codes = portal.format_codes(result_column_count);
+
+    let projection_columns = portal.projection_columns();
+
+    // Values are encoded with the DateStyle, TimeZone and float format of the session
+    let session = context.session_parameters();
+
+    let policy = context.decrypt_failure_policy();
+
+    // Each row is converted into Vec<Option<CipherText>>
+    // Unless the policy is to fail, a value that cannot be deserialised is handled as undecryptable
+    let mut ciphertexts: Vec<Option<EqlCiphertext>> = vec![];
+    let mut undeserialisable = vec![];
+    for value in rows
+        .iter_mut()
+        .flat_map(|row| row.as_ciphertext(projection_columns))
+    {
+        match value {
+            Ok(ciphertext) => {
+                ciphertexts.push(ciphertext);
+                undeserialisable.push(false);
+            }
+            Err(err) if policy == DecryptFailurePolicy::Fail => return Err(err.into()),
+            Err(_) => {
+                ciphertexts.push(None);
+                undeserialisable.push(true);
+            }
+        }
+    }
+
+    // Proxy-side aggregates carry many ciphertexts per column
+    let max_values = context.proxy_aggregate_max_values();
+    let max_bytes = context.proxy_aggregate_max_bytes();
+    let aggregate_ciphertexts = rows
+        .iter()
+        .map(|row| row.as_aggregate_ciphertexts(projection_columns, max_values, max_bytes))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let started_at = SystemTime::now();
+    let start = Instant::now();
+
+    check_column_config(projection_columns, &ciphertexts)?;
+
+    debug!(target: CONTEXT,
+        client_id = context.client_id,
+        keyset_id = ?context.keyset_identifier(),
+    );
+
+    // Decrypt CipherText -> Plaintext
+    // Unless the policy is to fail, a batch with an undecryptable value is bisected to find the value
+    let (plaintexts, mut failed) = match context.decrypt(ciphertexts).await {
+        Ok(plaintexts) => {
+            let failed = vec![false; plaintexts.len()];
+            (plaintexts, failed)
+        }
+        Err(err) => {
+            counter!(DECRYPTION_ERROR_TOTAL).increment(1);
+
+            if policy == DecryptFailurePolicy::Fail || !is_undecryptable(&err) {
+                return Err(err);
+            }
+
+            debug!(target: DECRYPT, client_id = context.client_id, msg = "Batch could not be decrypted, bisecting the batch", error = err.to_string());
+
+            decrypt_bisected(&context, &mut rows, projection_columns).await?
+        }
+    };
+
+    for (failed, undeserialisable) in failed.iter_mut().zip(undeserialisable) {
+        *failed |= undeserialisable;
+    }
+
+    let duration = Instant::now().duration_since(start);
+
+    // Prometheus metrics remain gated
+    if context.prometheus_enabled() {
+        let decrypted_count = plaintexts
+            .iter()
+            .fold(0, |acc, o| if o.is_some() { acc + 1 } else { acc });
+
+        counter!(DECRYPTION_REQUESTS_TOTAL).increment(1);
+        counter!(DECRYPTED_VALUES_TOTAL).increment(decrypted_count);
+        histogram!(DECRYPTION_DURATION_SECONDS).record(duration);
+    }
+
+    let aggregates =
+        decrypt_aggregates(&context, projection_columns, aggregate_ciphertexts).await?;
+
+    // Undecryptable values by column index
+    let mut failed_counts = vec![0u64; result_column_count];
+
+    // Chunk rows into sets of columns
+    let rows = plaintexts
+        .chunks(result_column_count)
+        .zip(failed.chunks(result_column_count))
+        .zip(rows)
+        .zip(aggregates);
+
+    // Stitch Plaintext back into Rows encoded with the appropriate Format Code
+    let mut encoded = Vec::with_capacity(rows.len());
+    for (((chunk, failed), mut row), aggregates) in rows {
+        let mut data = chunk
+            .iter()
+            .zip(result_column_format_codes.iter())
+            .zip(projection_columns.iter())
+            .map(
+                |((plaintext, format_code), column)| match (plaintext, column) {
+                    (Some(plaintext), Some(column)) => {
+                        to_sql(plaintext, &column.postgres_type, format_code, &session)
+                    }
+                    _ => Ok(None),
+                },
+            )
+            .collect::<Result<Vec<_>, _>>()?;
+
+        for (idx, _) in failed.iter().enumerate().filter(|(_, failed)| **failed) {
+            failed_counts[idx] += 1;
+
+            match (policy, &projection_columns[idx]) {
+                (DecryptFailurePolicy::Sentinel, Some(column))
+                    if column.cast_type() == ColumnType::Text =>
+                {
+                    let sentinel = Plaintext::new(context.decrypt_failure_sentinel().to_owned());
+                    data[idx] = to_sql(
+                        &sentinel,
+                        &column.postgres_type,
+                        &result_column_format_codes[idx],
+                        &session,
+                    )?
+                }
+                _ => row.columns[idx].set_null(),
+            }
+        }
+
+        for (idx, result) in aggregates {
+            match (result, &projection_columns[idx]) {
+                (Some(plaintext), Some(column)) => {
+                    data[idx] = to_sql(
+                        &plaintext,
+                        &column.postgres_type,
+                        &result_column_format_codes[idx],
+                        &session,
+                    )?
+                }
+                _ => row.columns[idx].set_null(),
+            }
+        }
+
+        row.rewrite(&data)?;
+        encoded.push(BytesMut::try_from(row)?);
+    }
+
+    let notices = failed_counts
+        .into_iter()
+        .zip(projection_columns.iter())
+        .filter(|(count, _)| *count > 0)
+        .filter_map(|(count, column)| column.as_ref().map(|column| (count, column)))
+        .map(|(count, column)| failure_notice(policy, count, column))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(DecryptedBatch {
+        notices,
+        rows: encoded,
+        decrypt: PhaseSpan {
+            start: started_at,
+            end: started_at + duration,
+        },
+    })
+}
+
+///
+/// Decrypts the values of rows that failed as a batch, so that one undecryptable value does not fail the others
+///
+/// The batch is split in half, and a half that fails is split again until the value that cannot be
+/// decrypted is found. A batch with a few undecryptable values takes a few requests for each of them,
+/// rather than a request for every value in the batch.
+///
+/// Returns the plaintexts, and whether each value could not be decrypted.
+/// Errors that are not caused by a value, such as limits or ZeroKMS being unavailable, are returned.
+///
+async fn decrypt_bisected<S>(
+    context: &Context<S>,
+    rows: &mut [DataRow],
+    projection_columns: &Vec<Option<Column>>,
+) -> Result<(Vec<Option<Plaintext>>, Vec<bool>), Error>
+where
+    S: EncryptionService,
+{
+    let len = rows.len() * projection_columns.len();
+
+    let mut plaintexts = vec![None; len];
+    let mut failed = vec![false; len];
+
+    // The whole batch has already failed
+    let mut ranges = halves(0..len);
+
+    while let Some(range) = ranges.pop() {
+        let ciphertexts = ciphertexts_in(rows, projection_columns, range.clone());
+
+        let values = ciphertexts
+            .iter()
+            .enumerate()
+            .filter(|(_, ciphertext)| ciphertext.is_some())
+            .map(|(idx, _)| range.start + idx)
+            .collect::<Vec<_>>();
+
+        if values.is_empty() {
+            continue;
+        }
+
+        match context.decrypt(ciphertexts).await {
+            Ok(decrypted) => {
+                for (idx, plaintext) in range.zip(decrypted) {
+                    plaintexts[idx] = plaintext;
+                }
+            }
+            Err(err) if is_undecryptable(&err) => match values.as_slice() {
+                [idx] => {
+                    debug!(target: DECRYPT, client_id = context.client_id, msg = "Value could not be decrypted", error = err.to_string());
+                    failed[*idx] = true;
+                }
+                _ => ranges.extend(halves(range)),
+            },
+            Err(err) => return Err(err),
+        }
+    }
+
+    Ok((plaintexts, failed))
+}
+
+///
+/// Splits a range of values in two, with the first half last so that it is decrypted first
+///
+fn halves(range: Range<usize>) -> [Range<usize>; 2] {
+    let mid = range.start + range.len() / 2;
+    [mid..range.end, range.start..mid]
+}
+
+///
+/// The ciphertexts of a range of the values of the rows
+///
+/// Values that cannot be deserialised have already been handled, and are `None`.
+///
+fn ciphertexts_in(
+    rows: &mut [DataRow],
+    projection_columns: &Vec<Option<Column>>,
+    range: Range<usize>,
+) -> Vec<Option<EqlCiphertext>> {
+    let columns = projection_columns.len().max(1);
+    let first_row = range.start / columns;
+    let last_row = range.end.div_ceil(columns);
+
+    rows[first_row..last_row]
+        .iter_mut()
+        .flat_map(|row| row.as_ciphertext(projection_columns))
+        .skip(range.start - first_row * columns)
+        .take(range.len())
+        .map(|value| value.ok().flatten())
+        .collect()
+}
+
+///
+/// True if the error is caused by a value that cannot be decrypted,
+/// such as a value encrypted with another keyset or a corrupt ciphertext
+///
+/// Every other error, including ZeroKMS being unavailable or a request failing once its retries are used up,
+/// fails the statement, so that an outage is never returned as NULL.
+///
+fn is_undecryptable(err: &Error) -> bool {
+    matches!(
+        err.root(),
+        Error::ZeroKMS(ZeroKMSError::System(
+            cipherstash_client::zerokms::Error::Decrypt(_)
+        )) | Error::Encrypt(
+            EncryptError::CouldNotDecryptDataForKeyset { .. }
+                | EncryptError::ColumnCouldNotBeDeserialised { .. }
+                | EncryptError::PlaintextCouldNotBeDecoded(_)
+                | EncryptError::SteVecMissingRootEntry
+                | EncryptError::SteVecSelectorInvalid { .. }
+        )
+    )
+}
+
+///
+/// Records the undecryptable values of a column, and warns the client with a NoticeResponse
+///
+fn failure_notice(
+    policy: DecryptFailurePolicy,
+    count: u64,
+    column: &Column,
+) -> Result<BytesMut, Error> {
+    let table = column.table_name();
+    let column_name = column.column_name();
+
+    counter!(DECRYPTION_FAILED_VALUES_TOTAL, "table" => table.clone(), "column" => column_name.clone())
+        .increment(count);
+
+    let returned_as = match policy {
+        DecryptFailurePolicy::Sentinel if column.cast_type() == ColumnType::Text => "the sentinel",
+        _ => "NULL",
+    };
+
+    let message = format!(
+        "{count} value(s) of column '{column_name}' in table '{table}' could not be decrypted and were returned as {returned_as}"
+    );
+    warn!(target: DECRYPT, msg = message);
+
+    BytesMut::try_from(NoticeResponse::warning(message, &table, &column_name))
+}
+
+/// Decrypts the values of each proxy-side aggregate in `rows` and computes
+/// the aggregate, returning the results by row and column index.
+///
+/// The values of every aggregate in the batch are decrypted in one request.
+async fn decrypt_aggregates<S>(
+    context: &Context<S>,
+    projection_columns: &[Option<Column>],
+    rows: Vec<Vec<(usize, Vec<EqlCiphertext>)>>,
+) -> Result<Vec<Vec<(usize, Option<Plaintext>)>>, Error>
+where
+    S: EncryptionService,
+{
+    let value_count: usize = rows
+        .iter()
+        .flatten()
+        .map(|(_, ciphertexts)| ciphertexts.len())
+        .sum();
+
+    if value_count > 0 && context.prometheus_enabled() {
+        counter!(PROXY_AGGREGATE_VALUES_TOTAL).increment(value_count as u64);
+    }
+
+    let shape = rows
+        .iter()
+        .map(|row| {
+            row.iter()
+                .map(|(idx, ciphertexts)| (*idx, ciphertexts.len()))
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+
+    let ciphertexts = rows
+        .into_iter()
+        .flatten()
+        .flat_map(|(_, ciphertexts)| ciphertexts)
+        .map(Some)
+        .collect::<Vec<_>>();
+
+    let plaintexts = if ciphertexts.is_empty() {
+        vec![]
+    } else {
+        context.decrypt(ciphertexts).await.inspect_err(|_| {
+            counter!(DECRYPTION_ERROR_TOTAL).increment(1);
+        })?
+    };
+
+    let mut plaintexts = plaintexts.into_iter();
+    let mut results = Vec::with_capacity(shape.len());
+
+    for row in shape {
+        let mut row_results = vec![];
+        for (idx, len) in row {
+            let values = plaintexts.by_ref().take(len).flatten().collect::<Vec<_>>();
+
+            if let Some(column) = &projection_columns[idx] {
+                row_results.push((idx, aggregate::compute(column, &values)?));
+            }
+        }
+        results.push(row_results);
+    }
+
+    Ok(results)
+}
+
+fn check_column_config(
+    projection_columns: &[Option<Column>],
+    ciphertexts: &[Option<EqlCiphertext>],
+) -> Result<(), Error> {
+    for (col, ct) in projection_columns.iter().zip(ciphertexts) {
+        match (col, ct) {
+            (Some(col), Some(ct)) => {
+                if &col.identifier != ct.identifier() {
+                    return Err(EncryptError::ColumnConfigurationMismatch {
+                        table: col.identifier.table.to_owned(),
+                        column: col.identifier.column.to_owned(),
+                    }
+                    .into());
+                }
+            }
+            // configured column with NULL ciphertext
+            (Some(_), None) => {}
+            // unconfigured column *should* have no ciphertext,
+            (None, None) => {}
+            // ciphertext with no column configuration is bad
+            (None, Some(ct)) => {
+                return Err(EncryptError::ColumnConfigurationMismatch {
+                    table: ct.identifier().table.to_owned(),
+                    column: ct.identifier().column.to_owned(),
+                }
+                .into());
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{halves, is_undecryptable};
+    use crate::error::{EncryptError, Error, ZeroKMSError};
+    use std::sync::Arc;
+
+    #[test]
+    fn halves_decrypts_the_first_half_first() {
+        assert_eq!(halves(0..5), [2..5, 0..2]);
+        assert_eq!(halves(4..6), [5..6, 4..5]);
+    }
+
+    #[test]
+    fn undecryptable_values() {
+        let keyset = Error::Encrypt(EncryptError::CouldNotDecryptDataForKeyset {
+            keyset_id: "keyset".to_string(),
+        });
+        assert!(is_undecryptable(&keyset));
+        assert!(is_undecryptable(&Error::Shared(Arc::new(keyset))));
+
+        let corrupt = Error::Encrypt(EncryptError::ColumnCouldNotBeDeserialised {
+            table: "users".to_string(),
+            column: "email".to_string(),
+        });
+        assert!(is_undecryptable(&corrupt));
+    }
+
+    #[test]
+    fn outages_are_not_undecryptable() {
+        let unavailable = Error::ZeroKMS(ZeroKMSError::Unavailable {
+            retry_after_seconds: 5,
+        });
+        assert!(!is_undecryptable(&unavailable));
+        assert!(!is_undecryptable(&Error::Shared(Arc::new(unavailable))));
+
+        assert!(!is_undecryptable(&Error::ZeroKMS(
+            ZeroKMSError::AuthenticationFailed
+        )));
+        assert!(!is_undecryptable(&Error::Unknown));
+    }
+}
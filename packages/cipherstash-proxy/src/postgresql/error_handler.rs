@@ -5,7 +5,7 @@
 /// ErrorResponse messages and sent to clients in a protocol-compliant manner.
 use crate::{
     connect::Sender,
-    error::{EncryptError, Error, MappingError},
+    error::{EncryptError, Error, LimitError, MappingError},
     postgresql::messages::error_response::ErrorResponse,
 };
 
@@ -28,14 +28,16 @@ pub trait PostgreSqlErrorHandler {
     /// - `MappingError` -> InvalidSqlStatement error
     /// - `EncryptError::UnknownColumn` -> Unknown column error
     /// - `EncryptError::CouldNotRetrieveKey` -> Key retrieval error
+    /// - `LimitError` connection limits -> Too many connections error
+    /// - `LimitError` rate limits -> Configuration limit exceeded error
     /// - All others -> System error
     ///
     /// # Arguments
     ///
     /// * `err` - The error to be converted to a PostgreSQL ErrorResponse
     fn error_to_response(&self, err: Error) -> ErrorResponse {
-        match err {
-            Error::Mapping(MappingError::InvalidParameter(ref column)) => {
+        match err.root() {
+            Error::Mapping(MappingError::InvalidParameter(column)) => {
                 ErrorResponse::invalid_parameter(
                     err.to_string(),
                     &column.table_name(),
@@ -43,10 +45,9 @@ pub trait PostgreSqlErrorHandler {
                 )
             }
             Error::Mapping(err) => ErrorResponse::invalid_sql_statement(err.to_string()),
-            Error::Encrypt(EncryptError::UnknownColumn {
-                ref table,
-                ref column,
-            }) => ErrorResponse::unknown_column(err.to_string(), table, column),
+            Error::Encrypt(EncryptError::UnknownColumn { table, column }) => {
+                ErrorResponse::unknown_column(err.to_string(), table, column)
+            }
             Error::Encrypt(EncryptError::CouldNotDecryptDataForKeyset { .. }) => {
                 ErrorResponse::system_error(err.to_string())
             }
@@ -54,6 +55,10 @@ pub trait PostgreSqlErrorHandler {
                 ErrorResponse::system_error(err.to_string())
             }
             Error::ConnectionTimeout { .. } => ErrorResponse::connection_timeout(err.to_string()),
+            Error::Limit(
+                LimitError::TooManyConnections { .. } | LimitError::TooManyRoleConnections { .. },
+            ) => ErrorResponse::too_many_connections(err.to_string()),
+            Error::Limit(_) => ErrorResponse::configuration_limit_exceeded(err.to_string()),
             _ => ErrorResponse::system_error(err.to_string()),
         }
     }
@@ -73,9 +78,10 @@ pub trait PostgreSqlErrorHandler {
 mod tests {
     use super::*;
     use crate::postgresql::messages::error_response::{
-        ErrorResponseCode, CODE_IDLE_SESSION_TIMEOUT, CODE_SYSTEM_ERROR,
+        ErrorResponseCode, CODE_CONFIGURATION_LIMIT_EXCEEDED, CODE_IDLE_SESSION_TIMEOUT,
+        CODE_SYSTEM_ERROR, CODE_TOO_MANY_CONNECTIONS,
     };
-    use std::time::Duration;
+    use std::{sync::Arc, time::Duration};
 
     /// Minimal implementation of PostgreSqlErrorHandler for testing the default method.
     struct TestHandler;
@@ -131,4 +137,66 @@ mod tests {
         let response = handler.error_to_response(err);
         assert_eq!(error_code(&response), Some(CODE_SYSTEM_ERROR));
     }
+
+    #[test]
+    fn connection_limits_map_to_53300() {
+        let handler = TestHandler;
+
+        let err = LimitError::TooManyConnections { limit: 10 };
+        let response = handler.error_to_response(err.into());
+        assert_eq!(error_code(&response), Some(CODE_TOO_MANY_CONNECTIONS));
+        assert!(response.is_fatal());
+
+        let err = LimitError::TooManyRoleConnections {
+            role: "app".to_string(),
+            limit: 2,
+        };
+        let response = handler.error_to_response(err.into());
+        assert_eq!(error_code(&response), Some(CODE_TOO_MANY_CONNECTIONS));
+        assert!(response.is_fatal());
+    }
+
+    #[test]
+    fn shared_errors_map_like_their_root() {
+        let handler = TestHandler;
+
+        let err = LimitError::TooManyConnections { limit: 10 };
+        let response = handler.error_to_response(Error::Shared(Arc::new(err.into())));
+        assert_eq!(error_code(&response), Some(CODE_TOO_MANY_CONNECTIONS));
+        assert_eq!(
+            error_message(&response),
+            Some(
+                Error::from(LimitError::TooManyConnections { limit: 10 })
+                    .to_string()
+                    .as_str()
+            )
+        );
+    }
+
+    #[test]
+    fn rate_limits_map_to_53400() {
+        let handler = TestHandler;
+
+        let err = LimitError::StatementRateLimitExceeded {
+            keyset: "default".to_string(),
+            limit: 100,
+        };
+        let response = handler.error_to_response(err.into());
+        assert_eq!(
+            error_code(&response),
+            Some(CODE_CONFIGURATION_LIMIT_EXCEEDED)
+        );
+        assert!(!response.is_fatal());
+
+        let err = LimitError::ValueRateLimitExceeded {
+            keyset: "default".to_string(),
+            limit: 1000,
+        };
+        let response = handler.error_to_response(err.into());
+        assert_eq!(
+            error_code(&response),
+            Some(CODE_CONFIGURATION_LIMIT_EXCEEDED)
+        );
+        assert!(!response.is_fatal());
+    }
 }
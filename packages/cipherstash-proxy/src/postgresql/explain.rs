@@ -0,0 +1,248 @@
+//! `CIPHERSTASH EXPLAIN <statement>` — shows how Proxy would map a statement
+//! without executing it.
+//!
+//! The statement is type-checked and rewritten exactly as it would be for
+//! execution, except that encrypted literals are replaced by a placeholder
+//! rather than encrypted. The result set has one row per fact:
+//!
+//! | kind         | name                     | detail                                  |
+//! |--------------|--------------------------|-----------------------------------------|
+//! | `statement`  |                          | the rewritten SQL                       |
+//! | `param`      | `$1`                     | `EQL:Full users.email`, or `native`     |
+//! | `literal`    | `'<encrypted>'`          | `EQL:Full users.email`                  |
+//! | `projection` | the column name          | `decrypt users.email as text`           |
+//! | `rule`       | the transformation rule  |                                         |
+//!
+//! Only the simple query protocol is supported. The explained statement may
+//! still contain `$n` placeholders, since it is never bound.
+//!
+//! The proxy answers with a constant `VALUES` query in place of the original
+//! statement — the same substitution it makes for a frontend error — so the
+//! response keeps its place among any in-flight results and carries the
+//! server's transaction status. The explained statement never reaches the
+//! database, and neither does the plaintext of its encrypted literals: they
+//! are redacted from every row of the explanation, as they are from the
+//! rewritten statement.
+
+use crate::postgresql::Column;
+use eql_mapper::{TransformedStatement, TypeCheckedStatement, Value};
+use pg_escape::quote_literal;
+use sqltk::parser::ast::{self, Statement};
+use sqltk::NodeKey;
+use std::collections::HashMap;
+
+/// The text an encrypted literal is shown as in the rewritten statement.
+pub const ENCRYPTED_LITERAL_PLACEHOLDER: &str = "<encrypted>";
+
+///
+/// The statement to explain, if `sql` is a `CIPHERSTASH EXPLAIN` command.
+///
+/// The keywords are case-insensitive and may be separated by any whitespace.
+///
+pub fn strip_explain_prefix(sql: &str) -> Option<&str> {
+    let rest = strip_keyword(sql.trim_start(), "CIPHERSTASH")?;
+    strip_keyword(rest, "EXPLAIN")
+}
+
+fn strip_keyword<'a>(sql: &'a str, keyword: &str) -> Option<&'a str> {
+    let head = sql.get(..keyword.len())?;
+    let rest = &sql[keyword.len()..];
+
+    if head.eq_ignore_ascii_case(keyword) && rest.starts_with(char::is_whitespace) {
+        Some(rest.trim_start())
+    } else {
+        None
+    }
+}
+
+///
+/// Replaces every encrypted literal of `typed_statement` with
+/// [`ENCRYPTED_LITERAL_PLACEHOLDER`], in the form `TypeCheckedStatement::transform` expects.
+///
+pub fn placeholder_literals<'ast>(
+    typed_statement: &TypeCheckedStatement<'ast>,
+) -> HashMap<NodeKey<'ast>, ast::Value> {
+    typed_statement
+        .literal_values()
+        .iter()
+        .filter(|(_, literal)| !matches!(literal, ast::Value::Null))
+        .map(|(_, literal)| {
+            (
+                NodeKey::new(*literal),
+                ast::Value::SingleQuotedString(ENCRYPTED_LITERAL_PLACEHOLDER.to_string()),
+            )
+        })
+        .collect()
+}
+
+#[derive(Debug, PartialEq)]
+struct Row {
+    kind: &'static str,
+    name: Option<String>,
+    detail: Option<String>,
+}
+
+impl Row {
+    fn new(kind: &'static str, name: Option<String>, detail: Option<String>) -> Self {
+        Self { kind, name, detail }
+    }
+}
+
+/// The result of `CIPHERSTASH EXPLAIN`.
+#[derive(Debug, PartialEq)]
+pub struct Explanation {
+    rows: Vec<Row>,
+}
+
+impl Explanation {
+    ///
+    /// A statement that does not need type checking, and is forwarded as-is.
+    ///
+    pub fn passthrough(statement: &Statement) -> Self {
+        Self {
+            rows: vec![Row::new("statement", None, Some(statement.to_string()))],
+        }
+    }
+
+    ///
+    /// A type-checked statement, with its rewritten form if it needs one.
+    ///
+    /// `projection_columns` are the decrypt plan for the statement's
+    /// projection, as `Context::get_projection_columns` returns it.
+    ///
+    pub fn new(
+        typed_statement: &TypeCheckedStatement<'_>,
+        transformed: Option<&TransformedStatement>,
+        projection_columns: &[Option<Column>],
+    ) -> Self {
+        let sql = match transformed {
+            Some(transformed) => transformed.to_string(),
+            None => typed_statement.statement.to_string(),
+        };
+
+        let mut rows = vec![Row::new("statement", None, Some(sql))];
+
+        for (param, value) in &typed_statement.params {
+            rows.push(Row::new(
+                "param",
+                Some(param.to_string()),
+                Some(value_detail(value)),
+            ));
+        }
+
+        // The explanation is sent to the database, so the plaintext is redacted
+        for (term, _) in typed_statement.literal_values() {
+            rows.push(Row::new(
+                "literal",
+                Some(quote_literal(ENCRYPTED_LITERAL_PLACEHOLDER).to_string()),
+                Some(format!("{} {}", term.variant(), term.eql_value().0)),
+            ));
+        }
+
+        let columns = typed_statement.projection.columns();
+        for (idx, column) in columns.iter().enumerate() {
+            let name = column
+                .alias
+                .as_ref()
+                .map(|alias| alias.value.clone())
+                .unwrap_or_else(|| "?column?".to_string());
+
+            let detail = match projection_columns.get(idx).and_then(Option::as_ref) {
+                Some(column) => projection_detail(column),
+                None => "native".to_string(),
+            };
+
+            rows.push(Row::new("projection", Some(name), Some(detail)));
+        }
+
+        for rule in transformed.iter().flat_map(|t| t.applied_rules.iter()) {
+            rows.push(Row::new("rule", Some(rule.to_string()), None));
+        }
+
+        Self { rows }
+    }
+
+    ///
+    /// The query that returns this explanation as a result set of
+    /// `(kind, name, detail)` text columns.
+    ///
+    pub fn to_sql(&self) -> String {
+        let literal = |value: &Option<String>| match value {
+            Some(value) => format!("{}::text", quote_literal(value)),
+            None => "NULL::text".to_string(),
+        };
+
+        let rows = self
+            .rows
+            .iter()
+            .map(|row| {
+                format!(
+                    "({}, {}, {})",
+                    quote_literal(row.kind),
+                    literal(&row.name),
+                    literal(&row.detail)
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        format!("SELECT * FROM (VALUES {rows}) AS cipherstash_explain (kind, name, detail)")
+    }
+}
+
+fn value_detail(value: &Value) -> String {
+    match value {
+        Value::Eql(term) => format!("{} {}", term.variant(), term.eql_value().0),
+        _ => "native".to_string(),
+    }
+}
+
+fn projection_detail(column: &Column) -> String {
+    let identifier = format!("{}.{}", column.table_name(), column.column_name());
+
+    match column.aggregate {
+        Some(aggregate) => format!(
+            "decrypt {identifier} and compute {aggregate} as {}",
+            column.postgres_type
+        ),
+        None => format!("decrypt {identifier} as {}", column.postgres_type),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn strips_explain_prefix() {
+        assert_eq!(
+            strip_explain_prefix("CIPHERSTASH EXPLAIN SELECT 1"),
+            Some("SELECT 1")
+        );
+        assert_eq!(
+            strip_explain_prefix("  cipherstash\n\texplain   SELECT 1"),
+            Some("SELECT 1")
+        );
+        assert_eq!(strip_explain_prefix("CIPHERSTASH EXPLAINSELECT 1"), None);
+        assert_eq!(strip_explain_prefix("EXPLAIN SELECT 1"), None);
+        assert_eq!(strip_explain_prefix("SELECT 'CIPHERSTASH EXPLAIN'"), None);
+    }
+
+    #[test]
+    fn explanation_is_a_values_query() {
+        let explanation = Explanation {
+            rows: vec![
+                Row::new("statement", None, Some("SELECT 'it''s'".to_string())),
+                Row::new("rule", Some("RewriteEqlOrderBy".to_string()), None),
+            ],
+        };
+
+        assert_eq!(
+            explanation.to_sql(),
+            "SELECT * FROM (VALUES \
+             ('statement', NULL::text, 'SELECT ''it''''s'''::text), \
+             ('rule', 'RewriteEqlOrderBy'::text, NULL::text)\
+             ) AS cipherstash_explain (kind, name, detail)"
+        );
+    }
+}
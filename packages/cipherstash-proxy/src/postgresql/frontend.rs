@@ -1,14 +1,22 @@
+use super::catalog;
+use super::context::diversion::DivertedResponse;
 use super::context::phase_timing::PhaseTimer;
-use super::context::{Context, SessionId, Statement};
+use super::context::{self, Context, SessionId, Statement};
 use super::error_handler::PostgreSqlErrorHandler;
+use super::explain::{self, Explanation};
 use super::messages::bind::Bind;
 use super::messages::describe::Describe;
 use super::messages::execute::Execute;
+use super::messages::flush::Flush;
 use super::messages::parse::Parse;
 use super::messages::query::Query;
+use super::messages::sync::Sync as SyncMessage;
 use super::messages::FrontendCode as Code;
 use super::parser::SqlParser;
+use super::pipeline::{PendingMessage, PendingMessages};
+use super::plaintext_source::{self, PlaintextSource};
 use super::protocol::{self};
+use super::router::{Held, ReplicaBatch, Route};
 use crate::connect::Sender;
 use crate::error::{EncryptError, Error, MappingError};
 use crate::log::{MAPPER, PROTOCOL};
@@ -16,7 +24,9 @@ use crate::postgresql::context::column::Column;
 use crate::postgresql::context::statement::{
     output_params_from_plan, OutputParam, OutputParamSource,
 };
-use crate::postgresql::context::statement_metadata::{ProtocolType, StatementType};
+use crate::postgresql::context::statement_metadata::{
+    statement_fingerprint, ProtocolType, StatementType,
+};
 use crate::postgresql::context::Portal;
 use crate::postgresql::data::{
     compose_json_selector_path, json_value_selector_plaintext, literal_from_sql, literal_json_value,
@@ -30,13 +40,18 @@ use crate::prometheus::{
     CLIENTS_BYTES_RECEIVED_TOTAL, ENCRYPTED_VALUES_TOTAL, ENCRYPTION_DURATION_SECONDS,
     ENCRYPTION_ERROR_TOTAL, ENCRYPTION_REQUESTS_TOTAL, SERVER_BYTES_SENT_TOTAL,
     STATEMENTS_ENCRYPTED_TOTAL, STATEMENTS_PASSTHROUGH_MAPPING_DISABLED_TOTAL,
-    STATEMENTS_PASSTHROUGH_TOTAL, STATEMENTS_UNMAPPABLE_TOTAL,
+    STATEMENTS_PASSTHROUGH_TOTAL, STATEMENTS_REPLICA_TOTAL, STATEMENTS_STRICT_REFUSED_TOTAL,
+    STATEMENTS_UNMAPPABLE_TOTAL,
 };
 use crate::proxy::EncryptionService;
 use crate::{EqlOutput, EqlQueryPayload};
 use bytes::BytesMut;
 use cipherstash_client::encryption::Plaintext;
-use eql_mapper::{self, EqlMapperError, EqlTermVariant, JsonSelectorSegment, TypeCheckedStatement};
+use cipherstash_client::schema::ColumnType;
+use eql_mapper::{
+    self, EqlMapperError, EqlTermVariant, JsonSelectorSegment, TypeCheckOptions,
+    TypeCheckedStatement,
+};
 use metrics::{counter, histogram};
 use pg_escape::quote_literal;
 use serde::Serialize;
@@ -48,6 +63,11 @@ use std::time::Instant;
 use tokio::io::{AsyncRead, AsyncWrite, AsyncWriteExt};
 use tracing::{debug, error, info, warn};
 
+/// The name of the statement and portal Proxy uses to read a setting, which are closed once it is read
+const READ_SETTING_NAME: &str = "cipherstash_proxy_read_setting";
+
+const READ_EXTRA_FLOAT_DIGITS: &str = "SELECT current_setting('extra_float_digits')";
+
 /// The PostgreSQL proxy frontend that handles client-to-server message processing.
 ///
 /// The Frontend intercepts messages from PostgreSQL clients, analyzes SQL statements for
@@ -107,6 +127,12 @@ where
     context: Context<S>,
     /// Error state flag for extended query protocol error handling
     error_state: Option<ErrorState>,
+    /// The database connection for the current message
+    route: Route,
+    /// An extended protocol batch held until its Sync, so it can be sent to the replica
+    replica_batch: ReplicaBatch,
+    /// Client messages read ahead of the current message, to encrypt pipelined Binds together
+    pending: PendingMessages,
 }
 
 /// How a frontend failure was delivered, which determines how the batch's
@@ -142,12 +168,17 @@ where
         server_writer: W,
         context: Context<S>,
     ) -> Self {
+        let pending = PendingMessages::new(context.bind_batch_size());
+
         Frontend {
             client_reader,
             client_sender,
             server_writer,
             context,
             error_state: None,
+            route: Route::Primary,
+            replica_batch: ReplicaBatch::default(),
+            pending,
         }
     }
 
@@ -175,23 +206,22 @@ where
     /// Returns `Ok(())` on successful message processing, or an `Error` if a fatal
     /// error occurs that should terminate the connection.
     pub async fn rewrite(&mut self) -> Result<(), Error> {
-        let (code, mut bytes) = protocol::read_message(
-            &mut self.client_reader,
-            self.context.client_id,
-            self.context.connection_timeout(),
-        )
-        .await?;
+        // Messages read ahead by a Bind are processed before reading from the client again
+        let (code, mut bytes, encrypted) = match self.pending.pop() {
+            Some(message) => (message.code, message.bytes, message.encrypted),
+            None => {
+                let (code, bytes) = self.read_message().await?;
+                (code, bytes, None)
+            }
+        };
 
-        let sent: u64 = bytes.len() as u64;
-        counter!(CLIENTS_BYTES_RECEIVED_TOTAL).increment(sent);
+        self.route = Route::Primary;
 
         if self.context.mapping_disabled() {
             self.write_to_server(bytes).await?;
             return Ok(());
         }
 
-        let code = Code::from(code);
-
         // When an error is detected while processing any extended-query message, the backend issues ErrorResponse, then reads and discards messages until a Sync is reached,
         // https://www.postgresql.org/docs/current/protocol-flow.html#PROTOCOL-FLOW-EXT-QUERY
         if self.error_state.is_some() {
@@ -267,7 +297,7 @@ where
                 }
             }
             Code::Bind => {
-                match self.bind_handler(&bytes).await {
+                match self.bind_handler(&bytes, encrypted).await {
                     Ok(Some(mapped)) => bytes = mapped,
                     // No mapping needed, don't change the bytes
                     Ok(None) => (),
@@ -317,6 +347,7 @@ where
                     Some(ErrorState::ErrorResponseSent) => {
                         // Nothing was forwarded to the server for this batch;
                         // answer the Sync directly.
+                        self.release_replica_batch().await?;
                         self.send_ready_for_query()?;
                         return Ok(());
                     }
@@ -339,11 +370,85 @@ where
         Ok(())
     }
 
+    async fn read_message(&mut self) -> Result<(Code, BytesMut), Error> {
+        let (code, bytes) = protocol::read_message(
+            &mut self.client_reader,
+            self.context.client_id,
+            self.context.connection_timeout(),
+        )
+        .await?;
+
+        let sent: u64 = bytes.len() as u64;
+        counter!(CLIENTS_BYTES_RECEIVED_TOTAL).increment(sent);
+
+        Ok((Code::from(code), bytes))
+    }
+
+    ///
+    /// Reads the rest of the run of Bind, Execute and Describe messages that follows a Bind
+    ///
+    /// A client ends a run with a Sync or Flush before it waits for results,
+    /// as PostgreSQL does not send results until then.
+    /// A client that stops part way through a run and waits anyway gets no results for the run.
+    /// The read waits until `connection_timeout`, or indefinitely if none is set.
+    ///
+    async fn read_ahead(&mut self) -> Result<(), Error> {
+        while self.pending.requires_read() {
+            let (code, bytes) = self.read_message().await?;
+            self.pending.push(PendingMessage::new(code, bytes));
+        }
+        Ok(())
+    }
+
+    ///
+    /// Writes messages to the server, holding an extended protocol batch that may be sent to the replica
+    ///
+    /// See [`ReplicaBatch`].
+    ///
     pub async fn write_to_server(&mut self, bytes: BytesMut) -> Result<(), Error> {
+        let route = std::mem::take(&mut self.route);
+
+        match self.replica_batch.hold(route, bytes) {
+            Held::Pending => Ok(()),
+            Held::Ready(batch) => self.write_routed(Route::Replica, batch).await,
+            Held::Released { held, bytes, route } => {
+                if let Some(held) = held {
+                    self.write_routed(Route::Primary, held).await?;
+                }
+                self.write_routed(route, bytes).await
+            }
+        }
+    }
+
+    ///
+    /// Sends a held batch to the primary
+    ///
+    async fn release_replica_batch(&mut self) -> Result<(), Error> {
+        if let Some(held) = self.replica_batch.take() {
+            self.write_routed(Route::Primary, held).await?;
+        }
+        Ok(())
+    }
+
+    ///
+    /// Waits until the server has answered every request sent to it
+    ///
+    /// Returns false without waiting while a batch is held or open, see [`Context::wait_until_server_idle`].
+    ///
+    async fn wait_until_server_idle(&self) -> bool {
+        self.replica_batch.is_empty() && self.context.wait_until_server_idle().await
+    }
+
+    async fn write_routed(&mut self, route: Route, bytes: BytesMut) -> Result<(), Error> {
         debug!(target: PROTOCOL, msg = "Write to server", ?bytes);
         let sent: u64 = bytes.len() as u64;
         counter!(SERVER_BYTES_SENT_TOTAL).increment(sent);
 
+        if self.context.route_message(route, &bytes).await == Route::Replica {
+            debug!(target: PROTOCOL, client_id = self.context.client_id, msg = "Routed to replica");
+            counter!(STATEMENTS_REPLICA_TOTAL).increment(1);
+        }
+
         let start = Instant::now();
         self.server_writer.write_all(&bytes).await?;
         let duration = start.elapsed();
@@ -430,9 +535,18 @@ where
         let parse_timer = PhaseTimer::start();
 
         let mut query = Query::try_from(bytes)?;
+        self.context
+            .set_statement_traceparent(session_id, &query.statement);
+
+        if let Some(sql) = explain::strip_explain_prefix(&query.statement) {
+            let sql = sql.to_owned();
+            return self.explain(&sql).map(Some);
+        }
 
         // Simple Query may contain many statements
         let parsed_statements = SqlParser::parse_statements(&query.statement)?;
+        self.context.check_statement_rate(parsed_statements.len())?;
+        self.route = self.context.route_statements(&parsed_statements);
         let mut transformed_statements = vec![];
 
         debug!(target: MAPPER,
@@ -444,8 +558,17 @@ where
         let mut encrypted = false;
         let mut parse_duration_recorded = false;
 
+        let float_digits_may_change = parsed_statements
+            .iter()
+            .any(context::may_change_extra_float_digits);
+
         for statement in &parsed_statements {
-            if let Some(mapping_disabled) = self.context.maybe_set_unsafe_disable_mapping(statement)
+            self.context.maybe_set_traceparent(statement);
+            self.context.maybe_set_decrypt(statement);
+            self.context.maybe_set_decrypt_failure_policy(statement);
+
+            if let Some(mapping_disabled) =
+                self.context.maybe_set_unsafe_disable_mapping(statement)?
             {
                 warn!(
                     msg = "SET CIPHERSTASH.DISABLE_MAPPING = {mapping_disabled}",
@@ -464,25 +587,51 @@ where
 
             self.check_for_schema_change(statement);
 
+            if self.context.catalog_masquerade_enabled()
+                && catalog::is_catalog_query(&self.context.get_table_resolver(), statement)
+            {
+                debug!(target: MAPPER,
+                    client_id = self.context.client_id,
+                    msg = "Catalog Statement"
+                );
+                counter!(STATEMENTS_PASSTHROUGH_TOTAL).increment(1);
+                transformed_statements.push(statement.clone());
+                portal = Portal::catalog(Some(session_id));
+                continue;
+            }
+
             if !eql_mapper::requires_type_check(statement) {
+                self.check_strict_passthrough(statement)?;
                 counter!(STATEMENTS_PASSTHROUGH_TOTAL).increment(1);
                 continue;
             }
 
             let typed_statement = match self.type_check(statement) {
                 Ok(ts) => ts,
-                Err(err) => {
-                    // `must_fail_closed` errors are refusals: passing the
-                    // original statement to the database is the harm they exist
-                    // to prevent, so they ignore the passthrough fallback. This
-                    // holds today, independently of CIP-3680 removing that
-                    // fallback altogether.
-                    if self.context.mapping_errors_enabled() || err.must_fail_closed() {
-                        return Err(err);
-                    } else {
-                        return Ok(None);
-                    };
-                }
+                Err(err) => match self.to_plaintext_source(statement, &err) {
+                    Some((source, columns)) => {
+                        if parsed_statements.len() > 1 {
+                            return Err(MappingError::PlaintextSourceNotAlone.into());
+                        }
+
+                        self.context
+                            .record_parse_duration(session_id, parse_timer.elapsed());
+                        self.context.update_statement_metadata(session_id, |m| {
+                            m.statement_type = Some(StatementType::from_statement(statement));
+                            m.set_query_fingerprint(&query.statement);
+                        });
+                        counter!(STATEMENTS_ENCRYPTED_TOTAL).increment(1);
+
+                        if float_digits_may_change {
+                            self.context.extra_float_digits_may_change();
+                        }
+
+                        return self
+                            .run_plaintext_source(session_id, &source, &columns)
+                            .await;
+                    }
+                    None => return self.handle_type_check_error(statement, err),
+                },
             };
 
             match self.to_encryptable_statement(&typed_statement, vec![])? {
@@ -527,7 +676,10 @@ where
                     counter!(STATEMENTS_ENCRYPTED_TOTAL).increment(1);
 
                     // Set Encrypted portal and mark as mapped
-                    portal = Portal::encrypted(Arc::new(statement), Some(session_id));
+                    // With CIPHERSTASH.DECRYPT = off the results pass through as stored
+                    if self.context.decrypt_enabled() {
+                        portal = Portal::encrypted(Arc::new(statement), Some(session_id));
+                    }
                     self.context.update_statement_metadata(session_id, |m| {
                         m.encrypted = true;
                     });
@@ -568,6 +720,13 @@ where
             m.set_query_fingerprint(&query.statement);
         });
 
+        // Read before the statements run, so a change they make is read before the next statement
+        self.refresh_extra_float_digits(portal.projection_columns())
+            .await?;
+        if float_digits_may_change {
+            self.context.extra_float_digits_may_change();
+        }
+
         self.context.add_portal(Name::unnamed(), portal);
         self.context.set_execute(Name::unnamed(), Some(session_id));
 
@@ -794,6 +953,8 @@ where
         let parse_timer = PhaseTimer::start();
 
         let mut message = Parse::try_from(bytes)?;
+        self.context
+            .set_statement_traceparent(session_id, &message.statement);
 
         debug!(
             target: PROTOCOL,
@@ -825,9 +986,36 @@ where
         self.context
             .set_statement_session(message.name.to_owned(), session_id);
 
+        if explain::strip_explain_prefix(&message.statement).is_some() {
+            return Err(MappingError::ExplainRequiresSimpleQuery.into());
+        }
+
         let statement = SqlParser::parse_statement(&message.statement)?;
 
-        if let Some(mapping_disabled) = self.context.maybe_set_unsafe_disable_mapping(&statement) {
+        // A batch that parses an unnamed statement may be sent to the replica, see `ReplicaBatch`
+        // A named statement is only prepared on the primary
+        match message.name.is_unnamed() {
+            true => {
+                self.route = self
+                    .context
+                    .route_statements(std::slice::from_ref(&statement))
+            }
+            false => self
+                .context
+                .observe_statements(std::slice::from_ref(&statement)),
+        }
+
+        self.context.maybe_set_traceparent(&statement);
+
+        // The statement may change extra_float_digits each time a Bind executes it
+        if context::may_change_extra_float_digits(&statement) {
+            self.context
+                .add_float_digits_statement(message.name.to_owned());
+        }
+        self.context.maybe_set_decrypt(&statement);
+        self.context.maybe_set_decrypt_failure_policy(&statement);
+
+        if let Some(mapping_disabled) = self.context.maybe_set_unsafe_disable_mapping(&statement)? {
             warn!(
                 msg = "SET CIPHERSTASH.DISABLE_MAPPING = {mapping_disabled}",
                 mapping_disabled
@@ -845,22 +1033,27 @@ where
 
         self.check_for_schema_change(&statement);
 
+        if self.context.catalog_masquerade_enabled()
+            && catalog::is_catalog_query(&self.context.get_table_resolver(), &statement)
+        {
+            debug!(target: MAPPER,
+                client_id = self.context.client_id,
+                msg = "Catalog Parse"
+            );
+            counter!(STATEMENTS_PASSTHROUGH_TOTAL).increment(1);
+            self.context.add_catalog_statement(message.name.to_owned());
+            return Ok(None);
+        }
+
         if !eql_mapper::requires_type_check(&statement) {
+            self.check_strict_passthrough(&statement)?;
             counter!(STATEMENTS_PASSTHROUGH_TOTAL).increment(1);
             return Ok(None);
         }
 
         let typed_statement = match self.type_check(&statement) {
             Ok(ts) => ts,
-            Err(err) => {
-                // See the matching comment on the simple-protocol path: a
-                // refusal is never downgraded to a passthrough.
-                if self.context.mapping_errors_enabled() || err.must_fail_closed() {
-                    return Err(err);
-                } else {
-                    return Ok(None);
-                };
-            }
+            Err(err) => return self.handle_type_check_error(&statement, err),
         };
 
         // Capture the parse message param_types
@@ -907,6 +1100,8 @@ where
                 counter!(STATEMENTS_ENCRYPTED_TOTAL).increment(1);
 
                 message.rewrite_param_types(&statement.output_params);
+                self.refresh_extra_float_digits(&statement.projection_columns)
+                    .await?;
                 self.context
                     .add_statement(message.name.to_owned(), statement);
             }
@@ -945,6 +1140,51 @@ where
         }
     }
 
+    ///
+    /// Answers `CIPHERSTASH EXPLAIN <sql>` with a query that returns how `sql`
+    /// would be mapped, in place of executing it. See [`explain`].
+    ///
+    /// Nothing about `sql` is recorded: DDL is not collected, and `SET
+    /// CIPHERSTASH` commands are not applied.
+    ///
+    fn explain(&mut self, sql: &str) -> Result<BytesMut, Error> {
+        let statements = SqlParser::parse_statements(sql)?;
+
+        let [statement] = &statements[..] else {
+            return Err(MappingError::InvalidSqlStatement(
+                "CIPHERSTASH EXPLAIN takes exactly one statement".to_string(),
+            )
+            .into());
+        };
+
+        let explanation = if eql_mapper::requires_type_check(statement) {
+            let typed_statement = self.type_check(statement)?;
+            let projection_columns = self.context.get_projection_columns(&typed_statement)?;
+
+            let transformed = if typed_statement.requires_transform() {
+                let transformed = typed_statement
+                    .transform(explain::placeholder_literals(&typed_statement))
+                    .map_err(|e| MappingError::StatementCouldNotBeTransformed(e.to_string()))?;
+                Some(transformed)
+            } else {
+                None
+            };
+
+            Explanation::new(&typed_statement, transformed.as_ref(), &projection_columns)
+        } else {
+            Explanation::passthrough(statement)
+        };
+
+        debug!(target: MAPPER,
+            client_id = self.context.client_id,
+            msg = "CIPHERSTASH EXPLAIN",
+            ?explanation,
+        );
+
+        let query = Query::new(explanation.to_sql());
+        BytesMut::try_from(query)
+    }
+
     ///
     /// Check the Statement AST for DDL
     /// Sets a schema changed flag in the Context
@@ -985,6 +1225,387 @@ where
         Ok(())
     }
 
+    ///
+    /// Returns the plaintext source of a statement that could not be type checked
+    /// because it copies native columns into encrypted columns,
+    /// with the Column configuration of each value read from the source.
+    ///
+    /// Returns `None` if the source reads any encrypted value,
+    /// and the type check error is handled as for any other statement.
+    ///
+    fn to_plaintext_source(
+        &self,
+        statement: &ast::Statement,
+        err: &Error,
+    ) -> Option<(PlaintextSource, Vec<Option<Column>>)> {
+        if err.must_fail_closed() || self.context.plaintext_source_max_rows() == 0 {
+            return None;
+        }
+
+        let source =
+            PlaintextSource::from_statement(statement, &self.context.get_table_resolver())?;
+
+        let target_statement = source.target_statement().ok()?;
+        let typed_target = self.type_check(&target_statement).ok()?;
+        let columns = self.context.get_param_columns(&typed_target).ok()?;
+
+        let source_statement = source.source_statement().ok()?;
+        let typed_source = self.type_check(&source_statement).ok()?;
+        let projection = typed_source.projection.columns();
+
+        let native_source = !typed_source.requires_transform()
+            && projection.len() == columns.len()
+            && projection.iter().all(|column| {
+                !matches!(
+                    &*column.ty,
+                    eql_mapper::Type::Value(eql_mapper::Value::Eql(_))
+                )
+            });
+
+        if !native_source {
+            return None;
+        }
+
+        debug!(target: MAPPER,
+            client_id = self.context.client_id,
+            msg = "Plaintext source",
+            ?columns,
+        );
+
+        Some((source, columns))
+    }
+
+    ///
+    /// Runs a statement with a plaintext source on the client's server connection,
+    /// and returns the message that replaces the client's Query
+    ///
+    /// The source rows are read, and the encrypted values written, with the extended protocol and without a Sync,
+    /// so both run in the client's transaction, or in one implicit transaction.
+    /// The responses are collected instead of being sent to the client, and the client receives the
+    /// CommandComplete of the write. The Sync that replaces the Query closes the batch,
+    /// and the server's ReadyForQuery answers the client's Query.
+    ///
+    /// Once the source has been read, a failure is sent through the server with [`Self::handle_statement_error`],
+    /// so the open transaction is aborted and nothing is written.
+    ///
+    async fn run_plaintext_source(
+        &mut self,
+        session_id: SessionId,
+        source: &PlaintextSource,
+        columns: &[Option<Column>],
+    ) -> Result<Option<BytesMut>, Error> {
+        if !self.wait_until_server_idle().await {
+            return Err(MappingError::PlaintextSourceNotAlone.into());
+        }
+
+        let max_rows = self.context.plaintext_source_max_rows();
+        let read = [
+            plaintext_source::save_session_parameters(),
+            plaintext_source::set_session_parameters(),
+            source.read_query(max_rows.saturating_add(1)),
+        ];
+
+        let [saved, _, rows] = match self.run_statements(read).await? {
+            Ok(responses) => responses,
+            Err(error_response) => return self.plaintext_source_failed(error_response).map(Some),
+        };
+
+        let write = self
+            .write_plaintext_source(session_id, source, columns, saved, rows)
+            .await;
+
+        match write {
+            Ok(bytes) => Ok(Some(bytes)),
+            Err(err) => match self.handle_statement_error(err)? {
+                Some(exception) => Ok(Some(exception)),
+                // FATAL error written directly to the client, the Sync closes the batch
+                None => Ok(Some(SyncMessage::message())),
+            },
+        }
+    }
+
+    ///
+    /// Encrypts and writes the rows read from a plaintext source, and restores the session parameters
+    ///
+    /// Sends the CommandComplete of the write to the client, and returns the Sync that closes the batch.
+    ///
+    async fn write_plaintext_source(
+        &mut self,
+        session_id: SessionId,
+        source: &PlaintextSource,
+        columns: &[Option<Column>],
+        saved: DivertedResponse,
+        read: DivertedResponse,
+    ) -> Result<BytesMut, Error> {
+        let max_rows = self.context.plaintext_source_max_rows();
+        let rows = read.rows;
+
+        if rows.len() > max_rows {
+            warn!(
+                client_id = self.context.client_id,
+                msg = "Plaintext source exceeds mapping.plaintext_source_max_rows",
+                max_rows,
+            );
+            return Err(MappingError::PlaintextSourceTooLarge { max: max_rows }.into());
+        }
+
+        let read_count = rows.len();
+        let statement = self
+            .encrypt_plaintext_source(session_id, source, columns, rows)
+            .await?;
+
+        debug!(target: MAPPER,
+            client_id = self.context.client_id,
+            transformed_statement = ?statement,
+        );
+
+        let saved = saved.rows.into_iter().next().unwrap_or_default();
+        let write = [
+            statement.to_string(),
+            plaintext_source::restore_session_parameters(&saved),
+        ];
+
+        let [written, _] = match self.run_statements(write).await? {
+            Ok(responses) => responses,
+            Err(error_response) => return self.plaintext_source_failed(error_response),
+        };
+
+        let written_count = written.row_count().unwrap_or_default();
+        if written_count != read_count {
+            warn!(
+                client_id = self.context.client_id,
+                msg = "Plaintext source rows were not all written",
+                read = read_count,
+                written = written_count,
+            );
+            return Err(MappingError::PlaintextSourceRowsChanged {
+                read: read_count,
+                written: written_count,
+            }
+            .into());
+        }
+
+        self.client_sender.send(written.command_complete)?;
+        self.context.finish_session();
+
+        Ok(SyncMessage::message())
+    }
+
+    ///
+    /// Sends the ErrorResponse of a statement Proxy ran for a plaintext source to the client,
+    /// and returns the Sync that closes the batch
+    ///
+    fn plaintext_source_failed(&mut self, error_response: BytesMut) -> Result<BytesMut, Error> {
+        debug!(target: PROTOCOL,
+            client_id = self.context.client_id,
+            msg = "Plaintext source failed",
+            ?error_response,
+        );
+
+        self.client_sender.send(error_response)?;
+        self.context.statement_failed();
+        self.context.finish_session();
+
+        Ok(SyncMessage::message())
+    }
+
+    ///
+    /// Runs statements with the extended protocol and returns their responses, instead of sending them to the client
+    ///
+    /// The statements are followed by a Flush and not a Sync, and the caller must close the batch.
+    /// The server must be idle, see [`Context::wait_until_server_idle`].
+    ///
+    async fn run_statements<const N: usize>(
+        &mut self,
+        statements: [String; N],
+    ) -> Result<Result<[DivertedResponse; N], BytesMut>, Error> {
+        let mut bytes = BytesMut::new();
+        for statement in statements {
+            bytes.extend(BytesMut::try_from(Parse::unnamed(statement))?);
+            bytes.extend(BytesMut::try_from(Bind::unnamed())?);
+            bytes.extend(BytesMut::try_from(Execute::unnamed())?);
+        }
+        bytes.extend(Flush::message());
+
+        let receiver = self.context.divert_responses(N);
+        self.write_to_server(bytes).await?;
+
+        match receiver.await.map_err(|_| Error::ConnectionClosed)? {
+            // A response is collected for every statement
+            Ok(responses) => Ok(Ok(responses.try_into().map_err(|_| Error::Unknown)?)),
+            Err(error_response) => Ok(Err(error_response)),
+        }
+    }
+
+    ///
+    /// Reads `extra_float_digits` from the server before a statement that returns encrypted floats,
+    /// if it may have changed since it was last read
+    ///
+    /// Postgres does not report `extra_float_digits` with a `ParameterStatus` message, and it is changed by `SET`,
+    /// `RESET`, `set_config`, `ROLLBACK TO SAVEPOINT` and role and database defaults, so it is not derived from
+    /// the statements. The read uses a named statement and portal, so the client's unnamed statement and portal
+    /// are kept, and ends with its own Sync, so a failure does not affect the client's next message.
+    ///
+    /// The read waits until the server is idle. While the client has an extended protocol batch open the
+    /// last value is used, as it is when a statement earlier in the same simple query changes the value.
+    ///
+    async fn refresh_extra_float_digits(
+        &mut self,
+        columns: &[Option<Column>],
+    ) -> Result<(), Error> {
+        let returns_float = columns
+            .iter()
+            .flatten()
+            .any(|column| column.cast_type() == ColumnType::Float);
+
+        if !returns_float || !self.context.session_parameters().extra_float_digits_stale {
+            return Ok(());
+        }
+
+        if !self.wait_until_server_idle().await {
+            debug!(target: PROTOCOL,
+                client_id = self.context.client_id,
+                msg = "extra_float_digits not read in an open batch",
+            );
+            return Ok(());
+        }
+
+        let name = Name::from(READ_SETTING_NAME);
+
+        let mut parse = Parse::unnamed(READ_EXTRA_FLOAT_DIGITS.to_string());
+        parse.name = name.clone();
+        let mut bind = Bind::unnamed();
+        bind.portal = name.clone();
+        bind.prepared_statement = name.clone();
+        let mut execute = Execute::unnamed();
+        execute.portal = name.clone();
+
+        let mut bytes = BytesMut::new();
+        bytes.extend(BytesMut::try_from(parse)?);
+        bytes.extend(BytesMut::try_from(bind)?);
+        bytes.extend(BytesMut::try_from(execute)?);
+        bytes.extend(BytesMut::try_from(Close {
+            target: Target::Portal,
+            name: name.clone(),
+        })?);
+        bytes.extend(BytesMut::try_from(Close {
+            target: Target::Statement,
+            name,
+        })?);
+        bytes.extend(SyncMessage::message());
+
+        // Read from the connection the client's statement is routed to
+        let route = self.route;
+        let receiver = self.context.divert_synced_responses(1);
+        self.write_to_server(bytes).await?;
+        self.route = route;
+
+        match receiver.await.map_err(|_| Error::ConnectionClosed)? {
+            Ok(responses) => {
+                let value = responses
+                    .first()
+                    .and_then(|response| response.rows.first())
+                    .and_then(|row| row.first())
+                    .and_then(Option::as_deref);
+                if let Some(value) = value {
+                    self.context.read_extra_float_digits(value);
+                }
+            }
+            // In a failed transaction block the last value is used, and the read is tried again
+            Err(error_response) => {
+                debug!(target: PROTOCOL,
+                    client_id = self.context.client_id,
+                    msg = "extra_float_digits could not be read",
+                    ?error_response,
+                );
+            }
+        }
+
+        Ok(())
+    }
+
+    ///
+    /// Encrypts the rows read from a plaintext source,
+    /// and returns the statement that writes them
+    ///
+    async fn encrypt_plaintext_source(
+        &mut self,
+        session_id: SessionId,
+        source: &PlaintextSource,
+        columns: &[Option<Column>],
+        rows: Vec<Vec<Option<String>>>,
+    ) -> Result<ast::Statement, Error> {
+        // An UPDATE reads the ctid of each row ahead of its values
+        let offset = match source {
+            PlaintextSource::Insert { .. } => 0,
+            PlaintextSource::Update { .. } => 1,
+        };
+
+        let mut plaintexts = vec![];
+        let mut plaintext_columns = vec![];
+
+        for row in &rows {
+            for (value, column) in row[offset..].iter().zip(columns) {
+                let plaintext = match (value, column) {
+                    (Some(value), Some(column)) => {
+                        let literal = Value::SingleQuotedString(value.to_owned());
+                        literal_from_sql(&literal, column.eql_term(), column.cast_type())
+                            .map_err(|_| MappingError::InvalidParameter(Box::new(column.clone())))?
+                    }
+                    _ => None,
+                };
+                plaintexts.push(plaintext);
+                plaintext_columns.push(column.clone());
+            }
+        }
+
+        let start = Instant::now();
+
+        let encrypted = self
+            .context
+            .encrypt(plaintexts, &plaintext_columns)
+            .await
+            .inspect_err(|_| {
+                counter!(ENCRYPTION_ERROR_TOTAL).increment(1);
+            })?;
+
+        let duration = Instant::now().duration_since(start);
+        self.context.add_encrypt_duration(session_id, duration);
+
+        let encrypted_count = encrypted.iter().filter(|e| e.is_some()).count();
+        self.context.update_statement_metadata(session_id, |m| {
+            m.encrypted = true;
+            m.set_encrypted_values_count(encrypted_count);
+        });
+
+        if self.context.prometheus_enabled() {
+            counter!(ENCRYPTION_REQUESTS_TOTAL).increment(1);
+            counter!(ENCRYPTED_VALUES_TOTAL).increment(encrypted_count as u64);
+            histogram!(ENCRYPTION_DURATION_SECONDS).record(duration);
+        }
+
+        let mut encrypted = encrypted.into_iter();
+        let mut values = vec![];
+
+        for row in rows {
+            let mut row_values = vec![];
+            for (idx, value) in row.into_iter().enumerate() {
+                let value = if idx < offset {
+                    value.map(Value::SingleQuotedString)
+                } else {
+                    match encrypted.next().flatten() {
+                        Some(encrypted) => Some(to_json_literal_value(&encrypted)?),
+                        None => value.map(Value::SingleQuotedString),
+                    }
+                };
+                row_values.push(value.unwrap_or(Value::Null));
+            }
+            values.push(row_values);
+        }
+
+        source.write_statement(&values)
+    }
+
     ///
     /// Creates a Statement from an EQL Mapper Typed Statement
     /// Returned Statement contains the Column configuration for any encrypted columns in params, literals and projection.
@@ -1084,16 +1705,32 @@ where
     /// - `Ok(Some(bytes))` - Modified Bind message with encrypted parameter values
     /// - `Ok(None)` - No parameter encryption needed, forward original message
     /// - `Err(error)` - Processing failed, error should be sent to client
-    async fn bind_handler(&mut self, bytes: &BytesMut) -> Result<Option<BytesMut>, Error> {
+    ///
+    /// `encrypted` holds the params when they were encrypted in a batch with an earlier Bind
+    ///
+    async fn bind_handler(
+        &mut self,
+        bytes: &BytesMut,
+        encrypted: Option<Vec<Option<EqlOutput>>>,
+    ) -> Result<Option<BytesMut>, Error> {
+        // Each Bind executes a statement
+        self.context.check_statement_rate(1)?;
+
+        let mut bind = Bind::try_from(bytes)?;
+        let float_digits_may_change = self
+            .context
+            .is_float_digits_statement(&bind.prepared_statement);
+
         if self.context.unsafe_disable_mapping() {
             warn!(msg = "Encrypted statement mapping is not enabled");
             counter!(STATEMENTS_PASSTHROUGH_MAPPING_DISABLED_TOTAL).increment(1);
             counter!(STATEMENTS_PASSTHROUGH_TOTAL).increment(1);
+            if float_digits_may_change {
+                self.context.extra_float_digits_may_change();
+            }
             return Ok(None);
         }
 
-        let mut bind = Bind::try_from(bytes)?;
-
         let session_id = self
             .context
             .get_statement_session_or_latest(&bind.prepared_statement);
@@ -1107,14 +1744,28 @@ where
 
         let mut portal = Portal::passthrough(session_id);
 
+        if self.context.is_catalog_statement(&bind.prepared_statement) {
+            portal = Portal::catalog(session_id);
+        }
+
         if let Some(statement) = self.context.get_statement(&bind.prepared_statement) {
             debug!(target:MAPPER, client_id = self.context.client_id, ?statement);
 
             if statement.has_params() {
-                let encrypted = self.encrypt_params(session_id, &bind, &statement).await?;
+                let encrypted = match encrypted {
+                    Some(encrypted) => {
+                        let encrypted_count = encrypted.iter().filter(|e| e.is_some()).count();
+                        self.context.with_session(session_id, |m| {
+                            m.metadata.encrypted = true;
+                            m.metadata.set_encrypted_values_count(encrypted_count);
+                        });
+                        encrypted
+                    }
+                    None => self.encrypt_params(session_id, &bind, &statement).await?,
+                };
                 bind.rewrite(&statement.output_params, encrypted)?;
             }
-            if statement.has_projection() {
+            if statement.has_projection() && self.context.decrypt_enabled() {
                 portal = Portal::encrypted_with_format_codes(
                     statement,
                     bind.result_columns_format_codes.to_owned(),
@@ -1126,6 +1777,14 @@ where
         };
 
         debug!(target: MAPPER, client_id = self.context.client_id, portal = ?portal);
+
+        // Read before the statement runs, so a change it makes is read before the next statement
+        self.refresh_extra_float_digits(portal.projection_columns())
+            .await?;
+        if float_digits_may_change {
+            self.context.extra_float_digits_may_change();
+        }
+
         self.context.add_portal(bind.portal.to_owned(), portal);
 
         if bind.requires_rewrite() {
@@ -1150,6 +1809,11 @@ where
     ///
     /// Params are converted to plaintext using the column configuration and any `postgres_param_types` specified on Parse.
     ///
+    /// The params of the Binds pipelined after this Bind are encrypted in the same request,
+    /// and kept with the pending messages until each Bind is processed.
+    /// If the batch fails, only this Bind is encrypted, and the others are encrypted on their own,
+    /// so an error is always returned for the Bind that caused it.
+    ///
     async fn encrypt_params(
         &mut self,
         session_id: Option<SessionId>,
@@ -1161,23 +1825,34 @@ where
 
         // Encryption is positional over the OUTPUT params — the values actually
         // sent — not over what the client bound.
-        let output_param_columns = statement
-            .output_params
-            .iter()
-            .map(|output| output.column.to_owned())
-            .collect::<Vec<_>>();
+        let output_param_columns = output_param_columns(statement);
 
         debug!(target: MAPPER, client_id = self.context.client_id, plaintexts = ?plaintexts);
 
+        self.read_ahead().await?;
+
+        let context = &self.context;
+        let pipelined = self
+            .pending
+            .run_binds()
+            .map(|message| pipelined_bind_plaintexts(context, message))
+            .collect::<Vec<_>>();
+
         let start = Instant::now();
 
-        let mut encrypted = self
-            .context
-            .encrypt(plaintexts, &output_param_columns)
-            .await
-            .inspect_err(|_| {
-                counter!(ENCRYPTION_ERROR_TOTAL).increment(1);
-            })?;
+        let (mut encrypted, encrypted_pipelined) = if pipelined.iter().any(Option::is_some) {
+            self.encrypt_pipelined(plaintexts, output_param_columns, &pipelined)
+                .await?
+        } else {
+            let encrypted = self
+                .context
+                .encrypt(plaintexts, &output_param_columns)
+                .await
+                .inspect_err(|_| {
+                    counter!(ENCRYPTION_ERROR_TOTAL).increment(1);
+                })?;
+            (encrypted, vec![])
+        };
 
         for (output, encrypted) in statement.output_params.iter().zip(encrypted.iter_mut()) {
             project_query_operand(output.query_operand, encrypted);
@@ -1197,19 +1872,115 @@ where
 
         // Prometheus metrics remain gated
         if self.context.prometheus_enabled() {
+            let pipelined_count = encrypted_pipelined
+                .iter()
+                .flatten()
+                .flatten()
+                .filter(|e| e.is_some())
+                .count();
+
             counter!(ENCRYPTION_REQUESTS_TOTAL).increment(1);
-            counter!(ENCRYPTED_VALUES_TOTAL).increment(encrypted_count as u64);
+            counter!(ENCRYPTED_VALUES_TOTAL).increment((encrypted_count + pipelined_count) as u64);
             histogram!(ENCRYPTION_DURATION_SECONDS).record(duration);
         }
 
+        for (message, encrypted) in self.pending.run_binds().zip(encrypted_pipelined) {
+            if encrypted.is_some() {
+                message.encrypted = encrypted;
+            }
+        }
+
         Ok(encrypted)
     }
 
+    ///
+    /// Encrypts the params of a Bind together with the params of the Binds pipelined after it
+    ///
+    /// Returns the params of the Bind, and the params of each pipelined Bind that was included.
+    /// If the batch fails, the Bind is encrypted on its own.
+    ///
+    #[allow(clippy::type_complexity)]
+    async fn encrypt_pipelined(
+        &self,
+        plaintexts: Vec<Option<Plaintext>>,
+        columns: Vec<Option<Column>>,
+        pipelined: &[Option<PipelinedBind>],
+    ) -> Result<(Vec<Option<EqlOutput>>, Vec<Option<Vec<Option<EqlOutput>>>>), Error> {
+        let mut batch_plaintexts = plaintexts.clone();
+        let mut batch_columns = columns.clone();
+        for bind in pipelined.iter().flatten() {
+            batch_plaintexts.extend(bind.plaintexts.iter().cloned());
+            batch_columns.extend(output_param_columns(&bind.statement));
+        }
+
+        debug!(target: MAPPER,
+            client_id = self.context.client_id,
+            msg = "Encrypt pipelined Binds",
+            binds = pipelined.iter().flatten().count() + 1,
+            values = batch_plaintexts.len(),
+        );
+
+        match self.context.encrypt(batch_plaintexts, &batch_columns).await {
+            Ok(batch) => {
+                let mut batch = batch.into_iter();
+                let encrypted = batch.by_ref().take(plaintexts.len()).collect();
+
+                let encrypted_pipelined = pipelined
+                    .iter()
+                    .map(|bind| {
+                        bind.as_ref().map(|bind| {
+                            let mut encrypted = batch
+                                .by_ref()
+                                .take(bind.plaintexts.len())
+                                .collect::<Vec<_>>();
+                            for (output, encrypted) in bind
+                                .statement
+                                .output_params
+                                .iter()
+                                .zip(encrypted.iter_mut())
+                            {
+                                project_query_operand(output.query_operand, encrypted);
+                            }
+                            encrypted
+                        })
+                    })
+                    .collect();
+
+                Ok((encrypted, encrypted_pipelined))
+            }
+            Err(err) => {
+                warn!(target: MAPPER,
+                    client_id = self.context.client_id,
+                    msg = "Pipelined Binds could not be encrypted together, encrypting each Bind",
+                    error = err.to_string(),
+                );
+
+                let encrypted = self
+                    .context
+                    .encrypt(plaintexts, &columns)
+                    .await
+                    .inspect_err(|_| {
+                        counter!(ENCRYPTION_ERROR_TOTAL).increment(1);
+                    })?;
+
+                Ok((encrypted, vec![]))
+            }
+        }
+    }
+
     fn type_check<'a>(
         &self,
         statement: &'a ast::Statement,
     ) -> Result<TypeCheckedStatement<'a>, Error> {
-        match eql_mapper::type_check(self.context.get_table_resolver(), statement) {
+        let options = TypeCheckOptions {
+            proxy_aggregates: self.context.proxy_aggregates_enabled(),
+        };
+
+        match eql_mapper::type_check_with_options(
+            self.context.get_table_resolver(),
+            statement,
+            options,
+        ) {
             Ok(typed_statement) => {
                 debug!(target: MAPPER,
                     client_id = self.context.client_id,
@@ -1261,6 +2032,98 @@ where
         }
     }
 
+    ///
+    /// Decides whether a statement that could not be type checked is refused or passed through unchanged
+    ///
+    /// - `Ok(None)` - Forward the original statement
+    /// - `Err(error)` - The statement is refused, and the error is sent to the client
+    ///
+    /// In strict mode, a statement that references a table with encrypted columns is refused,
+    /// unless its fingerprint is in `mapping.strict_allowlist`.
+    ///
+    fn handle_type_check_error(
+        &self,
+        statement: &ast::Statement,
+        err: Error,
+    ) -> Result<Option<BytesMut>, Error> {
+        // `must_fail_closed` errors are refusals: passing the original
+        // statement to the database is the harm they exist to prevent, so they
+        // ignore the passthrough fallback and the strict allowlist. This holds
+        // today, independently of CIP-3680 removing that fallback altogether.
+        if err.must_fail_closed() {
+            return Err(err);
+        }
+
+        if self.context.strict_mapping_enabled() {
+            let fingerprint = statement_fingerprint(statement);
+
+            if self.context.strict_allowlist_contains(&fingerprint) {
+                debug!(target: MAPPER,
+                    client_id = self.context.client_id,
+                    msg = "Statement in strict allowlist passed through",
+                    fingerprint,
+                );
+                counter!(STATEMENTS_PASSTHROUGH_TOTAL).increment(1);
+                return Ok(None);
+            }
+
+            if eql_mapper::references_encrypted_table(&self.context.get_table_resolver(), statement)
+            {
+                warn!(
+                    client_id = self.context.client_id,
+                    msg = "Statement refused in strict mode",
+                    fingerprint,
+                    error = err.to_string(),
+                );
+                counter!(STATEMENTS_STRICT_REFUSED_TOTAL).increment(1);
+                return Err(MappingError::StrictModeRefused { fingerprint }.into());
+            }
+        }
+
+        if self.context.mapping_errors_enabled() {
+            Err(err)
+        } else {
+            Ok(None)
+        }
+    }
+
+    ///
+    /// Refuses a statement that is passed through without a type check in strict mode,
+    /// if it references a table with encrypted columns
+    ///
+    /// Statements such as `COPY` are not mapped, so values they write to encrypted columns would not be encrypted.
+    /// Kinds that cannot read or write column values, such as `CREATE INDEX` and `DROP`, are allowed,
+    /// see [`strict_passthrough_allowed`], as are statements in `mapping.strict_allowlist`.
+    ///
+    fn check_strict_passthrough(&self, statement: &ast::Statement) -> Result<(), Error> {
+        if !self.context.strict_mapping_enabled() || strict_passthrough_allowed(statement) {
+            return Ok(());
+        }
+
+        if !eql_mapper::references_encrypted_table(&self.context.get_table_resolver(), statement) {
+            return Ok(());
+        }
+
+        let fingerprint = statement_fingerprint(statement);
+
+        if self.context.strict_allowlist_contains(&fingerprint) {
+            debug!(target: MAPPER,
+                client_id = self.context.client_id,
+                msg = "Statement in strict allowlist passed through",
+                fingerprint,
+            );
+            return Ok(());
+        }
+
+        warn!(
+            client_id = self.context.client_id,
+            msg = "Statement refused in strict mode",
+            fingerprint,
+        );
+        counter!(STATEMENTS_STRICT_REFUSED_TOTAL).increment(1);
+        Err(MappingError::StrictModeRefused { fingerprint }.into())
+    }
+
     ///
     /// Send a ReadyForQuery to the client, answering a Sync (or simple Query)
     /// for a batch of which nothing reached the server.
@@ -1309,6 +2172,9 @@ where
     fn handle_statement_error(&mut self, err: Error) -> Result<Option<BytesMut>, Error> {
         error!(client_id = self.context.client_id, msg = err.to_string(), error = ?err);
 
+        // The exception statement is sent to the primary
+        self.route = Route::Primary;
+
         let error_response = self.error_to_response(err);
 
         if error_response.is_fatal() {
@@ -1364,6 +2230,30 @@ where
     }
 }
 
+///
+/// Returns true for the kinds of statement that are passed through in strict mode
+/// even when they reference a table with encrypted columns
+///
+/// None of them read or write column values. Every other kind that is not type checked,
+/// such as `COPY`, `CALL` or `EXECUTE`, is refused.
+///
+fn strict_passthrough_allowed(statement: &ast::Statement) -> bool {
+    matches!(
+        statement,
+        ast::Statement::CreateTable(_)
+            | ast::Statement::CreateView { .. }
+            | ast::Statement::CreateIndex(_)
+            | ast::Statement::AlterTable { .. }
+            | ast::Statement::AlterIndex { .. }
+            | ast::Statement::Drop { .. }
+            | ast::Statement::Truncate { .. }
+            | ast::Statement::Comment { .. }
+            | ast::Statement::Grant { .. }
+            | ast::Statement::Revoke { .. }
+            | ast::Statement::Analyze { .. }
+    )
+}
+
 /// Projects a stored payload into its query operand when the value is bound in
 /// a predicate rather than stored.
 ///
@@ -1388,6 +2278,52 @@ fn project_query_operand(query_operand: bool, encrypted: &mut Option<EqlOutput>)
     }
 }
 
+/// The column of each output param, in the order params are sent to the server.
+fn output_param_columns(statement: &Statement) -> Vec<Option<Column>> {
+    statement
+        .output_params
+        .iter()
+        .map(|output| output.column.to_owned())
+        .collect()
+}
+
+/// The params of a Bind pipelined after the Bind being processed, ready to encrypt.
+struct PipelinedBind {
+    statement: Arc<Statement>,
+    plaintexts: Vec<Option<Plaintext>>,
+}
+
+///
+/// The params of a pipelined Bind to encrypt with the Bind being processed
+///
+/// Returns `None` if the Bind has nothing to encrypt, or if its params cannot be converted.
+/// The Bind is then handled on its own when it is processed, and returns any error itself.
+///
+fn pipelined_bind_plaintexts<S: EncryptionService>(
+    context: &Context<S>,
+    message: &PendingMessage,
+) -> Option<PipelinedBind> {
+    if message.encrypted.is_some() {
+        return None;
+    }
+
+    let bind = Bind::try_from(&message.bytes).ok()?;
+    let statement = context.get_statement(&bind.prepared_statement)?;
+
+    if !statement.has_params() {
+        return None;
+    }
+
+    let plaintexts = bind
+        .to_plaintext(&statement.output_params, &statement.postgres_param_types)
+        .ok()?;
+
+    Some(PipelinedBind {
+        statement,
+        plaintexts,
+    })
+}
+
 fn literals_to_plaintext(
     typed_statement: &TypeCheckedStatement<'_>,
     literal_columns: &Vec<Option<Column>>,
@@ -1565,3 +2501,111 @@ where
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{TandemConfig, DEFAULT_CLIENT_WRITE_BUFFER_BYTES};
+    use crate::connect;
+    use crate::postgresql::context::KeysetIdentifier;
+    use crate::proxy::EncryptConfig;
+    use eql_mapper::Schema;
+    use std::sync::Mutex;
+    use tokio::io::{empty, sink, Empty, Sink};
+    use tokio::sync::mpsc;
+
+    /// Fails any request for more than one value, as a batch rejected by ZeroKMS would,
+    /// and records the number of values in each request.
+    #[derive(Clone, Default)]
+    struct SingleValueService {
+        requests: Arc<Mutex<Vec<usize>>>,
+    }
+
+    #[async_trait::async_trait]
+    impl EncryptionService for SingleValueService {
+        async fn encrypt(
+            &self,
+            _keyset_id: Option<KeysetIdentifier>,
+            plaintexts: Vec<Option<Plaintext>>,
+            _columns: &[Option<Column>],
+        ) -> Result<Vec<Option<EqlOutput>>, Error> {
+            self.requests.lock().unwrap().push(plaintexts.len());
+
+            if plaintexts.len() > 1 {
+                return Err(Error::Unknown);
+            }
+            Ok(plaintexts.iter().map(|_| None).collect())
+        }
+
+        async fn decrypt(
+            &self,
+            _keyset_id: Option<KeysetIdentifier>,
+            _ciphertexts: Vec<Option<crate::EqlCiphertext>>,
+        ) -> Result<Vec<Option<Plaintext>>, Error> {
+            Ok(vec![])
+        }
+    }
+
+    fn frontend(service: SingleValueService) -> Frontend<Empty, Sink, SingleValueService> {
+        let config = Arc::new(TandemConfig::for_testing());
+        let encrypt_config = Arc::new(EncryptConfig::default());
+        let schema = Arc::new(Schema::new("public"));
+        let (reload_sender, _reload_receiver) = mpsc::unbounded_channel();
+        let context = Context::new(1, config, encrypt_config, schema, service, reload_sender);
+
+        let (client_sender, _client_receiver) = connect::channel(DEFAULT_CLIENT_WRITE_BUFFER_BYTES);
+        Frontend::new(empty(), client_sender, sink(), context)
+    }
+
+    fn pipelined_bind(value: &str) -> Option<PipelinedBind> {
+        Some(PipelinedBind {
+            statement: Arc::new(Statement::new(vec![None], vec![], vec![], vec![], vec![])),
+            plaintexts: vec![Some(Plaintext::new(value.to_owned()))],
+        })
+    }
+
+    #[tokio::test]
+    async fn pipelined_binds_are_encrypted_on_their_own_if_the_batch_fails() {
+        let service = SingleValueService::default();
+        let frontend = frontend(service.clone());
+
+        let plaintexts = vec![Some(Plaintext::new("first".to_owned()))];
+        let pipelined = [pipelined_bind("second"), pipelined_bind("third")];
+
+        let (encrypted, encrypted_pipelined) = frontend
+            .encrypt_pipelined(plaintexts, vec![None], &pipelined)
+            .await
+            .unwrap();
+
+        // The batch of all three Binds fails, and the first Bind is retried on its own
+        assert_eq!(*service.requests.lock().unwrap(), vec![3, 1]);
+        assert_eq!(encrypted.len(), 1);
+
+        // The pipelined Binds are left to be encrypted when each is processed
+        assert!(encrypted_pipelined.is_empty());
+    }
+
+    #[test]
+    fn strict_mode_allows_statements_that_cannot_read_or_write_values() {
+        for sql in [
+            "CREATE INDEX ON encrypted (id)",
+            "DROP TABLE encrypted",
+            "TRUNCATE encrypted",
+            "GRANT SELECT ON encrypted TO reader",
+            "COMMENT ON TABLE encrypted IS 'customers'",
+        ] {
+            let statement = SqlParser::parse_statement(sql).unwrap();
+            assert!(strict_passthrough_allowed(&statement), "{sql}");
+        }
+
+        for sql in [
+            "COPY encrypted (id, encrypted_text) FROM STDIN",
+            "COPY encrypted TO STDOUT",
+            "EXECUTE insert_encrypted(1, 'plaintext')",
+            "CALL write_encrypted('plaintext')",
+        ] {
+            let statement = SqlParser::parse_statement(sql).unwrap();
+            assert!(!strict_passthrough_allowed(&statement), "{sql}");
+        }
+    }
+}
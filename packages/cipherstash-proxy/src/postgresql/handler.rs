@@ -1,6 +1,9 @@
 use super::backend::Backend;
 use super::frontend::Frontend;
+use super::messages::BackendCode;
 use super::protocol::StartupCode;
+use super::router::{self, Router};
+use crate::config::DatabaseConfig;
 use crate::connect::ChannelWriter;
 use crate::error::ConfigError;
 use crate::log::{AUTHENTICATION, PROTOCOL};
@@ -11,17 +14,18 @@ use crate::postgresql::messages::authentication::{
 };
 use crate::postgresql::messages::error_response::ErrorResponse;
 use crate::postgresql::{protocol, startup};
-use crate::proxy::ZeroKms;
+use crate::proxy::{DatabaseHosts, ReplicaLease, ReplicaPool, ZeroKms};
 use crate::{
-    connect::AsyncStream,
+    connect::{self, AsyncStream},
     error::{Error, ProtocolError},
     postgresql::context::Context,
-    tls,
+    tls::{self, ServerTlsManager},
 };
 use bytes::BytesMut;
 use md5::{Digest, Md5};
 use postgres_protocol::authentication::sasl::{ChannelBinding, ScramSha256};
 use rand::Rng;
+use std::{sync::Arc, time::Duration};
 use tokio::io::{AsyncRead, AsyncWrite, AsyncWriteExt};
 use tracing::{debug, error, info, warn};
 ///
@@ -52,20 +56,20 @@ use tracing::{debug, error, info, warn};
 ///         Propagate and continue
 ///
 ///
-pub async fn handler(client_stream: AsyncStream, context: Context<ZeroKms>) -> Result<(), Error> {
+pub async fn handler(
+    client_stream: AsyncStream,
+    mut context: Context<ZeroKms>,
+    server_tls: Option<ServerTlsManager>,
+    hosts: DatabaseHosts,
+    replicas: ReplicaPool,
+) -> Result<(), Error> {
     let mut client_stream = client_stream;
     let client_id = context.client_id;
 
-    // Connect to the database server, using TLS if configured
-    let stream = AsyncStream::connect(&context.database_socket_address()).await?;
-    let mut database_stream = startup::with_tls(stream, context.config()).await?;
-    info!(
-        msg = "Client connected",
-        database = context.database_socket_address(),
-        client_id = client_id,
-    );
-
-    loop {
+    // The database is not connected until the client has sent its startup message and been counted against the
+    // connection limit, so a client over the limit does not open a database connection
+    // The permit counts the connection against the connection limits until the handler returns
+    let (startup_message, mut connection_permit) = loop {
         let startup_message =
             match startup::read_message(&mut client_stream, context.connection_timeout()).await {
                 Ok(msg) => msg,
@@ -78,29 +82,78 @@ pub async fn handler(client_stream: AsyncStream, context: Context<ZeroKms>) -> R
 
         match &startup_message.code {
             StartupCode::SSLRequest => {
-                startup::send_ssl_response(&mut client_stream, context.use_tls()).await?;
-                if let Some(ref tls) = context.tls_config() {
+                // As with Postgres, TLS is not offered over a Unix domain socket
+                let server_tls = server_tls.as_ref().filter(|_| client_stream.is_tcp());
+                startup::send_ssl_response(&mut client_stream, server_tls.is_some()).await?;
+                if let Some(server_tls) = server_tls {
                     match client_stream {
                         AsyncStream::Tcp(stream) => {
                             // The Client is connecting to our Server
-                            let tls_stream = tls::server(stream, tls).await?;
+                            let tls_stream = tls::server(stream, server_tls).await?;
                             client_stream = AsyncStream::Tls(Box::new(tls_stream));
                         }
-                        AsyncStream::Tls(_) => {
+                        AsyncStream::Tls(_) | AsyncStream::Unix(_) => {
                             unreachable!();
                         }
                     }
                 }
             }
             StartupCode::CancelRequest => {
+                let (_, mut database_stream) = connect_database_host(&hosts, &context).await?;
                 database_stream.write_all(&startup_message.bytes).await?;
                 return Err(Error::CancelRequest);
             }
             StartupCode::ProtocolVersionNumber => {
-                database_stream.write_all(&startup_message.bytes).await?;
-                break;
+                // The total connection limit is checked before the startup is forwarded
+                // The per-role limit is checked once the role has authenticated, as Postgres does
+                let permit = match context.acquire_connection() {
+                    Ok(permit) => permit,
+                    Err(err) => {
+                        send_too_many_connections_error(&mut client_stream, &err).await;
+                        return Err(err);
+                    }
+                };
+
+                // extra_float_digits is not reported by the database, and the startup value is used until it is read
+                if let Some(digits) = startup_message.parameter("extra_float_digits") {
+                    context.set_session_parameter("extra_float_digits", &digits);
+                }
+
+                break (startup_message, permit);
             }
         }
+    };
+
+    let (database, mut database_stream) = connect_database_host(&hosts, &context).await?;
+    info!(
+        msg = "Client connected",
+        database = database.to_socket_address(),
+        client_id = client_id,
+    );
+
+    database_stream.write_all(&startup_message.bytes).await?;
+    let role = startup_message.parameter("user").unwrap_or_default();
+    let startup_bytes = startup_message.bytes;
+
+    // Client certificates are verified by the TLS handshake
+    // A client without a certificate is rejected here if one is required, or if the keyset is set by the certificate
+    let identity = client_stream.client_identity();
+    if let Some(identity) = &identity {
+        info!(
+            msg = "Client certificate verified",
+            client_id = client_id,
+            client_identity = identity.name(),
+            subject = identity.subject,
+            subject_alt_names = ?identity.subject_alt_names,
+        );
+    }
+    if let Err(err) = context.check_client_identity(identity) {
+        let message = ErrorResponse::client_certificate_required();
+        let bytes = BytesMut::try_from(message)?;
+        client_stream.write_all(&bytes).await?;
+
+        error!(msg = err.to_string(), client_id = client_id);
+        return Err(err);
     }
 
     // Proxy -> Client Authentication
@@ -160,59 +213,17 @@ pub async fn handler(client_stream: AsyncStream, context: Context<ZeroKms>) -> R
     //            -> Client -> Auth response
     //
 
-    // First message should always be Auth
-    let auth = protocol::read_auth_message(&mut database_stream, client_id).await?;
-
-    match &auth.method {
-        AuthenticationMethod::AuthenticationOk => {
-            debug!(target: AUTHENTICATION, msg = "AuthenticationOk");
-        }
-        AuthenticationMethod::AuthenticationCleartextPassword => {
-            debug!(target: AUTHENTICATION, msg = "AuthenticationCleartextPassword");
-            let password = context.database_password();
-            let message = PasswordMessage::new(password);
-            let bytes = BytesMut::try_from(message)?;
-            database_stream.write_all(&bytes).await?;
-        }
-        AuthenticationMethod::Md5Password { salt } => {
-            debug!(target: AUTHENTICATION, msg = "Md5Password");
-            let username = context.database_username().as_bytes();
-            let password = context.database_password();
-            let password = password.as_bytes();
+    authenticate_database(&mut database_stream, &client_stream, &context).await?;
 
-            let hash = md5_hash(username, password, salt);
-            let message = PasswordMessage::new(hash);
-            let bytes = BytesMut::try_from(message)?;
-            database_stream.write_all(&bytes).await?;
-        }
-        AuthenticationMethod::Sasl { .. } => {
-            debug!(target: AUTHENTICATION, msg = "Sasl");
-            let mechanism = auth.sasl_mechanism()?;
-            sanity_check_sasl_mechanism(&mechanism, &client_stream);
-
-            // Toby: I don't think we need to do anything here
-            // If we are connected via TLS, we can support SCRAM-SHA-256-PLUS
-            // If we are not connected via TLS, the database won't ask for SCRAM-SHA-256-PLUS
-            let channel_binding = database_stream.channel_binding();
-            let password = context.database_password();
-            let password = password.as_bytes();
-            scram_sha_256_plus_handler(&mut database_stream, mechanism, password, channel_binding)
-                .await?;
-        }
-        AuthenticationMethod::Other { method_code, .. } => {
-            debug!(target: AUTHENTICATION, msg = "UnsupportedAuthentication");
-            return Err(ProtocolError::UnsupportedAuthentication {
-                method_code: *method_code,
-            }
-            .into());
-        }
-        method => {
-            debug!(target: AUTHENTICATION, msg = "UnexpectedStartupMessage", authentication_method = ?method);
-            return Err(ProtocolError::UnexpectedStartupMessage.into());
-        }
+    // The role has authenticated with the database, so the connection is counted against it
+    // Before authentication, a client could use up the connections of a role it cannot log in as
+    if let Err(err) = connection_permit.acquire_role(&role) {
+        send_too_many_connections_error(&mut client_stream, &err).await;
+        return Err(err);
     }
 
-    if context.require_tls() && !client_stream.is_tls() {
+    // Access to a Unix domain socket is controlled by filesystem permissions
+    if context.require_tls() && client_stream.is_tcp() {
         let message = ErrorResponse::tls_required();
         let bytes = BytesMut::try_from(message)?;
         client_stream.write_all(&bytes).await?;
@@ -221,10 +232,28 @@ pub async fn handler(client_stream: AsyncStream, context: Context<ZeroKms>) -> R
         return Err(ConfigError::TlsRequired.into());
     }
 
+    // Read-only statements are routed to a replica connection, if a healthy replica is available
+    // The router holds the lease, which counts the connection to the replica until the client disconnects
+    let (replica_lease, replica_stream) =
+        match connect_replica(&replicas, &startup_bytes, &client_stream, &context).await {
+            Some((lease, stream)) => (Some(lease), Some(stream)),
+            None => (None, None),
+        };
+
+    let router = Arc::new(Router::new(
+        replica_lease,
+        context.config().database.replica_sticky_primary(),
+    ));
+    context.set_router(router.clone());
+
     let (client_reader, client_writer) = client_stream.split();
-    let (server_reader, server_writer) = database_stream.split();
+    let (server_reader, server_writer) = router::split(database_stream, replica_stream, router);
 
-    let channel_writer = ChannelWriter::new(client_writer, client_id);
+    let channel_writer = ChannelWriter::new(
+        client_writer,
+        client_id,
+        context.config().server.client_write_buffer_bytes,
+    );
 
     let mut frontend = Frontend::new(
         client_reader,
@@ -309,6 +338,175 @@ pub async fn handler(client_stream: AsyncStream, context: Context<ZeroKms>) -> R
 }
 
 // Keep for debugging
+///
+/// Connects to the selected database host, using TLS if configured
+///
+/// With multiple hosts, a host is selected again if the selected host cannot be connected.
+///
+async fn connect_database_host(
+    hosts: &DatabaseHosts,
+    context: &Context<ZeroKms>,
+) -> Result<(DatabaseConfig, AsyncStream), Error> {
+    let config = context.config();
+    let tls_disabled = config.database_tls_disabled();
+
+    let database = hosts.select().await?;
+    match connect_database(&database, tls_disabled).await {
+        Ok(stream) => Ok((database, stream)),
+        Err(err) if config.database.selects_host() => {
+            debug!(target: PROTOCOL, msg = "Selecting database host again", error = err.to_string());
+            hosts.mark_failed(&database).await;
+            let database = hosts.select().await?;
+            let stream = connect_database(&database, tls_disabled).await?;
+            Ok((database, stream))
+        }
+        Err(err) => Err(err),
+    }
+}
+
+///
+/// Connects to a database, using TLS if configured
+///
+async fn connect_database(
+    database: &DatabaseConfig,
+    tls_disabled: bool,
+) -> Result<AsyncStream, Error> {
+    let stream = match database.unix_socket_path() {
+        Some(path) => AsyncStream::connect_unix(&path).await?,
+        None => AsyncStream::connect(&database.to_socket_address()).await?,
+    };
+    startup::with_tls(stream, database, tls_disabled).await
+}
+
+///
+/// Authenticates the database connection with the configured credentials
+///
+async fn authenticate_database(
+    database_stream: &mut AsyncStream,
+    client_stream: &AsyncStream,
+    context: &Context<ZeroKms>,
+) -> Result<(), Error> {
+    // First message should always be Auth
+    let auth = protocol::read_auth_message(&mut *database_stream, context.client_id).await?;
+
+    match &auth.method {
+        AuthenticationMethod::AuthenticationOk => {
+            debug!(target: AUTHENTICATION, msg = "AuthenticationOk");
+        }
+        AuthenticationMethod::AuthenticationCleartextPassword => {
+            debug!(target: AUTHENTICATION, msg = "AuthenticationCleartextPassword");
+            let password = context.database_password();
+            let message = PasswordMessage::new(password);
+            let bytes = BytesMut::try_from(message)?;
+            database_stream.write_all(&bytes).await?;
+        }
+        AuthenticationMethod::Md5Password { salt } => {
+            debug!(target: AUTHENTICATION, msg = "Md5Password");
+            let username = context.database_username().as_bytes();
+            let password = context.database_password();
+            let password = password.as_bytes();
+
+            let hash = md5_hash(username, password, salt);
+            let message = PasswordMessage::new(hash);
+            let bytes = BytesMut::try_from(message)?;
+            database_stream.write_all(&bytes).await?;
+        }
+        AuthenticationMethod::Sasl { .. } => {
+            debug!(target: AUTHENTICATION, msg = "Sasl");
+            let mechanism = auth.sasl_mechanism()?;
+            sanity_check_sasl_mechanism(&mechanism, client_stream);
+
+            // Toby: I don't think we need to do anything here
+            // If we are connected via TLS, we can support SCRAM-SHA-256-PLUS
+            // If we are not connected via TLS, the database won't ask for SCRAM-SHA-256-PLUS
+            let channel_binding = database_stream.channel_binding();
+            let password = context.database_password();
+            let password = password.as_bytes();
+            scram_sha_256_plus_handler(&mut *database_stream, mechanism, password, channel_binding)
+                .await?;
+        }
+        AuthenticationMethod::Other { method_code, .. } => {
+            debug!(target: AUTHENTICATION, msg = "UnsupportedAuthentication");
+            return Err(ProtocolError::UnsupportedAuthentication {
+                method_code: *method_code,
+            }
+            .into());
+        }
+        method => {
+            debug!(target: AUTHENTICATION, msg = "UnexpectedStartupMessage", authentication_method = ?method);
+            return Err(ProtocolError::UnexpectedStartupMessage.into());
+        }
+    }
+
+    Ok(())
+}
+
+const REPLICA_CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+///
+/// Connects and authenticates to a healthy replica with the startup message of the client
+///
+/// The session parameters and cancellation key sent by the replica are discarded, as the client has those of the primary.
+/// If the replica cannot be connected, it is marked unhealthy and the client uses the primary only.
+///
+async fn connect_replica(
+    replicas: &ReplicaPool,
+    startup_bytes: &BytesMut,
+    client_stream: &AsyncStream,
+    context: &Context<ZeroKms>,
+) -> Option<(ReplicaLease, AsyncStream)> {
+    let lease = replicas.select()?;
+
+    let connect = async {
+        let config = context.config();
+        let mut stream = connect_database(lease.config(), config.database_tls_disabled()).await?;
+        stream.write_all(startup_bytes).await?;
+        authenticate_database(&mut stream, client_stream, context).await?;
+
+        loop {
+            let (code, _bytes) = protocol::read_message(
+                &mut stream,
+                context.client_id,
+                context.connection_timeout(),
+            )
+            .await?;
+            match code.into() {
+                BackendCode::ReadyForQuery => return Ok::<_, Error>(stream),
+                BackendCode::ErrorResponse => {
+                    return Err(ProtocolError::UnexpectedStartupMessage.into())
+                }
+                _ => {}
+            }
+        }
+    };
+
+    let result = match tokio::time::timeout(REPLICA_CONNECT_TIMEOUT, connect).await {
+        Ok(result) => result,
+        Err(_) => Err(Error::DatabaseConnection),
+    };
+
+    match result {
+        Ok(stream) => {
+            info!(
+                msg = "Replica connected",
+                client_id = context.client_id,
+                replica = lease.address(),
+            );
+            Some((lease, stream))
+        }
+        Err(err) => {
+            warn!(
+                msg = "Could not connect to replica, statements are sent to the primary",
+                client_id = context.client_id,
+                replica = lease.address(),
+                error = err.to_string()
+            );
+            lease.mark_unhealthy();
+            None
+        }
+    }
+}
+
 fn sanity_check_sasl_mechanism(mechanism: &SaslMechanism, client_stream: &AsyncStream) {
     match mechanism {
         SaslMechanism::ScramSha256 => {
@@ -392,3 +590,10 @@ async fn send_timeout_error<S: AsyncWrite + Unpin>(stream: &mut S, err: &Error)
         let _ = stream.write_all(&bytes).await;
     }
 }
+
+async fn send_too_many_connections_error<S: AsyncWrite + Unpin>(stream: &mut S, err: &Error) {
+    let error_response = ErrorResponse::too_many_connections(err.to_string());
+    if let Ok(bytes) = BytesMut::try_from(error_response) {
+        let _ = stream.write_all(&bytes).await;
+    }
+}
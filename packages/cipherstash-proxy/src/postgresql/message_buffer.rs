@@ -1,30 +1,40 @@
 use super::messages::data_row::DataRow;
 
+///
+/// Buffers DataRows until there are enough rows or bytes to decrypt as a batch
+///
 pub struct MessageBuffer {
-    // buffer: RwLock<Vec<DataRow>>,
     buffer: Vec<DataRow>,
+    bytes: usize,
+    max_rows: usize,
+    max_bytes: usize,
 }
 
 impl MessageBuffer {
-    /// Default number of rows to keep in the buffer.
-    /// Larger rows will require more memory.
-    const DEFAULT_RESPONSE_BUFFER_SIZE: usize = 4096;
-
-    pub fn new() -> Self {
+    pub fn new(max_rows: usize, max_bytes: usize) -> Self {
         Self {
-            buffer: Vec::with_capacity(Self::DEFAULT_RESPONSE_BUFFER_SIZE),
+            buffer: vec![],
+            bytes: 0,
+            max_rows: max_rows.max(1),
+            max_bytes: max_bytes.max(1),
         }
     }
 
-    pub fn push(&mut self, row: DataRow) {
+    ///
+    /// Adds a row, and `len` as the size of the row in bytes
+    ///
+    pub fn push(&mut self, row: DataRow, len: usize) {
         self.buffer.push(row);
+        self.bytes += len;
     }
 
     pub fn drain(&mut self) -> Vec<DataRow> {
+        self.bytes = 0;
         std::mem::take(&mut self.buffer)
     }
 
     pub fn clear(&mut self) {
+        self.bytes = 0;
         self.buffer.clear();
     }
 
@@ -32,11 +42,41 @@ impl MessageBuffer {
         self.buffer.len()
     }
 
+    pub fn bytes(&self) -> usize {
+        self.bytes
+    }
+
     pub fn is_empty(&self) -> bool {
         self.buffer.is_empty()
     }
 
     pub fn at_capacity(&self) -> bool {
-        self.buffer.len() >= Self::DEFAULT_RESPONSE_BUFFER_SIZE
+        self.buffer.len() >= self.max_rows || self.bytes >= self.max_bytes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::MessageBuffer;
+    use crate::postgresql::messages::data_row::DataRow;
+
+    fn row() -> DataRow {
+        DataRow { columns: vec![] }
+    }
+
+    #[test]
+    fn at_capacity_by_rows_or_bytes() {
+        let mut buffer = MessageBuffer::new(2, 100);
+        buffer.push(row(), 10);
+        assert!(!buffer.at_capacity());
+        buffer.push(row(), 10);
+        assert!(buffer.at_capacity());
+
+        let rows = buffer.drain();
+        assert_eq!(rows.len(), 2);
+        assert_eq!(buffer.bytes(), 0);
+
+        buffer.push(row(), 100);
+        assert!(buffer.at_capacity());
     }
 }
@@ -1,4 +1,4 @@
-use super::{maybe_json, maybe_jsonb, Name, NULL};
+use super::{maybe_json, maybe_jsonb, FrontendCode, Name, NULL};
 use crate::error::{Error, MappingError, ProtocolError};
 use crate::log::MAPPER;
 use crate::postgresql::context::column::Column;
@@ -48,6 +48,22 @@ pub struct BindParam {
 }
 
 impl Bind {
+    /// A Bind of the unnamed statement to the unnamed portal, without params and with every result in the text format
+    pub fn unnamed() -> Bind {
+        Bind {
+            code: FrontendCode::Bind.into(),
+            portal: Name::unnamed(),
+            prepared_statement: Name::unnamed(),
+            num_param_format_codes: 0,
+            param_format_codes: vec![],
+            num_param_values: 0,
+            param_values: vec![],
+            num_result_column_format_codes: 0,
+            result_columns_format_codes: vec![],
+            reshaped: false,
+        }
+    }
+
     pub fn requires_rewrite(&self) -> bool {
         self.reshaped
             || self
@@ -656,7 +672,9 @@ mod tests {
                 mode: ColumnMode::PlaintextDuplicate,
             },
             postgres_type: postgres_types::Type::JSONB,
+            token_type: None,
             eql_term: EqlTermVariant::JsonValueSelector,
+            aggregate: None,
         }
     }
 
@@ -24,40 +24,98 @@ pub struct DataColumn {
 }
 
 impl DataRow {
+    ///
+    /// The encrypted value of each column, or `None` for a NULL or a column that is not encrypted
+    ///
+    /// A value that cannot be deserialised is returned as `ColumnCouldNotBeDeserialised`,
+    /// so that it is handled by the decryption failure policy.
+    ///
     pub fn as_ciphertext(
         &mut self,
         column_configuration: &Vec<Option<Column>>,
-    ) -> Vec<Option<EqlCiphertext>> {
+    ) -> Vec<Result<Option<EqlCiphertext>, EncryptError>> {
         let mut result = vec![];
         for (data_column, column_config) in self.columns.iter_mut().zip(column_configuration) {
-            let encrypted = column_config
+            // Proxy-side aggregates hold many values, see `as_aggregate_ciphertexts`
+            let Some(config) = column_config
                 .as_ref()
+                .filter(|config| config.aggregate.is_none())
                 .filter(|_| data_column.is_not_null())
-                .and_then(|config| {
-                    data_column
-                        .to_eql_ciphertext()
-                        .inspect_err(|err| match err {
-                            Error::Encrypt(EncryptError::ColumnIsNull) => {
-                                debug!(target: DECRYPT, msg ="ColumnIsNull", ?config);
-                                // Not an error, as you were
-                                data_column.set_null();
-                            }
-                            _ => {
-                                let err = EncryptError::ColumnCouldNotBeDeserialised {
-                                    table: config.identifier.table.to_owned(),
-                                    column: config.identifier.column.to_owned(),
-                                };
-                                error!(target: DECRYPT, msg = err.to_string());
-                            }
-                        })
-                        .ok()
-                });
+            else {
+                result.push(Ok(None));
+                continue;
+            };
+
+            let encrypted = match data_column.to_eql_ciphertext() {
+                Ok(ciphertext) => Ok(Some(ciphertext)),
+                Err(Error::Encrypt(EncryptError::ColumnIsNull)) => {
+                    debug!(target: DECRYPT, msg ="ColumnIsNull", ?config);
+                    // Not an error, as you were
+                    data_column.set_null();
+                    Ok(None)
+                }
+                Err(_) => {
+                    let err = EncryptError::ColumnCouldNotBeDeserialised {
+                        table: config.identifier.table.to_owned(),
+                        column: config.identifier.column.to_owned(),
+                    };
+                    error!(target: DECRYPT, msg = err.to_string());
+                    Err(err)
+                }
+            };
             result.push(encrypted);
         }
 
         result
     }
 
+    /// The encrypted values of each proxy-side aggregate column, by column index.
+    ///
+    /// The database returns the group's values as a `jsonb` array. A NULL
+    /// column — the aggregate of an empty group — has no values.
+    ///
+    /// `max_values` and `max_bytes` bound the row as a whole, so a row with
+    /// several aggregate columns shares them. The size of each column is
+    /// checked before its array is parsed. A JSON `null` in an array counts
+    /// toward both, as every other element does.
+    pub fn as_aggregate_ciphertexts(
+        &self,
+        column_configuration: &[Option<Column>],
+        max_values: usize,
+        max_bytes: usize,
+    ) -> Result<Vec<(usize, Vec<EqlCiphertext>)>, Error> {
+        let mut result = vec![];
+        let mut values = 0;
+        let mut bytes = 0;
+
+        for (idx, (data_column, column_config)) in
+            self.columns.iter().zip(column_configuration).enumerate()
+        {
+            let Some(config) = column_config.as_ref().filter(|c| c.aggregate.is_some()) else {
+                continue;
+            };
+
+            bytes += data_column.bytes.as_ref().map_or(0, |b| b.len());
+            if bytes > max_bytes {
+                let err = Error::from(EncryptError::ProxyAggregateTooManyBytes {
+                    table: config.table_name(),
+                    column: config.column_name(),
+                    max_bytes,
+                });
+                error!(target: DECRYPT, msg = err.to_string());
+                return Err(err);
+            }
+
+            let ciphertexts = data_column
+                .to_eql_ciphertexts(config, &mut values, max_values)
+                .inspect_err(|err| error!(target: DECRYPT, msg = err.to_string()))?;
+
+            result.push((idx, ciphertexts));
+        }
+
+        Ok(result)
+    }
+
     pub fn column_count(&self) -> usize {
         self.columns.len()
     }
@@ -86,6 +144,10 @@ impl DataColumn {
         self.bytes.is_some()
     }
 
+    pub fn as_bytes(&self) -> Option<&[u8]> {
+        self.bytes.as_deref()
+    }
+
     pub fn set_null(&mut self) {
         self.bytes = None;
     }
@@ -212,11 +274,7 @@ impl DataColumn {
             return Err(EncryptError::ColumnCouldNotBeParsed.into());
         };
 
-        let json = match bytes.first() {
-            Some(&JSONB_BINARY_VERSION) => &bytes[1..],
-            Some(_) => &bytes[..],
-            None => return Err(EncryptError::ColumnCouldNotBeParsed.into()),
-        };
+        let json = jsonb_text(bytes)?;
 
         let mut value: serde_json::Value =
             serde_json::from_slice(json).map_err(log_deserialise_error)?;
@@ -229,6 +287,67 @@ impl DataColumn {
     }
 }
 
+impl DataColumn {
+    /// Parse this column's bytes into the [`EqlCiphertext`]s of a proxy-side
+    /// aggregate: a `jsonb` array of EQL payloads, as built by `jsonb_agg`.
+    ///
+    /// Rows where the encrypted column was NULL appear as JSON `null` and are
+    /// skipped, as the aggregate would skip them. Every element of the array,
+    /// `null` or not, is added to the row's `values`, and the array is refused
+    /// if the row then holds more than `max_values`.
+    fn to_eql_ciphertexts(
+        &self,
+        column: &Column,
+        values_in_row: &mut usize,
+        max_values: usize,
+    ) -> Result<Vec<EqlCiphertext>, Error> {
+        let Some(bytes) = &self.bytes else {
+            return Ok(vec![]);
+        };
+
+        let could_not_be_deserialised = || EncryptError::ColumnCouldNotBeDeserialised {
+            table: column.table_name(),
+            column: column.column_name(),
+        };
+
+        let values: Vec<serde_json::Value> = serde_json::from_slice(jsonb_text(bytes)?)
+            .map_err(log_deserialise_error)
+            .map_err(|_| could_not_be_deserialised())?;
+
+        *values_in_row += values.len();
+        if *values_in_row > max_values {
+            return Err(EncryptError::ProxyAggregateTooLarge {
+                table: column.table_name(),
+                column: column.column_name(),
+                max: max_values,
+            }
+            .into());
+        }
+
+        values
+            .into_iter()
+            .filter(|value| !value.is_null())
+            .map(|value| {
+                serde_json::from_value(value)
+                    .map_err(log_deserialise_error)
+                    .map_err(|_| Error::from(could_not_be_deserialised()))
+            })
+            .collect()
+    }
+}
+
+/// The JSON text of a `jsonb` column in either wire format.
+///
+/// Binary `jsonb` is a 1-byte version header followed by the JSON text; text
+/// `jsonb` is the JSON text alone, which never starts with `0x01`.
+fn jsonb_text(bytes: &[u8]) -> Result<&[u8], Error> {
+    match bytes.first() {
+        Some(&JSONB_BINARY_VERSION) => Ok(&bytes[1..]),
+        Some(_) => Ok(bytes),
+        None => Err(EncryptError::ColumnCouldNotBeParsed.into()),
+    }
+}
+
 /// Whether a decoded EQL payload is a bare `eql_v3_json_entry` — the result of
 /// a JSON field access (`eql_v3."->"(…)` / `eql_v3.jsonb_path_query(…)`).
 ///
@@ -275,14 +394,16 @@ fn log_deserialise_error(err: serde_json::Error) -> Error {
 #[cfg(test)]
 mod tests {
     use super::DataRow;
-    use crate::Identifier;
+    use crate::error::{EncryptError, Error};
     use crate::{
         config::{LogConfig, LogLevel},
         log,
         postgresql::{messages::data_row::DataColumn, Column},
     };
-    use bytes::BytesMut;
+    use crate::{EqlCiphertext, Identifier};
+    use bytes::{BufMut, BytesMut};
     use cipherstash_client::schema::{ColumnConfig, ColumnType};
+    use eql_mapper::ProxyAggregate;
 
     fn to_message(s: &[u8]) -> BytesMut {
         BytesMut::from(s)
@@ -291,7 +412,13 @@ mod tests {
     fn column_config(column: &str) -> Option<Column> {
         let identifier = Identifier::new("encrypted", column);
         let config = ColumnConfig::build("column".to_string()).casts_as(ColumnType::SmallInt);
-        let column = Column::new(identifier, config, None, eql_mapper::EqlTermVariant::Full);
+        let column = Column::new(
+            identifier,
+            config,
+            None,
+            None,
+            eql_mapper::EqlTermVariant::Full,
+        );
         Some(column)
     }
 
@@ -323,10 +450,10 @@ mod tests {
         let encrypted = data_row.as_ciphertext(&column_config);
 
         assert_eq!(encrypted.len(), 1);
-        assert!(encrypted[0].is_some());
+        let ciphertext = encrypted[0].as_ref().unwrap().as_ref().unwrap();
         assert_eq!(
             &column_config[0].as_ref().unwrap().identifier,
-            encrypted[0].as_ref().unwrap().identifier()
+            ciphertext.identifier()
         );
     }
 
@@ -346,8 +473,8 @@ mod tests {
         let encrypted = data_row.as_ciphertext(&column_config);
 
         assert_eq!(encrypted.len(), 2);
-        assert!(encrypted[0].is_some());
-        assert!(encrypted[1].is_none());
+        assert!(encrypted[0].as_ref().unwrap().is_some());
+        assert!(encrypted[1].as_ref().unwrap().is_none());
     }
 
     #[test]
@@ -363,10 +490,10 @@ mod tests {
         let encrypted = data_row.as_ciphertext(&column_config);
 
         assert_eq!(encrypted.len(), 1);
-        assert!(encrypted[0].is_some());
+        let ciphertext = encrypted[0].as_ref().unwrap().as_ref().unwrap();
         assert_eq!(
             &column_config[0].as_ref().unwrap().identifier,
-            encrypted[0].as_ref().unwrap().identifier()
+            ciphertext.identifier()
         );
     }
 
@@ -386,8 +513,136 @@ mod tests {
         let encrypted = data_row.as_ciphertext(&column_config);
 
         assert_eq!(encrypted.len(), 2);
-        assert!(encrypted[0].is_some());
-        assert!(encrypted[1].is_none());
+        assert!(encrypted[0].as_ref().unwrap().is_some());
+        assert!(encrypted[1].as_ref().unwrap().is_none());
+    }
+
+    #[test]
+    pub fn to_ciphertext_with_invalid_payload() {
+        log::init(LogConfig::with_level(LogLevel::Debug));
+
+        let payload = br#"{"not": "a ciphertext"}"#;
+        let mut bytes = BytesMut::new();
+        bytes.put_u8(b'D');
+        bytes.put_i32((4 + 2 + 4 + payload.len()) as i32);
+        bytes.put_i16(1);
+        bytes.put_i32(payload.len() as i32);
+        bytes.put_slice(payload);
+
+        let mut data_row = DataRow::try_from(&bytes).unwrap();
+
+        let column_config = vec![column_config("encrypted_text")];
+        let encrypted = data_row.as_ciphertext(&column_config);
+
+        assert_eq!(encrypted.len(), 1);
+        assert!(matches!(
+            encrypted[0],
+            Err(EncryptError::ColumnCouldNotBeDeserialised { .. })
+        ));
+    }
+
+    // `aggregates` columns of a `jsonb_agg` as returned for a proxy-side
+    // aggregate, in the binary wire format: each holds two copies of the
+    // `encrypted_text` payload above and one NULL.
+    // Parsing does not depend on the column's type.
+    fn aggregate_row(
+        aggregates: usize,
+        max_values: usize,
+        max_bytes: usize,
+    ) -> Result<Vec<(usize, Vec<EqlCiphertext>)>, Error> {
+        let payload = br#"{"c": "mBbL3gJuL?E})+>NeOq5<7N279rs9aRhBwjz3>wOdg{d64myql`6cXIurM_?B|pR<+M8(SeOLoLt~axenSv%=hCOb&m`FC5F;fS-ykq76u4Qgxa(QrcWn^D;Wq5SN5EJ90LtnW_NroxKJj=JLK>", "i": {"c": "encrypted_text", "t": "encrypted"}, "v": 3, "bf": [1512, 1681, 836, 288, 1837, 1131, 415, 1430, 60, 812, 1990, 1211, 1368, 343, 1473, 1980, 598, 1549, 457, 1389, 1557, 941, 494, 1009, 1604, 1033, 2046, 222, 2012, 671, 7, 1525, 265, 901, 743, 543, 1771, 1149, 890, 755, 1974, 1960, 387, 1947, 1298, 130, 1758, 1060, 268, 844, 1375, 746, 1251, 2040], "hm": "96aeaf9852416229d6b33ceb018d9abc90d70cbe7632539d69ef1462c9aa86a0", "op": "00bf0281ccb68cc6fe496bb1c8277e3484f6392517d5b8425536af7ec00ad7cc40e17e6336568ac4ed98dd659f7581f8a113fe5669b89833d9dd8eadc587a8950b6bd94f872e7f4205a6859e071df47134d3cccf1e53295417"}"#;
+
+        let mut bytes = BytesMut::from(&[1u8][..]);
+        bytes.extend_from_slice(b"[");
+        bytes.extend_from_slice(payload);
+        bytes.extend_from_slice(b", null, ");
+        bytes.extend_from_slice(payload);
+        bytes.extend_from_slice(b"]");
+
+        let mut columns = vec![DataColumn { bytes: None }];
+        let mut column_configuration = vec![None];
+        for _ in 0..aggregates {
+            columns.push(DataColumn {
+                bytes: Some(bytes.clone()),
+            });
+            column_configuration.push(
+                column_config("encrypted_text").map(|c| c.with_aggregate(ProxyAggregate::Sum)),
+            );
+        }
+
+        let data_row = DataRow { columns };
+
+        data_row.as_aggregate_ciphertexts(&column_configuration, max_values, max_bytes)
+    }
+
+    #[test]
+    pub fn to_aggregate_ciphertexts_skips_nulls() {
+        log::init(LogConfig::with_level(LogLevel::Debug));
+
+        let aggregates = aggregate_row(1, 10, usize::MAX).unwrap();
+
+        assert_eq!(aggregates.len(), 1);
+
+        let (idx, ciphertexts) = &aggregates[0];
+        assert_eq!(*idx, 1);
+        assert_eq!(ciphertexts.len(), 2);
+        assert_eq!(
+            ciphertexts[0].identifier(),
+            &Identifier::new("encrypted", "encrypted_text")
+        );
+    }
+
+    #[test]
+    pub fn to_aggregate_ciphertexts_is_bounded() {
+        log::init(LogConfig::with_level(LogLevel::Debug));
+
+        let err = aggregate_row(1, 2, usize::MAX).unwrap_err();
+
+        assert!(matches!(
+            err,
+            Error::Encrypt(EncryptError::ProxyAggregateTooLarge { max: 2, .. })
+        ));
+    }
+
+    #[test]
+    pub fn to_aggregate_ciphertexts_is_bounded_across_the_row() {
+        log::init(LogConfig::with_level(LogLevel::Debug));
+
+        assert!(aggregate_row(1, 4, usize::MAX).is_ok());
+
+        let err = aggregate_row(2, 4, usize::MAX).unwrap_err();
+
+        assert!(matches!(
+            err,
+            Error::Encrypt(EncryptError::ProxyAggregateTooLarge { max: 4, .. })
+        ));
+    }
+
+    #[test]
+    pub fn to_aggregate_ciphertexts_counts_nulls_across_the_row() {
+        log::init(LogConfig::with_level(LogLevel::Debug));
+
+        // Each column holds two payloads and a null
+        assert!(aggregate_row(2, 6, usize::MAX).is_ok());
+
+        let err = aggregate_row(2, 5, usize::MAX).unwrap_err();
+
+        assert!(matches!(
+            err,
+            Error::Encrypt(EncryptError::ProxyAggregateTooLarge { max: 5, .. })
+        ));
+    }
+
+    #[test]
+    pub fn to_aggregate_ciphertexts_is_bounded_in_bytes() {
+        log::init(LogConfig::with_level(LogLevel::Debug));
+
+        let err = aggregate_row(1, 10, 16).unwrap_err();
+
+        assert!(matches!(
+            err,
+            Error::Encrypt(EncryptError::ProxyAggregateTooManyBytes { max_bytes: 16, .. })
+        ));
     }
 
     #[test]
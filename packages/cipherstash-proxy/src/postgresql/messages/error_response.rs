@@ -18,6 +18,8 @@ pub const CODE_SYNTAX_ERROR: &str = "42601";
 pub const CODE_INVALID_TEXT_REPRESENTATION: &str = "22P02";
 pub const CODE_IDLE_SESSION_TIMEOUT: &str = "57P05";
 pub const CODE_SYSTEM_ERROR: &str = "58000";
+pub const CODE_TOO_MANY_CONNECTIONS: &str = "53300";
+pub const CODE_CONFIGURATION_LIMIT_EXCEEDED: &str = "53400";
 
 ///
 /// ErrorResponse (B)
@@ -277,6 +279,85 @@ impl ErrorResponse {
         }
     }
 
+    ///
+    /// Connection limit reached as PostgreSQL error
+    /// Code: 53300 too_many_connections
+    ///
+    pub fn too_many_connections(message: String) -> Self {
+        Self {
+            fields: vec![
+                Field {
+                    code: ErrorResponseCode::Severity,
+                    value: "FATAL".to_string(),
+                },
+                Field {
+                    code: ErrorResponseCode::SeverityLegacy,
+                    value: "FATAL".to_string(),
+                },
+                Field {
+                    code: ErrorResponseCode::Code,
+                    value: CODE_TOO_MANY_CONNECTIONS.to_string(),
+                },
+                Field {
+                    code: ErrorResponseCode::Message,
+                    value: message,
+                },
+            ],
+        }
+    }
+
+    ///
+    /// Rate limit exceeded as PostgreSQL error
+    /// Code: 53400 configuration_limit_exceeded
+    ///
+    /// The statement fails, the connection remains open.
+    ///
+    pub fn configuration_limit_exceeded(message: String) -> Self {
+        Self {
+            fields: vec![
+                Field {
+                    code: ErrorResponseCode::Severity,
+                    value: "ERROR".to_string(),
+                },
+                Field {
+                    code: ErrorResponseCode::SeverityLegacy,
+                    value: "ERROR".to_string(),
+                },
+                Field {
+                    code: ErrorResponseCode::Code,
+                    value: CODE_CONFIGURATION_LIMIT_EXCEEDED.to_string(),
+                },
+                Field {
+                    code: ErrorResponseCode::Message,
+                    value: message,
+                },
+            ],
+        }
+    }
+
+    pub fn client_certificate_required() -> Self {
+        Self {
+            fields: vec![
+                Field {
+                    code: ErrorResponseCode::Severity,
+                    value: "FATAL".to_string(),
+                },
+                Field {
+                    code: ErrorResponseCode::SeverityLegacy,
+                    value: "FATAL".to_string(),
+                },
+                Field {
+                    code: ErrorResponseCode::Code,
+                    value: "28000".to_string(),
+                },
+                Field {
+                    code: ErrorResponseCode::Message,
+                    value: "Client certificate is required".to_string(),
+                },
+            ],
+        }
+    }
+
     pub fn tls_required() -> Self {
         Self {
             fields: vec![
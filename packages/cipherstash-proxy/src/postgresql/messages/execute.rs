@@ -1,8 +1,10 @@
 use super::{FrontendCode, Name};
 use crate::error::{Error, ProtocolError};
 use crate::postgresql::protocol::BytesMutReadString;
-use bytes::{Buf, BytesMut};
+use crate::SIZE_I32;
+use bytes::{Buf, BufMut, BytesMut};
 use std::convert::TryFrom;
+use std::ffi::CString;
 use std::io::Cursor;
 
 #[derive(Debug, Clone)]
@@ -11,6 +13,16 @@ pub(crate) struct Execute {
     pub max_rows: i32,
 }
 
+impl Execute {
+    /// An Execute of the unnamed portal that returns every row
+    pub fn unnamed() -> Execute {
+        Execute {
+            portal: Name::unnamed(),
+            max_rows: 0,
+        }
+    }
+}
+
 impl TryFrom<&BytesMut> for Execute {
     type Error = Error;
 
@@ -35,3 +47,23 @@ impl TryFrom<&BytesMut> for Execute {
         Ok(Execute { portal, max_rows })
     }
 }
+
+impl TryFrom<Execute> for BytesMut {
+    type Error = Error;
+
+    fn try_from(execute: Execute) -> Result<BytesMut, Error> {
+        let mut bytes = BytesMut::new();
+
+        let portal = CString::new(&*execute.portal)?;
+        let portal = portal.as_bytes_with_nul();
+
+        let len = SIZE_I32 + portal.len() + SIZE_I32;
+
+        bytes.put_u8(FrontendCode::Execute.into());
+        bytes.put_i32(len as i32);
+        bytes.put_slice(portal);
+        bytes.put_i32(execute.max_rows);
+
+        Ok(bytes)
+    }
+}
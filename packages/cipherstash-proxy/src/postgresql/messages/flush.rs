@@ -0,0 +1,17 @@
+use super::FrontendCode;
+use bytes::{BufMut, BytesMut};
+
+/// Flush (H) message, which asks the server to send the responses to the messages before it.
+/// See: <https://www.postgresql.org/docs/current/protocol-message-formats.html>
+pub struct Flush;
+
+impl Flush {
+    pub fn message() -> BytesMut {
+        let mut bytes = BytesMut::new();
+
+        bytes.put_u8(FrontendCode::Flush.into());
+        bytes.put_i32(4);
+
+        bytes
+    }
+}
@@ -9,12 +9,16 @@ pub mod data_row;
 pub mod describe;
 pub mod error_response;
 pub mod execute;
+pub mod flush;
 pub mod name;
+pub mod notice_response;
 pub mod param_description;
+pub mod parameter_status;
 pub mod parse;
 pub mod query;
 pub mod ready_for_query;
 pub mod row_description;
+pub mod sync;
 pub mod target;
 pub mod terminate;
 
@@ -105,7 +109,7 @@ impl From<FrontendCode> for u8 {
             FrontendCode::Close => b'C',
             FrontendCode::Describe => b'D',
             FrontendCode::Execute => b'E',
-            FrontendCode::Flush => b'F',
+            FrontendCode::Flush => b'H',
             FrontendCode::Parse => b'P',
             FrontendCode::PasswordMessage => b'p',
             FrontendCode::Query => b'Q',
@@ -125,7 +129,7 @@ impl From<FrontendCode> for char {
             FrontendCode::Close => 'C',
             FrontendCode::Describe => 'D',
             FrontendCode::Execute => 'E',
-            FrontendCode::Flush => 'F',
+            FrontendCode::Flush => 'H',
             FrontendCode::Parse => 'P',
             FrontendCode::PasswordMessage => 'p',
             FrontendCode::Query => 'Q',
@@ -0,0 +1,106 @@
+use super::error_response::{ErrorResponseCode, Field};
+use super::BackendCode;
+use crate::error::Error;
+use crate::SIZE_I32;
+use bytes::{BufMut, BytesMut};
+use std::ffi::CString;
+
+/// Postgres warning code
+/// https://www.postgresql.org/docs/current/errcodes-appendix.html
+pub const CODE_WARNING: &str = "01000";
+
+///
+/// NoticeResponse (N)
+/// https://www.postgresql.org/docs/current/protocol-message-formats.html#PROTOCOL-MESSAGE-FORMATS-NOTICERESPONSE
+///
+/// A NoticeResponse has the same fields as an ErrorResponse, and does not end the statement.
+///
+#[derive(Debug, Clone)]
+pub struct NoticeResponse {
+    pub fields: Vec<Field>,
+}
+
+impl NoticeResponse {
+    ///
+    /// Warning for a column of the current result
+    /// Code: 01000 warning
+    ///
+    pub fn warning(message: String, table: &str, column: &str) -> Self {
+        Self {
+            fields: vec![
+                Field {
+                    code: ErrorResponseCode::Severity,
+                    value: "WARNING".to_string(),
+                },
+                Field {
+                    code: ErrorResponseCode::SeverityLegacy,
+                    value: "WARNING".to_string(),
+                },
+                Field {
+                    code: ErrorResponseCode::Code,
+                    value: CODE_WARNING.to_string(),
+                },
+                Field {
+                    code: ErrorResponseCode::Message,
+                    value: message,
+                },
+                Field {
+                    code: ErrorResponseCode::Table,
+                    value: table.to_string(),
+                },
+                Field {
+                    code: ErrorResponseCode::Column,
+                    value: column.to_string(),
+                },
+                Field {
+                    code: ErrorResponseCode::Routine,
+                    value: "cipherstash-proxy".to_string(),
+                },
+            ],
+        }
+    }
+}
+
+impl TryFrom<NoticeResponse> for BytesMut {
+    type Error = Error;
+
+    fn try_from(notice_response: NoticeResponse) -> Result<BytesMut, Error> {
+        let mut field_bytes = BytesMut::new();
+
+        for field in notice_response.fields {
+            let value = CString::new(field.value)?;
+            let value = value.as_bytes_with_nul();
+
+            field_bytes.put_u8(field.code.into());
+            field_bytes.put_slice(value);
+        }
+        field_bytes.put_u8(0); // field terminator
+
+        let mut bytes = BytesMut::new();
+
+        let len = SIZE_I32 + field_bytes.len(); // len + fields
+
+        bytes.put_u8(BackendCode::NoticeResponse.into());
+        bytes.put_i32(len as i32);
+        bytes.put_slice(&field_bytes);
+
+        Ok(bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::NoticeResponse;
+    use bytes::BytesMut;
+
+    #[test]
+    pub fn notice_response_message() {
+        let notice = NoticeResponse::warning("1 value".to_string(), "users", "email");
+
+        let bytes = BytesMut::try_from(notice).unwrap();
+        let expected = BytesMut::from(
+            &b"N\0\0\0\x48SWARNING\0VWARNING\0C01000\0M1 value\0tusers\0cemail\0Rcipherstash-proxy\0\0"[..],
+        );
+        assert_eq!(bytes, expected);
+    }
+}
@@ -0,0 +1,74 @@
+use crate::error::{Error, ProtocolError};
+use crate::postgresql::protocol::BytesMutReadString;
+
+use bytes::{Buf, BytesMut};
+use std::convert::TryFrom;
+use std::io::Cursor;
+
+use super::BackendCode;
+
+///
+/// ParameterStatus b'S' (Backend) message.
+///
+/// See: <https://www.postgresql.org/docs/current/protocol-message-formats.html>
+///
+///     Byte1('S')
+///     Identifies the message as a run-time parameter status report.
+///
+///     Int32
+///     Length of message contents in bytes, including self.
+///
+///     String
+///     The name of the run-time parameter being reported.
+///
+///     String
+///     The current value of the parameter.
+///
+#[derive(Debug, Clone)]
+pub struct ParameterStatus {
+    pub name: String,
+    pub value: String,
+}
+
+impl TryFrom<&BytesMut> for ParameterStatus {
+    type Error = Error;
+
+    fn try_from(bytes: &BytesMut) -> Result<ParameterStatus, Self::Error> {
+        let mut cursor = Cursor::new(bytes);
+        let code = cursor.get_u8();
+
+        if BackendCode::from(code) != BackendCode::ParameterStatus {
+            return Err(ProtocolError::UnexpectedMessageCode {
+                expected: BackendCode::ParameterStatus.into(),
+                received: code as char,
+            }
+            .into());
+        }
+
+        let _len = cursor.get_i32(); // read and progress cursor
+        let name = cursor.read_string()?;
+        let value = cursor.read_string()?;
+
+        Ok(ParameterStatus { name, value })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ParameterStatus;
+    use bytes::{BufMut, BytesMut};
+
+    #[test]
+    fn parameter_status_from_bytes() {
+        let body = b"DateStyle\0ISO, MDY\0";
+
+        let mut bytes = BytesMut::new();
+        bytes.put_u8(b'S');
+        bytes.put_i32(4 + body.len() as i32);
+        bytes.put_slice(body);
+
+        let status = ParameterStatus::try_from(&bytes).unwrap();
+        assert_eq!(status.name, "DateStyle");
+        assert_eq!(status.value, "ISO, MDY");
+    }
+}
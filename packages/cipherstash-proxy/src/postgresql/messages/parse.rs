@@ -20,6 +20,18 @@ pub struct Parse {
 }
 
 impl Parse {
+    /// A Parse of `statement` into the unnamed statement, leaving the database to infer any param types
+    pub fn unnamed(statement: String) -> Parse {
+        Parse {
+            code: FrontendCode::Parse.into(),
+            name: Name::unnamed(),
+            statement,
+            num_params: 0,
+            param_types: vec![],
+            dirty: false,
+        }
+    }
+
     pub fn requires_rewrite(&self) -> bool {
         self.dirty
     }
@@ -204,7 +216,13 @@ mod tests {
 
         let config = ColumnConfig::build("column".to_string()).casts_as(ColumnType::SmallInt);
 
-        let column = Column::new(identifier, config, None, eql_mapper::EqlTermVariant::Full);
+        let column = Column::new(
+            identifier,
+            config,
+            None,
+            None,
+            eql_mapper::EqlTermVariant::Full,
+        );
         let output_params = vec![
             OutputParam {
                 column: None,
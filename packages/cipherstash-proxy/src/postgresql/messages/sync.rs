@@ -0,0 +1,17 @@
+use super::FrontendCode;
+use bytes::{BufMut, BytesMut};
+
+/// Sync (S) message, which ends an extended query batch and is answered with a ReadyForQuery.
+/// See: <https://www.postgresql.org/docs/current/protocol-message-formats.html>
+pub struct Sync;
+
+impl Sync {
+    pub fn message() -> BytesMut {
+        let mut bytes = BytesMut::new();
+
+        bytes.put_u8(FrontendCode::Sync.into());
+        bytes.put_i32(4);
+
+        bytes
+    }
+}
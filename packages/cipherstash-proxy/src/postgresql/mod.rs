@@ -1,18 +1,26 @@
+mod aggregate;
 mod backend;
+mod catalog;
 mod column_mapper;
 mod context;
 mod data;
+mod decrypt_pipeline;
 mod error_handler;
+mod explain;
 mod format_code;
 mod frontend;
 mod handler;
 mod message_buffer;
 mod messages;
 mod parser;
+mod pipeline;
+mod plaintext_source;
 mod protocol;
+mod router;
 mod startup;
 
 pub use context::column::Column;
+pub use context::phase_timing::PhaseSpan;
 pub use context::Context;
 pub use context::KeysetIdentifier;
 pub use handler::handler;
@@ -0,0 +1,176 @@
+use super::messages::FrontendCode as Code;
+use crate::EqlOutput;
+use bytes::BytesMut;
+use std::collections::VecDeque;
+
+///
+/// A client message read ahead of the message being processed
+///
+/// `encrypted` holds the params of a Bind that were encrypted in a batch with an earlier Bind
+///
+pub struct PendingMessage {
+    pub code: Code,
+    pub bytes: BytesMut,
+    pub encrypted: Option<Vec<Option<EqlOutput>>>,
+}
+
+impl PendingMessage {
+    pub fn new(code: Code, bytes: BytesMut) -> Self {
+        Self {
+            code,
+            bytes,
+            encrypted: None,
+        }
+    }
+}
+
+///
+/// Messages read ahead of a Bind, up to the end of the run of Binds that can be encrypted together
+///
+/// Pipelining clients send runs of Bind/Execute followed by a single Sync or Flush.
+/// Binds in a run are encrypted in one request instead of one request per Bind.
+///
+/// A run ends at any message other than Bind, Execute or Describe.
+/// A Parse or Close can change the statement a later Bind refers to, and a Sync, Flush or Query
+/// must be answered before the client sends more, so a run never reads past them.
+///
+pub struct PendingMessages {
+    messages: VecDeque<PendingMessage>,
+    max_binds: usize,
+}
+
+impl PendingMessages {
+    pub fn new(max_binds: usize) -> Self {
+        Self {
+            messages: VecDeque::new(),
+            max_binds,
+        }
+    }
+
+    pub fn push(&mut self, message: PendingMessage) {
+        self.messages.push_back(message);
+    }
+
+    pub fn pop(&mut self) -> Option<PendingMessage> {
+        self.messages.pop_front()
+    }
+
+    ///
+    /// Whether more messages can be read ahead into the current run
+    ///
+    /// Batching is disabled when `max_binds` is `1`
+    ///
+    pub fn requires_read(&self) -> bool {
+        if self.max_binds <= 1 {
+            return false;
+        }
+
+        let open = self
+            .messages
+            .back()
+            .is_none_or(|message| continues_run(message.code));
+
+        let binds = self
+            .messages
+            .iter()
+            .take_while(|message| continues_run(message.code))
+            .filter(|message| message.code == Code::Bind)
+            .count();
+
+        open && binds < self.max_binds - 1
+    }
+
+    ///
+    /// The Binds in the run of messages after the Bind being processed,
+    /// limited to `max_binds` Binds in total
+    ///
+    pub fn run_binds(&mut self) -> impl Iterator<Item = &mut PendingMessage> {
+        self.messages
+            .iter_mut()
+            .take_while(|message| continues_run(message.code))
+            .filter(|message| message.code == Code::Bind)
+            .take(self.max_binds.saturating_sub(1))
+    }
+}
+
+fn continues_run(code: Code) -> bool {
+    matches!(code, Code::Bind | Code::Execute | Code::Describe)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{PendingMessage, PendingMessages};
+    use crate::postgresql::messages::FrontendCode as Code;
+    use bytes::BytesMut;
+
+    fn pending(max_binds: usize, codes: &[Code]) -> PendingMessages {
+        let mut messages = PendingMessages::new(max_binds);
+        for code in codes {
+            messages.push(PendingMessage::new(*code, BytesMut::new()));
+        }
+        messages
+    }
+
+    #[test]
+    fn run_ends_at_sync_flush_or_parse() {
+        for end in [
+            Code::Sync,
+            Code::Flush,
+            Code::Parse,
+            Code::Close,
+            Code::Query,
+        ] {
+            let mut messages = pending(
+                100,
+                &[Code::Execute, Code::Bind, Code::Execute, end, Code::Bind],
+            );
+
+            assert!(!messages.requires_read(), "{end:?}");
+            assert_eq!(messages.run_binds().count(), 1, "{end:?}");
+        }
+    }
+
+    #[test]
+    fn run_is_read_until_it_ends() {
+        let mut messages = pending(100, &[]);
+        assert!(messages.requires_read());
+
+        messages.push(PendingMessage::new(Code::Execute, BytesMut::new()));
+        messages.push(PendingMessage::new(Code::Describe, BytesMut::new()));
+        messages.push(PendingMessage::new(Code::Bind, BytesMut::new()));
+        assert!(messages.requires_read());
+
+        messages.push(PendingMessage::new(Code::Sync, BytesMut::new()));
+        assert!(!messages.requires_read());
+    }
+
+    #[test]
+    fn run_is_limited_to_max_binds() {
+        let mut messages = pending(3, &[Code::Execute, Code::Bind, Code::Execute, Code::Bind]);
+
+        // The Bind being processed and the two read ahead
+        assert!(!messages.requires_read());
+        assert_eq!(messages.run_binds().count(), 2);
+
+        messages.push(PendingMessage::new(Code::Bind, BytesMut::new()));
+        assert_eq!(messages.run_binds().count(), 2);
+    }
+
+    #[test]
+    fn batching_is_disabled_with_one_bind() {
+        let mut messages = pending(1, &[Code::Execute, Code::Bind]);
+
+        assert!(!messages.requires_read());
+        assert_eq!(messages.run_binds().count(), 0);
+    }
+
+    #[test]
+    fn messages_are_returned_in_order() {
+        let mut messages = pending(100, &[Code::Execute, Code::Bind, Code::Sync]);
+
+        assert_eq!(messages.pop().map(|m| m.code), Some(Code::Execute));
+        assert_eq!(messages.pop().map(|m| m.code), Some(Code::Bind));
+        assert_eq!(messages.pop().map(|m| m.code), Some(Code::Sync));
+        assert!(messages.pop().is_none());
+    }
+}
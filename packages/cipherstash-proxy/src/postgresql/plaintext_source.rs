@@ -0,0 +1,569 @@
+//! Encrypts statements that copy plaintext columns into encrypted columns.
+//!
+//! `INSERT INTO enc SELECT plain FROM t` and `UPDATE enc SET secret = plain`
+//! write values that only exist inside the database, so there is no literal or
+//! param for Proxy to encrypt and the statement cannot be type checked.
+//!
+//! Proxy runs the source query itself, on the client's server connection and in
+//! the client's transaction, encrypts the rows it returns, and then runs a
+//! statement that writes the encrypted values:
+//!
+//! - `INSERT ... SELECT` becomes a multi-row `INSERT ... VALUES`
+//! - `UPDATE ... SET` joins the table to the encrypted values on `ctid`
+//!
+//! The source query sees the same data as the client's statement would,
+//! including rows written earlier in the transaction. An `UPDATE` locks the
+//! rows it reads, and fails if the write does not match every row read, so a
+//! row is never dropped silently.
+//!
+//! The client receives the command tag of the statement that wrote the values.
+//!
+//! The number of rows is bounded by `mapping.plaintext_source_max_rows`.
+
+use crate::error::Error;
+use eql_mapper::{ColumnKind, TableResolver};
+use pg_escape::quote_literal;
+use sqltk::parser::ast::{
+    Assignment, AssignmentTarget, Expr, Ident, Insert, ObjectName, ObjectNamePart, Query, SetExpr,
+    Statement, TableFactor, TableObject, Value,
+};
+use sqltk::parser::dialect::PostgreSqlDialect;
+use sqltk::parser::parser::Parser;
+
+/// Alias of the source rows in the statements Proxy generates
+const SOURCE_ALIAS: &str = "cipherstash_source";
+
+/// Column of the source rows that holds the `ctid` of the row to update
+const CTID_COLUMN: &str = "cipherstash_ctid";
+
+/// The session parameters that the text parsers of [`crate::postgresql::data`] expect the source rows in
+const SESSION_PARAMETERS: [(&str, &str); 3] = [
+    ("TimeZone", "UTC"),
+    ("DateStyle", "ISO"),
+    ("extra_float_digits", "3"),
+];
+
+///
+/// A statement that writes values read from the database into encrypted columns
+///
+#[derive(Debug, Clone)]
+pub enum PlaintextSource {
+    /// `INSERT INTO table (columns) <source>`
+    Insert {
+        table: ObjectName,
+        columns: Vec<Ident>,
+        source: Box<Query>,
+    },
+    /// `UPDATE table SET ... WHERE selection`
+    ///
+    /// `encrypted` are the assignments to encrypted columns, which are read from the source.
+    /// `native` are written as they are.
+    Update {
+        table: ObjectName,
+        alias: Option<Ident>,
+        encrypted: Vec<(Ident, Expr)>,
+        native: Vec<Assignment>,
+        selection: Option<Expr>,
+    },
+}
+
+impl PlaintextSource {
+    ///
+    /// Returns the plaintext source of an `INSERT ... SELECT` or `UPDATE` that writes to an encrypted column
+    ///
+    /// `INSERT ... VALUES`, `ON CONFLICT`, `RETURNING` and `UPDATE ... FROM` are not supported.
+    ///
+    pub fn from_statement(statement: &Statement, table_resolver: &TableResolver) -> Option<Self> {
+        match statement {
+            Statement::Insert(Insert {
+                table: TableObject::TableName(table),
+                table_alias: None,
+                columns,
+                source: Some(source),
+                on: None,
+                returning: None,
+                ..
+            }) => {
+                if matches!(*source.body, SetExpr::Values(_)) {
+                    return None;
+                }
+
+                let table_columns = if columns.is_empty() {
+                    table_resolver.resolve_table_columns(table).ok()?
+                } else {
+                    columns
+                        .iter()
+                        .map(|column| table_resolver.resolve_table_column(table, column))
+                        .collect::<Result<Vec<_>, _>>()
+                        .ok()?
+                };
+
+                if !table_columns
+                    .iter()
+                    .any(|column| matches!(column.kind, ColumnKind::Eql(..)))
+                {
+                    return None;
+                }
+
+                // Columns named in the statement keep the quoting they were written with
+                let columns = if columns.is_empty() {
+                    table_columns
+                        .into_iter()
+                        .map(|column| column.column)
+                        .collect()
+                } else {
+                    columns.clone()
+                };
+
+                Some(PlaintextSource::Insert {
+                    table: table.clone(),
+                    columns,
+                    source: source.clone(),
+                })
+            }
+
+            Statement::Update {
+                table,
+                assignments,
+                from: None,
+                selection,
+                returning: None,
+                ..
+            } => {
+                let TableFactor::Table { name, alias, .. } = &table.relation else {
+                    return None;
+                };
+
+                if !table.joins.is_empty() {
+                    return None;
+                }
+
+                let mut encrypted = vec![];
+                let mut native = vec![];
+
+                for assignment in assignments {
+                    let AssignmentTarget::ColumnName(ObjectName(parts)) = &assignment.target else {
+                        return None;
+                    };
+                    let [ObjectNamePart::Identifier(column)] = parts.as_slice() else {
+                        return None;
+                    };
+
+                    let column = table_resolver.resolve_table_column(name, column).ok()?;
+                    match column.kind {
+                        ColumnKind::Eql(..) => {
+                            encrypted.push((column.column, assignment.value.clone()))
+                        }
+                        ColumnKind::Native => native.push(assignment.clone()),
+                        ColumnKind::UnmappableEncrypted(_) => return None,
+                    }
+                }
+
+                if encrypted.is_empty() {
+                    return None;
+                }
+
+                Some(PlaintextSource::Update {
+                    table: name.clone(),
+                    alias: alias.as_ref().map(|alias| alias.name.clone()),
+                    encrypted,
+                    native,
+                    selection: selection.clone(),
+                })
+            }
+
+            _ => None,
+        }
+    }
+
+    ///
+    /// The query that reads the source values, in the order of [`Self::target_statement`]
+    ///
+    /// Type checked to confirm that the source values are all native.
+    ///
+    pub fn source_statement(&self) -> Result<Statement, Error> {
+        match self {
+            PlaintextSource::Insert { source, .. } => Ok(Statement::Query(source.clone())),
+            PlaintextSource::Update {
+                encrypted,
+                selection,
+                ..
+            } => parse(&format!(
+                "SELECT {} FROM {}{}",
+                join(encrypted.iter().map(|(_, expr)| expr)),
+                self.relation(),
+                where_clause(selection)
+            )),
+        }
+    }
+
+    ///
+    /// An `INSERT` with a param for each source value
+    ///
+    /// Type checked to map the source values to the encrypted columns they are written to.
+    ///
+    pub fn target_statement(&self) -> Result<Statement, Error> {
+        let (table, columns) = match self {
+            PlaintextSource::Insert { table, columns, .. } => (table, columns.clone()),
+            PlaintextSource::Update {
+                table, encrypted, ..
+            } => (
+                table,
+                encrypted.iter().map(|(column, _)| column.clone()).collect(),
+            ),
+        };
+
+        let params = (1..=columns.len()).map(|idx| format!("${idx}"));
+
+        parse(&format!(
+            "INSERT INTO {table} ({}) VALUES ({})",
+            join(&columns),
+            join(params)
+        ))
+    }
+
+    ///
+    /// The SQL Proxy runs to read the source rows, limited to `limit` rows
+    ///
+    /// The rows of an `UPDATE` start with the `ctid` of the row to update, and are locked until the transaction ends.
+    ///
+    pub fn read_query(&self, limit: usize) -> String {
+        match self {
+            PlaintextSource::Insert { source, .. } => {
+                format!("SELECT * FROM ({source}) AS {SOURCE_ALIAS} LIMIT {limit}")
+            }
+            PlaintextSource::Update {
+                encrypted,
+                selection,
+                ..
+            } => format!(
+                "SELECT {}.ctid::text, {} FROM {}{} LIMIT {limit} FOR UPDATE OF {}",
+                self.qualifier(),
+                join(encrypted.iter().map(|(_, expr)| expr)),
+                self.relation(),
+                where_clause(selection),
+                self.qualifier(),
+            ),
+        }
+    }
+
+    ///
+    /// The statement that writes the rows read by [`Self::read_query`]
+    ///
+    /// Values to encrypted columns are the encrypted payload, and values to native columns the text read from the source.
+    ///
+    pub fn write_statement(&self, rows: &[Vec<Value>]) -> Result<Statement, Error> {
+        let sql = match self {
+            PlaintextSource::Insert { table, columns, .. } => {
+                if rows.is_empty() {
+                    // Inserts no rows, with the command tag of the original statement
+                    let nulls = join(columns.iter().map(|_| "NULL"));
+                    format!(
+                        "INSERT INTO {table} ({}) SELECT {nulls} WHERE false",
+                        join(columns)
+                    )
+                } else {
+                    let values = rows.iter().map(|row| format!("({})", join(row)));
+                    format!(
+                        "INSERT INTO {table} ({}) VALUES {}",
+                        join(columns),
+                        join(values)
+                    )
+                }
+            }
+
+            PlaintextSource::Update {
+                encrypted,
+                native,
+                selection,
+                ..
+            } => {
+                if rows.is_empty() {
+                    let assignments = encrypted
+                        .iter()
+                        .map(|(column, _)| format!("{column} = NULL"))
+                        .chain(native.iter().map(ToString::to_string));
+                    format!(
+                        "UPDATE {} SET {} WHERE false",
+                        self.relation(),
+                        join(assignments)
+                    )
+                } else {
+                    let values = (0..encrypted.len()).map(|idx| format!("value_{idx}"));
+                    let assignments = encrypted
+                        .iter()
+                        .enumerate()
+                        .map(|(idx, (column, _))| {
+                            format!("{column} = {SOURCE_ALIAS}.value_{idx}::jsonb")
+                        })
+                        .chain(native.iter().map(ToString::to_string));
+                    let rows = rows.iter().map(|row| format!("({})", join(row)));
+
+                    let selection = match selection {
+                        Some(selection) => format!(" AND ({selection})"),
+                        None => String::new(),
+                    };
+
+                    format!(
+                        "UPDATE {} SET {} FROM (VALUES {}) AS {SOURCE_ALIAS} ({CTID_COLUMN}, {}) WHERE {}.ctid = {SOURCE_ALIAS}.{CTID_COLUMN}::tid{selection}",
+                        self.relation(),
+                        join(assignments),
+                        join(rows),
+                        join(values),
+                        self.qualifier(),
+                    )
+                }
+            }
+        };
+
+        parse(&sql)
+    }
+
+    /// The table of an `UPDATE`, with its alias
+    fn relation(&self) -> String {
+        match self {
+            PlaintextSource::Update {
+                table,
+                alias: Some(alias),
+                ..
+            } => format!("{table} AS {alias}"),
+            PlaintextSource::Update { table, .. } | PlaintextSource::Insert { table, .. } => {
+                table.to_string()
+            }
+        }
+    }
+
+    /// The name that qualifies a column of the table of an `UPDATE`
+    fn qualifier(&self) -> String {
+        match self {
+            PlaintextSource::Update {
+                alias: Some(alias), ..
+            } => alias.to_string(),
+            PlaintextSource::Update { table, .. } | PlaintextSource::Insert { table, .. } => {
+                match table.0.last() {
+                    Some(ObjectNamePart::Identifier(ident)) => ident.to_string(),
+                    None => table.to_string(),
+                }
+            }
+        }
+    }
+}
+
+///
+/// Reads the session parameters that [`set_session_parameters`] changes
+///
+pub fn save_session_parameters() -> String {
+    let settings = SESSION_PARAMETERS
+        .iter()
+        .map(|(name, _)| format!("current_setting({})", quote_literal(name)));
+    format!("SELECT {}", join(settings))
+}
+
+///
+/// Sets the session parameters the source rows are read with, until the transaction ends
+///
+pub fn set_session_parameters() -> String {
+    set_config(SESSION_PARAMETERS.into_iter())
+}
+
+///
+/// Restores the session parameters read by [`save_session_parameters`]
+///
+pub fn restore_session_parameters(saved: &[Option<String>]) -> String {
+    let settings = SESSION_PARAMETERS
+        .iter()
+        .zip(saved)
+        .filter_map(|((name, _), value)| Some((*name, value.as_deref()?)));
+    set_config(settings)
+}
+
+fn set_config<'a>(settings: impl Iterator<Item = (&'a str, &'a str)>) -> String {
+    let settings = settings.map(|(name, value)| {
+        format!(
+            "set_config({}, {}, true)",
+            quote_literal(name),
+            quote_literal(value)
+        )
+    });
+    format!("SELECT {}", join(settings))
+}
+
+fn parse(sql: &str) -> Result<Statement, Error> {
+    let statement = Parser::new(&PostgreSqlDialect {})
+        .try_with_sql(sql)?
+        .parse_statement()?;
+    Ok(statement)
+}
+
+fn join<T: ToString>(items: impl IntoIterator<Item = T>) -> String {
+    items
+        .into_iter()
+        .map(|item| item.to_string())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn where_clause(selection: &Option<Expr>) -> String {
+    match selection {
+        Some(selection) => format!(" WHERE {selection}"),
+        None => String::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::PlaintextSource;
+    use crate::postgresql::parser::SqlParser;
+    use eql_mapper::{Column, DomainIdentity, EqlTraits, Schema, Table, TableResolver};
+    use sqltk::parser::ast::{Ident, Value};
+    use std::sync::Arc;
+
+    fn resolver() -> TableResolver {
+        let mut users = Table::new(Ident::new("users"));
+        users.add_column(Arc::new(Column::native(Ident::new("id"))));
+        users.add_column(Arc::new(Column::eql(
+            Ident::new("email"),
+            EqlTraits::none(),
+            DomainIdentity::from_domain_name("eql_v3_text_eq").unwrap(),
+        )));
+        users.add_column(Arc::new(Column::native(Ident::new("name"))));
+
+        let mut plain_users = Table::new(Ident::new("plain_users"));
+        for column in ["id", "email", "name"] {
+            plain_users.add_column(Arc::new(Column::native(Ident::new(column))));
+        }
+
+        let mut schema = Schema::new("public");
+        schema.add_table(users);
+        schema.add_table(plain_users);
+        TableResolver::new_fixed(Arc::new(schema))
+    }
+
+    fn source(sql: &str) -> Option<PlaintextSource> {
+        PlaintextSource::from_statement(&SqlParser::parse_statement(sql).unwrap(), &resolver())
+    }
+
+    /// The SQL as rendered by the parser
+    fn sql(sql: &str) -> String {
+        SqlParser::parse_statement(sql).unwrap().to_string()
+    }
+
+    fn text(s: &str) -> Value {
+        Value::SingleQuotedString(s.to_owned())
+    }
+
+    #[test]
+    fn insert_select_into_encrypted_column() {
+        let source = source("INSERT INTO users SELECT id, email, name FROM plain_users").unwrap();
+
+        assert_eq!(
+            source.source_statement().unwrap().to_string(),
+            sql("SELECT id, email, name FROM plain_users")
+        );
+        assert_eq!(
+            source.target_statement().unwrap().to_string(),
+            sql("INSERT INTO users (id, email, name) VALUES ($1, $2, $3)")
+        );
+        assert_eq!(
+            source.read_query(11),
+            "SELECT * FROM (SELECT id, email, name FROM plain_users) AS cipherstash_source LIMIT 11"
+        );
+
+        let rows = vec![
+            vec![text("1"), text("{\"c\":\"a\"}"), text("Alice")],
+            vec![text("2"), Value::Null, Value::Null],
+        ];
+        assert_eq!(
+            source.write_statement(&rows).unwrap().to_string(),
+            sql("INSERT INTO users (id, email, name) VALUES ('1', '{\"c\":\"a\"}', 'Alice'), ('2', NULL, NULL)")
+        );
+        assert_eq!(
+            source.write_statement(&[]).unwrap().to_string(),
+            sql("INSERT INTO users (id, email, name) SELECT NULL, NULL, NULL WHERE false")
+        );
+    }
+
+    #[test]
+    fn insert_select_with_column_list() {
+        let source = source("INSERT INTO users (email) SELECT email FROM plain_users").unwrap();
+
+        assert_eq!(
+            source.target_statement().unwrap().to_string(),
+            sql("INSERT INTO users (email) VALUES ($1)")
+        );
+    }
+
+    #[test]
+    fn update_set_encrypted_column() {
+        let source =
+            source("UPDATE users AS u SET email = u.name, name = 'x' WHERE u.id > 10").unwrap();
+
+        assert_eq!(
+            source.source_statement().unwrap().to_string(),
+            sql("SELECT u.name FROM users AS u WHERE u.id > 10")
+        );
+        assert_eq!(
+            source.target_statement().unwrap().to_string(),
+            sql("INSERT INTO users (email) VALUES ($1)")
+        );
+        assert_eq!(
+            source.read_query(11),
+            "SELECT u.ctid::text, u.name FROM users AS u WHERE u.id > 10 LIMIT 11 FOR UPDATE OF u"
+        );
+
+        let rows = vec![vec![text("(0,1)"), text("{\"c\":\"a\"}")]];
+        assert_eq!(
+            source.write_statement(&rows).unwrap().to_string(),
+            sql("UPDATE users AS u SET email = cipherstash_source.value_0::jsonb, name = 'x' FROM (VALUES ('(0,1)', '{\"c\":\"a\"}')) AS cipherstash_source (cipherstash_ctid, value_0) WHERE u.ctid = cipherstash_source.cipherstash_ctid::tid AND (u.id > 10)")
+        );
+        assert_eq!(
+            source.write_statement(&[]).unwrap().to_string(),
+            sql("UPDATE users AS u SET email = NULL, name = 'x' WHERE false")
+        );
+    }
+
+    #[test]
+    fn session_parameters_are_set_and_restored() {
+        assert_eq!(
+            super::save_session_parameters(),
+            "SELECT current_setting('TimeZone'), current_setting('DateStyle'), current_setting('extra_float_digits')"
+        );
+        assert_eq!(
+            super::set_session_parameters(),
+            "SELECT set_config('TimeZone', 'UTC', true), set_config('DateStyle', 'ISO', true), set_config('extra_float_digits', '3', true)"
+        );
+
+        let saved = vec![
+            Some("Australia/Sydney".to_string()),
+            Some("SQL, DMY".to_string()),
+            Some("1".to_string()),
+        ];
+        assert_eq!(
+            super::restore_session_parameters(&saved),
+            "SELECT set_config('TimeZone', 'Australia/Sydney', true), set_config('DateStyle', 'SQL, DMY', true), set_config('extra_float_digits', '1', true)"
+        );
+    }
+
+    #[test]
+    fn statements_without_a_plaintext_source() {
+        for sql in [
+            "INSERT INTO users (email) VALUES ('a')",
+            "INSERT INTO plain_users SELECT id, email, name FROM users",
+            "INSERT INTO users (email) SELECT email FROM plain_users RETURNING id",
+            "INSERT INTO users (email) SELECT email FROM plain_users ON CONFLICT DO NOTHING",
+            "UPDATE users SET name = email",
+            "UPDATE users SET email = p.email FROM plain_users AS p WHERE p.id = users.id",
+            "UPDATE users SET email = name RETURNING id",
+            "DELETE FROM users",
+            "SELECT * FROM users",
+        ] {
+            assert!(source(sql).is_none(), "{sql}");
+        }
+    }
+
+    #[test]
+    fn statements_on_unknown_tables_are_ignored() {
+        assert!(source("INSERT INTO missing SELECT * FROM plain_users").is_none());
+        assert!(source("UPDATE users SET missing = name").is_none());
+    }
+}
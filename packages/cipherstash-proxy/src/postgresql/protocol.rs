@@ -37,6 +37,34 @@ pub struct Message {
     pub bytes: BytesMut,
 }
 
+impl StartupMessage {
+    ///
+    /// The value of a parameter in a `ProtocolVersionNumber` startup message, such as `user` or `database`
+    ///
+    /// Parameters are null-terminated name and value pairs following the length and protocol version
+    ///
+    pub fn parameter(&self, name: &str) -> Option<String> {
+        if self.code != StartupCode::ProtocolVersionNumber || self.bytes.len() < SIZE_I32 * 2 {
+            return None;
+        }
+
+        let mut parameters = self.bytes[SIZE_I32 * 2..]
+            .split(|b| *b == b'\0')
+            .map(String::from_utf8_lossy);
+
+        while let Some(key) = parameters.next() {
+            if key.is_empty() {
+                return None;
+            }
+            let value = parameters.next()?;
+            if key == name {
+                return Some(value.to_string());
+            }
+        }
+        None
+    }
+}
+
 impl From<i32> for StartupCode {
     fn from(code: i32) -> Self {
         match code {
@@ -161,3 +189,41 @@ async fn read<S: AsyncRead + Unpin>(
 
     Ok((code, bytes))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{StartupCode, StartupMessage};
+    use crate::postgresql::PROTOCOL_VERSION_NUMBER;
+    use bytes::{BufMut, BytesMut};
+
+    fn startup_message(parameters: &[(&str, &str)]) -> StartupMessage {
+        let mut body = BytesMut::new();
+        body.put_i32(PROTOCOL_VERSION_NUMBER);
+        for (key, value) in parameters {
+            body.put_slice(key.as_bytes());
+            body.put_u8(0);
+            body.put_slice(value.as_bytes());
+            body.put_u8(0);
+        }
+        body.put_u8(0);
+
+        let mut bytes = BytesMut::new();
+        bytes.put_i32(body.len() as i32 + 4);
+        bytes.put_slice(&body);
+
+        StartupMessage {
+            code: StartupCode::ProtocolVersionNumber,
+            bytes,
+        }
+    }
+
+    #[test]
+    fn startup_message_parameter() {
+        let message = startup_message(&[("user", "alice"), ("database", "app")]);
+
+        assert_eq!(message.parameter("user"), Some("alice".to_string()));
+        assert_eq!(message.parameter("database"), Some("app".to_string()));
+        assert_eq!(message.parameter("application_name"), None);
+        assert_eq!(message.parameter("alice"), None);
+    }
+}
@@ -0,0 +1,931 @@
+use crate::{connect::AsyncStream, log::PROTOCOL, proxy::ReplicaLease};
+use bytes::BytesMut;
+use sqltk::parser::ast::{
+    CreateTable, Function, ObjectName, ObjectNamePart, Query, Set, SetExpr, Statement,
+};
+use sqltk::{Break, Visitable, Visitor};
+use std::{
+    convert::Infallible,
+    io,
+    ops::ControlFlow,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicBool, AtomicU8, AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    task::{Context, Poll, Waker},
+    time::{Duration, Instant},
+};
+use tokio::{
+    io::{AsyncRead, AsyncWrite, ReadBuf, ReadHalf, WriteHalf},
+    sync::Notify,
+};
+use tracing::debug;
+
+/// ReadyForQuery transaction status outside of a transaction block
+const TRANSACTION_IDLE: u8 = b'I';
+
+/// The most messages held for a replica batch before it is sent to the primary instead
+const REPLICA_BATCH_MAX_MESSAGES: usize = 64;
+
+/// The schema Postgres searches implicitly, so its functions may be unqualified
+const PG_CATALOG: &str = "pg_catalog";
+
+/// The schema of the EQL functions, which only read their arguments
+const EQL_SCHEMA: &str = "eql_v3";
+
+/// Built-in functions that cannot write, lock or advance a sequence
+///
+/// A query calling any other function, including every user-defined function, is sent to the primary.
+const READ_ONLY_FUNCTIONS: &[&str] = &[
+    "abs",
+    "array_agg",
+    "array_length",
+    "avg",
+    "bool_and",
+    "bool_or",
+    "btrim",
+    "ceil",
+    "char_length",
+    "coalesce",
+    "concat",
+    "concat_ws",
+    "count",
+    "current_date",
+    "current_timestamp",
+    "date_part",
+    "date_trunc",
+    "extract",
+    "floor",
+    "format_type",
+    "greatest",
+    "json_agg",
+    "jsonb_agg",
+    "jsonb_build_object",
+    "json_build_object",
+    "least",
+    "left",
+    "length",
+    "lower",
+    "ltrim",
+    "max",
+    "min",
+    "now",
+    "nullif",
+    "position",
+    "replace",
+    "right",
+    "round",
+    "rtrim",
+    "string_agg",
+    "substr",
+    "substring",
+    "sum",
+    "to_char",
+    "trim",
+    "trunc",
+    "upper",
+    "version",
+];
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Route {
+    #[default]
+    Primary,
+    Replica,
+}
+
+///
+/// Routes read-only statements to a replica connection
+///
+/// A client connection has a primary connection and, if a healthy replica was available when it connected, a replica
+/// connection. Only one is active at a time, and the active connection only changes once every message sent to it has
+/// been answered with a ReadyForQuery, so responses reach the client in the order it sent its messages.
+///
+/// A simple query, or an extended protocol batch that parses an unnamed statement (see [`ReplicaBatch`]), is sent to
+/// the replica when every statement is read-only, the connection is not in a transaction, no write has been sent
+/// within the sticky-primary window, the session has not changed state (for example with `SET` or `PREPARE`) that
+/// the replica connection would not share, and the replica is available. A replica that fails its health check or
+/// falls more than `database.replica_max_lag_ms` behind is not available, and reads use the primary until it recovers.
+/// Everything else is sent to the primary.
+///
+#[derive(Debug)]
+pub struct Router {
+    replica: Option<ReplicaLease>,
+    sticky_primary: Duration,
+    replica_active: AtomicBool,
+    /// Requests sent to the active connection that have not been answered with a ReadyForQuery.
+    /// Starts at one for the ReadyForQuery that completes the primary startup.
+    pending: AtomicUsize,
+    /// An extended protocol batch has been started and not yet closed by Sync
+    in_batch: AtomicBool,
+    idle: Notify,
+    transaction_status: AtomicU8,
+    pinned: AtomicBool,
+    last_write: Mutex<Option<Instant>>,
+    reader_waker: Mutex<Option<Waker>>,
+}
+
+impl Router {
+    pub fn new(replica: Option<ReplicaLease>, sticky_primary: Duration) -> Self {
+        Router {
+            replica,
+            sticky_primary,
+            replica_active: AtomicBool::new(false),
+            pending: AtomicUsize::new(1),
+            in_batch: AtomicBool::new(false),
+            idle: Notify::new(),
+            transaction_status: AtomicU8::new(TRANSACTION_IDLE),
+            pinned: AtomicBool::new(false),
+            last_write: Mutex::new(None),
+            reader_waker: Mutex::new(None),
+        }
+    }
+
+    ///
+    /// Records the statements and returns the connection they should be sent to
+    ///
+    pub fn route(&self, statements: &[Statement]) -> Route {
+        self.observe(statements);
+
+        if !self
+            .replica
+            .as_ref()
+            .is_some_and(ReplicaLease::is_available)
+            || statements.is_empty()
+            || self.pinned.load(Ordering::Relaxed)
+            || self.transaction_status.load(Ordering::Relaxed) != TRANSACTION_IDLE
+            || !statements.iter().all(is_read_only)
+        {
+            return Route::Primary;
+        }
+
+        let written = self
+            .last_write
+            .lock()
+            .map(|last_write| last_write.is_some_and(|at| at.elapsed() < self.sticky_primary))
+            .unwrap_or(true);
+
+        match written {
+            true => Route::Primary,
+            false => Route::Replica,
+        }
+    }
+
+    ///
+    /// Records writes and session changes in statements that are always sent to the primary
+    ///
+    pub fn observe(&self, statements: &[Statement]) {
+        if self.replica.is_none() {
+            return;
+        }
+
+        for statement in statements {
+            if !is_read_only(statement) {
+                if let Ok(mut last_write) = self.last_write.lock() {
+                    *last_write = Some(Instant::now());
+                }
+            }
+
+            if changes_session(statement) && !self.pinned.swap(true, Ordering::Relaxed) {
+                debug!(target: PROTOCOL, msg = "Session state changed, statements are sent to the primary");
+            }
+        }
+    }
+
+    ///
+    /// Makes the route the active connection, and returns the route that was used
+    ///
+    /// Moving to the replica only happens when the primary is idle, and otherwise stays on the primary.
+    /// Moving back to the primary waits until the replica has answered.
+    ///
+    pub async fn switch(&self, route: Route) -> Route {
+        let replica = route == Route::Replica;
+
+        if self.replica_active.load(Ordering::Acquire) == replica {
+            return route;
+        }
+
+        if replica {
+            if self.pending.load(Ordering::Acquire) > 0 {
+                return Route::Primary;
+            }
+        } else {
+            self.idle().await;
+        }
+
+        self.replica_active.store(replica, Ordering::Release);
+        if let Some(waker) = self.reader_waker.lock().ok().and_then(|mut w| w.take()) {
+            waker.wake();
+        }
+
+        route
+    }
+
+    ///
+    /// Waits until every request sent to the active connection has been answered
+    ///
+    /// Returns false without waiting when an extended protocol batch is open, as it is only answered after
+    /// the client sends a Sync.
+    ///
+    pub async fn wait_until_idle(&self) -> bool {
+        if self.in_batch.load(Ordering::Relaxed) {
+            return false;
+        }
+        self.idle().await;
+        true
+    }
+
+    async fn idle(&self) {
+        loop {
+            let idle = self.idle.notified();
+            if self.pending.load(Ordering::Acquire) == 0 {
+                return;
+            }
+            idle.await;
+        }
+    }
+
+    ///
+    /// Counts the messages in a buffer sent to the active connection, before it is written
+    ///
+    /// A buffer may hold several messages, such as a run of Bind and Execute messages read ahead,
+    /// so every message in it is counted.
+    ///
+    pub fn sent(&self, bytes: &[u8]) {
+        for code in message_codes(bytes) {
+            self.sent_message(code);
+        }
+    }
+
+    ///
+    /// Counts a message sent to the active connection
+    ///
+    /// Each simple query, function call and extended protocol batch is answered with exactly one ReadyForQuery.
+    ///
+    fn sent_message(&self, code: u8) {
+        let expects_ready = match code {
+            // Query closes an open batch with a single ReadyForQuery
+            b'Q' | b'S' => !self.in_batch.swap(false, Ordering::Relaxed),
+            b'F' => true,
+            b'P' | b'B' | b'D' | b'E' | b'C' | b'H' => !self.in_batch.swap(true, Ordering::Relaxed),
+            _ => false,
+        };
+
+        if expects_ready {
+            self.pending.fetch_add(1, Ordering::AcqRel);
+        }
+    }
+
+    pub fn ready_for_query(&self, transaction_status: u8) {
+        self.transaction_status
+            .store(transaction_status, Ordering::Relaxed);
+
+        let previous = self
+            .pending
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |pending| {
+                Some(pending.saturating_sub(1))
+            })
+            .unwrap_or(0);
+
+        if previous <= 1 {
+            self.idle.notify_waiters();
+        }
+    }
+
+    fn replica_active(&self) -> bool {
+        self.replica_active.load(Ordering::Acquire)
+    }
+
+    fn register_reader(&self, waker: &Waker) {
+        if let Ok(mut reader_waker) = self.reader_waker.lock() {
+            match reader_waker.as_ref() {
+                Some(current) if current.will_wake(waker) => {}
+                _ => *reader_waker = Some(waker.clone()),
+            }
+        }
+    }
+}
+
+///
+/// An extended protocol batch held until its Sync, so it can be sent to the replica
+///
+/// The replica connection only has the unnamed statement and portal of the batch, so a batch is held while it
+/// starts with a Parse of an unnamed read-only statement routed to the replica, and every message after it uses
+/// the unnamed statement and portal. A batch is sent to the replica once its Sync is held. A message that does not
+/// belong, such as a Bind of a named statement or a Flush, ends the hold and the batch is sent to the primary, as
+/// it would have been without a replica. A client does not wait for a response before a Sync or Flush, so holding
+/// the batch does not change what it receives.
+///
+#[derive(Debug, Default)]
+pub struct ReplicaBatch {
+    bytes: BytesMut,
+    messages: usize,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Held {
+    /// The messages are held
+    Pending,
+    /// The batch has been closed with a Sync and is sent to the replica
+    Ready(BytesMut),
+    /// The messages do not belong in a replica batch and are sent to `route`, after any batch that was held,
+    /// which is sent to the primary
+    Released {
+        held: Option<BytesMut>,
+        bytes: BytesMut,
+        route: Route,
+    },
+}
+
+impl ReplicaBatch {
+    pub fn is_empty(&self) -> bool {
+        self.messages == 0
+    }
+
+    ///
+    /// Holds the messages in `bytes` if they belong in a replica batch
+    ///
+    /// `route` is the route of the messages, which is the replica for the Parse that starts a batch.
+    ///
+    pub fn hold(&mut self, route: Route, bytes: BytesMut) -> Held {
+        let mut messages = 0;
+        let mut synced = false;
+
+        for (code, body) in message_frames(&bytes) {
+            let belongs = !synced
+                && match code {
+                    b'P' => route == Route::Replica && is_unnamed(body),
+                    // The unnamed portal, then the unnamed statement
+                    b'B' => body.starts_with(&[0, 0]),
+                    b'E' => is_unnamed(body),
+                    b'D' | b'C' => body.get(1..).is_some_and(is_unnamed),
+                    b'S' => {
+                        synced = true;
+                        true
+                    }
+                    _ => false,
+                };
+
+            if !belongs || (self.is_empty() && messages == 0 && code != b'P') {
+                return self.release(route, bytes);
+            }
+            messages += 1;
+        }
+
+        if self.messages + messages > REPLICA_BATCH_MAX_MESSAGES {
+            return self.release(route, bytes);
+        }
+
+        self.bytes.extend_from_slice(&bytes);
+        self.messages += messages;
+
+        match synced {
+            true => {
+                self.messages = 0;
+                Held::Ready(self.bytes.split())
+            }
+            false => Held::Pending,
+        }
+    }
+
+    ///
+    /// Releases the held batch, and the messages that did not belong in it
+    ///
+    /// The messages keep the replica route only when they end with a Sync or a Query,
+    /// so a batch is never left open on the replica.
+    ///
+    fn release(&mut self, route: Route, bytes: BytesMut) -> Held {
+        let held = self.take();
+
+        let route = match message_codes(&bytes).last() {
+            Some(b'S' | b'Q') if held.is_none() => route,
+            _ => Route::Primary,
+        };
+
+        Held::Released { held, bytes, route }
+    }
+
+    ///
+    /// Takes the held batch, which is sent to the primary
+    ///
+    pub fn take(&mut self) -> Option<BytesMut> {
+        let held = match self.is_empty() {
+            true => None,
+            false => Some(self.bytes.split()),
+        };
+        self.messages = 0;
+        held
+    }
+}
+
+/// Returns true if a message body starts with the empty name of the unnamed statement or portal
+fn is_unnamed(body: &[u8]) -> bool {
+    body.first() == Some(&0)
+}
+
+///
+/// Returns the codes of the messages framed in a buffer
+///
+fn message_codes(bytes: &[u8]) -> impl Iterator<Item = u8> + '_ {
+    message_frames(bytes).map(|(code, _)| code)
+}
+
+///
+/// Returns the code and body of the messages framed in a buffer
+///
+/// Each message is a one byte code followed by a four byte length that includes itself.
+/// A malformed length ends the iteration.
+///
+fn message_frames(bytes: &[u8]) -> impl Iterator<Item = (u8, &[u8])> + '_ {
+    let mut rest = bytes;
+    std::iter::from_fn(move || {
+        let (&code, tail) = rest.split_first()?;
+        let len = tail
+            .get(..4)
+            .map(|len| u32::from_be_bytes([len[0], len[1], len[2], len[3]]) as usize)
+            .filter(|len| *len >= 4)
+            .unwrap_or(tail.len());
+        let body = tail.get(4..len).unwrap_or_default();
+        rest = tail.get(len..).unwrap_or_default();
+        Some((code, body))
+    })
+}
+
+///
+/// Returns true if the statement cannot write, lock rows or create objects
+///
+/// Data-modifying CTEs, `SELECT INTO` and locking clauses are writes, and so is a call to any function
+/// that is not known to be read-only, such as `nextval` or a user-defined function.
+///
+pub fn is_read_only(statement: &Statement) -> bool {
+    match statement {
+        Statement::Query(query) => {
+            is_read_only_query(query) && calls_read_only_functions(statement)
+        }
+        _ => false,
+    }
+}
+
+///
+/// Returns true if every function the statement calls is known to be read-only
+///
+pub fn calls_read_only_functions(statement: &Statement) -> bool {
+    struct FunctionFinder;
+
+    impl<'ast> Visitor<'ast> for FunctionFinder {
+        type Error = Infallible;
+
+        fn enter<N: Visitable>(&mut self, node: &'ast N) -> ControlFlow<Break<Self::Error>> {
+            if let Some(function) = node.downcast_ref::<Function>() {
+                if !is_read_only_function(&function.name) {
+                    return ControlFlow::Break(Break::Finished);
+                }
+            }
+
+            ControlFlow::Continue(())
+        }
+    }
+
+    statement.accept(&mut FunctionFinder).is_continue()
+}
+
+fn is_read_only_function(name: &ObjectName) -> bool {
+    let parts = name
+        .0
+        .iter()
+        .map(|ObjectNamePart::Identifier(ident)| ident.value.to_lowercase())
+        .collect::<Vec<_>>();
+
+    match &parts[..] {
+        [name] => READ_ONLY_FUNCTIONS.contains(&name.as_str()),
+        [schema, name] if schema == PG_CATALOG => READ_ONLY_FUNCTIONS.contains(&name.as_str()),
+        [schema, _] => schema == EQL_SCHEMA,
+        _ => false,
+    }
+}
+
+fn is_read_only_query(query: &Query) -> bool {
+    query.locks.is_empty()
+        && query.with.as_ref().is_none_or(|with| {
+            with.cte_tables
+                .iter()
+                .all(|cte| is_read_only_query(&cte.query))
+        })
+        && is_read_only_set_expr(&query.body)
+}
+
+fn is_read_only_set_expr(body: &SetExpr) -> bool {
+    match body {
+        SetExpr::Select(select) => select.into.is_none(),
+        SetExpr::Query(query) => is_read_only_query(query),
+        SetExpr::SetOperation { left, right, .. } => {
+            is_read_only_set_expr(left) && is_read_only_set_expr(right)
+        }
+        SetExpr::Values(_) | SetExpr::Table(_) => true,
+        _ => false,
+    }
+}
+
+///
+/// Returns true if the statement may change session state that a replica connection would not share
+///
+/// Statements are allowed by kind, so anything unrecognised keeps the session on the primary.
+/// `SET CIPHERSTASH.*` is handled by the proxy for both connections.
+///
+fn changes_session(statement: &Statement) -> bool {
+    match statement {
+        Statement::Query(_)
+        | Statement::Insert(_)
+        | Statement::Update { .. }
+        | Statement::Delete(_)
+        | Statement::Merge { .. }
+        | Statement::Explain { .. }
+        | Statement::StartTransaction { .. }
+        | Statement::Commit { .. }
+        | Statement::Rollback { .. }
+        | Statement::AlterTable { .. }
+        | Statement::Drop { .. } => false,
+        Statement::CreateTable(CreateTable { temporary, .. }) => *temporary,
+        Statement::Set(Set::SingleAssignment { variable, .. }) => !matches!(
+            variable.0.first(),
+            Some(ObjectNamePart::Identifier(ident)) if variable.0.len() == 2 && ident.value.eq_ignore_ascii_case("cipherstash")
+        ),
+        _ => true,
+    }
+}
+
+///
+/// Reads server messages from the active connection
+///
+pub struct RoutedReader {
+    primary: ReadHalf<AsyncStream>,
+    replica: Option<ReadHalf<AsyncStream>>,
+    router: Arc<Router>,
+}
+
+///
+/// Writes client messages to the active connection
+///
+pub struct RoutedWriter {
+    primary: WriteHalf<AsyncStream>,
+    replica: Option<WriteHalf<AsyncStream>>,
+    router: Arc<Router>,
+}
+
+///
+/// Splits the primary and optional replica streams into a routed reader and writer
+///
+pub fn split(
+    primary: AsyncStream,
+    replica: Option<AsyncStream>,
+    router: Arc<Router>,
+) -> (RoutedReader, RoutedWriter) {
+    let (primary_reader, primary_writer) = primary.split();
+    let (replica_reader, replica_writer) = match replica.map(AsyncStream::split) {
+        Some((reader, writer)) => (Some(reader), Some(writer)),
+        None => (None, None),
+    };
+
+    let reader = RoutedReader {
+        primary: primary_reader,
+        replica: replica_reader,
+        router: router.clone(),
+    };
+    let writer = RoutedWriter {
+        primary: primary_writer,
+        replica: replica_writer,
+        router,
+    };
+    (reader, writer)
+}
+
+impl AsyncRead for RoutedReader {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = &mut *self;
+        if let Some(replica) = this.replica.as_mut() {
+            // Registered before checking the route, so a switch while the read is pending wakes it
+            this.router.register_reader(cx.waker());
+            if this.router.replica_active() {
+                return Pin::new(replica).poll_read(cx, buf);
+            }
+        }
+        Pin::new(&mut this.primary).poll_read(cx, buf)
+    }
+}
+
+impl RoutedWriter {
+    fn active(&mut self) -> &mut WriteHalf<AsyncStream> {
+        match self.replica.as_mut() {
+            Some(replica) if self.router.replica_active() => replica,
+            _ => &mut self.primary,
+        }
+    }
+}
+
+impl AsyncWrite for RoutedWriter {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(self.active()).poll_write(cx, buf)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(self.active()).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = &mut *self;
+        if let Some(replica) = this.replica.as_mut() {
+            if Pin::new(replica).poll_shutdown(cx)?.is_pending() {
+                return Poll::Pending;
+            }
+        }
+        Pin::new(&mut this.primary).poll_shutdown(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::postgresql::parser::SqlParser;
+
+    fn statements(sql: &str) -> Vec<Statement> {
+        SqlParser::parse_statements(sql).unwrap()
+    }
+
+    fn router() -> Router {
+        let router = Router::new(Some(ReplicaLease::for_testing()), Duration::from_secs(60));
+        // Startup ReadyForQuery
+        router.ready_for_query(TRANSACTION_IDLE);
+        router
+    }
+
+    #[test]
+    fn read_only_statements() {
+        for sql in [
+            "SELECT * FROM users",
+            "SELECT 1; SELECT 2",
+            "WITH u AS (SELECT * FROM users) SELECT * FROM u",
+            "SELECT * FROM users UNION SELECT * FROM admins",
+            "VALUES (1)",
+            "SELECT lower(name), count(*) FROM users GROUP BY lower(name)",
+            "SELECT pg_catalog.upper(name) FROM users",
+            "SELECT eql_v3.ciphertext(email) FROM users",
+        ] {
+            assert!(statements(sql).iter().all(is_read_only), "{sql}");
+        }
+
+        for sql in [
+            "SELECT * FROM users FOR UPDATE",
+            "SELECT * INTO copy FROM users",
+            "WITH d AS (DELETE FROM users RETURNING *) SELECT * FROM d",
+            "INSERT INTO users VALUES (1)",
+            "UPDATE users SET name = 'a'",
+            "CREATE TABLE t (id int)",
+            "SELECT nextval('users_id_seq')",
+            "SELECT app.audit(id) FROM users",
+            "SELECT * FROM users WHERE id IN (SELECT setval('s', 1))",
+            "SELECT pg_advisory_lock(1)",
+        ] {
+            assert!(!statements(sql).iter().all(is_read_only), "{sql}");
+        }
+    }
+
+    #[test]
+    fn reads_are_routed_to_replica() {
+        let router = router();
+        assert_eq!(router.route(&statements("SELECT 1")), Route::Replica);
+        assert_eq!(
+            router.route(&statements("SELECT 1; SELECT 2")),
+            Route::Replica
+        );
+    }
+
+    #[test]
+    fn reads_stay_on_primary_without_replica() {
+        let router = Router::new(None, Duration::from_secs(60));
+        router.ready_for_query(TRANSACTION_IDLE);
+        assert_eq!(router.route(&statements("SELECT 1")), Route::Primary);
+    }
+
+    #[test]
+    fn reads_stay_on_primary_after_write() {
+        let router = router();
+        assert_eq!(
+            router.route(&statements("INSERT INTO users VALUES (1)")),
+            Route::Primary
+        );
+        assert_eq!(router.route(&statements("SELECT 1")), Route::Primary);
+
+        let router = Router::new(Some(ReplicaLease::for_testing()), Duration::ZERO);
+        router.ready_for_query(TRANSACTION_IDLE);
+        router.route(&statements("INSERT INTO users VALUES (1)"));
+        assert_eq!(router.route(&statements("SELECT 1")), Route::Replica);
+    }
+
+    #[test]
+    fn reads_stay_on_primary_while_replica_lags() {
+        let router = router();
+        let replica = router.replica.as_ref().unwrap();
+
+        replica.set_lag(Some(Duration::from_secs(60)));
+        assert_eq!(router.route(&statements("SELECT 1")), Route::Primary);
+
+        replica.set_lag(Some(Duration::ZERO));
+        assert_eq!(router.route(&statements("SELECT 1")), Route::Replica);
+    }
+
+    #[test]
+    fn reads_stay_on_primary_in_transaction() {
+        let router = router();
+        router.ready_for_query(b'T');
+        assert_eq!(router.route(&statements("SELECT 1")), Route::Primary);
+
+        router.ready_for_query(TRANSACTION_IDLE);
+        assert_eq!(router.route(&statements("SELECT 1")), Route::Replica);
+    }
+
+    #[test]
+    fn session_changes_pin_to_primary() {
+        let router = router();
+        router.route(&statements("SET CIPHERSTASH.KEYSET_NAME = 'tenant'"));
+        assert_eq!(router.route(&statements("SELECT 1")), Route::Replica);
+
+        router.route(&statements("SET search_path = app"));
+        assert_eq!(router.route(&statements("SELECT 1")), Route::Primary);
+    }
+
+    fn message(code: u8, body: &[u8]) -> Vec<u8> {
+        let mut bytes = vec![code];
+        bytes.extend_from_slice(&(body.len() as u32 + 4).to_be_bytes());
+        bytes.extend_from_slice(body);
+        bytes
+    }
+
+    #[test]
+    fn every_message_in_a_buffer_is_counted() {
+        let router = router();
+
+        // A batch read ahead into one buffer is answered with one ReadyForQuery
+        let batch = [
+            message(b'B', b"portal"),
+            message(b'E', b"\0\0\0\0\0"),
+            message(b'B', b""),
+            message(b'E', b""),
+            message(b'S', b""),
+        ]
+        .concat();
+        router.sent(&batch);
+        assert_eq!(router.pending.load(Ordering::Acquire), 1);
+        router.ready_for_query(TRANSACTION_IDLE);
+
+        // Two queries in one buffer are answered with two
+        let queries = [message(b'Q', b"SELECT 1\0"), message(b'Q', b"SELECT 2\0")].concat();
+        router.sent(&queries);
+        assert_eq!(router.pending.load(Ordering::Acquire), 2);
+    }
+
+    fn unnamed_parse() -> BytesMut {
+        BytesMut::from(&message(b'P', b"\0SELECT 1\0\0\0")[..])
+    }
+
+    fn bytes(messages: &[Vec<u8>]) -> BytesMut {
+        BytesMut::from(&messages.concat()[..])
+    }
+
+    #[test]
+    fn unnamed_batch_is_held_until_sync() {
+        let mut batch = ReplicaBatch::default();
+
+        assert_eq!(batch.hold(Route::Replica, unnamed_parse()), Held::Pending);
+        assert_eq!(
+            batch.hold(Route::Primary, bytes(&[message(b'B', b"\0\0\0\0\0\0\0\0")])),
+            Held::Pending
+        );
+        assert_eq!(
+            batch.hold(Route::Primary, bytes(&[message(b'D', b"P\0")])),
+            Held::Pending
+        );
+        assert_eq!(
+            batch.hold(Route::Primary, bytes(&[message(b'E', b"\0\0\0\0\0")])),
+            Held::Pending
+        );
+
+        let Held::Ready(held) = batch.hold(Route::Primary, bytes(&[message(b'S', b"")])) else {
+            panic!("expected the batch to be ready");
+        };
+        assert_eq!(message_codes(&held).collect::<Vec<_>>(), b"PBDES".to_vec());
+        assert!(batch.is_empty());
+    }
+
+    #[test]
+    fn named_batch_is_released_to_primary() {
+        let mut batch = ReplicaBatch::default();
+
+        // A batch that does not start with a Parse routed to the replica is not held
+        let named = bytes(&[message(b'P', b"s1\0SELECT 1\0\0\0")]);
+        assert_eq!(
+            batch.hold(Route::Primary, named.clone()),
+            Held::Released {
+                held: None,
+                bytes: named,
+                route: Route::Primary
+            }
+        );
+
+        // A Bind of a named statement releases the held Parse to the primary
+        batch.hold(Route::Replica, unnamed_parse());
+        let bind = bytes(&[message(b'B', b"\0s1\0\0\0\0\0\0\0")]);
+        assert_eq!(
+            batch.hold(Route::Primary, bind.clone()),
+            Held::Released {
+                held: Some(unnamed_parse()),
+                bytes: bind,
+                route: Route::Primary
+            }
+        );
+
+        // So does a Flush, as the client may wait for the responses
+        batch.hold(Route::Replica, unnamed_parse());
+        let flush = bytes(&[message(b'H', b"")]);
+        assert_eq!(
+            batch.hold(Route::Primary, flush.clone()),
+            Held::Released {
+                held: Some(unnamed_parse()),
+                bytes: flush,
+                route: Route::Primary
+            }
+        );
+        assert!(batch.is_empty());
+    }
+
+    #[test]
+    fn released_messages_only_use_replica_when_complete() {
+        let mut batch = ReplicaBatch::default();
+
+        let query = bytes(&[message(b'Q', b"SELECT 1\0")]);
+        assert_eq!(
+            batch.hold(Route::Replica, query.clone()),
+            Held::Released {
+                held: None,
+                bytes: query,
+                route: Route::Replica
+            }
+        );
+
+        // A batch left open on the replica could not move back to the primary
+        let open = bytes(&[message(b'P', b"\0SELECT 1\0\0\0"), message(b'H', b"")]);
+        assert_eq!(
+            batch.hold(Route::Replica, open.clone()),
+            Held::Released {
+                held: None,
+                bytes: open,
+                route: Route::Primary
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn switch_waits_for_ready_for_query() {
+        let router = router();
+
+        // An open extended protocol batch keeps the primary active
+        router.sent(&message(b'P', b""));
+        assert_eq!(router.switch(Route::Replica).await, Route::Primary);
+        router.sent(&message(b'S', b""));
+        router.ready_for_query(TRANSACTION_IDLE);
+
+        assert_eq!(router.switch(Route::Replica).await, Route::Replica);
+        router.sent(&message(b'Q', b"SELECT 1\0"));
+
+        let switched =
+            tokio::time::timeout(Duration::from_millis(50), router.switch(Route::Primary));
+        assert!(switched.await.is_err());
+
+        router.ready_for_query(TRANSACTION_IDLE);
+        assert_eq!(router.switch(Route::Primary).await, Route::Primary);
+    }
+
+    #[tokio::test]
+    async fn wait_until_idle_waits_for_ready_for_query() {
+        let router = router();
+        router.sent(&message(b'Q', b"SELECT 1\0"));
+
+        let idle = tokio::time::timeout(Duration::from_millis(50), router.wait_until_idle());
+        assert!(idle.await.is_err());
+
+        router.ready_for_query(TRANSACTION_IDLE);
+        assert!(router.wait_until_idle().await);
+
+        // A batch without a Sync is only answered after the client sends one
+        router.sent(&message(b'P', b""));
+        assert!(!router.wait_until_idle().await);
+    }
+}
@@ -8,17 +8,35 @@ use tokio::{
 use tracing::{debug, error, warn};
 
 use crate::{
+    config::{DatabaseConfig, SslMode},
     connect::AsyncStream,
-    error::{Error, ProtocolError},
+    error::{ConfigError, Error, ProtocolError},
     log::PROTOCOL,
     postgresql::{SSL_REQUEST, SSL_RESPONSE_NO, SSL_RESPONSE_YES},
-    tls, TandemConfig, SIZE_I32,
+    tls, SIZE_I32,
 };
 
 use super::protocol::StartupMessage;
 
-pub async fn with_tls(stream: AsyncStream, config: &TandemConfig) -> Result<AsyncStream, Error> {
-    if config.database_tls_disabled() {
+///
+/// Negotiates TLS with the database according to the configured `sslmode`
+///
+/// If the database does not support TLS, only `prefer` falls back to an unencrypted connection.
+/// As with libpq, TLS is not negotiated over a Unix domain socket.
+///
+pub async fn with_tls(
+    stream: AsyncStream,
+    database: &DatabaseConfig,
+    tls_disabled: bool,
+) -> Result<AsyncStream, Error> {
+    let ssl_mode = database.ssl_mode();
+
+    if stream.is_unix() {
+        debug!(target: PROTOCOL, msg = "Connecting to database over Unix domain socket");
+        return Ok(stream);
+    }
+
+    if tls_disabled || ssl_mode == SslMode::Disable {
         warn!(msg = "Connecting to database without Transport Layer Security (TLS)");
         return Ok(stream);
     }
@@ -28,16 +46,23 @@ pub async fn with_tls(stream: AsyncStream, config: &TandemConfig) -> Result<Asyn
 
             match server_supports_ssl {
                 true => {
-                    let tls_stream = tls::client(tcp_stream, config).await?;
+                    let tls_stream = tls::client(tcp_stream, database).await?;
                     Ok(AsyncStream::Tls(Box::new(tls_stream)))
                 }
+                false if ssl_mode.requires_tls() => {
+                    error!(
+                        msg = "Database does not support Transport Layer Security (TLS)",
+                        %ssl_mode
+                    );
+                    Err(ConfigError::DatabaseTlsRequired { ssl_mode }.into())
+                }
                 false => {
                     warn!(msg = "Connecting to database without Transport Layer Security (TLS)");
                     Ok(AsyncStream::Tcp(tcp_stream))
                 }
             }
         }
-        AsyncStream::Tls(_) => {
+        AsyncStream::Tls(_) | AsyncStream::Unix(_) => {
             // Technically unreachable unless the server is misbehaving
             warn!(msg = "Database already connected over Transport Layer Security (TLS)");
             Ok(stream)
@@ -15,6 +15,9 @@ pub const DECRYPTED_VALUES_TOTAL: &str = "cipherstash_proxy_decrypted_values_tot
 pub const DECRYPTION_ERROR_TOTAL: &str = "cipherstash_proxy_decryption_error_total";
 pub const DECRYPTION_REQUESTS_TOTAL: &str = "cipherstash_proxy_decryption_requests_total";
 pub const DECRYPTION_DURATION_SECONDS: &str = "cipherstash_proxy_decryption_duration_seconds";
+pub const DECRYPTION_FAILED_VALUES_TOTAL: &str = "cipherstash_proxy_decryption_failed_values_total";
+
+pub const PROXY_AGGREGATE_VALUES_TOTAL: &str = "cipherstash_proxy_aggregate_values_total";
 
 pub const STATEMENTS_TOTAL: &str = "cipherstash_proxy_statements_total";
 pub const STATEMENTS_ENCRYPTED_TOTAL: &str = "cipherstash_proxy_statements_encrypted_total";
@@ -22,6 +25,9 @@ pub const STATEMENTS_PASSTHROUGH_MAPPING_DISABLED_TOTAL: &str =
     "cipherstash_proxy_statements_passthrough_mapping_disabled_total";
 pub const STATEMENTS_PASSTHROUGH_TOTAL: &str = "cipherstash_proxy_statements_passthrough_total";
 pub const STATEMENTS_UNMAPPABLE_TOTAL: &str = "cipherstash_proxy_statements_unmappable_total";
+pub const STATEMENTS_REPLICA_TOTAL: &str = "cipherstash_proxy_statements_replica_total";
+pub const STATEMENTS_STRICT_REFUSED_TOTAL: &str =
+    "cipherstash_proxy_statements_strict_refused_total";
 pub const STATEMENTS_SESSION_DURATION_SECONDS: &str =
     "cipherstash_proxy_statements_session_duration_seconds";
 pub const STATEMENTS_EXECUTION_DURATION_SECONDS: &str =
@@ -43,6 +49,18 @@ pub const KEYSET_CIPHER_CACHE_HITS_TOTAL: &str = "cipherstash_proxy_keyset_ciphe
 pub const KEYSET_CIPHER_CACHE_MISS_TOTAL: &str = "cipherstash_proxy_keyset_cipher_cache_miss_total";
 pub const KEYSET_CIPHER_INIT_DURATION_SECONDS: &str =
     "cipherstash_proxy_keyset_cipher_init_duration_seconds";
+pub const KEYSET_CIPHER_STALE_TOTAL: &str = "cipherstash_proxy_keyset_cipher_stale_total";
+
+pub const ZEROKMS_RETRIES_TOTAL: &str = "cipherstash_proxy_zerokms_retries_total";
+pub const ZEROKMS_CIRCUIT_BREAKER_OPEN: &str = "cipherstash_proxy_zerokms_circuit_breaker_open";
+pub const ZEROKMS_CIRCUIT_BREAKER_REJECTED_TOTAL: &str =
+    "cipherstash_proxy_zerokms_circuit_breaker_rejected_total";
+pub const ZEROKMS_COALESCED_REQUESTS: &str = "cipherstash_proxy_zerokms_coalesced_requests";
+pub const ZEROKMS_COALESCED_VALUES: &str = "cipherstash_proxy_zerokms_coalesced_values";
+pub const ZEROKMS_COALESCE_WAIT_SECONDS: &str = "cipherstash_proxy_zerokms_coalesce_wait_seconds";
+
+pub const LIMIT: &str = "cipherstash_proxy_limit";
+pub const LIMITS_REJECTED_TOTAL: &str = "cipherstash_proxy_limits_rejected_total";
 
 pub fn start(host: String, port: u16) -> Result<(), Error> {
     let address = format!("{host}:{port}");
@@ -88,6 +106,14 @@ pub fn start(host: String, port: u16) -> Result<(), Error> {
         Unit::Seconds,
         "Duration of time CipherStash Proxy spent performing decryption operations"
     );
+    describe_counter!(
+        DECRYPTION_FAILED_VALUES_TOTAL,
+        "Number of values that could not be decrypted, by table and column"
+    );
+    describe_counter!(
+        PROXY_AGGREGATE_VALUES_TOTAL,
+        "Number of encrypted values returned by the database for proxy-side aggregates"
+    );
 
     describe_counter!(
         STATEMENTS_TOTAL,
@@ -109,6 +135,14 @@ pub fn start(host: String, port: u16) -> Result<(), Error> {
         STATEMENTS_UNMAPPABLE_TOTAL,
         "Total number of unmappable SQL statements processed by CipherStash Proxy"
     );
+    describe_counter!(
+        STATEMENTS_STRICT_REFUSED_TOTAL,
+        "Number of statements refused in strict mapping mode"
+    );
+    describe_counter!(
+        STATEMENTS_REPLICA_TOTAL,
+        "Number of simple queries and extended protocol batches routed to a read replica"
+    );
     describe_histogram!(
         STATEMENTS_SESSION_DURATION_SECONDS,
         Unit::Seconds,
@@ -173,10 +207,50 @@ pub fn start(host: String, port: u16) -> Result<(), Error> {
         Unit::Seconds,
         "Duration of keyset-scoped cipher initialization (includes ZeroKMS network call)"
     );
+    describe_counter!(
+        KEYSET_CIPHER_STALE_TOTAL,
+        "Number of times an expired keyset-scoped cipher was used while it was refreshed"
+    );
+
+    describe_counter!(
+        ZEROKMS_RETRIES_TOTAL,
+        "Number of ZeroKMS requests retried after a transient error"
+    );
+    describe_gauge!(
+        ZEROKMS_CIRCUIT_BREAKER_OPEN,
+        "Whether the ZeroKMS circuit breaker is open (1) or closed (0)"
+    );
+    describe_counter!(
+        ZEROKMS_CIRCUIT_BREAKER_REJECTED_TOTAL,
+        "Number of ZeroKMS requests failed fast by the open circuit breaker"
+    );
+    describe_histogram!(
+        ZEROKMS_COALESCED_REQUESTS,
+        "Number of encrypt or decrypt requests coalesced into each ZeroKMS request"
+    );
+    describe_histogram!(
+        ZEROKMS_COALESCED_VALUES,
+        "Number of values in each coalesced ZeroKMS request"
+    );
+    describe_histogram!(
+        ZEROKMS_COALESCE_WAIT_SECONDS,
+        Unit::Seconds,
+        "Time a request waited to be coalesced before it was sent to ZeroKMS"
+    );
+
+    describe_gauge!(
+        LIMIT,
+        "Configured connection and rate limits by limit, 0 is unlimited"
+    );
+    describe_counter!(
+        LIMITS_REJECTED_TOTAL,
+        "Number of connections and statements rejected by a connection or rate limit"
+    );
 
     // Prometheus endpoint is empty on startup and looks like an error
     // Explicitly set count to zero
     gauge!(CLIENTS_ACTIVE_CONNECTIONS).set(0);
+    gauge!(ZEROKMS_CIRCUIT_BREAKER_OPEN).set(0);
 
     info!(msg = "Prometheus exporter started", port);
     Ok(())
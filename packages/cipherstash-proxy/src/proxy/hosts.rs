@@ -0,0 +1,175 @@
+use crate::{config::DatabaseConfig, connect, error::Error, log::DEVELOPMENT};
+use std::{
+    sync::{Arc, Weak},
+    time::Duration,
+};
+use tokio::sync::Mutex;
+use tracing::{debug, info, warn};
+
+///
+/// The database host used for client connections
+///
+/// With `hosts` or `target_session_attrs`, the selected host is cached so each client connection does not probe the
+/// hosts. The selection is checked again on an interval, and is cleared when a client connection to it fails, so the
+/// next connection selects a host again.
+///
+#[derive(Clone, Debug)]
+pub struct DatabaseHosts {
+    config: DatabaseConfig,
+    selected: Arc<Mutex<Option<DatabaseConfig>>>,
+}
+
+impl DatabaseHosts {
+    pub fn init(config: &DatabaseConfig) -> Self {
+        let selected = Arc::new(Mutex::new(None));
+
+        if config.selects_host() {
+            init_host_check(
+                config.clone(),
+                Arc::downgrade(&selected),
+                config.host_check_interval(),
+            );
+        }
+
+        DatabaseHosts {
+            config: config.clone(),
+            selected,
+        }
+    }
+
+    ///
+    /// Returns the selected host, selecting one if there is no selection
+    ///
+    /// Connections that arrive while a host is being selected wait for the selection.
+    ///
+    pub async fn select(&self) -> Result<DatabaseConfig, Error> {
+        if !self.config.selects_host() {
+            return Ok(self.config.clone());
+        }
+
+        let mut selected = self.selected.lock().await;
+        if let Some(host) = selected.as_ref() {
+            return Ok(host.clone());
+        }
+
+        let host = connect::select_database_host(&self.config).await?;
+        *selected = Some(host.clone());
+        Ok(host)
+    }
+
+    ///
+    /// Clears the selection if it is `host`, so the next connection selects a host again
+    ///
+    pub async fn mark_failed(&self, host: &DatabaseConfig) {
+        let mut selected = self.selected.lock().await;
+        if selected
+            .as_ref()
+            .is_some_and(|selected| same_host(selected, host))
+        {
+            warn!(
+                msg = "Could not connect to selected database host",
+                host = host.to_socket_address()
+            );
+            *selected = None;
+        }
+    }
+}
+
+fn same_host(a: &DatabaseConfig, b: &DatabaseConfig) -> bool {
+    a.host == b.host && a.port == b.port
+}
+
+///
+/// Checks the hosts on an interval, and replaces the selection with the host that now accepts the session
+///
+/// The task holds a weak reference, and stops once the hosts have been dropped (for example when the configuration is reloaded).
+///
+fn init_host_check(
+    config: DatabaseConfig,
+    selected: Weak<Mutex<Option<DatabaseConfig>>>,
+    interval: Duration,
+) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(interval);
+        // The first tick completes immediately, and the first connection selects a host
+        interval.tick().await;
+
+        loop {
+            interval.tick().await;
+
+            if selected.strong_count() == 0 {
+                debug!(target: DEVELOPMENT, msg = "Database hosts released, stopping host check");
+                return;
+            }
+
+            let host = connect::select_database_host(&config).await.ok();
+
+            let Some(selected) = selected.upgrade() else {
+                return;
+            };
+            let mut selected = selected.lock().await;
+
+            let changed = match (selected.as_ref(), host.as_ref()) {
+                (Some(previous), Some(host)) => !same_host(previous, host),
+                (previous, host) => previous.is_some() != host.is_some(),
+            };
+            if changed {
+                info!(
+                    msg = "Database host changed",
+                    host = host.as_ref().map(DatabaseConfig::to_socket_address),
+                );
+            }
+            *selected = host;
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hosts() -> DatabaseHosts {
+        let mut config = DatabaseConfig::for_testing();
+        config.hosts = vec!["primary".to_string(), "standby".to_string()];
+
+        DatabaseHosts {
+            config,
+            selected: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    #[tokio::test]
+    async fn selected_host_is_cached() {
+        let hosts = hosts();
+        let standby = hosts.config.host_configs().unwrap().remove(1);
+        *hosts.selected.lock().await = Some(standby);
+
+        // Probing would fail, as neither host exists
+        assert_eq!(hosts.select().await.unwrap().host, "standby");
+    }
+
+    #[tokio::test]
+    async fn failed_host_is_cleared() {
+        let hosts = hosts();
+        let mut configs = hosts.config.host_configs().unwrap();
+        let standby = configs.remove(1);
+        let primary = configs.remove(0);
+        *hosts.selected.lock().await = Some(standby.clone());
+
+        // A failure of another host keeps the selection
+        hosts.mark_failed(&primary).await;
+        assert!(hosts.selected.lock().await.is_some());
+
+        hosts.mark_failed(&standby).await;
+        assert!(hosts.selected.lock().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn configured_host_is_used_without_hosts() {
+        let config = DatabaseConfig::for_testing();
+        let hosts = DatabaseHosts::init(&config);
+
+        assert_eq!(hosts.select().await.unwrap().host, config.host);
+        assert!(hosts.selected.lock().await.is_none());
+    }
+}
@@ -0,0 +1,426 @@
+use crate::{
+    config::LimitsConfig,
+    error::{Error, LimitError},
+    log::CONTEXT,
+    prometheus::{LIMIT, LIMITS_REJECTED_TOTAL},
+};
+use arc_swap::ArcSwap;
+use metrics::{counter, gauge};
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+use tracing::debug;
+
+/// Idle keyset buckets are pruned once there are more than this many
+const MAX_IDLE_BUCKETS: usize = 1024;
+
+///
+/// Connection and rate limits shared by every connection
+///
+/// Created once, and updated in place when the configuration is reloaded,
+/// so that connections opened before and after a reload are counted together.
+///
+#[derive(Clone, Debug, Default)]
+pub struct Limits {
+    inner: Arc<LimitsState>,
+}
+
+#[derive(Debug, Default)]
+struct LimitsState {
+    config: ArcSwap<LimitsConfig>,
+    connections: Mutex<Connections>,
+    statements: Mutex<HashMap<String, TokenBucket>>,
+    values: Mutex<HashMap<String, TokenBucket>>,
+}
+
+#[derive(Debug, Default)]
+struct Connections {
+    total: usize,
+    roles: HashMap<String, usize>,
+}
+
+///
+/// Holds a connection against the connection limits until dropped
+///
+/// The connection is counted against the role once the role has authenticated.
+///
+#[derive(Debug)]
+pub struct ConnectionPermit {
+    limits: Limits,
+    role: Option<String>,
+}
+
+///
+/// Refills at `rate` per second, up to a burst of one second
+///
+/// A batch is let through while any tokens remain, and may overdraw the bucket.
+/// Otherwise a batch larger than the rate could never be let through.
+///
+#[derive(Debug)]
+struct TokenBucket {
+    tokens: f64,
+    updated_at: Instant,
+}
+
+impl Limits {
+    pub fn new(config: &LimitsConfig) -> Self {
+        let limits = Limits::default();
+        limits.update(config);
+        limits
+    }
+
+    pub fn update(&self, config: &LimitsConfig) {
+        gauge!(LIMIT, "limit" => "connections").set(config.max_connections as f64);
+        gauge!(LIMIT, "limit" => "role_connections").set(config.max_connections_per_role as f64);
+        gauge!(LIMIT, "limit" => "statements").set(config.statements_per_second_per_keyset as f64);
+        gauge!(LIMIT, "limit" => "values").set(config.values_per_second_per_keyset as f64);
+
+        self.inner.config.store(Arc::new(config.clone()));
+    }
+
+    ///
+    /// Counts a connection, unless the total connection limit has been reached
+    ///
+    /// Connections are counted even when unlimited, so that a limit applied on reload includes them.
+    /// The connection is counted against its role by [`ConnectionPermit::acquire_role`], once the role has
+    /// authenticated.
+    ///
+    pub fn acquire_connection(&self) -> Result<ConnectionPermit, Error> {
+        let config = self.inner.config.load();
+        let mut connections = self.connections();
+
+        if config.max_connections > 0 && connections.total >= config.max_connections {
+            counter!(LIMITS_REJECTED_TOTAL, "limit" => "connections").increment(1);
+            return Err(LimitError::TooManyConnections {
+                limit: config.max_connections,
+            }
+            .into());
+        }
+
+        connections.total += 1;
+
+        debug!(target: CONTEXT, msg = "Connection acquired", connections = connections.total);
+
+        Ok(ConnectionPermit {
+            limits: self.clone(),
+            role: None,
+        })
+    }
+
+    fn connections(&self) -> std::sync::MutexGuard<'_, Connections> {
+        self.inner
+            .connections
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+    }
+
+    ///
+    /// Counts `count` statements against the statement rate limit of the keyset
+    ///
+    pub fn check_statements(&self, keyset: &str, count: usize) -> Result<(), Error> {
+        let limit = self.inner.config.load().statements_per_second_per_keyset;
+
+        if take(&self.inner.statements, limit, keyset, count) {
+            return Ok(());
+        }
+
+        counter!(LIMITS_REJECTED_TOTAL, "limit" => "statements").increment(1);
+        Err(LimitError::StatementRateLimitExceeded {
+            keyset: keyset.to_string(),
+            limit,
+        }
+        .into())
+    }
+
+    ///
+    /// Counts `count` values against the encrypted value rate limit of the keyset
+    ///
+    pub fn check_values(&self, keyset: &str, count: usize) -> Result<(), Error> {
+        let limit = self.inner.config.load().values_per_second_per_keyset;
+
+        if take(&self.inner.values, limit, keyset, count) {
+            return Ok(());
+        }
+
+        counter!(LIMITS_REJECTED_TOTAL, "limit" => "values").increment(1);
+        Err(LimitError::ValueRateLimitExceeded {
+            keyset: keyset.to_string(),
+            limit,
+        }
+        .into())
+    }
+}
+
+///
+/// Takes `count` tokens from the bucket for the keyset, returning false if the bucket is empty
+///
+fn take(
+    buckets: &Mutex<HashMap<String, TokenBucket>>,
+    limit: u32,
+    keyset: &str,
+    count: usize,
+) -> bool {
+    if limit == 0 || count == 0 {
+        return true;
+    }
+
+    let now = Instant::now();
+    let mut buckets = buckets.lock().unwrap_or_else(|err| err.into_inner());
+
+    if buckets.len() > MAX_IDLE_BUCKETS {
+        buckets.retain(|_, bucket| now.duration_since(bucket.updated_at) < Duration::from_secs(1));
+    }
+
+    let bucket = buckets
+        .entry(keyset.to_string())
+        .or_insert_with(|| TokenBucket::new(limit, now));
+
+    bucket.take(limit, count, now)
+}
+
+impl TokenBucket {
+    fn new(rate: u32, now: Instant) -> Self {
+        TokenBucket {
+            tokens: rate as f64,
+            updated_at: now,
+        }
+    }
+
+    fn take(&mut self, rate: u32, count: usize, now: Instant) -> bool {
+        let rate = rate as f64;
+        let elapsed = now.saturating_duration_since(self.updated_at);
+
+        self.tokens = (self.tokens + elapsed.as_secs_f64() * rate).min(rate);
+        self.updated_at = now;
+
+        if self.tokens < 1.0 {
+            return false;
+        }
+
+        self.tokens -= count as f64;
+        true
+    }
+}
+
+impl ConnectionPermit {
+    ///
+    /// Counts the connection for `role`, unless the per-role connection limit has been reached
+    ///
+    /// Called once the role has authenticated, as Postgres does, so that a client that cannot authenticate as a role
+    /// cannot use up the connections of the role.
+    ///
+    pub fn acquire_role(&mut self, role: &str) -> Result<(), Error> {
+        let config = self.limits.inner.config.load();
+        let mut connections = self.limits.connections();
+
+        let role_connections = connections.roles.get(role).copied().unwrap_or_default();
+        if config.max_connections_per_role > 0
+            && role_connections >= config.max_connections_per_role
+        {
+            counter!(LIMITS_REJECTED_TOTAL, "limit" => "role_connections").increment(1);
+            return Err(LimitError::TooManyRoleConnections {
+                role: role.to_string(),
+                limit: config.max_connections_per_role,
+            }
+            .into());
+        }
+
+        *connections.roles.entry(role.to_string()).or_default() += 1;
+
+        debug!(target: CONTEXT, msg = "Role connection acquired", role, connections = role_connections + 1);
+
+        self.role = Some(role.to_string());
+        Ok(())
+    }
+}
+
+impl Drop for ConnectionPermit {
+    fn drop(&mut self) {
+        let mut connections = self.limits.connections();
+
+        connections.total = connections.total.saturating_sub(1);
+
+        let Some(role) = &self.role else {
+            return;
+        };
+        if let Some(count) = connections.roles.get_mut(role) {
+            *count = count.saturating_sub(1);
+            if *count == 0 {
+                connections.roles.remove(role);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ConnectionPermit, Limits, TokenBucket};
+    use crate::{
+        config::LimitsConfig,
+        error::{Error, LimitError},
+    };
+    use std::time::{Duration, Instant};
+
+    /// Acquires a connection that has authenticated as `role`
+    fn acquire(limits: &Limits, role: &str) -> Result<ConnectionPermit, Error> {
+        let mut permit = limits.acquire_connection()?;
+        permit.acquire_role(role)?;
+        Ok(permit)
+    }
+
+    #[test]
+    fn unlimited_by_default() {
+        let limits = Limits::new(&LimitsConfig::default());
+
+        let permits = (0..100)
+            .map(|_| acquire(&limits, "app").unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(permits.len(), 100);
+
+        for _ in 0..100 {
+            limits.check_statements("default", 1).unwrap();
+            limits.check_values("default", 1000).unwrap();
+        }
+    }
+
+    #[test]
+    fn connection_limit() {
+        let limits = Limits::new(&LimitsConfig {
+            max_connections: 2,
+            ..Default::default()
+        });
+
+        let first = limits.acquire_connection().unwrap();
+        let _second = limits.acquire_connection().unwrap();
+
+        assert!(matches!(
+            limits.acquire_connection(),
+            Err(Error::Limit(LimitError::TooManyConnections { limit: 2 }))
+        ));
+
+        drop(first);
+        limits.acquire_connection().unwrap();
+    }
+
+    #[test]
+    fn connection_limit_per_role() {
+        let limits = Limits::new(&LimitsConfig {
+            max_connections_per_role: 1,
+            ..Default::default()
+        });
+
+        let app = acquire(&limits, "app").unwrap();
+        let _reporting = acquire(&limits, "reporting").unwrap();
+
+        assert!(matches!(
+            acquire(&limits, "app"),
+            Err(Error::Limit(LimitError::TooManyRoleConnections { ref role, limit: 1 })) if role == "app"
+        ));
+
+        drop(app);
+        acquire(&limits, "app").unwrap();
+    }
+
+    #[test]
+    fn connections_that_have_not_authenticated_do_not_count_against_a_role() {
+        let limits = Limits::new(&LimitsConfig {
+            max_connections_per_role: 1,
+            ..Default::default()
+        });
+
+        // Clients that have not authenticated as the role yet
+        let _unauthenticated = (0..10)
+            .map(|_| limits.acquire_connection().unwrap())
+            .collect::<Vec<_>>();
+
+        let _app = acquire(&limits, "app").unwrap();
+    }
+
+    #[test]
+    fn refused_role_is_not_counted() {
+        let limits = Limits::new(&LimitsConfig {
+            max_connections_per_role: 1,
+            ..Default::default()
+        });
+
+        let app = acquire(&limits, "app").unwrap();
+
+        let mut refused = limits.acquire_connection().unwrap();
+        assert!(refused.acquire_role("app").is_err());
+        drop(refused);
+
+        drop(app);
+        acquire(&limits, "app").unwrap();
+    }
+
+    #[test]
+    fn connection_limit_applies_to_existing_connections_on_update() {
+        let limits = Limits::new(&LimitsConfig::default());
+
+        let _first = acquire(&limits, "app").unwrap();
+
+        limits.update(&LimitsConfig {
+            max_connections: 1,
+            ..Default::default()
+        });
+
+        assert!(limits.acquire_connection().is_err());
+    }
+
+    #[test]
+    fn statement_rate_limit_per_keyset() {
+        let limits = Limits::new(&LimitsConfig {
+            statements_per_second_per_keyset: 2,
+            ..Default::default()
+        });
+
+        limits.check_statements("default", 1).unwrap();
+        limits.check_statements("default", 1).unwrap();
+
+        assert!(matches!(
+            limits.check_statements("default", 1),
+            Err(Error::Limit(LimitError::StatementRateLimitExceeded {
+                limit: 2,
+                ..
+            }))
+        ));
+
+        limits.check_statements("tenant", 1).unwrap();
+    }
+
+    #[test]
+    fn value_rate_limit_lets_a_large_batch_overdraw() {
+        let limits = Limits::new(&LimitsConfig {
+            values_per_second_per_keyset: 10,
+            ..Default::default()
+        });
+
+        limits.check_values("default", 50).unwrap();
+
+        assert!(matches!(
+            limits.check_values("default", 1),
+            Err(Error::Limit(LimitError::ValueRateLimitExceeded {
+                limit: 10,
+                ..
+            }))
+        ));
+    }
+
+    #[test]
+    fn token_bucket_refills() {
+        let now = Instant::now();
+        let mut bucket = TokenBucket::new(10, now);
+
+        assert!(bucket.take(10, 10, now));
+        assert!(!bucket.take(10, 1, now));
+
+        assert!(bucket.take(10, 1, now + Duration::from_millis(100)));
+        assert!(!bucket.take(10, 1, now + Duration::from_millis(100)));
+
+        // The bucket holds at most one second of tokens
+        assert!(bucket.take(10, 10, now + Duration::from_secs(60)));
+        assert!(!bucket.take(10, 1, now + Duration::from_secs(60)));
+    }
+}
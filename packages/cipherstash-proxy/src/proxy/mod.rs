@@ -6,6 +6,7 @@ use crate::{
     error::Error,
     postgresql::{Column, Context, KeysetIdentifier},
     proxy::{encrypt_config::EncryptConfigManager, schema::SchemaManager},
+    tls::ServerTlsManager,
 };
 use cipherstash_client::encryption::Plaintext;
 use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
@@ -13,10 +14,16 @@ use tokio::sync::oneshot::Sender;
 use tracing::{debug, warn};
 
 mod encrypt_config;
+mod hosts;
+mod limits;
+mod replicas;
 mod schema;
 mod zerokms;
 
 pub use encrypt_config::EncryptConfig;
+pub use hosts::DatabaseHosts;
+pub use limits::{ConnectionPermit, Limits};
+pub use replicas::{ReplicaLease, ReplicaPool};
 pub use zerokms::ZeroKms;
 
 pub type ReloadSender = UnboundedSender<ReloadCommand>;
@@ -31,6 +38,16 @@ pub type ReloadResponder = Sender<bool>;
 /// single schema load — EQL v3 columns are self-configuring domain types.
 const SCHEMA_QUERY: &str = include_str!("./sql/select_table_schemas.sql");
 
+/// SQL Statement for loading view definitions as part of database schema.
+///
+/// View column types are not read from the catalog: they are derived by type
+/// checking each definition against the loaded tables.
+const VIEW_QUERY: &str = include_str!("./sql/select_view_schemas.sql");
+
+/// SQL Statement for loading user-declared function and operator signatures
+/// as part of database schema.
+const SQL_DECLS_QUERY: &str = include_str!("./sql/select_sql_decls.sql");
+
 /// SQL Statement for loading aggregates as part of database schema
 const AGGREGATE_QUERY: &str = include_str!("./sql/select_aggregates.sql");
 
@@ -49,6 +66,12 @@ pub struct Proxy {
     pub schema_manager: SchemaManager,
     /// The EQL version installed in the database or `None` if it was not present
     pub eql_version: Option<String>,
+    /// The listener TLS configuration or `None` if TLS is not configured
+    pub server_tls: Option<ServerTlsManager>,
+    /// The database host used for client connections
+    pub hosts: DatabaseHosts,
+    /// The read replicas, empty if none are configured
+    pub replicas: ReplicaPool,
     zerokms: ZeroKms,
     reload_sender: ReloadSender,
 }
@@ -67,6 +90,18 @@ impl Proxy {
 
         let eql_version = Proxy::eql_version(&config).await?;
 
+        let server_tls = match &config.tls {
+            Some(tls) => Some(ServerTlsManager::init(
+                tls,
+                config.server.tls_reload_interval(),
+            )?),
+            None => None,
+        };
+
+        let hosts = DatabaseHosts::init(&config.database);
+
+        let replicas = ReplicaPool::init(&config.database)?;
+
         let (reload_sender, reload_receiver) = mpsc::unbounded_channel();
 
         Proxy::receive(
@@ -81,6 +116,9 @@ impl Proxy {
             encrypt_config_manager,
             schema_manager,
             eql_version,
+            server_tls,
+            hosts,
+            replicas,
             reload_sender,
         })
     }
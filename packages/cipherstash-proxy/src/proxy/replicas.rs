@@ -0,0 +1,367 @@
+use crate::{
+    config::{DatabaseConfig, ReplicaBalancing},
+    error::Error,
+    log::DEVELOPMENT,
+    tls,
+};
+use std::{
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc, Weak,
+    },
+    time::Duration,
+};
+use tracing::{debug, info, warn};
+
+const HEALTH_CHECK_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How far the replica is behind the primary, in milliseconds
+///
+/// A replica that has replayed everything it has received is not behind, as `pg_last_xact_replay_timestamp`
+/// stops advancing while the primary is idle. NULL when the replica has not replayed a transaction since it started.
+const REPLICATION_LAG_MS: &str = "SELECT CASE
+    WHEN NOT pg_is_in_recovery() THEN 0
+    WHEN pg_last_wal_receive_lsn() = pg_last_wal_replay_lsn() THEN 0
+    ELSE (EXTRACT(EPOCH FROM now() - pg_last_xact_replay_timestamp()) * 1000)::bigint
+END";
+
+///
+/// The read replicas of the database
+///
+/// Replicas are health checked on an interval, and only available replicas are selected for new connections.
+/// A replica is available when it is healthy and is no more than `database.replica_max_lag_ms` behind the primary.
+/// A replica starts as unhealthy, and is used once the first health check succeeds.
+///
+#[derive(Clone, Debug)]
+pub struct ReplicaPool {
+    replicas: Arc<[Arc<Replica>]>,
+    balancing: ReplicaBalancing,
+    next: Arc<AtomicUsize>,
+}
+
+#[derive(Debug)]
+pub struct Replica {
+    pub config: DatabaseConfig,
+    healthy: AtomicBool,
+    lagging: AtomicBool,
+    connections: AtomicUsize,
+}
+
+///
+/// A replica selected for a client connection
+///
+/// The replica counts as connected until the lease is dropped.
+///
+#[derive(Debug)]
+pub struct ReplicaLease {
+    replica: Arc<Replica>,
+}
+
+impl ReplicaPool {
+    pub fn init(config: &DatabaseConfig) -> Result<Self, Error> {
+        let replicas: Arc<[Arc<Replica>]> = config
+            .replica_configs()?
+            .into_iter()
+            .map(|config| Arc::new(Replica::new(config)))
+            .collect();
+
+        if !replicas.is_empty() {
+            info!(
+                msg = "Routing read-only statements to replicas",
+                replicas = ?config.replicas,
+                balancing = ?config.replica_balancing,
+            );
+            init_health_check(
+                Arc::downgrade(&replicas),
+                config.replica_health_check_interval(),
+            );
+        }
+
+        Ok(ReplicaPool {
+            replicas,
+            balancing: config.replica_balancing,
+            next: Arc::new(AtomicUsize::new(0)),
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.replicas.is_empty()
+    }
+
+    ///
+    /// Select an available replica, or `None` if there are none
+    ///
+    pub fn select(&self) -> Option<ReplicaLease> {
+        let healthy = self
+            .replicas
+            .iter()
+            .filter(|replica| replica.is_available())
+            .collect::<Vec<_>>();
+
+        if healthy.is_empty() {
+            return None;
+        }
+
+        let replica = match self.balancing {
+            ReplicaBalancing::RoundRobin => {
+                let next = self.next.fetch_add(1, Ordering::Relaxed);
+                healthy[next % healthy.len()]
+            }
+            ReplicaBalancing::LeastConnections => healthy
+                .iter()
+                .min_by_key(|replica| replica.connections.load(Ordering::Relaxed))
+                .copied()?,
+        };
+
+        Some(ReplicaLease::new(replica.clone()))
+    }
+}
+
+impl Replica {
+    fn new(config: DatabaseConfig) -> Self {
+        Replica {
+            config,
+            healthy: AtomicBool::new(false),
+            lagging: AtomicBool::new(false),
+            connections: AtomicUsize::new(0),
+        }
+    }
+
+    pub fn is_healthy(&self) -> bool {
+        self.healthy.load(Ordering::Relaxed)
+    }
+
+    ///
+    /// Returns true if the replica is healthy and has not fallen behind the primary
+    ///
+    pub fn is_available(&self) -> bool {
+        self.is_healthy() && !self.lagging.load(Ordering::Relaxed)
+    }
+
+    ///
+    /// Records how far the replica is behind the primary, `None` if it is not known
+    ///
+    /// A replica with an unknown lag is treated as lagging.
+    ///
+    fn set_lag(&self, lag: Option<Duration>) {
+        let lagging = lag.is_none_or(|lag| lag > self.config.replica_max_lag());
+        let previous = self.lagging.swap(lagging, Ordering::Relaxed);
+        if previous != lagging {
+            match lagging {
+                true => warn!(
+                    msg = "Replica is lagging, reads use the primary",
+                    replica = self.address(),
+                    ?lag
+                ),
+                false => info!(
+                    msg = "Replica has caught up",
+                    replica = self.address(),
+                    ?lag
+                ),
+            }
+        }
+    }
+
+    fn set_healthy(&self, healthy: bool) {
+        let previous = self.healthy.swap(healthy, Ordering::Relaxed);
+        if previous != healthy {
+            match healthy {
+                true => info!(msg = "Replica is healthy", replica = self.address()),
+                false => warn!(msg = "Replica is unhealthy", replica = self.address()),
+            }
+        }
+    }
+
+    pub fn address(&self) -> String {
+        self.config.to_socket_address()
+    }
+}
+
+impl ReplicaLease {
+    fn new(replica: Arc<Replica>) -> Self {
+        replica.connections.fetch_add(1, Ordering::Relaxed);
+        ReplicaLease { replica }
+    }
+
+    pub fn config(&self) -> &DatabaseConfig {
+        &self.replica.config
+    }
+
+    pub fn address(&self) -> String {
+        self.replica.address()
+    }
+
+    ///
+    /// Returns true if reads can use the replica
+    ///
+    /// Checked for every statement, so a connection stops reading from a replica that falls behind or fails
+    /// its health check, and reads from it again once it recovers.
+    ///
+    pub fn is_available(&self) -> bool {
+        self.replica.is_available()
+    }
+
+    ///
+    /// Stop selecting the replica until the next successful health check
+    ///
+    pub fn mark_unhealthy(&self) {
+        self.replica.set_healthy(false);
+    }
+
+    #[cfg(test)]
+    pub fn for_testing() -> Self {
+        let replica = Replica::new(DatabaseConfig::for_testing());
+        replica.set_healthy(true);
+        ReplicaLease::new(Arc::new(replica))
+    }
+
+    #[cfg(test)]
+    pub fn set_lag(&self, lag: Option<Duration>) {
+        self.replica.set_lag(lag);
+    }
+}
+
+impl Drop for ReplicaLease {
+    fn drop(&mut self) {
+        self.replica.connections.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+///
+/// Checks each replica on an interval
+///
+/// The task holds a weak reference, and stops once the pool has been dropped (for example when the configuration is reloaded).
+///
+fn init_health_check(replicas: Weak<[Arc<Replica>]>, interval: Duration) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(interval);
+
+        loop {
+            interval.tick().await;
+
+            let Some(replicas) = replicas.upgrade() else {
+                debug!(target: DEVELOPMENT, msg = "Replica pool released, stopping health check");
+                return;
+            };
+
+            for replica in replicas.iter() {
+                let healthy = match tokio::time::timeout(
+                    HEALTH_CHECK_TIMEOUT,
+                    health_check(&replica.config),
+                )
+                .await
+                {
+                    Ok(Ok(lag)) => {
+                        replica.set_lag(lag);
+                        true
+                    }
+                    Ok(Err(err)) => {
+                        debug!(target: DEVELOPMENT, msg = "Replica health check failed", replica = replica.address(), error = err.to_string());
+                        false
+                    }
+                    Err(_) => {
+                        debug!(target: DEVELOPMENT, msg = "Replica health check timed out", replica = replica.address());
+                        false
+                    }
+                };
+                replica.set_healthy(healthy);
+            }
+        }
+    });
+}
+
+///
+/// Connects to the replica and returns how far it is behind the primary, `None` if it is not known
+///
+async fn health_check(config: &DatabaseConfig) -> Result<Option<Duration>, Error> {
+    let tls_config = tls::configure_client(config)?;
+    let tls = tokio_postgres_rustls::MakeRustlsConnect::new(tls_config);
+
+    let (client, connection) = config.to_connection_config().connect(tls).await?;
+    tokio::spawn(connection);
+
+    let row = client.query_one(REPLICATION_LAG_MS, &[]).await?;
+    let lag = row
+        .try_get::<_, Option<i64>>(0)?
+        .map(|lag_ms| Duration::from_millis(lag_ms.max(0) as u64));
+
+    Ok(lag)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pool(balancing: ReplicaBalancing) -> ReplicaPool {
+        let mut config = DatabaseConfig::for_testing();
+        config.replicas = vec!["replica-1".to_string(), "replica-2".to_string()];
+        config.replica_balancing = balancing;
+
+        let replicas: Arc<[Arc<Replica>]> = config
+            .replica_configs()
+            .unwrap()
+            .into_iter()
+            .map(|config| {
+                let replica = Replica::new(config);
+                replica.set_healthy(true);
+                Arc::new(replica)
+            })
+            .collect();
+
+        ReplicaPool {
+            replicas,
+            balancing,
+            next: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+
+    #[test]
+    fn round_robin_skips_unhealthy_replicas() {
+        let pool = pool(ReplicaBalancing::RoundRobin);
+
+        let first = pool.select().unwrap();
+        let second = pool.select().unwrap();
+        assert_eq!(first.config().host, "replica-1");
+        assert_eq!(second.config().host, "replica-2");
+
+        first.mark_unhealthy();
+        assert_eq!(pool.select().unwrap().config().host, "replica-2");
+        assert_eq!(pool.select().unwrap().config().host, "replica-2");
+
+        second.mark_unhealthy();
+        assert!(pool.select().is_none());
+    }
+
+    #[test]
+    fn least_connections_selects_least_used_replica() {
+        let pool = pool(ReplicaBalancing::LeastConnections);
+
+        let first = pool.select().unwrap();
+        assert_eq!(first.config().host, "replica-1");
+
+        let second = pool.select().unwrap();
+        assert_eq!(second.config().host, "replica-2");
+
+        drop(first);
+        assert_eq!(pool.select().unwrap().config().host, "replica-1");
+    }
+
+    #[test]
+    fn lagging_replicas_are_not_selected() {
+        let pool = pool(ReplicaBalancing::RoundRobin);
+
+        let first = pool.select().unwrap();
+        assert!(first.is_available());
+
+        first.set_lag(Some(Duration::from_secs(60)));
+        assert!(!first.is_available());
+        assert_eq!(pool.select().unwrap().config().host, "replica-2");
+        assert_eq!(pool.select().unwrap().config().host, "replica-2");
+
+        // An unknown lag is treated as lagging
+        first.set_lag(Some(Duration::ZERO));
+        assert!(first.is_available());
+        first.set_lag(None);
+        assert!(!first.is_available());
+    }
+}
@@ -1,11 +1,16 @@
 use super::eql_domains;
 use crate::config::DatabaseConfig;
-use crate::error::Error;
-use crate::proxy::{AGGREGATE_QUERY, SCHEMA_QUERY};
+use crate::error::{Error, MappingError};
+use crate::proxy::{AGGREGATE_QUERY, SCHEMA_QUERY, SQL_DECLS_QUERY, VIEW_QUERY};
 use crate::{connect, log::SCHEMA};
 use arc_swap::ArcSwap;
-use eql_mapper::{Column, Schema, Table};
-use sqltk::parser::ast::Ident;
+use eql_mapper::{
+    Column, Projection, ProjectionColumn, Schema, SqlDecls, SqlObject, Table, TableResolver, View,
+};
+use sqltk::parser::ast::{Ident, Statement};
+use sqltk::parser::dialect::PostgreSqlDialect;
+use sqltk::parser::parser::Parser;
+use std::collections::HashSet;
 use std::sync::Arc;
 use std::time::Duration;
 use tokio::{task::JoinHandle, time};
@@ -190,12 +195,14 @@ pub async fn load_schema(config: &DatabaseConfig) -> Result<Schema, Error> {
     };
 
     for table in tables {
+        let table_schema: String = table.get("table_schema");
         let table_name: String = table.get("table_name");
         let columns: Vec<String> = table.get("columns");
         let column_type_names: Vec<Option<String>> = table.get("column_type_names");
         let column_domain_names: Vec<Option<String>> = table.get("column_domain_names");
 
         let mut table = Table::new(Ident::new(&table_name));
+        table.schema = Some(Ident::new(&table_schema));
 
         columns
             .iter()
@@ -215,6 +222,55 @@ pub async fn load_schema(config: &DatabaseConfig) -> Result<Schema, Error> {
         schema.add_table(table);
     }
 
+    // Declarations first: a view definition may call a declared function.
+    let declarations = client
+        .query(SQL_DECLS_QUERY, &[])
+        .await?
+        .into_iter()
+        .map(|row| {
+            let kind: String = row.get("kind");
+            let schema: String = row.get("schema");
+            let name: String = row.get("name");
+            let args = row.get::<_, i32>("args").max(0) as usize;
+            let visible: bool = row.get("visible");
+
+            let object = match kind.as_str() {
+                "operator" => SqlObject::Operator {
+                    schema,
+                    name,
+                    args,
+                    visible,
+                },
+                _ => SqlObject::Function {
+                    schema,
+                    name,
+                    args,
+                    visible,
+                },
+            };
+
+            Declaration {
+                object,
+                declaration: row.get("declaration"),
+            }
+        })
+        .collect::<Vec<_>>();
+
+    schema.sql_decls = Arc::new(load_sql_decls(&declarations));
+
+    let views = client
+        .query(VIEW_QUERY, &[])
+        .await?
+        .into_iter()
+        .map(|row| ViewDefinition {
+            name: row.get("table_name"),
+            definition: row.get("definition"),
+            columns: row.get("columns"),
+        })
+        .collect::<Vec<_>>();
+
+    load_views(&mut schema, views);
+
     let aggregates = client.query(AGGREGATE_QUERY, &[]).await?;
     schema.aggregates = aggregates
         .into_iter()
@@ -227,6 +283,139 @@ pub async fn load_schema(config: &DatabaseConfig) -> Result<Schema, Error> {
     Ok(schema)
 }
 
+/// A signature declared in the comment on a function or operator.
+struct Declaration {
+    object: SqlObject,
+    declaration: String,
+}
+
+/// Parses the signatures declared in function and operator comments.
+///
+/// Each comment is parsed on its own, and must declare the object it is on, so
+/// a comment cannot change how another function is typed. One bad declaration
+/// only loses that comment's signature. The function it describes then falls
+/// back to native typing, which is how it was typed before declarations were
+/// supported.
+fn load_sql_decls(declarations: &[Declaration]) -> SqlDecls {
+    let mut decls = SqlDecls::default();
+
+    for Declaration {
+        object,
+        declaration,
+    } in declarations
+    {
+        let object_name = object.to_string();
+        match decls.extend_for_object(object, declaration) {
+            Ok(()) => {
+                debug!(target: SCHEMA, msg = "Declared SQL signature", object = object_name, declaration);
+            }
+            Err(err) => {
+                warn!(target: SCHEMA, msg = "Ignoring invalid eql-mapper declaration", object = object_name, declaration, error = err.to_string());
+            }
+        }
+    }
+
+    decls
+}
+
+/// A view as read from the catalog, before its column types are known.
+struct ViewDefinition {
+    name: String,
+    definition: String,
+    columns: Vec<String>,
+}
+
+/// Adds the views that type check against `schema` to it.
+///
+/// A view may select from another view, so views are derived in passes until a
+/// pass makes no progress. Whatever is left over either references something
+/// the type checker cannot resolve (e.g. a table outside the search path) or
+/// uses SQL it does not support. Those views are skipped with a warning:
+/// statements selecting from them fail to type check, exactly as they did
+/// before views were loaded.
+fn load_views(schema: &mut Schema, views: Vec<ViewDefinition>) {
+    let mut seen = HashSet::new();
+    // First wins, as for tables: the query returns views in search-path order.
+    let mut pending = views
+        .into_iter()
+        .filter(|view| seen.insert(view.name.clone()))
+        .collect::<Vec<_>>();
+
+    let mut errors = Vec::new();
+
+    loop {
+        let resolver = Arc::new(TableResolver::new_fixed(Arc::new(schema.clone())));
+        let before = pending.len();
+
+        errors.clear();
+        pending.retain(|view| match derive_view(resolver.clone(), view) {
+            Ok(derived) => {
+                debug!(target: SCHEMA, msg = "view", view = view.name, projection = %derived.projection);
+                schema.add_view(derived);
+                false
+            }
+            Err(err) => {
+                errors.push(err.to_string());
+                true
+            }
+        });
+
+        if pending.is_empty() || pending.len() == before {
+            break;
+        }
+    }
+
+    for (view, error) in pending.iter().zip(errors) {
+        warn!(target: SCHEMA, msg = "View could not be type checked. Statements selecting from it will not be mapped.", view = view.name, error);
+    }
+}
+
+/// Derives a view's projection by type checking its definition.
+///
+/// The projection columns are renamed to the names the catalog reports, which
+/// are the names a statement selecting from the view uses. Their types are left
+/// alone, so an encrypted view column keeps the identity of the base column it
+/// was selected from.
+fn derive_view(resolver: Arc<TableResolver>, view: &ViewDefinition) -> Result<View, Error> {
+    let statement = Parser::new(&PostgreSqlDialect {})
+        .try_with_sql(&view.definition)?
+        .parse_statement()?;
+
+    if !matches!(statement, Statement::Query(_)) {
+        return Err(MappingError::InvalidSqlStatement(format!(
+            "view definition is not a query: {statement}"
+        ))
+        .into());
+    }
+
+    let typed = eql_mapper::type_check(resolver, &statement).map_err(MappingError::from)?;
+
+    if typed.projection.len() != view.columns.len() {
+        return Err(MappingError::InvalidSqlStatement(format!(
+            "view has {} columns but its definition projects {}",
+            view.columns.len(),
+            typed.projection.len()
+        ))
+        .into());
+    }
+
+    let columns = typed
+        .projection
+        .columns()
+        .iter()
+        .zip(&view.columns)
+        .map(|(col, name)| ProjectionColumn {
+            ty: col.ty.clone(),
+            alias: Some(Ident::with_quote('"', name)),
+        })
+        .collect();
+
+    Ok(View {
+        name: Ident::new(&view.name),
+        projection: Projection::new(columns),
+    })
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -292,4 +481,150 @@ mod test {
             ColumnKind::Native
         );
     }
+
+    fn customers() -> Schema {
+        let mut schema = Schema::new("public");
+        let mut table = Table::new(Ident::new("customers"));
+        table.add_column(Arc::new(classify_column(
+            "customers",
+            "id",
+            Some("int4"),
+            None,
+        )));
+        table.add_column(Arc::new(classify_column(
+            "customers",
+            "email_enc",
+            Some("jsonb"),
+            Some("eql_v3_text_eq"),
+        )));
+        schema.add_table(table);
+        schema
+    }
+
+    fn view(name: &str, definition: &str, columns: &[&str]) -> ViewDefinition {
+        ViewDefinition {
+            name: name.to_string(),
+            definition: definition.to_string(),
+            columns: columns.iter().map(|c| c.to_string()).collect(),
+        }
+    }
+
+    fn view_column_is_eql(schema: &Schema, view: &str, index: usize) -> bool {
+        let view = schema
+            .views
+            .iter()
+            .find(|v| v.name.value == view)
+            .unwrap_or_else(|| panic!("view {view} was not loaded"));
+        view.projection.columns()[index].ty.contains_eql()
+    }
+
+    #[test]
+    fn view_columns_take_their_types_from_the_definition() {
+        let mut schema = customers();
+        load_views(
+            &mut schema,
+            vec![view(
+                "active_customers",
+                " SELECT customers.id,\n    customers.email_enc AS email\n   FROM customers;",
+                &["id", "email"],
+            )],
+        );
+
+        assert!(!view_column_is_eql(&schema, "active_customers", 0));
+        assert!(view_column_is_eql(&schema, "active_customers", 1));
+        assert_eq!(
+            schema.views[0].projection.columns()[1].alias,
+            Some(Ident::with_quote('"', "email"))
+        );
+    }
+
+    #[test]
+    fn views_over_views_load_regardless_of_catalog_order() {
+        let mut schema = customers();
+        load_views(
+            &mut schema,
+            vec![
+                view(
+                    "outer_view",
+                    "SELECT email_enc FROM inner_view",
+                    &["email_enc"],
+                ),
+                view(
+                    "inner_view",
+                    "SELECT id, email_enc FROM customers",
+                    &["id", "email_enc"],
+                ),
+            ],
+        );
+
+        assert_eq!(schema.views.len(), 2);
+        assert!(view_column_is_eql(&schema, "outer_view", 0));
+    }
+
+    #[test]
+    fn views_that_do_not_type_check_are_skipped() {
+        let mut schema = customers();
+        load_views(
+            &mut schema,
+            vec![
+                view("elsewhere", "SELECT id FROM other.customers", &["id"]),
+                view("ok", "SELECT id FROM customers", &["id"]),
+            ],
+        );
+
+        assert_eq!(schema.views.len(), 1);
+        assert_eq!(schema.views[0].name.value, "ok");
+    }
+
+    fn declaration(schema: &str, name: &str, args: usize, declaration: &str) -> Declaration {
+        Declaration {
+            object: SqlObject::Function {
+                schema: schema.to_string(),
+                name: name.to_string(),
+                args,
+                visible: true,
+            },
+            declaration: declaration.to_string(),
+        }
+    }
+
+    #[test]
+    fn invalid_declarations_are_skipped() {
+        let decls = load_sql_decls(&[
+            declaration(
+                "app",
+                "normalize_email",
+                1,
+                " app.normalize_email<T>(T) -> T where T: Eq",
+            ),
+            declaration("app", "broken", 1, " app.broken<T>(T) ->"),
+            declaration("pg_catalog", "min", 1, " pg_catalog.min<T>(T) -> Native"),
+            // A comment on one function declaring another
+            declaration("app", "audit", 1, " app.decrypt<T>(T) -> Native"),
+        ]);
+
+        assert_eq!(decls.len(), 1);
+    }
+
+    #[test]
+    fn views_may_call_declared_functions() {
+        let mut schema = customers();
+        schema.sql_decls = Arc::new(load_sql_decls(&[declaration(
+            "app",
+            "lower",
+            1,
+            "app.lower<T>(T) -> T where T: Eq",
+        )]));
+
+        load_views(
+            &mut schema,
+            vec![view(
+                "normalised",
+                "SELECT app.lower(email_enc) AS email FROM customers",
+                &["email"],
+            )],
+        );
+
+        assert!(view_column_is_eql(&schema, "normalised", 0));
+    }
 }
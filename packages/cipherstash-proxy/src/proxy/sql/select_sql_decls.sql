@@ -0,0 +1,43 @@
+-- Type signatures for user-defined functions and operators, declared in their
+-- comments in the `functions!` / `binary_operators!` syntax, e.g.
+--
+--   COMMENT ON FUNCTION app.normalize_email(jsonb)
+--       IS 'eql-mapper: app.normalize_email<T>(T) -> T where T: Eq';
+--
+-- Each declaration is returned with the function or operator the comment is on,
+-- so it can be checked against the object it declares. `visible` is true when
+-- the object can be used without its schema with the current search_path.
+-- Only the text after the `eql-mapper:` prefix is returned.
+SELECT
+    'function' AS kind,
+    n.nspname AS schema,
+    p.proname AS name,
+    p.pronargs::int4 AS args,
+    pg_function_is_visible(p.oid) AS visible,
+    substr(d.description, length('eql-mapper:') + 1) AS declaration
+FROM
+    pg_description d
+    JOIN pg_proc p ON d.objoid = p.oid
+    JOIN pg_namespace n ON p.pronamespace = n.oid
+WHERE
+    d.classoid = 'pg_proc'::regclass
+    AND d.objsubid = 0
+    AND d.description LIKE 'eql-mapper:%'
+UNION ALL
+SELECT
+    'operator' AS kind,
+    n.nspname AS schema,
+    o.oprname AS name,
+    CASE o.oprkind WHEN 'b' THEN 2 ELSE 1 END AS args,
+    pg_operator_is_visible(o.oid) AS visible,
+    substr(d.description, length('eql-mapper:') + 1) AS declaration
+FROM
+    pg_description d
+    JOIN pg_operator o ON d.objoid = o.oid
+    JOIN pg_namespace n ON o.oprnamespace = n.oid
+WHERE
+    d.classoid = 'pg_operator'::regclass
+    AND d.objsubid = 0
+    AND d.description LIKE 'eql-mapper:%'
+ORDER BY
+    kind, schema, name;
@@ -0,0 +1,24 @@
+SELECT
+    v.table_schema,
+    v.table_name,
+    -- `information_schema.views.view_definition` is NULL unless the current
+    -- role owns the view; `pg_get_viewdef` is not restricted that way. Tables
+    -- outside the search path are schema-qualified in the output, which the
+    -- type checker does not resolve, so such views are skipped by the loader.
+    pg_get_viewdef(format('%I.%I', v.table_schema, v.table_name)::regclass) AS definition,
+    array_agg(c.column_name ORDER BY c.ordinal_position)::text[] AS columns
+FROM
+    information_schema.views v
+JOIN
+    information_schema.columns c ON c.table_schema = v.table_schema
+                                AND c.table_name = v.table_name
+WHERE
+    -- Same scoping as select_table_schemas.sql: only the schemas this
+    -- connection resolves unqualified names in.
+    v.table_schema::text = ANY (current_schemas(false)::text[])
+GROUP BY
+    v.table_schema, v.table_name
+ORDER BY
+    -- Search-path order; consumers keep the first row for a name.
+    array_position(current_schemas(false)::text[], v.table_schema::text),
+    v.table_name;
@@ -0,0 +1,184 @@
+use crate::config::ServerConfig;
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use tokio::sync::Notify;
+
+///
+/// Gathers requests for the same keyset into batches, so that concurrent connections share a ZeroKMS call
+///
+/// The first request for a keyset opens a batch. Requests arriving within `window` join it,
+/// until it holds `max_values` values. The batch is closed when the window passes or it is full.
+///
+#[derive(Debug)]
+pub struct Coalescer<T> {
+    window: Duration,
+    max_values: usize,
+    open: Mutex<HashMap<String, Arc<OpenBatch<T>>>>,
+}
+
+#[derive(Debug)]
+pub struct OpenBatch<T> {
+    batch: Mutex<Batch<T>>,
+    full: Notify,
+}
+
+#[derive(Debug)]
+struct Batch<T> {
+    requests: Vec<T>,
+    values: usize,
+    closed: bool,
+}
+
+impl<T> Coalescer<T> {
+    pub fn new(config: &ServerConfig) -> Self {
+        Coalescer {
+            window: Duration::from_millis(config.zerokms_coalesce_window_ms),
+            max_values: config.zerokms_coalesce_max_values,
+            open: Mutex::new(HashMap::new()),
+        }
+    }
+
+    ///
+    /// Whether a request with `values` values is coalesced
+    /// Requests that fill a batch on their own are sent directly
+    ///
+    pub fn accepts(&self, values: usize) -> bool {
+        !self.window.is_zero() && values > 0 && values < self.max_values
+    }
+
+    ///
+    /// Adds the request to the open batch for `key`
+    ///
+    /// Returns the batch if the request opened a new one.
+    /// The caller is responsible for closing it with `close` and sending the batch.
+    ///
+    pub fn join(&self, key: &str, request: T, values: usize) -> Option<Arc<OpenBatch<T>>> {
+        let mut open = self.open.lock().unwrap_or_else(|err| err.into_inner());
+
+        if let Some(batch) = open.get(key).cloned() {
+            let mut inner = batch.batch.lock().unwrap_or_else(|err| err.into_inner());
+
+            if !inner.closed && inner.values + values <= self.max_values {
+                inner.requests.push(request);
+                inner.values += values;
+
+                if inner.values >= self.max_values {
+                    inner.closed = true;
+                    open.remove(key);
+                    batch.full.notify_one();
+                }
+                return None;
+            }
+
+            // The request does not fit, send the batch now and open another
+            inner.closed = true;
+            open.remove(key);
+            batch.full.notify_one();
+        }
+
+        let batch = Arc::new(OpenBatch {
+            batch: Mutex::new(Batch {
+                requests: vec![request],
+                values,
+                closed: false,
+            }),
+            full: Notify::new(),
+        });
+        open.insert(key.to_string(), batch.clone());
+
+        Some(batch)
+    }
+
+    ///
+    /// Waits until the window has passed or the batch is full, and returns the requests in the batch
+    ///
+    pub async fn close(&self, key: &str, batch: &Arc<OpenBatch<T>>) -> Vec<T> {
+        tokio::select! {
+            _ = tokio::time::sleep(self.window) => {},
+            _ = batch.full.notified() => {},
+        }
+
+        {
+            let mut open = self.open.lock().unwrap_or_else(|err| err.into_inner());
+            if open.get(key).is_some_and(|open| Arc::ptr_eq(open, batch)) {
+                open.remove(key);
+            }
+        }
+
+        let mut inner = batch.batch.lock().unwrap_or_else(|err| err.into_inner());
+        inner.closed = true;
+        std::mem::take(&mut inner.requests)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Coalescer;
+    use crate::config::ServerConfig;
+    use std::time::{Duration, Instant};
+
+    fn coalescer(window_ms: u64, max_values: usize) -> Coalescer<usize> {
+        Coalescer::new(&ServerConfig {
+            zerokms_coalesce_window_ms: window_ms,
+            zerokms_coalesce_max_values: max_values,
+            ..ServerConfig::default()
+        })
+    }
+
+    #[test]
+    fn disabled_without_a_window() {
+        let coalescer = coalescer(0, 100);
+        assert!(!coalescer.accepts(1));
+
+        let coalescer = coalescer(2, 100);
+        assert!(coalescer.accepts(1));
+        assert!(!coalescer.accepts(0));
+        assert!(!coalescer.accepts(100));
+    }
+
+    #[tokio::test]
+    async fn requests_within_the_window_share_a_batch() {
+        let coalescer = coalescer(20, 100);
+
+        let batch = coalescer.join("default", 1, 1).unwrap();
+        assert!(coalescer.join("default", 2, 1).is_none());
+        assert!(coalescer.join("default", 3, 1).is_none());
+
+        // Another keyset opens its own batch
+        let other = coalescer.join("tenant", 4, 1).unwrap();
+
+        assert_eq!(coalescer.close("default", &batch).await, vec![1, 2, 3]);
+        assert_eq!(coalescer.close("tenant", &other).await, vec![4]);
+
+        // Closed batches are not joined
+        assert!(coalescer.join("default", 5, 1).is_some());
+    }
+
+    #[tokio::test]
+    async fn full_batch_is_closed_before_the_window() {
+        let coalescer = coalescer(10_000, 10);
+
+        let batch = coalescer.join("default", 1, 5).unwrap();
+        assert!(coalescer.join("default", 2, 5).is_none());
+
+        let start = Instant::now();
+        assert_eq!(coalescer.close("default", &batch).await, vec![1, 2]);
+        assert!(start.elapsed() < Duration::from_secs(1));
+    }
+
+    #[tokio::test]
+    async fn request_that_does_not_fit_opens_a_new_batch() {
+        let coalescer = coalescer(10_000, 10);
+
+        let first = coalescer.join("default", 1, 6).unwrap();
+        let second = coalescer.join("default", 2, 6).unwrap();
+
+        assert_eq!(coalescer.close("default", &first).await, vec![1]);
+
+        assert!(coalescer.join("default", 3, 4).is_none());
+        assert_eq!(coalescer.close("default", &second).await, vec![2, 3]);
+    }
+}
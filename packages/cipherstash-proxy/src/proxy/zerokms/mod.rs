@@ -1,3 +1,5 @@
+mod coalesce;
+mod resilience;
 #[allow(clippy::module_inception)]
 mod zerokms;
 
@@ -0,0 +1,450 @@
+use crate::{
+    config::ServerConfig,
+    error::{EncryptError, Error, ZeroKMSError},
+    log::ZEROKMS,
+    prometheus::{
+        ZEROKMS_CIRCUIT_BREAKER_OPEN, ZEROKMS_CIRCUIT_BREAKER_REJECTED_TOTAL, ZEROKMS_RETRIES_TOTAL,
+    },
+};
+use metrics::{counter, gauge};
+use rand::Rng;
+use std::{
+    future::Future,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+use tracing::{info, warn};
+
+///
+/// Retries ZeroKMS calls that fail with a transient error
+///
+/// The delay grows exponentially from `base_delay` up to `max_delay`, with full jitter.
+///
+#[derive(Clone, Copy, Debug)]
+pub struct RetryPolicy {
+    max_retries: u32,
+    base_delay: Duration,
+    max_delay: Duration,
+}
+
+///
+/// Fails ZeroKMS calls fast while ZeroKMS is unavailable
+///
+/// The circuit opens after `threshold` consecutive transient failures.
+/// Once `reset_timeout` has passed, a single call is let through. The circuit closes if it succeeds, and opens again if it fails.
+///
+#[derive(Debug)]
+pub struct CircuitBreaker {
+    threshold: u32,
+    reset_timeout: Duration,
+    state: Mutex<CircuitState>,
+}
+
+#[derive(Debug, Default)]
+struct CircuitState {
+    failures: u32,
+    open_until: Option<Instant>,
+    trial_in_flight: bool,
+}
+
+///
+/// The call let through while the circuit is half-open
+///
+/// Dropping the trial lets another call through, so a trial that is cancelled before it completes does not hold the
+/// circuit open.
+///
+struct Trial<'a> {
+    breaker: Option<&'a CircuitBreaker>,
+}
+
+impl Drop for Trial<'_> {
+    fn drop(&mut self) {
+        if let Some(breaker) = self.breaker {
+            let mut state = breaker.state.lock().unwrap_or_else(|err| err.into_inner());
+            state.trial_in_flight = false;
+        }
+    }
+}
+
+impl RetryPolicy {
+    pub fn new(config: &ServerConfig) -> Self {
+        RetryPolicy {
+            max_retries: config.zerokms_max_retries,
+            base_delay: Duration::from_millis(config.zerokms_retry_base_delay_ms),
+            max_delay: Duration::from_millis(config.zerokms_retry_max_delay_ms),
+        }
+    }
+
+    ///
+    /// The delay before the retry following `attempt`, with full jitter
+    ///
+    fn delay(&self, attempt: u32) -> Duration {
+        let max = self
+            .base_delay
+            .saturating_mul(2_u32.saturating_pow(attempt))
+            .min(self.max_delay);
+
+        let jitter_ms = rand::rng().random_range(0..=max.as_millis() as u64);
+        Duration::from_millis(jitter_ms)
+    }
+}
+
+impl CircuitBreaker {
+    pub fn new(config: &ServerConfig) -> Self {
+        CircuitBreaker {
+            threshold: config.zerokms_circuit_breaker_threshold,
+            reset_timeout: Duration::from_secs(config.zerokms_circuit_breaker_reset_seconds),
+            state: Mutex::new(CircuitState::default()),
+        }
+    }
+
+    fn is_enabled(&self) -> bool {
+        self.threshold > 0
+    }
+
+    ///
+    /// Returns an error if the circuit is open, and the trial if this call is let through a half-open circuit
+    ///
+    fn check(&self) -> Result<Trial<'_>, Error> {
+        let no_trial = Trial { breaker: None };
+
+        if !self.is_enabled() {
+            return Ok(no_trial);
+        }
+
+        let mut state = self.state.lock().unwrap_or_else(|err| err.into_inner());
+
+        let Some(open_until) = state.open_until else {
+            return Ok(no_trial);
+        };
+
+        let now = Instant::now();
+        if now >= open_until && !state.trial_in_flight {
+            state.trial_in_flight = true;
+            return Ok(Trial {
+                breaker: Some(self),
+            });
+        }
+
+        counter!(ZEROKMS_CIRCUIT_BREAKER_REJECTED_TOTAL).increment(1);
+        Err(ZeroKMSError::Unavailable {
+            retry_after_seconds: open_until.saturating_duration_since(now).as_secs(),
+        }
+        .into())
+    }
+
+    fn success(&self) {
+        if !self.is_enabled() {
+            return;
+        }
+
+        let mut state = self.state.lock().unwrap_or_else(|err| err.into_inner());
+        if state.open_until.is_some() {
+            info!(target: ZEROKMS, msg = "ZeroKMS circuit breaker closed");
+            gauge!(ZEROKMS_CIRCUIT_BREAKER_OPEN).set(0);
+        }
+        *state = CircuitState::default();
+    }
+
+    fn failure(&self) {
+        if !self.is_enabled() {
+            return;
+        }
+
+        let mut state = self.state.lock().unwrap_or_else(|err| err.into_inner());
+        state.failures += 1;
+
+        if state.trial_in_flight || state.failures >= self.threshold {
+            if state.open_until.is_none() {
+                warn!(
+                    target: ZEROKMS,
+                    msg = "ZeroKMS circuit breaker opened",
+                    failures = state.failures,
+                    reset_timeout_seconds = self.reset_timeout.as_secs()
+                );
+                gauge!(ZEROKMS_CIRCUIT_BREAKER_OPEN).set(1);
+            }
+            state.open_until = Some(Instant::now() + self.reset_timeout);
+            state.trial_in_flight = false;
+        }
+    }
+
+    ///
+    /// Calls ZeroKMS, retrying transient errors with the `RetryPolicy`
+    ///
+    /// Every attempt is gated by the circuit breaker, and only transient errors count as failures.
+    /// A permanent error is still a response from ZeroKMS, and only ends the trial of a half-open circuit.
+    ///
+    pub async fn call<T, F, Fut>(
+        &self,
+        retry: &RetryPolicy,
+        operation: &'static str,
+        mut f: F,
+    ) -> Result<T, Error>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T, Error>>,
+    {
+        let mut attempt = 0;
+
+        loop {
+            let trial = self.check()?;
+
+            let result = f().await;
+            match &result {
+                Ok(_) => self.success(),
+                Err(err) if is_transient(err) => self.failure(),
+                Err(_) => {}
+            }
+            drop(trial);
+
+            match result {
+                Ok(value) => return Ok(value),
+                Err(err) if is_transient(&err) => {
+                    if attempt >= retry.max_retries {
+                        return Err(err);
+                    }
+
+                    let delay = retry.delay(attempt);
+                    attempt += 1;
+
+                    warn!(
+                        target: ZEROKMS,
+                        msg = "Retrying ZeroKMS request",
+                        operation,
+                        attempt,
+                        delay_ms = delay.as_millis(),
+                        error = err.to_string()
+                    );
+                    counter!(ZEROKMS_RETRIES_TOTAL).increment(1);
+
+                    tokio::time::sleep(delay).await;
+                }
+                Err(err) => return Err(err),
+            }
+        }
+    }
+}
+
+///
+/// Errors that may succeed if retried
+///
+/// Decrypt and cipher initialisation fail with the ZeroKMS error. Encrypt fails with an EQL pipeline error, and is
+/// transient if the ZeroKMS error is one of its sources. Authentication failures, unknown keysets, values that cannot be
+/// decrypted and every error that is not from ZeroKMS, such as a value that cannot be encoded, are permanent and
+/// returned immediately.
+///
+pub(super) fn is_transient(err: &Error) -> bool {
+    match err.root() {
+        Error::ZeroKMS(ZeroKMSError::System(err)) => is_transient_zerokms(err),
+        Error::Encrypt(EncryptError::Pipeline(err)) => {
+            zerokms_source(err).is_some_and(is_transient_zerokms)
+        }
+        Error::Encrypt(EncryptError::ZeroKMS(_)) => true,
+        _ => false,
+    }
+}
+
+fn is_transient_zerokms(err: &cipherstash_client::zerokms::Error) -> bool {
+    // A value that cannot be decrypted fails the same way every time
+    !matches!(
+        err,
+        cipherstash_client::zerokms::Error::Auth(_)
+            | cipherstash_client::zerokms::Error::LoadKeyset(_)
+            | cipherstash_client::zerokms::Error::Decrypt(_)
+    )
+}
+
+///
+/// The ZeroKMS error in the source chain of an error, starting with the error itself
+///
+fn zerokms_source<'e>(
+    err: &'e (dyn std::error::Error + 'static),
+) -> Option<&'e cipherstash_client::zerokms::Error> {
+    std::iter::successors(Some(err), |err| err.source()).find_map(|err| err.downcast_ref())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    fn config(max_retries: u32, threshold: u32) -> ServerConfig {
+        ServerConfig {
+            zerokms_max_retries: max_retries,
+            zerokms_retry_base_delay_ms: 1,
+            zerokms_retry_max_delay_ms: 1,
+            zerokms_circuit_breaker_threshold: threshold,
+            zerokms_circuit_breaker_reset_seconds: 60,
+            ..ServerConfig::default()
+        }
+    }
+
+    fn transient() -> Error {
+        EncryptError::ZeroKMS("unavailable".to_string()).into()
+    }
+
+    #[test]
+    fn encrypt_errors_that_are_not_from_zerokms_are_permanent() {
+        use cipherstash_client::eql::EqlError;
+
+        // The errors the encrypt path returns, converted as encrypt_now converts them
+        for err in [
+            EqlError::ColumnCouldNotBeParsed,
+            EqlError::PlaintextCouldNotBeEncoded,
+            EqlError::UnknownColumn {
+                table: "users".to_string(),
+                column: "email".to_string(),
+            },
+        ] {
+            let err = Error::from(EncryptError::from(err));
+            assert!(!is_transient(&err), "{err}");
+        }
+    }
+
+    #[test]
+    fn cipher_errors_that_are_not_from_zerokms_are_permanent() {
+        for err in [
+            Error::from(ZeroKMSError::AuthenticationFailed),
+            EncryptError::UnknownKeysetIdentifier {
+                keyset: "default".to_string(),
+            }
+            .into(),
+            ZeroKMSError::Unavailable {
+                retry_after_seconds: 1,
+            }
+            .into(),
+        ] {
+            assert!(!is_transient(&err), "{err}");
+        }
+    }
+
+    #[test]
+    fn shared_errors_keep_their_classification() {
+        let shared = Error::Shared(std::sync::Arc::new(transient()));
+        assert!(is_transient(&shared));
+
+        let shared = Error::Shared(std::sync::Arc::new(
+            ZeroKMSError::AuthenticationFailed.into(),
+        ));
+        assert!(!is_transient(&shared));
+    }
+
+    #[test]
+    fn retry_delay_is_bounded() {
+        let retry = RetryPolicy {
+            max_retries: 10,
+            base_delay: Duration::from_millis(100),
+            max_delay: Duration::from_millis(1000),
+        };
+
+        assert!(retry.delay(0) <= Duration::from_millis(100));
+        assert!(retry.delay(2) <= Duration::from_millis(400));
+        assert!(retry.delay(31) <= Duration::from_millis(1000));
+    }
+
+    #[tokio::test]
+    async fn retries_transient_errors() {
+        let config = config(3, 0);
+        let retry = RetryPolicy::new(&config);
+        let breaker = CircuitBreaker::new(&config);
+        let attempts = AtomicU32::new(0);
+
+        let result = breaker
+            .call(&retry, "test", || async {
+                match attempts.fetch_add(1, Ordering::Relaxed) {
+                    0 | 1 => Err(transient()),
+                    _ => Ok(()),
+                }
+            })
+            .await;
+
+        assert!(result.is_ok());
+        assert_eq!(attempts.load(Ordering::Relaxed), 3);
+    }
+
+    #[tokio::test]
+    async fn permanent_errors_are_not_retried() {
+        let config = config(3, 0);
+        let retry = RetryPolicy::new(&config);
+        let breaker = CircuitBreaker::new(&config);
+        let attempts = AtomicU32::new(0);
+
+        let result: Result<(), Error> = breaker
+            .call(&retry, "test", || async {
+                attempts.fetch_add(1, Ordering::Relaxed);
+                Err(ZeroKMSError::AuthenticationFailed.into())
+            })
+            .await;
+
+        assert!(result.is_err());
+        assert_eq!(attempts.load(Ordering::Relaxed), 1);
+    }
+
+    #[tokio::test]
+    async fn circuit_opens_after_consecutive_failures() {
+        let config = config(0, 2);
+        let retry = RetryPolicy::new(&config);
+        let breaker = CircuitBreaker::new(&config);
+        let attempts = AtomicU32::new(0);
+
+        for _ in 0..3 {
+            let _: Result<(), Error> = breaker
+                .call(&retry, "test", || async {
+                    attempts.fetch_add(1, Ordering::Relaxed);
+                    Err(transient())
+                })
+                .await;
+        }
+
+        // The third call fails fast without calling ZeroKMS
+        assert_eq!(attempts.load(Ordering::Relaxed), 2);
+
+        let result = breaker.call(&retry, "test", || async { Ok(()) }).await;
+        assert!(matches!(
+            result,
+            Err(Error::ZeroKMS(ZeroKMSError::Unavailable { .. }))
+        ));
+    }
+
+    #[tokio::test]
+    async fn circuit_closes_after_successful_trial() {
+        let mut config = config(0, 1);
+        config.zerokms_circuit_breaker_reset_seconds = 0;
+        let retry = RetryPolicy::new(&config);
+        let breaker = CircuitBreaker::new(&config);
+
+        let _: Result<(), Error> = breaker
+            .call(&retry, "test", || async { Err(transient()) })
+            .await;
+        assert!(breaker.state.lock().unwrap().open_until.is_some());
+
+        let result = breaker.call(&retry, "test", || async { Ok(()) }).await;
+        assert!(result.is_ok());
+        assert!(breaker.state.lock().unwrap().open_until.is_none());
+    }
+
+    #[tokio::test]
+    async fn dropped_trial_lets_another_call_through() {
+        let mut config = config(0, 1);
+        config.zerokms_circuit_breaker_reset_seconds = 0;
+        let retry = RetryPolicy::new(&config);
+        let breaker = CircuitBreaker::new(&config);
+
+        let _: Result<(), Error> = breaker
+            .call(&retry, "test", || async { Err(transient()) })
+            .await;
+
+        // The trial is cancelled while waiting for ZeroKMS
+        let trial = breaker.call(&retry, "test", || {
+            std::future::pending::<Result<(), Error>>()
+        });
+        assert!(tokio::time::timeout(Duration::from_millis(10), trial)
+            .await
+            .is_err());
+        assert!(!breaker.state.lock().unwrap().trial_in_flight);
+
+        let result = breaker.call(&retry, "test", || async { Ok(()) }).await;
+        assert!(result.is_ok());
+    }
+}
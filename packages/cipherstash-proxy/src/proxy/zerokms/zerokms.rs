@@ -5,7 +5,8 @@ use crate::{
     postgresql::{Column, KeysetIdentifier},
     prometheus::{
         KEYSET_CIPHER_CACHE_HITS_TOTAL, KEYSET_CIPHER_CACHE_MISS_TOTAL,
-        KEYSET_CIPHER_INIT_DURATION_SECONDS, KEYSET_CIPHER_INIT_TOTAL,
+        KEYSET_CIPHER_INIT_DURATION_SECONDS, KEYSET_CIPHER_INIT_TOTAL, KEYSET_CIPHER_STALE_TOTAL,
+        ZEROKMS_COALESCED_REQUESTS, ZEROKMS_COALESCED_VALUES, ZEROKMS_COALESCE_WAIT_SECONDS,
     },
     proxy::EncryptionService,
 };
@@ -24,13 +25,20 @@ use moka::future::Cache;
 use std::convert::Infallible;
 use std::{
     borrow::Cow,
-    sync::Arc,
+    collections::HashSet,
+    sync::{Arc, Mutex},
     time::{Duration, Instant},
 };
+use tokio::sync::oneshot;
 use tracing::{debug, info, warn};
 use uuid::Uuid;
 
-use super::{init_zerokms_client, ScopedCipher, ZerokmsClient};
+use super::{
+    coalesce::Coalescer,
+    init_zerokms_client,
+    resilience::{is_transient, CircuitBreaker, RetryPolicy},
+    ScopedCipher, ZerokmsClient,
+};
 
 /// Memory size of a single ScopedCipher instance for cache weighing
 const SCOPED_CIPHER_SIZE: usize = std::mem::size_of::<ScopedCipher>();
@@ -104,25 +112,196 @@ fn decode_ste_vec_selector(selector: &str) -> Result<[u8; 16], EncryptError> {
         })
 }
 
+/// Prepare the plaintexts to encrypt, with the index of each in `plaintexts`
+///
+/// Null plaintexts, and plaintexts without a column, are not encrypted.
+fn prepare_plaintexts<'a>(
+    plaintexts: &[Option<Plaintext>],
+    columns: &'a [Option<Column>],
+) -> Result<(Vec<usize>, Vec<PreparedPlaintext<'a>>), Error> {
+    // Collect indices and prepared plaintexts for non-None values
+    let mut indices: Vec<usize> = Vec::new();
+    let mut prepared_plaintexts: Vec<PreparedPlaintext<'a>> = Vec::new();
+
+    for (idx, (plaintext_opt, col_opt)) in plaintexts.iter().zip(columns.iter()).enumerate() {
+        if let (Some(plaintext), Some(col)) = (plaintext_opt, col_opt) {
+            // Determine the EQL operation based on the term variant
+            let eql_op = match col.eql_term {
+                // Full, Partial, and Tokenized terms store encrypted data with all indexes
+                EqlTermVariant::Full | EqlTermVariant::Partial | EqlTermVariant::Tokenized => {
+                    EqlOperation::Store
+                }
+
+                // JsonPath generates a selector term for SteVec queries (e.g., jsonb_path_query)
+                EqlTermVariant::JsonPath => col
+                    .config
+                    .indexes
+                    .iter()
+                    .find(|i| matches!(i.index_type, IndexType::SteVec { .. }))
+                    .map(|index| EqlOperation::Query(&index.index_type, QueryOp::SteVecSelector))
+                    .unwrap_or(EqlOperation::Store),
+
+                // JsonAccessor generates a selector for SteVec field access (-> operator)
+                EqlTermVariant::JsonAccessor => col
+                    .config
+                    .indexes
+                    .iter()
+                    .find(|i| matches!(i.index_type, IndexType::SteVec { .. }))
+                    .map(|index| EqlOperation::Query(&index.index_type, QueryOp::SteVecSelector))
+                    .unwrap_or(EqlOperation::Store),
+
+                // JsonOrd is the scalar value operand of a JSON field ordering
+                // comparison (`col -> sel < value`): a SteVec ordering term
+                // (`{v,i,op}`) compared via `eql_v3.ord_term`.
+                EqlTermVariant::JsonOrd => col
+                    .config
+                    .indexes
+                    .iter()
+                    .find(|i| matches!(i.index_type, IndexType::SteVec { .. }))
+                    .map(|index| EqlOperation::Query(&index.index_type, QueryOp::SteVecTerm))
+                    .unwrap_or(EqlOperation::Store),
+
+                // JsonValueSelector is the fused value operand of a JSON
+                // field equality (`col -> sel = value`). Its plaintext is the
+                // composition input `{"path", "value"}` (built by the
+                // frontend from BOTH SQL operands); the client MACs them
+                // together into one selector, applying the column's term
+                // filters to the value. The result is a one-entry containment
+                // needle matched by `eql_v3.jsonb_contains`.
+                EqlTermVariant::JsonValueSelector => col
+                    .config
+                    .indexes
+                    .iter()
+                    .find(|i| matches!(i.index_type, IndexType::SteVec { .. }))
+                    .map(|index| {
+                        EqlOperation::Query(&index.index_type, QueryOp::SteVecValueSelector)
+                    })
+                    .unwrap_or(EqlOperation::Store),
+
+                // The result of an extraction, not an operand: it is read
+                // back from the database and decrypted, never encrypted on
+                // the way in. Refuse rather than fall through to `Store`,
+                // which would encrypt it in the wrong shape and silently
+                // return the wrong rows.
+                EqlTermVariant::JsonExtracted => {
+                    return Err(EncryptError::JsonExtractedIsNotAnOperand.into())
+                }
+            };
+
+            let prepared = PreparedPlaintext::new(
+                Cow::Owned(col.config.clone()),
+                col.identifier.clone(),
+                plaintext.clone(),
+                eql_op,
+            );
+            indices.push(idx);
+            prepared_plaintexts.push(prepared);
+        }
+    }
+
+    Ok((indices, prepared_plaintexts))
+}
+
+/// The records to decrypt, with the index of each in `ciphertexts`
+fn decryptable_records(
+    ciphertexts: &[Option<EqlCiphertextV3>],
+) -> Result<(Vec<usize>, Vec<V3Record>), Error> {
+    // Collect indices and the root records for non-None values.
+    //
+    // cipherstash-client has no `decrypt_eql_v3` counterpart to
+    // `encrypt_eql_v3` — the v2 `decrypt_eql` only accepts `EqlCiphertext`.
+    // We assemble the decryptable record ourselves, which is what
+    // protect-ffi does too (`encrypted_record_from_value`).
+    //
+    // Scalar: `c` is already the `EncryptedRecord` the v2 path would have
+    // unwrapped, and `EncryptedRecord` is `Decryptable`.
+    //
+    // SteVec: the document holds the key material once in the `h` header
+    // and each entry carries only raw AEAD bytes, so the record has to be
+    // reassembled from the header plus the ROOT entry (`sv[0]`, the same
+    // decryption-root invariant v2 had). The selector is the AEAD binding —
+    // its first 12 bytes are the nonce and all 16 go into the AAD — which is
+    // why the reassembled record is a `RecordWithNonce`.
+    let mut indices: Vec<usize> = Vec::new();
+    let mut records_to_decrypt: Vec<V3Record> = Vec::new();
+
+    for (idx, ct_opt) in ciphertexts.iter().enumerate() {
+        if let Some(ct) = ct_opt {
+            let record = match ct {
+                EqlCiphertextV3::Encrypted(payload) => V3Record::Scalar(payload.ciphertext.clone()),
+                EqlCiphertextV3::SteVec(document) => {
+                    let root = document
+                        .ste_vec
+                        .first()
+                        .ok_or(EncryptError::SteVecMissingRootEntry)?;
+
+                    let selector = decode_ste_vec_selector(&root.selector)?;
+                    V3Record::SteVecRoot(
+                        document
+                            .key_header
+                            .record_with_selector(root.ciphertext.clone(), selector),
+                    )
+                }
+            };
+            indices.push(idx);
+            records_to_decrypt.push(record);
+        }
+    }
+
+    Ok((indices, records_to_decrypt))
+}
+
 #[derive(Clone)]
 pub struct ZeroKms {
     default_keyset_id: Option<Uuid>,
     zerokms_client: Arc<ZerokmsClient>,
-    cipher_cache: Cache<String, Arc<ScopedCipher>>,
+    cipher_cache: Cache<String, CachedCipher>,
+    /// How long a cached cipher is used before it is refreshed
+    cipher_ttl: Duration,
+    /// Cache keys of the ciphers being refreshed in the background
+    refreshing: Arc<Mutex<HashSet<String>>>,
+    retry: RetryPolicy,
+    circuit_breaker: Arc<CircuitBreaker>,
+    encrypts: Arc<Coalescer<EncryptRequest>>,
+    decrypts: Arc<Coalescer<DecryptRequest>>,
+}
+
+/// An encrypt request waiting to be sent to ZeroKMS in a coalesced batch
+struct EncryptRequest {
+    plaintexts: Vec<Option<Plaintext>>,
+    columns: Vec<Option<Column>>,
+    queued_at: Instant,
+    sender: oneshot::Sender<Result<Vec<Option<EqlOutputV3>>, Error>>,
+}
+
+/// A decrypt request waiting to be sent to ZeroKMS in a coalesced batch
+struct DecryptRequest {
+    ciphertexts: Vec<Option<EqlCiphertextV3>>,
+    queued_at: Instant,
+    sender: oneshot::Sender<Result<Vec<Option<Plaintext>>, Error>>,
+}
+
+/// A cached cipher and when it was initialized
+#[derive(Clone)]
+struct CachedCipher {
+    cipher: Arc<ScopedCipher>,
+    initialized_at: Instant,
 }
 
 impl ZeroKms {
     pub fn init(config: &TandemConfig) -> Result<Self, Error> {
         let zerokms_client = init_zerokms_client(config)?;
 
+        let cipher_ttl = Duration::from_secs(config.server.cipher_cache_ttl_seconds);
+        let stale = Duration::from_secs(config.server.cipher_cache_stale_seconds);
+
         let cipher_cache = Cache::builder()
             // Use weigher to calculate actual memory usage of ScopedCipher instances
-            .weigher(|_key: &String, _value: &Arc<ScopedCipher>| -> u32 {
-                SCOPED_CIPHER_SIZE as u32
-            })
+            .weigher(|_key: &String, _value: &CachedCipher| -> u32 { SCOPED_CIPHER_SIZE as u32 })
             // Set capacity in bytes (entry count * actual struct size)
             .max_capacity((config.server.cipher_cache_size as u64) * SCOPED_CIPHER_SIZE as u64)
-            .time_to_live(Duration::from_secs(config.server.cipher_cache_ttl_seconds))
+            // Expired ciphers are kept for the stale period, and used while they are refreshed
+            .time_to_live(cipher_ttl + stale)
             .eviction_listener(|key, _value, cause| {
                 info!(target: ZEROKMS, msg = "ScopedCipher evicted from cache", cache_key = %key, cause = ?cause);
             })
@@ -134,6 +313,12 @@ impl ZeroKms {
             default_keyset_id,
             zerokms_client: Arc::new(zerokms_client),
             cipher_cache,
+            cipher_ttl,
+            refreshing: Arc::new(Mutex::new(HashSet::new())),
+            retry: RetryPolicy::new(&config.server),
+            circuit_breaker: Arc::new(CircuitBreaker::new(&config.server)),
+            encrypts: Arc::new(Coalescer::new(&config.server)),
+            decrypts: Arc::new(Coalescer::new(&config.server)),
         })
     }
 
@@ -146,6 +331,8 @@ impl ZeroKms {
     }
 
     /// Initialize cipher using the stored zerokms_config, with async Moka caching and memory tracking
+    ///
+    /// An expired cipher is used for up to `cipher_cache_stale_seconds` while it is refreshed in the background.
     pub async fn init_cipher(
         &self,
         keyset_id: Option<KeysetIdentifier>,
@@ -153,17 +340,92 @@ impl ZeroKms {
         let cache_key = Self::cache_key_for_keyset(&keyset_id);
 
         // Check cache first
-        if let Some(cached_cipher) = self.cipher_cache.get(&cache_key).await {
-            debug!(target: ZEROKMS, msg = "Use cached ScopedCipher", ?keyset_id);
-            counter!(KEYSET_CIPHER_CACHE_HITS_TOTAL).increment(1);
-            return Ok(cached_cipher);
-        }
+        if let Some(cached) = self.cipher_cache.get(&cache_key).await {
+            if cached.initialized_at.elapsed() < self.cipher_ttl {
+                debug!(target: ZEROKMS, msg = "Use cached ScopedCipher", ?keyset_id);
+                counter!(KEYSET_CIPHER_CACHE_HITS_TOTAL).increment(1);
+                return Ok(cached.cipher);
+            }
 
-        let zerokms_client = self.zerokms_client.clone();
+            debug!(target: ZEROKMS, msg = "Use stale ScopedCipher while refreshing", ?keyset_id);
+            counter!(KEYSET_CIPHER_STALE_TOTAL).increment(1);
+            self.refresh_cipher(keyset_id, cache_key);
+            return Ok(cached.cipher);
+        }
 
         info!(target: ZEROKMS, msg = "Initializing ZeroKMS ScopedCipher (cache miss)", ?keyset_id);
         counter!(KEYSET_CIPHER_CACHE_MISS_TOTAL).increment(1);
 
+        self.load_cipher(keyset_id, cache_key).await
+    }
+
+    /// Refresh a stale cipher in the background, at most once at a time for each keyset
+    fn refresh_cipher(&self, keyset_id: Option<KeysetIdentifier>, cache_key: String) {
+        {
+            let mut refreshing = self
+                .refreshing
+                .lock()
+                .unwrap_or_else(|err| err.into_inner());
+            if !refreshing.insert(cache_key.clone()) {
+                return;
+            }
+        }
+
+        let zerokms = self.clone();
+        tokio::spawn(async move {
+            if let Err(err) = zerokms
+                .load_cipher(keyset_id.clone(), cache_key.clone())
+                .await
+            {
+                warn!(target: ZEROKMS, msg = "Could not refresh stale ScopedCipher", ?keyset_id, error = err.to_string());
+            }
+
+            let mut refreshing = zerokms
+                .refreshing
+                .lock()
+                .unwrap_or_else(|err| err.into_inner());
+            refreshing.remove(&cache_key);
+        });
+    }
+
+    /// Initialize a cipher from ZeroKMS, retrying transient errors, and cache it
+    async fn load_cipher(
+        &self,
+        keyset_id: Option<KeysetIdentifier>,
+        cache_key: String,
+    ) -> Result<Arc<ScopedCipher>, Error> {
+        let cipher = self
+            .circuit_breaker
+            .call(&self.retry, "init_cipher", || {
+                self.init_scoped_cipher(&keyset_id)
+            })
+            .await?;
+
+        let arc_cipher = Arc::new(cipher);
+
+        // Store in cache
+        let cached = CachedCipher {
+            cipher: arc_cipher.clone(),
+            initialized_at: Instant::now(),
+        };
+        self.cipher_cache.insert(cache_key, cached).await;
+
+        // Update pending tasks to get accurate cache statistics
+        self.cipher_cache.run_pending_tasks().await;
+
+        let entry_count = self.cipher_cache.entry_count();
+        let memory_usage_bytes = self.cipher_cache.weighted_size();
+
+        debug!(target: ZEROKMS, msg = "ScopedCipher cached", ?keyset_id, entry_count, memory_usage_bytes);
+
+        Ok(arc_cipher)
+    }
+
+    async fn init_scoped_cipher(
+        &self,
+        keyset_id: &Option<KeysetIdentifier>,
+    ) -> Result<ScopedCipher, Error> {
+        let zerokms_client = self.zerokms_client.clone();
         let identified_by = keyset_id.as_ref().map(|id| id.0.clone());
 
         let start = Instant::now();
@@ -177,26 +439,12 @@ impl ZeroKms {
 
         match result {
             Ok(cipher) => {
-                let arc_cipher = Arc::new(cipher);
-
                 counter!(KEYSET_CIPHER_INIT_TOTAL).increment(1);
                 histogram!(KEYSET_CIPHER_INIT_DURATION_SECONDS).record(init_duration);
 
-                // Store in cache
-                self.cipher_cache
-                    .insert(cache_key, arc_cipher.clone())
-                    .await;
-
-                // Update pending tasks to get accurate cache statistics
-                self.cipher_cache.run_pending_tasks().await;
-
-                let entry_count = self.cipher_cache.entry_count();
-                let memory_usage_bytes = self.cipher_cache.weighted_size();
-
                 info!(target: ZEROKMS, msg = "Connected to ZeroKMS", init_duration_ms);
-                debug!(target: ZEROKMS, msg = "ScopedCipher cached", ?keyset_id, entry_count, memory_usage_bytes, init_duration_ms);
 
-                Ok(arc_cipher)
+                Ok(cipher)
             }
             Err(err) => {
                 warn!(target: ZEROKMS, msg = "Error initializing ZeroKMS", error = err.to_string(), init_duration_ms);
@@ -204,7 +452,9 @@ impl ZeroKms {
                 match err {
                     cipherstash_client::zerokms::Error::LoadKeyset(_) => {
                         Err(EncryptError::UnknownKeysetIdentifier {
-                            keyset: keyset_id.map_or("default".to_string(), |id| id.to_string()),
+                            keyset: keyset_id
+                                .as_ref()
+                                .map_or("default".to_string(), |id| id.to_string()),
                         }
                         .into())
                     }
@@ -216,111 +466,19 @@ impl ZeroKms {
             }
         }
     }
-}
 
-#[async_trait::async_trait]
-impl EncryptionService for ZeroKms {
     ///
     /// Encrypt `Plaintexts` using the `Column` configuration
     ///
-    async fn encrypt(
+    async fn encrypt_now(
         &self,
         keyset_id: Option<KeysetIdentifier>,
-        plaintexts: Vec<Option<Plaintext>>,
+        plaintexts: &[Option<Plaintext>],
         columns: &[Option<Column>],
     ) -> Result<Vec<Option<EqlOutputV3>>, Error> {
-        debug!(target: ENCRYPT, msg="Encrypt", ?keyset_id, default_keyset_id = ?self.default_keyset_id);
-
-        // A keyset is required if no default keyset has been configured
-        if self.default_keyset_id.is_none() && keyset_id.is_none() {
-            return Err(EncryptError::MissingKeysetIdentifier.into());
-        }
-
         let cipher = self.init_cipher(keyset_id.clone()).await?;
 
-        // Collect indices and prepared plaintexts for non-None values
-        let mut indices: Vec<usize> = Vec::new();
-        let mut prepared_plaintexts: Vec<PreparedPlaintext> = Vec::new();
-
-        for (idx, (plaintext_opt, col_opt)) in plaintexts.iter().zip(columns.iter()).enumerate() {
-            if let (Some(plaintext), Some(col)) = (plaintext_opt, col_opt) {
-                // Determine the EQL operation based on the term variant
-                let eql_op = match col.eql_term {
-                    // Full, Partial, and Tokenized terms store encrypted data with all indexes
-                    EqlTermVariant::Full | EqlTermVariant::Partial | EqlTermVariant::Tokenized => {
-                        EqlOperation::Store
-                    }
-
-                    // JsonPath generates a selector term for SteVec queries (e.g., jsonb_path_query)
-                    EqlTermVariant::JsonPath => col
-                        .config
-                        .indexes
-                        .iter()
-                        .find(|i| matches!(i.index_type, IndexType::SteVec { .. }))
-                        .map(|index| {
-                            EqlOperation::Query(&index.index_type, QueryOp::SteVecSelector)
-                        })
-                        .unwrap_or(EqlOperation::Store),
-
-                    // JsonAccessor generates a selector for SteVec field access (-> operator)
-                    EqlTermVariant::JsonAccessor => col
-                        .config
-                        .indexes
-                        .iter()
-                        .find(|i| matches!(i.index_type, IndexType::SteVec { .. }))
-                        .map(|index| {
-                            EqlOperation::Query(&index.index_type, QueryOp::SteVecSelector)
-                        })
-                        .unwrap_or(EqlOperation::Store),
-
-                    // JsonOrd is the scalar value operand of a JSON field ordering
-                    // comparison (`col -> sel < value`): a SteVec ordering term
-                    // (`{v,i,op}`) compared via `eql_v3.ord_term`.
-                    EqlTermVariant::JsonOrd => col
-                        .config
-                        .indexes
-                        .iter()
-                        .find(|i| matches!(i.index_type, IndexType::SteVec { .. }))
-                        .map(|index| EqlOperation::Query(&index.index_type, QueryOp::SteVecTerm))
-                        .unwrap_or(EqlOperation::Store),
-
-                    // JsonValueSelector is the fused value operand of a JSON
-                    // field equality (`col -> sel = value`). Its plaintext is the
-                    // composition input `{"path", "value"}` (built by the
-                    // frontend from BOTH SQL operands); the client MACs them
-                    // together into one selector, applying the column's term
-                    // filters to the value. The result is a one-entry containment
-                    // needle matched by `eql_v3.jsonb_contains`.
-                    EqlTermVariant::JsonValueSelector => col
-                        .config
-                        .indexes
-                        .iter()
-                        .find(|i| matches!(i.index_type, IndexType::SteVec { .. }))
-                        .map(|index| {
-                            EqlOperation::Query(&index.index_type, QueryOp::SteVecValueSelector)
-                        })
-                        .unwrap_or(EqlOperation::Store),
-
-                    // The result of an extraction, not an operand: it is read
-                    // back from the database and decrypted, never encrypted on
-                    // the way in. Refuse rather than fall through to `Store`,
-                    // which would encrypt it in the wrong shape and silently
-                    // return the wrong rows.
-                    EqlTermVariant::JsonExtracted => {
-                        return Err(EncryptError::JsonExtractedIsNotAnOperand.into())
-                    }
-                };
-
-                let prepared = PreparedPlaintext::new(
-                    Cow::Owned(col.config.clone()),
-                    col.identifier.clone(),
-                    plaintext.clone(),
-                    eql_op,
-                );
-                indices.push(idx);
-                prepared_plaintexts.push(prepared);
-            }
-        }
+        let (indices, prepared_plaintexts) = prepare_plaintexts(plaintexts, columns)?;
 
         // If no plaintexts to encrypt, return all None.
         //
@@ -337,9 +495,25 @@ impl EncryptionService for ZeroKms {
 
         debug!(target: ENCRYPT, msg="Calling encrypt_eql_v3", count = prepared_plaintexts.len());
         let encrypt_start = Instant::now();
-        let encrypted = encrypt_eql_v3(cipher, prepared_plaintexts, &opts)
-            .await
-            .map_err(EncryptError::from)?;
+
+        // Plaintexts are consumed by each attempt, and prepared again to retry
+        let mut prepared_plaintexts = Some(prepared_plaintexts);
+        let opts = &opts;
+        let encrypted = self
+            .circuit_breaker
+            .call(&self.retry, "encrypt", || {
+                let prepared = match prepared_plaintexts.take() {
+                    Some(prepared) => Ok(prepared),
+                    None => prepare_plaintexts(plaintexts, columns).map(|(_, prepared)| prepared),
+                };
+                let cipher = cipher.clone();
+                async move {
+                    encrypt_eql_v3(cipher, prepared?, opts)
+                        .await
+                        .map_err(|err| EncryptError::from(err).into())
+                }
+            })
+            .await?;
         let encrypt_duration = encrypt_start.elapsed();
         debug!(target: ENCRYPT, msg="encrypt_eql_v3 completed", count = encrypted.len(), duration_ms = encrypt_duration.as_millis());
 
@@ -357,63 +531,14 @@ impl EncryptionService for ZeroKms {
     ///
     /// Database values are stored as `eql::Ciphertext`
     ///
-    async fn decrypt(
+    async fn decrypt_now(
         &self,
         keyset_id: Option<KeysetIdentifier>,
-        ciphertexts: Vec<Option<EqlCiphertextV3>>,
+        ciphertexts: &[Option<EqlCiphertextV3>],
     ) -> Result<Vec<Option<Plaintext>>, Error> {
-        debug!(target: ENCRYPT, msg="Decrypt", ?keyset_id, default_keyset_id = ?self.default_keyset_id);
-
-        // A keyset is required if no default keyset has been configured
-        if self.default_keyset_id.is_none() && keyset_id.is_none() {
-            return Err(EncryptError::MissingKeysetIdentifier.into());
-        }
-
         let cipher = self.init_cipher(keyset_id.clone()).await?;
 
-        // Collect indices and the root records for non-None values.
-        //
-        // cipherstash-client has no `decrypt_eql_v3` counterpart to
-        // `encrypt_eql_v3` — the v2 `decrypt_eql` only accepts `EqlCiphertext`.
-        // We assemble the decryptable record ourselves, which is what
-        // protect-ffi does too (`encrypted_record_from_value`).
-        //
-        // Scalar: `c` is already the `EncryptedRecord` the v2 path would have
-        // unwrapped, and `EncryptedRecord` is `Decryptable`.
-        //
-        // SteVec: the document holds the key material once in the `h` header
-        // and each entry carries only raw AEAD bytes, so the record has to be
-        // reassembled from the header plus the ROOT entry (`sv[0]`, the same
-        // decryption-root invariant v2 had). The selector is the AEAD binding —
-        // its first 12 bytes are the nonce and all 16 go into the AAD — which is
-        // why the reassembled record is a `RecordWithNonce`.
-        let mut indices: Vec<usize> = Vec::new();
-        let mut records_to_decrypt: Vec<V3Record> = Vec::new();
-
-        for (idx, ct_opt) in ciphertexts.iter().enumerate() {
-            if let Some(ct) = ct_opt {
-                let record = match ct {
-                    EqlCiphertextV3::Encrypted(payload) => {
-                        V3Record::Scalar(payload.ciphertext.clone())
-                    }
-                    EqlCiphertextV3::SteVec(document) => {
-                        let root = document
-                            .ste_vec
-                            .first()
-                            .ok_or(EncryptError::SteVecMissingRootEntry)?;
-
-                        let selector = decode_ste_vec_selector(&root.selector)?;
-                        V3Record::SteVecRoot(
-                            document
-                                .key_header
-                                .record_with_selector(root.ciphertext.clone(), selector),
-                        )
-                    }
-                };
-                indices.push(idx);
-                records_to_decrypt.push(record);
-            }
-        }
+        let (indices, records_to_decrypt) = decryptable_records(ciphertexts)?;
 
         // If no ciphertexts to decrypt, return all None
         if records_to_decrypt.is_empty() {
@@ -426,10 +551,26 @@ impl EncryptionService for ZeroKms {
 
         debug!(target: ENCRYPT, msg="Decrypting EQL v3 records", count = records_to_decrypt.len());
         let decrypt_start = Instant::now();
-        let decrypted = cipher
-            .decrypt(records_to_decrypt, &opts)
-            .await
-            .map_err(ZeroKMSError::from)?
+
+        // Records are consumed by each attempt, and collected again to retry
+        let mut records_to_decrypt = Some(records_to_decrypt);
+        let opts = &opts;
+        let decrypted = self
+            .circuit_breaker
+            .call(&self.retry, "decrypt", || {
+                let records = match records_to_decrypt.take() {
+                    Some(records) => Ok(records),
+                    None => decryptable_records(ciphertexts).map(|(_, records)| records),
+                };
+                let cipher = cipher.clone();
+                async move {
+                    cipher
+                        .decrypt(records?, opts)
+                        .await
+                        .map_err(|err| Error::from(ZeroKMSError::from(err)))
+                }
+            })
+            .await?
             .into_iter()
             .map(|bytes| Plaintext::from_slice(&bytes))
             .collect::<Result<Vec<_>, _>>()
@@ -445,4 +586,253 @@ impl EncryptionService for ZeroKms {
 
         Ok(result)
     }
+
+    ///
+    /// Encrypts a coalesced batch of requests in one ZeroKMS request, and sends each request its results
+    ///
+    async fn encrypt_batch(
+        &self,
+        keyset_id: Option<KeysetIdentifier>,
+        requests: Vec<EncryptRequest>,
+    ) {
+        let mut lengths = Vec::with_capacity(requests.len());
+        let mut senders = Vec::with_capacity(requests.len());
+        let mut plaintexts = vec![];
+        let mut columns = vec![];
+
+        for request in requests {
+            histogram!(ZEROKMS_COALESCE_WAIT_SECONDS, "operation" => "encrypt")
+                .record(request.queued_at.elapsed());
+            lengths.push(request.plaintexts.len());
+            senders.push(request.sender);
+            plaintexts.extend(request.plaintexts);
+            columns.extend(request.columns);
+        }
+
+        record_coalesced(
+            "encrypt",
+            senders.len(),
+            plaintexts.iter().flatten().count(),
+        );
+
+        match self
+            .encrypt_now(keyset_id.clone(), &plaintexts, &columns)
+            .await
+        {
+            Ok(encrypted) => {
+                let mut encrypted = encrypted.into_iter();
+                for (sender, len) in senders.into_iter().zip(lengths) {
+                    let _ = sender.send(Ok(encrypted.by_ref().take(len).collect()));
+                }
+            }
+            Err(err) if senders.len() == 1 || is_transient(&err) => {
+                send_error(senders, err);
+            }
+            Err(err) => {
+                // The error may be caused by a single value, so each request is sent on its own
+                debug!(target: ENCRYPT, msg = "Coalesced encrypt failed, sending requests individually", error = err.to_string());
+
+                let mut plaintexts = plaintexts.into_iter();
+                let mut columns = columns.into_iter();
+                for (sender, len) in senders.into_iter().zip(lengths) {
+                    let plaintexts = plaintexts.by_ref().take(len).collect::<Vec<_>>();
+                    let columns = columns.by_ref().take(len).collect::<Vec<_>>();
+                    let zerokms = self.clone();
+                    let keyset_id = keyset_id.clone();
+                    tokio::spawn(async move {
+                        let result = zerokms.encrypt_now(keyset_id, &plaintexts, &columns).await;
+                        let _ = sender.send(result);
+                    });
+                }
+            }
+        }
+    }
+
+    ///
+    /// Decrypts a coalesced batch of requests in one ZeroKMS request, and sends each request its results
+    ///
+    async fn decrypt_batch(
+        &self,
+        keyset_id: Option<KeysetIdentifier>,
+        requests: Vec<DecryptRequest>,
+    ) {
+        let mut lengths = Vec::with_capacity(requests.len());
+        let mut senders = Vec::with_capacity(requests.len());
+        let mut ciphertexts = vec![];
+
+        for request in requests {
+            histogram!(ZEROKMS_COALESCE_WAIT_SECONDS, "operation" => "decrypt")
+                .record(request.queued_at.elapsed());
+            lengths.push(request.ciphertexts.len());
+            senders.push(request.sender);
+            ciphertexts.extend(request.ciphertexts);
+        }
+
+        record_coalesced(
+            "decrypt",
+            senders.len(),
+            ciphertexts.iter().flatten().count(),
+        );
+
+        match self.decrypt_now(keyset_id.clone(), &ciphertexts).await {
+            Ok(decrypted) => {
+                let mut decrypted = decrypted.into_iter();
+                for (sender, len) in senders.into_iter().zip(lengths) {
+                    let _ = sender.send(Ok(decrypted.by_ref().take(len).collect()));
+                }
+            }
+            Err(err) if senders.len() == 1 || is_transient(&err) => {
+                send_error(senders, err);
+            }
+            Err(err) => {
+                // The error may be caused by a single value, so each request is sent on its own
+                debug!(target: ENCRYPT, msg = "Coalesced decrypt failed, sending requests individually", error = err.to_string());
+
+                let mut ciphertexts = ciphertexts.into_iter();
+                for (sender, len) in senders.into_iter().zip(lengths) {
+                    let ciphertexts = ciphertexts.by_ref().take(len).collect::<Vec<_>>();
+                    let zerokms = self.clone();
+                    let keyset_id = keyset_id.clone();
+                    tokio::spawn(async move {
+                        let result = zerokms.decrypt_now(keyset_id, &ciphertexts).await;
+                        let _ = sender.send(result);
+                    });
+                }
+            }
+        }
+    }
+}
+
+fn record_coalesced(operation: &'static str, requests: usize, values: usize) {
+    debug!(target: ZEROKMS, msg = "Coalesced ZeroKMS request", operation, requests, values);
+    histogram!(ZEROKMS_COALESCED_REQUESTS, "operation" => operation).record(requests as f64);
+    histogram!(ZEROKMS_COALESCED_VALUES, "operation" => operation).record(values as f64);
+}
+
+///
+/// Sends the error of a coalesced request to every request in the batch
+///
+/// `Error` is not `Clone`, so the requests share the error, and each request sees its variant through `Error::root`.
+///
+fn send_error<T>(senders: Vec<oneshot::Sender<Result<T, Error>>>, err: Error) {
+    let err = Arc::new(err);
+
+    for sender in senders {
+        let _ = sender.send(Err(Error::Shared(err.clone())));
+    }
+}
+
+fn dropped_request() -> Error {
+    EncryptError::ZeroKMS("coalesced request was dropped before it completed".to_string()).into()
+}
+
+#[async_trait::async_trait]
+impl EncryptionService for ZeroKms {
+    ///
+    /// Encrypt `Plaintexts` using the `Column` configuration
+    ///
+    /// Requests are coalesced with concurrent requests for the same keyset if `zerokms_coalesce_window_ms` is set.
+    ///
+    async fn encrypt(
+        &self,
+        keyset_id: Option<KeysetIdentifier>,
+        plaintexts: Vec<Option<Plaintext>>,
+        columns: &[Option<Column>],
+    ) -> Result<Vec<Option<EqlOutputV3>>, Error> {
+        debug!(target: ENCRYPT, msg="Encrypt", ?keyset_id, default_keyset_id = ?self.default_keyset_id);
+
+        // A keyset is required if no default keyset has been configured
+        if self.default_keyset_id.is_none() && keyset_id.is_none() {
+            return Err(EncryptError::MissingKeysetIdentifier.into());
+        }
+
+        let values = plaintexts.iter().flatten().count();
+        if !self.encrypts.accepts(values) {
+            return self.encrypt_now(keyset_id, &plaintexts, columns).await;
+        }
+
+        let (sender, receiver) = oneshot::channel();
+        let request = EncryptRequest {
+            plaintexts,
+            columns: columns.to_vec(),
+            queued_at: Instant::now(),
+            sender,
+        };
+
+        let key = Self::cache_key_for_keyset(&keyset_id);
+        if let Some(batch) = self.encrypts.join(&key, request, values) {
+            let zerokms = self.clone();
+            tokio::spawn(async move {
+                let requests = zerokms.encrypts.close(&key, &batch).await;
+                zerokms.encrypt_batch(keyset_id, requests).await;
+            });
+        }
+
+        receiver.await.unwrap_or_else(|_| Err(dropped_request()))
+    }
+
+    ///
+    /// Decrypt eql::Ciphertext into Plaintext
+    ///
+    /// Database values are stored as `eql::Ciphertext`.
+    /// Requests are coalesced with concurrent requests for the same keyset if `zerokms_coalesce_window_ms` is set.
+    ///
+    async fn decrypt(
+        &self,
+        keyset_id: Option<KeysetIdentifier>,
+        ciphertexts: Vec<Option<EqlCiphertextV3>>,
+    ) -> Result<Vec<Option<Plaintext>>, Error> {
+        debug!(target: ENCRYPT, msg="Decrypt", ?keyset_id, default_keyset_id = ?self.default_keyset_id);
+
+        // A keyset is required if no default keyset has been configured
+        if self.default_keyset_id.is_none() && keyset_id.is_none() {
+            return Err(EncryptError::MissingKeysetIdentifier.into());
+        }
+
+        let values = ciphertexts.iter().flatten().count();
+        if !self.decrypts.accepts(values) {
+            return self.decrypt_now(keyset_id, &ciphertexts).await;
+        }
+
+        let (sender, receiver) = oneshot::channel();
+        let request = DecryptRequest {
+            ciphertexts,
+            queued_at: Instant::now(),
+            sender,
+        };
+
+        let key = Self::cache_key_for_keyset(&keyset_id);
+        if let Some(batch) = self.decrypts.join(&key, request, values) {
+            let zerokms = self.clone();
+            tokio::spawn(async move {
+                let requests = zerokms.decrypts.close(&key, &batch).await;
+                zerokms.decrypt_batch(keyset_id, requests).await;
+            });
+        }
+
+        receiver.await.unwrap_or_else(|_| Err(dropped_request()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn every_coalesced_request_receives_the_error_variant() {
+        let (senders, receivers): (Vec<_>, Vec<_>) = (0..3)
+            .map(|_| oneshot::channel::<Result<(), Error>>())
+            .unzip();
+
+        send_error(senders, ZeroKMSError::AuthenticationFailed.into());
+
+        for receiver in receivers {
+            let err = receiver.await.unwrap().unwrap_err();
+            assert!(matches!(
+                err.root(),
+                Error::ZeroKMS(ZeroKMSError::AuthenticationFailed)
+            ));
+            assert!(!is_transient(&err));
+        }
+    }
 }
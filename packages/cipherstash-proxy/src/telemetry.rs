@@ -0,0 +1,452 @@
+use crate::config::TelemetryConfig;
+use crate::error::Error;
+use crate::log::DEVELOPMENT;
+use crate::postgresql::PhaseSpan;
+use opentelemetry::{
+    global,
+    trace::{
+        Span, SpanContext, SpanId, SpanKind, TraceContextExt, TraceFlags, TraceId, TraceState,
+        Tracer,
+    },
+    Context, KeyValue,
+};
+use opentelemetry_otlp::{SpanExporter, WithExportConfig};
+use opentelemetry_sdk::{trace::SdkTracerProvider, Resource};
+use regex::Regex;
+use std::{
+    sync::{LazyLock, OnceLock},
+    time::{Duration, SystemTime},
+};
+use tracing::{debug, warn};
+
+const TRACER_NAME: &str = "cipherstash-proxy";
+
+static PROVIDER: OnceLock<SdkTracerProvider> = OnceLock::new();
+
+///
+/// Starts exporting statement traces to the configured OTLP/HTTP endpoint
+///
+pub fn init(config: &TelemetryConfig) -> Result<(), Error> {
+    debug!(target: DEVELOPMENT, msg = "Starting OpenTelemetry exporter", endpoint = config.endpoint);
+
+    let provider = tracer_provider(config)?;
+
+    global::set_tracer_provider(provider.clone());
+    let _ = PROVIDER.set(provider);
+
+    Ok(())
+}
+
+fn tracer_provider(config: &TelemetryConfig) -> Result<SdkTracerProvider, Error> {
+    let exporter = SpanExporter::builder()
+        .with_http()
+        .with_endpoint(&config.endpoint)
+        .build()?;
+
+    let provider = SdkTracerProvider::builder()
+        .with_batch_exporter(exporter)
+        .with_resource(
+            Resource::builder()
+                .with_service_name(config.service_name.to_owned())
+                .build(),
+        )
+        .build();
+
+    Ok(provider)
+}
+
+///
+/// Flushes any buffered spans
+///
+pub fn shutdown() {
+    if let Some(provider) = PROVIDER.get() {
+        if let Err(err) = provider.shutdown() {
+            warn!(
+                msg = "Could not shut down OpenTelemetry exporter",
+                error = err.to_string()
+            );
+        }
+    }
+}
+
+///
+/// A W3C `traceparent` passed by the application
+///
+/// Set for the connection with `SET CIPHERSTASH.TRACEPARENT = '...'`,
+/// or for a single statement with a sqlcommenter style comment: `/*traceparent='...'*/`.
+///
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TraceParent {
+    trace_id: [u8; 16],
+    span_id: [u8; 8],
+    flags: u8,
+}
+
+impl TraceParent {
+    ///
+    /// Parses `{version}-{trace-id}-{parent-id}-{trace-flags}`
+    ///
+    /// The all-zero trace and parent ids are invalid.
+    ///
+    pub fn parse(value: &str) -> Option<TraceParent> {
+        let mut parts = value.trim().split('-');
+
+        let version = parts.next()?;
+        let trace_id = parts.next()?;
+        let span_id = parts.next()?;
+        let flags = parts.next()?;
+
+        // Version 00 has exactly four fields, later versions may append more
+        if version.len() != 2 || version == "ff" || (version == "00" && parts.next().is_some()) {
+            return None;
+        }
+
+        let trace_id: [u8; 16] = decode_lowercase_hex(trace_id)?.try_into().ok()?;
+        let span_id: [u8; 8] = decode_lowercase_hex(span_id)?.try_into().ok()?;
+        let [flags]: [u8; 1] = decode_lowercase_hex(flags)?.try_into().ok()?;
+        decode_lowercase_hex(version)?;
+
+        if trace_id == [0; 16] || span_id == [0; 8] {
+            return None;
+        }
+
+        Some(TraceParent {
+            trace_id,
+            span_id,
+            flags,
+        })
+    }
+
+    ///
+    /// Finds a `traceparent` in a sqlcommenter style comment in the statement
+    ///
+    pub fn from_sql_comment(sql: &str) -> Option<TraceParent> {
+        static TRACEPARENT_COMMENT: LazyLock<Regex> = LazyLock::new(|| {
+            Regex::new(r"/\*[^*]*\btraceparent\s*=\s*'([^']*)'").expect("valid regex")
+        });
+
+        if !sql.contains("traceparent") {
+            return None;
+        }
+
+        TRACEPARENT_COMMENT
+            .captures(sql)
+            .and_then(|captures| TraceParent::parse(&captures[1]))
+    }
+
+    fn context(&self) -> Context {
+        let span_context = SpanContext::new(
+            TraceId::from_bytes(self.trace_id),
+            SpanId::from_bytes(self.span_id),
+            TraceFlags::new(self.flags),
+            true,
+            TraceState::default(),
+        );
+        Context::new().with_remote_span_context(span_context)
+    }
+}
+
+fn decode_lowercase_hex(value: &str) -> Option<Vec<u8>> {
+    if value.chars().any(|c| c.is_ascii_uppercase()) {
+        return None;
+    }
+    hex::decode(value).ok()
+}
+
+///
+/// The timing of a completed statement, recorded as a span with a child span for each phase
+///
+#[derive(Clone, Debug)]
+pub struct StatementTrace {
+    pub client_id: i32,
+    pub started_at: SystemTime,
+    pub duration: Duration,
+    pub statement_type: &'static str,
+    pub protocol: &'static str,
+    pub encrypted: bool,
+    pub encrypted_values_count: usize,
+    pub parse: Option<PhaseSpan>,
+    pub encrypt: Option<PhaseSpan>,
+    pub encrypt_requests: u32,
+    pub server_wait: Option<PhaseSpan>,
+    pub decrypt: Option<PhaseSpan>,
+    pub decrypt_requests: u32,
+    pub traceparent: Option<TraceParent>,
+}
+
+///
+/// Records the statement with the global tracer provider
+///
+pub fn record_statement(statement: &StatementTrace) {
+    record_statement_with(&global::tracer(TRACER_NAME), statement);
+}
+
+///
+/// Each phase is recorded as a child span from the start of its first operation to the end of its last.
+///
+fn record_statement_with<T>(tracer: &T, statement: &StatementTrace)
+where
+    T: Tracer,
+    T::Span: Send + Sync + 'static,
+{
+    let parent = statement
+        .traceparent
+        .map(|traceparent| traceparent.context())
+        .unwrap_or_default();
+
+    let end = statement.started_at + statement.duration;
+
+    let span = tracer
+        .span_builder("statement")
+        .with_kind(SpanKind::Server)
+        .with_start_time(statement.started_at)
+        .with_attributes([
+            KeyValue::new("db.system.name", "postgresql"),
+            KeyValue::new("cipherstash.client_id", statement.client_id as i64),
+            KeyValue::new("cipherstash.statement_type", statement.statement_type),
+            KeyValue::new("cipherstash.protocol", statement.protocol),
+            KeyValue::new("cipherstash.encrypted", statement.encrypted),
+            KeyValue::new(
+                "cipherstash.encrypted_values_count",
+                statement.encrypted_values_count as i64,
+            ),
+        ])
+        .start_with_context(tracer, &parent);
+
+    let cx = parent.with_span(span);
+
+    let phases = [
+        ("parse", statement.parse, None),
+        (
+            "encrypt",
+            statement.encrypt,
+            Some(statement.encrypt_requests),
+        ),
+        ("server_wait", statement.server_wait, None),
+        (
+            "decrypt",
+            statement.decrypt,
+            Some(statement.decrypt_requests),
+        ),
+    ];
+
+    for (name, phase, requests) in phases {
+        let Some(phase) = phase else {
+            continue;
+        };
+
+        let mut builder = tracer.span_builder(name).with_start_time(phase.start);
+        if let Some(requests) = requests {
+            builder = builder.with_attributes([KeyValue::new("zerokms.requests", requests as i64)]);
+        }
+
+        builder
+            .start_with_context(tracer, &cx)
+            .end_with_timestamp(phase.end);
+    }
+
+    cx.span().end_with_timestamp(end);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use opentelemetry::trace::TracerProvider;
+    use opentelemetry_sdk::trace::InMemorySpanExporter;
+
+    const TRACEPARENT: &str = "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01";
+
+    fn phase(started_at: SystemTime, start_ms: u64, end_ms: u64) -> Option<PhaseSpan> {
+        Some(PhaseSpan {
+            start: started_at + Duration::from_millis(start_ms),
+            end: started_at + Duration::from_millis(end_ms),
+        })
+    }
+
+    fn statement() -> StatementTrace {
+        let started_at = SystemTime::now();
+        StatementTrace {
+            client_id: 1,
+            started_at,
+            duration: Duration::from_millis(100),
+            statement_type: "select",
+            protocol: "simple",
+            encrypted: true,
+            encrypted_values_count: 2,
+            parse: phase(started_at, 1, 5),
+            encrypt: phase(started_at, 10, 30),
+            encrypt_requests: 1,
+            server_wait: phase(started_at, 35, 85),
+            decrypt: None,
+            decrypt_requests: 0,
+            traceparent: None,
+        }
+    }
+
+    #[test]
+    fn parse_traceparent() {
+        let traceparent = TraceParent::parse(TRACEPARENT).unwrap();
+
+        assert_eq!(
+            TraceId::from_bytes(traceparent.trace_id).to_string(),
+            "4bf92f3577b34da6a3ce929d0e0e4736"
+        );
+        assert_eq!(
+            SpanId::from_bytes(traceparent.span_id).to_string(),
+            "00f067aa0ba902b7"
+        );
+        assert_eq!(traceparent.flags, 1);
+    }
+
+    #[test]
+    fn parse_invalid_traceparent() {
+        for value in [
+            "",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7",
+            "00-4BF92F3577B34DA6A3CE929D0E0E4736-00f067aa0ba902b7-01",
+            "00-00000000000000000000000000000000-00f067aa0ba902b7-01",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-0000000000000000-01",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01-extra",
+            "ff-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01",
+        ] {
+            assert!(TraceParent::parse(value).is_none(), "{value}");
+        }
+    }
+
+    #[test]
+    fn traceparent_from_sql_comment() {
+        let sql = format!("SELECT * FROM users /*action='index',traceparent='{TRACEPARENT}'*/");
+        assert_eq!(
+            TraceParent::from_sql_comment(&sql),
+            TraceParent::parse(TRACEPARENT)
+        );
+
+        let sql = format!("SELECT '{TRACEPARENT}' AS traceparent");
+        assert!(TraceParent::from_sql_comment(&sql).is_none());
+    }
+
+    #[test]
+    fn records_statement_with_phase_spans() {
+        let exporter = InMemorySpanExporter::default();
+        let provider = SdkTracerProvider::builder()
+            .with_simple_exporter(exporter.clone())
+            .build();
+        let tracer = provider.tracer("test");
+
+        let mut statement = statement();
+        statement.traceparent = TraceParent::parse(TRACEPARENT);
+
+        record_statement_with(&tracer, &statement);
+
+        let spans = exporter.get_finished_spans().unwrap();
+        let names = spans.iter().map(|s| s.name.as_ref()).collect::<Vec<_>>();
+        assert_eq!(names, ["parse", "encrypt", "server_wait", "statement"]);
+
+        let root = spans.iter().find(|s| s.name == "statement").unwrap();
+        assert_eq!(
+            root.span_context.trace_id().to_string(),
+            "4bf92f3577b34da6a3ce929d0e0e4736"
+        );
+        assert_eq!(root.parent_span_id.to_string(), "00f067aa0ba902b7");
+        assert_eq!(root.span_kind, SpanKind::Server);
+
+        for span in spans.iter().filter(|s| s.name != "statement") {
+            assert_eq!(span.parent_span_id, root.span_context.span_id());
+        }
+
+        let encrypt = spans.iter().find(|s| s.name == "encrypt").unwrap();
+        assert!(encrypt
+            .attributes
+            .contains(&KeyValue::new("zerokms.requests", 1_i64)));
+        assert_eq!(
+            encrypt.start_time,
+            statement.started_at + Duration::from_millis(10)
+        );
+        assert_eq!(
+            encrypt.end_time,
+            statement.started_at + Duration::from_millis(30)
+        );
+    }
+
+    #[test]
+    fn records_statement_without_traceparent_as_root() {
+        let exporter = InMemorySpanExporter::default();
+        let provider = SdkTracerProvider::builder()
+            .with_simple_exporter(exporter.clone())
+            .build();
+        let tracer = provider.tracer("test");
+
+        record_statement_with(&tracer, &statement());
+
+        let spans = exporter.get_finished_spans().unwrap();
+        let root = spans.iter().find(|s| s.name == "statement").unwrap();
+        assert_eq!(root.parent_span_id, SpanId::INVALID);
+    }
+
+    #[test]
+    fn exports_statement_to_otlp_endpoint() {
+        use std::io::{BufRead, BufReader, Read, Write};
+
+        // A collector that accepts one export request
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let collector = std::thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream);
+
+            let mut headers = vec![];
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                if line.trim().is_empty() {
+                    break;
+                }
+                headers.push(line.trim().to_lowercase());
+            }
+
+            let length = headers
+                .iter()
+                .find_map(|header| header.strip_prefix("content-length:"))
+                .map(|length| length.trim().parse::<usize>().unwrap())
+                .unwrap_or_default();
+            let mut body = vec![0; length];
+            reader.read_exact(&mut body).unwrap();
+
+            reader
+                .get_mut()
+                .write_all(b"HTTP/1.1 200 OK\r\ncontent-length: 0\r\n\r\n")
+                .unwrap();
+            (headers, body)
+        });
+
+        let config = TelemetryConfig {
+            enabled: true,
+            endpoint: format!("http://{addr}/v1/traces"),
+            service_name: "proxy-telemetry-test".to_string(),
+        };
+        let provider = tracer_provider(&config).unwrap();
+
+        record_statement_with(&provider.tracer(TRACER_NAME), &statement());
+        provider.force_flush().unwrap();
+
+        let (headers, body) = collector.join().unwrap();
+        assert_eq!(headers[0], "post /v1/traces http/1.1");
+        assert!(headers.contains(&"content-type: application/x-protobuf".to_string()));
+
+        let contains = |needle: &[u8]| body.windows(needle.len()).any(|w| w == needle);
+        assert!(contains(b"proxy-telemetry-test"));
+        assert!(contains(b"server_wait"));
+
+        let _ = provider.shutdown();
+    }
+
+    #[test]
+    fn invalid_otlp_endpoint_is_an_error() {
+        let config = TelemetryConfig {
+            enabled: true,
+            endpoint: "not a url".to_string(),
+            ..TelemetryConfig::default()
+        };
+        assert!(tracer_provider(&config).is_err());
+    }
+}
@@ -0,0 +1,160 @@
+use super::configure_server;
+use crate::{config::TlsConfig, error::Error, log::CONFIG};
+use arc_swap::ArcSwap;
+use std::{
+    fs,
+    sync::{Arc, Weak},
+    time::{Duration, SystemTime},
+};
+use tracing::{debug, info, warn};
+
+///
+/// The listener TLS configuration
+///
+/// New handshakes use the current configuration. With path-based configuration the certificate, private key and client CA
+/// files are checked on an interval, and the configuration is rebuilt when any of them change, so rotated certificates are
+/// used without a restart. If the changed files are invalid (for example, only one of a certificate and key pair has been
+/// written yet) the previous configuration is kept until the next change.
+///
+#[derive(Clone, Debug)]
+pub struct ServerTlsManager {
+    server_config: Arc<ArcSwap<rustls::ServerConfig>>,
+}
+
+impl ServerTlsManager {
+    pub fn init(config: &TlsConfig, reload_interval: Duration) -> Result<Self, Error> {
+        let server_config = configure_server(config)?;
+        let server_config = Arc::new(ArcSwap::new(Arc::new(server_config)));
+
+        if matches!(config, TlsConfig::Path { .. }) {
+            init_reloader(
+                config.clone(),
+                Arc::downgrade(&server_config),
+                reload_interval,
+            );
+        }
+
+        Ok(ServerTlsManager { server_config })
+    }
+
+    pub fn load(&self) -> Arc<rustls::ServerConfig> {
+        self.server_config.load_full()
+    }
+}
+
+///
+/// Watches the files of a path-based configuration
+///
+/// The task holds a weak reference, and stops once the manager has been dropped
+/// (for example when the configuration is reloaded and every connection using the old one has closed).
+///
+fn init_reloader(
+    config: TlsConfig,
+    server_config: Weak<ArcSwap<rustls::ServerConfig>>,
+    reload_interval: Duration,
+) {
+    tokio::spawn(async move {
+        let mut modified = files_modified(&config);
+
+        let mut interval = tokio::time::interval_at(
+            tokio::time::Instant::now() + reload_interval,
+            reload_interval,
+        );
+
+        loop {
+            interval.tick().await;
+
+            let Some(server_config) = server_config.upgrade() else {
+                debug!(target: CONFIG, msg = "Server TLS configuration released, stopping reload");
+                return;
+            };
+
+            let current = files_modified(&config);
+            if current == modified {
+                continue;
+            }
+
+            match configure_server(&config) {
+                Ok(reloaded) => {
+                    server_config.store(Arc::new(reloaded));
+                    info!(msg = "Reloaded server Transport Layer Security (TLS) certificate");
+                }
+                Err(err) => {
+                    warn!(
+                        msg = "Error reloading server Transport Layer Security (TLS) certificate",
+                        error = err.to_string()
+                    );
+                }
+            }
+
+            modified = current;
+        }
+    });
+}
+
+fn files_modified(config: &TlsConfig) -> Vec<Option<SystemTime>> {
+    let paths = match config {
+        TlsConfig::Path {
+            certificate_path,
+            private_key_path,
+            client_ca_certificate_path,
+        } => [
+            Some(certificate_path),
+            Some(private_key_path),
+            client_ca_certificate_path.as_ref(),
+        ],
+        TlsConfig::Pem { .. } => return Vec::new(),
+    };
+
+    paths
+        .into_iter()
+        .flatten()
+        .map(|path| fs::metadata(path).and_then(|m| m.modified()).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn reloads_changed_certificate_files() {
+        let _ = rustls::crypto::aws_lc_rs::default_provider().install_default();
+
+        let dir = std::env::temp_dir().join(format!("proxy-tls-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+
+        let certificate_path = dir.join("server.cert");
+        let private_key_path = dir.join("server.key");
+        fs::copy("../../tests/tls/server.cert", &certificate_path).unwrap();
+        fs::copy("../../tests/tls/server.key", &private_key_path).unwrap();
+
+        let config = TlsConfig::Path {
+            certificate_path: certificate_path.to_string_lossy().to_string(),
+            private_key_path: private_key_path.to_string_lossy().to_string(),
+            client_ca_certificate_path: None,
+        };
+
+        let manager = ServerTlsManager::init(&config, Duration::from_millis(10)).unwrap();
+        let initial = manager.load();
+
+        // Unchanged files keep the current configuration
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(Arc::ptr_eq(&initial, &manager.load()));
+
+        // Rotated files are loaded
+        fs::copy("../../tests/tls/localhost.pem", &certificate_path).unwrap();
+        fs::copy("../../tests/tls/localhost-key.pem", &private_key_path).unwrap();
+        let file = fs::File::options()
+            .write(true)
+            .open(&certificate_path)
+            .unwrap();
+        file.set_modified(SystemTime::now() + Duration::from_secs(1))
+            .unwrap();
+
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert!(!Arc::ptr_eq(&initial, &manager.load()));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
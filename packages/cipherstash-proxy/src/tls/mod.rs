@@ -1,12 +1,20 @@
-use crate::{config::TlsConfig, error::Error};
-use crate::{DatabaseConfig, TandemConfig};
+mod manager;
+
+pub use manager::ServerTlsManager;
+
+use crate::config::{SslMode, TlsConfig};
+use crate::error::{ConfigError, Error, TlsConfigError};
+use crate::DatabaseConfig;
 use rustls::client::danger::ServerCertVerifier;
-use rustls::ClientConfig;
+use rustls::client::WebPkiServerVerifier;
+use rustls::server::WebPkiClientVerifier;
+use rustls::{CertificateError, ClientConfig, RootCertStore};
 use rustls_pki_types::{pem::PemObject, CertificateDer, PrivateKeyDer, ServerName};
-use rustls_platform_verifier::ConfigVerifierExt;
+use rustls_platform_verifier::Verifier;
 use std::sync::Arc;
 use tokio::net::TcpStream;
 use tokio_rustls::{TlsAcceptor, TlsConnector, TlsStream};
+use x509_parser::prelude::{FromDer, GeneralName, X509Certificate};
 
 ///
 /// Create a Server TLS connection
@@ -14,11 +22,11 @@ use tokio_rustls::{TlsAcceptor, TlsConnector, TlsStream};
 ///
 pub async fn client(
     stream: TcpStream,
-    config: &TandemConfig,
+    database: &DatabaseConfig,
 ) -> Result<TlsStream<TcpStream>, Error> {
-    let tls_config = configure_client(&config.database);
+    let tls_config = configure_client(database)?;
     let connector = TlsConnector::from(Arc::new(tls_config));
-    let domain = config.database.server_name()?.to_owned();
+    let domain = database.server_name()?.to_owned();
     let tls_stream = connector.connect(domain, stream).await?;
 
     Ok(tls_stream.into())
@@ -28,9 +36,13 @@ pub async fn client(
 /// Create a Server TLS connection
 /// The returned type is the higher-level TlsStream that wraps both Client & Server variants
 ///
-pub async fn server(stream: TcpStream, config: &TlsConfig) -> Result<TlsStream<TcpStream>, Error> {
-    let server_config = configure_server(config)?;
-    let acceptor = TlsAcceptor::from(Arc::new(server_config));
+/// The handshake uses the current configuration of the manager, so a reloaded certificate applies to new connections.
+///
+pub async fn server(
+    stream: TcpStream,
+    server_tls: &ServerTlsManager,
+) -> Result<TlsStream<TcpStream>, Error> {
+    let acceptor = TlsAcceptor::from(server_tls.load());
     let tls_stream = acceptor.accept(stream).await?;
 
     Ok(tls_stream.into())
@@ -43,6 +55,10 @@ pub async fn server(stream: TcpStream, config: &TlsConfig) -> Result<TlsStream<T
 /// Depending on whether the config is Pem or Path, this function will try to parse certificate and
 /// private_key from the string contents or the file contents.
 ///
+/// If a client CA certificate is configured, client certificates are verified against it.
+/// Clients without a certificate are still accepted by the handshake, and `server.require_client_certificate`
+/// is enforced once the connection has started, so the client receives a Postgres error rather than a TLS alert.
+///
 pub fn configure_server(config: &TlsConfig) -> Result<rustls::ServerConfig, Error> {
     let certs = match config {
         TlsConfig::Pem {
@@ -68,9 +84,41 @@ pub fn configure_server(config: &TlsConfig) -> Result<rustls::ServerConfig, Erro
         } => PrivateKeyDer::from_pem_file(private_key),
     }?;
 
-    let server_config = rustls::ServerConfig::builder()
-        .with_no_client_auth()
-        .with_single_cert(certs, key)?;
+    let client_ca_certs = match config {
+        TlsConfig::Pem {
+            client_ca_certificate_pem: Some(certificate),
+            ..
+        } => Some(
+            CertificateDer::pem_slice_iter(certificate.as_bytes())
+                .collect::<Result<Vec<_>, _>>()?,
+        ),
+        TlsConfig::Path {
+            client_ca_certificate_path: Some(certificate),
+            ..
+        } => Some(CertificateDer::pem_file_iter(certificate)?.collect::<Result<Vec<_>, _>>()?),
+        _ => None,
+    };
+
+    let builder = rustls::ServerConfig::builder();
+
+    let builder = match client_ca_certs {
+        Some(certs) => {
+            let mut roots = RootCertStore::empty();
+            for cert in certs {
+                roots
+                    .add(cert)
+                    .map_err(|_| ConfigError::from(TlsConfigError::InvalidClientCaCertificate))?;
+            }
+            let verifier = WebPkiClientVerifier::builder(Arc::new(roots))
+                .allow_unauthenticated()
+                .build()
+                .map_err(|_| ConfigError::from(TlsConfigError::InvalidClientCaCertificate))?;
+            builder.with_client_cert_verifier(verifier)
+        }
+        None => builder.with_no_client_auth(),
+    };
+
+    let server_config = builder.with_single_cert(certs, key)?;
 
     Ok(server_config)
 }
@@ -78,17 +126,277 @@ pub fn configure_server(config: &TlsConfig) -> Result<rustls::ServerConfig, Erro
 ///
 /// Configure the client TLS settings
 /// These are the settings for connecting to the database with TLS
-/// The client will use the system root certificates
 ///
-pub fn configure_client(config: &DatabaseConfig) -> ClientConfig {
-    let mut tls_config = ClientConfig::with_platform_verifier();
+/// The `sslmode` decides how the database certificate is verified:
+///   - `disable`, `prefer` and `require` do not verify the certificate
+///   - `verify-ca` verifies the certificate chain, but not the host name
+///   - `verify-full` verifies the certificate chain and the host name
+///
+/// The chain is verified against the configured CA certificates, or the system root certificates if none are configured.
+/// A client certificate is presented to the database if one is configured.
+///
+pub fn configure_client(config: &DatabaseConfig) -> Result<ClientConfig, Error> {
+    let ssl_mode = config.ssl_mode();
+
+    let verifier: Arc<dyn ServerCertVerifier> = if ssl_mode.verifies_certificate() {
+        let verifier: Arc<dyn ServerCertVerifier> = match database_ca_certificates(config)? {
+            Some(certificates) => {
+                let mut roots = RootCertStore::empty();
+                for certificate in certificates {
+                    roots.add(certificate).map_err(|_| {
+                        ConfigError::from(TlsConfigError::InvalidDatabaseTls {
+                            name: "CA certificate",
+                        })
+                    })?;
+                }
+                WebPkiServerVerifier::builder(Arc::new(roots))
+                    .build()
+                    .map_err(|_| {
+                        ConfigError::from(TlsConfigError::InvalidDatabaseTls {
+                            name: "CA certificate",
+                        })
+                    })?
+            }
+            None => Arc::new(Verifier::new()),
+        };
+
+        match ssl_mode {
+            SslMode::VerifyCa => Arc::new(NoHostnameVerification { verifier }),
+            _ => verifier,
+        }
+    } else {
+        Arc::new(NoCertificateVerification {})
+    };
+
+    let builder = ClientConfig::builder()
+        .dangerous()
+        .with_custom_certificate_verifier(verifier);
+
+    let tls_config = match database_client_certificate(config)? {
+        Some((certificates, private_key)) => {
+            builder.with_client_auth_cert(certificates, private_key)?
+        }
+        None => builder.with_no_client_auth(),
+    };
+
+    Ok(tls_config)
+}
+
+///
+/// The configured CA certificates for verifying the database, if any
+///
+fn database_ca_certificates(
+    config: &DatabaseConfig,
+) -> Result<Option<Vec<CertificateDer<'static>>>, ConfigError> {
+    let name = "CA certificate";
+
+    let certificates = match (&config.ca_certificate_path, &config.ca_certificate_pem) {
+        (Some(_), Some(_)) => return Err(TlsConfigError::AmbiguousDatabaseTls { name }.into()),
+        (Some(path), None) => {
+            CertificateDer::pem_file_iter(path).and_then(|iter| iter.collect::<Result<Vec<_>, _>>())
+        }
+        (None, Some(pem)) => {
+            CertificateDer::pem_slice_iter(pem.as_bytes()).collect::<Result<Vec<_>, _>>()
+        }
+        (None, None) => return Ok(None),
+    };
+
+    match certificates {
+        Ok(certificates) if !certificates.is_empty() => Ok(Some(certificates)),
+        _ => Err(TlsConfigError::InvalidDatabaseTls { name }.into()),
+    }
+}
+
+///
+/// The configured client certificate chain and private key for authenticating to the database, if any
+///
+fn database_client_certificate(
+    config: &DatabaseConfig,
+) -> Result<Option<(Vec<CertificateDer<'static>>, PrivateKeyDer<'static>)>, ConfigError> {
+    let certificate_name = "client certificate";
+    let private_key_name = "client private key";
+
+    let certificates = match (
+        &config.client_certificate_path,
+        &config.client_certificate_pem,
+    ) {
+        (Some(_), Some(_)) => {
+            return Err(TlsConfigError::AmbiguousDatabaseTls {
+                name: certificate_name,
+            }
+            .into())
+        }
+        (Some(path), None) => Some(
+            CertificateDer::pem_file_iter(path)
+                .and_then(|iter| iter.collect::<Result<Vec<_>, _>>()),
+        ),
+        (None, Some(pem)) => {
+            Some(CertificateDer::pem_slice_iter(pem.as_bytes()).collect::<Result<Vec<_>, _>>())
+        }
+        (None, None) => None,
+    };
 
-    if !config.with_tls_verification {
-        let mut dangerous = tls_config.dangerous();
-        dangerous.set_certificate_verifier(Arc::new(NoCertificateVerification {}));
+    let private_key = match (
+        &config.client_private_key_path,
+        &config.client_private_key_pem,
+    ) {
+        (Some(_), Some(_)) => {
+            return Err(TlsConfigError::AmbiguousDatabaseTls {
+                name: private_key_name,
+            }
+            .into())
+        }
+        (Some(path), None) => Some(PrivateKeyDer::from_pem_file(path)),
+        (None, Some(pem)) => Some(PrivateKeyDer::from_pem_slice(pem.as_bytes())),
+        (None, None) => None,
+    };
+
+    match (certificates, private_key) {
+        (Some(certificates), Some(private_key)) => {
+            let certificates = match certificates {
+                Ok(certificates) if !certificates.is_empty() => certificates,
+                _ => {
+                    return Err(TlsConfigError::InvalidDatabaseTls {
+                        name: certificate_name,
+                    }
+                    .into())
+                }
+            };
+            let private_key = private_key.map_err(|_| TlsConfigError::InvalidDatabaseTls {
+                name: private_key_name,
+            })?;
+            Ok(Some((certificates, private_key)))
+        }
+        (None, None) => Ok(None),
+        _ => Err(TlsConfigError::IncompleteDatabaseClientCertificate.into()),
     }
+}
 
-    tls_config
+///
+/// Verifies the certificate chain, but accepts a certificate issued for a different host name.
+/// This is `sslmode=verify-ca`.
+///
+/// The wrapped verifier checks the chain before the host name, so a host name error means the chain is trusted.
+///
+#[derive(Debug)]
+pub struct NoHostnameVerification {
+    verifier: Arc<dyn ServerCertVerifier>,
+}
+
+impl ServerCertVerifier for NoHostnameVerification {
+    fn verify_server_cert(
+        &self,
+        end_entity: &rustls_pki_types::CertificateDer<'_>,
+        intermediates: &[rustls_pki_types::CertificateDer<'_>],
+        server_name: &ServerName<'_>,
+        ocsp_response: &[u8],
+        now: rustls_pki_types::UnixTime,
+    ) -> Result<rustls::client::danger::ServerCertVerified, rustls::Error> {
+        match self.verifier.verify_server_cert(
+            end_entity,
+            intermediates,
+            server_name,
+            ocsp_response,
+            now,
+        ) {
+            Err(rustls::Error::InvalidCertificate(
+                CertificateError::NotValidForName | CertificateError::NotValidForNameContext { .. },
+            )) => Ok(rustls::client::danger::ServerCertVerified::assertion()),
+            result => result,
+        }
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &rustls_pki_types::CertificateDer<'_>,
+        dss: &rustls::DigitallySignedStruct,
+    ) -> Result<rustls::client::danger::HandshakeSignatureValid, rustls::Error> {
+        self.verifier.verify_tls12_signature(message, cert, dss)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &rustls_pki_types::CertificateDer<'_>,
+        dss: &rustls::DigitallySignedStruct,
+    ) -> Result<rustls::client::danger::HandshakeSignatureValid, rustls::Error> {
+        self.verifier.verify_tls13_signature(message, cert, dss)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<rustls::SignatureScheme> {
+        self.verifier.supported_verify_schemes()
+    }
+}
+
+///
+/// The identity of a client that connected with a verified certificate
+///
+#[derive(Clone, Debug, PartialEq)]
+pub struct ClientIdentity {
+    pub subject: String,
+    pub common_name: Option<String>,
+    pub subject_alt_names: Vec<String>,
+}
+
+impl ClientIdentity {
+    ///
+    /// The identity of the end-entity certificate, if it can be parsed
+    ///
+    /// DNS, URI and email subject alternative names are included, in certificate order.
+    ///
+    pub fn from_certificate(certificate: &CertificateDer<'_>) -> Option<Self> {
+        let (_, certificate) = X509Certificate::from_der(certificate).ok()?;
+
+        let subject = certificate.subject().to_string();
+
+        let common_name = certificate
+            .subject()
+            .iter_common_name()
+            .next()
+            .and_then(|cn| cn.as_str().ok())
+            .map(str::to_string);
+
+        let subject_alt_names = match certificate.subject_alternative_name() {
+            Ok(Some(extension)) => extension
+                .value
+                .general_names
+                .iter()
+                .filter_map(|name| match name {
+                    GeneralName::DNSName(name)
+                    | GeneralName::URI(name)
+                    | GeneralName::RFC822Name(name) => Some(name.to_string()),
+                    _ => None,
+                })
+                .collect(),
+            _ => Vec::new(),
+        };
+
+        Some(Self {
+            subject,
+            common_name,
+            subject_alt_names,
+        })
+    }
+
+    ///
+    /// The name that identifies the client
+    /// The first subject alternative name, falling back to the common name and then the whole subject.
+    ///
+    /// Service mesh workload identities (such as SPIFFE IDs) are carried in a URI subject alternative name.
+    ///
+    pub fn name(&self) -> &str {
+        self.subject_alt_names
+            .first()
+            .or(self.common_name.as_ref())
+            .unwrap_or(&self.subject)
+    }
+}
+
+impl std::fmt::Display for ClientIdentity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name())
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -205,6 +513,7 @@ B+qwsnNEiDoJhgYj+cQ=
         let tls_config = TlsConfig::Path {
             private_key_path: "../../tests/tls/server.key".to_string(),
             certificate_path: "../../tests/tls/server.cert".to_string(),
+            client_ca_certificate_path: None,
         };
         let server_config = configure_server(&tls_config);
 
@@ -218,6 +527,7 @@ B+qwsnNEiDoJhgYj+cQ=
         let tls_config = TlsConfig::Pem {
             private_key_pem: "../../tests/tls/server.key".to_string(),
             certificate_pem: "../../tests/tls/server.cert".to_string(),
+            client_ca_certificate_pem: None,
         };
         let server_config = configure_server(&tls_config);
 
@@ -231,6 +541,7 @@ B+qwsnNEiDoJhgYj+cQ=
         let tls_config = TlsConfig::Path {
             private_key_path: private_key_pem(),
             certificate_path: certificate_pem(),
+            client_ca_certificate_path: None,
         };
         let server_config = configure_server(&tls_config);
 
@@ -244,9 +555,119 @@ B+qwsnNEiDoJhgYj+cQ=
         let tls_config = TlsConfig::Pem {
             private_key_pem: private_key_pem(),
             certificate_pem: certificate_pem(),
+            client_ca_certificate_pem: None,
         };
         let server_config = configure_server(&tls_config);
 
         assert!(server_config.is_ok());
     }
+
+    #[test]
+    fn test_configure_server_with_client_ca() {
+        let _ = rustls::crypto::aws_lc_rs::default_provider().install_default();
+
+        let tls_config = TlsConfig::Pem {
+            private_key_pem: private_key_pem(),
+            certificate_pem: certificate_pem(),
+            client_ca_certificate_pem: Some(certificate_pem()),
+        };
+        assert!(configure_server(&tls_config).is_ok());
+
+        let tls_config = TlsConfig::Path {
+            private_key_path: "../../tests/tls/server.key".to_string(),
+            certificate_path: "../../tests/tls/server.cert".to_string(),
+            client_ca_certificate_path: Some("/path/to/non-existent/file".to_string()),
+        };
+        assert!(configure_server(&tls_config).is_err());
+    }
+
+    #[test]
+    fn test_client_identity_from_certificate() {
+        let certificate = CertificateDer::from_pem_slice(certificate_pem().as_bytes()).unwrap();
+        let identity = ClientIdentity::from_certificate(&certificate).unwrap();
+
+        assert_eq!(identity.subject, "CN=localhost");
+        assert_eq!(identity.common_name.as_deref(), Some("localhost"));
+        assert!(identity.subject_alt_names.is_empty());
+        assert_eq!(identity.name(), "localhost");
+
+        let identity = ClientIdentity {
+            subject_alt_names: vec!["spiffe://cluster.local/ns/app/sa/api".to_string()],
+            ..identity
+        };
+        assert_eq!(identity.name(), "spiffe://cluster.local/ns/app/sa/api");
+    }
+
+    #[test]
+    fn test_configure_client_defaults() {
+        let _ = rustls::crypto::aws_lc_rs::default_provider().install_default();
+
+        let config = DatabaseConfig::for_testing();
+        assert!(configure_client(&config).is_ok());
+    }
+
+    #[test]
+    fn test_configure_client_with_ca_certificate() {
+        let _ = rustls::crypto::aws_lc_rs::default_provider().install_default();
+
+        let mut config = DatabaseConfig::for_testing();
+        config.ssl_mode = Some(SslMode::VerifyFull);
+        config.ca_certificate_path = Some("../../tests/tls/server.cert".to_string());
+        assert!(configure_client(&config).is_ok());
+
+        config.ca_certificate_path = None;
+        config.ca_certificate_pem = Some(certificate_pem());
+        assert!(configure_client(&config).is_ok());
+
+        config.ssl_mode = Some(SslMode::VerifyCa);
+        assert!(configure_client(&config).is_ok());
+    }
+
+    #[test]
+    fn test_configure_client_with_invalid_ca_certificate() {
+        let _ = rustls::crypto::aws_lc_rs::default_provider().install_default();
+
+        let mut config = DatabaseConfig::for_testing();
+        config.ssl_mode = Some(SslMode::VerifyFull);
+        config.ca_certificate_pem = Some("-----INVALID PEM-----".to_string());
+        assert!(configure_client(&config).is_err());
+
+        config.ca_certificate_pem = None;
+        config.ca_certificate_path = Some("/path/to/non-existent/file".to_string());
+        assert!(configure_client(&config).is_err());
+
+        config.ca_certificate_pem = Some(certificate_pem());
+        assert!(configure_client(&config).is_err());
+    }
+
+    #[test]
+    fn test_configure_client_with_client_certificate() {
+        let _ = rustls::crypto::aws_lc_rs::default_provider().install_default();
+
+        let mut config = DatabaseConfig::for_testing();
+        config.client_certificate_pem = Some(certificate_pem());
+        config.client_private_key_pem = Some(private_key_pem());
+
+        let tls_config = configure_client(&config).unwrap();
+        assert!(tls_config.client_auth_cert_resolver.has_certs());
+
+        config.client_certificate_pem = None;
+        config.client_certificate_path = Some("../../tests/tls/server.cert".to_string());
+        config.client_private_key_pem = None;
+        config.client_private_key_path = Some("../../tests/tls/server.key".to_string());
+        assert!(configure_client(&config).is_ok());
+    }
+
+    #[test]
+    fn test_configure_client_with_incomplete_client_certificate() {
+        let _ = rustls::crypto::aws_lc_rs::default_provider().install_default();
+
+        let mut config = DatabaseConfig::for_testing();
+        config.client_certificate_pem = Some(certificate_pem());
+        assert!(configure_client(&config).is_err());
+
+        config.client_certificate_pem = None;
+        config.client_private_key_pem = Some(private_key_pem());
+        assert!(configure_client(&config).is_err());
+    }
 }
@@ -0,0 +1,678 @@
+//! The grammar of the function, binary operator and type declarations used by `eql-mapper`.
+//!
+//! Declarations are parsed into the plain syntax tree in this crate, and each consumer builds its own representation
+//! from the tree:
+//!
+//! - `eql-mapper-macros` parses the built-in declarations at compile time (`functions!`, `binary_operators!`, `ty!`
+//!   and friends) and generates the code that constructs them.
+//! - `eql_mapper::SqlDecls` parses declarations supplied by users at runtime.
+//!
+//! Sharing the grammar means the two cannot drift apart: a declaration that compiles in `sql_decls.rs` can also be
+//! declared by a user, and vice versa (subject to the restrictions `SqlDecls` places on concrete columns).
+//!
+//! ```text
+//! pg_catalog.min<T>(T) -> T where T: Ord;
+//! <T>(T -> <T as JsonLike>::Accessor) -> <T as JsonLike>::Output where T: JsonLike;
+//! <T>(T OPERATOR(app.~=) T) -> Native where T: Eq;
+//! ```
+
+use proc_macro2::{Delimiter, Spacing, Span, TokenTree};
+use syn::{
+    braced, bracketed,
+    ext::IdentExt,
+    parenthesized,
+    parse::{Parse, ParseStream},
+    punctuated::Punctuated,
+    token, Ident, Token,
+};
+
+mod kw {
+    syn::custom_keyword!(Contain);
+    syn::custom_keyword!(EQL);
+    syn::custom_keyword!(Eq);
+    syn::custom_keyword!(JsonLike);
+    syn::custom_keyword!(Native);
+    syn::custom_keyword!(OPERATOR);
+    syn::custom_keyword!(Ord);
+    syn::custom_keyword!(SetOf);
+    syn::custom_keyword!(TokenMatch);
+}
+
+/// `Eq`, `Ord`, `TokenMatch`, `JsonLike` or `Contain`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EqlTrait {
+    Eq,
+    Ord,
+    TokenMatch,
+    JsonLike,
+    Contain,
+}
+
+/// `Eq + Ord`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EqlTraits(pub Vec<EqlTrait>);
+
+/// A type variable, such as `T`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TVar(pub String);
+
+/// `table.column`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TableColumn {
+    pub table: String,
+    pub column: String,
+}
+
+/// `T: Eq + Ord`, in the `where` clause of a declaration.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BoundsDecl {
+    pub tvar: TVar,
+    pub traits: EqlTraits,
+}
+
+/// A column of a projection type, `T as alias`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProjectionColumnDecl {
+    pub type_decl: TypeDecl,
+    pub alias: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TypeDecl {
+    /// `T` or `T: Eq`. `bounds` is `None` when no bounds are written.
+    Var {
+        tvar: TVar,
+        bounds: Option<EqlTraits>,
+    },
+
+    /// `Native` or `Native(table.column)`
+    Native(Option<TableColumn>),
+
+    /// `EQL(table.column)` or `EQL(table.column: Eq)`
+    Eql {
+        table_column: TableColumn,
+        bounds: Option<EqlTraits>,
+    },
+
+    /// `SetOf<T>`
+    SetOf(Box<TypeDecl>),
+
+    /// `[T]`
+    Array(Box<TypeDecl>),
+
+    /// `<T as JsonLike>::Path`
+    AssociatedType {
+        tvar: TVar,
+        as_eql_trait: EqlTrait,
+        type_name: String,
+    },
+
+    /// `{Native as id, EQL(customer.email) as email}`
+    Projection(Vec<ProjectionColumnDecl>),
+}
+
+/// The operator of a binary operator declaration.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BinaryOp {
+    /// An operator written with symbols, such as `=`, `->>` or `~~*`.
+    Symbol(String),
+
+    /// `OPERATOR(schema.op)`
+    Qualified { schema: String, op: String },
+}
+
+/// `schema.name<T>(T, Native) -> T where T: Eq`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FunctionDecl {
+    pub schema: String,
+    pub name: String,
+    pub generic_args: Vec<TVar>,
+    pub args: Vec<TypeDecl>,
+    pub ret: TypeDecl,
+    pub bounds: Vec<BoundsDecl>,
+}
+
+/// `<T>(T = T) -> Native where T: Eq`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BinaryOpDecl {
+    pub generic_args: Vec<TVar>,
+    pub lhs: TypeDecl,
+    pub op: BinaryOp,
+    pub rhs: TypeDecl,
+    pub ret: TypeDecl,
+    pub bounds: Vec<BoundsDecl>,
+}
+
+/// A function or binary operator declaration.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Decl {
+    Function(FunctionDecl),
+    BinaryOp(BinaryOpDecl),
+}
+
+/// `;`-separated function and binary operator declarations, in any order.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Decls(pub Vec<Decl>);
+
+/// `;`-separated function declarations.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FunctionDecls(pub Vec<FunctionDecl>);
+
+/// `;`-separated binary operator declarations.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BinaryOpDecls(pub Vec<BinaryOpDecl>);
+
+impl Parse for EqlTrait {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        if input.peek(kw::Eq) {
+            kw::Eq::parse(input)?;
+            return Ok(EqlTrait::Eq);
+        }
+
+        if input.peek(kw::Ord) {
+            kw::Ord::parse(input)?;
+            return Ok(EqlTrait::Ord);
+        }
+
+        if input.peek(kw::TokenMatch) {
+            kw::TokenMatch::parse(input)?;
+            return Ok(EqlTrait::TokenMatch);
+        }
+
+        if input.peek(kw::JsonLike) {
+            kw::JsonLike::parse(input)?;
+            return Ok(EqlTrait::JsonLike);
+        }
+
+        if input.peek(kw::Contain) {
+            kw::Contain::parse(input)?;
+            return Ok(EqlTrait::Contain);
+        }
+
+        Err(syn::Error::new(
+            input.span(),
+            format!(
+                "Expected Eq, Ord, TokenMatch, JsonLike or Contain while parsing EqlTrait; got: {}",
+                input.cursor().token_stream()
+            ),
+        ))
+    }
+}
+
+impl Parse for EqlTraits {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let mut traits = vec![EqlTrait::parse(input)?];
+
+        while input.peek(Token![+]) {
+            let _: Token![+] = input.parse()?;
+            traits.push(EqlTrait::parse(input)?);
+        }
+
+        Ok(Self(traits))
+    }
+}
+
+impl Parse for TVar {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        Ok(Self(Ident::parse(input)?.to_string()))
+    }
+}
+
+impl Parse for TableColumn {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let table = Ident::parse(input)?.to_string();
+        let _: Token![.] = input.parse()?;
+        let column = Ident::parse(input)?.to_string();
+
+        Ok(Self { table, column })
+    }
+}
+
+impl Parse for BoundsDecl {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let tvar = TVar::parse(input)?;
+        let _: Token![:] = input.parse()?;
+        let traits = EqlTraits::parse(input)?;
+
+        Ok(Self { tvar, traits })
+    }
+}
+
+impl Parse for ProjectionColumnDecl {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let type_decl = TypeDecl::parse(input)?;
+        let alias = if input.peek(Token![as]) {
+            let _: Token![as] = input.parse()?;
+            Some(Ident::parse(input)?.to_string())
+        } else {
+            None
+        };
+
+        Ok(Self { type_decl, alias })
+    }
+}
+
+impl TypeDecl {
+    fn parse_associated_type(input: ParseStream) -> syn::Result<Self> {
+        let _: Token![<] = input.parse()?;
+        let tvar = TVar::parse(input)?;
+        let _: Token![as] = input.parse()?;
+        let as_eql_trait = EqlTrait::parse(input)?;
+        let _: Token![>] = input.parse()?;
+        let _: Token![::] = input.parse()?;
+        let type_name = Ident::parse(input)?.to_string();
+
+        Ok(TypeDecl::AssociatedType {
+            tvar,
+            as_eql_trait,
+            type_name,
+        })
+    }
+
+    fn parse_set_of(input: ParseStream) -> syn::Result<Self> {
+        let _: kw::SetOf = input.parse()?;
+        let _: Token![<] = input.parse()?;
+        let type_decl = TypeDecl::parse(input)?;
+        let _: Token![>] = input.parse()?;
+
+        Ok(TypeDecl::SetOf(Box::new(type_decl)))
+    }
+
+    fn parse_native(input: ParseStream) -> syn::Result<Self> {
+        let _: kw::Native = input.parse()?;
+        if input.peek(token::Paren) {
+            let content;
+            parenthesized!(content in input);
+            Ok(TypeDecl::Native(Some(TableColumn::parse(&content)?)))
+        } else {
+            Ok(TypeDecl::Native(None))
+        }
+    }
+
+    fn parse_eql(input: ParseStream) -> syn::Result<Self> {
+        let _: kw::EQL = input.parse()?;
+
+        let content;
+        parenthesized!(content in input);
+
+        let table_column = TableColumn::parse(&content)?;
+        let bounds = if content.peek(Token![:]) {
+            let _: Token![:] = content.parse()?;
+            Some(EqlTraits::parse(&content)?)
+        } else {
+            None
+        };
+
+        Ok(TypeDecl::Eql {
+            table_column,
+            bounds,
+        })
+    }
+
+    fn parse_var(input: ParseStream) -> syn::Result<Self> {
+        let tvar = TVar::parse(input)?;
+        let bounds = if input.peek(Token![:]) {
+            let _: Token![:] = input.parse()?;
+            Some(EqlTraits::parse(input)?)
+        } else {
+            None
+        };
+
+        Ok(TypeDecl::Var { tvar, bounds })
+    }
+
+    fn parse_array(input: ParseStream) -> syn::Result<Self> {
+        let content;
+        bracketed!(content in input);
+
+        Ok(TypeDecl::Array(Box::new(TypeDecl::parse(&content)?)))
+    }
+
+    fn parse_projection(input: ParseStream) -> syn::Result<Self> {
+        let content;
+        braced!(content in input);
+
+        let columns =
+            Punctuated::<ProjectionColumnDecl, Token![,]>::parse_separated_nonempty(&content)?
+                .into_iter()
+                .collect();
+
+        Ok(TypeDecl::Projection(columns))
+    }
+}
+
+impl Parse for TypeDecl {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        // The keywords `SetOf`, `Native` and `EQL` are also valid type variable names, so they are tried first.
+        let alternatives: [fn(ParseStream) -> syn::Result<TypeDecl>; 7] = [
+            TypeDecl::parse_associated_type,
+            TypeDecl::parse_set_of,
+            TypeDecl::parse_native,
+            TypeDecl::parse_eql,
+            TypeDecl::parse_var,
+            TypeDecl::parse_array,
+            TypeDecl::parse_projection,
+        ];
+
+        for parse in alternatives {
+            if parse(&input.fork()).is_ok() {
+                return parse(input);
+            }
+        }
+
+        Err(syn::Error::new(
+            input.span(),
+            "could not parse as TypeDecl".to_string(),
+        ))
+    }
+}
+
+impl Parse for BinaryOp {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        if input.peek(kw::OPERATOR) {
+            let _: kw::OPERATOR = input.parse()?;
+            let content;
+            parenthesized!(content in input);
+            let schema = Ident::parse_any(&content)?.to_string();
+            let _: Token![.] = content.parse()?;
+            let op = parse_symbol(&content, false)?;
+            if !content.is_empty() {
+                return Err(content.error("expected ')'"));
+            }
+            return Ok(BinaryOp::Qualified { schema, op });
+        }
+
+        Ok(BinaryOp::Symbol(parse_symbol(input, true)?))
+    }
+}
+
+/// Parses the punctuation characters of an operator.
+///
+/// Operator characters written together (`->>`) are one operator; the operator ends at whitespace, or at the first
+/// character that is not punctuation. With `stop_at_space` unset, punctuation runs to the end of the input.
+fn parse_symbol(input: ParseStream, stop_at_space: bool) -> syn::Result<String> {
+    input.step(|cursor| {
+        let mut rest = *cursor;
+        let mut symbol = String::new();
+
+        while let Some((punct, next)) = rest.punct() {
+            symbol.push(punct.as_char());
+            rest = next;
+            if stop_at_space && punct.spacing() == Spacing::Alone {
+                break;
+            }
+        }
+
+        if symbol.is_empty() {
+            Err(cursor.error("expected a binary operator"))
+        } else {
+            Ok((symbol, rest))
+        }
+    })
+}
+
+/// `<A, B>`, or nothing.
+fn parse_generic_args(input: ParseStream) -> syn::Result<Vec<TVar>> {
+    if input.peek(Token![<]) {
+        let _: Token![<] = input.parse()?;
+        let args = Punctuated::<TVar, Token![,]>::parse_separated_nonempty(input)?
+            .into_iter()
+            .collect();
+        let _: Token![>] = input.parse()?;
+        Ok(args)
+    } else {
+        Ok(Vec::new())
+    }
+}
+
+/// `where T: Eq, U: Ord`, or nothing.
+fn parse_bounds(input: ParseStream) -> syn::Result<Vec<BoundsDecl>> {
+    if input.peek(Token![where]) {
+        let _: Token![where] = input.parse()?;
+        let bounds = Punctuated::<BoundsDecl, Token![,]>::parse_separated_nonempty(input)?;
+        Ok(bounds.into_iter().collect())
+    } else {
+        Ok(Vec::new())
+    }
+}
+
+impl Parse for FunctionDecl {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let schema = Ident::parse_any(input)?.to_string();
+        let _: Token![.] = input.parse()?;
+        let name = Ident::parse_any(input)?.to_string();
+
+        let generic_args = parse_generic_args(input)?;
+
+        let content;
+        parenthesized!(content in input);
+        let args = Punctuated::<TypeDecl, Token![,]>::parse_separated_nonempty(&content)?
+            .into_iter()
+            .collect();
+
+        let _: Token![->] = input.parse()?;
+        let ret = TypeDecl::parse(input)?;
+        let bounds = parse_bounds(input)?;
+
+        Ok(Self {
+            schema,
+            name,
+            generic_args,
+            args,
+            ret,
+            bounds,
+        })
+    }
+}
+
+impl Parse for BinaryOpDecl {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let generic_args = parse_generic_args(input)?;
+
+        let content;
+        parenthesized!(content in input);
+        let lhs = TypeDecl::parse(&content)?;
+        let op = BinaryOp::parse(&content)?;
+        let rhs = TypeDecl::parse(&content)?;
+        if !content.is_empty() {
+            return Err(content.error("expected ')'"));
+        }
+
+        let _: Token![->] = input.parse()?;
+        let ret = TypeDecl::parse(input)?;
+        let bounds = parse_bounds(input)?;
+
+        Ok(Self {
+            generic_args,
+            lhs,
+            op,
+            rhs,
+            ret,
+            bounds,
+        })
+    }
+}
+
+impl Parse for Decl {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        // A binary operator declaration starts with its generic arguments or its parenthesised operands
+        if input.peek(Token![<]) || starts_with_group(input, Delimiter::Parenthesis) {
+            Ok(Decl::BinaryOp(BinaryOpDecl::parse(input)?))
+        } else {
+            Ok(Decl::Function(FunctionDecl::parse(input)?))
+        }
+    }
+}
+
+fn starts_with_group(input: ParseStream, delimiter: Delimiter) -> bool {
+    matches!(input.cursor().token_tree(), Some((TokenTree::Group(group), _)) if group.delimiter() == delimiter)
+}
+
+impl Parse for Decls {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let decls = Punctuated::<Decl, Token![;]>::parse_terminated(input)?
+            .into_iter()
+            .collect();
+        Ok(Self(decls))
+    }
+}
+
+impl Parse for FunctionDecls {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let decls = Punctuated::<FunctionDecl, Token![;]>::parse_terminated(input)?
+            .into_iter()
+            .collect();
+        Ok(Self(decls))
+    }
+}
+
+impl Parse for BinaryOpDecls {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let decls = Punctuated::<BinaryOpDecl, Token![;]>::parse_terminated(input)?
+            .into_iter()
+            .collect();
+        Ok(Self(decls))
+    }
+}
+
+/// A declaration that could not be parsed, with the line (1-based) and column (0-based) of the error.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+/// Parses declarations from a string at runtime.
+///
+/// `//` comments are allowed, as in the macro invocations.
+pub fn parse_str<T: Parse>(src: &str) -> Result<T, ParseError> {
+    syn::parse_str(src).map_err(|err| {
+        let span: Span = err.span();
+        let start = span.start();
+        ParseError {
+            line: start.line,
+            column: start.column,
+            message: err.to_string(),
+        }
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn var(name: &str) -> TypeDecl {
+        TypeDecl::Var {
+            tvar: TVar(name.into()),
+            bounds: None,
+        }
+    }
+
+    #[test]
+    fn parses_function_declarations() {
+        let decls: FunctionDecls = parse_str(
+            "pg_catalog.min<T>(T) -> T where T: Ord;
+             pg_catalog.jsonb_path_query<T>(T, <T as JsonLike>::Path) -> SetOf<T> where T: JsonLike + Contain;",
+        )
+        .unwrap();
+
+        assert_eq!(
+            decls.0[1],
+            FunctionDecl {
+                schema: "pg_catalog".into(),
+                name: "jsonb_path_query".into(),
+                generic_args: vec![TVar("T".into())],
+                args: vec![
+                    var("T"),
+                    TypeDecl::AssociatedType {
+                        tvar: TVar("T".into()),
+                        as_eql_trait: EqlTrait::JsonLike,
+                        type_name: "Path".into(),
+                    }
+                ],
+                ret: TypeDecl::SetOf(Box::new(var("T"))),
+                bounds: vec![BoundsDecl {
+                    tvar: TVar("T".into()),
+                    traits: EqlTraits(vec![EqlTrait::JsonLike, EqlTrait::Contain]),
+                }],
+            }
+        );
+    }
+
+    #[test]
+    fn parses_binary_operator_symbols() {
+        let decls: BinaryOpDecls = parse_str(
+            "<T>(T = T) -> Native where T: Eq;
+             <T>(T <> T) -> Native where T: Eq;
+             <T>(T < T) -> Native where T: Ord;
+             <T>(T ->> <T as JsonLike>::Accessor) -> T where T: JsonLike;
+             <T>(T !~~* <T as TokenMatch>::Tokenized) -> Native where T: TokenMatch; // NOT ILIKE
+             <T>(T || T) -> T",
+        )
+        .unwrap();
+
+        let ops = decls.0.into_iter().map(|decl| decl.op).collect::<Vec<_>>();
+        assert_eq!(
+            ops,
+            ["=", "<>", "<", "->>", "!~~*", "||"]
+                .map(|op| BinaryOp::Symbol(op.into()))
+                .to_vec()
+        );
+    }
+
+    #[test]
+    fn parses_qualified_operators() {
+        let decl: BinaryOpDecl =
+            parse_str("<T>(T OPERATOR(app.~=) T) -> Native where T: Eq").unwrap();
+
+        assert_eq!(
+            decl.op,
+            BinaryOp::Qualified {
+                schema: "app".into(),
+                op: "~=".into()
+            }
+        );
+    }
+
+    #[test]
+    fn parses_mixed_declarations() {
+        let decls: Decls =
+            parse_str("app.f<T>(T) -> T; <T>(T || T) -> T; app.g(Native) -> Native;").unwrap();
+
+        assert!(matches!(
+            &decls.0[..],
+            [Decl::Function(_), Decl::BinaryOp(_), Decl::Function(_)]
+        ));
+    }
+
+    #[test]
+    fn parses_concrete_types() {
+        let decl: TypeDecl = parse_str(
+            "{Native(customer.id) as id, EQL(customer.email: Eq) as email, [EQL(customer.name)]}",
+        )
+        .unwrap();
+
+        let TypeDecl::Projection(columns) = decl else {
+            panic!("expected a projection");
+        };
+        assert_eq!(
+            columns[1].type_decl,
+            TypeDecl::Eql {
+                table_column: TableColumn {
+                    table: "customer".into(),
+                    column: "email".into()
+                },
+                bounds: Some(EqlTraits(vec![EqlTrait::Eq])),
+            }
+        );
+        assert_eq!(columns[2].alias, None);
+    }
+
+    #[test]
+    fn reports_the_position_of_errors() {
+        let err = parse_str::<Decls>("app.f<T>(T) -> T;\napp.g<T>(T) => T").unwrap_err();
+
+        assert_eq!((err.line, err.column), (2, 12));
+    }
+}
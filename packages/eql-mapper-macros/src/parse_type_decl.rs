@@ -1,31 +1,21 @@
+//! Generates the code that builds the declarations parsed by the grammar in `eql_mapper_decls`.
+
+use eql_mapper_decls as decls;
 use proc_macro2::token_stream::TokenStream;
 use quote::{quote, ToTokens, TokenStreamExt};
 use syn::{
-    braced, bracketed, parenthesized,
+    braced,
     parse::{Parse, ParseStream},
     punctuated::Punctuated,
     token::{self},
     Ident, Token, TypePath,
 };
 
-mod kw {
-    syn::custom_keyword!(Accessor);
-    syn::custom_keyword!(Contain);
-    syn::custom_keyword!(EQL);
-    syn::custom_keyword!(Eq);
-    syn::custom_keyword!(Full);
-    syn::custom_keyword!(JsonLike);
-    syn::custom_keyword!(Native);
-    syn::custom_keyword!(Only);
-    syn::custom_keyword!(Ord);
-    syn::custom_keyword!(Partial);
-    syn::custom_keyword!(Path);
-    syn::custom_keyword!(SetOf);
-    syn::custom_keyword!(TokenMatch);
-}
-
 /// Generates a newtype wrapper struct around a `TokenStream` and a implements `ToTokens` for it.
 /// The newtype wrapper allows a `syn::parse::Parse` implementation to be attached to it.
+///
+/// The `Parse` implementation parses `$decl` from the shared declaration grammar and generates the tokens with
+/// `$ident::from_decl`.
 macro_rules! tokens_of {
     ($ident:ident) => {
         pub(super) struct $ident(TokenStream);
@@ -36,73 +26,47 @@ macro_rules! tokens_of {
             }
         }
     };
+
+    ($ident:ident, $decl:ty) => {
+        tokens_of!($ident);
+
+        impl Parse for $ident {
+            fn parse(input: ParseStream) -> syn::Result<Self> {
+                Self::from_decl(&<$decl>::parse(input)?)
+            }
+        }
+    };
 }
 
-tokens_of!(ArrayDecl);
 tokens_of!(AssociatedTypeDecl);
-tokens_of!(BinaryOpDecl);
+tokens_of!(BinaryOpDecl, decls::BinaryOpDecl);
 tokens_of!(BoundsDecl);
-tokens_of!(EqlTerm);
 tokens_of!(EqlTrait);
 tokens_of!(EqlTraits);
-tokens_of!(FunctionDecl);
-tokens_of!(NativeDecl);
-tokens_of!(ProjectionColumnDecl);
-tokens_of!(ProjectionDecl);
-tokens_of!(SetOfDecl);
+tokens_of!(FunctionDecl, decls::FunctionDecl);
 tokens_of!(SqltkBinOp);
-tokens_of!(TVar);
+tokens_of!(TVar, decls::TVar);
 tokens_of!(TableColumn);
 tokens_of!(TypeEquation);
 tokens_of!(TypeEnvDecl);
-tokens_of!(TypeDecl);
-tokens_of!(VarDecl);
+tokens_of!(TypeDecl, decls::TypeDecl);
 
-impl Parse for TVar {
-    fn parse(input: ParseStream) -> syn::Result<Self> {
-        let ident = Ident::parse(input)?.to_string();
+impl TVar {
+    fn from_decl(tvar: &decls::TVar) -> syn::Result<Self> {
+        let ident = &tvar.0;
         Ok(Self(quote! {
             crate::inference::unifier::TVar(#ident.to_string())
         }))
     }
 }
 
-impl Parse for VarDecl {
-    fn parse(input: ParseStream) -> syn::Result<Self> {
-        let ident = Ident::parse(input)?.to_string();
-        if input.peek(Token![:]) {
-            let _: Token![:] = input.parse()?;
-            let bounds = EqlTraits::parse(input)?;
-            Ok(Self(quote! {
-                crate::inference::unifier::VarDecl {
-                    tvar: crate::inference::unifier::TVar(#ident.to_string()),
-                    bounds: #bounds,
-                }
-            }))
-        } else {
-            Ok(Self(quote! {
-                crate::inference::unifier::VarDecl {
-                    tvar: crate::inference::unifier::TVar(#ident.to_string()),
-                    bounds: crate::inference::unifier::EqlTraits::default(),
-                }
-            }))
-        }
-    }
-}
-
-impl Parse for EqlTraits {
-    fn parse(input: ParseStream) -> syn::Result<Self> {
-        let mut traits: Vec<EqlTrait> = Vec::new();
-
-        loop {
-            traits.push(EqlTrait::parse(input)?);
-
-            if !input.peek(token::Plus) {
-                break;
-            }
-
-            token::Plus::parse(input)?;
-        }
+impl EqlTraits {
+    fn from_decl(traits: &decls::EqlTraits) -> syn::Result<Self> {
+        let traits = traits
+            .0
+            .iter()
+            .map(EqlTrait::from_decl)
+            .collect::<syn::Result<Vec<_>>>()?;
 
         Ok(Self(quote!(
             crate::inference::unifier::EqlTraits::from_iter(vec![#(#traits),*])
@@ -110,82 +74,42 @@ impl Parse for EqlTraits {
     }
 }
 
-impl Parse for BoundsDecl {
-    fn parse(input: ParseStream) -> syn::Result<Self> {
-        let mut traits: Vec<EqlTrait> = Vec::new();
-
-        let tvar = TVar::parse(input)?;
-
-        let _: token::Colon = input.parse()?;
-
-        loop {
-            traits.push(EqlTrait::parse(input)?);
-
-            if !input.peek(token::Plus) {
-                break;
-            }
-
-            let _: token::Plus = input.parse()?;
-        }
+impl BoundsDecl {
+    fn from_decl(bounds: &decls::BoundsDecl) -> syn::Result<Self> {
+        let tvar = TVar::from_decl(&bounds.tvar)?;
+        let traits = EqlTraits::from_decl(&bounds.traits)?;
 
         Ok(Self(quote! {
             crate::inference::unifier::BoundsDecl(
                 #tvar,
-                crate::inference::unifier::EqlTraits::from_iter(vec![#(#traits),*])
+                #traits
             )
         }))
     }
 }
 
-impl Parse for EqlTrait {
-    fn parse(input: ParseStream) -> syn::Result<Self> {
-        if input.peek(kw::Eq) {
-            kw::Eq::parse(input)?;
-            return Ok(Self(quote!(crate::inference::unifier::EqlTrait::Eq)));
-        }
-
-        if input.peek(kw::Ord) {
-            kw::Ord::parse(input)?;
-            return Ok(Self(quote!(crate::inference::unifier::EqlTrait::Ord)));
-        }
-
-        if input.peek(kw::TokenMatch) {
-            kw::TokenMatch::parse(input)?;
-            return Ok(Self(quote!(
-                crate::inference::unifier::EqlTrait::TokenMatch
-            )));
-        }
-
-        if input.peek(kw::JsonLike) {
-            kw::JsonLike::parse(input)?;
-            return Ok(Self(quote!(crate::inference::unifier::EqlTrait::JsonLike)));
-        }
-
-        if input.peek(kw::Contain) {
-            kw::Contain::parse(input)?;
-            return Ok(Self(quote!(crate::inference::unifier::EqlTrait::Contain)));
-        }
-
-        Err(syn::Error::new(
-            input.span(),
-            format!(
-                "Expected Eq, Ord, TokenMatch, JsonLike or Contain while parsing EqlTrait; got: {}",
-                input.cursor().token_stream()
-            ),
-        ))
+impl EqlTrait {
+    fn from_decl(eql_trait: &decls::EqlTrait) -> syn::Result<Self> {
+        Ok(Self(match eql_trait {
+            decls::EqlTrait::Eq => quote!(crate::inference::unifier::EqlTrait::Eq),
+            decls::EqlTrait::Ord => quote!(crate::inference::unifier::EqlTrait::Ord),
+            decls::EqlTrait::TokenMatch => {
+                quote!(crate::inference::unifier::EqlTrait::TokenMatch)
+            }
+            decls::EqlTrait::JsonLike => quote!(crate::inference::unifier::EqlTrait::JsonLike),
+            decls::EqlTrait::Contain => quote!(crate::inference::unifier::EqlTrait::Contain),
+        }))
     }
 }
 
-impl Parse for AssociatedTypeDecl {
-    fn parse(input: ParseStream) -> syn::Result<Self> {
-        let _: token::Lt = input.parse()?;
-        let impl_tvar = TVar::parse(input)?;
-        let _: token::As = input.parse()?;
-        let as_eql_trait = EqlTrait::parse(input)?;
-        let _: token::Gt = input.parse()?;
-        let _: token::PathSep = input.parse()?;
-        let type_name_ident = input.parse::<Ident>()?;
-        let type_name = type_name_ident.to_string();
+impl AssociatedTypeDecl {
+    fn from_decl(
+        tvar: &decls::TVar,
+        as_eql_trait: &decls::EqlTrait,
+        type_name: &str,
+    ) -> syn::Result<Self> {
+        let impl_tvar = TVar::from_decl(tvar)?;
+        let as_eql_trait = EqlTrait::from_decl(as_eql_trait)?;
 
         Ok(Self(quote! {
             crate::inference::unifier::AssociatedTypeDecl {
@@ -202,230 +126,176 @@ impl Parse for AssociatedTypeDecl {
     }
 }
 
-impl Parse for NativeDecl {
-    fn parse(input: ParseStream) -> syn::Result<Self> {
-        let _: kw::Native = input.parse()?;
-        if input.peek(token::Paren) {
-            let content;
-            parenthesized!(content in input);
-            let table_column = TableColumn::parse(&content)?;
-
-            Ok(Self(
-                quote!(crate::inference::unifier::NativeDecl(Some(#table_column))),
-            ))
-        } else {
-            Ok(Self(quote!(crate::inference::unifier::NativeDecl(None))))
-        }
-    }
-}
-
-impl Parse for ProjectionColumnDecl {
+impl Parse for AssociatedTypeDecl {
     fn parse(input: ParseStream) -> syn::Result<Self> {
-        let spec = TypeDecl::parse(input)?;
-        if input.peek(token::As) {
-            let _: token::As = input.parse()?;
-            let alias = Ident::parse(input)?;
-            let alias = alias.to_string();
-            Ok(Self(
-                quote!(crate::inference::unifier::ProjectionColumnDecl(Box::new(#spec), Some(#alias.into()))),
-            ))
-        } else {
-            Ok(Self(
-                quote!(crate::inference::unifier::ProjectionColumnDecl(Box::new(#spec), None)),
-            ))
+        match decls::TypeDecl::parse(input)? {
+            decls::TypeDecl::AssociatedType {
+                tvar,
+                as_eql_trait,
+                type_name,
+            } => Self::from_decl(&tvar, &as_eql_trait, &type_name),
+            _ => Err(input.error("expected an associated type")),
         }
     }
 }
 
-impl Parse for ProjectionDecl {
-    fn parse(input: ParseStream) -> syn::Result<Self> {
-        let content;
-        braced!(content in input);
+impl TableColumn {
+    fn from_decl(table_column: &decls::TableColumn) -> syn::Result<Self> {
+        let table = &table_column.table;
+        let column = &table_column.column;
 
-        let mut specs: Vec<ProjectionColumnDecl> = Vec::new();
-
-        loop {
-            specs.push(ProjectionColumnDecl::parse(&content)?);
-
-            if !content.peek(token::Comma) {
-                break;
+        Ok(Self(quote! {
+            crate::TableColumn {
+                table: sqltk::parser::ast::Ident::new(#table),
+                column: sqltk::parser::ast::Ident::new(#column),
             }
-
-            token::Comma::parse(&content)?;
-        }
-
-        Ok(Self(quote!(crate::inference::unifier::ProjectionDecl(
-            Vec::from_iter(vec![#(#specs,)*])
-        ))))
-    }
-}
-
-impl Parse for SetOfDecl {
-    fn parse(input: ParseStream) -> syn::Result<Self> {
-        let _: kw::SetOf = input.parse()?;
-        let _: Token![<] = input.parse()?;
-        let type_decl = TypeDecl::parse(input)?;
-        let _: Token![>] = input.parse()?;
-
-        Ok(Self(
-            quote!(crate::inference::unifier::SetOfDecl(Box::new(#type_decl))),
-        ))
+        }))
     }
 }
 
-impl Parse for ArrayDecl {
-    fn parse(input: ParseStream) -> syn::Result<Self> {
-        let content;
-        bracketed!(content in input);
-
-        let type_spec = TypeDecl::parse(&content)?;
-
-        Ok(Self(
-            quote!(crate::inference::unifier::ArrayDecl(Box::new(#type_spec))),
-        ))
-    }
-}
+impl TypeDecl {
+    fn from_decl(type_decl: &decls::TypeDecl) -> syn::Result<Self> {
+        let tokens = match type_decl {
+            decls::TypeDecl::AssociatedType {
+                tvar,
+                as_eql_trait,
+                type_name,
+            } => {
+                let inner = AssociatedTypeDecl::from_decl(tvar, as_eql_trait, type_name)?;
+                quote! {
+                    crate::inference::unifier::TypeDecl::AssociatedType(#inner)
+                }
+            }
 
-impl Parse for EqlTerm {
-    fn parse(input: ParseStream) -> syn::Result<Self> {
-        let _: kw::EQL = input.parse()?;
+            decls::TypeDecl::SetOf(type_decl) => {
+                let type_decl = TypeDecl::from_decl(type_decl)?;
+                quote! {
+                    crate::inference::unifier::TypeDecl::SetOf(
+                        crate::inference::unifier::SetOfDecl(Box::new(#type_decl))
+                    )
+                }
+            }
 
-        let content;
-        parenthesized!(content in input);
-
-        let table = Ident::parse(&content)?;
-        let table = table.to_string();
-        let _: token::Dot = content.parse()?;
-        let column = Ident::parse(&content)?;
-        let column = column.to_string();
-
-        if content.peek(token::Colon) {
-            let _: token::Colon = content.parse()?;
-            let bounds = EqlTraits::parse(&content)?;
-
-            Ok(Self(quote! {
-                crate::inference::unifier::EqlTerm::Full(
-                    crate::inference::unifier::EqlValue::with_canonical_identity(
-                        crate::inference::unifier::TableColumn {
-                            table: #table.into(),
-                            column: #column.into(),
-                        },
-                        #bounds,
-                    ),
+            decls::TypeDecl::Native(None) => quote! {
+                crate::inference::unifier::TypeDecl::Native(
+                    crate::inference::unifier::NativeDecl(None)
                 )
-            }))
-        } else {
-            Ok(Self(quote! {
-                crate::inference::unifier::EqlTerm::Full(
-                    crate::inference::unifier::EqlValue::with_canonical_identity(
-                        crate::inference::unifier::TableColumn {
-                            table: #table.into(),
-                            column: #column.into(),
-                        },
-                        crate::inference::unifier::EqlTraits::none(),
-                    ),
-                )
-            }))
-        }
-    }
-}
-
-impl Parse for TypeDecl {
-    fn parse(input: ParseStream) -> syn::Result<Self> {
-        if AssociatedTypeDecl::parse(&input.fork()).is_ok() {
-            let inner = AssociatedTypeDecl::parse(input)?;
-            return Ok(Self(quote! {
-                crate::inference::unifier::TypeDecl::AssociatedType(#inner)
-            }));
-        }
-
-        if SetOfDecl::parse(&input.fork()).is_ok() {
-            let inner = SetOfDecl::parse(input)?;
-            return Ok(Self(quote! {
-                crate::inference::unifier::TypeDecl::SetOf(#inner)
-            }));
-        }
+            },
 
-        if NativeDecl::parse(&input.fork()).is_ok() {
-            let inner = NativeDecl::parse(input)?;
-            return Ok(Self(quote! {
-                crate::inference::unifier::TypeDecl::Native(#inner)
-            }));
-        }
+            decls::TypeDecl::Native(Some(table_column)) => {
+                let table_column = TableColumn::from_decl(table_column)?;
+                quote! {
+                    crate::inference::unifier::TypeDecl::Native(
+                        crate::inference::unifier::NativeDecl(Some(#table_column))
+                    )
+                }
+            }
 
-        if EqlTerm::parse(&input.fork()).is_ok() {
-            let inner = EqlTerm::parse(input)?;
-            return Ok(Self(quote! {
-                crate::inference::unifier::TypeDecl::Eql(#inner)
-            }));
-        }
+            decls::TypeDecl::Eql {
+                table_column,
+                bounds,
+            } => {
+                let table = &table_column.table;
+                let column = &table_column.column;
+                let bounds = match bounds {
+                    Some(bounds) => EqlTraits::from_decl(bounds)?.0,
+                    None => quote!(crate::inference::unifier::EqlTraits::none()),
+                };
+                quote! {
+                    crate::inference::unifier::TypeDecl::Eql(
+                        crate::inference::unifier::EqlTerm::Full(
+                            crate::inference::unifier::EqlValue::with_canonical_identity(
+                                crate::inference::unifier::TableColumn {
+                                    table: #table.into(),
+                                    column: #column.into(),
+                                },
+                                #bounds,
+                            ),
+                        )
+                    )
+                }
+            }
 
-        if VarDecl::parse(&input.fork()).is_ok() {
-            let inner = VarDecl::parse(input)?;
-            return Ok(Self(quote! {
-                crate::inference::unifier::TypeDecl::Var(#inner)
-            }));
-        }
+            decls::TypeDecl::Var { tvar, bounds } => {
+                let tvar = TVar::from_decl(tvar)?;
+                let bounds = match bounds {
+                    Some(bounds) => EqlTraits::from_decl(bounds)?.0,
+                    None => quote!(crate::inference::unifier::EqlTraits::default()),
+                };
+                quote! {
+                    crate::inference::unifier::TypeDecl::Var(
+                        crate::inference::unifier::VarDecl {
+                            tvar: #tvar,
+                            bounds: #bounds,
+                        }
+                    )
+                }
+            }
 
-        if ArrayDecl::parse(&input.fork()).is_ok() {
-            let inner = ArrayDecl::parse(input)?;
-            return Ok(Self(quote! {
-                crate::inference::unifier::TypeDecl::Array(#inner)
-            }));
-        }
+            decls::TypeDecl::Array(type_decl) => {
+                let type_decl = TypeDecl::from_decl(type_decl)?;
+                quote! {
+                    crate::inference::unifier::TypeDecl::Array(
+                        crate::inference::unifier::ArrayDecl(Box::new(#type_decl))
+                    )
+                }
+            }
 
-        if ProjectionDecl::parse(&input.fork()).is_ok() {
-            let inner = ProjectionDecl::parse(input)?;
-            return Ok(Self(quote! {
-                crate::inference::unifier::TypeDecl::Projection(#inner)
-            }));
-        }
+            decls::TypeDecl::Projection(columns) => {
+                let columns = columns
+                    .iter()
+                    .map(|column| {
+                        let spec = TypeDecl::from_decl(&column.type_decl)?;
+                        Ok(match &column.alias {
+                            Some(alias) => quote!(
+                                crate::inference::unifier::ProjectionColumnDecl(Box::new(#spec), Some(#alias.into()))
+                            ),
+                            None => quote!(
+                                crate::inference::unifier::ProjectionColumnDecl(Box::new(#spec), None)
+                            ),
+                        })
+                    })
+                    .collect::<syn::Result<Vec<_>>>()?;
+                quote! {
+                    crate::inference::unifier::TypeDecl::Projection(
+                        crate::inference::unifier::ProjectionDecl(Vec::from_iter(vec![#(#columns,)*]))
+                    )
+                }
+            }
+        };
 
-        Err(syn::Error::new(
-            input.span(),
-            "could not parse as TypeDecl".to_string(),
-        ))
+        Ok(Self(tokens))
     }
 }
 
-impl Parse for FunctionDecl {
-    fn parse(input: ParseStream) -> syn::Result<Self> {
-        let schema = Ident::parse(input)?;
-        let schema = schema.to_string();
-        let _: token::Dot = input.parse()?;
-        let function_name = Ident::parse(input)?;
-        let function_name = function_name.to_string();
-
-        let generic_args = if input.peek(token::Lt) {
-            token::Lt::parse(input)?;
-            let args = Punctuated::<TVar, Token![,]>::parse_separated_nonempty(input)?
-                .into_iter()
-                .collect();
-            token::Gt::parse(input)?;
-            args
-        } else {
-            Vec::new()
-        };
-
-        let content;
-        parenthesized!(content in input);
-
-        let args: Vec<TypeDecl> =
-            Punctuated::<TypeDecl, Token![,]>::parse_separated_nonempty(&content)?
-                .into_iter()
-                .collect();
-
-        let _: token::RArrow = input.parse()?;
-
-        let ret = TypeDecl::parse(input)?;
-
-        let bounds: Vec<_> = if input.peek(token::Where) {
-            let _: token::Where = input.parse()?;
-            let boundeds = Punctuated::<BoundsDecl, Token![,]>::parse_separated_nonempty(input)?;
-            boundeds.into_iter().collect()
-        } else {
-            vec![]
-        };
+/// Generates `bounds` and `generic_args` for `FunctionSignatureDecl::new`.
+fn signature_tokens(
+    generic_args: &[decls::TVar],
+    bounds: &[decls::BoundsDecl],
+) -> syn::Result<(Vec<TVar>, Vec<BoundsDecl>)> {
+    let generic_args = generic_args
+        .iter()
+        .map(TVar::from_decl)
+        .collect::<syn::Result<_>>()?;
+    let bounds = bounds
+        .iter()
+        .map(BoundsDecl::from_decl)
+        .collect::<syn::Result<_>>()?;
+
+    Ok((generic_args, bounds))
+}
+
+impl FunctionDecl {
+    fn from_decl(decl: &decls::FunctionDecl) -> syn::Result<Self> {
+        let schema = &decl.schema;
+        let function_name = &decl.name;
+        let (generic_args, bounds) = signature_tokens(&decl.generic_args, &decl.bounds)?;
+        let args = decl
+            .args
+            .iter()
+            .map(TypeDecl::from_decl)
+            .collect::<syn::Result<Vec<_>>>()?;
+        let ret = TypeDecl::from_decl(&decl.ret)?;
 
         Ok(Self(quote! {
             crate::inference::unifier::FunctionDecl {
@@ -444,153 +314,53 @@ impl Parse for FunctionDecl {
     }
 }
 
-impl Parse for TableColumn {
-    fn parse(input: ParseStream) -> syn::Result<Self> {
-        let table = Ident::parse(input)?;
-        let table = table.to_string();
-        let _: token::Dot = input.parse()?;
-        let column = Ident::parse(input)?;
-        let column = column.to_string();
-
-        Ok(Self(quote! {
-            crate::TableColumn {
-                table: sqltk::parser::ast::Ident::new(#table),
-                column: sqltk::parser::ast::Ident::new(#column),
-            }
-        }))
-    }
-}
-
-impl Parse for SqltkBinOp {
-    fn parse(input: ParseStream) -> syn::Result<Self> {
-        if input.peek(token::RArrow) {
-            let _: token::RArrow = input.parse()?;
-            if input.peek(token::Gt) {
-                let _: token::Gt = input.parse()?;
+impl SqltkBinOp {
+    fn from_decl(op: &decls::BinaryOp) -> syn::Result<Self> {
+        let variant = match op {
+            decls::BinaryOp::Qualified { schema, op } => {
                 return Ok(Self(quote!(
-                    ::sqltk::parser::ast::BinaryOperator::LongArrow
-                )));
-            } else {
-                return Ok(Self(quote!(::sqltk::parser::ast::BinaryOperator::Arrow)));
-            }
-        }
-
-        if input.peek(token::At) {
-            let _: token::At = input.parse()?;
-            // `@@` (fuzzy match) or `@>` (containment).
-            if input.peek(token::At) {
-                let _: token::At = input.parse()?;
-                return Ok(Self(quote!(::sqltk::parser::ast::BinaryOperator::AtAt)));
+                    ::sqltk::parser::ast::BinaryOperator::PGCustomBinaryOperator(
+                        vec![#schema.to_string(), #op.to_string()]
+                    )
+                )))
             }
-            let _: token::Gt = input.parse()?;
-            return Ok(Self(quote!(::sqltk::parser::ast::BinaryOperator::AtArrow)));
-        }
-
-        if input.peek(token::Le) {
-            let _: token::Le = input.parse()?;
-            return Ok(Self(quote!(::sqltk::parser::ast::BinaryOperator::LtEq)));
-        }
-
-        if input.peek(token::Lt) {
-            let _: token::Lt = input.parse()?;
-            if input.peek(token::At) {
-                let _: token::At = input.parse()?;
-                return Ok(Self(quote!(::sqltk::parser::ast::BinaryOperator::ArrowAt)));
-            } else if input.peek(token::Gt) {
-                let _: token::Gt = input.parse()?;
-                return Ok(Self(quote!(::sqltk::parser::ast::BinaryOperator::NotEq)));
-            }
-            return Ok(Self(quote!(::sqltk::parser::ast::BinaryOperator::Lt)));
-        }
-
-        if input.peek(token::Ge) {
-            let _: token::Ge = input.parse()?;
-            return Ok(Self(quote!(::sqltk::parser::ast::BinaryOperator::GtEq)));
-        }
-
-        if input.peek(token::Eq) {
-            let _: token::Eq = input.parse()?;
-            return Ok(Self(quote!(::sqltk::parser::ast::BinaryOperator::Eq)));
-        }
-
-        if input.peek(token::Gt) {
-            let _: token::Gt = input.parse()?;
-            return Ok(Self(quote!(::sqltk::parser::ast::BinaryOperator::Gt)));
-        }
-
-        if input.peek(token::Tilde) {
-            let _: token::Tilde = input.parse()?;
-            if input.peek(token::Tilde) {
-                let _: token::Tilde = input.parse()?;
-                if input.peek(token::Star) {
-                    let _: token::Star = input.parse()?;
-                    return Ok(Self(quote!(
-                        ::sqltk::parser::ast::BinaryOperator::PGILikeMatch
-                    )));
-                } else {
-                    return Ok(Self(quote!(
-                        ::sqltk::parser::ast::BinaryOperator::PGLikeMatch
-                    )));
+            decls::BinaryOp::Symbol(symbol) => match symbol.as_str() {
+                "->" => quote!(Arrow),
+                "->>" => quote!(LongArrow),
+                "@@" => quote!(AtAt),
+                "@>" => quote!(AtArrow),
+                "<=" => quote!(LtEq),
+                "<@" => quote!(ArrowAt),
+                "<>" => quote!(NotEq),
+                "<" => quote!(Lt),
+                ">=" => quote!(GtEq),
+                "=" => quote!(Eq),
+                ">" => quote!(Gt),
+                "~~" => quote!(PGLikeMatch),
+                "~~*" => quote!(PGILikeMatch),
+                "!~~" => quote!(PGNotLikeMatch),
+                "!~~*" => quote!(PGNotILikeMatch),
+                "||" => quote!(StringConcat),
+                _ => {
+                    return Err(syn::Error::new(
+                        proc_macro2::Span::call_site(),
+                        format!("Unsupported binary operator `{symbol}`"),
+                    ))
                 }
-            }
-        }
-
-        if input.peek(token::Not) {
-            let _: token::Not = input.parse()?;
-            if input.peek(token::Tilde) {
-                let _: token::Tilde = input.parse()?;
-                if input.peek(token::Tilde) {
-                    let _: token::Tilde = input.parse()?;
-                    if input.peek(token::Star) {
-                        let _: token::Star = input.parse()?;
-                        return Ok(Self(quote!(
-                            ::sqltk::parser::ast::BinaryOperator::PGNotILikeMatch
-                        )));
-                    } else {
-                        return Ok(Self(quote!(
-                            ::sqltk::parser::ast::BinaryOperator::PGNotLikeMatch
-                        )));
-                    }
-                }
-            }
-        }
+            },
+        };
 
-        Err(syn::Error::new(
-            input.span(),
-            "Expected an operator corresponding to one of the EQL traits Eq, Ord, TokenMatch, JsonLike or Contain".to_string(),
-        ))
+        Ok(Self(quote!(::sqltk::parser::ast::BinaryOperator::#variant)))
     }
 }
 
-impl Parse for BinaryOpDecl {
-    fn parse(input: ParseStream) -> syn::Result<Self> {
-        let generic_args = if input.peek(token::Lt) {
-            token::Lt::parse(input)?;
-            let args = Punctuated::<TVar, Token![,]>::parse_separated_nonempty(input)?
-                .into_iter()
-                .collect();
-            token::Gt::parse(input)?;
-            args
-        } else {
-            Vec::new()
-        };
-
-        let content;
-        parenthesized!(content in input);
-        let lhs = TypeDecl::parse(&content)?;
-        let op = SqltkBinOp::parse(&content)?;
-        let rhs = TypeDecl::parse(&content)?;
-
-        let _: token::RArrow = input.parse()?;
-        let ret = TypeDecl::parse(input)?;
-
-        let bounds: Vec<_> = if input.peek(token::Where) {
-            let _: token::Where = input.parse()?;
-            let boundeds = Punctuated::<BoundsDecl, Token![,]>::parse_separated_nonempty(input)?;
-            boundeds.into_iter().collect()
-        } else {
-            vec![]
-        };
+impl BinaryOpDecl {
+    fn from_decl(decl: &decls::BinaryOpDecl) -> syn::Result<Self> {
+        let (generic_args, bounds) = signature_tokens(&decl.generic_args, &decl.bounds)?;
+        let lhs = TypeDecl::from_decl(&decl.lhs)?;
+        let op = SqltkBinOp::from_decl(&decl.op)?;
+        let rhs = TypeDecl::from_decl(&decl.rhs)?;
+        let ret = TypeDecl::from_decl(&decl.ret)?;
 
         Ok(Self(quote! {
             crate::inference::unifier::BinaryOpDecl {
@@ -647,9 +417,11 @@ impl ToTokens for BinaryOpDecls {
 
 impl Parse for BinaryOpDecls {
     fn parse(input: ParseStream) -> syn::Result<Self> {
-        let ops = Punctuated::<BinaryOpDecl, Token![;]>::parse_terminated(input)?
-            .into_iter()
-            .collect();
+        let ops = decls::BinaryOpDecls::parse(input)?
+            .0
+            .iter()
+            .map(BinaryOpDecl::from_decl)
+            .collect::<syn::Result<_>>()?;
         Ok(Self { ops })
     }
 }
@@ -667,9 +439,11 @@ impl ToTokens for FunctionDecls {
 
 impl Parse for FunctionDecls {
     fn parse(input: ParseStream) -> syn::Result<Self> {
-        let ops = Punctuated::<FunctionDecl, Token![;]>::parse_terminated(input)?
-            .into_iter()
-            .collect();
+        let ops = decls::FunctionDecls::parse(input)?
+            .0
+            .iter()
+            .map(FunctionDecl::from_decl)
+            .collect::<syn::Result<_>>()?;
         Ok(Self { ops })
     }
 }
@@ -2,13 +2,14 @@ use super::importer::{ImportError, Importer};
 use crate::{
     inference::{TypeError, TypeInferencer},
     unifier::{EqlTerm, Projection, Type, Unifier, Value},
-    DepMut, Param, ParamError, ScopeError, ScopeTracker, TableResolver, TypeCheckedStatement,
-    TypeRegistry,
+    DepMut, Param, ParamError, ProxyAggregates, ScopeError, ScopeTracker, TableResolver,
+    TypeCheckedStatement, TypeRegistry,
 };
-use sqltk::parser::ast::{self as ast, Statement};
+use sqltk::parser::ast::{self as ast, CreateTable, Statement};
 use sqltk::{Break, NodeKey, Visitable, Visitor};
 use std::{
-    cell::RefCell, collections::HashMap, marker::PhantomData, ops::ControlFlow, rc::Rc, sync::Arc,
+    cell::RefCell, collections::HashMap, convert::Infallible, marker::PhantomData,
+    ops::ControlFlow, rc::Rc, sync::Arc,
 };
 use tracing::{event, span, Level};
 
@@ -35,7 +36,25 @@ pub fn type_check<'ast>(
     resolver: Arc<TableResolver>,
     statement: &'ast Statement,
 ) -> Result<TypeCheckedStatement<'ast>, EqlMapperError> {
-    let mut mapper = EqlMapper::<'ast>::new_with_resolver(resolver);
+    type_check_with_options(resolver, statement, TypeCheckOptions::default())
+}
+
+/// Opt-in behaviours of [`type_check_with_options`]. The default is what [`type_check`] does.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TypeCheckOptions {
+    /// Accept aggregates over encrypted numerics that PostgreSQL cannot compute (`sum`, `avg`, `stddev`,
+    /// `percentile_disc`, ...) by rewriting them to ship the encrypted values to the proxy. See
+    /// [`crate::ProxyAggregate`].
+    pub proxy_aggregates: bool,
+}
+
+/// As [`type_check`], with the opt-in behaviours in `options`.
+pub fn type_check_with_options<'ast>(
+    resolver: Arc<TableResolver>,
+    statement: &'ast Statement,
+    options: TypeCheckOptions,
+) -> Result<TypeCheckedStatement<'ast>, EqlMapperError> {
+    let mut mapper = EqlMapper::<'ast>::new_with_resolver(resolver, options);
     match statement.accept(&mut mapper) {
         ControlFlow::Continue(()) => mapper.resolve(statement),
         ControlFlow::Break(Break::Err(err)) => Err(err),
@@ -57,10 +76,14 @@ pub fn type_check<'ast>(
 /// It is acceptable for `PREPARE` because we believe that most ORMs do not make direct use of it.
 ///
 /// In any case, support for those statements is coming soon!
+///
+/// `CREATE TABLE ... AS SELECT` is type-checked for its query, which may read encrypted columns; a plain
+/// `CREATE TABLE` is not.
 pub fn requires_type_check(statement: &Statement) -> bool {
     matches!(
         statement,
         Statement::Query(_)
+            | Statement::CreateTable(CreateTable { query: Some(_), .. })
             | Statement::Insert(_)
             | Statement::Update { .. }
             | Statement::Delete(_)
@@ -70,6 +93,43 @@ pub fn requires_type_check(statement: &Statement) -> bool {
     )
 }
 
+/// Checks if a statement names any table or view that has encrypted columns.
+///
+/// Every object name in the statement is resolved, not only those in table position, so a function that shares its
+/// name with an encrypted table is also a match. This errs on the side of treating a statement as touching encrypted
+/// data, and does not require the statement to type check.
+pub fn references_encrypted_table(resolver: &TableResolver, statement: &Statement) -> bool {
+    struct EncryptedTableFinder<'a> {
+        resolver: &'a TableResolver,
+    }
+
+    impl<'ast> Visitor<'ast> for EncryptedTableFinder<'_> {
+        type Error = Infallible;
+
+        fn enter<N: Visitable>(&mut self, node: &'ast N) -> ControlFlow<Break<Self::Error>> {
+            if let Some(name) = node.downcast_ref::<ast::ObjectName>() {
+                let encrypted = match self.resolver.resolve_table(name) {
+                    Ok(table) => table.has_encrypted_columns(),
+                    Err(_) => self
+                        .resolver
+                        .resolve_view(name)
+                        .is_ok_and(|view| view.has_encrypted_columns()),
+                };
+
+                if encrypted {
+                    return ControlFlow::Break(Break::Finished);
+                }
+            }
+
+            ControlFlow::Continue(())
+        }
+    }
+
+    statement
+        .accept(&mut EncryptedTableFinder { resolver })
+        .is_break()
+}
+
 /// The error type returned by various functions in the `eql_mapper` crate.
 #[derive(Debug, PartialEq, Eq, thiserror::Error)]
 pub enum EqlMapperError {
@@ -134,7 +194,7 @@ struct EqlMapper<'ast> {
 
 impl<'ast> EqlMapper<'ast> {
     /// Build an `EqlMapper`, initialising all the other visitor implementations that it depends on.
-    fn new_with_resolver(table_resolver: Arc<TableResolver>) -> Self {
+    fn new_with_resolver(table_resolver: Arc<TableResolver>, options: TypeCheckOptions) -> Self {
         let registry = DepMut::new(TypeRegistry::new());
         let scope_tracker = DepMut::new(ScopeTracker::new());
         let importer = DepMut::new(Importer::new(
@@ -148,6 +208,7 @@ impl<'ast> EqlMapper<'ast> {
             table_resolver.clone(),
             &scope_tracker,
             &unifier,
+            options,
         ));
 
         Self {
@@ -187,12 +248,21 @@ impl<'ast> EqlMapper<'ast> {
         let params = self.param_types(&self.unifier.borrow());
         let literals = self.literal_types();
         let node_types = self.node_types();
-
-        let combine_results =
-            || -> Result<_, EqlMapperError> { Ok((projection?, params?, literals?, node_types?)) };
+        let proxy_aggregates =
+            ProxyAggregates::locate(statement, self.inferencer.borrow().take_proxy_aggregates());
+
+        let combine_results = || -> Result<_, EqlMapperError> {
+            Ok((
+                projection?,
+                params?,
+                literals?,
+                node_types?,
+                proxy_aggregates?,
+            ))
+        };
 
         match combine_results() {
-            Ok((projection, params, literals, node_types)) => {
+            Ok((projection, params, literals, node_types, proxy_aggregates)) => {
                 // event!(
                 //     target: "eql-mapper::EVENT_RESOLVE_OK",
                 //     parent: &span_begin,
@@ -211,6 +281,7 @@ impl<'ast> EqlMapper<'ast> {
                     self.inferencer.borrow().take_json_value_selectors(),
                     self.inferencer.borrow().take_json_accessor_paths(),
                     self.inferencer.borrow().take_query_operands(),
+                    proxy_aggregates,
                     Arc::new(node_types),
                 ))
             }
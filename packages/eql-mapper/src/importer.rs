@@ -5,8 +5,8 @@ use crate::{
     Relation, ScopeError, ScopeTracker,
 };
 use sqltk::parser::ast::{
-    Cte, Ident, Insert, ObjectNamePart, OnConflict, OnConflictAction, TableAlias, TableFactor,
-    TableObject,
+    Cte, Ident, Insert, ObjectName, ObjectNamePart, OnConflict, OnConflictAction, TableAlias,
+    TableFactor, TableObject,
 };
 use sqltk::{Break, Visitable, Visitor};
 use std::{cell::RefCell, fmt::Debug, marker::PhantomData, ops::ControlFlow, rc::Rc, sync::Arc};
@@ -124,9 +124,7 @@ impl<'ast> Importer<'ast> {
                 let mut scope_tracker = self.scope_tracker.borrow_mut();
 
                 if scope_tracker.resolve_relation(name).is_err() {
-                    let table = self.table_resolver.resolve_table(name)?;
-
-                    let projection = Projection::new_from_schema_table(table.clone())?;
+                    let projection = self.resolve_table_or_view_projection(name)?;
 
                     scope_tracker.add_relation(Relation {
                         name: record_as.cloned().ok(),
@@ -288,6 +286,24 @@ impl<'ast> Importer<'ast> {
         Ok(())
     }
 
+    /// The projection a named relation in a `FROM` clause brings into scope.
+    ///
+    /// Tables are tried first. A view loaded from the catalog contributes the
+    /// projection derived from its definition; if there is no such view either,
+    /// the original table resolution error is returned.
+    fn resolve_table_or_view_projection(
+        &self,
+        name: &ObjectName,
+    ) -> Result<Projection, ImportError> {
+        match self.table_resolver.resolve_table(name) {
+            Ok(table) => Ok(Projection::new_from_schema_table(table)?),
+            Err(err) => match self.table_resolver.resolve_view(name) {
+                Ok(view) => Ok(view.projection.clone()),
+                Err(_) => Err(err.into()),
+            },
+        }
+    }
+
     fn validate_table_alias(alias: &TableAlias) -> Result<&Ident, ImportError> {
         match alias {
             TableAlias { name, columns } if columns.is_empty() => Ok(name),
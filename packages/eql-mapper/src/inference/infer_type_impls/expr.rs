@@ -1,5 +1,5 @@
 use crate::{
-    get_sql_binop_rule,
+    get_sql_binop_rule, get_sql_binop_rule_with_decls,
     inference::{
         unifier::{EqlTerm, EqlValue, TokenType, Type, Value},
         InferType, TypeError,
@@ -395,7 +395,8 @@ impl<'ast> InferType<'ast, Expr> for TypeInferencer<'ast> {
                         (&**left, &**right)
                     };
 
-                    get_sql_binop_rule(op).apply_constraints(self, lhs, rhs, expr_val)?;
+                    get_sql_binop_rule_with_decls(op, &self.table_resolver.sql_decls())
+                        .apply_constraints(self, lhs, rhs, expr_val)?;
 
                     // A comparison's result is native regardless of its operand
                     // type, so the operands (now unified with each other) may
@@ -1,15 +1,16 @@
 use eql_mapper_macros::trace_infer;
 use sqltk::parser::ast::{
-    DuplicateTreatment, Function, FunctionArg, FunctionArgumentClause, FunctionArguments,
+    DuplicateTreatment, Expr, Function, FunctionArg, FunctionArgumentClause, FunctionArguments,
 };
 use sqltk::NodeKey;
 
 use crate::{
     function_arg::function_arg_value,
-    get_sql_function,
+    get_sql_function_with_decls,
     inference::infer_type::InferType,
+    proxy_aggregate::literal_fraction,
     unifier::{Type, Value},
-    EqlTrait, TypeError, TypeInferencer,
+    EqlTrait, ProxyAggregate, TypeError, TypeInferencer,
 };
 
 /// Looks up the function signature.
@@ -45,6 +46,10 @@ impl<'ast> InferType<'ast, Function> for TypeInferencer<'ast> {
             ));
         }
 
+        if self.options.proxy_aggregates && self.infer_proxy_aggregate(function)? {
+            return Ok(());
+        }
+
         if let FunctionArguments::List(list) = &function.args {
             // `DISTINCT` dedupes the argument values by equality, so every
             // argument needs an equality term. Without the bound the dedup runs
@@ -91,6 +96,92 @@ impl<'ast> InferType<'ast, Function> for TypeInferencer<'ast> {
             self.unify_node_with_type(&order_by_expr.expr, Type::native())?;
         }
 
-        get_sql_function(&function.name).apply_constraints(self, function)
+        get_sql_function_with_decls(&function.name, &self.table_resolver.sql_decls())
+            .apply_constraints(self, function)
+    }
+}
+
+impl<'ast> TypeInferencer<'ast> {
+    /// Types `function` as an aggregate the proxy computes, if it is one over an
+    /// encrypted numeric ([`ProxyAggregate`]).
+    ///
+    /// The call takes the type of its input, so the column it lands in decrypts
+    /// as the input column does: what the proxy receives is that column's
+    /// encrypted values, and what it writes to the client is computed from their
+    /// plaintexts.
+    ///
+    /// Returns `Ok(false)` when the call is not such an aggregate, leaving it to
+    /// the ordinary rules — over a native input PostgreSQL computes it as usual.
+    fn infer_proxy_aggregate(&self, function: &'ast Function) -> Result<bool, TypeError> {
+        let Some(aggregate) = ProxyAggregate::from_function_name(&function.name) else {
+            return Ok(false);
+        };
+
+        let FunctionArguments::List(list) = &function.args else {
+            return Ok(false);
+        };
+
+        let args: Vec<&'ast Expr> = list.args.iter().filter_map(function_arg_value).collect();
+
+        let (input, fraction) = if aggregate.is_ordered_set() {
+            match (&function.within_group[..], &args[..]) {
+                ([key], [fraction]) => (&key.expr, Some(*fraction)),
+                _ => return Ok(false),
+            }
+        } else {
+            match &args[..] {
+                [input] if function.within_group.is_empty() => (*input, None),
+                _ => return Ok(false),
+            }
+        };
+
+        let ty = self.get_node_type(input);
+        let ty = ty.follow_tvars(&self.unifier.borrow());
+        let Type::Value(Value::Eql(eql_term)) = &*ty else {
+            return Ok(false);
+        };
+
+        let unsupported = |detail: &str| {
+            Err(TypeError::UnsupportedSqlFeature(format!(
+                "{aggregate} {detail}"
+            )))
+        };
+
+        if !eql_term.eql_value().domain_identity().token.is_numeric() {
+            return unsupported("over an encrypted non-numeric column");
+        }
+
+        if function.over.is_some() {
+            return unsupported("over an encrypted column as a window function");
+        }
+
+        // The values are shipped as they are stored, and every encryption of a
+        // value is distinct, so neither deduplicating nor sorting them in the
+        // database means anything.
+        if list.duplicate_treatment == Some(DuplicateTreatment::Distinct) {
+            return unsupported("(DISTINCT ...) over an encrypted column");
+        }
+
+        if !list.clauses.is_empty() {
+            return unsupported("over an encrypted column with clauses in its argument list");
+        }
+
+        let aggregate = match fraction {
+            Some(fraction) => {
+                let Some(value) = literal_fraction(fraction) else {
+                    return unsupported(
+                        "over an encrypted column with a fraction that is not a literal between 0 and 1",
+                    );
+                };
+                self.unify_node_with_type(fraction, Type::native())?;
+                aggregate.with_fraction(value)
+            }
+            None => aggregate,
+        };
+
+        self.unify_node_with_type(function, ty.clone())?;
+        self.record_proxy_aggregate(function, aggregate);
+
+        Ok(true)
     }
 }
@@ -15,3 +15,5 @@ mod delete_statement;
 mod insert_statement;
 mod query_statement;
 mod statement; // <-- UPDATE is not missing, it's handled in here!
+
+pub(crate) use query_statement::resolve_positional_key;
@@ -2,9 +2,10 @@ use eql_mapper_macros::trace_infer;
 use sqltk::parser::ast::{
     Distinct, Expr, GroupByExpr, JoinConstraint, JoinOperator, Select, SelectItem,
 };
+use sqltk::AsNodeKey;
 
 use super::query_statement::resolve_positional_key;
-use crate::unifier::{Projection, Type, Value};
+use crate::unifier::{EqlTerm, Projection, Type, Value};
 use crate::{
     inference::{type_error::TypeError, InferType},
     EqlTrait, TypeInferencer,
@@ -15,22 +16,12 @@ impl<'ast> InferType<'ast, Select> for TypeInferencer<'ast> {
     fn infer_exit(&mut self, select: &'ast Select) -> Result<(), TypeError> {
         self.unify_nodes(select, &select.projection)?;
 
-        // `SELECT ... INTO` copies the projection into a brand-new table the
-        // schema knows nothing about. An encrypted column landing there would
-        // be raw ciphertext in a table the mapper can never resolve — no
-        // decryption on read, no term rewrites on query — so reject it rather
-        // than let the data silently escape the schema. A projection of only
-        // native columns is passed through untouched.
+        // `SELECT ... INTO` copies the projection into a brand-new table.
+        // Encrypted columns keep their domain there (see
+        // `CastFullPayloadOperands`), so the projection only has to be
+        // storable.
         if select.into.is_some() {
-            let ty = self.get_node_type(&select.projection);
-            let ty = ty.follow_tvars(&self.unifier.borrow());
-            if let Type::Value(Value::Projection(projection)) = &*ty {
-                if Self::projection_contains_eql(projection) {
-                    return Err(TypeError::UnsupportedSqlFeature(
-                        "SELECT INTO with an encrypted column".into(),
-                    ));
-                }
-            }
+            self.check_stored_projection(&select.projection, "SELECT INTO")?;
         }
 
         // `WHERE`, `HAVING` and join `ON` conditions are boolean expressions,
@@ -145,15 +136,41 @@ impl<'ast> InferType<'ast, Select> for TypeInferencer<'ast> {
 }
 
 impl<'ast> TypeInferencer<'ast> {
+    /// Checks that the projection of `node` can be stored as the columns of a
+    /// new table, as by `CREATE TABLE AS` or `SELECT INTO` (`statement`).
+    ///
+    /// An encrypted column is stored in its own domain, which needs the whole
+    /// payload. A value that is only part of one — a JSON selector or a bare
+    /// search term — has no domain to store it in, so it is rejected rather
+    /// than written out as unreadable ciphertext.
+    pub(crate) fn check_stored_projection<N: AsNodeKey>(
+        &self,
+        node: &'ast N,
+        statement: &str,
+    ) -> Result<(), TypeError> {
+        let ty = self.get_node_type(node);
+        let ty = ty.follow_tvars(&self.unifier.borrow());
+        if let Type::Value(Value::Projection(projection)) = &*ty {
+            if Self::projection_contains_partial_eql(projection) {
+                return Err(TypeError::UnsupportedSqlFeature(format!(
+                    "{statement} with a value that is not a whole encrypted column"
+                )));
+            }
+        }
+
+        Ok(())
+    }
+
     /// Whether any column of `projection` — including the columns of a nested
-    /// projection, as produced by a wildcard — is an encrypted column.
+    /// projection, as produced by a wildcard — is an encrypted value other
+    /// than a whole payload.
     ///
     /// Callers must have followed type variables first (via
     /// [`Type::follow_tvars`]), which resolves column types recursively.
-    fn projection_contains_eql(projection: &Projection) -> bool {
+    fn projection_contains_partial_eql(projection: &Projection) -> bool {
         projection.columns().iter().any(|column| match &*column.ty {
-            Type::Value(Value::Projection(nested)) => Self::projection_contains_eql(nested),
-            Type::Value(Value::Eql(_)) => true,
+            Type::Value(Value::Projection(nested)) => Self::projection_contains_partial_eql(nested),
+            Type::Value(Value::Eql(term)) => !matches!(term, EqlTerm::Full(_)),
             _ => false,
         })
     }
@@ -1,7 +1,9 @@
 use std::sync::Arc;
 
 use eql_mapper_macros::trace_infer;
-use sqltk::parser::ast::{AssignmentTarget, ObjectName, ObjectNamePart, Statement, TableFactor};
+use sqltk::parser::ast::{
+    AssignmentTarget, CreateTable, ObjectName, ObjectNamePart, Statement, TableFactor,
+};
 
 use crate::{
     inference::infer_type::InferType,
@@ -103,6 +105,15 @@ impl<'ast> InferType<'ast, Statement> for TypeInferencer<'ast> {
                 };
             }
 
+            Statement::CreateTable(CreateTable {
+                query: Some(query), ..
+            }) => {
+                // The query's projection becomes the new table's columns, so
+                // it must be storable. The statement itself returns no rows.
+                self.check_stored_projection(&**query, "CREATE TABLE AS")?;
+                self.unify_node_with_type(statement, Type::empty_projection())?;
+            }
+
             Statement::Merge {
                 into: _,
                 table: _,
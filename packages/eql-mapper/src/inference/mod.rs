@@ -10,7 +10,12 @@ pub mod unifier;
 use unifier::{Unifier, *};
 
 use std::{
-    cell::RefCell, collections::HashSet, fmt::Debug, marker::PhantomData, ops::ControlFlow, rc::Rc,
+    cell::RefCell,
+    collections::{HashMap, HashSet},
+    fmt::Debug,
+    marker::PhantomData,
+    ops::ControlFlow,
+    rc::Rc,
     sync::Arc,
 };
 
@@ -22,13 +27,15 @@ use sqltk::parser::ast::{
 use sqltk::{into_control_flow, AsNodeKey, Break, NodeKey, Visitable, Visitor};
 
 use crate::{
-    JsonAccessorPaths, JsonSelectorSource, JsonValueSelectors, Param, QueryOperands, ScopeError,
-    ScopeTracker, TableResolver,
+    JsonAccessorPaths, JsonSelectorSource, JsonValueSelectors, Param, ProxyAggregate,
+    QueryOperands, ScopeError, ScopeTracker, TableResolver, TypeCheckOptions,
 };
 
+pub(crate) use infer_type_impls::resolve_positional_key;
 pub(crate) use registry::*;
 pub(crate) use sequence::*;
 pub(crate) use sql_types::*;
+pub use sql_types::{SqlDeclError, SqlDecls, SqlObject};
 pub(crate) use type_error::*;
 
 /// [`Visitor`] implementation that performs type inference on AST nodes.
@@ -98,6 +105,14 @@ pub struct TypeInferencer<'ast> {
     /// They are syntax, not value expressions, and must not resolve as columns.
     named_function_arg_labels: RefCell<HashSet<NodeKey<'ast>>>,
 
+    /// The opt-in behaviours the statement is checked with.
+    options: TypeCheckOptions,
+
+    /// The aggregate calls the proxy will compute ([`crate::ProxyAggregates`]).
+    /// Whether each is somewhere the proxy receives its result is only known
+    /// once the whole statement has been seen, so they are placed afterwards.
+    proxy_aggregates: RefCell<HashMap<NodeKey<'ast>, ProxyAggregate>>,
+
     _ast: PhantomData<&'ast ()>,
 }
 
@@ -107,6 +122,7 @@ impl<'ast> TypeInferencer<'ast> {
         table_resolver: impl Into<Arc<TableResolver>>,
         scope: impl Into<Rc<RefCell<ScopeTracker<'ast>>>>,
         unifier: impl Into<Rc<RefCell<Unifier<'ast>>>>,
+        options: TypeCheckOptions,
     ) -> Self {
         Self {
             table_resolver: table_resolver.into(),
@@ -117,6 +133,8 @@ impl<'ast> TypeInferencer<'ast> {
             query_operands: RefCell::new(QueryOperands::default()),
             fusable_json_chains: RefCell::new(HashSet::new()),
             named_function_arg_labels: RefCell::new(HashSet::new()),
+            options,
+            proxy_aggregates: RefCell::new(HashMap::new()),
             _ast: PhantomData,
         }
     }
@@ -138,6 +156,21 @@ impl<'ast> TypeInferencer<'ast> {
         std::mem::take(&mut self.query_operands.borrow_mut())
     }
 
+    /// Takes the recorded proxy-side aggregates, leaving the inferencer's set empty.
+    pub(crate) fn take_proxy_aggregates(&self) -> HashMap<NodeKey<'ast>, ProxyAggregate> {
+        std::mem::take(&mut self.proxy_aggregates.borrow_mut())
+    }
+
+    pub(crate) fn record_proxy_aggregate(
+        &self,
+        function: &'ast Function,
+        aggregate: ProxyAggregate,
+    ) {
+        self.proxy_aggregates
+            .borrow_mut()
+            .insert(function.as_node_key(), aggregate);
+    }
+
     /// Marks a JSON accessor chain as sitting under a comparison that will fuse
     /// it, before any of it has been typed.
     pub(crate) fn mark_fusable_json_chain<N: AsNodeKey>(&self, node: &'ast N) {
@@ -1,7 +1,9 @@
 mod sql_binary_operator_types;
 mod sql_decls;
 mod sql_function_types;
+mod user_decls;
 
 pub(crate) use sql_binary_operator_types::*;
 pub(crate) use sql_decls::*;
 pub(crate) use sql_function_types::*;
+pub use user_decls::{SqlDeclError, SqlDecls, SqlObject};
@@ -1,3 +1,5 @@
+use std::sync::Arc;
+
 use sqltk::parser::ast::Expr;
 
 use crate::{
@@ -11,6 +13,9 @@ pub(crate) enum SqlBinaryOp {
     /// An explicit predefined rule for handling EQL types in the expression.
     Explicit(&'static BinaryOpDecl),
 
+    /// A rule declared by the user (see [`crate::SqlDecls`]) for an operator that has no predefined rule.
+    Declared(Arc<BinaryOpDecl>),
+
     /// The fallback rule for when there is no explicit rule for a given operator.  This rule will force the left and
     /// right expressions of the operator and its return value to resolve to [`Type::native()`].
     Fallback,
@@ -24,8 +29,14 @@ impl SqlBinaryOp {
         rhs: &'ast Expr,
         return_val: &'ast Expr,
     ) -> Result<(), TypeError> {
-        match self {
-            SqlBinaryOp::Explicit(rule) => {
+        let rule = match self {
+            SqlBinaryOp::Explicit(rule) => Some(*rule),
+            SqlBinaryOp::Declared(rule) => Some(&**rule),
+            SqlBinaryOp::Fallback => None,
+        };
+
+        match rule {
+            Some(rule) => {
                 let lhs_ty = inferencer.get_node_type(lhs);
                 let rhs_ty = inferencer.get_node_type(rhs);
                 let ret_ty = inferencer.get_node_type(return_val);
@@ -37,7 +48,7 @@ impl SqlBinaryOp {
                 )?;
             }
 
-            SqlBinaryOp::Fallback => {
+            None => {
                 inferencer.unify_node_with_type(lhs, Type::native())?;
                 inferencer.unify_node_with_type(rhs, Type::native())?;
                 inferencer.unify_node_with_type(return_val, Type::native())?;
@@ -8,7 +8,7 @@ use crate::{
     IdentCase,
 };
 
-use super::{SqlBinaryOp, SqlFunction};
+use super::{SqlBinaryOp, SqlDecls, SqlFunction};
 
 /// SQL operators that can accept EQL types.
 static SQL_BINARY_OPERATORS: LazyLock<HashMap<BinaryOperator, BinaryOpDecl>> =
@@ -48,6 +48,17 @@ pub(crate) fn get_sql_binop_rule(op: &BinaryOperator) -> SqlBinaryOp {
         .unwrap_or(SqlBinaryOp::Fallback)
 }
 
+/// As [`get_sql_binop_rule`], falling back to the user's declarations before the all-native rule.
+pub(crate) fn get_sql_binop_rule_with_decls(op: &BinaryOperator, decls: &SqlDecls) -> SqlBinaryOp {
+    match get_sql_binop_rule(op) {
+        SqlBinaryOp::Fallback => decls
+            .get_binary_operator(op)
+            .map(SqlBinaryOp::Declared)
+            .unwrap_or(SqlBinaryOp::Fallback),
+        explicit => explicit,
+    }
+}
+
 /// SQL functions that are handled with special case type checking rules for EQL.
 static SQL_FUNCTION_TYPES: LazyLock<HashMap<IdentCase<ObjectName>, FunctionDecl>> =
     LazyLock::new(|| {
@@ -104,6 +115,10 @@ static SQL_FUNCTION_TYPES: LazyLock<HashMap<IdentCase<ObjectName>, FunctionDecl>
             eql_v3.jsonb_array<T>(T) -> Native where T: Contain;
             eql_v3.jsonb_contains<T>(T, T) -> Native where T: Contain;
             eql_v3.jsonb_contained_by<T>(T, T) -> Native where T: Contain;
+            // Not a database function: `RewriteCipherstashRaw` replaces the call
+            // with its argument cast to `jsonb`, so the stored payload of an
+            // encrypted column is returned as a native value, not decrypted.
+            cipherstash.raw<T>(T) -> Native;
         };
 
         HashMap::from_iter(
@@ -130,6 +145,28 @@ pub(crate) fn get_sql_function(fn_name: &ObjectName) -> SqlFunction {
         .unwrap_or(SqlFunction::Fallback)
 }
 
+/// As [`get_sql_function`], falling back to the user's declarations before the all-native rule.
+pub(crate) fn get_sql_function_with_decls(fn_name: &ObjectName, decls: &SqlDecls) -> SqlFunction {
+    match get_sql_function(fn_name) {
+        SqlFunction::Fallback => decls
+            .get_function(fn_name)
+            .map(SqlFunction::Declared)
+            .unwrap_or(SqlFunction::Fallback),
+        explicit => explicit,
+    }
+}
+
+/// Checks if `fn_name` is `cipherstash.raw`, which returns the stored payload of
+/// an encrypted column instead of its decrypted value.
+pub(crate) fn is_cipherstash_raw(fn_name: &ObjectName) -> bool {
+    matches!(
+        &fn_name.0[..],
+        [ObjectNamePart::Identifier(schema), ObjectNamePart::Identifier(name)]
+            if schema.value.eq_ignore_ascii_case("cipherstash")
+                && name.value.eq_ignore_ascii_case("raw")
+    )
+}
+
 /// The `eql_v3.<name>` counterpart a `pg_catalog` function is rewritten to on EQL
 /// types, or `None` if none is declared. `count`, for example, works on encrypted
 /// values natively (Postgres counts the domain directly), so it has no counterpart
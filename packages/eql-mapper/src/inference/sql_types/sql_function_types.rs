@@ -13,6 +13,8 @@ use crate::{
 #[derive(Debug)]
 pub(crate) enum SqlFunction {
     Explicit(&'static FunctionDecl),
+    /// Typing rules declared by the user (see [`crate::SqlDecls`]).
+    Declared(Arc<FunctionDecl>),
     Fallback,
 }
 
@@ -23,7 +25,7 @@ impl SqlFunction {
     pub(crate) fn should_rewrite(&self) -> bool {
         match self {
             SqlFunction::Explicit(function_decl) => function_decl.name.starts_with(&PG_CATALOG),
-            SqlFunction::Fallback => false,
+            SqlFunction::Declared(_) | SqlFunction::Fallback => false,
         }
     }
 }
@@ -35,8 +37,14 @@ impl SqlFunction {
         function: &'ast Function,
     ) -> Result<(), TypeError> {
         let ret_type = inferencer.get_node_type(function);
-        match self {
-            SqlFunction::Explicit(rule) => {
+        let rule = match self {
+            SqlFunction::Explicit(rule) => Some(*rule),
+            SqlFunction::Declared(rule) => Some(&**rule),
+            SqlFunction::Fallback => None,
+        };
+
+        match rule {
+            Some(rule) => {
                 match &function.args {
                     FunctionArguments::None => {
                         rule.inner
@@ -63,7 +71,7 @@ impl SqlFunction {
 
                 Ok(())
             }
-            SqlFunction::Fallback => {
+            None => {
                 match &function.args {
                     FunctionArguments::None => NativeFunction::new(0).apply_constraints(
                         &mut inferencer.unifier.borrow_mut(),
@@ -0,0 +1,697 @@
+//! Function and operator signatures declared by users rather than built into the mapper.
+//!
+//! A SQL function that is not in the built-in registry falls back to "all native", so a user-defined function that
+//! accepts or returns encrypted values is either rejected or, worse, returns ciphertext the proxy does not know to
+//! decrypt. [`SqlDecls`] lets those functions be described with the same syntax the `functions!` and
+//! `binary_operators!` macros use:
+//!
+//! ```text
+//! app.normalize_email<T>(T) -> T where T: Eq;
+//! <T>(T OPERATOR(app.~=) T) -> Native where T: Eq;
+//! ```
+//!
+//! Declarations are parsed with the grammar in `eql_mapper_decls`, which the macros also use, so the two syntaxes
+//! cannot drift apart. Only the subset that makes sense for a declaration is accepted: type variables, `Native`,
+//! `SetOf<_>`, arrays and associated types. `EQL(table.column)` and projections name concrete columns and are only
+//! meaningful in tests.
+//!
+//! User declarations never override the built-in ones: the built-in registry is always consulted first, and a
+//! declaration for a name it already covers is rejected. An operator declaration must name a user-defined operator
+//! (`OPERATOR(schema.op)`, or a symbol that is not a built-in operator), as a declaration for a built-in operator
+//! such as `||` would apply to every use of it.
+//!
+//! Declarations read from the catalog are bound to the object they are attached to with
+//! [`SqlDecls::extend_for_object`]: the declaration must name that object, with its number of arguments. An
+//! unqualified call only resolves to a declared function that the catalog reports as visible in the `search_path`.
+
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::{self, Display},
+    sync::Arc,
+};
+
+use eql_mapper_decls as decls;
+use sqltk::parser::{
+    ast::{BinaryOperator, Expr, Ident, ObjectName, ObjectNamePart},
+    dialect::PostgreSqlDialect,
+    parser::Parser,
+};
+
+use crate::{
+    unifier::{
+        ArrayDecl, AssociatedTypeDecl, BinaryOpDecl, BoundsDecl, EqlTraits, FunctionDecl,
+        FunctionSignatureDecl, NativeDecl, SetOfDecl, TVar, TypeDecl, VarDecl,
+    },
+    EqlTrait, IdentCase,
+};
+
+use super::{get_sql_binop_rule, get_sql_function, SqlBinaryOp, SqlFunction};
+
+/// A set of user-declared function and binary operator signatures.
+///
+/// Built with [`SqlDecls::parse`] and attached to a [`crate::Schema`], from where the type checker consults it for any
+/// function or operator that the built-in registry does not cover.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SqlDecls {
+    functions: HashMap<IdentCase<ObjectName>, Arc<FunctionDecl>>,
+    binary_operators: HashMap<BinaryOperator, Arc<BinaryOpDecl>>,
+    /// Declared functions that can be called without a schema
+    visible: HashSet<IdentCase<ObjectName>>,
+}
+
+/// A function or operator in the catalog that a declaration is attached to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SqlObject {
+    Function {
+        schema: String,
+        name: String,
+        args: usize,
+        /// The function can be called without a schema, as its schema is in the `search_path`
+        visible: bool,
+    },
+    Operator {
+        schema: String,
+        name: String,
+        args: usize,
+        /// The operator can be used without a schema, as its schema is in the `search_path`
+        visible: bool,
+    },
+}
+
+impl SqlObject {
+    fn schema(&self) -> &str {
+        match self {
+            SqlObject::Function { schema, .. } | SqlObject::Operator { schema, .. } => schema,
+        }
+    }
+
+    fn is_built_in(&self) -> bool {
+        ["pg_catalog", "information_schema"].contains(&self.schema())
+    }
+
+    /// Returns true if `decl` names this object, with its number of arguments.
+    fn is_declared_by(&self, decl: &decls::Decl) -> bool {
+        match (self, decl) {
+            (
+                SqlObject::Function {
+                    schema, name, args, ..
+                },
+                decls::Decl::Function(decl),
+            ) => {
+                decl.schema.eq_ignore_ascii_case(schema)
+                    && decl.name.eq_ignore_ascii_case(name)
+                    && decl.args.len() == *args
+            }
+            (
+                SqlObject::Operator {
+                    schema,
+                    name,
+                    args,
+                    visible,
+                },
+                decls::Decl::BinaryOp(decl),
+            ) => {
+                *args == 2
+                    && match &decl.op {
+                        decls::BinaryOp::Symbol(symbol) => *visible && symbol == name,
+                        decls::BinaryOp::Qualified {
+                            schema: decl_schema,
+                            op,
+                        } => decl_schema.eq_ignore_ascii_case(schema) && op == name,
+                    }
+            }
+            _ => false,
+        }
+    }
+}
+
+impl Display for SqlObject {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SqlObject::Function {
+                schema, name, args, ..
+            } => write!(f, "function {schema}.{name} with {args} argument(s)"),
+            SqlObject::Operator {
+                schema, name, args, ..
+            } => write!(f, "operator {schema}.{name} with {args} operand(s)"),
+        }
+    }
+}
+
+/// The error type returned when user-declared signatures cannot be parsed or registered.
+#[derive(Debug, PartialEq, Eq, thiserror::Error)]
+pub enum SqlDeclError {
+    #[error("Invalid declaration at line {line}, column {column}: {message}")]
+    Syntax {
+        line: usize,
+        column: usize,
+        message: String,
+    },
+
+    #[error("Invalid declaration '{decl}': {message}")]
+    Invalid { decl: String, message: String },
+
+    #[error("'{}' is built in and cannot be redeclared", _0)]
+    BuiltIn(String),
+
+    #[error("'{}' is declared more than once", _0)]
+    Duplicate(String),
+
+    #[error("The comment on the {object} must declare a signature for it, and only it")]
+    Mismatch { object: String },
+}
+
+impl SqlDecls {
+    /// Parses zero or more `;`-separated function and binary operator declarations.
+    ///
+    /// Function declarations must be schema-qualified (`schema.name<...>(...) -> ...`), and the declared functions can
+    /// only be called with their schema. An unqualified call resolves to a declared function when exactly one
+    /// function declared with [`SqlDecls::extend_for_object`] as visible has that name.
+    pub fn parse(src: &str) -> Result<Self, SqlDeclError> {
+        let mut decls = Self::default();
+        decls.extend_from_str(src)?;
+        Ok(decls)
+    }
+
+    /// Parses `src` as for [`SqlDecls::parse`] and adds its declarations to `self`.
+    ///
+    /// The declared functions can only be called with their schema. On error `self` is left unchanged.
+    pub fn extend_from_str(&mut self, src: &str) -> Result<(), SqlDeclError> {
+        let mut next = self.clone();
+
+        for decl in &parse_decls(src)?.0 {
+            next.insert(decl, false)?;
+        }
+
+        *self = next;
+        Ok(())
+    }
+
+    /// Parses the declaration in the comment on `object` and adds it to `self`.
+    ///
+    /// The comment must hold exactly one declaration, which names `object` with its number of arguments, so a
+    /// comment cannot declare a signature for another function or operator. A declared function can be called
+    /// without its schema when `object` is visible. On error `self` is left unchanged.
+    pub fn extend_for_object(&mut self, object: &SqlObject, src: &str) -> Result<(), SqlDeclError> {
+        if object.is_built_in() {
+            return Err(SqlDeclError::BuiltIn(object.to_string()));
+        }
+
+        let parsed = parse_decls(src)?;
+        let [decl] = &parsed.0[..] else {
+            return Err(SqlDeclError::Mismatch {
+                object: object.to_string(),
+            });
+        };
+
+        if !object.is_declared_by(decl) {
+            return Err(SqlDeclError::Mismatch {
+                object: object.to_string(),
+            });
+        }
+
+        let visible = matches!(object, SqlObject::Function { visible: true, .. });
+
+        let mut next = self.clone();
+        next.insert(decl, visible)?;
+
+        *self = next;
+        Ok(())
+    }
+
+    fn insert(&mut self, decl: &decls::Decl, visible: bool) -> Result<(), SqlDeclError> {
+        match decl {
+            decls::Decl::Function(decl) => {
+                let decl = DeclBuilder::function(decl)?;
+                if let SqlFunction::Explicit(_) = get_sql_function(&decl.name.0) {
+                    return Err(SqlDeclError::BuiltIn(decl.name.0.to_string()));
+                }
+                if self.functions.contains_key(&decl.name) {
+                    return Err(SqlDeclError::Duplicate(decl.name.0.to_string()));
+                }
+                if visible {
+                    self.visible.insert(decl.name.clone());
+                }
+                self.functions.insert(decl.name.clone(), Arc::new(decl));
+            }
+            decls::Decl::BinaryOp(decl) => {
+                let decl = DeclBuilder::binary_op(decl)?;
+                // sqltk parses a built-in operator to its own variant, whichever operator Postgres resolves it to
+                if !matches!(decl.op, BinaryOperator::PGCustomBinaryOperator(_)) {
+                    return Err(SqlDeclError::BuiltIn(decl.op.to_string()));
+                }
+                if let SqlBinaryOp::Explicit(_) = get_sql_binop_rule(&decl.op) {
+                    return Err(SqlDeclError::BuiltIn(decl.op.to_string()));
+                }
+                if self.binary_operators.contains_key(&decl.op) {
+                    return Err(SqlDeclError::Duplicate(decl.op.to_string()));
+                }
+                self.binary_operators
+                    .insert(decl.op.clone(), Arc::new(decl));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.functions.is_empty() && self.binary_operators.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.functions.len() + self.binary_operators.len()
+    }
+
+    pub(crate) fn get_function(&self, fn_name: &ObjectName) -> Option<Arc<FunctionDecl>> {
+        if let Some(decl) = self.functions.get(&IdentCase(fn_name.clone())) {
+            return Some(decl.clone());
+        }
+
+        let [ObjectNamePart::Identifier(bare)] = &fn_name.0[..] else {
+            return None;
+        };
+
+        let mut candidates = self
+            .functions
+            .values()
+            .filter(|decl| self.visible.contains(&decl.name))
+            .filter(|decl| match decl.name.0 .0.last() {
+                Some(ObjectNamePart::Identifier(name)) => IdentCase(name) == IdentCase(bare),
+                None => false,
+            });
+
+        match (candidates.next(), candidates.next()) {
+            (Some(decl), None) => Some(decl.clone()),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn get_binary_operator(&self, op: &BinaryOperator) -> Option<Arc<BinaryOpDecl>> {
+        self.binary_operators.get(op).cloned()
+    }
+}
+
+fn parse_decls(src: &str) -> Result<decls::Decls, SqlDeclError> {
+    decls::parse_str(src).map_err(|err| SqlDeclError::Syntax {
+        line: err.line,
+        column: err.column,
+        message: err.message,
+    })
+}
+
+/// Builds the unifier's representation of the declarations parsed by the grammar shared with `eql-mapper-macros`.
+///
+/// The grammar also accepts concrete types (`EQL(table.column)`, `Native(table.column)` and projections) because the
+/// macros use them in tests. They are rejected here.
+struct DeclBuilder {
+    decl: String,
+}
+
+impl DeclBuilder {
+    fn function(decl: &decls::FunctionDecl) -> Result<FunctionDecl, SqlDeclError> {
+        let builder = Self {
+            decl: format!("{}.{}", decl.schema, decl.name),
+        };
+
+        let args = decl
+            .args
+            .iter()
+            .map(|arg| builder.type_decl(arg))
+            .collect::<Result<Vec<_>, _>>()?;
+        let ret = builder.type_decl(&decl.ret)?;
+
+        Ok(FunctionDecl {
+            name: IdentCase(ObjectName(vec![
+                ObjectNamePart::Identifier(Ident::new(&decl.schema)),
+                ObjectNamePart::Identifier(Ident::new(&decl.name)),
+            ])),
+            inner: builder.signature(&decl.generic_args, &decl.bounds, args, ret)?,
+        })
+    }
+
+    fn binary_op(decl: &decls::BinaryOpDecl) -> Result<BinaryOpDecl, SqlDeclError> {
+        let text = match &decl.op {
+            decls::BinaryOp::Symbol(symbol) => symbol.clone(),
+            decls::BinaryOp::Qualified { schema, op } => format!("OPERATOR({schema}.{op})"),
+        };
+        let builder = Self { decl: text };
+
+        let op = builder.binary_operator()?;
+        let lhs = builder.type_decl(&decl.lhs)?;
+        let rhs = builder.type_decl(&decl.rhs)?;
+        let ret = builder.type_decl(&decl.ret)?;
+
+        Ok(BinaryOpDecl {
+            op,
+            inner: builder.signature(&decl.generic_args, &decl.bounds, vec![lhs, rhs], ret)?,
+        })
+    }
+
+    fn signature(
+        &self,
+        generic_args: &[decls::TVar],
+        bounds: &[decls::BoundsDecl],
+        args: Vec<TypeDecl>,
+        ret: TypeDecl,
+    ) -> Result<FunctionSignatureDecl, SqlDeclError> {
+        let generic_args = generic_args
+            .iter()
+            .map(|tvar| TVar(tvar.0.clone()))
+            .collect();
+        let bounds = bounds
+            .iter()
+            .map(|bounds| BoundsDecl(TVar(bounds.tvar.0.clone()), eql_traits(&bounds.traits)))
+            .collect();
+
+        FunctionSignatureDecl::new(generic_args, bounds, args, ret)
+            .map_err(|err| self.invalid(err.to_string()))
+    }
+
+    fn type_decl(&self, type_decl: &decls::TypeDecl) -> Result<TypeDecl, SqlDeclError> {
+        match type_decl {
+            decls::TypeDecl::Var { tvar, bounds } => Ok(TypeDecl::Var(VarDecl {
+                tvar: TVar(tvar.0.clone()),
+                bounds: bounds.as_ref().map(eql_traits).unwrap_or_default(),
+            })),
+
+            decls::TypeDecl::Native(None) => Ok(TypeDecl::Native(NativeDecl(None))),
+
+            decls::TypeDecl::SetOf(inner) => {
+                Ok(TypeDecl::SetOf(SetOfDecl(Box::new(self.type_decl(inner)?))))
+            }
+
+            decls::TypeDecl::Array(inner) => {
+                Ok(TypeDecl::Array(ArrayDecl(Box::new(self.type_decl(inner)?))))
+            }
+
+            decls::TypeDecl::AssociatedType {
+                tvar,
+                as_eql_trait,
+                type_name,
+            } => {
+                let as_eql_trait = eql_trait(as_eql_trait);
+                let Some(type_name) = as_eql_trait
+                    .associated_type_names()
+                    .iter()
+                    .copied()
+                    .find(|name| *name == type_name.as_str())
+                else {
+                    return Err(
+                        self.invalid(format!("{as_eql_trait} has no associated type {type_name}"))
+                    );
+                };
+
+                Ok(TypeDecl::AssociatedType(AssociatedTypeDecl {
+                    impl_decl: Box::new(TypeDecl::Var(VarDecl {
+                        tvar: TVar(tvar.0.clone()),
+                        bounds: EqlTraits::none(),
+                    })),
+                    as_eql_trait,
+                    type_name,
+                }))
+            }
+
+            decls::TypeDecl::Eql { .. } | decls::TypeDecl::Native(Some(_)) => Err(self.invalid(
+                "a type naming a concrete column cannot be used in a declaration; use a bounded type variable",
+            )),
+
+            decls::TypeDecl::Projection(_) => {
+                Err(self.invalid("a projection cannot be used in a declaration"))
+            }
+        }
+    }
+
+    /// Hands the operator text to sqltk, so that the declared operator is exactly the [`BinaryOperator`] that sqltk
+    /// will produce when it parses a statement using it.
+    fn binary_operator(&self) -> Result<BinaryOperator, SqlDeclError> {
+        let expr = Parser::new(&PostgreSqlDialect {})
+            .try_with_sql(&format!("a {} b", self.decl))
+            .and_then(|mut parser| parser.parse_expr());
+
+        match expr {
+            Ok(Expr::BinaryOp { left, op, right })
+                if matches!(
+                    (&*left, &*right),
+                    (Expr::Identifier(_), Expr::Identifier(_))
+                ) =>
+            {
+                Ok(op)
+            }
+            _ => Err(self.invalid("not a binary operator")),
+        }
+    }
+
+    fn invalid(&self, message: impl Into<String>) -> SqlDeclError {
+        SqlDeclError::Invalid {
+            decl: self.decl.clone(),
+            message: message.into(),
+        }
+    }
+}
+
+fn eql_traits(traits: &decls::EqlTraits) -> EqlTraits {
+    EqlTraits::from_iter(traits.0.iter().map(eql_trait))
+}
+
+fn eql_trait(eql_trait: &decls::EqlTrait) -> EqlTrait {
+    match eql_trait {
+        decls::EqlTrait::Eq => EqlTrait::Eq,
+        decls::EqlTrait::Ord => EqlTrait::Ord,
+        decls::EqlTrait::TokenMatch => EqlTrait::TokenMatch,
+        decls::EqlTrait::JsonLike => EqlTrait::JsonLike,
+        decls::EqlTrait::Contain => EqlTrait::Contain,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use sqltk::parser::ast::ObjectNamePart;
+
+    fn name(schema: &str, function: &str) -> ObjectName {
+        ObjectName(vec![
+            ObjectNamePart::Identifier(Ident::new(schema)),
+            ObjectNamePart::Identifier(Ident::new(function)),
+        ])
+    }
+
+    #[test]
+    fn parses_function_declarations() {
+        let decls = SqlDecls::parse(
+            "app.normalize_email<T>(T) -> T where T: Eq;
+             app.email_domain<T>(T) -> Native;
+             app.entries<T>(T, <T as JsonLike>::Path) -> SetOf<T> where T: JsonLike + Contain;",
+        )
+        .unwrap();
+
+        assert_eq!(decls.len(), 3);
+        assert!(decls
+            .get_function(&name("app", "normalize_email"))
+            .is_some());
+        assert!(decls.get_function(&name("APP", "Email_Domain")).is_some());
+    }
+
+    fn function(schema: &str, name: &str, args: usize, visible: bool) -> SqlObject {
+        SqlObject::Function {
+            schema: schema.into(),
+            name: name.into(),
+            args,
+            visible,
+        }
+    }
+
+    fn operator(schema: &str, name: &str, visible: bool) -> SqlObject {
+        SqlObject::Operator {
+            schema: schema.into(),
+            name: name.into(),
+            args: 2,
+            visible,
+        }
+    }
+
+    fn bare(name: &str) -> ObjectName {
+        ObjectName(vec![ObjectNamePart::Identifier(Ident::new(name))])
+    }
+
+    #[test]
+    fn unqualified_names_resolve_to_visible_functions() {
+        let mut decls = SqlDecls::parse("app.f<T>(T) -> T").unwrap();
+        decls
+            .extend_for_object(&function("app", "g", 1, true), "app.g<T>(T) -> T")
+            .unwrap();
+        decls
+            .extend_for_object(&function("other", "h", 1, false), "other.h<T>(T) -> T")
+            .unwrap();
+
+        assert!(decls.get_function(&bare("f")).is_none());
+        assert!(decls.get_function(&bare("g")).is_some());
+        assert!(decls.get_function(&bare("h")).is_none());
+        assert!(decls.get_function(&name("other", "h")).is_some());
+    }
+
+    #[test]
+    fn unqualified_names_resolve_when_unambiguous() {
+        let mut decls = SqlDecls::default();
+        for (schema, function_name) in [("app", "f"), ("app", "g"), ("other", "g")] {
+            decls
+                .extend_for_object(
+                    &function(schema, function_name, 1, true),
+                    &format!("{schema}.{function_name}<T>(T) -> T"),
+                )
+                .unwrap();
+        }
+
+        assert!(decls.get_function(&bare("f")).is_some());
+        assert!(decls.get_function(&bare("g")).is_none());
+    }
+
+    #[test]
+    fn declarations_must_match_the_commented_object() {
+        let mut decls = SqlDecls::default();
+
+        // Another function
+        assert!(matches!(
+            decls.extend_for_object(&function("app", "f", 1, true), "app.g<T>(T) -> T"),
+            Err(SqlDeclError::Mismatch { .. })
+        ));
+        // Another schema
+        assert!(matches!(
+            decls.extend_for_object(&function("app", "f", 1, true), "other.f<T>(T) -> T"),
+            Err(SqlDeclError::Mismatch { .. })
+        ));
+        // Another number of arguments
+        assert!(matches!(
+            decls.extend_for_object(&function("app", "f", 2, true), "app.f<T>(T) -> T"),
+            Err(SqlDeclError::Mismatch { .. })
+        ));
+        // A second declaration in the same comment
+        assert!(matches!(
+            decls.extend_for_object(
+                &function("app", "f", 1, true),
+                "app.f<T>(T) -> T; app.g<T>(T) -> T"
+            ),
+            Err(SqlDeclError::Mismatch { .. })
+        ));
+        // An operator declared in a function comment
+        assert!(matches!(
+            decls.extend_for_object(
+                &function("app", "f", 2, true),
+                "<T>(T OPERATOR(app.~=) T) -> Native"
+            ),
+            Err(SqlDeclError::Mismatch { .. })
+        ));
+        assert!(decls.is_empty());
+
+        decls
+            .extend_for_object(&function("APP", "F", 1, true), "app.f<T>(T) -> T")
+            .unwrap();
+        decls
+            .extend_for_object(
+                &operator("app", "~=", false),
+                "<T>(T OPERATOR(app.~=) T) -> Native where T: Eq",
+            )
+            .unwrap();
+        assert_eq!(decls.len(), 2);
+    }
+
+    #[test]
+    fn built_in_objects_cannot_be_declared() {
+        assert!(matches!(
+            SqlDecls::default().extend_for_object(
+                &function("pg_catalog", "textcat", 2, true),
+                "pg_catalog.textcat<T>(T, T) -> T"
+            ),
+            Err(SqlDeclError::BuiltIn(_))
+        ));
+        assert!(matches!(
+            SqlDecls::default().extend_for_object(
+                &operator("pg_catalog", "||", true),
+                "<T>(T || T) -> T where T: Eq"
+            ),
+            Err(SqlDeclError::BuiltIn(_))
+        ));
+    }
+
+    #[test]
+    fn parses_binary_operator_declarations() {
+        let decls = SqlDecls::parse("<T>(T OPERATOR(app.~=) T) -> Native where T: Eq").unwrap();
+
+        assert!(decls
+            .get_binary_operator(&BinaryOperator::PGCustomBinaryOperator(vec![
+                "app".into(),
+                "~=".into()
+            ]))
+            .is_some());
+    }
+
+    #[test]
+    fn built_in_operators_cannot_be_declared() {
+        // A declaration for `||` would apply to every string concatenation
+        for decl in [
+            "<T>(T || T) -> T where T: Eq",
+            "<T>(T @> T) -> Native where T: Contain",
+        ] {
+            assert!(
+                matches!(SqlDecls::parse(decl), Err(SqlDeclError::BuiltIn(_))),
+                "{decl}"
+            );
+        }
+
+        // Also when the comment is on a user-defined operator with the same symbol
+        assert!(matches!(
+            SqlDecls::default()
+                .extend_for_object(&operator("app", "||", true), "<T>(T || T) -> T where T: Eq"),
+            Err(SqlDeclError::BuiltIn(_))
+        ));
+    }
+
+    #[test]
+    fn built_in_declarations_cannot_be_overridden() {
+        assert_eq!(
+            SqlDecls::parse("pg_catalog.min<T>(T) -> Native"),
+            Err(SqlDeclError::BuiltIn("pg_catalog.min".into()))
+        );
+        assert!(matches!(
+            SqlDecls::parse("<T>(T = T) -> Native"),
+            Err(SqlDeclError::BuiltIn(_))
+        ));
+    }
+
+    #[test]
+    fn duplicates_are_rejected() {
+        assert_eq!(
+            SqlDecls::parse("app.f<T>(T) -> T; app.f<T>(T) -> Native"),
+            Err(SqlDeclError::Duplicate("app.f".into()))
+        );
+    }
+
+    #[test]
+    fn failed_extend_leaves_decls_unchanged() {
+        let mut decls = SqlDecls::parse("app.f<T>(T) -> T").unwrap();
+        assert!(decls
+            .extend_from_str("app.g<T>(T) -> T; app.h<T>(T) ->")
+            .is_err());
+        assert_eq!(decls.len(), 1);
+    }
+
+    #[test]
+    fn undeclared_type_variables_are_rejected() {
+        assert!(matches!(
+            SqlDecls::parse("app.f<T>(U) -> T"),
+            Err(SqlDeclError::Invalid { .. })
+        ));
+    }
+
+    #[test]
+    fn concrete_columns_are_rejected() {
+        assert!(matches!(
+            SqlDecls::parse("app.f(EQL(users.email)) -> Native"),
+            Err(SqlDeclError::Invalid { .. })
+        ));
+    }
+
+    #[test]
+    fn unknown_traits_and_associated_types_are_rejected() {
+        assert!(SqlDecls::parse("app.f<T>(T) -> T where T: Hash").is_err());
+        assert!(SqlDecls::parse("app.f<T>(T, <T as Eq>::Path) -> T").is_err());
+    }
+}
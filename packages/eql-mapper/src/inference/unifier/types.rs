@@ -378,6 +378,20 @@ impl TokenType {
         }
     }
 
+    /// Whether the plaintext is a number, which is what arithmetic aggregates
+    /// computed by the proxy ([`crate::ProxyAggregate`]) require.
+    pub fn is_numeric(&self) -> bool {
+        matches!(
+            self,
+            TokenType::SmallInt
+                | TokenType::Integer
+                | TokenType::BigInt
+                | TokenType::Real
+                | TokenType::Double
+                | TokenType::Numeric
+        )
+    }
+
     /// Parse the token type from a v3 domain typname. The token type is the
     /// first segment after the `eql_v3_` prefix; every token type is a single
     /// underscore-free word, so a multi-part capability suffix never interferes.
@@ -880,7 +894,7 @@ impl Projection {
         &self.0
     }
 
-    fn contains_eql(&self) -> bool {
+    pub(crate) fn contains_eql(&self) -> bool {
         self.columns().iter().any(|col| col.ty.contains_eql())
     }
 
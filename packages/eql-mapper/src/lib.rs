@@ -11,6 +11,7 @@ mod json_value_selector;
 mod model;
 mod param;
 mod param_plan;
+mod proxy_aggregate;
 mod query_operands;
 mod renumber_params;
 mod scope_tracker;
@@ -22,10 +23,12 @@ mod test_helpers;
 
 pub use display_helpers::*;
 pub use eql_mapper::*;
+pub use inference::{SqlDeclError, SqlDecls, SqlObject};
 pub use json_value_selector::*;
 pub use model::*;
 pub use param::*;
 pub use param_plan::*;
+pub use proxy_aggregate::*;
 pub use query_operands::*;
 pub use type_checked_statement::*;
 pub use unifier::{
@@ -41,15 +44,18 @@ pub(crate) use transformation_rules::*;
 
 #[cfg(test)]
 mod test {
-    use super::{test_helpers::*, type_check, EqlMapperError, ScopeError, TypeError};
+    use super::{
+        references_encrypted_table, test_helpers::*, type_check, type_check_with_options,
+        EqlMapperError, ScopeError, TypeCheckOptions, TypeError,
+    };
     use crate::{
         projection, schema, test_helpers,
         unifier::{
             EqlTerm, EqlTrait, EqlTraits, EqlValue, InstantiateType, NativeValue, Projection,
             ProjectionColumn, Type, Value,
         },
-        JsonSelectorSegment, JsonSelectorSource, OutputParamSource, Param, Schema, TableColumn,
-        TableResolver, TypeCheckedStatement,
+        JsonSelectorSegment, JsonSelectorSource, OutputParamSource, Param, ProxyAggregate, Schema,
+        SqlDecls, TableColumn, TableResolver, TypeCheckedStatement, View,
     };
     use eql_mapper_macros::concrete_ty;
     use pretty_assertions::assert_eq;
@@ -5576,43 +5582,228 @@ mod test {
         assert!(type_check(schema, &statement).is_ok());
     }
 
-    /// `SELECT ... INTO` copies the projection into a table the schema has
-    /// never seen: an encrypted column landing there would be unreachable
-    /// ciphertext, so it is rejected.
+    fn type_check_with_proxy_aggregates(
+        resolver: Arc<TableResolver>,
+        statement: &Statement,
+    ) -> Result<TypeCheckedStatement<'_>, EqlMapperError> {
+        type_check_with_options(
+            resolver,
+            statement,
+            TypeCheckOptions {
+                proxy_aggregates: true,
+            },
+        )
+    }
+
+    /// With proxy-side aggregates enabled, an aggregate PostgreSQL cannot compute
+    /// over an encrypted numeric ships the group's encrypted values instead, and
+    /// keeps the column name the client asked for.
     #[test]
-    fn select_into_with_encrypted_column_is_rejected() {
+    fn proxy_aggregates_are_rewritten_to_ship_encrypted_values() {
         let schema = resolver(schema! {
             tables: {
                 employees: {
                     id,
-                    salary (EQL),
+                    dept,
+                    salary (EQL("eql_v3_integer_ord"): Ord),
                 }
             }
         });
 
-        let statement = parse("SELECT salary INTO tmp_table FROM employees");
+        for (input, expected, aggregate) in [
+            (
+                "SELECT dept, avg(salary) FROM employees GROUP BY dept",
+                "SELECT dept, jsonb_agg(salary) AS avg FROM employees GROUP BY dept",
+                ProxyAggregate::Avg,
+            ),
+            (
+                "SELECT dept, sum(salary) AS total FROM employees GROUP BY dept",
+                "SELECT dept, jsonb_agg(salary) AS total FROM employees GROUP BY dept",
+                ProxyAggregate::Sum,
+            ),
+            (
+                "SELECT dept, stddev(salary) FILTER (WHERE id > 1) FROM employees GROUP BY dept",
+                "SELECT dept, jsonb_agg(salary) FILTER (WHERE id > 1) AS stddev FROM employees GROUP BY dept",
+                ProxyAggregate::StddevSamp,
+            ),
+            (
+                "SELECT dept, percentile_disc(0.9) WITHIN GROUP (ORDER BY salary) FROM employees GROUP BY dept",
+                "SELECT dept, jsonb_agg(salary) AS percentile_disc FROM employees GROUP BY dept",
+                ProxyAggregate::PercentileDisc(0.9),
+            ),
+        ] {
+            let statement = parse(input);
+            let typed = type_check_with_proxy_aggregates(schema.clone(), &statement).unwrap();
 
-        assert!(
-            type_check(schema, &statement).is_err(),
-            "SELECT INTO projecting an encrypted column should fail type checking"
-        );
+            assert_eq!(typed.proxy_aggregates.for_column(0), None);
+            assert_eq!(typed.proxy_aggregates.for_column(1), Some(aggregate));
+
+            // The column decrypts as the input column does.
+            let projection = typed.projection.columns();
+            assert!(matches!(
+                &*projection[1].ty,
+                Type::Value(Value::Eql(term))
+                    if term.table_column() == &TableColumn { table: id("employees"), column: id("salary") }
+            ));
+
+            assert_eq!(
+                typed.transform(HashMap::new()).unwrap().to_string(),
+                expected,
+                "unexpected rewrite for `{input}`"
+            );
+        }
     }
 
-    /// `SELECT ... INTO` with only native columns passes through untouched.
+    /// Without the option nothing changes: the aggregate is an ordinary function
+    /// over an encrypted value and fails the type check.
     #[test]
-    fn select_into_with_native_columns_is_allowed() {
+    fn proxy_aggregates_are_opt_in() {
         let schema = resolver(schema! {
             tables: {
                 employees: {
                     id,
-                    salary (EQL),
+                    salary (EQL("eql_v3_integer_ord"): Ord),
                 }
             }
         });
 
-        let statement = parse("SELECT id INTO tmp_table FROM employees");
+        let statement = parse("SELECT sum(salary) FROM employees");
+        assert!(type_check(schema.clone(), &statement).is_err());
 
-        assert!(type_check(schema, &statement).is_ok());
+        // Over a native column PostgreSQL computes the aggregate as usual.
+        let statement = parse("SELECT sum(id) FROM employees");
+        let typed = type_check_with_proxy_aggregates(schema, &statement).unwrap();
+        assert!(typed.proxy_aggregates.is_empty());
+        assert!(!typed.requires_transform());
+    }
+
+    /// The proxy computes the aggregate from the row it receives, so anywhere
+    /// the database would consume the shipped values itself is rejected.
+    #[test]
+    fn proxy_aggregate_outside_the_top_level_select_list_is_rejected() {
+        let schema = resolver(schema! {
+            tables: {
+                employees: {
+                    id,
+                    dept,
+                    name (EQL("eql_v3_text_eq"): Eq),
+                    salary (EQL("eql_v3_integer_ord"): Ord),
+                }
+            }
+        });
+
+        for input in [
+            "SELECT dept FROM employees GROUP BY dept HAVING sum(salary) > 10",
+            "SELECT dept, sum(salary) FROM employees GROUP BY dept ORDER BY sum(salary)",
+            "SELECT dept, sum(salary) FROM employees GROUP BY dept ORDER BY 2",
+            "SELECT dept, sum(salary) AS total FROM employees GROUP BY dept ORDER BY total",
+            "SELECT s.total FROM (SELECT sum(salary) AS total FROM employees) AS s",
+            "SELECT sum(salary) FROM employees UNION ALL SELECT sum(salary) FROM employees",
+            "SELECT DISTINCT sum(salary) FROM employees",
+            "SELECT sum(salary) OVER () FROM employees",
+            "SELECT sum(DISTINCT salary) FROM employees",
+            "SELECT percentile_disc($1) WITHIN GROUP (ORDER BY salary) FROM employees",
+            "SELECT sum(name) FROM employees",
+        ] {
+            let statement = parse(input);
+            assert!(
+                type_check_with_proxy_aggregates(schema.clone(), &statement).is_err(),
+                "expected `{input}` to be rejected"
+            );
+        }
+    }
+
+    /// The rewritten statement names the rules that produced it, for
+    /// diagnostics.
+    #[test]
+    fn transformed_statement_reports_applied_rules() {
+        let schema = resolver(schema! {
+            tables: {
+                employees: {
+                    id,
+                    salary (EQL: Eq),
+                }
+            }
+        });
+
+        let statement = parse("SELECT id FROM employees WHERE salary = $1");
+        let typed = type_check(schema, &statement).unwrap();
+        let transformed = typed.transform(HashMap::new()).unwrap();
+
+        assert_eq!(transformed.applied_rules, vec!["RewriteEqlComparisonOps"]);
+    }
+
+    /// `CREATE TABLE AS` is type checked for its query, so a predicate on an
+    /// encrypted column is rewritten like any other. A projected column keeps
+    /// its domain in the new table: a bare column already has it, and a
+    /// computed value is cast back to it under its original name.
+    #[test]
+    fn create_table_as_keeps_encrypted_domains() {
+        let schema = resolver(schema! {
+            tables: {
+                employees: {
+                    id,
+                    email (EQL: Eq),
+                }
+            }
+        });
+
+        for (input, expected) in [
+            (
+                "CREATE TABLE snapshot AS SELECT id, email FROM employees WHERE email = $1",
+                "CREATE TABLE snapshot AS SELECT id, email FROM employees \
+                 WHERE eql_v3.eq_term(email) = eql_v3.eq_term($1::JSONB::eql_v3.query_text_eq)",
+            ),
+            (
+                "CREATE TABLE snapshot AS SELECT email FROM employees GROUP BY email",
+                "CREATE TABLE snapshot AS SELECT eql_v3.grouped_value(email)::JSONB::public.eql_v3_text_eq AS email \
+                 FROM employees GROUP BY eql_v3.eq_term(email)",
+            ),
+        ] {
+            let statement = parse(input);
+            let typed = type_check(schema.clone(), &statement)
+                .unwrap_or_else(|err| panic!("type check failed for `{input}`: {err}"));
+
+            assert!(typed.projection.is_empty());
+            assert_eq!(
+                typed.transform(HashMap::new()).unwrap().to_string(),
+                expected
+            );
+        }
+    }
+
+    /// `SELECT ... INTO` is the same as `CREATE TABLE AS`, spelled as a query.
+    #[test]
+    fn select_into_keeps_encrypted_domains() {
+        let schema = resolver(schema! {
+            tables: {
+                employees: {
+                    id,
+                    email (EQL: Eq),
+                }
+            }
+        });
+
+        for (input, expected) in [
+            (
+                "SELECT id, email INTO snapshot FROM employees",
+                "SELECT id, email INTO snapshot FROM employees",
+            ),
+            (
+                "SELECT email INTO snapshot FROM employees GROUP BY email",
+                "SELECT eql_v3.grouped_value(email)::JSONB::public.eql_v3_text_eq AS email \
+                 INTO snapshot FROM employees GROUP BY eql_v3.eq_term(email)",
+            ),
+        ] {
+            let statement = parse(input);
+            let typed = type_check(schema.clone(), &statement)
+                .unwrap_or_else(|err| panic!("type check failed for `{input}`: {err}"));
+
+            assert_eq!(
+                typed.transform(HashMap::new()).unwrap().to_string(),
+                expected
+            );
+        }
     }
 
     /// `ORDER BY ALL` (DuckDB/ClickHouse syntax) names every projected column
@@ -5642,4 +5833,296 @@ mod test {
             "ORDER BY ALL should fail type checking"
         );
     }
+
+    /// Builds a catalog view the way the proxy's schema loader does: type check
+    /// the definition against `schema` and keep the resulting projection.
+    fn add_view(schema: &mut Schema, name: &str, definition: &str) {
+        let definition = parse(definition);
+        let projection = type_check(resolver(schema.clone()), &definition)
+            .unwrap_or_else(|err| panic!("view definition failed to type check: {err}"))
+            .projection;
+        schema.add_view(View {
+            name: id(name),
+            projection,
+        });
+    }
+
+    #[test]
+    fn select_from_catalog_view_keeps_base_column_identity() {
+        let mut schema = schema! {
+            tables: {
+                customers: {
+                    id,
+                    email_enc (EQL: Eq),
+                    active,
+                }
+            }
+        };
+        add_view(
+            &mut schema,
+            "active_customers",
+            "SELECT id, email_enc FROM customers WHERE active = true",
+        );
+
+        let statement = parse("SELECT email_enc FROM active_customers");
+
+        match type_check(resolver(schema), &statement) {
+            Ok(typed) => assert_eq!(
+                typed.projection,
+                concrete_ty! {{EQL(customers.email_enc: Eq) as email_enc} as Projection}
+            ),
+            Err(err) => panic!("type check failed: {err}"),
+        }
+    }
+
+    #[test]
+    fn predicate_on_catalog_view_column_is_encrypted() {
+        let mut schema = schema! {
+            tables: {
+                customers: {
+                    id,
+                    email_enc (EQL: Eq),
+                }
+            }
+        };
+        add_view(
+            &mut schema,
+            "active_customers",
+            "SELECT id, email_enc FROM customers",
+        );
+
+        let statement =
+            parse("SELECT id FROM active_customers WHERE email_enc = 'alice@example.com'");
+
+        match type_check(resolver(schema), &statement) {
+            Ok(typed) => {
+                assert_eq!(typed.literals.len(), 1);
+                assert_eq!(
+                    typed.literals[0].0.table_column(),
+                    &TableColumn {
+                        table: id("customers"),
+                        column: id("email_enc"),
+                    }
+                );
+            }
+            Err(err) => panic!("type check failed: {err}"),
+        }
+    }
+
+    #[test]
+    fn catalog_view_over_catalog_view_resolves() {
+        let mut schema = schema! {
+            tables: {
+                customers: {
+                    id,
+                    email_enc (EQL: Eq),
+                }
+            }
+        };
+        add_view(&mut schema, "v1", "SELECT id, email_enc FROM customers");
+        add_view(&mut schema, "v2", "SELECT email_enc FROM v1");
+
+        let statement = parse("SELECT * FROM v2");
+
+        match type_check(resolver(schema), &statement) {
+            Ok(typed) => assert_eq!(
+                typed.projection,
+                concrete_ty! {{EQL(customers.email_enc: Eq) as email_enc} as Projection}
+            ),
+            Err(err) => panic!("type check failed: {err}"),
+        }
+    }
+
+    #[test]
+    fn catalog_view_dropped_in_transaction_is_not_resolved() {
+        let mut schema = schema! {
+            tables: {
+                customers: {
+                    id,
+                    email_enc (EQL: Eq),
+                }
+            }
+        };
+        add_view(&mut schema, "v1", "SELECT id, email_enc FROM customers");
+
+        let resolver = Arc::new(TableResolver::new_editable(schema.into()));
+        crate::collect_ddl(resolver.clone(), &parse("DROP VIEW v1"));
+
+        assert!(type_check(resolver, &parse("SELECT email_enc FROM v1")).is_err());
+    }
+
+    #[test]
+    fn user_declared_function_preserves_eql_type() {
+        let mut schema = schema! {
+            tables: {
+                users: {
+                    id,
+                    email (EQL: Eq),
+                }
+            }
+        };
+        schema.sql_decls =
+            Arc::new(SqlDecls::parse("app.normalize_email<T>(T) -> T where T: Eq").unwrap());
+
+        let statement = parse("SELECT app.normalize_email(email) AS email FROM users");
+
+        match type_check(resolver(schema), &statement) {
+            Ok(typed) => assert_eq!(
+                typed.projection,
+                concrete_ty! {{EQL(users.email: Eq) as email} as Projection}
+            ),
+            Err(err) => panic!("type check failed: {err}"),
+        }
+    }
+
+    #[test]
+    fn user_declared_function_bounds_are_enforced() {
+        let mut schema = schema! {
+            tables: {
+                users: {
+                    id,
+                    email (EQL),
+                }
+            }
+        };
+        schema.sql_decls =
+            Arc::new(SqlDecls::parse("app.normalize_email<T>(T) -> T where T: Eq").unwrap());
+
+        let statement = parse("SELECT app.normalize_email(email) FROM users");
+
+        assert!(type_check(resolver(schema), &statement).is_err());
+    }
+
+    #[test]
+    fn undeclared_function_on_eql_column_still_falls_back_to_native() {
+        let schema = schema! {
+            tables: {
+                users: {
+                    id,
+                    email (EQL: Eq),
+                }
+            }
+        };
+
+        let statement = parse("SELECT app.normalize_email(email) FROM users");
+
+        assert!(type_check(resolver(schema), &statement).is_err());
+    }
+
+    #[test]
+    fn user_declared_operator_is_applied() {
+        let mut schema = schema! {
+            tables: {
+                users: {
+                    id,
+                    email (EQL: Eq),
+                }
+            }
+        };
+        schema.sql_decls =
+            Arc::new(SqlDecls::parse("<T>(T OPERATOR(app.~=) T) -> Native where T: Eq").unwrap());
+
+        let statement =
+            parse("SELECT id FROM users WHERE email OPERATOR(app.~=) 'alice@example.com'");
+
+        match type_check(resolver(schema), &statement) {
+            Ok(typed) => assert_eq!(typed.literals.len(), 1),
+            Err(err) => panic!("type check failed: {err}"),
+        }
+    }
+
+    /// `cipherstash.raw(col)` projects the stored EQL payload as native jsonb, so
+    /// it is not decrypted while the other encrypted columns still are.
+    #[test]
+    fn cipherstash_raw_projects_native_payload() {
+        let schema = resolver(schema! {
+            tables: {
+                users: {
+                    id,
+                    email (EQL: Eq),
+                    first_name (EQL),
+                }
+            }
+        });
+
+        let statement = parse("SELECT id, cipherstash.raw(email) AS email, first_name FROM users");
+
+        let typed = match type_check(schema, &statement) {
+            Ok(typed) => typed,
+            Err(err) => panic!("type check failed: {err:#?}"),
+        };
+
+        assert_eq!(
+            typed.projection,
+            projection![
+                (NATIVE(users.id) as id),
+                (NATIVE as email),
+                (EQL(users.first_name) as first_name)
+            ]
+        );
+
+        match typed.transform(HashMap::new()) {
+            Ok(transformed_statement) => assert_eq!(
+                transformed_statement.to_string(),
+                "SELECT id, email::JSONB AS email, first_name FROM users"
+            ),
+            Err(err) => panic!("statement transformation failed: {err}"),
+        }
+    }
+
+    #[test]
+    fn cipherstash_raw_on_native_value_returns_argument() {
+        let schema = resolver(schema! {
+            tables: {
+                users: {
+                    id,
+                    email (EQL: Eq),
+                }
+            }
+        });
+
+        let statement = parse("SELECT cipherstash.raw(id + 1) FROM users");
+        let typed = type_check(schema, &statement).unwrap();
+
+        assert_eq!(
+            typed.transform(HashMap::new()).unwrap().to_string(),
+            "SELECT (id + 1) FROM users"
+        );
+    }
+
+    #[test]
+    fn statements_referencing_encrypted_tables() {
+        let schema = resolver(schema! {
+            tables: {
+                users: {
+                    id,
+                    email (EQL: Eq),
+                }
+                todos: {
+                    id,
+                    title,
+                }
+            }
+        });
+
+        for sql in [
+            "SELECT id FROM users",
+            "SELECT t.id FROM todos t JOIN public.users u ON u.id = t.id",
+            "INSERT INTO users (id) VALUES (1)",
+            "UPDATE users SET id = 2",
+            "DELETE FROM users WHERE id = 1",
+            "SELECT id FROM todos WHERE id IN (SELECT id FROM users)",
+            "SELECT no_such_function(id) FROM users",
+        ] {
+            assert!(references_encrypted_table(&schema, &parse(sql)), "{sql}");
+        }
+
+        for sql in [
+            "SELECT id FROM todos",
+            "SELECT 1",
+            "SELECT id FROM unknown_table",
+        ] {
+            assert!(!references_encrypted_table(&schema, &parse(sql)), "{sql}");
+        }
+    }
 }
@@ -5,7 +5,8 @@
 use super::ident_case::*;
 use crate::{
     iterator_ext::IteratorExt,
-    unifier::{DomainIdentity, EqlTraits},
+    unifier::{DomainIdentity, EqlTraits, Projection},
+    SqlDecls,
 };
 use core::fmt::Debug;
 use derive_more::Display;
@@ -15,12 +16,18 @@ use thiserror::Error;
 
 /// A database schema.
 ///
-/// It has a name and some tables. Tables and views are represented identically.
+/// It has a name, some tables and some views. Views created by DDL in the
+/// current transaction are modelled as tables (see [`super::SchemaWithEdits`]);
+/// views loaded from the database catalog are [`View`]s.
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Schema {
     pub name: Ident,
     pub tables: Vec<Arc<Table>>,
+    pub views: Vec<Arc<View>>,
     pub aggregates: Vec<Arc<String>>,
+    /// User-declared function and operator signatures, consulted for anything
+    /// the built-in registry does not cover.
+    pub sql_decls: Arc<SqlDecls>,
 }
 
 /// A table (or view).
@@ -30,9 +37,25 @@ pub struct Schema {
 #[display("Table<{}>", name)]
 pub struct Table {
     pub name: Ident,
+    /// The schema the table was loaded from, if known
+    pub schema: Option<Ident>,
     pub columns: Vec<Arc<Column>>,
 }
 
+/// A view loaded from the database catalog.
+///
+/// A view has no columns of its own: its projection is the type of its defining
+/// query, as derived by [`crate::type_check`], with each column renamed to the
+/// name the catalog reports for it. An encrypted view column therefore keeps
+/// the `TableColumn` of the base table column it was selected from, which is
+/// what the proxy needs to find its encrypt configuration.
+#[derive(Debug, Clone, PartialEq, Eq, Display)]
+#[display("View<{}>", name)]
+pub struct View {
+    pub name: Ident,
+    pub projection: Projection,
+}
+
 /// A column.
 #[derive(Debug, Clone, PartialEq, Eq, Display, Hash)]
 #[display("Column<{}: {}>", name, kind)]
@@ -105,6 +128,9 @@ pub enum SchemaError {
     #[error("Table not found: {}", _0)]
     TableNotFound(String),
 
+    #[error("View not found: {}", _0)]
+    ViewNotFound(String),
+
     #[error("Column: {} not found for table: {}", _0, _1)]
     ColumnNotFound(String, String),
 }
@@ -120,15 +146,22 @@ impl Schema {
         Self {
             name,
             tables: Default::default(),
+            views: Default::default(),
             aggregates: Default::default(),
+            sql_decls: Default::default(),
         }
     }
 
-    /// Adds a table (or view) to the schema.
+    /// Adds a table to the schema.
     pub fn add_table(&mut self, table: Table) {
         self.tables.push(Arc::new(table));
     }
 
+    /// Adds a view to the schema.
+    pub fn add_view(&mut self, view: View) {
+        self.views.push(Arc::new(view));
+    }
+
     /// Resolves a table by `Ident`, which takes into account the SQL rules
     /// of quoted and new identifier matching.
     pub fn resolve_table(&self, name: &ObjectName) -> Result<Arc<Table>, SchemaError> {
@@ -144,6 +177,21 @@ impl Schema {
         }
     }
 
+    /// Resolves a view by `Ident`, using the same identifier matching rules as
+    /// [`Schema::resolve_table`].
+    pub fn resolve_view(&self, name: &ObjectName) -> Result<Arc<View>, SchemaError> {
+        if name.0.len() == 1 {
+            let ObjectNamePart::Identifier(name) = name.0.last().unwrap();
+            let mut haystack = self.views.iter();
+            haystack
+                .find_unique(&|view| IdentCase::from(&view.name) == IdentCase::from(name))
+                .cloned()
+                .map_err(|_| SchemaError::ViewNotFound(name.to_string()))
+        } else {
+            Err(SchemaError::ViewNotFound(format!("{name}")))
+        }
+    }
+
     pub fn resolve_table_columns(
         &self,
         table_name: &ObjectName,
@@ -198,6 +246,7 @@ impl Table {
     pub fn new(name: Ident) -> Self {
         Self {
             name,
+            schema: None,
             columns: Vec::with_capacity(16),
         }
     }
@@ -221,6 +270,20 @@ impl Table {
             .cloned()
             .map_err(|_| SchemaError::ColumnNotFound(self.name.to_string(), name.to_string()))
     }
+
+    /// Checks if any column of the table holds encrypted data, including columns this build cannot map.
+    pub fn has_encrypted_columns(&self) -> bool {
+        self.columns
+            .iter()
+            .any(|column| column.kind != ColumnKind::Native)
+    }
+}
+
+impl View {
+    /// Checks if any column of the view is selected from an encrypted column.
+    pub fn has_encrypted_columns(&self) -> bool {
+        self.projection.contains_eql()
+    }
 }
 
 #[macro_export]
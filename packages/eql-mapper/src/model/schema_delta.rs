@@ -7,13 +7,17 @@ use std::{
 
 use sqltk::parser::ast::{
     AlterTableOperation, ColumnDef, CreateTable, Ident, ObjectName, ObjectNamePart, ObjectType,
-    Statement, ViewColumnDef,
+    SelectInto, SetExpr, Statement, ViewColumnDef,
 };
 
 use sqltk::{Break, Visitable, Visitor};
 
+use crate::unifier::{EqlTerm, EqlValue, Type, Value};
+use crate::{type_check, SqlDecls};
+
 use super::{
     Column, ColumnKind, IdentCase, Schema, SchemaError, SchemaTableColumn, Table, TableResolver,
+    View,
 };
 
 /// The current state of the schema as viewed by the current transaction.
@@ -67,6 +71,22 @@ impl SchemaWithEdits {
         }
     }
 
+    pub(crate) fn sql_decls(&self) -> Arc<SqlDecls> {
+        self.schema.sql_decls.clone()
+    }
+
+    /// Resolves a view loaded from the catalog.
+    ///
+    /// Any overlay for the name shadows the loaded view: a view dropped in the
+    /// current transaction is gone, and one (re)created in it is resolved as a
+    /// table by [`Self::resolve_table`].
+    pub(crate) fn resolve_view(&self, name: &ObjectName) -> Result<Arc<View>, SchemaError> {
+        match self.overlays.get(name) {
+            Some(_) => Err(SchemaError::ViewNotFound(name.to_string())),
+            None => self.schema.resolve_view(name),
+        }
+    }
+
     pub(crate) fn resolve_table_columns(
         &self,
         table_name: &ObjectName,
@@ -127,6 +147,7 @@ enum Overlay {
 #[derive(Debug, Clone)]
 struct OverlayTable {
     pub name: ObjectName,
+    pub schema: Option<Ident>,
     pub columns: Vec<Column>,
 }
 
@@ -134,6 +155,7 @@ impl OverlayTable {
     fn new(name: ObjectName) -> Self {
         Self {
             name,
+            schema: None,
             columns: Vec::new(),
         }
     }
@@ -169,6 +191,7 @@ impl From<&Table> for OverlayTable {
     fn from(value: &Table) -> Self {
         Self {
             name: ObjectName(vec![ObjectNamePart::Identifier(value.name.clone())]),
+            schema: value.schema.clone(),
             columns: value.columns.iter().map(|col| (**col).clone()).collect(),
         }
     }
@@ -181,6 +204,7 @@ impl From<&OverlayTable> for Table {
 
         Self {
             name: ident.clone(),
+            schema: value.schema.clone(),
             columns: value.columns.iter().cloned().map(Arc::new).collect(),
         }
     }
@@ -192,6 +216,7 @@ impl From<&OverlayTable> for Table {
 pub fn collect_ddl(table_resolver: Arc<TableResolver>, statement: &Statement) -> bool {
     if let Some(schema_with_edits) = table_resolver.as_schema_with_edits() {
         let mut visitor = DdlCollector {
+            table_resolver: table_resolver.clone(),
             schema: schema_with_edits,
             changed: false,
         };
@@ -203,11 +228,60 @@ pub fn collect_ddl(table_resolver: Arc<TableResolver>, statement: &Statement) ->
 }
 
 struct DdlCollector {
+    table_resolver: Arc<TableResolver>,
     schema: Arc<RwLock<SchemaWithEdits>>,
     changed: bool,
 }
 
 impl DdlCollector {
+    /// Captures a table created from the result of `query`, as by
+    /// `CREATE TABLE ... AS` or `SELECT ... INTO`.
+    ///
+    /// An encrypted column of the result keeps its domain in the new table (see
+    /// [`crate::CastFullPayloadOperands`]), so the new table's column is
+    /// encrypted too. `names` overrides the result's column names, as the column
+    /// list of `CREATE TABLE t (a, b) AS ...` does.
+    ///
+    /// If `query` does not type check, its columns are captured as native: the
+    /// statement will fail the same check when it is mapped.
+    fn capture_create_table_as(&self, name: &ObjectName, names: &[Ident], query: &Statement) {
+        let columns = match type_check(self.table_resolver.clone(), query) {
+            Ok(typed) => typed
+                .projection
+                .columns()
+                .iter()
+                .enumerate()
+                .map(|(idx, column)| Column {
+                    name: names
+                        .get(idx)
+                        .or(column.alias.as_ref())
+                        .cloned()
+                        .unwrap_or_else(|| Ident::new("?column?")),
+                    kind: match &*column.ty {
+                        Type::Value(Value::Eql(EqlTerm::Full(EqlValue(_, identity, features)))) => {
+                            ColumnKind::Eql(*features, identity.clone())
+                        }
+                        _ => ColumnKind::Native,
+                    },
+                })
+                .collect(),
+            Err(_) => names
+                .iter()
+                .map(|name| Column {
+                    name: name.clone(),
+                    kind: ColumnKind::Native,
+                })
+                .collect(),
+        };
+
+        let mut table = OverlayTable::new(name.clone());
+        for column in columns {
+            table.add_column(column);
+        }
+
+        *self.schema.write().unwrap().get_overlay_mut(name) = Overlay::Table(table)
+    }
+
     fn capture_create_view(&self, name: &ObjectName, columns: &[ViewColumnDef]) {
         let name = name.clone();
         let mut table = OverlayTable::new(name.clone());
@@ -328,11 +402,29 @@ impl<'ast> Visitor<'ast> for DdlCollector {
                     self.changed = true;
                 }
 
+                Statement::CreateTable(CreateTable {
+                    name,
+                    columns,
+                    query: Some(query),
+                    ..
+                }) => {
+                    let names: Vec<Ident> = columns.iter().map(|def| def.name.clone()).collect();
+                    self.capture_create_table_as(name, &names, &Statement::Query(query.clone()));
+                    self.changed = true;
+                }
+
                 Statement::CreateTable(CreateTable { name, columns, .. }) => {
                     self.capture_create_table(name, columns);
                     self.changed = true;
                 }
 
+                Statement::Query(query) => {
+                    if let Some(into) = select_into(&query.body) {
+                        self.capture_create_table_as(&into.name, &[], statement);
+                        self.changed = true;
+                    }
+                }
+
                 Statement::AlterTable {
                     name, operations, ..
                 } => {
@@ -358,6 +450,17 @@ impl<'ast> Visitor<'ast> for DdlCollector {
     }
 }
 
+/// The `INTO` clause of a `SELECT ... INTO`, which PostgreSQL allows only on
+/// the first `SELECT` of a set operation.
+fn select_into(body: &SetExpr) -> Option<&SelectInto> {
+    match body {
+        SetExpr::Select(select) => select.into.as_ref(),
+        SetExpr::Query(query) => select_into(&query.body),
+        SetExpr::SetOperation { left, .. } => select_into(left),
+        _ => None,
+    }
+}
+
 #[cfg(test)]
 mod test {
     use std::sync::Arc;
@@ -516,6 +619,81 @@ mod test {
         )
     }
 
+    #[test]
+    fn create_table_as_keeps_encrypted_columns() {
+        let schema = Arc::new(schema! {
+            tables: {
+                users: {
+                    id,
+                    email (EQL),
+                }
+            }
+        });
+
+        for sql in [
+            "create table snapshot as select id, email from users",
+            "select id, email into snapshot from users",
+        ] {
+            let resolver = Arc::new(TableResolver::new_editable(schema.clone()));
+
+            crate::collect_ddl(resolver.clone(), &parse(sql));
+
+            assert_eq!(
+                resolver.resolve_table_column(&object_name("snapshot"), &id("id")),
+                Ok(SchemaTableColumn {
+                    table: id("snapshot"),
+                    column: id("id"),
+                    kind: ColumnKind::Native
+                }),
+                "{sql}"
+            );
+
+            assert_eq!(
+                resolver.resolve_table_column(&object_name("snapshot"), &id("email")),
+                Ok(SchemaTableColumn {
+                    table: id("snapshot"),
+                    column: id("email"),
+                    kind: ColumnKind::Eql(
+                        EqlTraits::default(),
+                        DomainIdentity::canonical(TokenType::Text, EqlTraits::default())
+                    )
+                }),
+                "{sql}"
+            );
+        }
+    }
+
+    #[test]
+    fn create_table_as_with_column_names() {
+        let schema = Arc::new(schema! {
+            tables: {
+                users: {
+                    id,
+                    email (EQL),
+                }
+            }
+        });
+
+        let resolver = Arc::new(TableResolver::new_editable(schema));
+
+        let statement =
+            parse("create table snapshot (user_id, contact) as select id, email from users");
+
+        crate::collect_ddl(resolver.clone(), &statement);
+
+        assert_eq!(
+            resolver.resolve_table_column(&object_name("snapshot"), &id("contact")),
+            Ok(SchemaTableColumn {
+                table: id("snapshot"),
+                column: id("contact"),
+                kind: ColumnKind::Eql(
+                    EqlTraits::default(),
+                    DomainIdentity::canonical(TokenType::Text, EqlTraits::default())
+                )
+            })
+        )
+    }
+
     #[test]
     fn drop_table() {
         let schema = Arc::new(schema! {
@@ -2,7 +2,9 @@ use std::sync::{Arc, RwLock};
 
 use sqltk::parser::ast::{Ident, ObjectName};
 
-use super::{Schema, SchemaError, SchemaTableColumn, SchemaWithEdits, Table};
+use crate::SqlDecls;
+
+use super::{Schema, SchemaError, SchemaTableColumn, SchemaWithEdits, Table, View};
 
 #[derive(Debug)]
 pub enum TableResolver {
@@ -35,6 +37,15 @@ impl TableResolver {
         }
     }
 
+    pub fn sql_decls(&self) -> Arc<SqlDecls> {
+        match self {
+            TableResolver::ViaSchema(schema) => schema.sql_decls.clone(),
+            TableResolver::ViaSchemaWithEdits(schema_with_edits) => {
+                schema_with_edits.read().unwrap().sql_decls()
+            }
+        }
+    }
+
     pub fn resolve_table(&self, name: &ObjectName) -> Result<Arc<Table>, SchemaError> {
         match self {
             TableResolver::ViaSchema(schema) => schema.resolve_table(name),
@@ -44,6 +55,15 @@ impl TableResolver {
         }
     }
 
+    pub fn resolve_view(&self, name: &ObjectName) -> Result<Arc<View>, SchemaError> {
+        match self {
+            TableResolver::ViaSchema(schema) => schema.resolve_view(name),
+            TableResolver::ViaSchemaWithEdits(schema_with_edits) => {
+                schema_with_edits.read().unwrap().resolve_view(name)
+            }
+        }
+    }
+
     pub fn resolve_table_columns(
         &self,
         table_name: &ObjectName,
@@ -0,0 +1,269 @@
+//! Aggregates over encrypted numerics that the **proxy** computes, because
+//! PostgreSQL cannot.
+//!
+//! An encrypted numeric column supports only `min`/`max`/`count` in the
+//! database: those need nothing more than an ordering term. `sum`, `avg`, the
+//! variance family and ordered-set aggregates such as `percentile_disc` need the
+//! plaintext, which PostgreSQL never sees.
+//!
+//! When enabled via [`crate::TypeCheckOptions::proxy_aggregates`] such an
+//! aggregate is rewritten to ship the group's encrypted values instead —
+//!
+//! ```sql
+//! SELECT dept, avg(salary) FROM employees GROUP BY dept
+//! -- becomes
+//! SELECT dept, jsonb_agg(salary) AS avg FROM employees GROUP BY dept
+//! ```
+//!
+//! — and the proxy decrypts each group's values and computes the aggregate
+//! before it writes the row to the client.
+//!
+//! The proxy can only do that for a value it actually receives, so an aggregate
+//! is accepted only as a top-level item of the outermost `SELECT` list. Anywhere
+//! else — `HAVING`, `ORDER BY`, a subquery, a CTE, one side of a `UNION` — the
+//! database would consume the shipped array itself, and the statement is
+//! rejected.
+
+use std::collections::{BTreeMap, HashMap};
+
+use derive_more::Display;
+use sqltk::parser::ast::{
+    Expr, Function, Ident, ObjectName, ObjectNamePart, OrderByKind, SelectItem, SetExpr, Statement,
+    Value as SqltkValue,
+};
+use sqltk::NodeKey;
+
+use crate::inference::{resolve_positional_key, TypeError};
+use crate::transformation_rules::derive_effective_alias;
+
+/// An aggregate the proxy computes from decrypted values.
+#[derive(Debug, Clone, Copy, PartialEq, Display)]
+pub enum ProxyAggregate {
+    #[display("sum")]
+    Sum,
+    #[display("avg")]
+    Avg,
+    #[display("var_samp")]
+    VarSamp,
+    #[display("var_pop")]
+    VarPop,
+    #[display("stddev_samp")]
+    StddevSamp,
+    #[display("stddev_pop")]
+    StddevPop,
+    /// `percentile_disc(fraction) WITHIN GROUP (ORDER BY col)`. The fraction
+    /// must be a literal: the proxy needs it to compute the result, and a
+    /// placeholder is not bound until long after the statement is rewritten.
+    #[display("percentile_disc")]
+    PercentileDisc(f64),
+}
+
+impl ProxyAggregate {
+    /// The aggregate a function name refers to, ignoring `percentile_disc`'s
+    /// fraction (see [`Self::with_fraction`]).
+    ///
+    /// Only a built-in is eligible: an unqualified name, or one explicitly
+    /// qualified with `pg_catalog`, for the same reason as
+    /// `get_eql_v3_function_name`.
+    pub(crate) fn from_function_name(name: &ObjectName) -> Option<Self> {
+        let bare = match &name.0[..] {
+            [ObjectNamePart::Identifier(name)] => name,
+            [ObjectNamePart::Identifier(schema), ObjectNamePart::Identifier(name)]
+                if schema.value.eq_ignore_ascii_case("pg_catalog") =>
+            {
+                name
+            }
+            _ => return None,
+        };
+
+        match bare.value.to_lowercase().as_str() {
+            "sum" => Some(Self::Sum),
+            "avg" => Some(Self::Avg),
+            "variance" | "var_samp" => Some(Self::VarSamp),
+            "var_pop" => Some(Self::VarPop),
+            "stddev" | "stddev_samp" => Some(Self::StddevSamp),
+            "stddev_pop" => Some(Self::StddevPop),
+            "percentile_disc" => Some(Self::PercentileDisc(0.0)),
+            _ => None,
+        }
+    }
+
+    /// Whether the aggregate takes its input from `WITHIN GROUP (ORDER BY ...)`
+    /// rather than from its argument list.
+    pub fn is_ordered_set(&self) -> bool {
+        matches!(self, Self::PercentileDisc(_))
+    }
+
+    pub(crate) fn with_fraction(self, fraction: f64) -> Self {
+        match self {
+            Self::PercentileDisc(_) => Self::PercentileDisc(fraction),
+            other => other,
+        }
+    }
+}
+
+/// The proxy-computed aggregates of a statement, by the function node that was
+/// rewritten and by the projection column the proxy receives it in.
+#[derive(Debug, Default, Clone)]
+pub struct ProxyAggregates<'ast> {
+    functions: HashMap<NodeKey<'ast>, ProxyAggregate>,
+    columns: BTreeMap<usize, ProxyAggregate>,
+}
+
+impl<'ast> ProxyAggregates<'ast> {
+    /// The aggregate the proxy must compute for projection column `idx`, if any.
+    pub fn for_column(&self, idx: usize) -> Option<ProxyAggregate> {
+        self.columns.get(&idx).copied()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.columns.is_empty()
+    }
+
+    /// Whether `function` is rewritten to ship its encrypted inputs.
+    pub(crate) fn contains(&self, function: &Function) -> bool {
+        self.functions.contains_key(&NodeKey::new(function))
+    }
+
+    /// Places the aggregates recorded during inference in the projection of
+    /// `statement`, failing if any of them is somewhere the proxy never sees
+    /// its result.
+    pub(crate) fn locate(
+        statement: &'ast Statement,
+        functions: HashMap<NodeKey<'ast>, ProxyAggregate>,
+    ) -> Result<Self, TypeError> {
+        if functions.is_empty() {
+            return Ok(Self::default());
+        }
+
+        let unsupported = |detail: &str| {
+            Err(TypeError::UnsupportedSqlFeature(format!(
+                "proxy-side aggregate {detail}"
+            )))
+        };
+
+        let Statement::Query(query) = statement else {
+            return unsupported("outside a SELECT statement");
+        };
+
+        let SetExpr::Select(select) = &*query.body else {
+            return unsupported("in a set operation or VALUES list");
+        };
+
+        if select.distinct.is_some() {
+            return unsupported("in a SELECT DISTINCT");
+        }
+
+        // The rows go into the new table, not to the proxy.
+        if select.into.is_some() {
+            return unsupported("in a SELECT INTO");
+        }
+
+        let mut columns = BTreeMap::new();
+        let mut aliases: Vec<Ident> = Vec::new();
+
+        for (idx, item) in select.projection.iter().enumerate() {
+            let expr = match item {
+                SelectItem::UnnamedExpr(expr) | SelectItem::ExprWithAlias { expr, .. } => expr,
+                // A wildcard shifts every later column by an amount only the
+                // schema knows, so positions would no longer line up.
+                SelectItem::Wildcard(_) | SelectItem::QualifiedWildcard(_, _) => {
+                    return unsupported("alongside a wildcard");
+                }
+            };
+
+            if let Some(aggregate) = strip_nested(expr)
+                .and_then(|function| functions.get(&NodeKey::new(function)).copied())
+            {
+                columns.insert(idx, aggregate);
+                aliases.extend(derive_effective_alias(item));
+            }
+        }
+
+        if columns.len() != functions.len() {
+            return unsupported("outside the top-level SELECT list");
+        }
+
+        // The rewritten column holds the group's encrypted values, so sorting by
+        // it — by ordinal or by its output name — would sort by ciphertext.
+        if let Some(order_by) = &query.order_by {
+            if let OrderByKind::Expressions(exprs) = &order_by.kind {
+                for order_by_expr in exprs {
+                    let key = resolve_positional_key(Some(select), &order_by_expr.expr);
+                    let is_aggregate = strip_nested(key)
+                        .is_some_and(|function| functions.contains_key(&NodeKey::new(function)));
+                    let is_alias = matches!(key, Expr::Identifier(ident)
+                        if aliases.iter().any(|alias| alias.value == ident.value));
+
+                    if is_aggregate || is_alias {
+                        return unsupported("in ORDER BY");
+                    }
+                }
+            }
+        }
+
+        Ok(Self { functions, columns })
+    }
+}
+
+/// The fraction of a `percentile_disc` call, if it is a single numeric literal
+/// in `[0, 1]`.
+pub(crate) fn literal_fraction(expr: &Expr) -> Option<f64> {
+    let Expr::Value(value) = strip_nested_expr(expr) else {
+        return None;
+    };
+
+    let SqltkValue::Number(n, _) = &value.value else {
+        return None;
+    };
+
+    n.to_string()
+        .parse::<f64>()
+        .ok()
+        .filter(|fraction| (0.0..=1.0).contains(fraction))
+}
+
+fn strip_nested(expr: &Expr) -> Option<&Function> {
+    match strip_nested_expr(expr) {
+        Expr::Function(function) => Some(function),
+        _ => None,
+    }
+}
+
+fn strip_nested_expr(expr: &Expr) -> &Expr {
+    match expr {
+        Expr::Nested(inner) => strip_nested_expr(inner),
+        other => other,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn name(parts: &[&str]) -> ObjectName {
+        ObjectName(
+            parts
+                .iter()
+                .map(|part| ObjectNamePart::Identifier(Ident::new(*part)))
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn only_built_in_names_are_proxy_aggregates() {
+        assert_eq!(
+            ProxyAggregate::from_function_name(&name(&["SUM"])),
+            Some(ProxyAggregate::Sum)
+        );
+        assert_eq!(
+            ProxyAggregate::from_function_name(&name(&["pg_catalog", "stddev"])),
+            Some(ProxyAggregate::StddevSamp)
+        );
+        assert_eq!(
+            ProxyAggregate::from_function_name(&name(&["app", "sum"])),
+            None
+        );
+        assert_eq!(ProxyAggregate::from_function_name(&name(&["min"])), None);
+    }
+}
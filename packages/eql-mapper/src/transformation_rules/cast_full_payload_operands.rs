@@ -1,35 +1,47 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::mem;
 use std::sync::Arc;
 
-use sqltk::parser::ast::{Assignment, Expr, Function, FunctionArguments, Values};
+use sqltk::parser::ast::{
+    Assignment, CreateTable, Expr, Function, FunctionArguments, Ident, Query, Select, SelectItem,
+    SetExpr, Statement, Value as SqltkValue, ValueWithSpan, Values,
+};
+use sqltk::parser::tokenizer::Span;
 use sqltk::{NodeKey, NodePath, Visitable};
 
 use crate::function_arg::{function_arg_value, function_arg_value_mut};
 use crate::unifier::{Type, Value};
 use crate::EqlMapperError;
 
-use super::helpers::{cast_encrypted_operand, full_payload_domain};
+use super::helpers::{cast_encrypted_operand, cast_expr_to_v3_domain, full_payload_domain};
+use super::preserve_effective_aliases::derive_effective_alias;
 use super::TransformationRule;
 
 /// Casts encrypted values that must carry the column's **whole payload** — the
 /// ciphertext plus every search term the column indexes — to the column domain,
 /// rather than to a term-only `eql_v3.query_*` twin.
 ///
-/// Three contexts need it, and none of them is a predicate whose own rewrite
+/// Four contexts need it, and none of them is a predicate whose own rewrite
 /// rule could own the cast:
 ///
 /// - `INSERT INTO t (col) VALUES ($1)` — the value is stored.
 /// - `UPDATE t SET col = 'x'` — likewise.
+/// - `CREATE TABLE t AS SELECT ...` and `SELECT ... INTO t` — the projection
+///   becomes the columns of `t`, which take the type of each projected value.
+///   A bare column reference already has its domain type, but a computed value
+///   (`coalesce(email, $1)`, an `eql_v3.*` call returning `jsonb`) may not, and
+///   would leave `t` with a plain `jsonb` column the proxy does not recognise as
+///   encrypted.
 /// - `eql_v3.jsonb_contains(col, $1)` and friends — a containment needle is a
 ///   whole document, and the cast is what lets PostgreSQL use the GIN index over
 ///   `eql_v3.jsonb_array(col)`. Clients on platforms without operator support
 ///   (Supabase, PostgREST) write these function forms directly, so they arrive
 ///   already spelled `eql_v3.*` with no operator for a rewrite rule to catch.
 ///
-/// The rule fires on the enclosing `Values`, `Assignment` and `Function` nodes
-/// rather than on the value expressions, so "this operand carries a full
-/// payload" is a fact about the construct that owns it, not a guess made by
-/// walking up the tree from a literal.
+/// The rule fires on the enclosing `Values`, `Assignment`, `Function` and
+/// `Select` nodes rather than on the value expressions, so "this operand carries
+/// a full payload" is a fact about the construct that owns it, not a guess made
+/// by walking up the tree from a literal.
 ///
 /// A JSON selector argument is left uncast — [`full_payload_domain`] returns
 /// `None` for it, because `eql_v3.jsonb_path_query(json, text)` takes the bare
@@ -37,11 +49,76 @@ use super::TransformationRule;
 #[derive(Debug)]
 pub struct CastFullPayloadOperands<'ast> {
     node_types: Arc<HashMap<NodeKey<'ast>, Type>>,
+    stored_selects: HashSet<NodeKey<'ast>>,
 }
 
 impl<'ast> CastFullPayloadOperands<'ast> {
-    pub fn new(node_types: Arc<HashMap<NodeKey<'ast>, Type>>) -> Self {
-        Self { node_types }
+    pub fn new(statement: &'ast Statement, node_types: Arc<HashMap<NodeKey<'ast>, Type>>) -> Self {
+        Self {
+            node_types,
+            stored_selects: Self::stored_selects(statement),
+        }
+    }
+
+    /// The `SELECT`s of `statement` whose projection is stored as the columns
+    /// of a new table: every branch of a `CREATE TABLE AS` query, or of a query
+    /// with a `SELECT ... INTO`.
+    fn stored_selects(statement: &'ast Statement) -> HashSet<NodeKey<'ast>> {
+        let mut selects = Vec::new();
+
+        match statement {
+            Statement::CreateTable(CreateTable {
+                query: Some(query), ..
+            }) => Self::collect_selects(query, &mut selects),
+            Statement::Query(query) => {
+                Self::collect_selects(query, &mut selects);
+                if !selects.iter().any(|select| select.into.is_some()) {
+                    selects.clear();
+                }
+            }
+            _ => {}
+        }
+
+        selects.into_iter().map(NodeKey::new).collect()
+    }
+
+    fn collect_selects(query: &'ast Query, selects: &mut Vec<&'ast Select>) {
+        fn walk<'ast>(set_expr: &'ast SetExpr, selects: &mut Vec<&'ast Select>) {
+            match set_expr {
+                SetExpr::Select(select) => selects.push(select),
+                SetExpr::Query(query) => walk(&query.body, selects),
+                SetExpr::SetOperation { left, right, .. } => {
+                    walk(left, selects);
+                    walk(right, selects);
+                }
+                _ => {}
+            }
+        }
+
+        walk(&query.body, selects);
+    }
+
+    /// The domain of a stored projection item, if it is an encrypted value.
+    fn stored_item_domain(&self, item: &'ast SelectItem) -> Option<(String, String)> {
+        let expr = Self::item_expr(item)?;
+
+        match self.node_types.get(&NodeKey::new(expr)) {
+            Some(Type::Value(Value::Eql(eql_term))) => full_payload_domain(eql_term),
+            _ => None,
+        }
+    }
+
+    /// The expression of a projection item, or `None` for a wildcard — which,
+    /// like a column reference, already has its columns' types.
+    fn item_expr(item: &SelectItem) -> Option<&Expr> {
+        match item {
+            SelectItem::UnnamedExpr(expr) | SelectItem::ExprWithAlias { expr, .. } => Some(expr),
+            SelectItem::Wildcard(_) | SelectItem::QualifiedWildcard(_, _) => None,
+        }
+    }
+
+    fn is_column_reference(expr: &Expr) -> bool {
+        matches!(expr, Expr::Identifier(_) | Expr::CompoundIdentifier(_))
     }
 
     /// Whether `expr` is an encrypted literal or placeholder that this rule
@@ -127,6 +204,59 @@ impl<'ast> TransformationRule<'ast> for CastFullPayloadOperands<'ast> {
             ));
         }
 
+        if let Some((original,)) = node_path.last_1_as::<Select>() {
+            if !self.stored_selects.contains(&NodeKey::new(original)) {
+                return Ok(false);
+            }
+
+            let Some(target) = target_node.downcast_mut::<Select>() else {
+                return Ok(false);
+            };
+
+            let mut edited = false;
+            for (original_item, target_item) in
+                original.projection.iter().zip(target.projection.iter_mut())
+            {
+                let Some((schema, domain)) = self.stored_item_domain(original_item) else {
+                    continue;
+                };
+
+                // The target is checked rather than the original: an earlier
+                // rule may already have wrapped a column reference, as
+                // `RewriteEqlGroupBy` does.
+                if Self::item_expr(target_item).is_none_or(Self::is_column_reference) {
+                    continue;
+                }
+
+                // A cast would otherwise name the column after its type, so
+                // keep the name PostgreSQL would have given the value.
+                let alias =
+                    derive_effective_alias(original_item).unwrap_or_else(|| Ident::new("?column?"));
+
+                let (SelectItem::UnnamedExpr(expr) | SelectItem::ExprWithAlias { expr, .. }) =
+                    target_item
+                else {
+                    continue;
+                };
+
+                let projected = mem::replace(
+                    expr,
+                    Expr::Value(ValueWithSpan {
+                        value: SqltkValue::Null,
+                        span: Span::empty(),
+                    }),
+                );
+
+                *target_item = SelectItem::ExprWithAlias {
+                    expr: cast_expr_to_v3_domain(projected, &schema, &domain),
+                    alias,
+                };
+                edited = true;
+            }
+
+            return Ok(edited);
+        }
+
         if let Some((original,)) = node_path.last_1_as::<Function>() {
             if !Self::is_eql_v3_function(original) {
                 return Ok(false);
@@ -165,6 +295,14 @@ impl<'ast> TransformationRule<'ast> for CastFullPayloadOperands<'ast> {
             return self.needs_cast(&original.value);
         }
 
+        if let Some((original,)) = node_path.last_1_as::<Select>() {
+            return self.stored_selects.contains(&NodeKey::new(original))
+                && original.projection.iter().any(|item| {
+                    self.stored_item_domain(item).is_some()
+                        && !Self::item_expr(item).is_some_and(Self::is_column_reference)
+                });
+        }
+
         if let Some((original,)) = node_path.last_1_as::<Function>() {
             return Self::is_eql_v3_function(original)
                 && Self::args(original).any(|expr| self.needs_cast(expr));
@@ -15,6 +15,7 @@ mod cast_full_payload_operands;
 mod collapse_json_accessor_chain;
 mod fail_on_placeholder_change;
 mod preserve_effective_aliases;
+mod rewrite_cipherstash_raw;
 mod rewrite_containment_ops;
 mod rewrite_eql_aggregate_distinct;
 mod rewrite_eql_any_all_ops;
@@ -27,6 +28,7 @@ mod rewrite_eql_order_by;
 mod rewrite_eql_ordinal_order_by;
 mod rewrite_eql_partition_by;
 mod rewrite_json_value_selector_eq;
+mod rewrite_proxy_aggregates;
 mod rewrite_standard_sql_fns_on_eql_types;
 mod substitute_encrypted_literals;
 
@@ -36,6 +38,7 @@ pub(crate) use cast_full_payload_operands::*;
 pub(crate) use collapse_json_accessor_chain::*;
 pub(crate) use fail_on_placeholder_change::*;
 pub(crate) use preserve_effective_aliases::*;
+pub(crate) use rewrite_cipherstash_raw::*;
 pub(crate) use rewrite_containment_ops::*;
 pub(crate) use rewrite_eql_aggregate_distinct::*;
 pub(crate) use rewrite_eql_any_all_ops::*;
@@ -48,6 +51,7 @@ pub(crate) use rewrite_eql_order_by::*;
 pub(crate) use rewrite_eql_ordinal_order_by::*;
 pub(crate) use rewrite_eql_partition_by::*;
 pub(crate) use rewrite_json_value_selector_eq::*;
+pub(crate) use rewrite_proxy_aggregates::*;
 pub(crate) use rewrite_standard_sql_fns_on_eql_types::*;
 pub(crate) use substitute_encrypted_literals::*;
 
@@ -92,6 +96,12 @@ pub(crate) trait TransformationRule<'ast> {
     fn check_postcondition(&self) -> Result<(), EqlMapperError> {
         Ok(())
     }
+
+    /// Appends the name of every rule that has modified the AST to `rules`, in rule order.
+    ///
+    /// Only a [`Tracked`] rule knows whether it has fired, so the default implementation appends nothing.
+    #[allow(unused)]
+    fn applied_rules(&self, rules: &mut Vec<&'static str>) {}
 }
 
 /// A [`TransformationRule`] with two modes: one for testing if edits will be applied and another for actually applying
@@ -166,6 +176,60 @@ impl<'ast, T: TransformationRule<'ast>> TransformationRule<'ast> for DryRunnable
         self.did_edit = self.rule.would_edit(node_path, target_node) || self.did_edit;
         self.did_edit
     }
+
+    fn applied_rules(&self, rules: &mut Vec<&'static str>) {
+        self.rule.applied_rules(rules)
+    }
+}
+
+/// A [`TransformationRule`] that remembers whether the rule it wraps has modified the AST, so that
+/// [`crate::TransformedStatement::applied_rules`] can report which rules fired.
+#[derive(Debug)]
+pub struct Tracked<T> {
+    rule: T,
+    applied: bool,
+}
+
+impl<T> Tracked<T> {
+    pub fn new(rule: T) -> Self {
+        Self {
+            rule,
+            applied: false,
+        }
+    }
+
+    /// The unqualified type name of the wrapped rule, e.g. `RewriteEqlOrderBy`.
+    fn rule_name() -> &'static str {
+        let name = std::any::type_name::<T>();
+        let name = name.split('<').next().unwrap_or(name);
+        name.rsplit("::").next().unwrap_or(name)
+    }
+}
+
+impl<'ast, T: TransformationRule<'ast>> TransformationRule<'ast> for Tracked<T> {
+    fn apply<N: Visitable>(
+        &mut self,
+        node_path: &NodePath<'ast>,
+        target_node: &mut N,
+    ) -> Result<bool, EqlMapperError> {
+        let did_edit = self.rule.apply(node_path, target_node)?;
+        self.applied |= did_edit;
+        Ok(did_edit)
+    }
+
+    fn would_edit<N: Visitable>(&mut self, node_path: &NodePath<'ast>, target_node: &N) -> bool {
+        self.rule.would_edit(node_path, target_node)
+    }
+
+    fn check_postcondition(&self) -> Result<(), EqlMapperError> {
+        self.rule.check_postcondition()
+    }
+
+    fn applied_rules(&self, rules: &mut Vec<&'static str>) {
+        if self.applied {
+            rules.push(Self::rule_name());
+        }
+    }
 }
 
 impl<'ast, T: TransformationRule<'ast>> Transform<'ast> for DryRunnable<'ast, T> {
@@ -206,4 +270,8 @@ impl<'ast> TransformationRule<'ast> for Tuple {
 
         Ok(())
     }
+
+    fn applied_rules(&self, rules: &mut Vec<&'static str>) {
+        for_tuples!( #(Tuple.applied_rules(rules); )* );
+    }
 }
@@ -0,0 +1,119 @@
+use std::collections::HashMap;
+use std::mem;
+use std::sync::Arc;
+
+use sqltk::parser::ast::helpers::attached_token::AttachedToken;
+use sqltk::parser::ast::{CastKind, DataType, Expr, FunctionArguments};
+use sqltk::parser::tokenizer::{Span, Token, TokenWithSpan};
+use sqltk::{NodeKey, NodePath, Visitable};
+
+use crate::function_arg::{function_arg_value, function_arg_value_mut};
+use crate::unifier::{Type, Value};
+use crate::{is_cipherstash_raw, EqlMapperError};
+
+use super::TransformationRule;
+
+/// Rewrites `cipherstash.raw(col)` to `col::JSONB`, so the stored EQL payload of
+/// an encrypted column is returned verbatim rather than decrypted.
+///
+/// `cipherstash.raw` is declared as `<T>(T) -> Native`, so the expression is a
+/// native value: a projected `cipherstash.raw(col)` is not decrypted, while the
+/// other columns of the statement still are. The argument of a call on a native
+/// value is returned unchanged.
+///
+/// The function does not exist in the database, so every call is rewritten.
+#[derive(Debug)]
+pub struct RewriteCipherstashRaw<'ast> {
+    node_types: Arc<HashMap<NodeKey<'ast>, Type>>,
+}
+
+impl<'ast> RewriteCipherstashRaw<'ast> {
+    pub fn new(node_types: Arc<HashMap<NodeKey<'ast>, Type>>) -> Self {
+        Self { node_types }
+    }
+
+    /// The single argument of a `cipherstash.raw` call.
+    fn raw_arg(expr: &Expr) -> Option<&Expr> {
+        let Expr::Function(function) = expr else {
+            return None;
+        };
+
+        if !is_cipherstash_raw(&function.name) {
+            return None;
+        }
+
+        match &function.args {
+            FunctionArguments::List(list) if list.args.len() == 1 => {
+                function_arg_value(&list.args[0])
+            }
+            _ => None,
+        }
+    }
+
+    fn is_eql_typed(&self, expr: &'ast Expr) -> bool {
+        matches!(
+            self.node_types.get(&NodeKey::new(expr)),
+            Some(Type::Value(Value::Eql(_)))
+        )
+    }
+}
+
+impl<'ast> TransformationRule<'ast> for RewriteCipherstashRaw<'ast> {
+    fn apply<N: Visitable>(
+        &mut self,
+        node_path: &NodePath<'ast>,
+        target_node: &mut N,
+    ) -> Result<bool, EqlMapperError> {
+        if self.would_edit(node_path, target_node) {
+            // `node_types` is keyed by the original argument
+            let Some((original,)) = node_path.last_1_as::<Expr>() else {
+                return Ok(false);
+            };
+            let encrypted = Self::raw_arg(original).is_some_and(|arg| self.is_eql_typed(arg));
+
+            let expr = target_node.downcast_mut::<Expr>().unwrap();
+            let Expr::Function(function) = expr else {
+                return Ok(false);
+            };
+            let FunctionArguments::List(list) = &mut function.args else {
+                return Ok(false);
+            };
+            let Some(arg) = list.args.first_mut().and_then(function_arg_value_mut) else {
+                return Ok(false);
+            };
+
+            // The argument is nested unless it is a column, so that it binds as
+            // tightly as the call it replaces. `Expr::Wildcard` is a cheap
+            // placeholder while the argument is moved out.
+            let placeholder =
+                Expr::Wildcard(AttachedToken(TokenWithSpan::new(Token::EOF, Span::empty())));
+            let arg = match mem::replace(arg, placeholder) {
+                arg @ (Expr::Identifier(_) | Expr::CompoundIdentifier(_) | Expr::Nested(_)) => arg,
+                arg => Expr::Nested(Box::new(arg)),
+            };
+
+            *expr = if encrypted {
+                Expr::Cast {
+                    kind: CastKind::DoubleColon,
+                    expr: Box::new(arg),
+                    data_type: DataType::JSONB,
+                    format: None,
+                }
+            } else {
+                arg
+            };
+
+            return Ok(true);
+        }
+
+        Ok(false)
+    }
+
+    fn would_edit<N: Visitable>(&mut self, node_path: &NodePath<'ast>, _target_node: &N) -> bool {
+        if let Some((expr,)) = node_path.last_1_as::<Expr>() {
+            return Self::raw_arg(expr).is_some();
+        }
+
+        false
+    }
+}
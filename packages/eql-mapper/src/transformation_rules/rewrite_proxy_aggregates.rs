@@ -0,0 +1,80 @@
+use sqltk::parser::ast::{
+    Expr, Function, FunctionArg, FunctionArgExpr, FunctionArgumentList, FunctionArguments, Ident,
+    ObjectName, ObjectNamePart,
+};
+use sqltk::{NodePath, Visitable};
+
+use crate::{EqlMapperError, ProxyAggregates};
+
+use super::TransformationRule;
+
+/// Rewrites an aggregate the proxy computes ([`crate::ProxyAggregate`]) to ship
+/// the group's encrypted values instead:
+///
+/// ```sql
+/// SELECT avg(salary) FROM employees
+/// -- becomes
+/// SELECT jsonb_agg(salary) AS avg FROM employees
+///
+/// SELECT percentile_disc(0.9) WITHIN GROUP (ORDER BY salary) FROM employees
+/// -- becomes
+/// SELECT jsonb_agg(salary) AS percentile_disc FROM employees
+/// ```
+///
+/// `jsonb_agg` rather than `array_agg` because an encrypted column is a domain
+/// over `jsonb`, so the result is a JSON array of the same payloads a bare
+/// column would return, in the same wire format. A `FILTER` clause is kept: it
+/// selects rows, which the database can still do. The original projection name
+/// is restored by [`super::PreserveEffectiveAliases`].
+#[derive(Debug)]
+pub struct RewriteProxyAggregates<'ast> {
+    aggregates: ProxyAggregates<'ast>,
+}
+
+impl<'ast> RewriteProxyAggregates<'ast> {
+    pub fn new(aggregates: ProxyAggregates<'ast>) -> Self {
+        Self { aggregates }
+    }
+}
+
+impl<'ast> TransformationRule<'ast> for RewriteProxyAggregates<'ast> {
+    fn apply<N: Visitable>(
+        &mut self,
+        node_path: &NodePath<'ast>,
+        target_node: &mut N,
+    ) -> Result<bool, EqlMapperError> {
+        if !self.would_edit(node_path, target_node) {
+            return Ok(false);
+        }
+
+        let (_expr, original) = node_path.last_2_as::<Expr, Function>().unwrap();
+        let function = target_node.downcast_mut::<Function>().unwrap();
+
+        // The input of an ordered-set aggregate is its sort key; the argument
+        // list holds the fraction, which the proxy already has. The key is
+        // taken from the original node: by now `RewriteEqlOrderBy` has
+        // replaced it with its ordering term, and it is the payload the proxy
+        // needs.
+        if let Some(key) = original.within_group.first() {
+            let key = key.expr.clone();
+            function.within_group.clear();
+            function.args = FunctionArguments::List(FunctionArgumentList {
+                args: vec![FunctionArg::Unnamed(FunctionArgExpr::Expr(Box::new(key)))],
+                duplicate_treatment: None,
+                clauses: vec![],
+            });
+        }
+
+        function.name = ObjectName(vec![ObjectNamePart::Identifier(Ident::new("jsonb_agg"))]);
+
+        Ok(true)
+    }
+
+    fn would_edit<N: Visitable>(&mut self, node_path: &NodePath<'ast>, _target_node: &N) -> bool {
+        if let Some((_expr, function)) = node_path.last_2_as::<Expr, Function>() {
+            return self.aggregates.contains(function);
+        }
+
+        false
+    }
+}
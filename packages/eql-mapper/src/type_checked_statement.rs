@@ -11,11 +11,12 @@ use crate::QueryOperands;
 use crate::{
     CastFullPayloadOperands, CollapseJsonAccessorChain, DryRunnable, EqlMapperError,
     FailOnPlaceholderChange, JsonAccessorPaths, JsonValueSelectors, OutputParam, OutputParamSource,
-    Param, ParamPlan, PreserveEffectiveAliases, RenumberParams, RewriteContainmentOps,
-    RewriteEqlAggregateDistinct, RewriteEqlAnyAllOps, RewriteEqlComparisonOps, RewriteEqlDistinct,
-    RewriteEqlDistinctOrderBy, RewriteEqlGroupBy, RewriteEqlMatchOps, RewriteEqlOrderBy,
-    RewriteEqlOrdinalOrderBy, RewriteEqlPartitionBy, RewriteJsonValueSelectorEq,
-    RewriteStandardSqlFnsOnEqlTypes, SubstituteEncryptedLiterals, TransformationRule,
+    Param, ParamPlan, PreserveEffectiveAliases, ProxyAggregates, RenumberParams,
+    RewriteCipherstashRaw, RewriteContainmentOps, RewriteEqlAggregateDistinct, RewriteEqlAnyAllOps,
+    RewriteEqlComparisonOps, RewriteEqlDistinct, RewriteEqlDistinctOrderBy, RewriteEqlGroupBy,
+    RewriteEqlMatchOps, RewriteEqlOrderBy, RewriteEqlOrdinalOrderBy, RewriteEqlPartitionBy,
+    RewriteJsonValueSelectorEq, RewriteProxyAggregates, RewriteStandardSqlFnsOnEqlTypes,
+    SubstituteEncryptedLiterals, Tracked, TransformationRule,
 };
 
 use crate::unifier::{Projection, Type, Value};
@@ -30,6 +31,9 @@ use crate::unifier::{Projection, Type, Value};
 pub struct TransformedStatement {
     pub statement: Statement,
     pub params: ParamPlan,
+
+    /// The names of the transformation rules that modified the statement, in the order they run.
+    pub applied_rules: Vec<&'static str>,
 }
 
 impl std::ops::Deref for TransformedStatement {
@@ -88,6 +92,12 @@ pub struct TypeCheckedStatement<'ast> {
     /// [`QueryOperands`].
     pub query_operands: QueryOperands<'ast>,
 
+    /// The projection columns whose value the proxy computes from decrypted
+    /// values, because PostgreSQL cannot — see [`ProxyAggregates`]. Empty unless
+    /// the statement was checked with
+    /// [`TypeCheckOptions::proxy_aggregates`](crate::TypeCheckOptions::proxy_aggregates).
+    pub proxy_aggregates: ProxyAggregates<'ast>,
+
     /// A [`HashMap`] of AST node (using [`NodeKey`] as the key) to [`Type`].  The map contains a `Type` for every node
     /// in the AST with the node type is one of: [`Statement`], [`Query`], [`Insert`], [`Delete`], [`Expr`],
     /// [`SetExpr`], [`Select`], [`SelectItem`], [`Vec<SelectItem>`], [`Function`], [`Values`], [`Value`].
@@ -119,6 +129,7 @@ impl<'ast> TypeCheckedStatement<'ast> {
         json_value_selectors: JsonValueSelectors<'ast>,
         json_accessor_paths: JsonAccessorPaths<'ast>,
         query_operands: QueryOperands<'ast>,
+        proxy_aggregates: ProxyAggregates<'ast>,
         node_types: Arc<HashMap<NodeKey<'ast>, Type>>,
     ) -> Self {
         Self {
@@ -129,6 +140,7 @@ impl<'ast> TypeCheckedStatement<'ast> {
             json_value_selectors,
             json_accessor_paths,
             query_operands,
+            proxy_aggregates,
             node_types,
         }
     }
@@ -170,6 +182,9 @@ impl<'ast> TypeCheckedStatement<'ast> {
         transformer.set_real_run_mode();
         let rewritten = self.statement.apply_transform(&mut transformer)?;
 
+        let mut applied_rules = Vec::new();
+        transformer.applied_rules(&mut applied_rules);
+
         // Renumber in a second pass, so the rules above are free to drop or
         // duplicate placeholders without having to maintain `$n` themselves —
         // and so `FailOnPlaceholderChange` still governs the rules' own edits.
@@ -178,7 +193,11 @@ impl<'ast> TypeCheckedStatement<'ast> {
         let (sources, query_operands) = renumber.into_parts();
         let params = self.param_plan(sources, query_operands)?;
 
-        Ok(TransformedStatement { statement, params })
+        Ok(TransformedStatement {
+            statement,
+            params,
+            applied_rules,
+        })
     }
 
     /// Builds the [`ParamPlan`] from the input param each output placeholder was
@@ -315,30 +334,42 @@ impl<'ast> TypeCheckedStatement<'ast> {
     ) -> DryRunnable<'_, impl TransformationRule<'_>> {
         // Substitution runs first so every encrypted literal is in place before
         // any rule wraps it. Each rewrite rule then applies the cast its own
-        // context requires, and `CastFullPayloadOperands` covers the one
-        // context that has no rewrite of its own — INSERT and UPDATE values.
+        // context requires, and `CastFullPayloadOperands` covers the values
+        // that have no rewrite of their own — INSERT and UPDATE values, and the
+        // projection of CREATE TABLE AS and SELECT INTO.
         DryRunnable::new((
-            SubstituteEncryptedLiterals::new(encrypted_literals),
-            RewriteStandardSqlFnsOnEqlTypes::new(Arc::clone(&self.node_types)),
+            Tracked::new(SubstituteEncryptedLiterals::new(encrypted_literals)),
+            Tracked::new(RewriteCipherstashRaw::new(Arc::clone(&self.node_types))),
+            Tracked::new(RewriteStandardSqlFnsOnEqlTypes::new(Arc::clone(
+                &self.node_types,
+            ))),
             // Before `RewriteContainmentOps`: a collapsed chain is emitted as the
             // finished `eql_v3."->"` call, which makes that rule decline rather
             // than functionalise a node this one has already replaced.
-            CollapseJsonAccessorChain::new(Arc::clone(&self.node_types)),
-            RewriteContainmentOps::new(Arc::clone(&self.node_types)),
-            RewriteJsonValueSelectorEq::new(Arc::clone(&self.node_types)),
-            RewriteEqlComparisonOps::new(Arc::clone(&self.node_types)),
-            RewriteEqlAnyAllOps::new(Arc::clone(&self.node_types)),
-            RewriteEqlMatchOps::new(Arc::clone(&self.node_types)),
-            RewriteEqlOrderBy::new(Arc::clone(&self.node_types)),
-            RewriteEqlOrdinalOrderBy::new(Arc::clone(&self.node_types)),
-            RewriteEqlPartitionBy::new(Arc::clone(&self.node_types)),
-            RewriteEqlAggregateDistinct::new(Arc::clone(&self.node_types)),
-            RewriteEqlDistinct::new(Arc::clone(&self.node_types)),
-            RewriteEqlDistinctOrderBy::new(Arc::clone(&self.node_types)),
-            RewriteEqlGroupBy::new(Arc::clone(&self.node_types)),
-            CastFullPayloadOperands::new(Arc::clone(&self.node_types)),
-            PreserveEffectiveAliases,
-            FailOnPlaceholderChange::new(),
+            Tracked::new(CollapseJsonAccessorChain::new(Arc::clone(&self.node_types))),
+            Tracked::new(RewriteContainmentOps::new(Arc::clone(&self.node_types))),
+            Tracked::new(RewriteJsonValueSelectorEq::new(Arc::clone(
+                &self.node_types,
+            ))),
+            Tracked::new(RewriteEqlComparisonOps::new(Arc::clone(&self.node_types))),
+            Tracked::new(RewriteEqlAnyAllOps::new(Arc::clone(&self.node_types))),
+            Tracked::new(RewriteEqlMatchOps::new(Arc::clone(&self.node_types))),
+            Tracked::new(RewriteEqlOrderBy::new(Arc::clone(&self.node_types))),
+            Tracked::new(RewriteEqlOrdinalOrderBy::new(Arc::clone(&self.node_types))),
+            Tracked::new(RewriteEqlPartitionBy::new(Arc::clone(&self.node_types))),
+            Tracked::new(RewriteEqlAggregateDistinct::new(Arc::clone(
+                &self.node_types,
+            ))),
+            Tracked::new(RewriteEqlDistinct::new(Arc::clone(&self.node_types))),
+            Tracked::new(RewriteEqlDistinctOrderBy::new(Arc::clone(&self.node_types))),
+            Tracked::new(RewriteEqlGroupBy::new(Arc::clone(&self.node_types))),
+            Tracked::new(RewriteProxyAggregates::new(self.proxy_aggregates.clone())),
+            Tracked::new(CastFullPayloadOperands::new(
+                self.statement,
+                Arc::clone(&self.node_types),
+            )),
+            Tracked::new(PreserveEffectiveAliases),
+            Tracked::new(FailOnPlaceholderChange::new()),
         ))
     }
 }